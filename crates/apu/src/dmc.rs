@@ -0,0 +1,241 @@
+//! The delta modulation channel (DMC) of the APU.
+//!
+//! The DMC plays 1-bit delta-encoded samples from the CPU's address space.
+//! The samples are fetched one byte at a time by the DMA unit of the 2A03,
+//! which steals cycles from the CPU, so the channel only requests the fetch
+//! and the system layer performs it, see [`Pins::DMA`](crate::Pins::DMA).
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_DMC>
+
+use std::io::{self, Read};
+
+use crate::snapshot::{bytes, decode_option, encode_option, invalid, read_u16};
+use crate::units::Timer;
+use crate::Region;
+
+/// The periods of the timer on NTSC, in APU cycles, i.e. the bit rates.
+const NTSC_RATES: [u16; 16] = [
+    214, 190, 170, 160, 143, 127, 113, 107, 95, 80, 71, 64, 53, 42, 36, 27,
+];
+
+/// The periods of the timer on PAL, in APU cycles.
+const PAL_RATES: [u16; 16] = [
+    199, 177, 158, 149, 138, 118, 105, 99, 88, 74, 66, 59, 49, 39, 33, 25,
+];
+
+/// The delta modulation channel.
+#[derive(Debug, Clone, Copy)]
+pub struct Dmc {
+    /// A flag denoting if the channel interrupts at the end of a sample.
+    irq_enabled: bool,
+    /// A flag denoting if the sample restarts at its end.
+    looping: bool,
+    /// The interrupt flag.
+    pub irq: bool,
+    /// The timer.
+    timer: Timer,
+    /// The 7-bit output level.
+    level: u8,
+
+    /// The start address of the sample.
+    start: u16,
+    /// The length of the sample, in bytes.
+    length: u16,
+    /// The address of the next byte of the sample.
+    addr: u16,
+    /// The number of bytes of the sample left to fetch.
+    remaining: u16,
+    /// The sample buffer, i.e. the next byte to play, if already fetched.
+    buffer: Option<u8>,
+
+    /// The shift register of the output unit, or `None` while it is silent.
+    shift: Option<u8>,
+    /// The number of bits left in the current output cycle.
+    bits: u8,
+    /// The periods of the timer of the region.
+    rates: &'static [u16; 16],
+}
+
+impl Dmc {
+    /// Create the channel of the given region in its power-up state.
+    pub fn new(region: Region) -> Self {
+        let rates = match region {
+            Region::Ntsc => &NTSC_RATES,
+            Region::Pal => &PAL_RATES,
+        };
+
+        Self {
+            irq_enabled: false,
+            looping: false,
+            irq: false,
+            timer: Timer::new(rates[0] - 1),
+            level: 0,
+            start: 0xc000,
+            length: 1,
+            addr: 0xc000,
+            remaining: 0,
+            buffer: None,
+            shift: None,
+            bits: 8,
+            rates,
+        }
+    }
+
+    /// Write one of the four registers of the channel.
+    pub fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.irq_enabled = data & 0x80 != 0;
+                self.looping = data & 0x40 != 0;
+                self.timer.period = self.rates[usize::from(data & 0x0f)] - 1;
+
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            }
+            1 => self.level = data & 0x7f,
+            2 => self.start = 0xc000 | (u16::from(data) << 6),
+            _ => self.length = (u16::from(data) << 4) | 1,
+        }
+    }
+
+    /// Enable or disable the channel through `SND_CHN` (`$4015`).
+    ///
+    /// Enabling the channel restarts the sample, unless it is still playing.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+
+        if !enabled {
+            self.remaining = 0;
+        } else if self.remaining == 0 {
+            self.restart();
+        }
+    }
+
+    /// Clock the timer, on every APU cycle.
+    pub fn clock(&mut self) {
+        if !self.timer.clock() {
+            return;
+        }
+
+        if let Some(shift) = &mut self.shift {
+            if *shift & 1 == 0 {
+                self.level = self.level.saturating_sub(2);
+            } else if self.level <= 125 {
+                self.level += 2;
+            }
+
+            *shift >>= 1;
+        }
+
+        self.bits -= 1;
+        if self.bits == 0 {
+            // Start a new output cycle with the sample buffer, which is then
+            // refilled.
+            self.bits = 8;
+            self.shift = self.buffer.take();
+        }
+    }
+
+    /// Check if the sample buffer is empty while bytes of the sample are left,
+    /// i.e. the DMA unit should fetch the next byte.
+    pub const fn is_requesting(&self) -> bool {
+        self.buffer.is_none() && self.remaining > 0
+    }
+
+    /// Return the address of the next byte of the sample.
+    pub const fn address(&self) -> u16 {
+        self.addr
+    }
+
+    /// Fill the sample buffer with the fetched byte of the sample.
+    ///
+    /// The address wraps around from `$FFFF` to `$8000`. After the last byte
+    /// the sample either restarts or sets the interrupt flag.
+    pub fn fill(&mut self, data: u8) {
+        self.buffer = Some(data);
+        self.addr = self.addr.wrapping_add(1) | 0x8000;
+        self.remaining -= 1;
+
+        if self.remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// Check if bytes of the sample are left to fetch.
+    pub const fn is_active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Save the state of the channel.
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend([
+            u8::from(self.irq_enabled),
+            u8::from(self.looping),
+            u8::from(self.irq),
+            self.level,
+        ]);
+        self.timer.save_state(state);
+
+        for value in [self.start, self.length, self.addr, self.remaining] {
+            state.extend(value.to_le_bytes());
+        }
+
+        state.extend(encode_option(self.buffer));
+        state.extend(encode_option(self.shift));
+        state.push(self.bits);
+    }
+
+    /// Load the state of the channel saved by [`Dmc::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [irq_enabled, looping, irq, level] = bytes(reader)?;
+        let mut timer = Timer::default();
+        timer.load_state(reader)?;
+
+        let start = read_u16(reader)?;
+        let length = read_u16(reader)?;
+        let addr = read_u16(reader)?;
+        let remaining = read_u16(reader)?;
+        let buffer = decode_option(bytes(reader)?);
+        let shift = decode_option(bytes(reader)?);
+        let [bits] = bytes(reader)?;
+
+        if start < 0xc000 || addr < 0x8000 || remaining > 0x0ff1 || !(1..=8).contains(&bits) {
+            return Err(invalid("invalid dmc in apu state"));
+        }
+
+        *self = Self {
+            irq_enabled: irq_enabled != 0,
+            looping: looping != 0,
+            irq: irq != 0,
+            timer,
+            level: level & 0x7f,
+            start,
+            length: length & 0x0ff1,
+            addr,
+            remaining,
+            buffer,
+            shift,
+            bits,
+            rates: self.rates,
+        };
+        Ok(())
+    }
+
+    /// Return the current output of the channel, `0`-`127`.
+    pub const fn output(&self) -> u8 {
+        self.level
+    }
+
+    /// Restart the sample from its start address.
+    fn restart(&mut self) {
+        self.addr = self.start;
+        self.remaining = self.length;
+    }
+}
@@ -0,0 +1,185 @@
+//! The frame counter of the APU, which clocks the envelopes, the length
+//! counters and the sweep units, and generates the frame interrupt.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Frame_Counter>
+
+use std::io::{self, Read};
+
+use crate::snapshot::{bytes, invalid, read_u16};
+use crate::Region;
+
+/// The CPU cycles of the steps of the sequences on NTSC: the first three
+/// steps, the last step of the 4-step sequence, whose interrupt starts one
+/// cycle earlier, and the last step of the 5-step sequence.
+const NTSC_STEPS: [u16; 5] = [7457, 14913, 22371, 29829, 37281];
+
+/// The CPU cycles of the steps of the sequences on PAL.
+const PAL_STEPS: [u16; 5] = [8313, 16627, 24939, 33253, 41565];
+
+/// The clocks generated by the frame counter on a cycle.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clocks {
+    /// A quarter frame, which clocks the envelopes and the linear counter.
+    pub quarter: bool,
+    /// A half frame, which clocks the length counters and the sweep units.
+    pub half: bool,
+}
+
+impl Clocks {
+    /// Both clocks at once, as on the second and the last step.
+    const BOTH: Self = Self {
+        quarter: true,
+        half: true,
+    };
+}
+
+/// The frame counter.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameCounter {
+    /// A flag denoting if the 5-step sequence is used instead of the 4-step
+    /// sequence, which interrupts.
+    five: bool,
+    /// A flag denoting if the frame interrupt is inhibited.
+    inhibit: bool,
+    /// The interrupt flag.
+    pub irq: bool,
+    /// The number of CPU cycles since the start of the sequence.
+    cycle: u16,
+    /// A write to `$4017` which hasn't taken effect yet, as the mode and the
+    /// number of CPU cycles left.
+    pending: Option<(bool, u8)>,
+    /// The CPU cycles of the steps of the region.
+    steps: &'static [u16; 5],
+}
+
+impl FrameCounter {
+    /// Create the frame counter of the given region, in the 4-step mode.
+    pub const fn new(region: Region) -> Self {
+        Self {
+            five: false,
+            inhibit: false,
+            irq: false,
+            cycle: 0,
+            pending: None,
+            steps: match region {
+                Region::Ntsc => &NTSC_STEPS,
+                Region::Pal => &PAL_STEPS,
+            },
+        }
+    }
+
+    /// Write `$4017`.
+    ///
+    /// The interrupt inhibit takes effect immediately, while the sequence is
+    /// only restarted 3 CPU cycles later if the write happened on an APU
+    /// cycle, or 4 CPU cycles later if it happened between two.
+    pub fn write(&mut self, data: u8, apu_cycle: bool) {
+        self.inhibit = data & 0x40 != 0;
+        if self.inhibit {
+            self.irq = false;
+        }
+
+        let delay = if apu_cycle { 3 } else { 4 };
+        self.pending = Some((data & 0x80 != 0, delay));
+    }
+
+    /// Advance the sequence by the given number of CPU cycles at once,
+    /// without generating clocks or the interrupt, wrapping around at the end
+    /// of the 4-step sequence.
+    pub fn skip(&mut self, cycles: u16) {
+        let len = u32::from(self.steps[3]) + 1;
+        let cycle = (u32::from(self.cycle) + u32::from(cycles)) % len;
+        self.cycle = u16::try_from(cycle).unwrap_or_default();
+    }
+
+    /// Advance by one CPU cycle, returning the generated clocks.
+    pub fn clock(&mut self) -> Clocks {
+        if let Some((five, delay)) = &mut self.pending {
+            *delay -= 1;
+
+            if *delay == 0 {
+                // Writing a 5-step sequence also clocks everything at once.
+                self.five = *five;
+                self.pending = None;
+                self.cycle = 0;
+                return if self.five {
+                    Clocks::BOTH
+                } else {
+                    Clocks::default()
+                };
+            }
+        }
+
+        self.cycle += 1;
+
+        let [first, second, third, four, five] = *self.steps;
+        if self.cycle == first || self.cycle == third {
+            return Clocks {
+                quarter: true,
+                half: false,
+            };
+        }
+
+        if self.cycle == second {
+            return Clocks::BOTH;
+        }
+
+        // The 4-step sequence interrupts on the 3 cycles around its last step.
+        let last = if self.five { five } else { four };
+        if !self.five && (last - 1..=last + 1).contains(&self.cycle) {
+            self.interrupt();
+        }
+
+        if self.cycle == last {
+            Clocks::BOTH
+        } else if self.cycle == last + 1 {
+            self.cycle = 0;
+            Clocks::default()
+        } else {
+            Clocks::default()
+        }
+    }
+
+    /// Save the state of the frame counter.
+    pub fn save_state(self, state: &mut Vec<u8>) {
+        let (five, delay) = self.pending.unwrap_or((false, 0));
+
+        state.extend([
+            u8::from(self.five),
+            u8::from(self.inhibit),
+            u8::from(self.irq),
+        ]);
+        state.extend(self.cycle.to_le_bytes());
+        state.extend([u8::from(self.pending.is_some()), u8::from(five), delay]);
+    }
+
+    /// Load the state of the frame counter saved by [`FrameCounter::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [five, inhibit, irq] = bytes(reader)?;
+        let cycle = read_u16(reader)?;
+        let [pending, pending_five, delay] = bytes(reader)?;
+
+        if cycle > self.steps[4] + 1 || (pending != 0 && !(1..=4).contains(&delay)) {
+            return Err(invalid("invalid frame counter in apu state"));
+        }
+
+        *self = Self {
+            five: five != 0,
+            inhibit: inhibit != 0,
+            irq: irq != 0,
+            cycle,
+            pending: (pending != 0).then_some((pending_five != 0, delay)),
+            steps: self.steps,
+        };
+        Ok(())
+    }
+
+    /// Set the interrupt flag, unless it is inhibited.
+    fn interrupt(&mut self) {
+        if !self.inhibit {
+            self.irq = true;
+        }
+    }
+}
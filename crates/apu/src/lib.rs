@@ -0,0 +1,331 @@
+//! A cycle-accurate implementation of the Audio Processing Unit of the NES's
+//! 2A03.
+//!
+//! # Modularity
+//!
+//! On the NES, the APU is part of the same chip as the CPU, but it is a
+//! separate unit that only shares the CPU's clock and address space. Like
+//! Chuck's CPU and PPU, this APU is completely decoupled from the rest of the
+//! emulator: it is clocked once per CPU cycle, its registers are accessed by
+//! the system layer between the cycles, and it only communicates through its
+//! [`Pins`], i.e. the frame and DMC interrupt, and the DMC's requests for the
+//! DMA unit to fetch the next byte of a sample.
+//!
+//! The APU produces the raw outputs of its five channels on every cycle, see
+//! [`Output`], which are mixed by [`Output::mix`] with the non-linear mixer of
+//! the 2A03, by [`Output::mix_scaled`] with other volumes of the channels,
+//! or by [`Output::mix_with`] with the expansion audio of the cartridge.
+//! Filtering and resampling are left to the audio output.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU>
+//! - <https://www.nesdev.org/wiki/APU_registers>
+//! - <https://www.nesdev.org/wiki/APU_Frame_Counter>
+
+mod dmc;
+mod frame;
+mod mixer;
+mod noise;
+mod pulse;
+mod snapshot;
+mod triangle;
+mod units;
+
+use dmc::Dmc;
+use frame::FrameCounter;
+use noise::Noise;
+use pulse::Pulse;
+use triangle::Triangle;
+
+bitflags::bitflags! {
+    /// The output pins of the APU.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Pins: u8 {
+        /// The interrupt request output, `IRQ`.
+        ///
+        /// This pin is set by the APU while the frame interrupt flag or the
+        /// DMC interrupt flag is set. On the NES, it is wired to the `IRQ` pin
+        /// of the CPU, together with the interrupt of the cartridge.
+        const IRQ = 1 << 0;
+        /// The DMC DMA request, `DMA`.
+        ///
+        /// This pin is set by the APU while the sample buffer of the DMC is
+        /// empty and bytes of the sample are left. The system layer must then
+        /// halt the CPU through its `RDY` pin, read the byte at
+        /// [`Apu::dmc_address`] and pass it to [`Apu::fill_sample_buffer`].
+        const DMA = 1 << 1;
+    }
+}
+
+/// The timing of the APU, which differs between the 2A03 of NTSC consoles and
+/// the 2A07 of PAL consoles.
+///
+/// The 2A07 has other periods for the noise channel and the DMC to make up for
+/// its slower clock, and its frame counter steps at other cycles to still
+/// clock the units about 4 times per frame.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/wiki/Cycle_reference_chart>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The 2A03 of NTSC consoles, the Famicom and its clones like the Dendy.
+    Ntsc,
+    /// The 2A07 of PAL consoles.
+    Pal,
+}
+
+/// The raw outputs of the APU's channels, see [`Apu::output`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Output {
+    /// The output of the first pulse channel, `0`-`15`.
+    pub pulse1: u8,
+    /// The output of the second pulse channel, `0`-`15`.
+    pub pulse2: u8,
+    /// The output of the triangle channel, `0`-`15`.
+    pub triangle: u8,
+    /// The output of the noise channel, `0`-`15`.
+    pub noise: u8,
+    /// The output of the delta modulation channel, `0`-`127`.
+    pub dmc: u8,
+}
+
+/// The Audio Processing Unit (APU) of the NES's 2A03.
+///
+/// The APU is driven one CPU cycle at a time through [`Apu::step`]. The CPU
+/// accesses the registers between the steps, through [`Apu::read`] and
+/// [`Apu::write`].
+///
+/// ```
+/// # use chuck_apu::Apu;
+/// let mut apu = Apu::new();
+///
+/// // Play a 50% duty cycle at full volume on the first pulse channel.
+/// apu.write(0x4015, 0x01);
+/// apu.write(0x4000, 0xbf);
+/// apu.write(0x4002, 0xfd);
+/// apu.write(0x4003, 0x08);
+///
+/// let mut samples = Vec::new();
+/// for _ in 0..2000 {
+///     apu.step();
+///     samples.push(apu.output().pulse1);
+/// }
+///
+/// assert!(samples.contains(&0));
+/// assert!(samples.contains(&15));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Apu {
+    /// The output pins.
+    pub pins: Pins,
+
+    /// The two pulse channels.
+    pulse: [Pulse; 2],
+    /// The triangle channel.
+    triangle: Triangle,
+    /// The noise channel.
+    noise: Noise,
+    /// The delta modulation channel.
+    dmc: Dmc,
+    /// The frame counter.
+    frame: FrameCounter,
+
+    /// The number of CPU cycles since power-up. Every other CPU cycle is an
+    /// APU cycle, which clocks the pulse, noise and DMC timers.
+    cycles: u64,
+}
+
+impl Apu {
+    /// Create a new NTSC APU (2A03) in its power-up state, with all channels
+    /// disabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_region(Region::Ntsc)
+    }
+
+    /// Create a new APU with the timing of the given region in its power-up
+    /// state, with all channels disabled.
+    #[must_use]
+    pub fn with_region(region: Region) -> Self {
+        Self {
+            pins: Pins::empty(),
+            pulse: [Pulse::new(true), Pulse::new(false)],
+            triangle: Triangle::default(),
+            noise: Noise::new(region),
+            dmc: Dmc::new(region),
+            frame: FrameCounter::new(region),
+            cycles: 0,
+        }
+    }
+
+    /// Execute a single CPU cycle of the APU.
+    ///
+    /// The frame counter is clocked first, then the writes to the length
+    /// counters since the previous step take effect, and finally the timers
+    /// of the channels are clocked.
+    pub fn step(&mut self) {
+        let clocks = self.frame.clock();
+
+        if clocks.quarter {
+            self.pulse[0].envelope.clock();
+            self.pulse[1].envelope.clock();
+            self.triangle.clock_linear();
+            self.noise.envelope.clock();
+        }
+
+        if clocks.half {
+            for pulse in &mut self.pulse {
+                pulse.length.clock();
+                pulse.clock_sweep();
+            }
+            self.triangle.length.clock();
+            self.noise.length.clock();
+        }
+
+        self.pulse[0].length.commit();
+        self.pulse[1].length.commit();
+        self.triangle.length.commit();
+        self.noise.length.commit();
+
+        self.triangle.clock();
+        if self.is_apu_cycle() {
+            self.pulse[0].clock();
+            self.pulse[1].clock();
+            self.noise.clock();
+            self.dmc.clock();
+        }
+
+        self.cycles += 1;
+        self.update_pins();
+    }
+
+    /// Advance the frame counter by the given number of CPU cycles without
+    /// clocking the channels or interrupting, e.g. to vary where its sequence
+    /// is at power-up. Its 4-step sequence wraps around after 29830 cycles on
+    /// NTSC and 33254 cycles on PAL.
+    pub fn skip_frame_counter(&mut self, cycles: u16) {
+        self.frame.skip(cycles);
+    }
+
+    /// Read one of the registers, as the CPU does at the given address.
+    ///
+    /// Only `SND_CHN` (`$4015`) is readable. Its bit 5 and all other
+    /// registers are not driven by the APU, they read as zero and must be
+    /// replaced with the open bus by the system layer.
+    pub fn read(&mut self, addr: u16) -> u8 {
+        let data = self.peek(addr);
+
+        if addr == 0x4015 {
+            self.frame.irq = false;
+            self.update_pins();
+        }
+
+        data
+    }
+
+    /// Read one of the registers without any side effects, e.g. for debuggers.
+    #[must_use]
+    pub fn peek(&self, addr: u16) -> u8 {
+        if addr != 0x4015 {
+            return 0;
+        }
+
+        [
+            self.pulse[0].length.is_active(),
+            self.pulse[1].length.is_active(),
+            self.triangle.length.is_active(),
+            self.noise.length.is_active(),
+            self.dmc.is_active(),
+            false,
+            self.frame.irq,
+            self.dmc.irq,
+        ]
+        .into_iter()
+        .rev()
+        .fold(0, |data, bit| (data << 1) | u8::from(bit))
+    }
+
+    /// Write one of the registers, as the CPU does at the given address.
+    ///
+    /// The addresses outside of `$4000`-`$4013`, `$4015` and `$4017` are
+    /// ignored, i.e. `OAMDMA` (`$4014`) and the controller port (`$4016`) are
+    /// left to the system layer.
+    pub fn write(&mut self, addr: u16, data: u8) {
+        let reg = addr & 3;
+
+        match addr {
+            0x4000..=0x4003 => self.pulse[0].write(reg, data),
+            0x4004..=0x4007 => self.pulse[1].write(reg, data),
+            0x4008..=0x400b => self.triangle.write(reg, data),
+            0x400c..=0x400f => self.noise.write(reg, data),
+            0x4010..=0x4013 => self.dmc.write(reg, data),
+            0x4015 => {
+                self.pulse[0].length.set_enabled(data & 0x01 != 0);
+                self.pulse[1].length.set_enabled(data & 0x02 != 0);
+                self.triangle.length.set_enabled(data & 0x04 != 0);
+                self.noise.length.set_enabled(data & 0x08 != 0);
+                self.dmc.set_enabled(data & 0x10 != 0);
+            }
+            0x4017 => self.frame.write(data, self.is_apu_cycle()),
+            _ => {}
+        }
+
+        self.update_pins();
+    }
+
+    /// Return the address of the next byte of the DMC's sample, which is
+    /// fetched while [`Pins::DMA`] is set.
+    #[must_use]
+    pub const fn dmc_address(&self) -> u16 {
+        self.dmc.address()
+    }
+
+    /// Fill the sample buffer of the DMC with the byte fetched by the DMA
+    /// unit from [`Apu::dmc_address`], which clears [`Pins::DMA`].
+    pub fn fill_sample_buffer(&mut self, data: u8) {
+        self.dmc.fill(data);
+        self.update_pins();
+    }
+
+    /// Return the current outputs of the channels.
+    #[must_use]
+    pub fn output(&self) -> Output {
+        Output {
+            pulse1: self.pulse[0].output(),
+            pulse2: self.pulse[1].output(),
+            triangle: self.triangle.output(),
+            noise: self.noise.output(),
+            dmc: self.dmc.output(),
+        }
+    }
+
+    /// Return the current output of the mixer, see [`Output::mix`].
+    #[must_use]
+    pub fn sample(&self) -> f32 {
+        self.output().mix()
+    }
+
+    /// Return the current output of the mixer together with the given output
+    /// of an expansion sound chip, see [`Output::mix_with`].
+    #[must_use]
+    pub fn sample_with(&self, expansion: f32) -> f32 {
+        self.output().mix_with(expansion)
+    }
+
+    /// Check if the current cycle is an APU cycle, i.e. an odd CPU cycle.
+    ///
+    /// These are the get cycles of the 2A03's DMA unit, on which it can read
+    /// from the CPU bus, while it can only write on the other (put) cycles.
+    #[must_use]
+    pub const fn is_apu_cycle(&self) -> bool {
+        self.cycles & 1 == 1
+    }
+
+    /// Update the pins from the interrupt flags and the DMC's sample buffer.
+    fn update_pins(&mut self) {
+        self.pins.set(Pins::IRQ, self.frame.irq || self.dmc.irq);
+        self.pins.set(Pins::DMA, self.dmc.is_requesting());
+    }
+}
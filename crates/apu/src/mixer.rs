@@ -0,0 +1,110 @@
+//! The non-linear mixer of the APU's channels.
+//!
+//! The channels are mixed by two resistor networks on the 2A03, one for both
+//! pulse channels and one for the other three, whose outputs are non-linear
+//! in the channel outputs. This uses the usual approximation of the networks.
+//!
+//! The audio of the Famicom passes through the cartridge, so the boards with
+//! their own sound chips (like the VRC6) add their outputs to the mixed
+//! output of the APU, see [`Output::mix_with`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Mixer>
+
+use crate::Output;
+
+impl Output {
+    /// Mix the channel outputs into a single sample, between `0.0` and about
+    /// `1.0`.
+    ///
+    /// The sample is not filtered, the high-pass and low-pass filters of the
+    /// NES's audio output are left to the audio output.
+    #[must_use]
+    pub fn mix(&self) -> f32 {
+        self.mix_scaled([1.0; 5])
+    }
+
+    /// Mix the channel outputs into a single sample like [`Output::mix`],
+    /// but with every channel output scaled by a gain first, in the order
+    /// pulse 1, pulse 2, triangle, noise and DMC, e.g. to change the volumes
+    /// of the channels.
+    ///
+    /// A gain of `0.0` mutes a channel, as if it were silent, and a gain of
+    /// `1.0` keeps it as loud as on the console.
+    ///
+    /// ```
+    /// # use chuck_apu::Output;
+    /// let output = Output { pulse1: 15, noise: 8, ..Output::default() };
+    /// let noise = Output { noise: 8, ..Output::default() };
+    ///
+    /// assert_eq!(output.mix_scaled([1.0; 5]), output.mix());
+    /// assert_eq!(output.mix_scaled([0.0, 1.0, 1.0, 1.0, 1.0]), noise.mix());
+    /// ```
+    #[must_use]
+    pub fn mix_scaled(&self, [pulse1, pulse2, triangle, noise, dmc]: [f32; 5]) -> f32 {
+        let pulse = f32::from(self.pulse1).mul_add(pulse1, f32::from(self.pulse2) * pulse2);
+        let pulse = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+
+        let tnd = f32::from(self.triangle) * triangle / 8227.0
+            + f32::from(self.noise) * noise / 12241.0
+            + f32::from(self.dmc) * dmc / 22638.0;
+        let tnd = if tnd == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / tnd + 100.0)
+        };
+
+        pulse + tnd
+    }
+
+    /// Mix every channel on its own, i.e. return what the mixer would output
+    /// if the other channels were silent, in the order pulse 1, pulse 2,
+    /// triangle, noise and DMC.
+    ///
+    /// This isolates the channels, e.g. to record them separately. Since the
+    /// mixer isn't linear, the sum of the isolated channels is louder than
+    /// the mix of all channels.
+    #[must_use]
+    pub fn channels(&self) -> [f32; 5] {
+        let silent = Self::default();
+
+        [
+            Self {
+                pulse1: self.pulse1,
+                ..silent
+            },
+            Self {
+                pulse2: self.pulse2,
+                ..silent
+            },
+            Self {
+                triangle: self.triangle,
+                ..silent
+            },
+            Self {
+                noise: self.noise,
+                ..silent
+            },
+            Self {
+                dmc: self.dmc,
+                ..silent
+            },
+        ]
+        .map(|output| output.mix())
+    }
+
+    /// Mix the channel outputs with the output of an expansion sound chip of
+    /// the cartridge, into a single sample.
+    ///
+    /// The expansion audio is given in the units of [`Output::mix`], i.e. an
+    /// output of `1.0` is as loud as all channels of the APU at full volume.
+    #[must_use]
+    pub fn mix_with(&self, expansion: f32) -> f32 {
+        self.mix() + expansion
+    }
+}
@@ -0,0 +1,114 @@
+//! The noise channel of the APU.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Noise>
+
+use std::io::{self, Read};
+
+use crate::snapshot::{bytes, read_u16};
+use crate::units::{Envelope, LengthCounter, Timer};
+use crate::Region;
+
+/// The periods of the timer on NTSC, in APU cycles.
+const NTSC_PERIODS: [u16; 16] = [
+    2, 4, 8, 16, 32, 48, 64, 80, 101, 127, 190, 254, 381, 508, 1017, 2034,
+];
+
+/// The periods of the timer on PAL, in APU cycles.
+const PAL_PERIODS: [u16; 16] = [
+    2, 4, 7, 15, 30, 44, 59, 74, 94, 118, 177, 236, 354, 472, 945, 1889,
+];
+
+/// The noise channel, a pseudo-random bit generator.
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    /// The 15-bit linear feedback shift register.
+    shift: u16,
+    /// A flag denoting if the feedback is taken from bit 6 instead of bit 1,
+    /// which results in a short, more metallic sequence.
+    short: bool,
+    /// The timer.
+    timer: Timer,
+    /// The envelope generator.
+    pub envelope: Envelope,
+    /// The length counter.
+    pub length: LengthCounter,
+    /// The periods of the timer of the region.
+    periods: &'static [u16; 16],
+}
+
+impl Noise {
+    /// Create the noise channel of the given region, with the shift register
+    /// in its power-up state.
+    pub fn new(region: Region) -> Self {
+        let periods = match region {
+            Region::Ntsc => &NTSC_PERIODS,
+            Region::Pal => &PAL_PERIODS,
+        };
+
+        Self {
+            shift: 1,
+            short: false,
+            timer: Timer::new(periods[0] - 1),
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            periods,
+        }
+    }
+
+    /// Write one of the four registers of the channel.
+    pub fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.length.set_halt(data & 0x20 != 0);
+                self.envelope.write(data);
+            }
+            1 => {}
+            2 => {
+                self.short = data & 0x80 != 0;
+                self.timer.period = self.periods[usize::from(data & 0x0f)] - 1;
+            }
+            _ => {
+                self.length.load(data);
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    /// Clock the timer, on every APU cycle.
+    pub fn clock(&mut self) {
+        if self.timer.clock() {
+            let tap = if self.short { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+            self.shift = (self.shift >> 1) | (feedback << 14);
+        }
+    }
+
+    /// Save the state of the channel.
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.extend(self.shift.to_le_bytes());
+        state.push(u8::from(self.short));
+        self.timer.save_state(state);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+    }
+
+    /// Load the state of the channel saved by [`Noise::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        self.shift = read_u16(reader)? & 0x7fff;
+        self.short = bytes::<1>(reader)?[0] != 0;
+        self.timer.load_state(reader)?;
+        self.envelope.load_state(reader)?;
+        self.length.load_state(reader)
+    }
+
+    /// Return the current output of the channel, `0`-`15`.
+    pub const fn output(&self) -> u8 {
+        if self.shift & 1 == 0 && self.length.is_active() {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! The two pulse (square wave) channels of the APU.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Pulse>
+//! - <https://www.nesdev.org/wiki/APU_Sweep>
+
+use std::io::{self, Read};
+
+use crate::snapshot::bytes;
+use crate::units::{Envelope, LengthCounter, Timer};
+
+/// The waveforms of the four duty cycles, 12.5%, 25%, 50% and 25% negated,
+/// with bit `n` holding the output of step `n` of the sequencer.
+const DUTIES: [u8; 4] = [0b0000_0010, 0b0000_0110, 0b0001_1110, 0b1111_1001];
+
+/// The sweep unit of a pulse channel, which periodically adjusts its period.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sweep {
+    /// A flag denoting if the sweep unit adjusts the period.
+    enabled: bool,
+    /// A flag denoting if the period is decreased instead of increased.
+    negate: bool,
+    /// A flag denoting if the divider is reloaded on the next half frame.
+    reload: bool,
+    /// The period of the divider, minus one.
+    period: u8,
+    /// The shift count of the change of the period.
+    shift: u8,
+    /// The divider.
+    divider: u8,
+}
+
+/// A pulse channel.
+#[derive(Debug, Clone, Copy)]
+pub struct Pulse {
+    /// A flag denoting if this is the first pulse channel, whose sweep unit
+    /// negates with the ones' complement instead of the two's complement.
+    first: bool,
+    /// The duty cycle, `0`-`3`.
+    duty: u8,
+    /// The position within the waveform, counting down from 7.
+    step: u8,
+    /// The timer, with a period of `t + 1` APU cycles.
+    timer: Timer,
+    /// The envelope generator.
+    pub envelope: Envelope,
+    /// The length counter.
+    pub length: LengthCounter,
+    /// The sweep unit.
+    sweep: Sweep,
+}
+
+impl Pulse {
+    /// Create the first or the second pulse channel.
+    pub fn new(first: bool) -> Self {
+        Self {
+            first,
+            duty: 0,
+            step: 0,
+            timer: Timer::default(),
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep: Sweep::default(),
+        }
+    }
+
+    /// Write one of the four registers of the channel.
+    pub fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.duty = data >> 6;
+                self.length.set_halt(data & 0x20 != 0);
+                self.envelope.write(data);
+            }
+            1 => {
+                self.sweep.enabled = data & 0x80 != 0;
+                self.sweep.period = (data >> 4) & 7;
+                self.sweep.negate = data & 0x08 != 0;
+                self.sweep.shift = data & 7;
+                self.sweep.reload = true;
+            }
+            2 => self.timer.period = (self.timer.period & 0x0700) | u16::from(data),
+            _ => {
+                self.timer.period = (self.timer.period & 0x00ff) | (u16::from(data & 7) << 8);
+                self.length.load(data);
+                self.envelope.start = true;
+                self.step = 0;
+            }
+        }
+    }
+
+    /// Clock the timer, on every APU cycle.
+    pub fn clock(&mut self) {
+        if self.timer.clock() {
+            self.step = self.step.wrapping_sub(1) & 7;
+        }
+    }
+
+    /// Clock the sweep unit, on every half frame.
+    pub fn clock_sweep(&mut self) {
+        let sweep = self.sweep;
+        if sweep.divider == 0 && sweep.enabled && sweep.shift > 0 && !self.is_muted() {
+            self.timer.period = self.target();
+        }
+
+        let sweep = &mut self.sweep;
+        if sweep.divider == 0 || sweep.reload {
+            sweep.divider = sweep.period;
+            sweep.reload = false;
+        } else {
+            sweep.divider -= 1;
+        }
+    }
+
+    /// Return the current output of the channel, `0`-`15`.
+    pub fn output(&self) -> u8 {
+        let high = DUTIES[usize::from(self.duty)] & (1 << self.step) != 0;
+
+        if high && self.length.is_active() && !self.is_muted() {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+
+    /// Save the state of the channel.
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        let sweep = self.sweep;
+
+        state.extend([self.duty, self.step]);
+        self.timer.save_state(state);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+        state.extend([
+            u8::from(sweep.enabled),
+            u8::from(sweep.negate),
+            u8::from(sweep.reload),
+            sweep.period,
+            sweep.shift,
+            sweep.divider,
+        ]);
+    }
+
+    /// Load the state of the channel saved by [`Pulse::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [duty, step] = bytes(reader)?;
+        self.duty = duty & 3;
+        self.step = step & 7;
+        self.timer.load_state(reader)?;
+        self.timer.period &= 0x07ff;
+        self.envelope.load_state(reader)?;
+        self.length.load_state(reader)?;
+
+        let [enabled, negate, reload, period, shift, divider] = bytes(reader)?;
+        self.sweep = Sweep {
+            enabled: enabled != 0,
+            negate: negate != 0,
+            reload: reload != 0,
+            period: period & 7,
+            shift: shift & 7,
+            divider: divider & 7,
+        };
+        Ok(())
+    }
+
+    /// Return the target period of the sweep unit.
+    fn target(&self) -> u16 {
+        let period = self.timer.period;
+        let change = period >> self.sweep.shift;
+
+        if !self.sweep.negate {
+            period + change
+        } else if self.first {
+            period.saturating_sub(change + 1)
+        } else {
+            period.saturating_sub(change)
+        }
+    }
+
+    /// Check if the channel is muted by the sweep unit, which happens when
+    /// the period is too small, or the target period too large, even if the
+    /// sweep unit is disabled.
+    fn is_muted(&self) -> bool {
+        self.timer.period < 8 || self.target() > 0x07ff
+    }
+}
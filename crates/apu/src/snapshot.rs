@@ -0,0 +1,85 @@
+//! The serialization of the complete state of the APU.
+//!
+//! Every unit saves its fields in a fixed layout of little-endian values,
+//! which is versioned by the container of the whole console.
+
+use std::io::{self, Read, Write};
+
+use crate::{Apu, Pins};
+
+/// Create an error denoting a malformed state.
+pub fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read the given number of bytes.
+pub fn bytes<const N: usize>(reader: &mut dyn Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read a little-endian 16-bit value.
+pub fn read_u16(reader: &mut dyn Read) -> io::Result<u16> {
+    bytes(reader).map(u16::from_le_bytes)
+}
+
+/// Encode an optional byte as a flag and the byte.
+pub fn encode_option(value: Option<u8>) -> [u8; 2] {
+    [u8::from(value.is_some()), value.unwrap_or(0)]
+}
+
+/// Decode an optional byte encoded by [`encode_option`].
+pub const fn decode_option([some, value]: [u8; 2]) -> Option<u8> {
+    if some == 0 {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+impl Apu {
+    /// Save the complete state of the APU.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut state = Vec::with_capacity(128);
+
+        state.push(self.pins.bits());
+        state.extend(self.cycles.to_le_bytes());
+        self.pulse[0].save_state(&mut state);
+        self.pulse[1].save_state(&mut state);
+        self.triangle.save_state(&mut state);
+        self.noise.save_state(&mut state);
+        self.dmc.save_state(&mut state);
+        self.frame.save_state(&mut state);
+
+        writer.write_all(&state)
+    }
+
+    /// Load the complete state of the APU saved by [`Apu::save`].
+    ///
+    /// The state of the APU is left untouched if the state is malformed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed.
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut apu = self.clone();
+
+        apu.pins = Pins::from_bits_truncate(bytes::<1>(reader)?[0]);
+        apu.cycles = u64::from_le_bytes(bytes(reader)?);
+        apu.pulse[0].load_state(reader)?;
+        apu.pulse[1].load_state(reader)?;
+        apu.triangle.load_state(reader)?;
+        apu.noise.load_state(reader)?;
+        apu.dmc.load_state(reader)?;
+        apu.frame.load_state(reader)?;
+
+        *self = apu;
+        Ok(())
+    }
+}
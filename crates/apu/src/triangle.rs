@@ -0,0 +1,114 @@
+//! The triangle channel of the APU.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Triangle>
+
+use std::io::{self, Read};
+
+use crate::snapshot::bytes;
+use crate::units::{LengthCounter, Timer};
+
+/// The triangle channel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Triangle {
+    /// The position within the 32-step waveform.
+    step: u8,
+    /// The timer, with a period of `t + 1` CPU cycles.
+    timer: Timer,
+    /// The length counter.
+    pub length: LengthCounter,
+    /// A flag denoting if the linear counter is held at its reload value,
+    /// which is also the halt flag of the length counter.
+    control: bool,
+    /// A flag denoting if the linear counter is reloaded on the next quarter
+    /// frame.
+    reload: bool,
+    /// The value the linear counter is reloaded with.
+    linear_period: u8,
+    /// The linear counter, which silences the channel like the length
+    /// counter, but with a finer resolution.
+    linear: u8,
+}
+
+impl Triangle {
+    /// Write one of the four registers of the channel.
+    pub fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => {
+                self.control = data & 0x80 != 0;
+                self.length.set_halt(self.control);
+                self.linear_period = data & 0x7f;
+            }
+            1 => {}
+            2 => self.timer.period = (self.timer.period & 0x0700) | u16::from(data),
+            _ => {
+                self.timer.period = (self.timer.period & 0x00ff) | (u16::from(data & 7) << 8);
+                self.length.load(data);
+                self.reload = true;
+            }
+        }
+    }
+
+    /// Clock the timer, on every CPU cycle.
+    ///
+    /// The waveform only advances while both counters are not zero, so the
+    /// channel holds its last output instead of dropping to zero.
+    pub fn clock(&mut self) {
+        if self.timer.clock() && self.length.is_active() && self.linear > 0 {
+            self.step = (self.step + 1) & 31;
+        }
+    }
+
+    /// Clock the linear counter, on every quarter frame.
+    pub fn clock_linear(&mut self) {
+        if self.reload {
+            self.linear = self.linear_period;
+        } else if self.linear > 0 {
+            self.linear -= 1;
+        }
+
+        if !self.control {
+            self.reload = false;
+        }
+    }
+
+    /// Save the state of the channel.
+    pub fn save_state(&self, state: &mut Vec<u8>) {
+        state.push(self.step);
+        self.timer.save_state(state);
+        self.length.save_state(state);
+        state.extend([
+            u8::from(self.control),
+            u8::from(self.reload),
+            self.linear_period,
+            self.linear,
+        ]);
+    }
+
+    /// Load the state of the channel saved by [`Triangle::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [step] = bytes(reader)?;
+        self.step = step & 31;
+        self.timer.load_state(reader)?;
+        self.timer.period &= 0x07ff;
+        self.length.load_state(reader)?;
+
+        let [control, reload, linear_period, linear] = bytes(reader)?;
+        self.control = control != 0;
+        self.reload = reload != 0;
+        self.linear_period = linear_period & 0x7f;
+        self.linear = linear & 0x7f;
+        Ok(())
+    }
+
+    /// Return the current output of the channel, `0`-`15`.
+    pub const fn output(&self) -> u8 {
+        // The waveform descends from 15 to 0, then ascends back to 15.
+        if self.step < 16 {
+            15 - self.step
+        } else {
+            self.step - 16
+        }
+    }
+}
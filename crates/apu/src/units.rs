@@ -0,0 +1,241 @@
+//! The units shared by several channels of the APU.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Envelope>
+//! - <https://www.nesdev.org/wiki/APU_Length_Counter>
+
+use std::io::{self, Read};
+
+use crate::snapshot::{bytes, decode_option, encode_option, read_u16};
+
+/// The lengths loaded into the length counters, indexed by the upper 5 bits
+/// of the fourth register of a channel.
+const LENGTHS: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// A divider, which counts down from its period and is reloaded when it
+/// reaches zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timer {
+    /// The value the counter is reloaded with, i.e. the period minus one.
+    pub period: u16,
+    /// The current value of the counter.
+    counter: u16,
+}
+
+impl Timer {
+    /// Create a timer with the given reload value.
+    pub const fn new(period: u16) -> Self {
+        Self { period, counter: 0 }
+    }
+
+    /// Save the state of the timer.
+    pub fn save_state(self, state: &mut Vec<u8>) {
+        state.extend(self.period.to_le_bytes());
+        state.extend(self.counter.to_le_bytes());
+    }
+
+    /// Load the state of the timer saved by [`Timer::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        self.period = read_u16(reader)? & 0x0fff;
+        self.counter = read_u16(reader)? & 0x0fff;
+        Ok(())
+    }
+
+    /// Clock the timer, returning `true` when it was reloaded, which clocks
+    /// the unit driven by the timer.
+    pub fn clock(&mut self) -> bool {
+        if self.counter == 0 {
+            self.counter = self.period;
+            true
+        } else {
+            self.counter -= 1;
+            false
+        }
+    }
+}
+
+/// The envelope generator of the pulse and noise channels, which produces
+/// either a constant volume or a decreasing saw envelope.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    /// A flag denoting if the envelope restarts on the next quarter frame.
+    pub start: bool,
+    /// A flag denoting if the decay level loops from 0 back to 15.
+    looping: bool,
+    /// A flag denoting if the volume is constant instead of decaying.
+    constant: bool,
+    /// The constant volume, which is also the period of the divider.
+    volume: u8,
+    /// The divider of the decay.
+    divider: u8,
+    /// The decay level, which counts down from 15.
+    decay: u8,
+}
+
+impl Envelope {
+    /// Write the lower 6 bits of the first register of the channel.
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0x20 != 0;
+        self.constant = data & 0x10 != 0;
+        self.volume = data & 0x0f;
+    }
+
+    /// Clock the envelope, on every quarter frame.
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    /// Save the state of the envelope.
+    pub fn save_state(self, state: &mut Vec<u8>) {
+        state.extend([
+            u8::from(self.start),
+            u8::from(self.looping),
+            u8::from(self.constant),
+            self.volume,
+            self.divider,
+            self.decay,
+        ]);
+    }
+
+    /// Load the state of the envelope saved by [`Envelope::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [start, looping, constant, volume, divider, decay] = bytes(reader)?;
+
+        *self = Self {
+            start: start != 0,
+            looping: looping != 0,
+            constant: constant != 0,
+            volume: volume & 0x0f,
+            divider: divider & 0x0f,
+            decay: decay & 0x0f,
+        };
+        Ok(())
+    }
+
+    /// Return the current volume.
+    pub const fn volume(self) -> u8 {
+        if self.constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+/// The length counter of a channel, which silences the channel once it
+/// reaches zero.
+///
+/// A write to the halt flag or a reload of the counter on the same cycle as a
+/// half frame clock only takes effect after the clock, so both are delayed
+/// until [`LengthCounter::commit`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LengthCounter {
+    /// The current value of the counter.
+    counter: u8,
+    /// A flag denoting if the channel is enabled in `SND_CHN` (`$4015`).
+    enabled: bool,
+    /// A flag denoting if the counter is halted, i.e. isn't clocked.
+    halt: bool,
+    /// The halt flag written on this cycle, if any.
+    new_halt: Option<bool>,
+    /// The length loaded on this cycle, if any, together with the counter
+    /// before the clocks of this cycle.
+    reload: Option<(u8, u8)>,
+}
+
+impl LengthCounter {
+    /// Set the halt flag.
+    pub fn set_halt(&mut self, halt: bool) {
+        self.new_halt = Some(halt);
+    }
+
+    /// Load a length, indexed by the upper 5 bits of the given data, unless
+    /// the channel is disabled.
+    pub fn load(&mut self, data: u8) {
+        if self.enabled {
+            self.reload = Some((LENGTHS[usize::from(data >> 3)], self.counter));
+        }
+    }
+
+    /// Enable or disable the channel. Disabling it clears the counter.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.counter = 0;
+            self.reload = None;
+        }
+    }
+
+    /// Clock the counter, on every half frame.
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    /// Apply the writes of this cycle, after the clocks of the frame counter.
+    ///
+    /// A reload is ignored if the counter was clocked on the same cycle while
+    /// it was not zero.
+    pub fn commit(&mut self) {
+        if let Some(halt) = self.new_halt.take() {
+            self.halt = halt;
+        }
+
+        if let Some((length, previous)) = self.reload.take() {
+            if self.counter == previous {
+                self.counter = length;
+            }
+        }
+    }
+
+    /// Save the state of the length counter.
+    pub fn save_state(self, state: &mut Vec<u8>) {
+        let (length, previous) = self.reload.unwrap_or((0, 0));
+
+        state.extend([self.counter, u8::from(self.enabled), u8::from(self.halt)]);
+        state.extend(encode_option(self.new_halt.map(u8::from)));
+        state.extend([u8::from(self.reload.is_some()), length, previous]);
+    }
+
+    /// Load the state of the length counter saved by
+    /// [`LengthCounter::save_state`].
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let [counter, enabled, halt] = bytes(reader)?;
+        let new_halt = decode_option(bytes(reader)?);
+        let [reload, length, previous] = bytes(reader)?;
+
+        *self = Self {
+            counter,
+            enabled: enabled != 0,
+            halt: halt != 0,
+            new_halt: new_halt.map(|halt| halt != 0),
+            reload: (reload != 0).then_some((length, previous)),
+        };
+        Ok(())
+    }
+
+    /// Check if the counter is not zero, i.e. the channel is not silenced.
+    pub const fn is_active(self) -> bool {
+        self.counter > 0
+    }
+}
@@ -0,0 +1,151 @@
+//! The frame counter of the APU, observed through its registers and pins:
+//! the cycles of its steps in both modes, the interrupt of the 4-step mode,
+//! and the delay of the writes to `$4017`.
+
+use chuck_apu::{Apu, Pins};
+
+/// The CPU cycles of the quarter frames of the NTSC 4-step sequence.
+const QUARTERS: [usize; 4] = [7457, 14913, 22371, 29829];
+
+/// Execute the given number of CPU cycles.
+fn run(apu: &mut Apu, cycles: usize) {
+    for _ in 0..cycles {
+        apu.step();
+    }
+}
+
+/// Check if the length counter of the first pulse channel is non-zero.
+fn is_pulse_active(apu: &Apu) -> bool {
+    apu.peek(0x4015) & 0x01 != 0
+}
+
+/// Create an APU whose first pulse channel has a length of 2, so it's
+/// silenced by the second half frame.
+fn with_pulse() -> Apu {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    apu.write(0x4000, 0x10);
+    apu.write(0x4003, 3 << 3);
+    apu
+}
+
+#[test]
+fn quarter_frames() {
+    // The linear counter of the triangle channel is loaded with 1 by the
+    // first quarter frame and cleared by the second, so the triangle only
+    // plays between both.
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x04);
+    apu.write(0x4008, 0x01);
+    apu.write(0x400a, 0x02);
+    apu.write(0x400b, 0x08);
+
+    let mut outputs = Vec::new();
+    for _ in 0..QUARTERS[1] + 100 {
+        apu.step();
+        outputs.push(apu.output().triangle);
+    }
+
+    let is_playing = |cycles: std::ops::Range<usize>| {
+        outputs[cycles.start - 1..cycles.end - 1]
+            .windows(2)
+            .any(|pair| pair[0] != pair[1])
+    };
+    assert!(!is_playing(1..QUARTERS[0]));
+    assert!(is_playing(QUARTERS[0]..QUARTERS[0] + 20));
+    assert!(is_playing(QUARTERS[1] - 20..QUARTERS[1]));
+    assert!(!is_playing(QUARTERS[1] + 1..QUARTERS[1] + 100));
+}
+
+#[test]
+fn half_frames() {
+    // The length counter is clocked on the second and fourth steps of the
+    // 4-step sequence.
+    let mut apu = with_pulse();
+    run(&mut apu, QUARTERS[3] - 1);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+
+    // And on the second and fifth steps of the 5-step sequence, which also
+    // clocks it once it starts.
+    let mut apu = with_pulse();
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 4);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, 14913 - 1);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+}
+
+#[test]
+fn four_step_interrupt() {
+    // The interrupt is set on the cycle before the last step.
+    let mut apu = Apu::new();
+    run(&mut apu, QUARTERS[3] - 2);
+    assert!(!apu.pins.contains(Pins::IRQ));
+    run(&mut apu, 1);
+    assert!(apu.pins.contains(Pins::IRQ));
+    assert_eq!(apu.peek(0x4015) & 0x40, 0x40);
+
+    // Reading `$4015` clears it, but it's set again on the next two cycles.
+    assert_eq!(apu.read(0x4015) & 0x40, 0x40);
+    assert!(!apu.pins.contains(Pins::IRQ));
+    run(&mut apu, 1);
+    assert!(apu.pins.contains(Pins::IRQ));
+    apu.read(0x4015);
+    run(&mut apu, 1);
+    assert!(apu.pins.contains(Pins::IRQ));
+    apu.read(0x4015);
+    run(&mut apu, 1);
+    assert!(!apu.pins.contains(Pins::IRQ));
+
+    // It stays set until acknowledged, through the next sequence.
+    run(&mut apu, QUARTERS[3]);
+    run(&mut apu, QUARTERS[0]);
+    assert!(apu.pins.contains(Pins::IRQ));
+
+    // Setting the inhibit flag clears it at once, and keeps it clear.
+    apu.write(0x4017, 0x40);
+    assert!(!apu.pins.contains(Pins::IRQ));
+    run(&mut apu, 2 * QUARTERS[3]);
+    assert!(!apu.pins.contains(Pins::IRQ));
+
+    // The 5-step sequence never interrupts.
+    let mut apu = Apu::new();
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 3 * 37282);
+    assert!(!apu.pins.contains(Pins::IRQ));
+}
+
+#[test]
+fn write_delay() {
+    // The 5-step sequence starts, and clocks the length counter down from 1,
+    // 3 CPU cycles after a write on an APU cycle, the odd CPU cycles.
+    let mut apu = with_pulse();
+    run(&mut apu, QUARTERS[1]);
+    assert!(apu.is_apu_cycle());
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 2);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+
+    // And 4 CPU cycles after a write between two APU cycles.
+    let mut apu = with_pulse();
+    run(&mut apu, QUARTERS[1] + 1);
+    assert!(!apu.is_apu_cycle());
+    apu.write(0x4017, 0x80);
+    run(&mut apu, 3);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+
+    // Until then, the old sequence keeps running, and still interrupts.
+    let mut apu = Apu::new();
+    run(&mut apu, QUARTERS[3] - 3);
+    apu.write(0x4017, 0x00);
+    run(&mut apu, 2);
+    assert!(apu.pins.contains(Pins::IRQ));
+}
@@ -0,0 +1,102 @@
+//! The length counters of the APU, observed through `$4015`: their halt flag,
+//! their reloads, including those on the cycle of a half frame, and the
+//! enable flags of the channels.
+
+use chuck_apu::Apu;
+
+/// The CPU cycles of the half frames of the NTSC 4-step sequence.
+const HALVES: [usize; 2] = [14913, 29829];
+
+/// Execute the given number of CPU cycles.
+fn run(apu: &mut Apu, cycles: usize) {
+    for _ in 0..cycles {
+        apu.step();
+    }
+}
+
+/// Check if the length counter of the first pulse channel is non-zero.
+fn is_pulse_active(apu: &Apu) -> bool {
+    apu.peek(0x4015) & 0x01 != 0
+}
+
+/// Create an APU whose first pulse channel has a length of 2, and the given
+/// halt flag.
+fn with_pulse(halt: bool) -> Apu {
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    apu.write(0x4000, if halt { 0x30 } else { 0x10 });
+    apu.write(0x4003, 3 << 3);
+    apu
+}
+
+#[test]
+fn halt() {
+    // A halted counter isn't clocked.
+    let mut apu = with_pulse(true);
+    run(&mut apu, 3 * HALVES[1]);
+    assert!(is_pulse_active(&apu));
+
+    // Until the flag is cleared.
+    apu.write(0x4000, 0x10);
+    run(&mut apu, HALVES[1]);
+    assert!(!is_pulse_active(&apu));
+
+    // Clearing the flag on the cycle of a half frame only takes effect after
+    // its clock, which is still skipped.
+    let mut apu = with_pulse(true);
+    run(&mut apu, HALVES[0] - 1);
+    apu.write(0x4000, 0x10);
+    run(&mut apu, HALVES[1] - HALVES[0] + 1);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, HALVES[0] + 1);
+    assert!(!is_pulse_active(&apu));
+}
+
+#[test]
+fn reload() {
+    // Reloading the counter after the first half frame keeps the channel
+    // playing through the second one.
+    let mut apu = with_pulse(false);
+    run(&mut apu, HALVES[0]);
+    apu.write(0x4003, 3 << 3);
+    run(&mut apu, HALVES[1] - HALVES[0]);
+    assert!(is_pulse_active(&apu));
+    run(&mut apu, HALVES[0] + 1);
+    assert!(!is_pulse_active(&apu));
+
+    // A reload on the cycle of a half frame is ignored if the clock changed
+    // the counter.
+    let mut apu = with_pulse(false);
+    run(&mut apu, HALVES[1] - 1);
+    apu.write(0x4003, 3 << 3);
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+
+    // But not if the counter was already zero.
+    let mut apu = Apu::new();
+    apu.write(0x4015, 0x01);
+    run(&mut apu, HALVES[0] - 1);
+    apu.write(0x4003, 3 << 3);
+    run(&mut apu, 1);
+    assert!(is_pulse_active(&apu));
+}
+
+#[test]
+fn enable() {
+    // Disabling a channel clears its counter at once.
+    let mut apu = with_pulse(true);
+    run(&mut apu, 1);
+    assert!(is_pulse_active(&apu));
+    apu.write(0x4015, 0x00);
+    assert!(!is_pulse_active(&apu));
+
+    // And its counter isn't loaded while it's disabled.
+    apu.write(0x4003, 3 << 3);
+    run(&mut apu, 1);
+    assert!(!is_pulse_active(&apu));
+
+    apu.write(0x4015, 0x01);
+    apu.write(0x4003, 3 << 3);
+    run(&mut apu, 1);
+    assert!(is_pulse_active(&apu));
+}
@@ -0,0 +1,106 @@
+//! The first-order filters of the NES's audio output.
+//!
+//! Between the APU and the audio output, the NES has two high-pass filters,
+//! at 90 Hz and 440 Hz, which remove the DC offset of the APU's output, and a
+//! low-pass filter at 14 kHz.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Mixer>
+
+use std::f32::consts::TAU;
+
+/// The cutoff frequencies of the high-pass filters, in Hz.
+const HIGH_PASS: [f32; 2] = [90.0, 440.0];
+
+/// The cutoff frequency of the low-pass filter, in Hz.
+const LOW_PASS: f32 = 14_000.0;
+
+/// A first-order high-pass filter.
+#[derive(Debug, Clone, Copy)]
+struct HighPass {
+    /// The factor of the previous output.
+    factor: f32,
+    /// The previous input.
+    input: f32,
+    /// The previous output.
+    output: f32,
+}
+
+impl HighPass {
+    /// Create a filter with the given cutoff frequency.
+    fn new(cutoff: f32, rate: f32) -> Self {
+        let rc = (TAU * cutoff).recip();
+
+        Self {
+            factor: rc / (rc + rate.recip()),
+            input: 0.0,
+            output: 0.0,
+        }
+    }
+
+    /// Filter the next sample.
+    fn filter(&mut self, input: f32) -> f32 {
+        self.output = self.factor * (self.output + input - self.input);
+        self.input = input;
+        self.output
+    }
+}
+
+/// A first-order low-pass filter.
+#[derive(Debug, Clone, Copy)]
+struct LowPass {
+    /// The factor of the difference between the input and the previous
+    /// output.
+    factor: f32,
+    /// The previous output.
+    output: f32,
+}
+
+impl LowPass {
+    /// Create a filter with the given cutoff frequency.
+    fn new(cutoff: f32, rate: f32) -> Self {
+        let rc = (TAU * cutoff).recip();
+        let dt = rate.recip();
+
+        Self {
+            factor: dt / (rc + dt),
+            output: 0.0,
+        }
+    }
+
+    /// Filter the next sample.
+    fn filter(&mut self, input: f32) -> f32 {
+        self.output = self.factor.mul_add(input - self.output, self.output);
+        self.output
+    }
+}
+
+/// The chain of the filters of the NES's audio output.
+#[derive(Debug, Clone, Copy)]
+pub struct Filters {
+    /// The high-pass filters.
+    high_pass: [HighPass; 2],
+    /// The low-pass filter.
+    low_pass: LowPass,
+}
+
+impl Filters {
+    /// Create the filters for the given sample rate.
+    pub fn new(rate: f32) -> Self {
+        Self {
+            high_pass: HIGH_PASS.map(|cutoff| HighPass::new(cutoff, rate)),
+            low_pass: LowPass::new(LOW_PASS, rate),
+        }
+    }
+
+    /// Filter the next sample.
+    pub fn filter(&mut self, sample: f32) -> f32 {
+        let sample = self
+            .high_pass
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.filter(sample));
+
+        self.low_pass.filter(sample)
+    }
+}
@@ -0,0 +1,142 @@
+//! The band-limited steps of the resampler.
+//!
+//! The output of the APU only changes in steps, so instead of filtering every
+//! input sample, the resampler adds a band-limited step to the output for
+//! every change of the input. The steps are stored as their derivative, a
+//! windowed sinc, which the resampler sums up into the output. Since the
+//! sinc is sampled at the output rate, it's tabulated for a number of
+//! fractions of an output sample at which a step can start.
+//!
+//! The cheaper qualities replace the windowed sinc: a step is added to the
+//! output sample after it by the zero-order hold, or split between the output
+//! samples around it by the linear interpolation, which both alias.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Mixer>
+//! - <http://www.slack.net/~ant/bl-synth/>
+
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use crate::Quality;
+
+/// Half the width of a step, in output samples.
+const HALF: u8 = 8;
+
+/// The width of a step, in output samples.
+pub const WIDTH: usize = 2 * HALF as usize;
+
+/// The number of fractions of an output sample which are tabulated.
+const PHASES: u8 = 32;
+
+/// The cutoff frequency of the steps, relative to the output rate. Below half
+/// of the output rate, so the window has room to roll off before aliasing
+/// sets in.
+const CUTOFF: f32 = 0.45;
+
+/// The table of the band-limited steps.
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    /// The steps of the phases `0` to `PHASES` (inclusive), i.e. of a step
+    /// starting at the fraction `phase / PHASES` of an output sample.
+    steps: Box<[[f32; WIDTH]]>,
+    /// The smallest fractions of an output sample which are closest to the
+    /// phases `1` to `PHASES`.
+    thresholds: Box<[f64]>,
+    /// The samples of the steps which may be non-zero.
+    taps: Range<usize>,
+}
+
+impl Kernel {
+    /// Tabulate the steps of the given quality.
+    pub fn new(quality: Quality) -> Self {
+        let (step, taps): (fn(f32) -> [f32; WIDTH], _) = match quality {
+            Quality::Hold => (hold, WIDTH / 2..WIDTH / 2 + 1),
+            Quality::Linear => (linear, WIDTH / 2 - 1..WIDTH / 2 + 1),
+            Quality::Sinc => (windowed_sinc, 0..WIDTH),
+        };
+
+        let steps = (0..=PHASES)
+            .map(|phase| step(f32::from(phase) / f32::from(PHASES)))
+            .collect();
+
+        let thresholds = (1..=PHASES)
+            .map(|phase| (f64::from(phase) - 0.5) / f64::from(PHASES))
+            .collect();
+
+        Self {
+            steps,
+            thresholds,
+            taps,
+        }
+    }
+
+    /// Return the step starting at the given fraction of an output sample.
+    pub fn step(&self, fraction: f64) -> &[f32; WIDTH] {
+        &self.steps[self
+            .thresholds
+            .partition_point(|&threshold| threshold <= fraction)]
+    }
+
+    /// Return the samples of the steps which may be non-zero.
+    pub fn taps(&self) -> Range<usize> {
+        self.taps.clone()
+    }
+}
+
+/// Return the step of the zero-order hold starting at the given fraction of
+/// an output sample, which is entirely in the output sample after it.
+fn hold(_fraction: f32) -> [f32; WIDTH] {
+    let mut step = [0.0; WIDTH];
+    step[WIDTH / 2] = 1.0;
+    step
+}
+
+/// Return the step of the linear interpolation starting at the given
+/// fraction of an output sample, which is split between the output samples
+/// before and after it by their distance.
+fn linear(fraction: f32) -> [f32; WIDTH] {
+    let mut step = [0.0; WIDTH];
+    step[WIDTH / 2 - 1] = 1.0 - fraction;
+    step[WIDTH / 2] = fraction;
+    step
+}
+
+/// Return the band-limited step starting at the given fraction of an output
+/// sample.
+fn windowed_sinc(fraction: f32) -> [f32; WIDTH] {
+    let mut step = [0.0; WIDTH];
+
+    // The step is delayed by half its width, so it's centered
+    // within the samples it spans.
+    let mut sum = 0.0;
+    for (x, sample) in (0..=u8::MAX).zip(&mut step) {
+        let distance = f32::from(x) - fraction - f32::from(HALF) + 1.0;
+        let value = sinc(2.0 * CUTOFF * distance) * blackman(distance / f32::from(HALF));
+        *sample = value;
+        sum += value;
+    }
+
+    // The samples of every step must sum up to 1, else the
+    // output would drift away from the input.
+    step.map(|sample| sample / sum)
+}
+
+/// The normalized sinc function.
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The Blackman window, from `-1.0` to `1.0`.
+fn blackman(x: f32) -> f32 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.08f32.mul_add((2.0 * PI * x).cos(), 0.5f32.mul_add((PI * x).cos(), 0.42))
+    }
+}
@@ -0,0 +1,418 @@
+//! The audio output of the NES, which turns the APU's samples into audio at
+//! the sample rate of an audio device.
+//!
+//! The console produces a sample on every CPU cycle, so at about 1.79 MHz,
+//! which the [`Resampler`] band-limits and resamples to the rate of the audio
+//! device. On the way, it applies the filters of the NES's audio output, two
+//! high-pass filters and a low-pass filter.
+//!
+//! The console and the audio device are clocked independently, so the rate
+//! at which the console is run (usually synchronized to the display) never
+//! exactly matches the rate at which the device consumes samples. To keep the
+//! buffered samples from running out or piling up, the resampler slightly
+//! adapts its rate to the number of buffered samples (dynamic rate control),
+//! by at most half a percent, which isn't audible.
+//!
+//! The band-limiting by windowed sincs is the most accurate, but the cheaper
+//! [`Quality`]s of the resampling may be chosen for slow machines, at the
+//! cost of aliasing.
+//!
+//! The resampled audio can also be recorded into WAV files, see [`wav`].
+//!
+//! ```
+//! # use chuck_audio::Resampler;
+//! let mut resampler = Resampler::new(1_789_773.0, 48_000);
+//!
+//! // A frame of a square wave at about 440 Hz.
+//! let samples: Vec<f32> = (0..29_780)
+//!     .map(|i| if (i / 2034) % 2 == 0 { 0.0 } else { 0.5 })
+//!     .collect();
+//! resampler.push(&samples);
+//!
+//! // Around 800 samples at 48 kHz.
+//! let mut output = [0.0; 1024];
+//! let filled = resampler.fill(&mut output);
+//! assert!((790..810).contains(&filled));
+//! assert!(output[..filled].iter().any(|&sample| sample > 0.2));
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/APU_Mixer>
+//! - <https://docs.libretro.com/development/cores/dynamic-rate-control/>
+
+mod filter;
+mod kernel;
+pub mod wav;
+
+use std::collections::VecDeque;
+
+use filter::Filters;
+use kernel::{Kernel, WIDTH};
+
+/// The default largest relative deviation of the rate for the dynamic rate
+/// control.
+const MAX_DEVIATION: f64 = 0.005;
+
+/// The default latency, in seconds.
+const LATENCY: f32 = 0.05;
+
+/// The number of times the latency after which the buffered samples are
+/// dropped, if the audio device stopped pulling them.
+const OVERFLOW: f64 = 4.0;
+
+/// The quality of the resampling, from the cheapest to the most accurate.
+///
+/// The output rate is far below the input rate, so the cheaper qualities
+/// alias the tones above half of the output rate (e.g. the highest notes of
+/// the triangle channel and the noise channel) into audible tones below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// A zero-order hold, i.e. every output sample is the last input sample
+    /// before it.
+    Hold,
+    /// A linear interpolation, i.e. every output sample is the average of the
+    /// input samples around it weighted by their distance.
+    Linear,
+    /// A band-limiting by windowed sincs.
+    #[default]
+    Sinc,
+}
+
+/// A resampler from the sample rate of the console to the rate of an audio
+/// device.
+///
+/// The resampler is fed with the samples of every emulated frame by
+/// [`Resampler::push`], and buffers the resampled samples until the audio
+/// device pulls them by [`Resampler::fill`]. The output is mono.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    /// The input rate, in Hz.
+    input_rate: f64,
+    /// The output rate, in Hz.
+    output_rate: u32,
+    /// The duration of the buffered output samples targeted by the dynamic
+    /// rate control, in seconds.
+    latency: f32,
+    /// A flag denoting if the dynamic rate control is enabled.
+    rate_control: bool,
+    /// The largest relative deviation of the rate for the dynamic rate
+    /// control.
+    max_deviation: f64,
+    /// The quality of the resampling.
+    quality: Quality,
+    /// The table of the steps of the quality.
+    kernel: Kernel,
+    /// The last input sample.
+    last: f32,
+    /// The position of the next input sample within the output samples: the
+    /// index into `pending`, and the fraction of a sample after it.
+    index: usize,
+    /// See `index`.
+    fraction: f64,
+    /// The changes of the output samples which are still affected by the
+    /// next input samples, i.e. the derivative of the output.
+    pending: Vec<f32>,
+    /// The sum of all changes of the output samples, i.e. the output before
+    /// filtering.
+    level: f32,
+    /// The filters of the output.
+    filters: Filters,
+    /// The resampled samples which weren't pulled yet.
+    output: VecDeque<f32>,
+    /// The last pulled sample.
+    held: f32,
+    /// A flag denoting if the buffer was cleared and no samples were pushed
+    /// since, so running out of samples isn't an underrun.
+    cleared: bool,
+    /// A flag denoting if the last pull ran out of samples.
+    starved: bool,
+    /// The statistics of the buffer.
+    stats: Stats,
+}
+
+/// The statistics of the buffer of a [`Resampler`], e.g. to tune its latency
+/// to the audio device.
+///
+/// An underrun is counted once when the audio device pulls more samples than
+/// are buffered, and not again until it got all the samples it pulled. The
+/// buffer running out after it was [cleared](Resampler::clear), e.g. while
+/// the console is paused, isn't an underrun.
+///
+/// ```
+/// # use chuck_audio::Resampler;
+/// let mut resampler = Resampler::new(1_789_773.0, 48_000);
+/// resampler.push(&[0.0; 29_780]);
+///
+/// // The frame is about 800 samples, so the device runs out twice in a row.
+/// let mut output = [0.0; 512];
+/// for _ in 0..3 {
+///     resampler.fill(&mut output);
+/// }
+/// assert_eq!(resampler.stats().underruns, 1);
+/// assert!(resampler.stats().missing > 700);
+///
+/// // The buffer is dropped while paused, which doesn't count.
+/// resampler.clear();
+/// resampler.fill(&mut output);
+/// assert_eq!(resampler.stats().underruns, 1);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of times the audio device ran out of samples.
+    pub underruns: u64,
+    /// The number of samples which the audio device pulled during the
+    /// underruns, but which weren't buffered.
+    pub missing: u64,
+    /// The number of times the buffered samples were dropped, because the
+    /// audio device stopped pulling them.
+    pub overflows: u64,
+}
+
+impl Resampler {
+    /// Create a resampler between the given sample rates, in Hz.
+    ///
+    /// The input rate is the CPU clock of the console, see
+    /// `chuck_nes::Region::cpu_clock`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the output rate isn't lower than the input rate.
+    #[must_use]
+    pub fn new(input_rate: f64, output_rate: u32) -> Self {
+        assert!(f64::from(output_rate) < input_rate);
+
+        Self {
+            input_rate,
+            output_rate,
+            latency: LATENCY,
+            rate_control: true,
+            max_deviation: MAX_DEVIATION,
+            quality: Quality::default(),
+            kernel: Kernel::new(Quality::default()),
+            last: 0.0,
+            index: 0,
+            fraction: 0.0,
+            pending: vec![0.0; WIDTH],
+            level: 0.0,
+            filters: Filters::new(to_f32(output_rate)),
+            output: VecDeque::new(),
+            held: 0.0,
+            cleared: true,
+            starved: false,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Return the input rate, in Hz.
+    #[must_use]
+    pub const fn input_rate(&self) -> f64 {
+        self.input_rate
+    }
+
+    /// Change the input rate, e.g. for a console of another region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the output rate isn't lower than the input rate.
+    pub fn set_input_rate(&mut self, rate: f64) {
+        assert!(f64::from(self.output_rate) < rate);
+
+        self.input_rate = rate;
+    }
+
+    /// Return the output rate, in Hz.
+    #[must_use]
+    pub const fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Return the latency, in seconds.
+    #[must_use]
+    pub const fn latency(&self) -> f32 {
+        self.latency
+    }
+
+    /// Change the latency, in seconds, i.e. the duration of the samples which
+    /// the dynamic rate control keeps buffered. The default is 50 ms.
+    ///
+    /// The latency should be a few times the duration of the samples pulled
+    /// by the audio device at once.
+    pub fn set_latency(&mut self, latency: f32) {
+        self.latency = latency;
+    }
+
+    /// Enable or disable the dynamic rate control, which is enabled by
+    /// default.
+    ///
+    /// Without it, the output rate is exact, as it should be for recordings
+    /// which aren't played back by an audio device, see [`wav`].
+    pub fn set_rate_control(&mut self, enabled: bool) {
+        self.rate_control = enabled;
+    }
+
+    /// Change the largest relative deviation of the rate for the dynamic
+    /// rate control, which is 0.005 (half a percent) by default.
+    ///
+    /// A larger deviation recovers the latency faster, e.g. from an audio
+    /// device pulling the samples in bursts, but may be audible as a change
+    /// of the pitch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the deviation isn't between 0 and 0.1.
+    pub fn set_max_deviation(&mut self, deviation: f64) {
+        assert!((0.0..=0.1).contains(&deviation));
+
+        self.max_deviation = deviation;
+    }
+
+    /// Return the quality of the resampling.
+    #[must_use]
+    pub const fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Change the quality of the resampling, which is [`Quality::Sinc`] by
+    /// default.
+    pub fn set_quality(&mut self, quality: Quality) {
+        if quality != self.quality {
+            self.quality = quality;
+            self.kernel = Kernel::new(quality);
+        }
+    }
+
+    /// Return the statistics of the buffer, since the creation of the
+    /// resampler or the last [`Resampler::reset_stats`].
+    #[must_use]
+    pub const fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Reset the statistics of the buffer.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Return the number of buffered output samples.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Check if no output samples are buffered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.output.is_empty()
+    }
+
+    /// Drop the buffered samples, e.g. after pausing the console.
+    pub fn clear(&mut self) {
+        self.output.clear();
+        self.cleared = true;
+    }
+
+    /// Resample the given input samples, like the samples of an emulated
+    /// frame.
+    pub fn push(&mut self, samples: &[f32]) {
+        let step = f64::from(self.output_rate) / self.input_rate * self.adjustment();
+        let taps = self.kernel.taps();
+
+        for &sample in samples {
+            let delta = sample - self.last;
+            if delta != 0.0 {
+                let pending = &mut self.pending[self.index..self.index + WIDTH];
+                let kernel = self.kernel.step(self.fraction);
+                for (pending, &step) in pending[taps.clone()].iter_mut().zip(&kernel[taps.clone()])
+                {
+                    *pending += delta * step;
+                }
+
+                self.last = sample;
+            }
+
+            // The output rate is lower than the input rate, so the input
+            // samples advance by less than an output sample.
+            self.fraction += step;
+            if self.fraction >= 1.0 {
+                self.fraction -= 1.0;
+                self.index += 1;
+
+                if self.pending.len() < self.index + WIDTH {
+                    self.pending.resize(self.index + WIDTH, 0.0);
+                }
+            }
+        }
+
+        // The output samples before the position of the next input sample
+        // are complete.
+        for &delta in &self.pending[..self.index] {
+            self.level += delta;
+            self.output.push_back(self.filters.filter(self.level));
+        }
+
+        self.pending.drain(..self.index);
+        self.index = 0;
+        self.cleared &= self.output.is_empty();
+
+        if self.buffered() > OVERFLOW * self.target() {
+            self.output.clear();
+            self.stats.overflows += 1;
+        }
+    }
+
+    /// Fill the given buffer with the resampled samples, returning the
+    /// number of samples which were buffered.
+    ///
+    /// If there aren't enough buffered samples, the rest of the buffer is
+    /// filled with the last sample, which avoids a click.
+    pub fn fill(&mut self, output: &mut [f32]) -> usize {
+        let filled = output.len().min(self.output.len());
+        for (output, sample) in output.iter_mut().zip(self.output.drain(..filled)) {
+            *output = sample;
+            self.held = sample;
+        }
+
+        output[filled..].fill(self.held);
+
+        let starved = filled < output.len();
+        if starved && !self.cleared {
+            self.stats.underruns += u64::from(!self.starved);
+            self.stats.missing += (output.len() - filled) as u64;
+        }
+        self.starved = starved;
+
+        filled
+    }
+
+    /// Return the factor of the output rate for the dynamic rate control,
+    /// which produces more samples while fewer than the latency are
+    /// buffered, and fewer samples while more are buffered.
+    fn adjustment(&self) -> f64 {
+        if !self.rate_control {
+            return 1.0;
+        }
+
+        let target = self.target();
+        let deviation = (target - self.buffered()) / target;
+        self.max_deviation.mul_add(deviation.clamp(-1.0, 1.0), 1.0)
+    }
+
+    /// Return the number of buffered output samples targeted by the dynamic
+    /// rate control.
+    fn target(&self) -> f64 {
+        f64::from(self.latency) * f64::from(self.output_rate)
+    }
+
+    /// Return the number of buffered output samples, for the comparisons
+    /// with the latency.
+    fn buffered(&self) -> f64 {
+        f64::from(u32::try_from(self.output.len()).unwrap_or(u32::MAX))
+    }
+}
+
+/// Convert a sample rate into an `f32`, rounding it to 24 significant bits.
+fn to_f32(rate: u32) -> f32 {
+    let [low, high] = [rate & 0xffff, rate >> 16].map(|half| u16::try_from(half).unwrap_or(0));
+    f32::from(high).mul_add(65_536.0, f32::from(low))
+}
@@ -0,0 +1,156 @@
+//! A writer of WAV files, for recording the audio output.
+//!
+//! The files are mono (or stereo, see [`Wav::with_channels`]) with 32-bit
+//! floating point samples, which keeps the samples exactly as resampled,
+//! without clipping or dithering them.
+//!
+//! ```
+//! # use std::io::Cursor;
+//! # use chuck_audio::wav::Wav;
+//! let mut wav = Wav::new(Cursor::new(Vec::new()), 48_000)?;
+//! wav.write(&[0.0, 0.5, -0.5])?;
+//!
+//! let file = wav.finish()?.into_inner();
+//! assert_eq!(&file[..4], b"RIFF");
+//! assert_eq!(file.len(), 58 + 3 * 4);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.mmsp.ece.mcgill.ca/Documents/AudioFormats/WAVE/WAVE.html>
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// The format tag of floating point samples.
+const FORMAT_FLOAT: u16 = 3;
+
+/// The size of a sample, in bytes.
+const SAMPLE_SIZE: u16 = 4;
+
+/// The offset of the size of the `RIFF` chunk.
+const RIFF_SIZE: u64 = 4;
+
+/// The offset of the number of samples in the `fact` chunk.
+const FACT_SAMPLES: u64 = 46;
+
+/// The offset of the size of the `data` chunk.
+const DATA_SIZE: u64 = 54;
+
+/// The size of the header, up to the samples of the `data` chunk.
+const HEADER_SIZE: u32 = 58;
+
+/// The largest number of samples, for which the size of the file still fits
+/// into the 32 bits of the `RIFF` chunk's size.
+const MAX_SAMPLES: u32 = (u32::MAX - HEADER_SIZE) / 4;
+
+/// A WAV file being written.
+///
+/// The sizes in the header are only known once all samples are written, so
+/// the file must be completed by [`Wav::finish`].
+#[derive(Debug)]
+pub struct Wav<W: Write + Seek> {
+    /// The file.
+    writer: W,
+    /// The number of channels.
+    channels: u16,
+    /// The number of samples written, of all channels.
+    samples: u32,
+}
+
+impl<W: Write + Seek> Wav<W> {
+    /// Start a mono WAV file with the given sample rate, in Hz, writing its
+    /// header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header can't be written.
+    pub fn new(writer: W, rate: u32) -> io::Result<Self> {
+        Self::with_channels(writer, rate, 1)
+    }
+
+    /// Start a WAV file with the given sample rate, in Hz, and number of
+    /// channels, e.g. 2 for stereo, writing its header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header can't be written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of channels is 0.
+    pub fn with_channels(mut writer: W, rate: u32, channels: u16) -> io::Result<Self> {
+        assert!(channels > 0, "a WAV file needs a channel");
+        let block = channels * SAMPLE_SIZE;
+
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend(b"RIFF\0\0\0\0WAVE");
+
+        // The format chunk, with the extension size of non-PCM formats.
+        header.extend(b"fmt \x12\0\0\0");
+        header.extend(FORMAT_FLOAT.to_le_bytes());
+        header.extend(channels.to_le_bytes());
+        header.extend(rate.to_le_bytes());
+        header.extend(rate.saturating_mul(u32::from(block)).to_le_bytes());
+        header.extend(block.to_le_bytes());
+        header.extend((SAMPLE_SIZE * 8).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+
+        // The number of samples, which the non-PCM formats must declare.
+        header.extend(b"fact\x04\0\0\0\0\0\0\0");
+        header.extend(b"data\0\0\0\0");
+
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            channels,
+            samples: 0,
+        })
+    }
+
+    /// Append samples to the file, interleaved if it has several channels,
+    /// i.e. the samples of all channels at a time one after another.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the samples can't be written, or if the file would
+    /// exceed the 4 GiB that WAV files are limited to.
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let count = u32::try_from(samples.len())
+            .ok()
+            .and_then(|count| self.samples.checked_add(count))
+            .filter(|&count| count <= MAX_SAMPLES)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "WAV file too large"))?;
+
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        self.writer.write_all(&bytes)?;
+
+        self.samples = count;
+        Ok(())
+    }
+
+    /// Complete the header of the file, returning the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header can't be written.
+    pub fn finish(mut self) -> io::Result<W> {
+        let size = self.samples * u32::from(SAMPLE_SIZE);
+
+        for (offset, value) in [
+            (RIFF_SIZE, HEADER_SIZE - 8 + size),
+            (FACT_SAMPLES, self.samples / u32::from(self.channels)),
+            (DATA_SIZE, size),
+        ] {
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
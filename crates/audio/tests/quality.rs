@@ -0,0 +1,67 @@
+//! The aliasing of the qualities of the resampling: a tone above half of the
+//! output rate must vanish with the windowed sincs, while the cheaper
+//! qualities let more of its alias through, and all of them must keep a tone
+//! well below half of the output rate.
+
+use std::f32::consts::TAU;
+
+use chuck_audio::{Quality, Resampler};
+
+/// The input rate, the CPU clock of an NTSC console.
+const INPUT_RATE: f32 = 1_789_773.0;
+
+/// The output rate.
+const OUTPUT_RATE: u32 = 48_000;
+
+/// Return the RMS of the output of a sine of the given frequency, after the
+/// filters settled.
+fn rms(quality: Quality, frequency: f32) -> f64 {
+    let mut resampler = Resampler::new(f64::from(INPUT_RATE), OUTPUT_RATE);
+    resampler.set_quality(quality);
+    resampler.set_rate_control(false);
+
+    // A third of a second of the sine, in frames.
+    let step = frequency / INPUT_RATE;
+    let mut phase = 0.0;
+    let mut output = Vec::new();
+    let mut buffer = [0.0; 1024];
+    for _ in 0..20 {
+        let samples: Vec<f32> = (0..29_781)
+            .map(|_| {
+                phase += step;
+                if phase >= 1.0 {
+                    phase -= 1.0;
+                }
+                0.5 * (TAU * phase).sin()
+            })
+            .collect();
+        resampler.push(&samples);
+
+        let filled = resampler.fill(&mut buffer);
+        output.extend_from_slice(&buffer[..filled]);
+    }
+
+    let settled = &output[output.len() / 4..];
+    let sum: f64 = settled
+        .iter()
+        .map(|&sample| f64::from(sample).powi(2))
+        .sum();
+    (sum / f64::from(u32::try_from(settled.len()).unwrap())).sqrt()
+}
+
+#[test]
+fn alias_above_nyquist() {
+    let [hold, linear, sinc] =
+        [Quality::Hold, Quality::Linear, Quality::Sinc].map(|quality| rms(quality, 30_000.0));
+    assert!(sinc < 0.01 * hold, "{sinc} vs. {hold}");
+    assert!(linear < 0.5 * hold, "{linear} vs. {hold}");
+    assert!(sinc < linear, "{sinc} vs. {linear}");
+}
+
+#[test]
+fn keep_tones_below_nyquist() {
+    for quality in [Quality::Hold, Quality::Linear, Quality::Sinc] {
+        let rms = rms(quality, 1_000.0);
+        assert!((0.25..0.4).contains(&rms), "{quality:?}: {rms}");
+    }
+}
@@ -0,0 +1,80 @@
+//! Benchmarks measuring the per-cycle dispatch throughput of the CPU, in
+//! emulated cycles per second, for different instruction streams.
+
+use std::hint::black_box;
+
+use chuck_cpu::{Bus, Cpu, Model, Ricoh2A03, Wdc65C02};
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion, Throughput};
+
+/// The number of cycles executed by every benchmark iteration.
+const CYCLES: u64 = 100_000;
+
+/// Service a bus access using the given memory.
+fn service(ram: &mut [u8], bus: &mut Bus) {
+    if bus.write {
+        ram[usize::from(bus.addr)] = bus.data;
+    } else {
+        bus.data = ram[usize::from(bus.addr)];
+    }
+}
+
+/// Create a 64K memory filled with a single opcode, which is also used as the
+/// operand bytes of the opcode (and the reset vector).
+fn filled(opcode: u8) -> Vec<u8> {
+    vec![opcode; 0x10000]
+}
+
+/// Create a 64K memory filled with pseudo-random opcodes for the 65C02.
+///
+/// The `WAI` and `STP` opcodes are replaced with a `NOP`, so that the CPU
+/// never halts. Every other opcode is defined on the 65C02, so this exercises
+/// the dispatch of (almost) every instruction in an unpredictable order.
+fn random() -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+
+    (0..0x10000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            match state.to_le_bytes()[0] {
+                0xcb | 0xdb => 0xea,
+                opcode => opcode,
+            }
+        })
+        .collect()
+}
+
+/// Benchmark the execution of the given memory by a CPU of the given model.
+fn stream<M: Model>(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    name: &str,
+    model: M,
+    mut ram: Vec<u8>,
+) {
+    let mut cpu = Cpu::with_model(model);
+
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            cpu.run_cycles(CYCLES, |bus| service(&mut ram, bus));
+            black_box(cpu.regs.a)
+        });
+    });
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(CYCLES));
+
+    stream(&mut group, "implied", Ricoh2A03, filled(0xea));
+    stream(&mut group, "zero page", Ricoh2A03, filled(0xa5));
+    stream(&mut group, "absolute indexed", Ricoh2A03, filled(0xbd));
+    stream(&mut group, "random", Wdc65C02, random());
+
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
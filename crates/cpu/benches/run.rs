@@ -0,0 +1,87 @@
+//! Benchmarks comparing an external stepping loop against the batched
+//! execution API of the CPU.
+
+use std::hint::black_box;
+
+use chuck_cpu::{Bus, Cpu, Pins};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// The number of cycles executed by every benchmark iteration.
+const CYCLES: u64 = 100_000;
+
+/// A small program that endlessly copies (and increments) a page of memory.
+#[rustfmt::skip]
+const PROGRAM: [u8; 16] = [
+    0xa2, 0x00,       // LDX #$00
+    0xbd, 0x00, 0x02, // LDA $0200,X
+    0x69, 0x01,       // ADC #$01
+    0x9d, 0x00, 0x03, // STA $0300,X
+    0xe8,             // INX
+    0xd0, 0xf5,       // BNE $0402
+    0x4c, 0x00, 0x04, // JMP $0400
+];
+
+/// Create a 64K memory holding the program, pointed to by the reset vector.
+fn memory() -> Vec<u8> {
+    let mut ram = vec![0; 0x10000];
+    ram[0x0400..0x0410].copy_from_slice(&PROGRAM);
+    ram[0xfffc] = 0x00;
+    ram[0xfffd] = 0x04;
+    ram
+}
+
+/// Service a bus access using the given memory.
+fn service(ram: &mut [u8], bus: &mut Bus) {
+    if bus.write {
+        ram[usize::from(bus.addr)] = bus.data;
+    } else {
+        bus.data = ram[usize::from(bus.addr)];
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run");
+
+    group.bench_function("external loop", |b| {
+        let mut cpu = Cpu::new();
+        let mut ram = memory();
+
+        b.iter(|| {
+            for _ in 0..CYCLES {
+                cpu.step();
+                service(&mut ram, &mut cpu.bus);
+            }
+
+            black_box(cpu.regs.a)
+        });
+    });
+
+    group.bench_function("run_cycles", |b| {
+        let mut cpu = Cpu::new();
+        let mut ram = memory();
+
+        b.iter(|| {
+            cpu.run_cycles(CYCLES, |bus| service(&mut ram, bus));
+            black_box(cpu.regs.a)
+        });
+    });
+
+    group.bench_function("run_until", |b| {
+        let mut cpu = Cpu::new();
+        let mut ram = memory();
+
+        b.iter(|| {
+            let mut cycles = 0;
+            while cycles < CYCLES {
+                cycles += cpu.run_until(Pins::SYNC, |bus| service(&mut ram, bus));
+            }
+
+            black_box(cpu.regs.a)
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
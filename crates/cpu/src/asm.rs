@@ -0,0 +1,548 @@
+//! A tiny two-pass 6502 assembler for writing CPU tests.
+//!
+//! The assembler understands the instructions of both CPU variants using the
+//! standard syntax for every addressing mode, as well as:
+//!
+//! - Comments, starting with `;`.
+//! - Labels, `name:`, which may be followed by an instruction on the same line.
+//! - Constants, `name = value`.
+//! - The directives `.org`, `.byte` and `.word`.
+//! - Numbers in hexadecimal (`$ff`), binary (`%1010`) and decimal (`255`),
+//!   labels, the current address (`*`), the `+` and `-` operators and the
+//!   low/high byte prefixes `<` and `>`.
+//!
+//! An operand that is known to fit into the zero page when it is first seen
+//! chooses a zero-page addressing mode if possible, while forward references
+//! always choose an absolute addressing mode.
+//!
+//! ```
+//! # use chuck_cpu::asm;
+//! let bytes = asm!("LDX #$10\nloop: DEX\nBNE loop");
+//!
+//! assert_eq!(bytes, [0xa2, 0x10, 0xca, 0xd0, 0xfd]);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::instr::{Instr, Mode, Op, CMOS_INSTRS, NMOS_INSTRS};
+use crate::Variant;
+
+/// Assemble 6502 source code into machine code, panicking on any error.
+///
+/// The source is assembled for the NMOS 6502, unless a [`Variant`] is given
+/// before the source.
+///
+/// # Panics
+///
+/// Panics if the source code contains an error, see [`assemble`].
+#[macro_export]
+macro_rules! asm {
+    ($src:expr) => {
+        $crate::asm!($crate::Variant::Nmos, $src)
+    };
+    ($variant:expr, $src:expr) => {
+        match $crate::asm::assemble($variant, $src) {
+            Ok(bytes) => bytes,
+            Err(err) => panic!("{err}"),
+        }
+    };
+}
+
+/// An error encountered while assembling source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// The (one-based) line number of the error.
+    pub line: usize,
+    /// A description of the error.
+    pub msg: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The operand of an instruction, split by its syntax.
+#[derive(Debug, Clone, Copy)]
+enum Operand<'a> {
+    /// No operand, `OPC`.
+    None,
+    /// The accumulator, `OPC A`.
+    Acc,
+    /// An immediate value, `OPC #v`.
+    Imm(&'a str),
+    /// An address, `OPC v`.
+    Addr(&'a str),
+    /// An address indexed by `X`, `OPC v,X`.
+    AddrX(&'a str),
+    /// An address indexed by `Y`, `OPC v,Y`.
+    AddrY(&'a str),
+    /// An indirect address, `OPC (v)`.
+    Ind(&'a str),
+    /// An indexed indirect address, `OPC (v,X)`.
+    IndX(&'a str),
+    /// An indirect indexed address, `OPC (v),Y`.
+    IndY(&'a str),
+    /// A pair of addresses, `OPC v,w`.
+    Pair(&'a str, &'a str),
+}
+
+/// A statement of the source code which produces bytes.
+#[derive(Debug)]
+enum Stmt<'a> {
+    /// Set the current address, `.org v`.
+    Org,
+    /// Emit a list of bytes, `.byte v, ...`.
+    Bytes(Vec<&'a str>),
+    /// Emit a list of little-endian words, `.word v, ...`.
+    Words(Vec<&'a str>),
+    /// Emit an instruction with a resolved addressing mode.
+    Instr(u8, Mode, Operand<'a>),
+}
+
+/// A statement with its location in the source code.
+#[derive(Debug)]
+struct Line<'a> {
+    /// The (one-based) line number of the statement.
+    num: usize,
+    /// The address of the statement.
+    pc: u16,
+    /// The statement itself.
+    stmt: Stmt<'a>,
+}
+
+/// Assemble 6502 source code into machine code for the given variant.
+///
+/// The produced bytes start at the address of the first `.org` directive (or
+/// `0x0000` without one); any gaps left by later `.org` directives are filled
+/// with zeros.
+///
+/// # Errors
+///
+/// Returns an error on the first malformed or unknown statement, undefined
+/// label or out-of-range value.
+pub fn assemble(variant: Variant, src: &str) -> Result<Vec<u8>, Error> {
+    let table = match variant {
+        Variant::Nmos => &NMOS_INSTRS,
+        Variant::Cmos => &CMOS_INSTRS,
+    };
+
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+    let mut pc = 0u16;
+
+    // The first pass resolves the addressing modes and the label addresses.
+    for (num, text) in src.lines().enumerate() {
+        let num = num + 1;
+        let error = |msg: String| Error { line: num, msg };
+
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        if let Some((label, rest)) = text.split_once(':') {
+            if is_ident(label.trim()) {
+                define(&mut labels, label.trim(), i32::from(pc)).map_err(error)?;
+                text = rest.trim();
+            }
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        if let Some((name, value)) = text.split_once('=') {
+            let value = eval(value.trim(), &labels, pc)
+                .and_then(|value| value.ok_or_else(|| "constant uses a forward reference".into()))
+                .map_err(error)?;
+
+            define(&mut labels, name.trim(), value).map_err(error)?;
+            continue;
+        }
+
+        let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let rest = rest.trim();
+
+        let (stmt, size) = match name.to_ascii_lowercase().as_str() {
+            ".org" => {
+                pc = eval(rest, &labels, pc)
+                    .and_then(|value| value.ok_or_else(|| "origin uses a forward reference".into()))
+                    .and_then(to_word)
+                    .map_err(error)?;
+
+                (Stmt::Org, 0)
+            }
+            ".byte" => {
+                let values: Vec<_> = rest.split(',').map(str::trim).collect();
+                let size = values.len();
+                (Stmt::Bytes(values), size)
+            }
+            ".word" => {
+                let values: Vec<_> = rest.split(',').map(str::trim).collect();
+                let size = values.len() * 2;
+                (Stmt::Words(values), size)
+            }
+            _ => {
+                let op =
+                    mnemonic(name).ok_or_else(|| error(format!("unknown mnemonic `{name}`")))?;
+                let operand = parse_operand(rest).map_err(error)?;
+                let (opcode, mode) = resolve(table, op, operand, &labels, pc)
+                    .map_err(error)?
+                    .ok_or_else(|| error(format!("invalid addressing mode for `{name}`")))?;
+
+                (
+                    Stmt::Instr(opcode, mode, operand),
+                    1 + usize::from(mode.operand_size()),
+                )
+            }
+        };
+
+        lines.push(Line { num, pc, stmt });
+
+        let size = u16::try_from(size).map_err(|_| error("statement is too large".into()))?;
+        pc = pc.wrapping_add(size);
+    }
+
+    // The second pass encodes the statements using the resolved labels.
+    let mut bytes = Vec::new();
+    let mut origin = None;
+
+    for Line { num, pc, stmt } in lines {
+        let base = *origin.get_or_insert(pc);
+
+        encode(&mut bytes, base, pc, stmt, &labels).map_err(|msg| Error { line: num, msg })?;
+    }
+
+    Ok(bytes)
+}
+
+/// Encode a statement at the given address into the produced bytes, which
+/// start at the given base address.
+fn encode(
+    bytes: &mut Vec<u8>,
+    base: u16,
+    pc: u16,
+    stmt: Stmt<'_>,
+    labels: &HashMap<&str, i32>,
+) -> Result<(), String> {
+    let value =
+        |expr: &str| eval(expr, labels, pc)?.ok_or_else(|| format!("undefined label in `{expr}`"));
+
+    let offset = pc
+        .checked_sub(base)
+        .map(usize::from)
+        .filter(|&offset| offset >= bytes.len())
+        .ok_or("statement overlaps previous code")?;
+
+    bytes.resize(offset, 0);
+
+    match stmt {
+        Stmt::Org => {}
+        Stmt::Bytes(values) => {
+            for expr in values {
+                bytes.push(to_byte(value(expr)?)?);
+            }
+        }
+        Stmt::Words(values) => {
+            for expr in values {
+                bytes.extend(to_word(value(expr)?)?.to_le_bytes());
+            }
+        }
+        Stmt::Instr(opcode, mode, operand) => {
+            bytes.push(opcode);
+
+            match (mode.operand_size(), operand) {
+                (0, _) => {}
+                (_, Operand::Pair(zp, target)) => {
+                    bytes.push(to_byte(value(zp)?)?);
+                    bytes.push(relative(pc, 3, value(target)?)?);
+                }
+                (
+                    size,
+                    Operand::Imm(expr)
+                    | Operand::Addr(expr)
+                    | Operand::AddrX(expr)
+                    | Operand::AddrY(expr)
+                    | Operand::Ind(expr)
+                    | Operand::IndX(expr)
+                    | Operand::IndY(expr),
+                ) => {
+                    let value = value(expr)?;
+
+                    if mode == Mode::Rel {
+                        bytes.push(relative(pc, 2, value)?);
+                    } else if size == 1 {
+                        bytes.push(to_byte(value)?);
+                    } else {
+                        bytes.extend(to_word(value)?.to_le_bytes());
+                    }
+                }
+                (_, Operand::None | Operand::Acc) => unreachable!(),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a string is a valid label name.
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Define a label (or constant) with the given value.
+fn define<'a>(labels: &mut HashMap<&'a str, i32>, name: &'a str, value: i32) -> Result<(), String> {
+    if !is_ident(name) {
+        return Err(format!("invalid label name `{name}`"));
+    }
+
+    if labels.insert(name, value).is_some() {
+        return Err(format!("label `{name}` is defined twice"));
+    }
+
+    Ok(())
+}
+
+/// Evaluate an expression, returning `None` if it uses an undefined label.
+fn eval(expr: &str, labels: &HashMap<&str, i32>, pc: u16) -> Result<Option<i32>, String> {
+    let (expr, select): (_, fn(i32) -> i32) = match expr.as_bytes().first() {
+        Some(b'<') => (&expr[1..], |v| v & 0xff),
+        Some(b'>') => (&expr[1..], |v| (v >> 8) & 0xff),
+        _ => (expr, |v| v),
+    };
+
+    let mut total = Some(0i32);
+    let mut rest = expr.trim();
+    let mut sign = 1;
+
+    if let Some(stripped) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = stripped.trim_start();
+    }
+
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let (term, tail) = rest.split_at(end);
+        let term = term.trim();
+
+        let value = match term.as_bytes().first() {
+            None => return Err(format!("missing value in `{expr}`")),
+            Some(b'$') => Some(parse_number(&term[1..], 16)?),
+            Some(b'%') => Some(parse_number(&term[1..], 2)?),
+            Some(b'*') if term.len() == 1 => Some(i32::from(pc)),
+            Some(b'0'..=b'9') => Some(parse_number(term, 10)?),
+            Some(_) if is_ident(term) => labels.get(term).copied(),
+            Some(_) => return Err(format!("invalid value `{term}`")),
+        };
+
+        total = total.zip(value).map(|(total, value)| total + sign * value);
+
+        match tail.as_bytes().first() {
+            Some(b'+') => sign = 1,
+            Some(b'-') => sign = -1,
+            _ => return Ok(total.map(select)),
+        }
+
+        rest = &tail[1..];
+    }
+}
+
+/// Parse a number of the given radix.
+fn parse_number(digits: &str, radix: u32) -> Result<i32, String> {
+    i32::from_str_radix(digits, radix).map_err(|_| format!("invalid number `{digits}`"))
+}
+
+/// Convert a value into a byte, allowing negative values down to `-128`.
+fn to_byte(value: i32) -> Result<u8, String> {
+    u8::try_from(value)
+        .or_else(|_| i8::try_from(value).map(|v| v.to_le_bytes()[0]))
+        .map_err(|_| format!("value `{value}` does not fit into a byte"))
+}
+
+/// Convert a value into a word.
+fn to_word(value: i32) -> Result<u16, String> {
+    u16::try_from(value).map_err(|_| format!("value `{value}` does not fit into a word"))
+}
+
+/// Calculate the branch offset from an instruction of the given size to the
+/// given target address.
+fn relative(pc: u16, size: u16, target: i32) -> Result<u8, String> {
+    let offset = target - i32::from(pc.wrapping_add(size));
+
+    i8::try_from(offset)
+        .map(|offset| offset.to_le_bytes()[0])
+        .map_err(|_| format!("branch target `{target:#06x}` is out of range"))
+}
+
+/// Split the operand of an instruction by its syntax.
+fn parse_operand(text: &str) -> Result<Operand<'_>, String> {
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+
+    if text.eq_ignore_ascii_case("a") {
+        return Ok(Operand::Acc);
+    }
+
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Imm(value.trim()));
+    }
+
+    if let Some(inner) = text.strip_prefix('(') {
+        let (inner, after) = inner
+            .rsplit_once(')')
+            .ok_or_else(|| format!("invalid indirect operand `{text}`"))?;
+
+        return match (inner.split_once(','), after.trim()) {
+            (None, "") => Ok(Operand::Ind(inner.trim())),
+            (Some((value, index)), "") if index.trim().eq_ignore_ascii_case("x") => {
+                Ok(Operand::IndX(value.trim()))
+            }
+            (None, after)
+                if after
+                    .strip_prefix(',')
+                    .is_some_and(|index| index.trim().eq_ignore_ascii_case("y")) =>
+            {
+                Ok(Operand::IndY(inner.trim()))
+            }
+            _ => Err(format!("invalid indirect operand `{text}`")),
+        };
+    }
+
+    Ok(match text.split_once(',') {
+        None => Operand::Addr(text),
+        Some((value, index)) if index.trim().eq_ignore_ascii_case("x") => {
+            Operand::AddrX(value.trim())
+        }
+        Some((value, index)) if index.trim().eq_ignore_ascii_case("y") => {
+            Operand::AddrY(value.trim())
+        }
+        Some((value, target)) => Operand::Pair(value.trim(), target.trim()),
+    })
+}
+
+/// Resolve the opcode and addressing mode of an instruction.
+///
+/// Addresses that are known to fit into the zero page prefer the zero-page
+/// addressing modes, if available.
+fn resolve(
+    table: &[Instr; 256],
+    op: Op,
+    operand: Operand<'_>,
+    labels: &HashMap<&str, i32>,
+    pc: u16,
+) -> Result<Option<(u8, Mode)>, String> {
+    let is_zero_page = |expr| -> Result<bool, String> {
+        Ok(eval(expr, labels, pc)?.is_some_and(|value| (0..0x100).contains(&value)))
+    };
+
+    let modes: &[Mode] = match operand {
+        Operand::None => &[
+            Mode::Imp,
+            Mode::Rts,
+            Mode::Rti,
+            Mode::Brk,
+            Mode::Psh,
+            Mode::Pul,
+            Mode::Jam,
+            Mode::Wai,
+            Mode::Stp,
+        ],
+        Operand::Acc => &[Mode::Imp],
+        Operand::Imm(_) => &[Mode::Imm],
+        Operand::Addr(expr) if is_zero_page(expr)? => &[
+            Mode::Rel,
+            Mode::Zpg,
+            Mode::Abs,
+            Mode::Jmp,
+            Mode::Jsr,
+            Mode::Lng,
+        ],
+        Operand::Addr(_) => &[Mode::Rel, Mode::Abs, Mode::Jmp, Mode::Jsr, Mode::Lng],
+        Operand::AddrX(expr) if is_zero_page(expr)? => &[Mode::Zpx, Mode::Abx],
+        Operand::AddrX(_) => &[Mode::Abx],
+        Operand::AddrY(expr) if is_zero_page(expr)? => &[Mode::Zpy, Mode::Aby],
+        Operand::AddrY(_) => &[Mode::Aby],
+        Operand::Ind(expr) if is_zero_page(expr)? => &[Mode::Izp, Mode::Ind],
+        Operand::Ind(_) => &[Mode::Ind],
+        Operand::IndX(expr) if is_zero_page(expr)? => &[Mode::Izx, Mode::Iax],
+        Operand::IndX(_) => &[Mode::Iax],
+        Operand::IndY(_) => &[Mode::Izy],
+        Operand::Pair(..) => &[Mode::Zrl],
+    };
+
+    // The official `NOP` is preferred over its unofficial duplicates.
+    if op == Op::Nop && matches!(operand, Operand::None) {
+        return Ok(Some((0xea, Mode::Imp)));
+    }
+
+    Ok(modes.iter().find_map(|&mode| {
+        (0..=u8::MAX)
+            .find(|&opcode| {
+                let instr = table[usize::from(opcode)];
+                instr.op == op && instr.mode == mode
+            })
+            .map(|opcode| (opcode, mode))
+    }))
+}
+
+/// The mnemonics of the operations without a bit number.
+#[rustfmt::skip]
+const MNEMONICS: [(&str, Op); 86] = [
+    ("ADC", Op::Adc), ("AND", Op::And), ("ASL", Op::Asl), ("BCC", Op::Bcc),
+    ("BCS", Op::Bcs), ("BEQ", Op::Beq), ("BIT", Op::Bit), ("BMI", Op::Bmi),
+    ("BNE", Op::Bne), ("BPL", Op::Bpl), ("BRK", Op::Brk), ("BVC", Op::Bvc),
+    ("BVS", Op::Bvs), ("CLC", Op::Clc), ("CLD", Op::Cld), ("CLI", Op::Cli),
+    ("CLV", Op::Clv), ("CMP", Op::Cmp), ("CPX", Op::Cpx), ("CPY", Op::Cpy),
+    ("DEC", Op::Dec), ("DEX", Op::Dex), ("DEY", Op::Dey), ("EOR", Op::Eor),
+    ("INC", Op::Inc), ("INX", Op::Inx), ("INY", Op::Iny), ("JMP", Op::Jmp),
+    ("JSR", Op::Jsr), ("LDA", Op::Lda), ("LDX", Op::Ldx), ("LDY", Op::Ldy),
+    ("LSR", Op::Lsr), ("NOP", Op::Nop), ("ORA", Op::Ora), ("PHA", Op::Pha),
+    ("PHP", Op::Php), ("PLA", Op::Pla), ("PLP", Op::Plp), ("ROL", Op::Rol),
+    ("ROR", Op::Ror), ("RTI", Op::Rti), ("RTS", Op::Rts), ("SBC", Op::Sbc),
+    ("SEC", Op::Sec), ("SED", Op::Sed), ("SEI", Op::Sei), ("STA", Op::Sta),
+    ("STX", Op::Stx), ("STY", Op::Sty), ("TAX", Op::Tax), ("TAY", Op::Tay),
+    ("TSX", Op::Tsx), ("TXA", Op::Txa), ("TXS", Op::Txs), ("TYA", Op::Tya),
+    ("ALR", Op::Alr), ("ANC", Op::Anc), ("ANE", Op::Ane), ("ARR", Op::Arr),
+    ("DCP", Op::Dcp), ("ISC", Op::Isc), ("JAM", Op::Jam), ("LAS", Op::Las),
+    ("LAX", Op::Lax), ("LXA", Op::Lxa), ("RLA", Op::Rla), ("RRA", Op::Rra),
+    ("SAX", Op::Sax), ("SBX", Op::Sbx), ("SHA", Op::Sha), ("SHX", Op::Shx),
+    ("SHY", Op::Shy), ("SLO", Op::Slo), ("SRE", Op::Sre), ("TAS", Op::Tas),
+    ("BRA", Op::Bra), ("PHX", Op::Phx), ("PHY", Op::Phy), ("PLX", Op::Plx),
+    ("PLY", Op::Ply), ("STP", Op::Stp), ("STZ", Op::Stz), ("TRB", Op::Trb),
+    ("TSB", Op::Tsb), ("WAI", Op::Wai),
+];
+
+/// Parse the mnemonic of an operation, ignoring its case.
+fn mnemonic(name: &str) -> Option<Op> {
+    let name = name.to_ascii_uppercase();
+
+    // The 65C02 bit operations carry their bit number in the mnemonic.
+    if let Some((prefix, bit @ b'0'..=b'7')) = name
+        .as_bytes()
+        .split_last()
+        .map(|(&bit, prefix)| (prefix, bit))
+    {
+        let bit = bit - b'0';
+
+        match prefix {
+            b"BBR" => return Some(Op::Bbr(bit)),
+            b"BBS" => return Some(Op::Bbs(bit)),
+            b"RMB" => return Some(Op::Rmb(bit)),
+            b"SMB" => return Some(Op::Smb(bit)),
+            _ => {}
+        }
+    }
+
+    MNEMONICS
+        .iter()
+        .find(|(mnemonic, _)| *mnemonic == name)
+        .map(|&(_, op)| op)
+}
@@ -0,0 +1,148 @@
+//! A builder for CPUs with a specific starting state, mostly for tests.
+//!
+//! Instead of running the reset sequence and then changing the registers and
+//! the bus by hand, [`Cpu::builder`] creates a CPU that is about to execute
+//! the instruction at any program counter:
+//!
+//! ```
+//! # use chuck_cpu::{Cpu, Flags};
+//! let cpu = Cpu::builder().pc(0xc000).a(0x10).flags(Flags::C).sp(0xfd).build();
+//!
+//! assert_eq!(cpu.regs.pc, 0xc000);
+//! assert_eq!(cpu.bus.addr, 0xc000);
+//! ```
+
+use crate::{Cpu, Cycle, Flags, Model, Pins, Registers, Ricoh2A03, MAGIC};
+
+/// A builder of a CPU which is about to fetch an opcode, see [`Cpu::builder`].
+///
+/// Every register that is not given keeps the value it has after the reset
+/// sequence of [`Cpu::new`] with zeroed memory, i.e. `A = X = Y = 0`,
+/// `S = $FD`, `P = I` and `PC = $0000`.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct CpuBuilder<M = Ricoh2A03> {
+    /// The hardware model that is emulated.
+    model: M,
+    /// The registers.
+    regs: Registers,
+    /// The I/O control pins.
+    pins: Pins,
+    /// The "magic" constant of the unstable opcodes.
+    magic: u8,
+}
+
+impl Cpu {
+    /// Create a builder of a CPU with a specific starting state, emulating
+    /// the NES's [`Ricoh2A03`] unless another model is given with
+    /// [`CpuBuilder::model`].
+    pub const fn builder() -> CpuBuilder {
+        CpuBuilder {
+            model: Ricoh2A03,
+            regs: Registers {
+                flags: Flags::I,
+                a: 0,
+                x: 0,
+                y: 0,
+                sp: 0xfd,
+                pc: 0,
+            },
+            pins: Pins::empty(),
+            magic: MAGIC,
+        }
+    }
+}
+
+impl<M: Model> CpuBuilder<M> {
+    /// Emulate the given model instead.
+    pub fn model<N: Model>(self, model: N) -> CpuBuilder<N> {
+        CpuBuilder {
+            model,
+            regs: self.regs,
+            pins: self.pins,
+            magic: self.magic,
+        }
+    }
+
+    /// Replace all of the registers.
+    pub fn regs(mut self, regs: Registers) -> Self {
+        self.regs = regs;
+        self
+    }
+
+    /// Replace the flags register, `P`.
+    pub const fn flags(mut self, flags: Flags) -> Self {
+        self.regs.flags = flags;
+        self
+    }
+
+    /// Replace the accumulator, `A`.
+    pub const fn a(mut self, a: u8) -> Self {
+        self.regs.a = a;
+        self
+    }
+
+    /// Replace the first index register, `X`.
+    pub const fn x(mut self, x: u8) -> Self {
+        self.regs.x = x;
+        self
+    }
+
+    /// Replace the second index register, `Y`.
+    pub const fn y(mut self, y: u8) -> Self {
+        self.regs.y = y;
+        self
+    }
+
+    /// Replace the stack pointer, `S`.
+    pub const fn sp(mut self, sp: u8) -> Self {
+        self.regs.sp = sp;
+        self
+    }
+
+    /// Replace the program counter, `PC`, which is the address of the first
+    /// opcode fetch.
+    pub const fn pc(mut self, pc: u16) -> Self {
+        self.regs.pc = pc;
+        self
+    }
+
+    /// Replace the I/O control pins, e.g. to start with a pending `IRQ`.
+    pub const fn pins(mut self, pins: Pins) -> Self {
+        self.pins = pins;
+        self
+    }
+
+    /// Replace the "magic" constant of the unstable `ANE` and `LXA` opcodes,
+    /// see [`Cpu::magic`].
+    pub const fn magic(mut self, magic: u8) -> Self {
+        self.magic = magic;
+        self
+    }
+
+    /// Create the CPU, with the opcode fetch of the program counter placed
+    /// onto the bus.
+    ///
+    /// The fetch must be serviced before the first [`Cpu::step`], like any
+    /// other read. No cycles are counted by [`Cpu::cycles`] yet.
+    #[must_use]
+    pub fn build(self) -> Cpu<M> {
+        let mut cpu = Cpu::with_model(self.model);
+
+        // The reset sequence reads a zeroed memory, which leaves the internal
+        // state exactly as before the first instruction of a program.
+        while !cpu.pins.contains(Pins::SYNC) {
+            cpu.step();
+            cpu.bus.data = 0;
+        }
+
+        cpu.regs = self.regs;
+        cpu.pins = self.pins | Pins::SYNC;
+        cpu.bus.addr = cpu.regs.pc;
+        cpu.bus.kind = Cycle::Opcode;
+        cpu.magic = self.magic;
+        cpu.cycles = 0;
+
+        cpu
+    }
+}
@@ -0,0 +1,73 @@
+//! Opcode and address coverage tracking.
+//!
+//! Every opcode fetch is recorded by the CPU, which allows the authors of test
+//! ROMs (or homebrew games) to verify which code paths were exercised.
+//!
+//! ```
+//! # use chuck_cpu::Cpu;
+//! let mut cpu = Cpu::new();
+//! let mut ram = [0xea; 0x10000];
+//!
+//! for _ in 0..9 {
+//!     cpu.tick(&mut ram);
+//! }
+//!
+//! let coverage = cpu.coverage();
+//! assert!(coverage.is_executed(0xea));
+//! assert_eq!(coverage.fetched().collect::<Vec<_>>(), [0xeaea]);
+//! ```
+
+/// The opcodes and addresses covered by the execution of a CPU.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// The executed opcodes, as a bitset.
+    opcodes: [u64; 4],
+    /// The addresses of the opcode fetches, as a bitset.
+    addrs: Box<[u64; 1024]>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            opcodes: [0; 4],
+            addrs: Box::new([0; 1024]),
+        }
+    }
+}
+
+impl Coverage {
+    /// Check if the given opcode has been executed.
+    ///
+    /// Opcodes that are replaced by an interrupt are not executed.
+    #[must_use]
+    pub fn is_executed(&self, opcode: u8) -> bool {
+        self.opcodes[usize::from(opcode >> 6)] & 1 << (opcode & 0x3f) != 0
+    }
+
+    /// Check if an opcode has been fetched from the given address.
+    #[must_use]
+    pub fn is_fetched(&self, addr: u16) -> bool {
+        self.addrs[usize::from(addr >> 6)] & 1 << (addr & 0x3f) != 0
+    }
+
+    /// Return an iterator over the executed opcodes, in ascending order.
+    pub fn executed(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=u8::MAX).filter(|&opcode| self.is_executed(opcode))
+    }
+
+    /// Return an iterator over the addresses of the opcode fetches, in
+    /// ascending order.
+    pub fn fetched(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..=u16::MAX).filter(|&addr| self.is_fetched(addr))
+    }
+
+    /// Record the fetch of an opcode from the given address.
+    pub(crate) fn fetch(&mut self, addr: u16) {
+        self.addrs[usize::from(addr >> 6)] |= 1 << (addr & 0x3f);
+    }
+
+    /// Record the execution of the given opcode.
+    pub(crate) fn execute(&mut self, opcode: u8) {
+        self.opcodes[usize::from(opcode >> 6)] |= 1 << (opcode & 0x3f);
+    }
+}
@@ -0,0 +1,157 @@
+//! Breakpoints and watchpoints for debuggers.
+//!
+//! The hooks of a CPU are checked by [`Cpu::debug_step`] after every cycle,
+//! which makes them cycle-precise, while [`Cpu::step`] itself is unaffected.
+//!
+//! ```
+//! # use chuck_cpu::{debug::{Reason, StepOutcome}, Cpu};
+//! let mut cpu = Cpu::new();
+//! let mut ram = [0xea; 0x10000];
+//! cpu.hooks.add_breakpoint(0xeaeb);
+//!
+//! let reason = loop {
+//!     if let StepOutcome::Break(reason) = cpu.debug_step() {
+//!         break reason;
+//!     }
+//!
+//!     cpu.bus.data = ram[usize::from(cpu.bus.addr)];
+//! };
+//!
+//! assert_eq!(reason, Reason::Breakpoint(0xeaeb));
+//! ```
+
+use std::ops::RangeInclusive;
+
+use crate::{Cpu, Cycle, Interrupt, Model, Pins};
+
+/// The reasons for a break of the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The opcode at a breakpoint address is being fetched.
+    ///
+    /// The instruction at the address has not been executed yet.
+    Breakpoint(u16),
+    /// An instruction with a hooked opcode has started to execute.
+    Opcode(u8),
+    /// An address inside a read watchpoint is being read.
+    Read(u16),
+    /// An address inside a write watchpoint is being written.
+    Write(u16),
+}
+
+/// The outcome of a [`Cpu::debug_step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// No hook was hit.
+    Continue,
+    /// A hook was hit.
+    Break(Reason),
+}
+
+/// The breakpoints and watchpoints of a CPU.
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    /// The breakpoint addresses.
+    breakpoints: Vec<u16>,
+    /// The address ranges of the read watchpoints.
+    reads: Vec<RangeInclusive<u16>>,
+    /// The address ranges of the write watchpoints.
+    writes: Vec<RangeInclusive<u16>>,
+    /// The hooked opcodes, as a bitset.
+    opcodes: [u64; 4],
+}
+
+impl Hooks {
+    /// Add a breakpoint at the given address.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        if !self.breakpoints.contains(&addr) {
+            self.breakpoints.push(addr);
+        }
+    }
+
+    /// Remove the breakpoint at the given address.
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.retain(|&breakpoint| breakpoint != addr);
+    }
+
+    /// Add a watchpoint for reads of the given address range.
+    pub fn add_read_watchpoint(&mut self, range: RangeInclusive<u16>) {
+        self.reads.push(range);
+    }
+
+    /// Add a watchpoint for writes of the given address range.
+    pub fn add_write_watchpoint(&mut self, range: RangeInclusive<u16>) {
+        self.writes.push(range);
+    }
+
+    /// Remove all watchpoints (for both reads and writes) of the given
+    /// address range.
+    pub fn remove_watchpoint(&mut self, range: &RangeInclusive<u16>) {
+        self.reads.retain(|watchpoint| watchpoint != range);
+        self.writes.retain(|watchpoint| watchpoint != range);
+    }
+
+    /// Hook the given opcode, breaking whenever it starts to execute.
+    ///
+    /// Opcodes that are replaced by an interrupt are never executed, so they
+    /// don't cause a break.
+    pub fn add_opcode(&mut self, opcode: u8) {
+        self.opcodes[usize::from(opcode >> 6)] |= 1 << (opcode & 0x3f);
+    }
+
+    /// Unhook the given opcode.
+    pub fn remove_opcode(&mut self, opcode: u8) {
+        self.opcodes[usize::from(opcode >> 6)] &= !(1 << (opcode & 0x3f));
+    }
+
+    /// Remove all breakpoints, watchpoints and opcode hooks.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Check if the given opcode is hooked.
+    fn has_opcode(&self, opcode: u8) -> bool {
+        self.opcodes[usize::from(opcode >> 6)] & 1 << (opcode & 0x3f) != 0
+    }
+}
+
+impl<M: Model> Cpu<M> {
+    /// Execute a single cycle of the CPU like [`Cpu::step`], then check the
+    /// [`Hooks`] against the cycle.
+    ///
+    /// The bus access placed by the cycle must still be serviced if the CPU
+    /// breaks, so that execution can be resumed afterwards. If multiple hooks
+    /// are hit on the same cycle, breakpoints take priority over opcode hooks,
+    /// which take priority over watchpoints.
+    pub fn debug_step(&mut self) -> StepOutcome {
+        let fetched = self.pins.contains(Pins::SYNC);
+        self.step();
+
+        let addr = self.bus.addr;
+        let hooks = &self.hooks;
+
+        let reason = if self.bus.kind == Cycle::Opcode && hooks.breakpoints.contains(&addr) {
+            Some(Reason::Breakpoint(addr))
+        } else if fetched
+            && !self.pins.contains(Pins::SYNC)
+            && self.schedule == Interrupt::Brk
+            && hooks.has_opcode(self.opcode)
+        {
+            Some(Reason::Opcode(self.opcode))
+        } else if self.bus.write {
+            hooks
+                .writes
+                .iter()
+                .any(|range| range.contains(&addr))
+                .then_some(Reason::Write(addr))
+        } else {
+            hooks
+                .reads
+                .iter()
+                .any(|range| range.contains(&addr))
+                .then_some(Reason::Read(addr))
+        };
+
+        reason.map_or(StepOutcome::Continue, StepOutcome::Break)
+    }
+}
@@ -0,0 +1,204 @@
+//! A disassembler for the instructions of both CPU variants.
+//!
+//! Every instruction is decoded using the opcode metadata of [`OPCODES`] (or
+//! [`CMOS_OPCODES`]) and shown in the standard syntax of its addressing mode,
+//! which is also understood by the `asm` feature's assembler. Unlike the lines
+//! of an execution trace (see [`crate::trace`]), the disassembly does not
+//! depend on the state of the CPU, so it can be used for any memory.
+//!
+//! ```
+//! # use chuck_cpu::{disasm, Variant};
+//! let code = [0xa2, 0x10, 0xca, 0xd0, 0xfd];
+//! let peek = |addr: u16| code[usize::from(addr)];
+//!
+//! let ldx = disasm::disassemble(Variant::Nmos, 0x0000, peek);
+//! let bne = disasm::disassemble(Variant::Nmos, 0x0003, peek);
+//!
+//! assert_eq!(ldx.to_string(), "LDX #$10");
+//! assert_eq!(bne.to_string(), "BNE $0002");
+//! assert_eq!(bne.target(), Some(0x0002));
+//! ```
+
+use std::fmt;
+
+use crate::{AddrMode, OpInfo, Variant, CMOS_OPCODES, OPCODES};
+
+/// A single disassembled instruction, see [`disassemble`].
+///
+/// The [`fmt::Display`] implementation shows the mnemonic and operand of the
+/// instruction, e.g. `LDA ($10),Y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The address of the instruction.
+    pub addr: u16,
+    /// The metadata of the opcode of the instruction.
+    pub info: OpInfo,
+    /// The bytes of the instruction, of which the first `info.len` are used.
+    bytes: [u8; 3],
+}
+
+impl Disassembly {
+    /// Return the bytes of the instruction, including the opcode.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.info.len)]
+    }
+
+    /// Return the opcode of the instruction.
+    #[must_use]
+    pub const fn opcode(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Return the address of the instruction that follows this one in memory.
+    #[must_use]
+    pub const fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.info.len as u16)
+    }
+
+    /// Return the target address of a branch instruction, or `None` for any
+    /// other instruction.
+    #[must_use]
+    pub fn target(&self) -> Option<u16> {
+        let [_, low, high] = self.bytes;
+
+        match self.info.mode {
+            AddrMode::Relative => Some(branch_target(self.addr, 2, low)),
+            AddrMode::ZeroPageRelative => Some(branch_target(self.addr, 3, high)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, |_| None::<&str>)
+    }
+}
+
+impl Disassembly {
+    /// Return the instruction shown with the labels of the addresses in its
+    /// operand, as returned by the given function, e.g. `JSR init` instead
+    /// of `JSR $8123`.
+    ///
+    /// ```
+    /// # use chuck_cpu::{disasm, Variant};
+    /// let code = [0x20, 0x23, 0x81, 0xa5, 0x10];
+    /// let peek = |addr: u16| code[usize::from(addr)];
+    /// let labels = |addr| (addr == 0x8123).then_some("init");
+    ///
+    /// let jsr = disasm::disassemble(Variant::Nmos, 0x0000, peek);
+    /// let lda = disasm::disassemble(Variant::Nmos, 0x0003, peek);
+    ///
+    /// assert_eq!(jsr.labeled(labels).to_string(), "JSR init");
+    /// assert_eq!(lda.labeled(labels).to_string(), "LDA $10");
+    /// ```
+    #[must_use]
+    pub const fn labeled<F, D>(self, labels: F) -> Labeled<F>
+    where
+        F: Fn(u16) -> Option<D>,
+        D: fmt::Display,
+    {
+        Labeled {
+            disassembly: self,
+            labels,
+        }
+    }
+
+    /// Write the instruction, with the labels of the given function.
+    fn write<D: fmt::Display>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        labels: impl Fn(u16) -> Option<D>,
+    ) -> fmt::Result {
+        let [_, low, high] = self.bytes;
+        let operand =
+            |addr, digits| labels(addr).map_or(Operand::Addr(addr, digits), Operand::Label);
+        let zp = operand(u16::from(low), 2);
+        let abs = operand(u16::from_le_bytes([low, high]), 4);
+        let target = operand(self.target().unwrap_or_default(), 4);
+
+        f.write_str(self.info.mnemonic)?;
+
+        match self.info.mode {
+            AddrMode::Implied => Ok(()),
+            AddrMode::Accumulator => write!(f, " A"),
+            AddrMode::Immediate => write!(f, " #${low:02X}"),
+            AddrMode::ZeroPage => write!(f, " {zp}"),
+            AddrMode::ZeroPageX => write!(f, " {zp},X"),
+            AddrMode::ZeroPageY => write!(f, " {zp},Y"),
+            AddrMode::Absolute => write!(f, " {abs}"),
+            AddrMode::AbsoluteX => write!(f, " {abs},X"),
+            AddrMode::AbsoluteY => write!(f, " {abs},Y"),
+            AddrMode::Indirect => write!(f, " ({abs})"),
+            AddrMode::IndexedIndirect => write!(f, " ({zp},X)"),
+            AddrMode::IndirectIndexed => write!(f, " ({zp}),Y"),
+            AddrMode::Relative => write!(f, " {target}"),
+            AddrMode::ZeroPageIndirect => write!(f, " ({zp})"),
+            AddrMode::AbsoluteIndexedIndirect => write!(f, " ({abs},X)"),
+            AddrMode::ZeroPageRelative => write!(f, " {zp},{target}"),
+        }
+    }
+}
+
+/// An instruction shown with labels, see [`Disassembly::labeled`].
+#[derive(Debug, Clone, Copy)]
+pub struct Labeled<F> {
+    /// The instruction.
+    disassembly: Disassembly,
+    /// The function returning the label of an address, if any.
+    labels: F,
+}
+
+impl<F, D> fmt::Display for Labeled<F>
+where
+    F: Fn(u16) -> Option<D>,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.disassembly.write(f, &self.labels)
+    }
+}
+
+/// An address in the operand of an instruction.
+enum Operand<D> {
+    /// An address without a label, with the given number of hex digits.
+    Addr(u16, usize),
+    /// The label of an address.
+    Label(D),
+}
+
+impl<D: fmt::Display> fmt::Display for Operand<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Addr(addr, digits) => write!(f, "${addr:0digits$X}"),
+            Self::Label(label) => label.fmt(f),
+        }
+    }
+}
+
+/// Disassemble the instruction at the given address.
+///
+/// The given function is used to read the bytes of the instruction, which
+/// wrap around at the end of the address space.
+#[must_use]
+pub fn disassemble(variant: Variant, addr: u16, peek: impl Fn(u16) -> u8) -> Disassembly {
+    let opcode = peek(addr);
+    let info = match variant {
+        Variant::Nmos => &OPCODES,
+        Variant::Cmos => &CMOS_OPCODES,
+    }[usize::from(opcode)];
+
+    let mut bytes = [opcode, 0, 0];
+    for offset in 1..info.len {
+        bytes[usize::from(offset)] = peek(addr.wrapping_add(u16::from(offset)));
+    }
+
+    Disassembly { addr, info, bytes }
+}
+
+/// Calculate the target address of a branch instruction of the given size.
+pub(crate) fn branch_target(pc: u16, size: u16, offset: u8) -> u16 {
+    pc.wrapping_add(size)
+        .wrapping_add_signed(i16::from(i8::from_le_bytes([offset])))
+}
@@ -0,0 +1,1267 @@
+//! The cycle-by-cycle execution of CPU instructions.
+//!
+//! Every instruction is executed as a sequence of partial instruction states,
+//! one per CPU cycle. Each cycle consumes the data of the previous bus access
+//! (if any) and places the next bus access onto the memory bus.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/6502_cpu.txt>
+//! - <https://www.nesdev.org/wiki/CPU_unofficial_opcodes>
+//! - <https://www.nesdev.org/wiki/Visual6502wiki/6502_State_Machine>
+
+use crate::instr::{Kind, Mode, Op, CMOS_INSTRS, NMOS_INSTRS};
+use crate::{Cpu, Cycle, Flags, Interrupt, Model, Pins, State, Variant};
+
+/// The base address of the stack page.
+const STACK: u16 = 0x0100;
+
+/// The address of the `NMI` interrupt vector.
+const NMI_VECTOR: u16 = 0xfffa;
+/// The address of the reset interrupt vector.
+const RES_VECTOR: u16 = 0xfffc;
+/// The address of the `IRQ`/`BRK` interrupt vector.
+const IRQ_VECTOR: u16 = 0xfffe;
+
+impl<M: Model> Cpu<M> {
+    /// Execute the current partial instruction state of the current opcode.
+    pub(crate) fn execute(&mut self) {
+        let instr = match M::VARIANT {
+            Variant::Nmos => &NMOS_INSTRS,
+            Variant::Cmos => &CMOS_INSTRS,
+        }[usize::from(self.opcode)];
+
+        match instr.mode {
+            Mode::Imp => self.implied(instr.op),
+            Mode::Imm => self.immediate(instr.op),
+            Mode::Zpg => self.zero_page(instr.op),
+            Mode::Zpx => self.zero_page_indexed(instr.op, self.regs.x),
+            Mode::Zpy => self.zero_page_indexed(instr.op, self.regs.y),
+            Mode::Abs => self.absolute(instr.op),
+            Mode::Abx => self.absolute_indexed(instr.op, self.regs.x),
+            Mode::Aby => self.absolute_indexed(instr.op, self.regs.y),
+            Mode::Izx => self.indexed_indirect(instr.op),
+            Mode::Izy => self.indirect_indexed(instr.op),
+            Mode::Rel => self.branch(instr.op),
+            Mode::Jmp => self.jmp(),
+            Mode::Ind => self.jmp_indirect(),
+            Mode::Jsr => self.jsr(),
+            Mode::Rts => self.rts(),
+            Mode::Rti => self.rti(),
+            Mode::Brk => self.brk(),
+            Mode::Psh => self.push(instr.op),
+            Mode::Pul => self.pull(instr.op),
+            Mode::Jam => self.jam(),
+            Mode::Izp => self.zero_page_indirect(instr.op),
+            Mode::Iax => self.jmp_indexed_indirect(),
+            Mode::Zrl => self.bit_branch(instr.op),
+            Mode::Sgl => self.fetch(),
+            Mode::Lng => self.long_nop(),
+            Mode::Wai => self.wai(),
+            Mode::Stp => self.stp(),
+        }
+    }
+
+    /// Check if the CPU is a 65C02.
+    fn is_cmos() -> bool {
+        matches!(M::VARIANT, Variant::Cmos)
+    }
+
+    /// Place a read access of an address onto the memory bus.
+    fn read(&mut self, addr: u16) {
+        self.read_as(addr, Cycle::Read);
+    }
+
+    /// Place a read access of an address, whose data is discarded, onto the
+    /// memory bus.
+    fn dummy_read(&mut self, addr: u16) {
+        self.read_as(addr, Cycle::DummyRead);
+    }
+
+    /// Place a read access of an address onto the memory bus, tagged with the
+    /// given reason.
+    fn read_as(&mut self, addr: u16, kind: Cycle) {
+        self.bus.addr = addr;
+        self.bus.write = false;
+        self.bus.kind = kind;
+    }
+
+    /// Place a write access of an address onto the memory bus.
+    fn write(&mut self, addr: u16, data: u8) {
+        self.write_as(addr, data, Cycle::Write);
+    }
+
+    /// Place a write access of an address onto the memory bus, tagged with the
+    /// given reason.
+    fn write_as(&mut self, addr: u16, data: u8, kind: Cycle) {
+        self.bus.addr = addr;
+        self.bus.data = data;
+        self.bus.write = true;
+        self.bus.kind = kind;
+    }
+
+    /// Read the byte pointed to by the program counter and increment it.
+    fn operand(&mut self) {
+        self.read_as(self.regs.pc, Cycle::Operand);
+        self.regs.pc = self.regs.pc.wrapping_add(1);
+    }
+
+    /// Fetch the opcode of the next instruction, ending the current one.
+    fn fetch(&mut self) {
+        self.read_as(self.regs.pc, Cycle::Opcode);
+        self.pins.insert(Pins::SYNC);
+        self.tcu.reset();
+    }
+
+    /// Return the address of the current stack slot.
+    fn stack(&self) -> u16 {
+        STACK | u16::from(self.regs.sp)
+    }
+
+    /// Push a byte onto the stack.
+    fn push_stack(&mut self, data: u8) {
+        self.write_as(self.stack(), data, Cycle::Push);
+        self.regs.sp = self.regs.sp.wrapping_sub(1);
+    }
+
+    /// Pull the byte of the current stack slot from the stack.
+    fn pull_stack(&mut self) {
+        self.read_as(self.stack(), Cycle::Pull);
+    }
+
+    /// Return the number of the current cycle relative to the given state.
+    fn since(&self, state: State) -> u8 {
+        self.tcu.state as u8 - state as u8
+    }
+
+    /// Perform the memory access cycles of an instruction whose effective
+    /// address has been calculated into the ADL.
+    ///
+    /// The given cycle number is relative to the first access of the
+    /// effective address.
+    fn access(&mut self, op: Op, cycle: u8) {
+        match (op.kind(), cycle) {
+            (Kind::Read | Kind::Modify, 0) => self.read(self.adl),
+            (Kind::Read, 1) => {
+                self.consume(op, self.bus.data);
+
+                if self.has_decimal_penalty(op) {
+                    self.dummy_read(self.regs.pc);
+                } else {
+                    self.fetch();
+                }
+            }
+            (Kind::Write, 0) => self.write(self.adl, self.store(op)),
+            (Kind::Modify, 1) if Self::is_cmos() => self.dummy_read(self.adl),
+            (Kind::Modify, 1) => self.write_as(self.adl, self.bus.data, Cycle::DummyWrite),
+            (Kind::Modify, 2) => {
+                let data = self.modify(op, self.bus.data);
+                self.write(self.adl, data);
+            }
+            _ => self.fetch(),
+        }
+    }
+
+    /// Execute an instruction with implied (or accumulator) addressing.
+    fn implied(&mut self, op: Op) {
+        if matches!(self.tcu.state, State::T0) {
+            self.dummy_read(self.regs.pc);
+        } else {
+            self.operate(op);
+            self.fetch();
+        }
+    }
+
+    /// Check if an operation takes an extra cycle because of decimal mode.
+    ///
+    /// This is the case for `ADC` and `SBC` on the 65C02, which spends an
+    /// extra cycle to correct the flags of the decimal result.
+    fn has_decimal_penalty(&self, op: Op) -> bool {
+        M::DECIMAL
+            && Self::is_cmos()
+            && self.regs.flags.contains(Flags::D)
+            && matches!(op, Op::Adc | Op::Sbc)
+    }
+
+    /// Execute an instruction with immediate addressing.
+    ///
+    /// The immediate `BIT` of the 65C02 only affects the zero flag.
+    fn immediate(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            _ if op == Op::Bit => {
+                self.regs
+                    .flags
+                    .set(Flags::Z, self.regs.a & self.bus.data == 0);
+                self.fetch();
+            }
+            _ => self.access(op, self.since(State::T0)),
+        }
+    }
+
+    /// Execute an instruction with zero-page addressing.
+    fn zero_page(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.access(op, 0);
+            }
+            _ => self.access(op, self.since(State::T1)),
+        }
+    }
+
+    /// Execute an instruction with indexed zero-page addressing.
+    fn zero_page_indexed(&mut self, op: Op, index: u8) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.dummy_read(self.adl);
+            }
+            State::T2 => {
+                let [base, _] = self.adl.to_le_bytes();
+                self.adl = u16::from(base.wrapping_add(index));
+                self.access(op, 0);
+            }
+            _ => self.access(op, self.since(State::T2)),
+        }
+    }
+
+    /// Execute an instruction with absolute addressing.
+    fn absolute(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.operand();
+            }
+            State::T2 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.access(op, 0);
+            }
+            _ => self.access(op, self.since(State::T2)),
+        }
+    }
+
+    /// Add an index to the base address in the ADL, placing a (possibly
+    /// dummy) read of the partially calculated address onto the memory bus.
+    ///
+    /// Read instructions skip their fix-up cycle when the indexing does not
+    /// cross a page boundary, as the partial address is already correct. The
+    /// 65C02 also skips it for its shift and rotate instructions, and instead
+    /// of reading the partial address, it re-reads the last instruction byte.
+    fn index(&mut self, op: Op, index: u8) {
+        let base = self.adl;
+        self.adl = base.wrapping_add(u16::from(index));
+        let crossed = base & 0xff00 != self.adl & 0xff00;
+
+        let skips = match op.kind() {
+            Kind::Read => true,
+            Kind::Modify => Self::is_cmos() && matches!(op, Op::Asl | Op::Lsr | Op::Rol | Op::Ror),
+            Kind::Write => false,
+        } && !crossed;
+
+        let kind = if skips { Cycle::Read } else { Cycle::DummyRead };
+
+        if crossed && Self::is_cmos() {
+            self.read_as(self.regs.pc.wrapping_sub(1), kind);
+        } else {
+            self.read_as((base & 0xff00) | (self.adl & 0x00ff), kind);
+        }
+
+        if skips {
+            self.tcu.skip();
+        }
+    }
+
+    /// Perform the first memory access of an indexed instruction.
+    fn access_indexed(&mut self, op: Op, index: u8) {
+        if op.is_unstable_store() {
+            self.unstable_store(op, index);
+        } else {
+            self.access(op, 0);
+        }
+    }
+
+    /// Execute an instruction with indexed absolute addressing.
+    fn absolute_indexed(&mut self, op: Op, index: u8) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.operand();
+            }
+            State::T2 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.index(op, index);
+            }
+            State::T3 => self.access_indexed(op, index),
+            _ => self.access(op, self.since(State::T3)),
+        }
+    }
+
+    /// Execute an instruction with indexed indirect addressing.
+    fn indexed_indirect(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.dummy_read(self.adl);
+            }
+            State::T2 => {
+                let [base, _] = self.adl.to_le_bytes();
+                self.adl = u16::from(base.wrapping_add(self.regs.x));
+                self.read(self.adl);
+            }
+            State::T3 => {
+                let [ptr, _] = self.adl.to_le_bytes();
+                self.adl = u16::from(self.bus.data);
+                self.read(u16::from(ptr.wrapping_add(1)));
+            }
+            State::T4 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.access(op, 0);
+            }
+            _ => self.access(op, self.since(State::T4)),
+        }
+    }
+
+    /// Execute an instruction with indirect indexed addressing.
+    fn indirect_indexed(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.read(self.adl);
+            }
+            State::T2 => {
+                let [ptr, _] = self.adl.to_le_bytes();
+                self.adl = u16::from(self.bus.data);
+                self.read(u16::from(ptr.wrapping_add(1)));
+            }
+            State::T3 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.index(op, self.regs.y);
+            }
+            State::T4 => self.access_indexed(op, self.regs.y),
+            _ => self.access(op, self.since(State::T4)),
+        }
+    }
+
+    /// Execute an instruction with zero-page indirect addressing.
+    fn zero_page_indirect(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.read(self.adl);
+            }
+            State::T2 => {
+                let [ptr, _] = self.adl.to_le_bytes();
+                self.adl = u16::from(self.bus.data);
+                self.read(u16::from(ptr.wrapping_add(1)));
+            }
+            State::T3 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.access(op, 0);
+            }
+            _ => self.access(op, self.since(State::T3)),
+        }
+    }
+
+    /// Execute a branch instruction.
+    fn branch(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            _ => self.take_branch(self.condition(op), self.since(State::T1)),
+        }
+    }
+
+    /// Perform the cycles of a branch after its offset has been read.
+    ///
+    /// The given cycle number is relative to the cycle consuming the offset.
+    /// A taken branch that does not cross a page boundary delays interrupt
+    /// polling by one cycle, which is emulated by undoing a pipeline shift.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/CPU_interrupts#Branch_instructions_and_interrupts>
+    fn take_branch(&mut self, taken: bool, cycle: u8) {
+        match cycle {
+            0 if taken => {
+                let offset = i8::from_le_bytes([self.bus.data]);
+                self.adl = self.regs.pc.wrapping_add_signed(i16::from(offset));
+                self.dummy_read(self.regs.pc);
+            }
+            0 => self.fetch(),
+            1 if self.adl & 0xff00 == self.regs.pc & 0xff00 => {
+                self.regs.pc = self.adl;
+                self.pipelines.undo();
+                self.fetch();
+            }
+            1 => self.dummy_read((self.regs.pc & 0xff00) | (self.adl & 0x00ff)),
+            _ => {
+                self.regs.pc = self.adl;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute a bit branch instruction (`BBR` or `BBS`) of the 65C02.
+    ///
+    /// The result of the bit test is kept in the high byte of the ADL until
+    /// the branch offset has been read.
+    fn bit_branch(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 | State::T3 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.read(self.adl);
+            }
+            State::T2 => {
+                let taken = match op {
+                    Op::Bbr(bit) => self.bus.data & 1 << bit == 0,
+                    Op::Bbs(bit) => self.bus.data & 1 << bit != 0,
+                    _ => false,
+                };
+
+                self.dummy_read(self.adl);
+                self.adl |= u16::from(taken) << 8;
+            }
+            State::T4 => {
+                let taken = self.adl & 0xff00 != 0;
+                self.take_branch(taken, 0);
+            }
+            _ => self.take_branch(true, self.since(State::T4)),
+        }
+    }
+
+    /// Execute the absolute `JMP` instruction.
+    fn jmp(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.read_as(self.regs.pc, Cycle::Operand);
+            }
+            _ => {
+                self.regs.pc = self.adl | u16::from(self.bus.data) << 8;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the indirect `JMP` instruction.
+    ///
+    /// On the NMOS 6502, the high byte of the target is fetched without
+    /// carrying into the high byte of the pointer, so `JMP ($xxFF)` wraps
+    /// around within the page. The 65C02 fixes this at the cost of an extra
+    /// cycle.
+    fn jmp_indirect(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.operand();
+            }
+            State::T2 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+
+                if Self::is_cmos() {
+                    self.dummy_read(self.regs.pc.wrapping_sub(1));
+                } else {
+                    self.read(self.adl);
+                    self.tcu.skip();
+                }
+            }
+            State::T3 => self.read(self.adl),
+            State::T4 => {
+                self.regs.pc = u16::from(self.bus.data);
+
+                if Self::is_cmos() {
+                    self.read(self.adl.wrapping_add(1));
+                } else {
+                    self.read((self.adl & 0xff00) | (self.adl.wrapping_add(1) & 0x00ff));
+                }
+            }
+            _ => {
+                self.regs.pc |= u16::from(self.bus.data) << 8;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the indexed indirect `JMP` instruction of the 65C02.
+    fn jmp_indexed_indirect(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.operand();
+            }
+            State::T2 => {
+                self.adl |= u16::from(self.bus.data) << 8;
+                self.adl = self.adl.wrapping_add(u16::from(self.regs.x));
+                self.dummy_read(self.regs.pc.wrapping_sub(1));
+            }
+            State::T3 => self.read(self.adl),
+            State::T4 => {
+                self.regs.pc = u16::from(self.bus.data);
+                self.read(self.adl.wrapping_add(1));
+            }
+            _ => {
+                self.regs.pc |= u16::from(self.bus.data) << 8;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the `JSR` instruction.
+    fn jsr(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.operand(),
+            State::T1 => {
+                self.adl = u16::from(self.bus.data);
+                self.dummy_read(self.stack());
+            }
+            State::T2 => {
+                let [_, high] = self.regs.pc.to_le_bytes();
+                self.push_stack(high);
+            }
+            State::T3 => {
+                let [low, _] = self.regs.pc.to_le_bytes();
+                self.push_stack(low);
+            }
+            State::T4 => self.read_as(self.regs.pc, Cycle::Operand),
+            _ => {
+                self.regs.pc = self.adl | u16::from(self.bus.data) << 8;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the `RTS` instruction.
+    fn rts(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.dummy_read(self.regs.pc),
+            State::T1 => {
+                self.dummy_read(self.stack());
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T2 => {
+                self.pull_stack();
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T3 => {
+                self.adl = u16::from(self.bus.data);
+                self.pull_stack();
+            }
+            State::T4 => {
+                self.regs.pc = self.adl | u16::from(self.bus.data) << 8;
+                self.dummy_read(self.regs.pc);
+                self.regs.pc = self.regs.pc.wrapping_add(1);
+            }
+            _ => self.fetch(),
+        }
+    }
+
+    /// Execute the `RTI` instruction.
+    fn rti(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.dummy_read(self.regs.pc),
+            State::T1 => {
+                self.dummy_read(self.stack());
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T2 => {
+                self.pull_stack();
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T3 => {
+                self.regs.flags = Flags::from_bits_truncate(self.bus.data);
+                self.pull_stack();
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T4 => {
+                self.adl = u16::from(self.bus.data);
+                self.pull_stack();
+            }
+            _ => {
+                self.regs.pc = self.adl | u16::from(self.bus.data) << 8;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Push a byte onto the stack during an interrupt sequence.
+    ///
+    /// The reset sequence performs reads instead of writes, which suppresses
+    /// the stack writes while still decrementing the stack pointer.
+    fn push_interrupt(&mut self, data: u8) {
+        if matches!(self.schedule, Interrupt::Res) {
+            self.dummy_read(self.stack());
+            self.regs.sp = self.regs.sp.wrapping_sub(1);
+        } else {
+            self.push_stack(data);
+        }
+    }
+
+    /// Execute the `BRK` instruction, which is also used to service hardware
+    /// interrupts.
+    ///
+    /// An `NMI` that is detected before the status flags are pushed will
+    /// hijack the sequence, causing the `NMI` vector to be used instead.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/CPU_interrupts#Interrupt_hijacking>
+    fn brk(&mut self) {
+        match self.tcu.state {
+            State::T0 => {
+                self.hijacked = None;
+
+                #[cfg(feature = "history")]
+                if matches!(self.schedule, Interrupt::Res) {
+                    self.history.start(
+                        self.regs.pc,
+                        self.opcode,
+                        Some(Interrupt::Res),
+                        &self.regs,
+                        M::VARIANT,
+                    );
+                }
+
+                if matches!(self.schedule, Interrupt::Brk) {
+                    self.operand();
+                } else {
+                    self.dummy_read(self.regs.pc);
+                }
+            }
+            State::T1 => {
+                let [_, high] = self.regs.pc.to_le_bytes();
+                self.push_interrupt(high);
+            }
+            State::T2 => {
+                let [low, _] = self.regs.pc.to_le_bytes();
+                self.push_interrupt(low);
+            }
+            State::T3 => {
+                let brk = if matches!(self.schedule, Interrupt::Brk) {
+                    0x10
+                } else {
+                    0x00
+                };
+                self.push_interrupt(self.regs.flags.bits() | brk | 0x20);
+
+                if !matches!(self.schedule, Interrupt::Res) && self.pipelines.is_nmi_serviceable() {
+                    self.pipelines.trim_nmi();
+
+                    if matches!(self.schedule, Interrupt::Brk | Interrupt::Irq) {
+                        self.hijacked = Some(self.schedule);
+                    }
+
+                    self.schedule = Interrupt::Nmi;
+                }
+
+                self.adl = match self.schedule {
+                    Interrupt::Res => RES_VECTOR,
+                    Interrupt::Nmi => NMI_VECTOR,
+                    Interrupt::Brk | Interrupt::Irq => IRQ_VECTOR,
+                };
+            }
+            State::T4 => {
+                self.read_as(self.adl, Cycle::Vector);
+                self.regs.flags.insert(Flags::I);
+
+                if Self::is_cmos() {
+                    self.regs.flags.remove(Flags::D);
+                }
+            }
+            State::T5 => {
+                let vector = self.adl;
+                self.adl = u16::from(self.bus.data);
+                self.read_as(vector.wrapping_add(1), Cycle::Vector);
+            }
+            _ => {
+                self.regs.pc = self.adl | u16::from(self.bus.data) << 8;
+                self.schedule = Interrupt::Brk;
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the `PHA` or `PHP` instruction (or `PHX` and `PHY`).
+    fn push(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.dummy_read(self.regs.pc),
+            State::T1 => {
+                let data = match op {
+                    Op::Php => self.regs.flags.bits() | 0x30,
+                    Op::Phx => self.regs.x,
+                    Op::Phy => self.regs.y,
+                    _ => self.regs.a,
+                };
+
+                self.push_stack(data);
+            }
+            _ => self.fetch(),
+        }
+    }
+
+    /// Execute the `PLA` or `PLP` instruction (or `PLX` and `PLY`).
+    fn pull(&mut self, op: Op) {
+        match self.tcu.state {
+            State::T0 => self.dummy_read(self.regs.pc),
+            State::T1 => {
+                self.dummy_read(self.stack());
+                self.regs.sp = self.regs.sp.wrapping_add(1);
+            }
+            State::T2 => self.pull_stack(),
+            _ => {
+                let data = self.bus.data;
+
+                match op {
+                    Op::Plp => self.regs.flags = Flags::from_bits_truncate(data),
+                    Op::Plx => self.regs.x = data,
+                    Op::Ply => self.regs.y = data,
+                    _ => self.regs.a = data,
+                }
+
+                if op != Op::Plp {
+                    self.set_nz(data);
+                }
+
+                self.fetch();
+            }
+        }
+    }
+
+    /// Execute the `JAM` instruction, halting the CPU.
+    ///
+    /// Once jammed, the CPU can only be recovered by a reset.
+    fn jam(&mut self) {
+        self.dummy_read(self.regs.pc);
+        self.jammed = true;
+    }
+
+    /// Execute the long `NOP` instruction (`0x5c`) of the 65C02.
+    ///
+    /// This reads its operand, then spends five more cycles reading from the
+    /// last page of memory.
+    fn long_nop(&mut self) {
+        match self.tcu.state {
+            State::T0 | State::T1 => self.operand(),
+            State::T2 => {
+                let [low, _] = self.regs.pc.wrapping_sub(2).to_le_bytes();
+                self.dummy_read(u16::from_le_bytes([low, 0xff]));
+            }
+            State::T7 => self.fetch(),
+            _ => self.dummy_read(0xffff),
+        }
+    }
+
+    /// Execute the `WAI` instruction of the 65C02.
+    ///
+    /// The CPU sleeps until an interrupt is requested, see [`Cpu::step`].
+    fn wai(&mut self) {
+        match self.tcu.state {
+            State::T0 => self.dummy_read(self.regs.pc),
+            State::T1 => {
+                self.dummy_read(self.regs.pc);
+                self.waiting = true;
+            }
+            _ => self.fetch(),
+        }
+    }
+
+    /// Execute the `STP` instruction of the 65C02, halting the CPU.
+    ///
+    /// Much like a `JAM`, the CPU can only be recovered by a reset.
+    fn stp(&mut self) {
+        self.dummy_read(self.regs.pc);
+        self.jammed = !matches!(self.tcu.state, State::T0);
+    }
+
+    /// Check the condition of a branch instruction.
+    fn condition(&self, op: Op) -> bool {
+        let flags = self.regs.flags;
+
+        match op {
+            Op::Bra => true,
+            Op::Bpl => !flags.contains(Flags::N),
+            Op::Bmi => flags.contains(Flags::N),
+            Op::Bvc => !flags.contains(Flags::V),
+            Op::Bvs => flags.contains(Flags::V),
+            Op::Bcc => !flags.contains(Flags::C),
+            Op::Bcs => flags.contains(Flags::C),
+            Op::Bne => !flags.contains(Flags::Z),
+            _ => flags.contains(Flags::Z),
+        }
+    }
+
+    /// Perform the unstable "high byte" stores (`SHA`, `SHX`, `SHY`, `TAS`).
+    ///
+    /// The effective address is in the ADL, and the given index is used to
+    /// recover whether the indexing crossed a page boundary.
+    fn unstable_store(&mut self, op: Op, index: u8) {
+        let [low, high] = self.adl.to_le_bytes();
+        let crossed = low < index;
+        let mask = high.wrapping_sub(u8::from(crossed)).wrapping_add(1);
+
+        let data = match op {
+            Op::Sha => self.regs.a & self.regs.x & mask,
+            Op::Shx => self.regs.x & mask,
+            Op::Shy => self.regs.y & mask,
+            _ => {
+                self.regs.sp = self.regs.a & self.regs.x;
+                self.regs.sp & mask
+            }
+        };
+
+        let addr = if crossed {
+            u16::from_le_bytes([low, data])
+        } else {
+            self.adl
+        };
+
+        self.write(addr, data);
+    }
+
+    /// Return the value stored by a write operation.
+    fn store(&self, op: Op) -> u8 {
+        match op {
+            Op::Sta => self.regs.a,
+            Op::Stx => self.regs.x,
+            Op::Sty => self.regs.y,
+            Op::Stz => 0,
+            _ => self.regs.a & self.regs.x,
+        }
+    }
+
+    /// Perform a read operation on an operand.
+    fn consume(&mut self, op: Op, data: u8) {
+        match op {
+            Op::Lda => {
+                self.regs.a = data;
+                self.set_nz(data);
+            }
+            Op::Ldx => {
+                self.regs.x = data;
+                self.set_nz(data);
+            }
+            Op::Ldy => {
+                self.regs.y = data;
+                self.set_nz(data);
+            }
+            Op::Lax => {
+                self.regs.a = data;
+                self.regs.x = data;
+                self.set_nz(data);
+            }
+            Op::And => {
+                self.regs.a &= data;
+                self.set_nz(self.regs.a);
+            }
+            Op::Ora => {
+                self.regs.a |= data;
+                self.set_nz(self.regs.a);
+            }
+            Op::Eor => {
+                self.regs.a ^= data;
+                self.set_nz(self.regs.a);
+            }
+            Op::Adc => self.adc(data),
+            Op::Sbc => self.sbc(data),
+            Op::Cmp => self.compare(self.regs.a, data),
+            Op::Cpx => self.compare(self.regs.x, data),
+            Op::Cpy => self.compare(self.regs.y, data),
+            Op::Bit => {
+                self.regs.flags.set(Flags::Z, self.regs.a & data == 0);
+                self.regs.flags.set(Flags::N, data & 0x80 != 0);
+                self.regs.flags.set(Flags::V, data & 0x40 != 0);
+            }
+            _ => self.consume_unofficial(op, data),
+        }
+    }
+
+    /// Perform an unofficial read operation on an operand.
+    fn consume_unofficial(&mut self, op: Op, data: u8) {
+        match op {
+            Op::Anc => {
+                self.regs.a &= data;
+                self.set_nz(self.regs.a);
+                self.regs.flags.set(Flags::C, self.regs.a & 0x80 != 0);
+            }
+            Op::Alr => self.regs.a = self.lsr(self.regs.a & data),
+            Op::Arr if M::DECIMAL && self.regs.flags.contains(Flags::D) => {
+                self.arr_decimal(data);
+            }
+            Op::Arr => {
+                let carry = u8::from(self.regs.flags.contains(Flags::C));
+                let value = (self.regs.a & data) >> 1 | carry << 7;
+
+                self.regs.a = value;
+                self.set_nz(value);
+                self.regs.flags.set(Flags::C, value & 0x40 != 0);
+                let overflow = (value >> 6 ^ value >> 5) & 1 != 0;
+                self.regs.flags.set(Flags::V, overflow);
+            }
+            Op::Ane => {
+                self.regs.a = (self.regs.a | self.magic) & self.regs.x & data;
+                self.set_nz(self.regs.a);
+            }
+            Op::Lxa => {
+                self.regs.a = (self.regs.a | self.magic) & data;
+                self.regs.x = self.regs.a;
+                self.set_nz(self.regs.a);
+            }
+            Op::Sbx => {
+                let (value, borrow) = (self.regs.a & self.regs.x).overflowing_sub(data);
+
+                self.regs.x = value;
+                self.set_nz(value);
+                self.regs.flags.set(Flags::C, !borrow);
+            }
+            Op::Las => {
+                let value = data & self.regs.sp;
+
+                self.regs.a = value;
+                self.regs.x = value;
+                self.regs.sp = value;
+                self.set_nz(value);
+            }
+            _ => {}
+        }
+    }
+
+    /// Perform a read-modify-write operation on an operand, returning the
+    /// modified value.
+    fn modify(&mut self, op: Op, data: u8) -> u8 {
+        match op {
+            Op::Asl => self.asl(data),
+            Op::Lsr => self.lsr(data),
+            Op::Rol => self.rol(data),
+            Op::Ror => self.ror(data),
+            Op::Inc => {
+                let value = data.wrapping_add(1);
+                self.set_nz(value);
+                value
+            }
+            Op::Dec => {
+                let value = data.wrapping_sub(1);
+                self.set_nz(value);
+                value
+            }
+            Op::Slo => {
+                let value = self.asl(data);
+                self.regs.a |= value;
+                self.set_nz(self.regs.a);
+                value
+            }
+            Op::Rla => {
+                let value = self.rol(data);
+                self.regs.a &= value;
+                self.set_nz(self.regs.a);
+                value
+            }
+            Op::Sre => {
+                let value = self.lsr(data);
+                self.regs.a ^= value;
+                self.set_nz(self.regs.a);
+                value
+            }
+            Op::Rra => {
+                let value = self.ror(data);
+                self.adc(value);
+                value
+            }
+            Op::Dcp => {
+                let value = data.wrapping_sub(1);
+                self.compare(self.regs.a, value);
+                value
+            }
+            Op::Tsb => {
+                self.regs.flags.set(Flags::Z, self.regs.a & data == 0);
+                data | self.regs.a
+            }
+            Op::Trb => {
+                self.regs.flags.set(Flags::Z, self.regs.a & data == 0);
+                data & !self.regs.a
+            }
+            Op::Rmb(bit) => data & !(1 << bit),
+            Op::Smb(bit) => data | 1 << bit,
+            _ => {
+                let value = data.wrapping_add(1);
+                self.sbc(value);
+                value
+            }
+        }
+    }
+
+    /// Perform an implied (or accumulator) operation.
+    fn operate(&mut self, op: Op) {
+        match op {
+            Op::Asl => self.regs.a = self.asl(self.regs.a),
+            Op::Lsr => self.regs.a = self.lsr(self.regs.a),
+            Op::Rol => self.regs.a = self.rol(self.regs.a),
+            Op::Ror => self.regs.a = self.ror(self.regs.a),
+            Op::Inc => {
+                self.regs.a = self.regs.a.wrapping_add(1);
+                self.set_nz(self.regs.a);
+            }
+            Op::Dec => {
+                self.regs.a = self.regs.a.wrapping_sub(1);
+                self.set_nz(self.regs.a);
+            }
+            _ => self.transfer(op),
+        }
+    }
+
+    /// Perform an implied register transfer, increment/decrement or flag
+    /// operation.
+    fn transfer(&mut self, op: Op) {
+        let regs = &mut self.regs;
+
+        match op {
+            Op::Tax => regs.x = regs.a,
+            Op::Tay => regs.y = regs.a,
+            Op::Txa => regs.a = regs.x,
+            Op::Tya => regs.a = regs.y,
+            Op::Tsx => regs.x = regs.sp,
+            Op::Txs => regs.sp = regs.x,
+            Op::Inx => regs.x = regs.x.wrapping_add(1),
+            Op::Iny => regs.y = regs.y.wrapping_add(1),
+            Op::Dex => regs.x = regs.x.wrapping_sub(1),
+            Op::Dey => regs.y = regs.y.wrapping_sub(1),
+            Op::Clc => regs.flags.remove(Flags::C),
+            Op::Sec => regs.flags.insert(Flags::C),
+            Op::Cli => regs.flags.remove(Flags::I),
+            Op::Sei => regs.flags.insert(Flags::I),
+            Op::Clv => regs.flags.remove(Flags::V),
+            Op::Cld => regs.flags.remove(Flags::D),
+            Op::Sed => regs.flags.insert(Flags::D),
+            _ => return,
+        }
+
+        match op {
+            Op::Tax | Op::Tsx | Op::Inx | Op::Dex => self.set_nz(self.regs.x),
+            Op::Tay | Op::Iny | Op::Dey => self.set_nz(self.regs.y),
+            Op::Txa | Op::Tya => self.set_nz(self.regs.a),
+            _ => {}
+        }
+    }
+
+    /// Update the zero and negative flags from a value.
+    fn set_nz(&mut self, value: u8) {
+        self.regs.flags.set(Flags::Z, value == 0);
+        self.regs.flags.set(Flags::N, value & 0x80 != 0);
+    }
+
+    /// Add a value and the carry flag to the accumulator.
+    ///
+    /// The 2A03 lacks the decimal mode of the 6502, so the `D` flag is ignored
+    /// unless the model has a decimal mode.
+    fn adc(&mut self, data: u8) {
+        if M::DECIMAL && self.regs.flags.contains(Flags::D) {
+            self.adc_decimal(data);
+            return;
+        }
+
+        self.add(data);
+    }
+
+    /// Subtract a value and the inverted carry flag from the accumulator.
+    ///
+    /// The 2A03 lacks the decimal mode of the 6502, so the `D` flag is ignored
+    /// unless the model has a decimal mode.
+    fn sbc(&mut self, data: u8) {
+        if M::DECIMAL && self.regs.flags.contains(Flags::D) {
+            self.sbc_decimal(data);
+            return;
+        }
+
+        self.add(!data);
+    }
+
+    /// Perform a binary addition of a value and the carry flag to the
+    /// accumulator.
+    fn add(&mut self, data: u8) {
+        let carry = self.regs.flags.contains(Flags::C);
+        let (sum, c1) = self.regs.a.overflowing_add(data);
+        let (sum, c2) = sum.overflowing_add(u8::from(carry));
+        let overflow = (!(self.regs.a ^ data) & (self.regs.a ^ sum)) & 0x80 != 0;
+
+        self.regs.a = sum;
+        self.set_nz(sum);
+        self.regs.flags.set(Flags::C, c1 || c2);
+        self.regs.flags.set(Flags::V, overflow);
+    }
+
+    /// Perform a decimal (BCD) addition of a value and the carry flag to the
+    /// accumulator.
+    ///
+    /// On an NMOS 6502, the `Z` flag reflects the binary result, while the `N`
+    /// and `V` flags are taken from the intermediate result, after the low
+    /// nibble has been adjusted but before the high nibble has been. The 65C02
+    /// instead sets the `N` and `Z` flags from the decimal result.
+    ///
+    /// # Link(s)
+    ///
+    /// - <http://www.6502.org/tutorials/decimal_mode.html>
+    fn adc_decimal(&mut self, data: u8) {
+        let a = self.regs.a;
+        let carry = u8::from(self.regs.flags.contains(Flags::C));
+
+        let mut low = (a & 0x0f) + (data & 0x0f) + carry;
+        if low > 0x09 {
+            low += 0x06;
+        }
+
+        let mut high = (a >> 4) + (data >> 4) + u8::from(low > 0x0f);
+        let overflow = !(a ^ data) & (a ^ high << 4) & 0x80 != 0;
+
+        self.regs
+            .flags
+            .set(Flags::Z, a.wrapping_add(data).wrapping_add(carry) == 0);
+        self.regs.flags.set(Flags::N, high & 0x08 != 0);
+        self.regs.flags.set(Flags::V, overflow);
+
+        if high > 0x09 {
+            high += 0x06;
+        }
+
+        self.regs.flags.set(Flags::C, high > 0x0f);
+        self.regs.a = high << 4 | low & 0x0f;
+
+        if Self::is_cmos() {
+            self.set_nz(self.regs.a);
+        }
+    }
+
+    /// Perform the unofficial `ARR` operation in decimal mode, which rotates
+    /// the accumulator masked by a value like in binary mode, and then
+    /// adjusts both nibbles of the rotated value like a decimal addition.
+    ///
+    /// The `N` and `Z` flags reflect the rotated value, and the `V` flag is
+    /// set like in binary mode, while the `C` flag is set by the adjustment of
+    /// the high nibble.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/6502_cpu.txt>
+    fn arr_decimal(&mut self, data: u8) {
+        let value = self.regs.a & data;
+        let carry = u8::from(self.regs.flags.contains(Flags::C));
+        let mut result = value >> 1 | carry << 7;
+
+        self.set_nz(result);
+        self.regs.flags.set(Flags::V, (value ^ result) & 0x40 != 0);
+
+        if (value & 0x0f) + (value & 0x01) > 0x05 {
+            result = result & 0xf0 | result.wrapping_add(0x06) & 0x0f;
+        }
+
+        let adjust = u16::from(value & 0xf0) + u16::from(value & 0x10) > 0x50;
+        if adjust {
+            result = result.wrapping_add(0x60);
+        }
+
+        self.regs.flags.set(Flags::C, adjust);
+        self.regs.a = result;
+    }
+
+    /// Perform a decimal (BCD) subtraction of a value and the inverted carry
+    /// flag from the accumulator.
+    ///
+    /// On an NMOS 6502, all flags reflect the binary result. The 65C02 uses a
+    /// different adjustment, and sets the `N` and `Z` flags from the decimal
+    /// result.
+    ///
+    /// # Link(s)
+    ///
+    /// - <http://www.6502.org/tutorials/decimal_mode.html>
+    fn sbc_decimal(&mut self, data: u8) {
+        let a = self.regs.a;
+
+        if Self::is_cmos() {
+            let borrow = i16::from(!self.regs.flags.contains(Flags::C));
+            let low = i16::from(a & 0x0f) - i16::from(data & 0x0f) - borrow;
+            let mut value = i16::from(a) - i16::from(data) - borrow;
+
+            if value < 0 {
+                value -= 0x60;
+            }
+
+            if low < 0 {
+                value -= 0x06;
+            }
+
+            self.add(!data);
+            self.regs.a = value.to_le_bytes()[0];
+            self.set_nz(self.regs.a);
+            return;
+        }
+
+        let borrow = u8::from(!self.regs.flags.contains(Flags::C));
+
+        let mut low = (a & 0x0f).wrapping_sub(data & 0x0f).wrapping_sub(borrow);
+        let mut high = (a >> 4).wrapping_sub(data >> 4);
+
+        if low & 0x10 != 0 {
+            low = low.wrapping_sub(0x06);
+            high = high.wrapping_sub(1);
+        }
+
+        if high & 0x10 != 0 {
+            high = high.wrapping_sub(0x06);
+        }
+
+        self.add(!data);
+        self.regs.a = high << 4 | low & 0x0f;
+    }
+
+    /// Compare a register with a value.
+    fn compare(&mut self, reg: u8, data: u8) {
+        let (value, borrow) = reg.overflowing_sub(data);
+
+        self.set_nz(value);
+        self.regs.flags.set(Flags::C, !borrow);
+    }
+
+    /// Shift a value left by one bit.
+    fn asl(&mut self, data: u8) -> u8 {
+        let value = data << 1;
+
+        self.set_nz(value);
+        self.regs.flags.set(Flags::C, data & 0x80 != 0);
+        value
+    }
+
+    /// Shift a value right by one bit.
+    fn lsr(&mut self, data: u8) -> u8 {
+        let value = data >> 1;
+
+        self.set_nz(value);
+        self.regs.flags.set(Flags::C, data & 0x01 != 0);
+        value
+    }
+
+    /// Rotate a value left by one bit through the carry flag.
+    fn rol(&mut self, data: u8) -> u8 {
+        let value = data << 1 | u8::from(self.regs.flags.contains(Flags::C));
+
+        self.set_nz(value);
+        self.regs.flags.set(Flags::C, data & 0x80 != 0);
+        value
+    }
+
+    /// Rotate a value right by one bit through the carry flag.
+    fn ror(&mut self, data: u8) -> u8 {
+        let value = data >> 1 | u8::from(self.regs.flags.contains(Flags::C)) << 7;
+
+        self.set_nz(value);
+        self.regs.flags.set(Flags::C, data & 0x01 != 0);
+        value
+    }
+}
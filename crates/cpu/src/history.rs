@@ -0,0 +1,202 @@
+//! A ring buffer of the last executed instructions.
+//!
+//! Unlike a full execution trace (see [`crate::trace`]), the history is cheap
+//! enough to be always-on, and can be dumped once the CPU has ended up in an
+//! unexpected state, e.g. when it jams.
+//!
+//! ```
+//! # use chuck_cpu::Cpu;
+//! let mut cpu = Cpu::new();
+//! let mut ram = [0xea; 0x10000];
+//! ram[0xeaec] = 0x02; // JAM
+//!
+//! while !cpu.is_jammed() {
+//!     cpu.tick(&mut ram);
+//! }
+//!
+//! let mut dump = Vec::new();
+//! cpu.history.dump(&mut dump).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(dump).unwrap(),
+//!     "0000            RES       A:00 X:00 Y:00 P:20 SP:00\n\
+//!      EAEA  EA        NOP       A:00 X:00 Y:00 P:24 SP:FD\n\
+//!      EAEB  EA        NOP       A:00 X:00 Y:00 P:24 SP:FD\n\
+//!      EAEC  02        JAM       A:00 X:00 Y:00 P:24 SP:FD\n",
+//! );
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+
+use crate::{Interrupt, Registers, Variant, CMOS_OPCODES, OPCODES};
+
+/// The default number of instructions kept by a [`History`].
+const DEPTH: usize = 32;
+
+/// A single instruction (or interrupt sequence) of a [`History`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The address of the instruction.
+    ///
+    /// For interrupt sequences, this is the address of the interrupted
+    /// instruction, or the previous program counter for a reset.
+    pub pc: u16,
+    /// The opcode of the instruction.
+    pub opcode: u8,
+    /// The interrupt serviced instead of the instruction, if any.
+    pub interrupt: Option<Interrupt>,
+    /// The registers before the instruction was executed.
+    pub regs: Registers,
+    /// The variant of the CPU that executed the instruction.
+    pub variant: Variant,
+    /// The operand bytes of the instruction.
+    operands: [u8; 2],
+    /// The number of operand bytes that were fetched.
+    len: u8,
+}
+
+impl Record {
+    /// Return the operand bytes of the instruction that have been fetched.
+    #[must_use]
+    pub fn operands(&self) -> &[u8] {
+        &self.operands[..usize::from(self.len)]
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.interrupt {
+            Some(Interrupt::Irq) => "IRQ",
+            Some(Interrupt::Nmi) => "NMI",
+            Some(Interrupt::Res) => "RES",
+            Some(Interrupt::Brk) | None => match self.variant {
+                Variant::Nmos => OPCODES[usize::from(self.opcode)].mnemonic,
+                Variant::Cmos => CMOS_OPCODES[usize::from(self.opcode)].mnemonic,
+            },
+        };
+
+        let mut bytes = String::new();
+        if self.interrupt.is_none() {
+            let _ = write!(bytes, "{:02X}", self.opcode);
+            for byte in self.operands() {
+                let _ = write!(bytes, " {byte:02X}");
+            }
+        }
+
+        write!(
+            f,
+            "{:04X}  {bytes:<8}  {name:<8}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.pc,
+            self.regs.a,
+            self.regs.x,
+            self.regs.y,
+            self.regs.flags.bits() | 0x20,
+            self.regs.sp,
+        )
+    }
+}
+
+/// A ring buffer of the last executed instructions of a CPU.
+#[derive(Debug, Clone)]
+pub struct History {
+    /// The recorded instructions, from oldest to newest.
+    records: VecDeque<Record>,
+    /// The maximum number of recorded instructions.
+    depth: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_depth(DEPTH)
+    }
+}
+
+impl History {
+    /// Create a new, empty history keeping the given number of instructions.
+    #[must_use]
+    pub fn with_depth(depth: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    /// Return the maximum number of recorded instructions.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Change the maximum number of recorded instructions, dropping the oldest
+    /// ones if there are too many.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.records.len() > depth {
+            self.records.pop_front();
+        }
+    }
+
+    /// Return an iterator over the recorded instructions, from oldest to
+    /// newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Record> {
+        self.records.iter()
+    }
+
+    /// Remove all recorded instructions.
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Write every recorded instruction (and a newline) to the given writer,
+    /// from oldest to newest.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn dump<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for record in &self.records {
+            writeln!(writer, "{record}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Start recording a new instruction.
+    pub(crate) fn start(
+        &mut self,
+        pc: u16,
+        opcode: u8,
+        interrupt: Option<Interrupt>,
+        regs: &Registers,
+        variant: Variant,
+    ) {
+        if self.depth == 0 {
+            return;
+        }
+
+        if self.records.len() == self.depth {
+            self.records.pop_front();
+        }
+
+        self.records.push_back(Record {
+            pc,
+            opcode,
+            interrupt,
+            regs: regs.clone(),
+            variant,
+            operands: [0; 2],
+            len: 0,
+        });
+    }
+
+    /// Record an operand byte of the current instruction.
+    pub(crate) fn operand(&mut self, data: u8) {
+        if let Some(record) = self.records.back_mut() {
+            if let Some(slot) = record.operands.get_mut(usize::from(record.len)) {
+                *slot = data;
+                record.len += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,676 @@
+//! The instruction decoding table of the CPU.
+//!
+//! Every one of the 256 possible opcodes (official or not) is decoded into an
+//! operation, which describes what the instruction does with its operand, and
+//! an addressing mode, which describes the sequence of bus cycles used to get
+//! to that operand.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/CPU_unofficial_opcodes>
+//! - <https://www.nesdev.org/6502_cpu.txt>
+//! - <https://www.masswerk.at/6502/6502_instruction_set.html>
+
+use std::fmt;
+
+/// The operations (mnemonics) of the CPU instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[rustfmt::skip]
+pub enum Op {
+    // The official operations.
+    Adc, And, Asl, Bcc, Bcs, Beq, Bit, Bmi, Bne, Bpl, Brk, Bvc, Bvs, Clc,
+    Cld, Cli, Clv, Cmp, Cpx, Cpy, Dec, Dex, Dey, Eor, Inc, Inx, Iny, Jmp,
+    Jsr, Lda, Ldx, Ldy, Lsr, Nop, Ora, Pha, Php, Pla, Plp, Rol, Ror, Rti,
+    Rts, Sbc, Sec, Sed, Sei, Sta, Stx, Sty, Tax, Tay, Tsx, Txa, Txs, Tya,
+
+    // The unofficial (illegal) operations.
+    Alr, Anc, Ane, Arr, Dcp, Isc, Jam, Las, Lax, Lxa, Rla, Rra, Sax, Sbx,
+    Sha, Shx, Shy, Slo, Sre, Tas,
+
+    // The operations added by the 65C02.
+    Bra, Phx, Phy, Plx, Ply, Stp, Stz, Trb, Tsb, Wai,
+
+    // The bit operations added by the 65C02, with their bit number.
+    Bbr(u8), Bbs(u8), Rmb(u8), Smb(u8),
+}
+
+/// The way an operation accesses its operand in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The operand is read from memory.
+    Read,
+    /// A value is written to memory.
+    Write,
+    /// The operand is read, modified and then written back to memory.
+    Modify,
+}
+
+impl Op {
+    /// Return the memory access kind of this operation.
+    #[must_use]
+    pub const fn kind(self) -> Kind {
+        match self {
+            Self::Sta
+            | Self::Stx
+            | Self::Sty
+            | Self::Sax
+            | Self::Sha
+            | Self::Shx
+            | Self::Shy
+            | Self::Tas
+            | Self::Stz => Kind::Write,
+            Self::Asl
+            | Self::Lsr
+            | Self::Rol
+            | Self::Ror
+            | Self::Inc
+            | Self::Dec
+            | Self::Slo
+            | Self::Rla
+            | Self::Sre
+            | Self::Rra
+            | Self::Dcp
+            | Self::Isc
+            | Self::Trb
+            | Self::Tsb
+            | Self::Rmb(_)
+            | Self::Smb(_) => Kind::Modify,
+            _ => Kind::Read,
+        }
+    }
+
+    /// Check if this operation is one of the unstable "high byte" stores.
+    ///
+    /// These operations `AND` the value they store with the high byte of the
+    /// base address plus one, and when indexing crosses a page boundary, the
+    /// stored value also replaces the high byte of the effective address.
+    #[must_use]
+    pub fn is_unstable_store(self) -> bool {
+        matches!(self, Self::Sha | Self::Shx | Self::Shy | Self::Tas)
+    }
+
+    /// Return the (uppercase) mnemonic of this operation.
+    #[must_use]
+    pub const fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Adc => "ADC",
+            Self::And => "AND",
+            Self::Asl => "ASL",
+            Self::Bcc => "BCC",
+            Self::Bcs => "BCS",
+            Self::Beq => "BEQ",
+            Self::Bit => "BIT",
+            Self::Bmi => "BMI",
+            Self::Bne => "BNE",
+            Self::Bpl => "BPL",
+            Self::Brk => "BRK",
+            Self::Bvc => "BVC",
+            Self::Bvs => "BVS",
+            Self::Clc => "CLC",
+            Self::Cld => "CLD",
+            Self::Cli => "CLI",
+            Self::Clv => "CLV",
+            Self::Cmp => "CMP",
+            Self::Cpx => "CPX",
+            Self::Cpy => "CPY",
+            Self::Dec => "DEC",
+            Self::Dex => "DEX",
+            Self::Dey => "DEY",
+            Self::Eor => "EOR",
+            Self::Inc => "INC",
+            Self::Inx => "INX",
+            Self::Iny => "INY",
+            Self::Jmp => "JMP",
+            Self::Jsr => "JSR",
+            Self::Lda => "LDA",
+            Self::Ldx => "LDX",
+            Self::Ldy => "LDY",
+            Self::Lsr => "LSR",
+            Self::Nop => "NOP",
+            Self::Ora => "ORA",
+            Self::Pha => "PHA",
+            Self::Php => "PHP",
+            Self::Pla => "PLA",
+            Self::Plp => "PLP",
+            Self::Rol => "ROL",
+            Self::Ror => "ROR",
+            Self::Rti => "RTI",
+            Self::Rts => "RTS",
+            Self::Sbc => "SBC",
+            Self::Sec => "SEC",
+            Self::Sed => "SED",
+            Self::Sei => "SEI",
+            Self::Sta => "STA",
+            Self::Stx => "STX",
+            Self::Sty => "STY",
+            Self::Tax => "TAX",
+            Self::Tay => "TAY",
+            Self::Tsx => "TSX",
+            Self::Txa => "TXA",
+            Self::Txs => "TXS",
+            Self::Tya => "TYA",
+            Self::Alr => "ALR",
+            Self::Anc => "ANC",
+            Self::Ane => "ANE",
+            Self::Arr => "ARR",
+            Self::Dcp => "DCP",
+            Self::Isc => "ISC",
+            Self::Jam => "JAM",
+            Self::Las => "LAS",
+            Self::Lax => "LAX",
+            Self::Lxa => "LXA",
+            Self::Rla => "RLA",
+            Self::Rra => "RRA",
+            Self::Sax => "SAX",
+            Self::Sbx => "SBX",
+            Self::Sha => "SHA",
+            Self::Shx => "SHX",
+            Self::Shy => "SHY",
+            Self::Slo => "SLO",
+            Self::Sre => "SRE",
+            Self::Tas => "TAS",
+            Self::Bra => "BRA",
+            Self::Phx => "PHX",
+            Self::Phy => "PHY",
+            Self::Plx => "PLX",
+            Self::Ply => "PLY",
+            Self::Stp => "STP",
+            Self::Stz => "STZ",
+            Self::Trb => "TRB",
+            Self::Tsb => "TSB",
+            Self::Wai => "WAI",
+            Self::Bbr(bit) => [
+                "BBR0", "BBR1", "BBR2", "BBR3", "BBR4", "BBR5", "BBR6", "BBR7",
+            ][bit as usize & 7],
+            Self::Bbs(bit) => [
+                "BBS0", "BBS1", "BBS2", "BBS3", "BBS4", "BBS5", "BBS6", "BBS7",
+            ][bit as usize & 7],
+            Self::Rmb(bit) => [
+                "RMB0", "RMB1", "RMB2", "RMB3", "RMB4", "RMB5", "RMB6", "RMB7",
+            ][bit as usize & 7],
+            Self::Smb(bit) => [
+                "SMB0", "SMB1", "SMB2", "SMB3", "SMB4", "SMB5", "SMB6", "SMB7",
+            ][bit as usize & 7],
+        }
+    }
+
+    /// Check if this operation is one of the unofficial (illegal) operations.
+    #[must_use]
+    pub const fn is_unofficial(self) -> bool {
+        matches!(
+            self,
+            Self::Alr
+                | Self::Anc
+                | Self::Ane
+                | Self::Arr
+                | Self::Dcp
+                | Self::Isc
+                | Self::Jam
+                | Self::Las
+                | Self::Lax
+                | Self::Lxa
+                | Self::Rla
+                | Self::Rra
+                | Self::Sax
+                | Self::Sbx
+                | Self::Sha
+                | Self::Shx
+                | Self::Shy
+                | Self::Slo
+                | Self::Sre
+                | Self::Tas
+        )
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.mnemonic())
+    }
+}
+
+/// The addressing modes (bus cycle sequences) of the CPU instructions.
+///
+/// Apart from the standard 6502 addressing modes, instructions with a unique
+/// cycle sequence (like `BRK` or `JSR`) are given their own mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Implied or accumulator addressing, `OPC` or `OPC A`.
+    Imp,
+    /// Immediate addressing, `OPC #$BB`.
+    Imm,
+    /// Zero-page addressing, `OPC $LL`.
+    Zpg,
+    /// Zero-page addressing indexed by `X`, `OPC $LL,X`.
+    Zpx,
+    /// Zero-page addressing indexed by `Y`, `OPC $LL,Y`.
+    Zpy,
+    /// Absolute addressing, `OPC $LLHH`.
+    Abs,
+    /// Absolute addressing indexed by `X`, `OPC $LLHH,X`.
+    Abx,
+    /// Absolute addressing indexed by `Y`, `OPC $LLHH,Y`.
+    Aby,
+    /// Indexed indirect addressing, `OPC ($LL,X)`.
+    Izx,
+    /// Indirect indexed addressing, `OPC ($LL),Y`.
+    Izy,
+    /// Relative addressing, `OPC $BB`, used by branches.
+    Rel,
+    /// The absolute jump sequence, `JMP $LLHH`.
+    Jmp,
+    /// The indirect jump sequence, `JMP ($LLHH)`.
+    Ind,
+    /// The subroutine call sequence, `JSR $LLHH`.
+    Jsr,
+    /// The subroutine return sequence, `RTS`.
+    Rts,
+    /// The interrupt return sequence, `RTI`.
+    Rti,
+    /// The interrupt sequence, `BRK` (and hardware interrupts).
+    Brk,
+    /// The stack push sequence, `PHA` and `PHP`.
+    Psh,
+    /// The stack pull sequence, `PLA` and `PLP`.
+    Pul,
+    /// The sequence that halts the CPU, `JAM`.
+    Jam,
+
+    /// Zero-page indirect addressing, `OPC ($LL)`, added by the 65C02.
+    Izp,
+    /// The indexed indirect jump sequence, `JMP ($LLHH,X)`, added by the 65C02.
+    Iax,
+    /// Zero-page relative addressing, `OPC $LL,$BB`, used by the 65C02's bit
+    /// branches.
+    Zrl,
+    /// The single-cycle sequence of the 65C02's unused opcodes.
+    Sgl,
+    /// The long eight-cycle sequence of the 65C02's unused opcode `0x5c`.
+    Lng,
+    /// The sequence that waits for an interrupt, `WAI`.
+    Wai,
+    /// The sequence that stops the CPU, `STP`.
+    Stp,
+}
+
+impl Mode {
+    /// Return the number of operand bytes that follow the opcode.
+    #[must_use]
+    pub const fn operand_size(self) -> u8 {
+        match self {
+            Self::Imp
+            | Self::Rts
+            | Self::Rti
+            | Self::Brk
+            | Self::Psh
+            | Self::Pul
+            | Self::Jam
+            | Self::Sgl
+            | Self::Wai
+            | Self::Stp => 0,
+            Self::Imm
+            | Self::Zpg
+            | Self::Zpx
+            | Self::Zpy
+            | Self::Izx
+            | Self::Izy
+            | Self::Rel
+            | Self::Izp => 1,
+            Self::Abs
+            | Self::Abx
+            | Self::Aby
+            | Self::Jmp
+            | Self::Ind
+            | Self::Jsr
+            | Self::Iax
+            | Self::Zrl
+            | Self::Lng => 2,
+        }
+    }
+}
+
+/// A decoded CPU instruction.
+#[derive(Debug, Clone, Copy)]
+pub struct Instr {
+    /// The operation of the instruction.
+    pub op: Op,
+    /// The addressing mode of the instruction.
+    pub mode: Mode,
+}
+
+/// Build the instruction table from a list of `operation mode` pairs.
+macro_rules! table {
+    ($($op:ident $(($bit:literal))? $mode:ident),* $(,)?) => {
+        [$(Instr { op: Op::$op $(($bit))?, mode: Mode::$mode }),*]
+    };
+}
+
+/// The decoding table of the NMOS 6502 for all 256 opcodes, indexed by the
+/// opcode value.
+#[rustfmt::skip]
+pub const NMOS_INSTRS: [Instr; 256] = table![
+    // 0x00-0x0f
+    Brk Brk, Ora Izx, Jam Jam, Slo Izx, Nop Zpg, Ora Zpg, Asl Zpg, Slo Zpg,
+    Php Psh, Ora Imm, Asl Imp, Anc Imm, Nop Abs, Ora Abs, Asl Abs, Slo Abs,
+    // 0x10-0x1f
+    Bpl Rel, Ora Izy, Jam Jam, Slo Izy, Nop Zpx, Ora Zpx, Asl Zpx, Slo Zpx,
+    Clc Imp, Ora Aby, Nop Imp, Slo Aby, Nop Abx, Ora Abx, Asl Abx, Slo Abx,
+    // 0x20-0x2f
+    Jsr Jsr, And Izx, Jam Jam, Rla Izx, Bit Zpg, And Zpg, Rol Zpg, Rla Zpg,
+    Plp Pul, And Imm, Rol Imp, Anc Imm, Bit Abs, And Abs, Rol Abs, Rla Abs,
+    // 0x30-0x3f
+    Bmi Rel, And Izy, Jam Jam, Rla Izy, Nop Zpx, And Zpx, Rol Zpx, Rla Zpx,
+    Sec Imp, And Aby, Nop Imp, Rla Aby, Nop Abx, And Abx, Rol Abx, Rla Abx,
+    // 0x40-0x4f
+    Rti Rti, Eor Izx, Jam Jam, Sre Izx, Nop Zpg, Eor Zpg, Lsr Zpg, Sre Zpg,
+    Pha Psh, Eor Imm, Lsr Imp, Alr Imm, Jmp Jmp, Eor Abs, Lsr Abs, Sre Abs,
+    // 0x50-0x5f
+    Bvc Rel, Eor Izy, Jam Jam, Sre Izy, Nop Zpx, Eor Zpx, Lsr Zpx, Sre Zpx,
+    Cli Imp, Eor Aby, Nop Imp, Sre Aby, Nop Abx, Eor Abx, Lsr Abx, Sre Abx,
+    // 0x60-0x6f
+    Rts Rts, Adc Izx, Jam Jam, Rra Izx, Nop Zpg, Adc Zpg, Ror Zpg, Rra Zpg,
+    Pla Pul, Adc Imm, Ror Imp, Arr Imm, Jmp Ind, Adc Abs, Ror Abs, Rra Abs,
+    // 0x70-0x7f
+    Bvs Rel, Adc Izy, Jam Jam, Rra Izy, Nop Zpx, Adc Zpx, Ror Zpx, Rra Zpx,
+    Sei Imp, Adc Aby, Nop Imp, Rra Aby, Nop Abx, Adc Abx, Ror Abx, Rra Abx,
+    // 0x80-0x8f
+    Nop Imm, Sta Izx, Nop Imm, Sax Izx, Sty Zpg, Sta Zpg, Stx Zpg, Sax Zpg,
+    Dey Imp, Nop Imm, Txa Imp, Ane Imm, Sty Abs, Sta Abs, Stx Abs, Sax Abs,
+    // 0x90-0x9f
+    Bcc Rel, Sta Izy, Jam Jam, Sha Izy, Sty Zpx, Sta Zpx, Stx Zpy, Sax Zpy,
+    Tya Imp, Sta Aby, Txs Imp, Tas Aby, Shy Abx, Sta Abx, Shx Aby, Sha Aby,
+    // 0xa0-0xaf
+    Ldy Imm, Lda Izx, Ldx Imm, Lax Izx, Ldy Zpg, Lda Zpg, Ldx Zpg, Lax Zpg,
+    Tay Imp, Lda Imm, Tax Imp, Lxa Imm, Ldy Abs, Lda Abs, Ldx Abs, Lax Abs,
+    // 0xb0-0xbf
+    Bcs Rel, Lda Izy, Jam Jam, Lax Izy, Ldy Zpx, Lda Zpx, Ldx Zpy, Lax Zpy,
+    Clv Imp, Lda Aby, Tsx Imp, Las Aby, Ldy Abx, Lda Abx, Ldx Aby, Lax Aby,
+    // 0xc0-0xcf
+    Cpy Imm, Cmp Izx, Nop Imm, Dcp Izx, Cpy Zpg, Cmp Zpg, Dec Zpg, Dcp Zpg,
+    Iny Imp, Cmp Imm, Dex Imp, Sbx Imm, Cpy Abs, Cmp Abs, Dec Abs, Dcp Abs,
+    // 0xd0-0xdf
+    Bne Rel, Cmp Izy, Jam Jam, Dcp Izy, Nop Zpx, Cmp Zpx, Dec Zpx, Dcp Zpx,
+    Cld Imp, Cmp Aby, Nop Imp, Dcp Aby, Nop Abx, Cmp Abx, Dec Abx, Dcp Abx,
+    // 0xe0-0xef
+    Cpx Imm, Sbc Izx, Nop Imm, Isc Izx, Cpx Zpg, Sbc Zpg, Inc Zpg, Isc Zpg,
+    Inx Imp, Sbc Imm, Nop Imp, Sbc Imm, Cpx Abs, Sbc Abs, Inc Abs, Isc Abs,
+    // 0xf0-0xff
+    Beq Rel, Sbc Izy, Jam Jam, Isc Izy, Nop Zpx, Sbc Zpx, Inc Zpx, Isc Zpx,
+    Sed Imp, Sbc Aby, Nop Imp, Isc Aby, Nop Abx, Sbc Abx, Inc Abx, Isc Abx,
+];
+
+/// The decoding table of the 65C02 for all 256 opcodes, indexed by the
+/// opcode value.
+///
+/// All of the unofficial opcodes of the NMOS 6502 are either replaced with new
+/// instructions or turned into `NOP`s of various lengths.
+///
+/// # Link(s)
+///
+/// - <https://www.westerndesigncenter.com/wdc/documentation/w65c02s.pdf>
+/// - <http://www.6502.org/tutorials/65c02opcodes.html>
+#[rustfmt::skip]
+pub const CMOS_INSTRS: [Instr; 256] = table![
+    // 0x00-0x0f
+    Brk Brk, Ora Izx, Nop Imm, Nop Sgl, Tsb Zpg, Ora Zpg, Asl Zpg, Rmb(0) Zpg,
+    Php Psh, Ora Imm, Asl Imp, Nop Sgl, Tsb Abs, Ora Abs, Asl Abs, Bbr(0) Zrl,
+    // 0x10-0x1f
+    Bpl Rel, Ora Izy, Ora Izp, Nop Sgl, Trb Zpg, Ora Zpx, Asl Zpx, Rmb(1) Zpg,
+    Clc Imp, Ora Aby, Inc Imp, Nop Sgl, Trb Abs, Ora Abx, Asl Abx, Bbr(1) Zrl,
+    // 0x20-0x2f
+    Jsr Jsr, And Izx, Nop Imm, Nop Sgl, Bit Zpg, And Zpg, Rol Zpg, Rmb(2) Zpg,
+    Plp Pul, And Imm, Rol Imp, Nop Sgl, Bit Abs, And Abs, Rol Abs, Bbr(2) Zrl,
+    // 0x30-0x3f
+    Bmi Rel, And Izy, And Izp, Nop Sgl, Bit Zpx, And Zpx, Rol Zpx, Rmb(3) Zpg,
+    Sec Imp, And Aby, Dec Imp, Nop Sgl, Bit Abx, And Abx, Rol Abx, Bbr(3) Zrl,
+    // 0x40-0x4f
+    Rti Rti, Eor Izx, Nop Imm, Nop Sgl, Nop Zpg, Eor Zpg, Lsr Zpg, Rmb(4) Zpg,
+    Pha Psh, Eor Imm, Lsr Imp, Nop Sgl, Jmp Jmp, Eor Abs, Lsr Abs, Bbr(4) Zrl,
+    // 0x50-0x5f
+    Bvc Rel, Eor Izy, Eor Izp, Nop Sgl, Nop Zpx, Eor Zpx, Lsr Zpx, Rmb(5) Zpg,
+    Cli Imp, Eor Aby, Phy Psh, Nop Sgl, Nop Lng, Eor Abx, Lsr Abx, Bbr(5) Zrl,
+    // 0x60-0x6f
+    Rts Rts, Adc Izx, Nop Imm, Nop Sgl, Stz Zpg, Adc Zpg, Ror Zpg, Rmb(6) Zpg,
+    Pla Pul, Adc Imm, Ror Imp, Nop Sgl, Jmp Ind, Adc Abs, Ror Abs, Bbr(6) Zrl,
+    // 0x70-0x7f
+    Bvs Rel, Adc Izy, Adc Izp, Nop Sgl, Stz Zpx, Adc Zpx, Ror Zpx, Rmb(7) Zpg,
+    Sei Imp, Adc Aby, Ply Pul, Nop Sgl, Jmp Iax, Adc Abx, Ror Abx, Bbr(7) Zrl,
+    // 0x80-0x8f
+    Bra Rel, Sta Izx, Nop Imm, Nop Sgl, Sty Zpg, Sta Zpg, Stx Zpg, Smb(0) Zpg,
+    Dey Imp, Bit Imm, Txa Imp, Nop Sgl, Sty Abs, Sta Abs, Stx Abs, Bbs(0) Zrl,
+    // 0x90-0x9f
+    Bcc Rel, Sta Izy, Sta Izp, Nop Sgl, Sty Zpx, Sta Zpx, Stx Zpy, Smb(1) Zpg,
+    Tya Imp, Sta Aby, Txs Imp, Nop Sgl, Stz Abs, Sta Abx, Stz Abx, Bbs(1) Zrl,
+    // 0xa0-0xaf
+    Ldy Imm, Lda Izx, Ldx Imm, Nop Sgl, Ldy Zpg, Lda Zpg, Ldx Zpg, Smb(2) Zpg,
+    Tay Imp, Lda Imm, Tax Imp, Nop Sgl, Ldy Abs, Lda Abs, Ldx Abs, Bbs(2) Zrl,
+    // 0xb0-0xbf
+    Bcs Rel, Lda Izy, Lda Izp, Nop Sgl, Ldy Zpx, Lda Zpx, Ldx Zpy, Smb(3) Zpg,
+    Clv Imp, Lda Aby, Tsx Imp, Nop Sgl, Ldy Abx, Lda Abx, Ldx Aby, Bbs(3) Zrl,
+    // 0xc0-0xcf
+    Cpy Imm, Cmp Izx, Nop Imm, Nop Sgl, Cpy Zpg, Cmp Zpg, Dec Zpg, Smb(4) Zpg,
+    Iny Imp, Cmp Imm, Dex Imp, Wai Wai, Cpy Abs, Cmp Abs, Dec Abs, Bbs(4) Zrl,
+    // 0xd0-0xdf
+    Bne Rel, Cmp Izy, Cmp Izp, Nop Sgl, Nop Zpx, Cmp Zpx, Dec Zpx, Smb(5) Zpg,
+    Cld Imp, Cmp Aby, Phx Psh, Stp Stp, Nop Abs, Cmp Abx, Dec Abx, Bbs(5) Zrl,
+    // 0xe0-0xef
+    Cpx Imm, Sbc Izx, Nop Imm, Nop Sgl, Cpx Zpg, Sbc Zpg, Inc Zpg, Smb(6) Zpg,
+    Inx Imp, Sbc Imm, Nop Imp, Nop Sgl, Cpx Abs, Sbc Abs, Inc Abs, Bbs(6) Zrl,
+    // 0xf0-0xff
+    Beq Rel, Sbc Izy, Sbc Izp, Nop Sgl, Nop Zpx, Sbc Zpx, Inc Zpx, Smb(7) Zpg,
+    Sed Imp, Sbc Aby, Plx Pul, Nop Sgl, Nop Abs, Sbc Abx, Inc Abx, Bbs(7) Zrl,
+];
+
+/// The standard addressing modes of the CPU instructions, as used by
+/// assemblers and disassemblers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrMode {
+    /// Implied addressing, `OPC`.
+    Implied,
+    /// Accumulator addressing, `OPC A`.
+    Accumulator,
+    /// Immediate addressing, `OPC #$BB`.
+    Immediate,
+    /// Zero-page addressing, `OPC $LL`.
+    ZeroPage,
+    /// Zero-page addressing indexed by `X`, `OPC $LL,X`.
+    ZeroPageX,
+    /// Zero-page addressing indexed by `Y`, `OPC $LL,Y`.
+    ZeroPageY,
+    /// Absolute addressing, `OPC $LLHH`.
+    Absolute,
+    /// Absolute addressing indexed by `X`, `OPC $LLHH,X`.
+    AbsoluteX,
+    /// Absolute addressing indexed by `Y`, `OPC $LLHH,Y`.
+    AbsoluteY,
+    /// Indirect addressing, `OPC ($LLHH)`.
+    Indirect,
+    /// Indexed indirect addressing, `OPC ($LL,X)`.
+    IndexedIndirect,
+    /// Indirect indexed addressing, `OPC ($LL),Y`.
+    IndirectIndexed,
+    /// Relative addressing, `OPC $BB`.
+    Relative,
+    /// Zero-page indirect addressing, `OPC ($LL)`, added by the 65C02.
+    ZeroPageIndirect,
+    /// Absolute indexed indirect addressing, `OPC ($LLHH,X)`, added by the
+    /// 65C02.
+    AbsoluteIndexedIndirect,
+    /// Zero-page relative addressing, `OPC $LL,$BB`, added by the 65C02.
+    ZeroPageRelative,
+}
+
+/// The metadata of an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpInfo {
+    /// The (uppercase) mnemonic of the instruction.
+    pub mnemonic: &'static str,
+    /// The addressing mode of the instruction.
+    pub mode: AddrMode,
+    /// The base number of cycles taken by the instruction.
+    ///
+    /// This is zero for the `JAM` instructions, which never finish.
+    pub cycles: u8,
+    /// A flag denoting if the instruction takes an extra cycle when indexing
+    /// crosses a page boundary.
+    ///
+    /// For branches, this denotes that a taken branch takes an extra cycle,
+    /// plus another extra cycle when the target is on a different page.
+    pub page_penalty: bool,
+    /// A flag denoting if the instruction is an official (documented) one.
+    pub official: bool,
+    /// The length of the instruction in bytes, including the opcode.
+    pub len: u8,
+}
+
+/// The metadata of the NMOS 6502 for all 256 opcodes, indexed by the opcode
+/// value.
+pub const OPCODES: [OpInfo; 256] = opcodes(&NMOS_INSTRS, false);
+
+/// The metadata of the 65C02 for all 256 opcodes, indexed by the opcode value.
+///
+/// The base number of cycles does not include the extra cycle taken by `ADC`
+/// and `SBC` in decimal mode.
+pub const CMOS_OPCODES: [OpInfo; 256] = opcodes(&CMOS_INSTRS, true);
+
+/// Build the opcode metadata table from an instruction decoding table.
+const fn opcodes(instrs: &[Instr; 256], cmos: bool) -> [OpInfo; 256] {
+    let mut table = [OpInfo {
+        mnemonic: "",
+        mode: AddrMode::Implied,
+        cycles: 0,
+        page_penalty: false,
+        official: false,
+        len: 0,
+    }; 256];
+
+    let mut opcode = 0;
+    while opcode < 256 {
+        let Instr { op, mode } = instrs[opcode];
+
+        // The duplicate `NOP`s and `SBC` are not official, except for `0xea`.
+        let duplicate = (matches!(op, Op::Nop) && opcode != 0xea) || opcode == 0xeb;
+
+        table[opcode] = OpInfo {
+            mnemonic: op.mnemonic(),
+            mode: addr_mode(op, mode),
+            cycles: cycles(op, mode, cmos),
+            page_penalty: page_penalty(op, mode, cmos),
+            official: !duplicate && (cmos || !op.is_unofficial()),
+            len: 1 + mode.operand_size(),
+        };
+
+        opcode += 1;
+    }
+
+    table
+}
+
+/// Return the standard addressing mode of an instruction.
+const fn addr_mode(op: Op, mode: Mode) -> AddrMode {
+    match mode {
+        Mode::Imp
+            if matches!(
+                op,
+                Op::Asl | Op::Lsr | Op::Rol | Op::Ror | Op::Inc | Op::Dec
+            ) =>
+        {
+            AddrMode::Accumulator
+        }
+        Mode::Imp
+        | Mode::Rts
+        | Mode::Rti
+        | Mode::Brk
+        | Mode::Psh
+        | Mode::Pul
+        | Mode::Jam
+        | Mode::Sgl
+        | Mode::Wai
+        | Mode::Stp => AddrMode::Implied,
+        Mode::Imm => AddrMode::Immediate,
+        Mode::Zpg => AddrMode::ZeroPage,
+        Mode::Zpx => AddrMode::ZeroPageX,
+        Mode::Zpy => AddrMode::ZeroPageY,
+        Mode::Abs | Mode::Jmp | Mode::Jsr | Mode::Lng => AddrMode::Absolute,
+        Mode::Abx => AddrMode::AbsoluteX,
+        Mode::Aby => AddrMode::AbsoluteY,
+        Mode::Ind => AddrMode::Indirect,
+        Mode::Izx => AddrMode::IndexedIndirect,
+        Mode::Izy => AddrMode::IndirectIndexed,
+        Mode::Rel => AddrMode::Relative,
+        Mode::Izp => AddrMode::ZeroPageIndirect,
+        Mode::Iax => AddrMode::AbsoluteIndexedIndirect,
+        Mode::Zrl => AddrMode::ZeroPageRelative,
+    }
+}
+
+/// Check if an operation is one of the shifts and rotates, whose absolute
+/// indexed forms take a page crossing penalty on the 65C02.
+const fn is_shift(op: Op) -> bool {
+    matches!(op, Op::Asl | Op::Lsr | Op::Rol | Op::Ror)
+}
+
+/// Return the base number of cycles taken by an instruction.
+const fn cycles(op: Op, mode: Mode, cmos: bool) -> u8 {
+    let modify = matches!(op.kind(), Kind::Modify);
+
+    match mode {
+        Mode::Jam => 0,
+        Mode::Sgl => 1,
+        Mode::Imp | Mode::Imm | Mode::Rel => 2,
+        Mode::Jmp | Mode::Psh | Mode::Wai | Mode::Stp => 3,
+        Mode::Pul => 4,
+        Mode::Izp | Mode::Zrl => 5,
+        Mode::Jsr | Mode::Rts | Mode::Rti | Mode::Iax => 6,
+        Mode::Brk => 7,
+        Mode::Lng => 8,
+        Mode::Ind => {
+            if cmos {
+                6
+            } else {
+                5
+            }
+        }
+        Mode::Zpg => {
+            if modify {
+                5
+            } else {
+                3
+            }
+        }
+        Mode::Zpx | Mode::Zpy | Mode::Abs => {
+            if modify {
+                6
+            } else {
+                4
+            }
+        }
+        Mode::Izx => {
+            if modify {
+                8
+            } else {
+                6
+            }
+        }
+        Mode::Abx | Mode::Aby => match op.kind() {
+            Kind::Read => 4,
+            Kind::Write => 5,
+            Kind::Modify if cmos && is_shift(op) => 6,
+            Kind::Modify => 7,
+        },
+        Mode::Izy => match op.kind() {
+            Kind::Read => 5,
+            Kind::Write => 6,
+            Kind::Modify => 8,
+        },
+    }
+}
+
+/// Check if an instruction takes a page crossing penalty.
+const fn page_penalty(op: Op, mode: Mode, cmos: bool) -> bool {
+    match (mode, op.kind()) {
+        (Mode::Abx | Mode::Aby | Mode::Izy, Kind::Read) | (Mode::Rel | Mode::Zrl, _) => true,
+        (Mode::Abx, Kind::Modify) => cmos && is_shift(op),
+        _ => false,
+    }
+}
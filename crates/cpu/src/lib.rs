@@ -14,11 +14,61 @@
 //! simpler, takes less code to implement, and overall makes the whole system
 //! easier to reason about and test.
 //!
+//! The `chuck-machine` crate is the smallest example of such a transplant:
+//! the CPU wired to 64 KiB of RAM, a hundred lines which run the functional
+//! tests of Klaus Dormann.
+//!
+//! The hardware differences between the NES's 2A03 and the 6502s of other
+//! systems are selected by the [`Model`] type parameter of the CPU, so they
+//! cost nothing at runtime, see the [`model`] module.
+//!
+//! # Feature(s)
+//!
+//! - `asm`: Enable the [`asm!`] macro and the tiny assembler behind it, which
+//!   is useful for writing CPU tests without raw machine code.
+//! - `coverage`: Enable the tracking of the executed opcodes and fetched
+//!   addresses of the CPU, see [`Cpu::coverage`].
+//! - `debug`: Enable the breakpoint and watchpoint hooks of the [`debug`]
+//!   module, which are checked by [`Cpu::debug_step`].
+//! - `history`: Enable the ring buffer of the [`history`] module, which keeps
+//!   the last executed instructions of the CPU for debugging.
+//! - `serde`: Implement `Serialize` and `Deserialize` for the complete state of
+//!   the CPU, including any mid-instruction state, for use in save states.
+//! - `wasm`: Enable the JavaScript bindings of the [`wasm`] module, for using
+//!   the CPU from WebAssembly.
+//!
 //! # Link(s)
 //!
 //! - <https://www.nesdev.org/wiki/CPU>
 //! - <https://www.nesdev.org/6502_cpu.txt>
 
+#[cfg(feature = "asm")]
+pub mod asm;
+mod builder;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+#[cfg(feature = "debug")]
+pub mod debug;
+pub mod disasm;
+mod exec;
+#[cfg(feature = "history")]
+pub mod history;
+mod instr;
+pub mod model;
+mod power;
+mod snapshot;
+pub mod stream;
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use builder::CpuBuilder;
+pub use instr::{AddrMode, OpInfo, CMOS_OPCODES, OPCODES};
+pub use model::{Model, Nmos6502, Ricoh2A03, Wdc65C02};
+pub use power::PowerUpState;
+
+use std::marker::PhantomData;
+
 bitflags::bitflags! {
     /// The status flags of a 6502 CPU.
     ///
@@ -41,6 +91,7 @@ bitflags::bitflags! {
     ///
     /// - <https://www.nesdev.org/wiki/Status_flags>
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Flags: u8 {
         /// The carry flag, `C`.
         const C = 1 << 0;
@@ -67,6 +118,7 @@ bitflags::bitflags! {
     /// - <https://www.nesdev.org/wiki/CPU_pinout>
     /// - <http://user.xmission.com/~trevin/atari/6502_pinout.html>
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Pins: u8 {
         /// The synchronize-output pin, `SYNC`.
         ///
@@ -92,6 +144,19 @@ bitflags::bitflags! {
         /// This is the pin used internally to implement Direct Memory Access
         /// (DMA) on the NES.
         const RDY = 1 << 3;
+        /// The reset pin, `RES`.
+        ///
+        /// This pin can be set externally to reset the CPU. While set, the
+        /// CPU is held in reset, and once disabled again, the CPU executes the
+        /// 7-cycle reset sequence, see [`Cpu::reset`].
+        const RES = 1 << 4;
+        /// The set-overflow pin, `SO`.
+        ///
+        /// This pin can be set externally to set the `V` flag, which happens
+        /// once every time the pin goes from disabled to set. Some peripherals,
+        /// like the Apple II's Disk II controller, use this for fast polling
+        /// with the `BVC` instruction.
+        const SO = 1 << 5;
     }
 }
 
@@ -102,6 +167,7 @@ bitflags::bitflags! {
 /// - <https://www.nesdev.org/wiki/CPU_pinout>
 /// - <http://user.xmission.com/~trevin/atari/6502_pinout.html>
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bus {
     /// The 16-bit address bus.
     ///
@@ -115,6 +181,123 @@ pub struct Bus {
     ///
     /// This corresponds to the pin labeled `R/W` on a 6502.
     pub write: bool,
+    /// The reason of the memory access.
+    ///
+    /// This has no corresponding pin on a 6502 (except `SYNC` for opcode
+    /// fetches), but it is useful to emulate the side effects of accesses to
+    /// memory-mapped registers and for debuggers.
+    pub kind: Cycle,
+}
+
+/// The reasons for a memory access of a 6502 CPU.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/6502_cpu.txt>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cycle {
+    /// The fetch of the opcode of an instruction.
+    Opcode,
+    /// The fetch of an operand byte of an instruction.
+    Operand,
+    /// A read whose data is discarded by the CPU.
+    ///
+    /// This includes the read of the partially calculated address of indexed
+    /// instructions, which is repeated once the address has been fixed up.
+    DummyRead,
+    /// A write of the unmodified data by a read-modify-write instruction.
+    DummyWrite,
+    /// A push of a byte onto the stack.
+    Push,
+    /// A pull of a byte from the stack.
+    Pull,
+    /// The fetch of a byte of an interrupt vector.
+    Vector,
+    /// A read of data (or of a pointer) by an instruction.
+    Read,
+    /// A write of data by an instruction.
+    Write,
+}
+
+/// A memory that is accessed by the CPU through [`Cpu::tick`].
+///
+/// This is an ergonomic alternative to servicing the [`Bus`] by hand after
+/// every [`Cpu::step`], which remains the primary interface of the CPU.
+pub trait Memory {
+    /// Read the byte at the given address.
+    fn read(&mut self, addr: u16) -> u8;
+
+    /// Write the given byte to the given address.
+    fn write(&mut self, addr: u16, data: u8);
+}
+
+impl Memory for [u8; 0x10000] {
+    fn read(&mut self, addr: u16) -> u8 {
+        self[usize::from(addr)]
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self[usize::from(addr)] = data;
+    }
+}
+
+/// The instruction sets of the 6502 that the CPU can emulate, see
+/// [`Model::VARIANT`].
+///
+/// # Link(s)
+///
+/// - <https://www.westerndesigncenter.com/wdc/documentation/w65c02s.pdf>
+/// - <http://www.6502.org/tutorials/65c02opcodes.html>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// The original NMOS 6502, as found inside the NES's 2A03.
+    Nmos,
+    /// The CMOS WDC 65C02S, as found inside the later Apple II models.
+    ///
+    /// This variant adds new instructions and addressing modes, turns all of
+    /// the unofficial opcodes into `NOP`s, fixes the `JMP ($xxFF)` bug and
+    /// changes the cycle timing of some instructions.
+    Cmos,
+}
+
+/// The cause of a jammed CPU, see [`Cpu::jammed_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jam {
+    /// The opcode that jammed the CPU.
+    ///
+    /// This is one of the unofficial `JAM` (also known as `KIL`) opcodes on
+    /// the 6502, or the `STP` opcode on the 65C02.
+    pub opcode: u8,
+    /// The address of the opcode.
+    pub addr: u16,
+}
+
+/// The progress of the CPU through an instruction, see
+/// [`Cpu::instruction_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of the cycle of the bus access, where `0` is the opcode
+    /// fetch of the instruction.
+    pub cycle: u8,
+    /// The opcode of the instruction.
+    ///
+    /// This is `0x00` (`BRK`) while an interrupt or reset is being serviced.
+    pub opcode: u8,
+    /// A flag denoting if the bus access is the opcode fetch of the next
+    /// instruction, in which case the cycle number is `0` and the opcode is
+    /// still that of the previous instruction.
+    pub fetching: bool,
+}
+
+/// The outcome of an interrupt poll, see [`Cpu::interrupt_poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poll {
+    /// A flag denoting if an `IRQ` interrupt was latched.
+    pub irq: bool,
+    /// A flag denoting if an `NMI` interrupt was latched.
+    pub nmi: bool,
 }
 
 /// The registers of a 6502 CPU.
@@ -122,7 +305,8 @@ pub struct Bus {
 /// # Link(s)
 ///
 /// - <https://www.nesdev.org/wiki/CPU_registers>
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Registers {
     /// The flags register, `P`.
     pub flags: Flags,
@@ -144,6 +328,7 @@ pub struct Registers {
 ///
 /// - <https://www.nesdev.org/wiki/Visual6502wiki/6502_State_Machine>
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[rustfmt::skip]
 #[repr(u8)]
 pub(crate) enum State {
@@ -162,14 +347,25 @@ pub(crate) enum State {
 ///
 /// - <https://www.westerndesigncenter.com/wdc/documentation/w65c02s.pdf>
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct Tcu {
     /// The current partial instruction state.
     pub(crate) state: State,
+    /// The number of cycles executed since the opcode fetch.
+    ///
+    /// This differs from the state when an instruction skips a state.
+    pub(crate) cycle: u8,
 }
 
 impl Tcu {
-    /// Goto the next partial instruction state.
+    /// Goto the next partial instruction state, spending a cycle.
     pub(crate) fn advance(&mut self) {
+        self.skip();
+        self.cycle += 1;
+    }
+
+    /// Goto the next partial instruction state, without spending a cycle.
+    pub(crate) fn skip(&mut self) {
         self.state = match self.state {
             State::T0 => State::T1,
             State::T1 => State::T2,
@@ -185,19 +381,26 @@ impl Tcu {
     /// Reset the current partial instruction state.
     pub(crate) fn reset(&mut self) {
         self.state = State::T7;
+        self.cycle = 0;
     }
 }
 
-/// A timing pipeline for controlling when interrupts are serviced.
+/// The timing pipelines for controlling when interrupts are serviced.
 ///
 /// When an external interrupt request pin, such as `IRQ` or `NMI`, is pulled
 /// active, it will be placed onto a (emulator fictional) timing pipeline. Every
-/// CPU cycle, the pipeline will shift its internal data to the left. Once the
-/// data contained in the pipeline actives any bit specified inside a given
-/// bitmask, the interrupt denoted by this pipeline is viable to be serviced.
+/// CPU cycle, the pipelines will shift their internal data to the left. Once
+/// the data contained in a pipeline actives any bit specified inside its
+/// bitmask, the interrupt denoted by that pipeline is viable to be serviced.
 ///
-/// There can be different bitmasks specified because `IRQ` interrupts vary from
-/// `NMI` interrupts in the timeline in which they can be serviced.
+/// There are different bitmasks because `IRQ` interrupts vary from `NMI`
+/// interrupts in the timeline in which they can be serviced.
+///
+/// The `IRQ` pipeline occupies the lower and the `NMI` pipeline the upper half
+/// of a single word, so that both are shifted by a single operation on every
+/// cycle. As two adjacent fields, the compiler merged their accesses into wider
+/// loads that could not be forwarded from the narrower stores before them,
+/// which stalled every cycle.
 ///
 /// # Link(s)
 ///
@@ -206,41 +409,66 @@ impl Tcu {
 /// - <https://www.nesdev.org/wiki/CPU_interrupts>
 /// - <https://www.nesdev.org/wiki/Visual6502wiki/6502_Interrupt_Recognition_Stages_and_Tolerances>
 #[derive(Debug, Clone)]
-pub(crate) struct Pipeline<const MASK: u16> {
-    data: u16,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Pipelines {
+    data: u32,
 }
 
-impl<const MASK: u16> Pipeline<MASK> {
-    /// Register an interrupt request on this pipeline if its corresponding pin
-    /// is pulled active.
-    pub(crate) fn register_with(&mut self, pin: bool) {
-        if pin {
-            self.data |= 0x100;
-        }
+impl Pipelines {
+    /// The bitmask of the `IRQ` pipeline.
+    const IRQ: u32 = 0x0000_0400;
+    /// The bitmask of the `NMI` pipeline.
+    const NMI: u32 = 0xfc00_0000;
+
+    /// Create new, empty pipelines.
+    pub(crate) const fn new() -> Self {
+        Self { data: 0 }
     }
 
-    /// Check if the interrupt denoted by this pipeline can be serviced.
+    /// Register an interrupt request on the pipelines whose corresponding pins
+    /// are pulled active.
+    pub(crate) fn register(&mut self, irq: bool, nmi: bool) {
+        self.data |= u32::from(irq) << 8 | u32::from(nmi) << 24;
+    }
+
+    /// Check if an `IRQ` interrupt can be serviced.
     #[must_use]
-    pub(crate) fn is_serviceable(&self) -> bool {
-        self.data & MASK != 0
+    pub(crate) fn is_irq_serviceable(&self) -> bool {
+        self.data & Self::IRQ != 0
     }
 
-    /// Trim this pipeline.
+    /// Check if an `NMI` interrupt can be serviced.
+    #[must_use]
+    pub(crate) fn is_nmi_serviceable(&self) -> bool {
+        self.data & Self::NMI != 0
+    }
+
+    /// Trim both pipelines.
     ///
     /// This prevents the CPU from accidentally re-servicing the same interrupt
     /// request.
     pub(crate) fn trim(&mut self) {
-        self.data &= 0x3ff;
+        self.data &= 0x03ff_03ff;
+    }
+
+    /// Trim the `NMI` pipeline, see [`Pipelines::trim`].
+    pub(crate) fn trim_nmi(&mut self) {
+        self.data &= 0x03ff_ffff;
     }
 
-    /// Shift the data in this pipeline.
+    /// Shift the data in both pipelines.
     pub(crate) fn shift(&mut self) {
-        self.data <<= 1;
+        self.data = self.data << 1 & 0xfffe_fffe;
     }
 
-    /// Undo a pipeline data shift.
+    /// Shift the data in the `IRQ` pipeline only.
+    pub(crate) fn shift_irq(&mut self) {
+        self.data = self.data & 0xffff_0000 | self.data << 1 & 0x0000_fffe;
+    }
+
+    /// Undo a data shift of both pipelines.
     pub(crate) fn undo(&mut self) {
-        self.data >>= 1;
+        self.data = self.data >> 1 & 0x7fff_7fff;
     }
 }
 
@@ -249,8 +477,9 @@ impl<const MASK: u16> Pipeline<MASK> {
 /// # Link(s)
 ///
 /// - <https://www.nesdev.org/wiki/CPU_interrupts>
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum Interrupt {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interrupt {
     /// A software requested break interrupt.
     Brk,
     /// An externally requested maskable interrupt.
@@ -264,9 +493,44 @@ pub(crate) enum Interrupt {
 /// The opcode value of the `BRK` instruction.
 const BRK: u8 = 0x00;
 
+/// The default value of the "magic" constant used by unstable opcodes.
+///
+/// This is the value expected by the `SingleStepTests` `ProcessorTests`.
+const MAGIC: u8 = 0xee;
+
 /// The 6502-based Central Processing Unit (CPU) of the NES.
+///
+/// The emulated hardware is selected by the [`Model`], which defaults to the
+/// NES's [`Ricoh2A03`], see [`Cpu::with_model`] for the others.
+///
+/// # Stepping
+///
+/// The CPU is driven one cycle at a time through [`Cpu::step`]. Every step
+/// places a memory access onto the [`Bus`], which must then be serviced by
+/// the caller before the next step. For a write, the byte on the data bus is
+/// stored at the bus address; for a read, the byte at the bus address is
+/// placed onto the data bus.
+///
+/// ```
+/// # use chuck_cpu::Cpu;
+/// let mut cpu = Cpu::new();
+/// let mut ram = [0; 0x10000];
+///
+/// for _ in 0..7 {
+///     cpu.step();
+///
+///     if cpu.bus.write {
+///         ram[usize::from(cpu.bus.addr)] = cpu.bus.data;
+///     } else {
+///         cpu.bus.data = ram[usize::from(cpu.bus.addr)];
+///     }
+/// }
+/// ```
+///
+/// Alternatively, [`Cpu::tick`] performs both in one go for any [`Memory`].
 #[derive(Debug, Clone)]
-pub struct Cpu {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cpu<M = Ricoh2A03> {
     /// The I/O control pins.
     pub pins: Pins,
     /// The memory bus.
@@ -274,20 +538,43 @@ pub struct Cpu {
     /// The registers.
     pub regs: Registers,
 
+    /// The hardware model that is emulated.
+    pub(crate) model: PhantomData<M>,
+    /// The "magic" constant used by the unstable `ANE` and `LXA` opcodes.
+    ///
+    /// On real hardware, this value depends on the specific chip, its
+    /// temperature and even the data on the bus, so it is left configurable.
+    /// Common values are `0x00`, `0xee` (the default) and `0xff`.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/Visual6502wiki/6502_Opcode_8B_(XAA,_ANE)>
+    pub magic: u8,
+    /// The number of cycles executed since the CPU was created.
+    ///
+    /// This is incremented by every [`Cpu::step`], including the cycles where
+    /// the CPU is stalled, and can be freely changed (e.g. reset to zero).
+    pub cycles: u64,
+
     /// A flag denoting if the CPU is jammed.
     pub(crate) jammed: bool,
+    /// A flag denoting if the CPU is waiting for an interrupt (`WAI`).
+    pub(crate) waiting: bool,
 
     /// The next interrupt type that will be serviced by the CPU.
     ///
     /// This value only takes effect when a `BRK` instruction is executed, it
     /// is not polled to determine if the CPU should service an interrupt.
     pub(crate) schedule: Interrupt,
-    /// A copy of the previous `NMI` pin value used for edge detection.
-    pub(crate) nmi_edge: bool,
-    /// The timing pipeline for `IRQ` interrupts.
-    pub(crate) irq_pip: Pipeline<0x0400>,
-    /// The timing pipeline for `NMI` interrupts.
-    pub(crate) nmi_pip: Pipeline<0xfc00>,
+    /// The interrupt whose sequence was hijacked by an `NMI`, if any.
+    ///
+    /// This is cleared at the start of every interrupt sequence.
+    pub(crate) hijacked: Option<Interrupt>,
+    /// A copy of the previous `NMI` and `SO` pin values used for edge
+    /// detection.
+    pub(crate) edges: Pins,
+    /// The timing pipelines for `IRQ` and `NMI` interrupts.
+    pub(crate) pipelines: Pipelines,
 
     /// The internal Address Decoding Latch (ADL).
     ///
@@ -298,18 +585,63 @@ pub struct Cpu {
     pub(crate) opcode: u8,
     /// The Timing Control Unit (TCU).
     pub(crate) tcu: Tcu,
+
+    /// The breakpoints and watchpoints checked by [`Cpu::debug_step`].
+    ///
+    /// These are not part of the state of the CPU, so they are kept when a
+    /// snapshot is loaded and are not serialized.
+    #[cfg(feature = "debug")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub hooks: debug::Hooks,
+    /// The coverage of the execution so far.
+    ///
+    /// Much like the hooks, this is kept when a snapshot is loaded and is not
+    /// serialized.
+    #[cfg(feature = "coverage")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) coverage: coverage::Coverage,
+    /// The last executed instructions.
+    ///
+    /// Much like the hooks, this is kept when a snapshot is loaded and is not
+    /// serialized.
+    #[cfg(feature = "history")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub history: history::History,
 }
 
+/// Fails to compile unless the CPUs of all models can be moved to and shared
+/// with other threads, as the state of a CPU is all in its fields.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Cpu<Ricoh2A03>>();
+    assert_send_sync::<Cpu<Nmos6502>>();
+    assert_send_sync::<Cpu<Wdc65C02>>();
+};
+
 impl Cpu {
-    /// Create a new CPU.
+    /// Create a new CPU emulating the NES's [`Ricoh2A03`].
+    ///
+    /// All of the registers are zeroed, see [`Cpu::with_power_up`] for other
+    /// power-up states.
     #[must_use]
     pub fn new() -> Self {
+        Self::with_model(Ricoh2A03)
+    }
+}
+
+impl<M: Model> Cpu<M> {
+    /// Create a new CPU emulating the given model.
+    ///
+    /// All of the registers are zeroed, like for [`Cpu::new`].
+    #[must_use]
+    pub fn with_model(_model: M) -> Self {
         Self {
             pins: Pins::empty(),
             bus: Bus {
                 addr: 0,
                 data: 0,
                 write: false,
+                kind: Cycle::Read,
             },
             regs: Registers {
                 flags: Flags::empty(),
@@ -319,14 +651,374 @@ impl Cpu {
                 sp: 0,
                 pc: 0,
             },
+            model: PhantomData,
+            magic: MAGIC,
+            cycles: 0,
             jammed: false,
+            waiting: false,
             schedule: Interrupt::Res,
-            nmi_edge: false,
-            irq_pip: Pipeline { data: 0 },
-            nmi_pip: Pipeline { data: 0 },
+            hijacked: None,
+            edges: Pins::empty(),
+            pipelines: Pipelines::new(),
             adl: 0,
             opcode: BRK,
-            tcu: Tcu { state: State::T7 },
+            tcu: Tcu {
+                state: State::T7,
+                cycle: 0,
+            },
+            #[cfg(feature = "debug")]
+            hooks: debug::Hooks::default(),
+            #[cfg(feature = "coverage")]
+            coverage: coverage::Coverage::default(),
+            #[cfg(feature = "history")]
+            history: history::History::default(),
+        }
+    }
+
+    /// Execute a single cycle of the CPU.
+    ///
+    /// This services the previous bus access placed onto the memory bus, then
+    /// places the next one. If the `RDY` pin is set and the previous access
+    /// was a read, the CPU stalls and the read is repeated.
+    ///
+    /// After a `WAI` instruction on the 65C02, the CPU also stalls until the
+    /// `IRQ` pin is set (regardless of the `I` flag) or an `NMI` is detected.
+    ///
+    /// While the `RES` pin is set, the CPU is held in reset and reads from the
+    /// program counter, see [`Cpu::reset`].
+    pub fn step(&mut self) {
+        self.cycles = self.cycles.wrapping_add(1);
+
+        if self.pins.contains(Pins::RES) {
+            self.reset();
+            self.bus.addr = self.regs.pc;
+            self.bus.write = false;
+            self.bus.kind = Cycle::DummyRead;
+            return;
+        }
+
+        if self.jammed {
+            self.bus.addr = 0xffff;
+            self.bus.write = false;
+            self.bus.kind = Cycle::DummyRead;
+            return;
+        }
+
+        let rising = self.pins.difference(self.edges);
+        self.edges = self.pins.intersection(Pins::NMI | Pins::SO);
+
+        let irq = self.pins.contains(Pins::IRQ) && !self.regs.flags.contains(Flags::I);
+        self.pipelines.register(irq, rising.contains(Pins::NMI));
+
+        if rising.contains(Pins::SO) {
+            self.regs.flags.insert(Flags::V);
+        }
+
+        if self.pins.contains(Pins::RDY) && !self.bus.write {
+            self.pipelines.shift_irq();
+            return;
+        }
+
+        if self.waiting {
+            if !self.pins.contains(Pins::IRQ) && !self.pipelines.is_nmi_serviceable() {
+                self.pipelines.shift();
+                return;
+            }
+
+            self.waiting = false;
+        }
+
+        #[cfg(feature = "history")]
+        if self.bus.kind == Cycle::Operand {
+            self.history.operand(self.bus.data);
+        }
+
+        if self.pins.contains(Pins::SYNC) {
+            self.decode();
+        }
+
+        self.tcu.advance();
+        self.execute();
+
+        self.pipelines.shift();
+    }
+
+    /// Return the progress of the CPU through the current instruction, as of
+    /// the bus access that was placed by the last [`Cpu::step`].
+    ///
+    /// ```
+    /// # use chuck_cpu::{Cpu, Progress, OPCODES};
+    /// let mut cpu = Cpu::new();
+    /// let mut ram = [0xbd; 0x10000]; // LDA $BDBD,X
+    ///
+    /// cpu.step_instruction(|bus| bus.data = ram[usize::from(bus.addr)]);
+    /// cpu.tick(&mut ram);
+    /// cpu.tick(&mut ram);
+    ///
+    /// let progress = cpu.instruction_progress();
+    /// assert_eq!(progress, Progress { cycle: 2, opcode: 0xbd, fetching: false });
+    /// assert_eq!(OPCODES[usize::from(progress.opcode)].cycles, 4);
+    /// ```
+    #[must_use]
+    pub fn instruction_progress(&self) -> Progress {
+        Progress {
+            cycle: self.tcu.cycle,
+            opcode: self.opcode,
+            fetching: self.pins.contains(Pins::SYNC),
+        }
+    }
+
+    /// Return the outcome of the interrupt poll of the current instruction.
+    ///
+    /// The CPU polls for interrupts on the last cycle of every instruction,
+    /// the cycle before the opcode fetch of the next instruction, so this only
+    /// returns an outcome once the opcode fetch has been placed onto the bus
+    /// (with the `SYNC` pin set). If any interrupt was latched, the fetched
+    /// opcode is discarded and the interrupt is serviced instead, with `NMI`
+    /// interrupts taking priority.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/CPU_interrupts#Detailed_interrupt_behavior>
+    #[must_use]
+    pub fn interrupt_poll(&self) -> Option<Poll> {
+        self.pins.contains(Pins::SYNC).then(|| Poll {
+            irq: self.pipelines.is_irq_serviceable(),
+            nmi: self.pipelines.is_nmi_serviceable(),
+        })
+    }
+
+    /// Return the interrupt that is serviced by the current (or next) `BRK`
+    /// sequence.
+    ///
+    /// Hardware interrupts are serviced by replacing the next opcode with a
+    /// `BRK`, so this is [`Interrupt::Brk`] unless a hardware interrupt was
+    /// latched by the last interrupt poll (see [`Cpu::interrupt_poll`]) or is
+    /// being serviced.
+    #[must_use]
+    pub fn scheduled_interrupt(&self) -> Interrupt {
+        self.schedule
+    }
+
+    /// Return the interrupt whose sequence was hijacked by an `NMI`, if any.
+    ///
+    /// When an `NMI` is detected during a `BRK` or `IRQ` sequence, before the
+    /// status flags are pushed, the sequence continues with the `NMI` vector
+    /// instead. This returns the hijacked interrupt from the cycle of the
+    /// hijack until the start of the next interrupt sequence.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/CPU_interrupts#Interrupt_hijacking>
+    #[must_use]
+    pub fn hijacked(&self) -> Option<Interrupt> {
+        self.hijacked
+    }
+
+    /// Return the opcodes and addresses covered by the execution so far.
+    #[cfg(feature = "coverage")]
+    #[must_use]
+    pub fn coverage(&self) -> &coverage::Coverage {
+        &self.coverage
+    }
+
+    /// Clear the coverage of the execution so far.
+    #[cfg(feature = "coverage")]
+    pub fn clear_coverage(&mut self) {
+        self.coverage = coverage::Coverage::default();
+    }
+
+    /// Return the instruction set of the emulated model.
+    #[must_use]
+    pub const fn variant(&self) -> Variant {
+        M::VARIANT
+    }
+
+    /// Check if the CPU is jammed.
+    ///
+    /// A jammed CPU no longer executes any instructions, or services any
+    /// interrupts, and can only be recovered by a reset, see [`Cpu::reset`].
+    #[must_use]
+    pub fn is_jammed(&self) -> bool {
+        self.jammed
+    }
+
+    /// Return the cause of the jam if the CPU is jammed.
+    ///
+    /// ```
+    /// # use chuck_cpu::{Cpu, Jam};
+    /// let mut cpu = Cpu::new();
+    /// let mut ram = [0x02; 0x10000];
+    ///
+    /// while !cpu.is_jammed() {
+    ///     cpu.tick(&mut ram);
+    /// }
+    ///
+    /// assert_eq!(cpu.jammed_by(), Some(Jam { opcode: 0x02, addr: 0x0202 }));
+    ///
+    /// cpu.reset();
+    /// assert_eq!(cpu.jammed_by(), None);
+    /// ```
+    #[must_use]
+    pub fn jammed_by(&self) -> Option<Jam> {
+        self.jammed.then(|| Jam {
+            opcode: self.opcode,
+            addr: self.regs.pc.wrapping_sub(1),
+        })
+    }
+
+    /// Reset the CPU, aborting the current instruction.
+    ///
+    /// The reset sequence starts with the next [`Cpu::step`] and takes seven
+    /// cycles just like an interrupt, except that the stack writes are turned
+    /// into reads. The stack pointer is still decremented by three, the `I`
+    /// flag is set and the program counter is loaded from the reset vector at
+    /// `0xfffc`. A jammed or waiting CPU is recovered, and any pending `IRQ`
+    /// or `NMI` interrupts are discarded.
+    pub fn reset(&mut self) {
+        self.pins.remove(Pins::SYNC);
+        self.jammed = false;
+        self.waiting = false;
+        self.schedule = Interrupt::Res;
+        self.pipelines = Pipelines::new();
+        self.opcode = BRK;
+        self.tcu.reset();
+    }
+
+    /// Execute a single cycle of the CPU, servicing its bus access with the
+    /// given memory.
+    ///
+    /// ```
+    /// # use chuck_cpu::Cpu;
+    /// let mut cpu = Cpu::new();
+    /// let mut ram = [0; 0x10000];
+    ///
+    /// for _ in 0..7 {
+    ///     cpu.tick(&mut ram);
+    /// }
+    /// ```
+    #[inline]
+    pub fn tick(&mut self, mem: &mut impl Memory) {
+        self.step();
+
+        if self.bus.write {
+            mem.write(self.bus.addr, self.bus.data);
+        } else {
+            self.bus.data = mem.read(self.bus.addr);
+        }
+    }
+
+    /// Return an iterator which executes a cycle of the CPU for every item,
+    /// servicing its bus access with the given memory, and yields the record
+    /// of the cycle, see the [`stream`] module.
+    pub fn stream<'a, T: Memory>(&'a mut self, mem: &'a mut T) -> stream::Stream<'a, M, T> {
+        stream::Stream::new(self, mem)
+    }
+
+    /// Execute the CPU until the opcode of the next instruction is fetched.
+    ///
+    /// The given function is called after every cycle to service the bus
+    /// access, exactly like the caller of [`Cpu::step`] would. If the CPU is
+    /// jammed, stopped, waiting for an interrupt or held in reset, only a
+    /// single cycle is executed so that the caller can still change the pins.
+    ///
+    /// This returns the number of cycles that were executed.
+    pub fn step_instruction(&mut self, mut mem: impl FnMut(&mut Bus)) -> u32 {
+        let mut cycles = 0;
+
+        loop {
+            self.step();
+            mem(&mut self.bus);
+            cycles += 1;
+
+            if self.pins.contains(Pins::SYNC) || self.is_halted() {
+                return cycles;
+            }
+        }
+    }
+
+    /// Execute the given number of cycles of the CPU.
+    ///
+    /// The given function is called after every cycle to service the bus
+    /// access, exactly like the caller of [`Cpu::step`] would. Keeping this
+    /// loop inside the CPU allows the function to be inlined into it.
+    #[inline]
+    pub fn run_cycles(&mut self, n: u64, mut mem: impl FnMut(&mut Bus)) {
+        for _ in 0..n {
+            self.step();
+            mem(&mut self.bus);
+        }
+    }
+
+    /// Execute the CPU until any of the given pins is set.
+    ///
+    /// The given function is called after every cycle to service the bus
+    /// access, exactly like the caller of [`Cpu::step`] would. If the CPU is
+    /// jammed, stopped, waiting for an interrupt or held in reset, this returns
+    /// early so that the caller can still change the pins.
+    ///
+    /// This returns the number of cycles that were executed, which is always
+    /// at least one.
+    #[inline]
+    pub fn run_until(&mut self, pins: Pins, mut mem: impl FnMut(&mut Bus)) -> u64 {
+        let mut cycles = 0;
+
+        loop {
+            self.step();
+            mem(&mut self.bus);
+            cycles += 1;
+
+            if self.pins.intersects(pins) || self.is_halted() {
+                return cycles;
+            }
+        }
+    }
+
+    /// Check if the CPU is unable to make progress on its own.
+    fn is_halted(&self) -> bool {
+        self.jammed || self.waiting || self.pins.contains(Pins::RES)
+    }
+
+    /// Decode the opcode fetched onto the data bus, polling for interrupts.
+    ///
+    /// If an interrupt is serviceable, the opcode is replaced with a `BRK`
+    /// and the program counter is not incremented.
+    fn decode(&mut self) {
+        self.pins.remove(Pins::SYNC);
+        self.opcode = self.bus.data;
+
+        #[cfg(feature = "coverage")]
+        self.coverage.fetch(self.bus.addr);
+
+        let irq = self.pipelines.is_irq_serviceable();
+        let nmi = self.pipelines.is_nmi_serviceable();
+        self.pipelines.trim();
+
+        let interrupt = if nmi {
+            Some(Interrupt::Nmi)
+        } else if irq {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "history")]
+        self.history.start(
+            self.regs.pc,
+            interrupt.map_or(self.opcode, |_| BRK),
+            interrupt,
+            &self.regs,
+            M::VARIANT,
+        );
+
+        if let Some(interrupt) = interrupt {
+            self.schedule = interrupt;
+            self.opcode = BRK;
+        } else {
+            self.regs.pc = self.regs.pc.wrapping_add(1);
+
+            #[cfg(feature = "coverage")]
+            self.coverage.execute(self.opcode);
         }
     }
 }
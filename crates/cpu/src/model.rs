@@ -0,0 +1,67 @@
+//! The hardware models of the 6502 that the CPU can emulate.
+//!
+//! A model is selected by the type parameter of [`Cpu`](crate::Cpu), so
+//! every behavior that differs between the models is resolved at compile
+//! time. The NES's [`Ricoh2A03`] is the default model, which keeps the NES
+//! path free of any decimal mode or 65C02 checks.
+//!
+//! ```
+//! # use chuck_cpu::{Cpu, Variant, Wdc65C02};
+//! let nes = Cpu::new();
+//! let apple = Cpu::with_model(Wdc65C02);
+//!
+//! assert_eq!(nes.variant(), Variant::Nmos);
+//! assert_eq!(apple.variant(), Variant::Cmos);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/2A03>
+//! - <https://www.westerndesigncenter.com/wdc/documentation/w65c02s.pdf>
+//! - <http://www.6502.org/tutorials/65c02opcodes.html>
+
+use crate::Variant;
+
+/// A hardware model of the 6502, see
+/// [`Cpu::with_model`](crate::Cpu::with_model).
+pub trait Model {
+    /// The instruction set of the model.
+    ///
+    /// This controls the available (and unofficial) opcodes, the `JMP ($xxFF)`
+    /// bug, the cycle timing (and dummy accesses) of some instructions and
+    /// the clearing of the `D` flag by interrupt sequences.
+    const VARIANT: Variant;
+    /// Whether the decimal (BCD) mode of the `ADC` and `SBC` family of
+    /// instructions (and of the unofficial `ARR`) is available.
+    ///
+    /// Without it, the `D` flag can still be changed, but has no effect.
+    const DECIMAL: bool;
+}
+
+/// The original NMOS 6502, as found inside the Commodore 64 or the earlier
+/// Apple II models.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Nmos6502;
+
+impl Model for Nmos6502 {
+    const VARIANT: Variant = Variant::Nmos;
+    const DECIMAL: bool = true;
+}
+
+/// The Ricoh 2A03 of the NES, an NMOS 6502 without the decimal mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ricoh2A03;
+
+impl Model for Ricoh2A03 {
+    const VARIANT: Variant = Variant::Nmos;
+    const DECIMAL: bool = false;
+}
+
+/// The CMOS WDC 65C02S, as found inside the later Apple II models.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wdc65C02;
+
+impl Model for Wdc65C02 {
+    const VARIANT: Variant = Variant::Cmos;
+    const DECIMAL: bool = true;
+}
@@ -0,0 +1,89 @@
+//! The configurable power-up state of the CPU.
+//!
+//! The registers of a real 6502 hold arbitrary values at power-up, only the
+//! reset sequence (which decrements the stack pointer by three and sets the
+//! `I` flag) brings them into a partially known state. Emulators commonly use
+//! zeroed registers instead, which results in the well-known `S = $FD` and
+//! `P = $34` values once the reset sequence has been executed.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/CPU_power_up_state>
+
+use crate::{Cpu, Flags, Registers};
+
+/// The state of the registers of the CPU at power-up, see
+/// [`Cpu::with_power_up`].
+///
+/// This is the state before the reset sequence is executed by the first
+/// cycles of the CPU.
+#[derive(Debug, Clone)]
+pub enum PowerUpState {
+    /// Zeroed registers, the state used by [`Cpu::new`].
+    Canonical,
+    /// Pseudo-random registers generated from the given seed.
+    ///
+    /// The same seed always results in the same registers, which keeps the
+    /// emulation deterministic (e.g. for tool-assisted speedruns).
+    Random(u64),
+    /// The given registers.
+    Custom(Registers),
+}
+
+impl PowerUpState {
+    /// Return the registers of this power-up state.
+    #[must_use]
+    pub fn regs(&self) -> Registers {
+        match self {
+            Self::Canonical => Cpu::new().regs,
+            Self::Random(seed) => {
+                let [flags, a, x, y, sp, _, pc_low, pc_high] = splitmix64(*seed).to_le_bytes();
+
+                Registers {
+                    flags: Flags::from_bits_truncate(flags),
+                    a,
+                    x,
+                    y,
+                    sp,
+                    pc: u16::from_le_bytes([pc_low, pc_high]),
+                }
+            }
+            Self::Custom(regs) => regs.clone(),
+        }
+    }
+}
+
+/// Generate the first number of the `SplitMix64` pseudo-random sequence of
+/// the given seed.
+///
+/// # Link(s)
+///
+/// - <https://prng.di.unimi.it/splitmix64.c>
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Cpu {
+    /// Create a new CPU emulating the NES's [`Ricoh2A03`](crate::Ricoh2A03)
+    /// with the given power-up state.
+    ///
+    /// For the other models, the [`PowerUpState::regs`] can be assigned to the
+    /// registers of a CPU created by [`Cpu::with_model`].
+    ///
+    /// ```
+    /// # use chuck_cpu::{Cpu, PowerUpState};
+    /// let a = Cpu::with_power_up(&PowerUpState::Random(42));
+    /// let b = Cpu::with_power_up(&PowerUpState::Random(42));
+    /// assert_eq!(a.regs, b.regs);
+    /// ```
+    #[must_use]
+    pub fn with_power_up(state: &PowerUpState) -> Self {
+        Self {
+            regs: state.regs(),
+            ..Self::new()
+        }
+    }
+}
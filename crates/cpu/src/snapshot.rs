@@ -0,0 +1,259 @@
+//! A compact binary snapshot format for the complete state of the CPU.
+//!
+//! A snapshot consists of a header followed by a payload:
+//!
+//! | Offset | Size | Description                          |
+//! |--------|------|--------------------------------------|
+//! | 0      | 4    | The magic bytes, `CPU\x1a`.          |
+//! | 4      | 1    | The format version.                  |
+//! | 5      | 2    | The payload size (little-endian).    |
+//! | 7      | *    | The payload.                         |
+//!
+//! The payload of each version has a fixed layout of little-endian fields.
+//! Later versions may only append new fields to the end of the payload, so
+//! that older snapshots can still be loaded (any missing fields keep their
+//! default value) and newer snapshots can be loaded by older versions of the
+//! crate (any unknown fields are skipped).
+//!
+//! | Version | Size | Appended field(s)                    |
+//! |---------|------|--------------------------------------|
+//! | 1       | 26   | None, this is the initial layout.    |
+//! | 2       | 27   | The previous `SO` pin value.         |
+//! | 3       | 28   | The reason of the bus access.        |
+//! | 4       | 36   | The cycle counter.                   |
+//! | 5       | 37   | The cycle number of the instruction. |
+//! | 6       | 38   | The hijacked interrupt (plus one).   |
+
+use std::io::{self, Read, Write};
+
+use std::marker::PhantomData;
+
+use crate::{
+    Bus, Cpu, Cycle, Flags, Interrupt, Model, Pins, Pipelines, Registers, State, Tcu, Variant,
+};
+
+/// The magic bytes that start a snapshot.
+const MAGIC: [u8; 4] = *b"CPU\x1a";
+
+/// The current version of the snapshot format.
+const VERSION: u8 = 6;
+
+/// The payload size of the first version of the snapshot format.
+const V1_SIZE: u16 = 26;
+/// The payload size of the sixth (and current) version of the snapshot format.
+const V6_SIZE: u16 = 38;
+
+/// Create an error denoting a malformed snapshot.
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Decode a boolean from a byte of a snapshot.
+fn decode_bool(byte: u8) -> io::Result<bool> {
+    match byte {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(invalid("invalid boolean in cpu snapshot")),
+    }
+}
+
+/// Decode an interrupt type from a byte of a snapshot.
+fn decode_interrupt(byte: u8) -> io::Result<Interrupt> {
+    match byte {
+        0 => Ok(Interrupt::Brk),
+        1 => Ok(Interrupt::Irq),
+        2 => Ok(Interrupt::Nmi),
+        3 => Ok(Interrupt::Res),
+        _ => Err(invalid("invalid interrupt in cpu snapshot")),
+    }
+}
+
+impl<M: Model> Cpu<M> {
+    /// Save the complete state of the CPU as a binary snapshot.
+    ///
+    /// This includes any mid-instruction state, so a CPU that is restored from
+    /// the snapshot with [`Cpu::load`] continues on the exact same cycle.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let [pc_low, pc_high] = self.regs.pc.to_le_bytes();
+        let [addr_low, addr_high] = self.bus.addr.to_le_bytes();
+        let [irq_low, irq_high, nmi_low, nmi_high] = self.pipelines.data.to_le_bytes();
+        let [adl_low, adl_high] = self.adl.to_le_bytes();
+
+        let [c0, c1, c2, c3, c4, c5, c6, c7] = self.cycles.to_le_bytes();
+
+        let payload: [u8; V6_SIZE as usize] = [
+            self.pins.bits(),
+            addr_low,
+            addr_high,
+            self.bus.data,
+            u8::from(self.bus.write),
+            self.regs.flags.bits(),
+            self.regs.a,
+            self.regs.x,
+            self.regs.y,
+            self.regs.sp,
+            pc_low,
+            pc_high,
+            M::VARIANT as u8,
+            self.magic,
+            u8::from(self.jammed),
+            u8::from(self.waiting),
+            self.schedule as u8,
+            u8::from(self.edges.contains(Pins::NMI)),
+            irq_low,
+            irq_high,
+            nmi_low,
+            nmi_high,
+            adl_low,
+            adl_high,
+            self.opcode,
+            self.tcu.state as u8,
+            u8::from(self.edges.contains(Pins::SO)),
+            self.bus.kind as u8,
+            c0,
+            c1,
+            c2,
+            c3,
+            c4,
+            c5,
+            c6,
+            c7,
+            self.tcu.cycle,
+            self.hijacked.map_or(0, |interrupt| interrupt as u8 + 1),
+        ];
+
+        let [size_low, size_high] = V6_SIZE.to_le_bytes();
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, size_low, size_high])?;
+        writer.write_all(&payload)
+    }
+
+    /// Load the complete state of the CPU from a binary snapshot.
+    ///
+    /// The state of the CPU is left untouched if the snapshot is malformed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the snapshot is malformed or was
+    /// saved by a CPU with another instruction set (see [`Model::VARIANT`]).
+    pub fn load<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let mut header = [0; 7];
+        reader.read_exact(&mut header)?;
+
+        let [m0, m1, m2, m3, _, size_low, size_high] = header;
+        if [m0, m1, m2, m3] != MAGIC {
+            return Err(invalid("invalid cpu snapshot magic"));
+        }
+
+        let size = u16::from_le_bytes([size_low, size_high]);
+        if size < V1_SIZE {
+            return Err(invalid("truncated cpu snapshot payload"));
+        }
+
+        let mut payload = [0; V1_SIZE as usize];
+        reader.read_exact(&mut payload)?;
+
+        // Read the fields appended by later versions, keeping the default
+        // value of any field that is missing from the snapshot.
+        let mut appended = [0, Cycle::Read as u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let known = usize::from(size.min(V6_SIZE) - V1_SIZE);
+        reader.read_exact(&mut appended[..known])?;
+
+        // Skip any fields appended by later versions of the format.
+        let rest = u64::from(size - size.min(V6_SIZE));
+        if io::copy(&mut reader.take(rest), &mut io::sink())? != rest {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let [pins, addr_low, addr_high, data, write, flags, a, x, y, sp, pc_low, pc_high, variant, magic, jammed, waiting, schedule, nmi_edge, irq_low, irq_high, nmi_low, nmi_high, adl_low, adl_high, opcode, state] =
+            payload;
+        let [so_edge, kind, c0, c1, c2, c3, c4, c5, c6, c7, cycle, hijacked] = appended;
+
+        let variant = match variant {
+            0 => Variant::Nmos,
+            1 => Variant::Cmos,
+            _ => return Err(invalid("invalid variant in cpu snapshot")),
+        };
+
+        if variant != M::VARIANT {
+            return Err(invalid("cpu snapshot of another variant"));
+        }
+
+        let mut edges = Pins::empty();
+        edges.set(Pins::NMI, decode_bool(nmi_edge)?);
+        edges.set(Pins::SO, decode_bool(so_edge)?);
+
+        *self = Self {
+            pins: Pins::from_bits_retain(pins),
+            bus: Bus {
+                addr: u16::from_le_bytes([addr_low, addr_high]),
+                data,
+                write: decode_bool(write)?,
+                kind: match kind {
+                    0 => Cycle::Opcode,
+                    1 => Cycle::Operand,
+                    2 => Cycle::DummyRead,
+                    3 => Cycle::DummyWrite,
+                    4 => Cycle::Push,
+                    5 => Cycle::Pull,
+                    6 => Cycle::Vector,
+                    7 => Cycle::Read,
+                    8 => Cycle::Write,
+                    _ => return Err(invalid("invalid bus cycle in cpu snapshot")),
+                },
+            },
+            regs: Registers {
+                flags: Flags::from_bits_retain(flags),
+                a,
+                x,
+                y,
+                sp,
+                pc: u16::from_le_bytes([pc_low, pc_high]),
+            },
+            model: PhantomData,
+            magic,
+            cycles: u64::from_le_bytes([c0, c1, c2, c3, c4, c5, c6, c7]),
+            jammed: decode_bool(jammed)?,
+            waiting: decode_bool(waiting)?,
+            schedule: decode_interrupt(schedule)?,
+            hijacked: match hijacked {
+                0 => None,
+                n => Some(decode_interrupt(n - 1)?),
+            },
+            edges,
+            pipelines: Pipelines {
+                data: u32::from_le_bytes([irq_low, irq_high, nmi_low, nmi_high]),
+            },
+            adl: u16::from_le_bytes([adl_low, adl_high]),
+            opcode,
+            tcu: Tcu {
+                state: match state {
+                    0 => State::T0,
+                    1 => State::T1,
+                    2 => State::T2,
+                    3 => State::T3,
+                    4 => State::T4,
+                    5 => State::T5,
+                    6 => State::T6,
+                    7 => State::T7,
+                    _ => return Err(invalid("invalid state in cpu snapshot")),
+                },
+                cycle,
+            },
+            #[cfg(feature = "debug")]
+            hooks: std::mem::take(&mut self.hooks),
+            #[cfg(feature = "coverage")]
+            coverage: std::mem::take(&mut self.coverage),
+            #[cfg(feature = "history")]
+            history: std::mem::take(&mut self.history),
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! The execution of the CPU as a stream of cycles, see [`Cpu::stream`].
+//!
+//! Every item of the stream is a [`CycleRecord`] of a single cycle, taken
+//! once its bus access was serviced by the memory. So analyses of the
+//! execution are written with the combinators of iterators, instead of a
+//! loop around [`Cpu::step`]:
+//!
+//! ```
+//! # use chuck_cpu::{Cpu, Cycle};
+//! let mut cpu = Cpu::new();
+//! let mut ram = [0xea; 0x10000];
+//! // LDA #$42; STA $6000; JMP $8005
+//! ram[0x8000..0x8008].copy_from_slice(&[0xa9, 0x42, 0x8d, 0x00, 0x60, 0x4c, 0x05, 0x80]);
+//! ram[0xfffc..0xfffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let write = cpu.stream(&mut ram).find(|record| record.write).unwrap();
+//! assert_eq!((write.addr, write.data, write.kind), (0x6000, 0x42, Cycle::Write));
+//!
+//! let opcodes: Vec<_> = cpu
+//!     .stream(&mut ram)
+//!     .take(6)
+//!     .filter(|record| record.kind == Cycle::Opcode)
+//!     .map(|record| record.addr)
+//!     .collect();
+//! assert_eq!(opcodes, [0x8005, 0x8005]);
+//! ```
+
+use crate::{Cpu, Cycle, Memory, Model, Pins};
+
+/// The record of a single cycle of the CPU.
+#[derive(Debug, Clone, Copy)]
+pub struct CycleRecord {
+    /// The number of the cycle, i.e. [`Cpu::cycles`] after it.
+    pub cycle: u64,
+    /// The address on the bus.
+    pub addr: u16,
+    /// The data on the bus, i.e. the byte read from or written to the
+    /// memory.
+    pub data: u8,
+    /// A flag denoting if the access was a write.
+    pub write: bool,
+    /// The reason of the access.
+    pub kind: Cycle,
+    /// The pins of the CPU during the cycle, e.g. `SYNC` for the fetches of
+    /// opcodes, or `RDY` for the reads repeated while the CPU stalls.
+    pub pins: Pins,
+}
+
+/// An endless iterator over the cycles of a CPU, see [`Cpu::stream`].
+///
+/// The CPU keeps running as long as the stream is advanced, even once it's
+/// jammed, so the stream is usually cut short, e.g. by `take_while`.
+#[derive(Debug)]
+pub struct Stream<'a, M: Model, T> {
+    /// The CPU.
+    cpu: &'a mut Cpu<M>,
+    /// The memory, which services the accesses of the CPU.
+    mem: &'a mut T,
+}
+
+impl<'a, M: Model, T: Memory> Stream<'a, M, T> {
+    /// Create a stream of the cycles of the given CPU.
+    pub(crate) fn new(cpu: &'a mut Cpu<M>, mem: &'a mut T) -> Self {
+        Self { cpu, mem }
+    }
+
+    /// Return the CPU, e.g. to inspect its registers between two cycles.
+    #[must_use]
+    pub fn cpu(&self) -> &Cpu<M> {
+        self.cpu
+    }
+
+    /// Return the CPU mutably, e.g. to change its pins before the next cycle.
+    pub fn cpu_mut(&mut self) -> &mut Cpu<M> {
+        self.cpu
+    }
+
+    /// Return the memory mutably.
+    pub fn mem_mut(&mut self) -> &mut T {
+        self.mem
+    }
+}
+
+impl<M: Model, T: Memory> Iterator for Stream<'_, M, T> {
+    type Item = CycleRecord;
+
+    fn next(&mut self) -> Option<CycleRecord> {
+        self.cpu.tick(self.mem);
+
+        let bus = &self.cpu.bus;
+        Some(CycleRecord {
+            cycle: self.cpu.cycles,
+            addr: bus.addr,
+            data: bus.data,
+            write: bus.write,
+            kind: bus.kind,
+            pins: self.cpu.pins,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
@@ -0,0 +1,232 @@
+//! An execution trace logger in the classic `nestest` log format.
+//!
+//! On every cycle with the `SYNC` pin set, a single line is emitted for the
+//! instruction that is about to be executed, containing its address, its
+//! bytes, its disassembly (including the memory values it accesses) and the
+//! state of the registers before it is executed, e.g. for the first
+//! instruction of `nestest` (split after the disassembly, which is padded to
+//! 32 columns in the log):
+//!
+//! ```text
+//! C000  4C F5 C5  JMP $C5F5
+//!                 A:00 X:00 Y:00 P:24 SP:FD CYC:7
+//! ```
+//!
+//! This allows the execution of the CPU to be diffed against the golden logs
+//! of other emulators, such as Mesen or FCEUX. For chasing timing differences
+//! within an instruction, the [`visual6502`] module logs every half-cycle in
+//! the layout of Visual6502's trace instead.
+//!
+//! # Link(s)
+//!
+//! - <https://www.qmtpro.com/~nes/misc/nestest.log>
+//! - <https://www.nesdev.org/wiki/Emulator_tests>
+
+pub mod visual6502;
+
+use std::fmt::Write as _;
+use std::io;
+
+use crate::disasm::branch_target;
+use crate::instr::{Instr, Mode, Op, CMOS_INSTRS, NMOS_INSTRS};
+use crate::{Cpu, Model, Pins, Variant};
+
+/// A destination for the lines of an execution trace.
+pub trait Sink {
+    /// Emit a single line of the trace, without a trailing newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while emitting the line.
+    fn emit(&mut self, line: &str) -> io::Result<()>;
+}
+
+impl<F: FnMut(&str)> Sink for F {
+    fn emit(&mut self, line: &str) -> io::Result<()> {
+        self(line);
+        Ok(())
+    }
+}
+
+/// A [`Sink`] that writes every line (and a newline) to a writer.
+#[derive(Debug)]
+pub struct WriteSink<W>(pub W);
+
+impl<W: io::Write> Sink for WriteSink<W> {
+    fn emit(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.0, "{line}")
+    }
+}
+
+/// An execution trace logger.
+///
+/// The tracer must be called once after every [`Cpu::step`], it emits a line
+/// into its [`Sink`] on every cycle with the `SYNC` pin set. The cycle count
+/// of each line is taken from [`Cpu::cycles`].
+///
+/// ```
+/// # use chuck_cpu::{trace::Tracer, Cpu};
+/// let mut cpu = Cpu::new();
+/// let mut ram = [0xea; 0x10000];
+/// let mut lines = Vec::new();
+/// let mut tracer = Tracer::new(|line: &str| lines.push(line.to_owned()));
+///
+/// for _ in 0..9 {
+///     cpu.step();
+///     tracer.trace(&cpu, |addr| ram[usize::from(addr)]).unwrap();
+///
+///     if cpu.bus.write {
+///         ram[usize::from(cpu.bus.addr)] = cpu.bus.data;
+///     } else {
+///         cpu.bus.data = ram[usize::from(cpu.bus.addr)];
+///     }
+/// }
+///
+/// drop(tracer);
+/// assert_eq!(lines[0], "EAEA  EA        NOP                             A:00 X:00 Y:00 P:24 SP:FD CYC:7");
+/// ```
+#[derive(Debug)]
+pub struct Tracer<S> {
+    /// The destination of the trace lines.
+    sink: S,
+}
+
+impl<S: Sink> Tracer<S> {
+    /// Create a new tracer emitting into the given sink.
+    #[must_use]
+    pub fn new(sink: S) -> Self {
+        Self { sink }
+    }
+
+    /// Emit a line for the next instruction if the `SYNC` pin is set.
+    ///
+    /// The given function is used to read the instruction bytes and memory
+    /// values shown in the disassembly, must not have any side effects.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the sink.
+    pub fn trace<M: Model>(&mut self, cpu: &Cpu<M>, peek: impl Fn(u16) -> u8) -> io::Result<()> {
+        if cpu.pins.contains(Pins::SYNC) {
+            self.sink.emit(&line(cpu, peek))?;
+        }
+
+        Ok(())
+    }
+
+    /// Consume the tracer, returning its sink.
+    #[must_use]
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+/// Format a trace line for the instruction at the program counter.
+fn line<M: Model>(cpu: &Cpu<M>, peek: impl Fn(u16) -> u8) -> String {
+    let pc = cpu.regs.pc;
+    let opcode = peek(pc);
+    let instr = match M::VARIANT {
+        Variant::Nmos => &NMOS_INSTRS,
+        Variant::Cmos => &CMOS_INSTRS,
+    }[usize::from(opcode)];
+
+    let mut bytes = format!("{opcode:02X}");
+    for offset in 1..=u16::from(instr.mode.operand_size()) {
+        let _ = write!(bytes, " {:02X}", peek(pc.wrapping_add(offset)));
+    }
+
+    let unofficial = M::VARIANT == Variant::Nmos
+        && (instr.op.is_unofficial() || (instr.op == Op::Nop && opcode != 0xea) || opcode == 0xeb);
+    let mark = if unofficial { '*' } else { ' ' };
+    let name = match instr.op {
+        // The `nestest` logs use the alternative name of `ISC`.
+        Op::Isc => "ISB".to_owned(),
+        op => op.to_string(),
+    };
+
+    let operand = operand(cpu, instr, peek);
+    let disasm = format!("{name} {operand}");
+
+    format!(
+        "{pc:04X}  {bytes:<8} {mark}{:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        disasm.trim_end(),
+        cpu.regs.a,
+        cpu.regs.x,
+        cpu.regs.y,
+        cpu.regs.flags.bits() | 0x20,
+        cpu.regs.sp,
+        cpu.cycles,
+    )
+}
+
+/// Format the operand of an instruction, including the memory values that it
+/// accesses.
+fn operand<M: Model>(cpu: &Cpu<M>, instr: Instr, peek: impl Fn(u16) -> u8) -> String {
+    let pc = cpu.regs.pc;
+    let byte = |offset: u16| peek(pc.wrapping_add(offset));
+    let word = |addr: u16| u16::from_le_bytes([peek(addr), peek(addr.wrapping_add(1))]);
+    let zp_word =
+        |ptr: u8| u16::from_le_bytes([peek(u16::from(ptr)), peek(u16::from(ptr.wrapping_add(1)))]);
+
+    let [low, high] = [byte(1), byte(2)];
+    let abs = u16::from_le_bytes([low, high]);
+    let (x, y) = (cpu.regs.x, cpu.regs.y);
+
+    match instr.mode {
+        Mode::Imp if matches!(instr.op, Op::Asl | Op::Lsr | Op::Rol | Op::Ror) => "A".to_owned(),
+        Mode::Imm => format!("#${low:02X}"),
+        Mode::Zpg => format!("${low:02X} = {:02X}", peek(u16::from(low))),
+        Mode::Zpx => {
+            let addr = low.wrapping_add(x);
+            format!("${low:02X},X @ {addr:02X} = {:02X}", peek(u16::from(addr)))
+        }
+        Mode::Zpy => {
+            let addr = low.wrapping_add(y);
+            format!("${low:02X},Y @ {addr:02X} = {:02X}", peek(u16::from(addr)))
+        }
+        Mode::Abs | Mode::Lng => format!("${abs:04X} = {:02X}", peek(abs)),
+        Mode::Abx => {
+            let addr = abs.wrapping_add(u16::from(x));
+            format!("${abs:04X},X @ {addr:04X} = {:02X}", peek(addr))
+        }
+        Mode::Aby => {
+            let addr = abs.wrapping_add(u16::from(y));
+            format!("${abs:04X},Y @ {addr:04X} = {:02X}", peek(addr))
+        }
+        Mode::Izx => {
+            let ptr = low.wrapping_add(x);
+            let addr = zp_word(ptr);
+            format!(
+                "(${low:02X},X) @ {ptr:02X} = {addr:04X} = {:02X}",
+                peek(addr)
+            )
+        }
+        Mode::Izy => {
+            let base = zp_word(low);
+            let addr = base.wrapping_add(u16::from(y));
+            format!(
+                "(${low:02X}),Y = {base:04X} @ {addr:04X} = {:02X}",
+                peek(addr)
+            )
+        }
+        Mode::Izp => {
+            let addr = zp_word(low);
+            format!("(${low:02X}) = {addr:04X} = {:02X}", peek(addr))
+        }
+        Mode::Rel => format!("${:04X}", branch_target(pc, 2, low)),
+        Mode::Zrl => format!("${low:02X},${:04X}", branch_target(pc, 3, high)),
+        Mode::Jmp | Mode::Jsr => format!("${abs:04X}"),
+        Mode::Ind if M::VARIANT == Variant::Nmos => {
+            // The page wrapping bug of the NMOS 6502 is shown in the target.
+            let [_, page] = abs.to_le_bytes();
+            let target = u16::from_le_bytes([
+                peek(abs),
+                peek(u16::from_le_bytes([low.wrapping_add(1), page])),
+            ]);
+            format!("(${abs:04X}) = {target:04X}")
+        }
+        Mode::Ind => format!("(${abs:04X}) = {:04X}", word(abs)),
+        Mode::Iax => format!("(${abs:04X},X)"),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,180 @@
+//! A per-half-cycle trace logger in the layout of Visual6502's trace table.
+//!
+//! Visual6502 simulates the transistors of the 6502, so its trace shows the
+//! state of the chip at every half-cycle (clock phase) instead of at every
+//! instruction. This logger emits the same tab-separated columns, which lets
+//! a trace of Chuck be pasted next to one of Visual6502 for the same program
+//! to find the exact cycle where both diverge:
+//!
+//! ```text
+//! cycle  ab    db  rw  Fetch  pc    a   x   y   s   p         sync  irq  ...
+//! 12     0000  00  1   LDA    0000  00  00  00  fd  nv-BdIzc  1     1    ...
+//! 13     0000  a9  1   LDA    0000  00  00  00  fd  nv-BdIzc  1     1    ...
+//! ```
+//!
+//! The columns after `sync` are the `irq`, `nmi`, `res` and `rdy` pins, see
+//! [`HEADER`].
+//!
+//! Every cycle of the CPU is emitted as two records, one for each phase of
+//! the clock. The address and `R/W` are valid during both phases, while the
+//! data bus still holds the data of the previous cycle during the first phase
+//! (`φ1`) and is only valid during the second phase (`φ2`).
+//!
+//! The pins are shown at the level of the chip's node, i.e. the active-low
+//! `IRQ`, `NMI`, `RES` and `RDY` pins are `0` while they are asserted. The
+//! internal nodes of the chip (e.g. the `ALU` or the `T`-states) are not
+//! emulated, so they can't be shown, and the registers are the architectural
+//! registers of the CPU, which only match the internal registers of the
+//! simulation at instruction boundaries.
+//!
+//! # Link(s)
+//!
+//! - <http://www.visual6502.org/JSSim/expert.html>
+//! - <https://www.nesdev.org/wiki/Visual6502wiki/JssimUserHelp>
+
+use std::io;
+
+use crate::{Cpu, Flags, Model, Pins, Variant, CMOS_OPCODES, OPCODES};
+
+use super::Sink;
+
+/// The header of the trace, naming the columns of every record.
+pub const HEADER: &str = "cycle\tab\tdb\trw\tFetch\tpc\ta\tx\ty\ts\tp\tsync\tirq\tnmi\tres\trdy";
+
+/// A per-half-cycle trace logger.
+///
+/// Unlike the [`Tracer`](super::Tracer), this tracer must be called once after
+/// every [`Cpu::step`] *and* the servicing of its bus access, so that the data
+/// bus holds the data of the cycle. It emits the [`HEADER`] before the first
+/// record, then two records into its [`Sink`] on every cycle. The half-cycles
+/// are numbered from zero, starting with the first cycle counted by
+/// [`Cpu::cycles`].
+///
+/// ```
+/// # use chuck_cpu::{trace::visual6502::HalfCycleTracer, Cpu};
+/// let mut cpu = Cpu::new();
+/// let mut ram = [0xea; 0x10000];
+/// let mut lines = Vec::new();
+/// let mut tracer = HalfCycleTracer::new(|line: &str| lines.push(line.to_owned()));
+///
+/// for _ in 0..8 {
+///     cpu.step();
+///
+///     if cpu.bus.write {
+///         ram[usize::from(cpu.bus.addr)] = cpu.bus.data;
+///     } else {
+///         cpu.bus.data = ram[usize::from(cpu.bus.addr)];
+///     }
+///
+///     tracer.trace(&cpu).unwrap();
+/// }
+///
+/// drop(tracer);
+/// assert_eq!(lines[14], "13\teaea\tea\t1\tNOP\teaea\t00\t00\t00\tfd\tnv-BdIzc\t1\t1\t1\t1\t1");
+/// ```
+#[derive(Debug)]
+pub struct HalfCycleTracer<S> {
+    /// The destination of the trace records.
+    sink: S,
+    /// A flag denoting if the header was already emitted.
+    started: bool,
+    /// The data bus of the previous cycle, which is held during `φ1`.
+    data: u8,
+}
+
+impl<S: Sink> HalfCycleTracer<S> {
+    /// Create a new tracer emitting into the given sink.
+    #[must_use]
+    pub const fn new(sink: S) -> Self {
+        Self {
+            sink,
+            started: false,
+            data: 0,
+        }
+    }
+
+    /// Emit the two records of the current cycle.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the sink.
+    pub fn trace<M: Model>(&mut self, cpu: &Cpu<M>) -> io::Result<()> {
+        if !self.started {
+            self.sink.emit(HEADER)?;
+            self.started = true;
+        }
+
+        let half = cpu.cycles.wrapping_sub(1).wrapping_mul(2);
+        self.sink.emit(&record(cpu, half, self.data))?;
+        self.sink.emit(&record(cpu, half + 1, cpu.bus.data))?;
+        self.data = cpu.bus.data;
+
+        Ok(())
+    }
+
+    /// Consume the tracer, returning its sink.
+    #[must_use]
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+/// Format the record of a half-cycle with the given data bus.
+fn record<M: Model>(cpu: &Cpu<M>, half: u64, data: u8) -> String {
+    let sync = cpu.pins.contains(Pins::SYNC);
+    let opcodes = match M::VARIANT {
+        Variant::Nmos => &OPCODES,
+        Variant::Cmos => &CMOS_OPCODES,
+    };
+    let fetch = if sync {
+        opcodes[usize::from(cpu.bus.data)].mnemonic
+    } else {
+        ""
+    };
+
+    // The levels of the nodes of the active-low pins.
+    let level = |pin: Pins| u8::from(!cpu.pins.contains(pin));
+
+    format!(
+        "{half}\t{:04x}\t{data:02x}\t{}\t{fetch}\t{:04x}\t{:02x}\t{:02x}\t{:02x}\t{:02x}\t{}\t{}\t{}\t{}\t{}\t{}",
+        cpu.bus.addr,
+        u8::from(!cpu.bus.write),
+        cpu.regs.pc,
+        cpu.regs.a,
+        cpu.regs.x,
+        cpu.regs.y,
+        cpu.regs.sp,
+        flags(cpu.regs.flags),
+        u8::from(sync),
+        level(Pins::IRQ),
+        level(Pins::NMI),
+        level(Pins::RES),
+        level(Pins::RDY),
+    )
+}
+
+/// Format the flags like Visual6502, i.e. `nv-BdIZc`, with an uppercase
+/// letter for every set flag.
+///
+/// The `B` flag does not exist in the register, it is always shown as set.
+fn flags(flags: Flags) -> String {
+    [
+        (Flags::N, 'n'),
+        (Flags::V, 'v'),
+        (Flags::empty(), '-'),
+        (Flags::empty(), 'B'),
+        (Flags::D, 'd'),
+        (Flags::I, 'i'),
+        (Flags::Z, 'z'),
+        (Flags::C, 'c'),
+    ]
+    .into_iter()
+    .map(|(flag, name)| {
+        if !flag.is_empty() && flags.contains(flag) {
+            name.to_ascii_uppercase()
+        } else {
+            name
+        }
+    })
+    .collect()
+}
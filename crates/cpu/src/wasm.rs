@@ -0,0 +1,299 @@
+//! A JavaScript-friendly wrapper of the CPU for WebAssembly, e.g. for
+//! interactive visualizers in the browser.
+//!
+//! The wrapper is exported as the `Cpu` class, which selects the hardware
+//! model by its name and exposes the pin-level interface of the CPU, one cycle
+//! at a time. The registers, the pins and the bus are returned as plain
+//! JavaScript objects:
+//!
+//! ```js
+//! import init, { Cpu } from "./chuck_cpu.js";
+//!
+//! await init();
+//!
+//! const ram = new Uint8Array(0x10000);
+//! const cpu = new Cpu("6502");
+//!
+//! for (let i = 0; i < 7; i++) {
+//!     const bus = cpu.step();
+//!
+//!     if (bus.write) {
+//!         ram[bus.addr] = bus.data;
+//!     } else {
+//!         cpu.setData(ram[bus.addr]);
+//!     }
+//! }
+//!
+//! console.log(cpu.registers()); // { a: 0, x: 0, y: 0, sp: 253, pc: 0, p: 4 }
+//! ```
+//!
+//! The module can be built with `wasm-bindgen` (or `wasm-pack`), e.g.:
+//!
+//! ```sh
+//! cargo rustc -p chuck-cpu --release --features wasm --target wasm32-unknown-unknown --crate-type cdylib
+//! wasm-bindgen --target web --out-dir www target/wasm32-unknown-unknown/release/chuck_cpu.wasm
+//! ```
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{Bus, Cpu, Cycle, Flags, Nmos6502, Pins, Registers, Ricoh2A03, Wdc65C02};
+
+/// The CPU of the emulated model.
+#[derive(Debug, Clone)]
+enum Core {
+    Ricoh2A03(Cpu<Ricoh2A03>),
+    Nmos6502(Cpu<Nmos6502>),
+    Wdc65C02(Cpu<Wdc65C02>),
+}
+
+/// Evaluate an expression with the CPU of a [`Core`], whatever its model is.
+macro_rules! with_cpu {
+    ($core:expr, $cpu:ident => $body:expr) => {
+        match $core {
+            Core::Ricoh2A03($cpu) => $body,
+            Core::Nmos6502($cpu) => $body,
+            Core::Wdc65C02($cpu) => $body,
+        }
+    };
+}
+
+/// The names of the pins, in the order of their bits.
+const PINS: [(&str, Pins); 6] = [
+    ("sync", Pins::SYNC),
+    ("irq", Pins::IRQ),
+    ("nmi", Pins::NMI),
+    ("rdy", Pins::RDY),
+    ("res", Pins::RES),
+    ("so", Pins::SO),
+];
+
+/// Create a plain JavaScript object from the given properties.
+fn object(props: &[(&str, JsValue)]) -> Object {
+    let object = Object::new();
+
+    for (key, value) in props {
+        // Defining a property on a plain object cannot fail.
+        let _ = Reflect::set(&object, &JsValue::from_str(key), value);
+    }
+
+    object
+}
+
+/// Return the name of the reason of a bus access.
+const fn cycle_name(kind: Cycle) -> &'static str {
+    match kind {
+        Cycle::Opcode => "opcode",
+        Cycle::Operand => "operand",
+        Cycle::DummyRead => "dummy-read",
+        Cycle::DummyWrite => "dummy-write",
+        Cycle::Push => "push",
+        Cycle::Pull => "pull",
+        Cycle::Vector => "vector",
+        Cycle::Read => "read",
+        Cycle::Write => "write",
+    }
+}
+
+/// A cycle-accurate 6502 CPU, exported to JavaScript as `Cpu`.
+#[wasm_bindgen(js_name = Cpu)]
+#[derive(Debug, Clone)]
+pub struct WasmCpu {
+    /// The wrapped CPU.
+    core: Core,
+}
+
+#[wasm_bindgen(js_class = Cpu)]
+impl WasmCpu {
+    /// Create a new CPU emulating the given model, one of `"2a03"` (the NES's
+    /// Ricoh 2A03, the default), `"6502"` or `"65c02"`.
+    ///
+    /// All of the registers are zeroed, and the CPU starts with the reset
+    /// sequence.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the model is unknown.
+    #[wasm_bindgen(constructor)]
+    pub fn new(model: Option<String>) -> Result<Self, JsError> {
+        let model = model.unwrap_or_else(|| "2a03".to_owned());
+        let core = match model.as_str() {
+            "2a03" => Core::Ricoh2A03(Cpu::new()),
+            "6502" => Core::Nmos6502(Cpu::with_model(Nmos6502)),
+            "65c02" => Core::Wdc65C02(Cpu::with_model(Wdc65C02)),
+            model => return Err(JsError::new(&format!("unknown model {model:?}"))),
+        };
+
+        Ok(Self { core })
+    }
+
+    /// Execute a single cycle of the CPU, returning the bus access that must
+    /// be serviced before the next cycle, see [`WasmCpu::bus`].
+    ///
+    /// For a read, the data must be placed onto the bus with
+    /// [`WasmCpu::set_data`].
+    pub fn step(&mut self) -> Object {
+        with_cpu!(&mut self.core, cpu => cpu.step());
+        self.bus()
+    }
+
+    /// Execute the given number of cycles of the CPU, servicing its bus
+    /// accesses with the given 64K memory, e.g. a `Uint8Array`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the memory is not 64K in size.
+    pub fn run(&mut self, cycles: u32, ram: &mut [u8]) -> Result<(), JsError> {
+        let ram: &mut [u8; 0x10000] = ram
+            .try_into()
+            .map_err(|_| JsError::new("memory is not 64K in size"))?;
+
+        with_cpu!(&mut self.core, cpu => {
+            for _ in 0..cycles {
+                cpu.tick(ram);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Return the current bus access, as an `{ addr, data, write, kind }`
+    /// object, where `kind` is the reason of the access, e.g. `"opcode"`.
+    #[must_use]
+    pub fn bus(&self) -> Object {
+        let Bus {
+            addr,
+            data,
+            write,
+            kind,
+        } = with_cpu!(&self.core, cpu => &cpu.bus);
+
+        object(&[
+            ("addr", (*addr).into()),
+            ("data", (*data).into()),
+            ("write", (*write).into()),
+            ("kind", cycle_name(*kind).into()),
+        ])
+    }
+
+    /// Place the data of a read access onto the bus.
+    #[wasm_bindgen(js_name = setData)]
+    pub fn set_data(&mut self, data: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.bus.data = data);
+    }
+
+    /// Return the registers, as an `{ a, x, y, sp, pc, p }` object.
+    #[must_use]
+    pub fn registers(&self) -> Object {
+        let Registers {
+            flags,
+            a,
+            x,
+            y,
+            sp,
+            pc,
+        } = with_cpu!(&self.core, cpu => &cpu.regs);
+
+        object(&[
+            ("a", (*a).into()),
+            ("x", (*x).into()),
+            ("y", (*y).into()),
+            ("sp", (*sp).into()),
+            ("pc", (*pc).into()),
+            ("p", flags.bits().into()),
+        ])
+    }
+
+    /// Replace the accumulator, `A`.
+    #[wasm_bindgen(js_name = setA)]
+    pub fn set_a(&mut self, a: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.a = a);
+    }
+
+    /// Replace the first index register, `X`.
+    #[wasm_bindgen(js_name = setX)]
+    pub fn set_x(&mut self, x: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.x = x);
+    }
+
+    /// Replace the second index register, `Y`.
+    #[wasm_bindgen(js_name = setY)]
+    pub fn set_y(&mut self, y: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.y = y);
+    }
+
+    /// Replace the stack pointer, `S`.
+    #[wasm_bindgen(js_name = setSp)]
+    pub fn set_sp(&mut self, sp: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.sp = sp);
+    }
+
+    /// Replace the program counter, `PC`.
+    #[wasm_bindgen(js_name = setPc)]
+    pub fn set_pc(&mut self, pc: u16) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.pc = pc);
+    }
+
+    /// Replace the flags register, `P`.
+    #[wasm_bindgen(js_name = setP)]
+    pub fn set_p(&mut self, p: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.regs.flags = Flags::from_bits_retain(p));
+    }
+
+    /// Return the I/O control pins, as an `{ sync, irq, nmi, rdy, res, so }`
+    /// object of booleans.
+    #[must_use]
+    pub fn pins(&self) -> Object {
+        let pins = with_cpu!(&self.core, cpu => cpu.pins);
+        let props = PINS.map(|(name, pin)| (name, JsValue::from_bool(pins.contains(pin))));
+
+        object(&props)
+    }
+
+    /// Set or clear one of the I/O control pins by its name, e.g. `"irq"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pin is unknown.
+    #[wasm_bindgen(js_name = setPin)]
+    pub fn set_pin(&mut self, name: &str, value: bool) -> Result<(), JsError> {
+        let (_, pin) = PINS
+            .into_iter()
+            .find(|(pin, _)| *pin == name)
+            .ok_or_else(|| JsError::new(&format!("unknown pin {name:?}")))?;
+
+        with_cpu!(&mut self.core, cpu => cpu.pins.set(pin, value));
+        Ok(())
+    }
+
+    /// Return the number of cycles executed since the CPU was created.
+    #[must_use]
+    pub fn cycles(&self) -> u64 {
+        with_cpu!(&self.core, cpu => cpu.cycles)
+    }
+
+    /// Start the reset sequence of the CPU with the next cycle.
+    pub fn reset(&mut self) {
+        with_cpu!(&mut self.core, cpu => cpu.reset());
+    }
+
+    /// Save the complete state of the CPU as a binary snapshot.
+    #[must_use]
+    pub fn save(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        // Writing into a vector cannot fail.
+        let _ = with_cpu!(&self.core, cpu => cpu.save(&mut state));
+        state
+    }
+
+    /// Load the complete state of the CPU from a binary snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error (and leaves the CPU untouched) if the snapshot is
+    /// malformed.
+    pub fn load(&mut self, state: &[u8]) -> Result<(), JsError> {
+        with_cpu!(&mut self.core, cpu => cpu.load(&mut &state[..]))
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+}
@@ -0,0 +1,179 @@
+//! Differential tests against a simple, instruction-level reference model.
+//!
+//! Every test case fills the memory with random data, places a random
+//! instruction stream at a random program counter and starts both the CPU and
+//! the reference model (see [`reference`]) with the same random registers.
+//! After every instruction, the registers, the status flags, the whole memory
+//! and the number of cycles the instruction took must be identical.
+//!
+//! A test case ends after [`MAX_INSTRUCTIONS`], or at the first opcode which
+//! is not modelled by the reference, i.e. one of the `JAM`s or the unstable
+//! unofficial opcodes. The number of test cases can be raised with the
+//! `PROPTEST_CASES` environment variable:
+//!
+//! ```no-run
+//! PROPTEST_CASES=100000 cargo test --release --test differential
+//! ```
+
+mod reference;
+
+use chuck_cpu::{Bus, Cpu, Flags, Model, Nmos6502, Ricoh2A03};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use reference::Reference;
+
+/// The maximum number of instructions executed by a test case.
+const MAX_INSTRUCTIONS: usize = 64;
+
+/// The initial state of a test case.
+#[derive(Debug, Clone)]
+struct State {
+    /// The seed of the random data in memory.
+    seed: u64,
+    pc: u16,
+    sp: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    /// The instruction stream, which is placed at the program counter.
+    program: Vec<u8>,
+}
+
+/// Generate the initial state of a test case.
+fn state() -> impl Strategy<Value = State> {
+    (
+        any::<u64>(),
+        any::<u16>(),
+        any::<[u8; 5]>(),
+        vec(any::<u8>(), 1..48),
+    )
+        .prop_map(|(seed, pc, [sp, a, x, y, p], program)| State {
+            seed,
+            pc,
+            sp,
+            a,
+            x,
+            y,
+            // The `B` and unused flags only exist on the stack.
+            p: p & 0xcf,
+            program,
+        })
+}
+
+/// Fill the memory with the random data of a test case.
+fn memory(state: &State) -> Vec<u8> {
+    // A xorshift generator, the seed of which must not be zero.
+    let mut seed = state.seed | 1;
+    let mut mem: Vec<u8> = (0..0x10000)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed.to_le_bytes()[0]
+        })
+        .collect();
+
+    let mut addr = state.pc;
+    for &byte in &state.program {
+        mem[usize::from(addr)] = byte;
+        addr = addr.wrapping_add(1);
+    }
+
+    mem
+}
+
+/// Service a bus access of the CPU with the given memory.
+fn service(bus: &mut Bus, mem: &mut [u8]) {
+    let addr = usize::from(bus.addr);
+
+    if bus.write {
+        mem[addr] = bus.data;
+    } else {
+        bus.data = mem[addr];
+    }
+}
+
+/// Run a test case with the CPU of the given model.
+fn run<M: Model>(model: M, state: &State) -> Result<(), TestCaseError> {
+    let mut cpu = Cpu::builder()
+        .model(model)
+        .pc(state.pc)
+        .sp(state.sp)
+        .a(state.a)
+        .x(state.x)
+        .y(state.y)
+        .flags(Flags::from_bits_truncate(state.p))
+        .build();
+
+    let mut reference = Reference {
+        a: state.a,
+        x: state.x,
+        y: state.y,
+        sp: state.sp,
+        pc: state.pc,
+        p: state.p,
+        decimal: M::DECIMAL,
+    };
+
+    let mut mem = memory(state);
+    let mut expected_mem = mem.clone();
+
+    // The opcode fetch of the first instruction was already placed onto the
+    // bus, the following ones are serviced as part of every instruction.
+    service(&mut cpu.bus, &mut mem);
+
+    for n in 0..MAX_INSTRUCTIONS {
+        let pc = reference.pc;
+        let opcode = expected_mem[usize::from(pc)];
+
+        let Some(expected_cycles) = reference.step(&mut expected_mem) else {
+            break;
+        };
+        let cycles = cpu.step_instruction(|bus| service(bus, &mut mem));
+
+        let context = format!("instruction #{n}, {opcode:02x} at {pc:04x}");
+        let regs = [
+            cpu.regs.pc,
+            cpu.regs.sp.into(),
+            cpu.regs.a.into(),
+            cpu.regs.x.into(),
+            cpu.regs.y.into(),
+            cpu.regs.flags.bits().into(),
+        ];
+        let expected = [
+            reference.pc,
+            reference.sp.into(),
+            reference.a.into(),
+            reference.x.into(),
+            reference.y.into(),
+            reference.p.into(),
+        ];
+
+        prop_assert_eq!(regs, expected, "{}: pc/s/a/x/y/p", context);
+        prop_assert_eq!(cycles, expected_cycles, "{}: cycles", context);
+
+        if let Some(addr) = (0..mem.len()).find(|&addr| mem[addr] != expected_mem[addr]) {
+            return Err(TestCaseError::fail(format!(
+                "{context}: mem[{addr:04x}] {:02x}, expected {:02x}",
+                mem[addr], expected_mem[addr]
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn ricoh_2a03(state in state()) {
+        run(Ricoh2A03, &state)?;
+    }
+
+    #[test]
+    fn nmos_6502(state in state()) {
+        run(Nmos6502, &state)?;
+    }
+}
@@ -0,0 +1,716 @@
+//! A simple, instruction-level model of the NMOS 6502, used as the reference
+//! implementation of the differential tests.
+//!
+//! The model executes a whole instruction at once and derives its cycle count
+//! from the addressing mode, which is the textbook description of the CPU and
+//! shares no code (or tables) with the pin-level implementation under test.
+//! It covers every official opcode and the stable unofficial ones, including
+//! the decimal mode of the NMOS 6502.
+//!
+//! # Link(s)
+//!
+//! - <https://www.masswerk.at/6502/6502_instruction_set.html>
+//! - <https://www.nesdev.org/wiki/CPU_unofficial_opcodes>
+//! - <http://www.6502.org/tutorials/decimal_mode.html#A>
+
+/// The carry flag.
+const C: u8 = 1 << 0;
+/// The zero flag.
+const Z: u8 = 1 << 1;
+/// The interrupt-disable flag.
+const I: u8 = 1 << 2;
+/// The decimal-mode flag.
+const D: u8 = 1 << 3;
+/// The break flag, which only exists on the stack.
+const B: u8 = 1 << 4;
+/// The unused flag, which is always pushed as one.
+const U: u8 = 1 << 5;
+/// The overflow flag.
+const V: u8 = 1 << 6;
+/// The negative flag.
+const N: u8 = 1 << 7;
+
+/// The operations of the modelled instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+    // The stable unofficial operations.
+    Alr,
+    Anc,
+    Arr,
+    Dcp,
+    Isc,
+    Las,
+    Lax,
+    Rla,
+    Rra,
+    Sax,
+    Sbx,
+    Slo,
+    Sre,
+}
+
+/// The addressing modes of the modelled instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Imp,
+    Acc,
+    Imm,
+    Zpg,
+    Zpx,
+    Zpy,
+    Abs,
+    Abx,
+    Aby,
+    Ind,
+    Izx,
+    Izy,
+    Rel,
+}
+
+/// Decode an opcode, returning `None` for the opcodes that are not modelled:
+/// the `JAM`s and the unstable unofficial opcodes.
+#[rustfmt::skip]
+fn decode(opcode: u8) -> Option<(Op, Mode)> {
+    use Mode::{Abs, Abx, Aby, Acc, Imm, Imp, Ind, Izx, Izy, Rel, Zpg, Zpx, Zpy};
+
+    // The opcodes of the ALU (`cc = 01`) and the unofficial combined
+    // read-modify-write/ALU (`cc = 11`) groups share their addressing modes.
+    let group = |opcode: u8| match (opcode >> 2) & 7 {
+        0 => Izx,
+        1 => Zpg,
+        2 => Imm,
+        3 => Abs,
+        4 => Izy,
+        5 => Zpx,
+        6 => Aby,
+        _ => Abx,
+    };
+
+    let decoded = match opcode {
+        0x00 => (Op::Brk, Imp),
+        0x20 => (Op::Jsr, Abs),
+        0x40 => (Op::Rti, Imp),
+        0x60 => (Op::Rts, Imp),
+        0x4c => (Op::Jmp, Abs),
+        0x6c => (Op::Jmp, Ind),
+
+        0x08 => (Op::Php, Imp), 0x28 => (Op::Plp, Imp),
+        0x48 => (Op::Pha, Imp), 0x68 => (Op::Pla, Imp),
+        0x18 => (Op::Clc, Imp), 0x38 => (Op::Sec, Imp),
+        0x58 => (Op::Cli, Imp), 0x78 => (Op::Sei, Imp),
+        0xb8 => (Op::Clv, Imp), 0xd8 => (Op::Cld, Imp),
+        0xf8 => (Op::Sed, Imp), 0x88 => (Op::Dey, Imp),
+        0x98 => (Op::Tya, Imp), 0xa8 => (Op::Tay, Imp),
+        0xc8 => (Op::Iny, Imp), 0xe8 => (Op::Inx, Imp),
+        0x8a => (Op::Txa, Imp), 0x9a => (Op::Txs, Imp),
+        0xaa => (Op::Tax, Imp), 0xba => (Op::Tsx, Imp),
+        0xca => (Op::Dex, Imp), 0xea => (Op::Nop, Imp),
+
+        0x10 => (Op::Bpl, Rel), 0x30 => (Op::Bmi, Rel),
+        0x50 => (Op::Bvc, Rel), 0x70 => (Op::Bvs, Rel),
+        0x90 => (Op::Bcc, Rel), 0xb0 => (Op::Bcs, Rel),
+        0xd0 => (Op::Bne, Rel), 0xf0 => (Op::Beq, Rel),
+
+        0x24 => (Op::Bit, Zpg), 0x2c => (Op::Bit, Abs),
+        0x84 => (Op::Sty, Zpg), 0x8c => (Op::Sty, Abs), 0x94 => (Op::Sty, Zpx),
+        0xa0 => (Op::Ldy, Imm), 0xa4 => (Op::Ldy, Zpg), 0xac => (Op::Ldy, Abs),
+        0xb4 => (Op::Ldy, Zpx), 0xbc => (Op::Ldy, Abx),
+        0xc0 => (Op::Cpy, Imm), 0xc4 => (Op::Cpy, Zpg), 0xcc => (Op::Cpy, Abs),
+        0xe0 => (Op::Cpx, Imm), 0xe4 => (Op::Cpx, Zpg), 0xec => (Op::Cpx, Abs),
+
+        0x86 => (Op::Stx, Zpg), 0x8e => (Op::Stx, Abs), 0x96 => (Op::Stx, Zpy),
+        0xa2 => (Op::Ldx, Imm), 0xa6 => (Op::Ldx, Zpg), 0xae => (Op::Ldx, Abs),
+        0xb6 => (Op::Ldx, Zpy), 0xbe => (Op::Ldx, Aby),
+
+        0x0a => (Op::Asl, Acc), 0x2a => (Op::Rol, Acc),
+        0x4a => (Op::Lsr, Acc), 0x6a => (Op::Ror, Acc),
+
+        // The read-modify-write group, `cc = 10`.
+        0x06 | 0x0e | 0x16 | 0x1e => (Op::Asl, rmw_mode(opcode)),
+        0x26 | 0x2e | 0x36 | 0x3e => (Op::Rol, rmw_mode(opcode)),
+        0x46 | 0x4e | 0x56 | 0x5e => (Op::Lsr, rmw_mode(opcode)),
+        0x66 | 0x6e | 0x76 | 0x7e => (Op::Ror, rmw_mode(opcode)),
+        0xc6 | 0xce | 0xd6 | 0xde => (Op::Dec, rmw_mode(opcode)),
+        0xe6 | 0xee | 0xf6 | 0xfe => (Op::Inc, rmw_mode(opcode)),
+
+        // The ALU group, `cc = 01`, where `STA #imm` is an unofficial `NOP`.
+        0x89 => (Op::Nop, Imm),
+        0x01..=0x1f if opcode & 3 == 1 => (Op::Ora, group(opcode)),
+        0x21..=0x3f if opcode & 3 == 1 => (Op::And, group(opcode)),
+        0x41..=0x5f if opcode & 3 == 1 => (Op::Eor, group(opcode)),
+        0x61..=0x7f if opcode & 3 == 1 => (Op::Adc, group(opcode)),
+        0x81..=0x9f if opcode & 3 == 1 => (Op::Sta, group(opcode)),
+        0xa1..=0xbf if opcode & 3 == 1 => (Op::Lda, group(opcode)),
+        0xc1..=0xdf if opcode & 3 == 1 => (Op::Cmp, group(opcode)),
+        0xe1..=0xff if opcode & 3 == 1 => (Op::Sbc, group(opcode)),
+
+        // The unofficial immediate opcodes of `cc = 11`.
+        0x0b | 0x2b => (Op::Anc, Imm),
+        0x4b => (Op::Alr, Imm),
+        0x6b => (Op::Arr, Imm),
+        0xcb => (Op::Sbx, Imm),
+        0xeb => (Op::Sbc, Imm),
+
+        // The unofficial loads and stores of `cc = 11`.
+        0x83 => (Op::Sax, Izx), 0x87 => (Op::Sax, Zpg),
+        0x8f => (Op::Sax, Abs), 0x97 => (Op::Sax, Zpy),
+        0xa3 => (Op::Lax, Izx), 0xa7 => (Op::Lax, Zpg), 0xaf => (Op::Lax, Abs),
+        0xb3 => (Op::Lax, Izy), 0xb7 => (Op::Lax, Zpy), 0xbf => (Op::Lax, Aby),
+        0xbb => (Op::Las, Aby),
+
+        // The unofficial combined read-modify-write/ALU opcodes of `cc = 11`.
+        0x03..=0x1f if opcode & 3 == 3 => (Op::Slo, group(opcode)),
+        0x23..=0x3f if opcode & 3 == 3 => (Op::Rla, group(opcode)),
+        0x43..=0x5f if opcode & 3 == 3 => (Op::Sre, group(opcode)),
+        0x63..=0x7f if opcode & 3 == 3 => (Op::Rra, group(opcode)),
+        0xc3..=0xdf if opcode & 3 == 3 => (Op::Dcp, group(opcode)),
+        0xe3..=0xff if opcode & 3 == 3 => (Op::Isc, group(opcode)),
+
+        // The unofficial `NOP`s.
+        0x1a | 0x3a | 0x5a | 0x7a | 0xda | 0xfa => (Op::Nop, Imp),
+        0x80 | 0x82 | 0xc2 | 0xe2 => (Op::Nop, Imm),
+        0x04 | 0x44 | 0x64 => (Op::Nop, Zpg),
+        0x14 | 0x34 | 0x54 | 0x74 | 0xd4 | 0xf4 => (Op::Nop, Zpx),
+        0x0c => (Op::Nop, Abs),
+        0x1c | 0x3c | 0x5c | 0x7c | 0xdc | 0xfc => (Op::Nop, Abx),
+
+        // The `JAM`s, `ANE`, `LXA`, `SHA`, `SHX`, `SHY` and `TAS`.
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+/// Return the addressing mode of a read-modify-write opcode of `cc = 10`.
+fn rmw_mode(opcode: u8) -> Mode {
+    match (opcode >> 2) & 7 {
+        1 => Mode::Zpg,
+        3 => Mode::Abs,
+        5 => Mode::Zpx,
+        _ => Mode::Abx,
+    }
+}
+
+/// The memory access pattern of an operation with an operand in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+    Modify,
+}
+
+/// Return the memory access pattern of an operation.
+fn access(op: Op) -> Access {
+    match op {
+        Op::Sta | Op::Stx | Op::Sty | Op::Sax => Access::Write,
+        Op::Asl
+        | Op::Lsr
+        | Op::Rol
+        | Op::Ror
+        | Op::Inc
+        | Op::Dec
+        | Op::Slo
+        | Op::Rla
+        | Op::Sre
+        | Op::Rra
+        | Op::Dcp
+        | Op::Isc => Access::Modify,
+        _ => Access::Read,
+    }
+}
+
+/// Return the number of cycles of an instruction with the given addressing
+/// mode and memory access pattern, other than a branch, jump or stack access.
+fn cycles(mode: Mode, access: Access, crossed: bool) -> u32 {
+    match (mode, access) {
+        (Mode::Imp | Mode::Acc | Mode::Imm, _) => 2,
+        (Mode::Zpg, Access::Read | Access::Write) => 3,
+        (Mode::Zpx | Mode::Zpy | Mode::Abs, Access::Read | Access::Write) => 4,
+        (Mode::Abx | Mode::Aby, Access::Read) => 4 + u32::from(crossed),
+        (Mode::Zpg, Access::Modify) | (Mode::Abx | Mode::Aby, Access::Write) => 5,
+        (Mode::Izy, Access::Read) => 5 + u32::from(crossed),
+        (Mode::Zpx | Mode::Zpy | Mode::Abs, Access::Modify)
+        | (Mode::Izx, Access::Read | Access::Write)
+        | (Mode::Izy, Access::Write) => 6,
+        (Mode::Abx | Mode::Aby, Access::Modify) => 7,
+        (Mode::Izx | Mode::Izy, Access::Modify) => 8,
+        (Mode::Ind | Mode::Rel, _) => unreachable!("only used by jumps and branches"),
+    }
+}
+
+/// An NMOS 6502 executing one instruction at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub pc: u16,
+    /// The status flags, without the `B` and unused flags.
+    pub p: u8,
+    /// Whether the `D` flag enables the decimal mode.
+    pub decimal: bool,
+}
+
+impl Reference {
+    /// Execute the instruction at the program counter, returning the number
+    /// of cycles it took, or `None` (without executing it) if its opcode is
+    /// not modelled.
+    pub fn step(&mut self, mem: &mut [u8]) -> Option<u32> {
+        let (op, mode) = decode(mem[usize::from(self.pc)])?;
+
+        let (addr, crossed, size) = self.address(mode, mem);
+        self.pc = self.pc.wrapping_add(size);
+
+        let cycles = match op {
+            Op::Bpl | Op::Bmi | Op::Bvc | Op::Bvs | Op::Bcc | Op::Bcs | Op::Bne | Op::Beq => {
+                self.branch(op, addr)
+            }
+            Op::Jmp | Op::Jsr | Op::Rts | Op::Brk | Op::Rti => self.control(op, mode, addr, mem),
+            Op::Pha | Op::Php | Op::Pla | Op::Plp => self.stack(op, mem),
+            _ => {
+                self.execute(op, mode, addr, mem);
+                cycles(mode, access(op), crossed)
+            }
+        };
+
+        Some(cycles)
+    }
+
+    /// Resolve the effective address of an instruction, returning it, whether
+    /// indexing crossed a page and the size of the instruction.
+    ///
+    /// The effective address of a branch is its target.
+    fn address(&self, mode: Mode, mem: &[u8]) -> (u16, bool, u16) {
+        let read = |addr: u16| mem[usize::from(addr)];
+        let word = |addr: u16| u16::from_le_bytes([read(addr), read(addr.wrapping_add(1))]);
+        let zp_word =
+            |ptr: u8| u16::from_le_bytes([read(ptr.into()), read(ptr.wrapping_add(1).into())]);
+        let indexed = |base: u16, index: u8| {
+            let addr = base.wrapping_add(index.into());
+            (addr, addr >> 8 != base >> 8)
+        };
+
+        let low = read(self.pc.wrapping_add(1));
+        let abs = word(self.pc.wrapping_add(1));
+
+        match mode {
+            Mode::Imp | Mode::Acc => (0, false, 1),
+            Mode::Imm => (self.pc.wrapping_add(1), false, 2),
+            Mode::Zpg => (low.into(), false, 2),
+            Mode::Zpx => (low.wrapping_add(self.x).into(), false, 2),
+            Mode::Zpy => (low.wrapping_add(self.y).into(), false, 2),
+            Mode::Abs => (abs, false, 3),
+            Mode::Abx => {
+                let (addr, crossed) = indexed(abs, self.x);
+                (addr, crossed, 3)
+            }
+            Mode::Aby => {
+                let (addr, crossed) = indexed(abs, self.y);
+                (addr, crossed, 3)
+            }
+            Mode::Ind => {
+                // The high byte of the pointer is not incremented.
+                let high = (abs & 0xff00) | (abs.wrapping_add(1) & 0x00ff);
+                (u16::from_le_bytes([read(abs), read(high)]), false, 3)
+            }
+            Mode::Izx => (zp_word(low.wrapping_add(self.x)), false, 2),
+            Mode::Izy => {
+                let (addr, crossed) = indexed(zp_word(low), self.y);
+                (addr, crossed, 2)
+            }
+            Mode::Rel => {
+                let next = self.pc.wrapping_add(2);
+                let target = next.wrapping_add_signed(i16::from(i8::from_le_bytes([low])));
+                (target, target >> 8 != next >> 8, 2)
+            }
+        }
+    }
+
+    /// Execute a branch to the given target, returning the number of cycles.
+    fn branch(&mut self, op: Op, target: u16) -> u32 {
+        let (flag, set) = match op {
+            Op::Bpl => (N, false),
+            Op::Bmi => (N, true),
+            Op::Bvc => (V, false),
+            Op::Bvs => (V, true),
+            Op::Bcc => (C, false),
+            Op::Bcs => (C, true),
+            Op::Bne => (Z, false),
+            _ => (Z, true),
+        };
+
+        if (self.p & flag != 0) != set {
+            return 2;
+        }
+
+        let crossed = target >> 8 != self.pc >> 8;
+        self.pc = target;
+        3 + u32::from(crossed)
+    }
+
+    /// Execute a jump, a subroutine call or return, or a `BRK`, returning the
+    /// number of cycles.
+    fn control(&mut self, op: Op, mode: Mode, addr: u16, mem: &mut [u8]) -> u32 {
+        match op {
+            Op::Jmp => {
+                self.pc = addr;
+                if mode == Mode::Ind {
+                    5
+                } else {
+                    3
+                }
+            }
+            Op::Jsr => {
+                let [low, high] = self.pc.wrapping_sub(1).to_le_bytes();
+                self.push(mem, high);
+                self.push(mem, low);
+                self.pc = addr;
+                6
+            }
+            Op::Rts => {
+                let low = self.pull(mem);
+                let high = self.pull(mem);
+                self.pc = u16::from_le_bytes([low, high]).wrapping_add(1);
+                6
+            }
+            Op::Brk => {
+                // The byte after `BRK` is skipped.
+                let [low, high] = self.pc.wrapping_add(1).to_le_bytes();
+                self.push(mem, high);
+                self.push(mem, low);
+                self.push(mem, self.p | B | U);
+                self.set(I, true);
+                self.pc = u16::from_le_bytes([mem[0xfffe], mem[0xffff]]);
+                7
+            }
+            _ => {
+                self.p = self.pull(mem) & !(B | U);
+                let low = self.pull(mem);
+                let high = self.pull(mem);
+                self.pc = u16::from_le_bytes([low, high]);
+                6
+            }
+        }
+    }
+
+    /// Execute a push or a pull, returning the number of cycles.
+    fn stack(&mut self, op: Op, mem: &mut [u8]) -> u32 {
+        match op {
+            Op::Pha => {
+                self.push(mem, self.a);
+                3
+            }
+            Op::Php => {
+                self.push(mem, self.p | B | U);
+                3
+            }
+            Op::Pla => {
+                let value = self.pull(mem);
+                self.a = self.nz(value);
+                4
+            }
+            _ => {
+                self.p = self.pull(mem) & !(B | U);
+                4
+            }
+        }
+    }
+
+    /// Execute any other instruction, with its operand at the given address.
+    fn execute(&mut self, op: Op, mode: Mode, addr: u16, mem: &mut [u8]) {
+        let operand = mem[usize::from(addr)];
+        let mut write = |data: u8| mem[usize::from(addr)] = data;
+
+        match op {
+            Op::Lda => self.a = self.nz(operand),
+            Op::Ldx => self.x = self.nz(operand),
+            Op::Ldy => self.y = self.nz(operand),
+            Op::Lax => {
+                self.a = self.nz(operand);
+                self.x = self.a;
+            }
+            Op::Las => {
+                let value = self.nz(operand & self.sp);
+                (self.a, self.x, self.sp) = (value, value, value);
+            }
+            Op::Sta => write(self.a),
+            Op::Stx => write(self.x),
+            Op::Sty => write(self.y),
+            Op::Sax => write(self.a & self.x),
+
+            Op::Ora => self.a = self.nz(self.a | operand),
+            Op::And => self.a = self.nz(self.a & operand),
+            Op::Eor => self.a = self.nz(self.a ^ operand),
+            Op::Adc => self.adc(operand),
+            Op::Sbc => self.sbc(operand),
+            Op::Cmp => self.compare(self.a, operand),
+            Op::Cpx => self.compare(self.x, operand),
+            Op::Cpy => self.compare(self.y, operand),
+            Op::Bit => {
+                self.set(Z, self.a & operand == 0);
+                self.p = (self.p & !(N | V)) | (operand & (N | V));
+            }
+
+            Op::Asl | Op::Lsr | Op::Rol | Op::Ror if mode == Mode::Acc => {
+                self.a = self.shift(op, self.a);
+            }
+            Op::Asl | Op::Lsr | Op::Rol | Op::Ror => write(self.shift(op, operand)),
+            Op::Inc => write(self.nz(operand.wrapping_add(1))),
+            Op::Dec => write(self.nz(operand.wrapping_sub(1))),
+            Op::Slo | Op::Rla | Op::Sre | Op::Rra => {
+                let shift = match op {
+                    Op::Slo => Op::Asl,
+                    Op::Rla => Op::Rol,
+                    Op::Sre => Op::Lsr,
+                    _ => Op::Ror,
+                };
+                let value = self.shift(shift, operand);
+                write(value);
+
+                match op {
+                    Op::Slo => self.a = self.nz(self.a | value),
+                    Op::Rla => self.a = self.nz(self.a & value),
+                    Op::Sre => self.a = self.nz(self.a ^ value),
+                    _ => self.adc(value),
+                }
+            }
+            Op::Dcp => {
+                let value = operand.wrapping_sub(1);
+                write(value);
+                self.compare(self.a, value);
+            }
+            Op::Isc => {
+                let value = operand.wrapping_add(1);
+                write(value);
+                self.sbc(value);
+            }
+
+            Op::Anc => {
+                self.a = self.nz(self.a & operand);
+                self.set(C, self.a & 0x80 != 0);
+            }
+            Op::Alr => self.a = self.shift(Op::Lsr, self.a & operand),
+            Op::Arr => {
+                let value = self.a & operand;
+                self.a = self.nz((value >> 1) | ((self.p & C) << 7));
+                self.set(V, ((self.a >> 6) ^ (self.a >> 5)) & 1 != 0);
+                if self.decimal && self.p & D != 0 {
+                    // The nibbles are adjusted like those of a decimal sum,
+                    // after the flags are set, except for the `C` flag.
+                    if (value & 0x0f) + (value & 0x01) > 0x05 {
+                        self.a = (self.a & 0xf0) | (self.a.wrapping_add(0x06) & 0x0f);
+                    }
+                    let high = u16::from(value & 0xf0) + u16::from(value & 0x10) > 0x50;
+                    if high {
+                        self.a = self.a.wrapping_add(0x60);
+                    }
+                    self.set(C, high);
+                } else {
+                    self.set(C, self.a & 0x40 != 0);
+                }
+            }
+            Op::Sbx => {
+                let value = self.a & self.x;
+                self.set(C, value >= operand);
+                self.x = self.nz(value.wrapping_sub(operand));
+            }
+
+            _ => self.implied(op),
+        }
+    }
+
+    /// Execute an instruction which only operates on the registers.
+    fn implied(&mut self, op: Op) {
+        match op {
+            Op::Inx => self.x = self.nz(self.x.wrapping_add(1)),
+            Op::Iny => self.y = self.nz(self.y.wrapping_add(1)),
+            Op::Dex => self.x = self.nz(self.x.wrapping_sub(1)),
+            Op::Dey => self.y = self.nz(self.y.wrapping_sub(1)),
+            Op::Tax => self.x = self.nz(self.a),
+            Op::Tay => self.y = self.nz(self.a),
+            Op::Txa => self.a = self.nz(self.x),
+            Op::Tya => self.a = self.nz(self.y),
+            Op::Tsx => self.x = self.nz(self.sp),
+            Op::Txs => self.sp = self.x,
+            Op::Clc => self.set(C, false),
+            Op::Sec => self.set(C, true),
+            Op::Cli => self.set(I, false),
+            Op::Sei => self.set(I, true),
+            Op::Cld => self.set(D, false),
+            Op::Sed => self.set(D, true),
+            Op::Clv => self.set(V, false),
+            _ => {}
+        }
+    }
+
+    /// Set or clear the given flag.
+    fn set(&mut self, flag: u8, value: bool) {
+        if value {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    /// Set the `N` and `Z` flags from a value, returning the value.
+    fn nz(&mut self, value: u8) -> u8 {
+        self.set(N, value & 0x80 != 0);
+        self.set(Z, value == 0);
+        value
+    }
+
+    /// Push a value onto the stack.
+    fn push(&mut self, mem: &mut [u8], value: u8) {
+        mem[0x100 | usize::from(self.sp)] = value;
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    /// Pull a value from the stack.
+    fn pull(&mut self, mem: &[u8]) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        mem[0x100 | usize::from(self.sp)]
+    }
+
+    /// Compare a register with a value.
+    fn compare(&mut self, reg: u8, value: u8) {
+        self.set(C, reg >= value);
+        self.nz(reg.wrapping_sub(value));
+    }
+
+    /// Shift or rotate a value, returning the result.
+    fn shift(&mut self, op: Op, value: u8) -> u8 {
+        let carry = self.p & C;
+        let (result, out) = match op {
+            Op::Asl => (value << 1, value >> 7),
+            Op::Lsr => (value >> 1, value & 1),
+            Op::Rol => ((value << 1) | carry, value >> 7),
+            _ => ((value >> 1) | (carry << 7), value & 1),
+        };
+
+        self.set(C, out != 0);
+        self.nz(result)
+    }
+
+    /// Add a value and the carry flag to the accumulator.
+    fn adc(&mut self, value: u8) {
+        let carry = self.p & C;
+        let binary = u16::from(self.a) + u16::from(value) + u16::from(carry);
+        let [result, _] = binary.to_le_bytes();
+
+        if !(self.decimal && self.p & D != 0) {
+            self.set(C, binary > 0xff);
+            self.set(V, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+            self.a = self.nz(result);
+            return;
+        }
+
+        // The `Z` flag is set from the binary sum, the `N` and `V` flags from
+        // the sum with an adjusted low nibble (seq. 1 and 2 of Appendix A).
+        let mut low = i16::from(self.a & 0x0f) + i16::from(value & 0x0f) + i16::from(carry);
+        if low >= 0x0a {
+            low = ((low + 0x06) & 0x0f) + 0x10;
+        }
+
+        let signed = i16::from(i8::from_le_bytes([self.a & 0xf0]))
+            + i16::from(i8::from_le_bytes([value & 0xf0]))
+            + low;
+        let mut sum = i16::from(self.a & 0xf0) + i16::from(value & 0xf0) + low;
+        if sum >= 0xa0 {
+            sum += 0x60;
+        }
+
+        self.set(Z, result == 0);
+        self.set(N, signed & 0x80 != 0);
+        self.set(V, !(-128..=127).contains(&signed));
+        self.set(C, sum >= 0x100);
+        [self.a, _] = sum.to_le_bytes();
+    }
+
+    /// Subtract a value and the inverted carry flag from the accumulator.
+    fn sbc(&mut self, value: u8) {
+        let borrow = i16::from(self.p & C == 0);
+        let binary = i16::from(self.a) - i16::from(value) - borrow;
+        let [result, _] = binary.to_le_bytes();
+
+        // All flags are set from the binary difference, even in decimal mode.
+        let a = self.a;
+        self.set(C, binary >= 0);
+        self.set(V, (a ^ value) & (a ^ result) & 0x80 != 0);
+        self.nz(result);
+
+        if !(self.decimal && self.p & D != 0) {
+            self.a = result;
+            return;
+        }
+
+        // Seq. 3 of Appendix A.
+        let mut low = i16::from(a & 0x0f) - i16::from(value & 0x0f) - borrow;
+        if low < 0 {
+            low = ((low - 0x06) & 0x0f) - 0x10;
+        }
+
+        let mut diff = i16::from(a & 0xf0) - i16::from(value & 0xf0) + low;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+
+        [self.a, _] = diff.to_le_bytes();
+    }
+}
@@ -0,0 +1,231 @@
+//! Exhaustive per-opcode tests using the `SingleStepTests` `ProcessorTests`.
+//!
+//! Every opcode has a JSON file of 10,000 randomized test cases, each with an
+//! initial state, a final state and the exact bus activity of every cycle in
+//! between. The CPU is driven through its pin-level interface and both the
+//! bus activity and the final state are verified.
+//!
+//! The test files are not part of this repository, so these tests are skipped
+//! unless the `CHUCK_SST_DIR` environment variable points to a directory with
+//! the files of a test set (`00.json` to `ff.json`). The `CHUCK_SST_MODEL`
+//! environment variable selects the model under test: `2a03` (the default,
+//! for the `nes6502` set), `6502` (for the `6502` set) or `65c02` (for the
+//! `wdc65c02` set).
+//!
+//! ```no-run
+//! CHUCK_SST_DIR=ProcessorTests/nes6502/v1 \
+//!     cargo test --release --test single_step
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/SingleStepTests/ProcessorTests>
+
+use std::path::Path;
+use std::{env, fs};
+
+use chuck_cpu::{Cpu, Flags, Model, Nmos6502, Pins, Ricoh2A03, Variant, Wdc65C02};
+use serde::Deserialize;
+
+/// The maximum number of failures that are reported for each opcode.
+const MAX_FAILURES: usize = 5;
+
+/// A single test case.
+#[derive(Debug, Deserialize)]
+struct Test {
+    /// The name of the test case.
+    name: String,
+    /// The state before the instruction is executed.
+    initial: State,
+    /// The state after the instruction is executed.
+    #[serde(rename = "final")]
+    after: State,
+    /// The bus activity of every cycle, as `(address, data, "read"/"write")`.
+    cycles: Vec<(u16, u8, String)>,
+}
+
+/// The state of the CPU and the memory of a test case.
+#[derive(Debug, Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+/// Check if an opcode is not tested for a variant.
+///
+/// These are the opcodes which halt the CPU, after which the bus activity
+/// recorded by the tests is meaningless.
+fn is_skipped(variant: Variant, opcode: u8) -> bool {
+    match variant {
+        Variant::Nmos => {
+            matches!(opcode & 0x0f, 0x02) && matches!(opcode >> 4, 0..=7 | 9 | 0xb | 0xd | 0xf)
+        }
+        Variant::Cmos => matches!(opcode, 0xcb | 0xdb),
+    }
+}
+
+/// Service the bus access of the CPU, returning it as `(address, data, kind)`.
+fn service<M: Model>(cpu: &mut Cpu<M>, ram: &mut [u8]) -> (u16, u8, &'static str) {
+    let addr = usize::from(cpu.bus.addr);
+
+    if cpu.bus.write {
+        ram[addr] = cpu.bus.data;
+        (cpu.bus.addr, cpu.bus.data, "write")
+    } else {
+        cpu.bus.data = ram[addr];
+        (cpu.bus.addr, cpu.bus.data, "read")
+    }
+}
+
+/// Run a single test case, returning a description of the first mismatch.
+fn run<M: Model>(model: M, ram: &mut [u8], test: &Test) -> Result<(), String> {
+    let mut cpu = Cpu::builder()
+        .model(model)
+        .pc(test.initial.pc)
+        .sp(test.initial.s)
+        .a(test.initial.a)
+        .x(test.initial.x)
+        .y(test.initial.y)
+        .flags(Flags::from_bits_truncate(test.initial.p))
+        .build();
+
+    for &(addr, data) in &test.initial.ram {
+        ram[usize::from(addr)] = data;
+    }
+
+    // The opcode fetch was already placed onto the bus, the remaining cycles
+    // (and the fetch of the next opcode) are placed by stepping the CPU.
+    let mut cycles = vec![service(&mut cpu, ram)];
+    for _ in 1..test.cycles.len() {
+        cpu.step();
+        cycles.push(service(&mut cpu, ram));
+    }
+
+    cpu.step();
+
+    let expected: Vec<_> = test
+        .cycles
+        .iter()
+        .map(|(addr, data, kind)| (*addr, *data, kind.as_str()))
+        .collect();
+
+    if cycles != expected {
+        return Err(format!("cycles {cycles:02x?}, expected {expected:02x?}"));
+    }
+
+    if !cpu.pins.contains(Pins::SYNC) {
+        return Err("instruction did not end with an opcode fetch".into());
+    }
+
+    let regs = [
+        cpu.regs.pc,
+        cpu.regs.sp.into(),
+        cpu.regs.a.into(),
+        cpu.regs.x.into(),
+        cpu.regs.y.into(),
+    ];
+    let after = &test.after;
+    let expected = [
+        after.pc,
+        after.s.into(),
+        after.a.into(),
+        after.x.into(),
+        after.y.into(),
+    ];
+
+    if regs != expected {
+        return Err(format!("pc/s/a/x/y {regs:02x?}, expected {expected:02x?}"));
+    }
+
+    if cpu.regs.flags != Flags::from_bits_truncate(after.p) {
+        return Err(format!(
+            "p {:02x}, expected {:02x}",
+            cpu.regs.flags.bits(),
+            after.p
+        ));
+    }
+
+    for &(addr, data) in &after.ram {
+        if ram[usize::from(addr)] != data {
+            return Err(format!(
+                "ram[{addr:04x}] {:02x}, expected {data:02x}",
+                ram[usize::from(addr)]
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run every test case of the test set in the given directory, returning a
+/// description of the failures.
+fn run_set<M: Model + Copy>(dir: &Path, model: M) -> Vec<String> {
+    let mut ram = vec![0; 0x10000];
+    let mut failures = Vec::new();
+
+    for opcode in (0..=u8::MAX).filter(|&opcode| !is_skipped(M::VARIANT, opcode)) {
+        let path = dir.join(format!("{opcode:02x}.json"));
+        let json =
+            fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        let tests: Vec<Test> =
+            serde_json::from_str(&json).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+        let mut failed = 0;
+
+        for test in &tests {
+            if let Err(msg) = run(model, &mut ram, test) {
+                failed += 1;
+
+                if failed <= MAX_FAILURES {
+                    failures.push(format!("{}: {msg}", test.name));
+                }
+            }
+
+            // Clear the memory touched by the test for the next one.
+            for &(addr, _) in &test.after.ram {
+                ram[usize::from(addr)] = 0;
+            }
+
+            for &(addr, _, _) in &test.cycles {
+                ram[usize::from(addr)] = 0;
+            }
+        }
+
+        if failed > MAX_FAILURES {
+            failures.push(format!(
+                "{opcode:02x}: {} more failures",
+                failed - MAX_FAILURES
+            ));
+        }
+    }
+
+    failures
+}
+
+#[test]
+fn single_step_tests() {
+    let Some(dir) = env::var_os("CHUCK_SST_DIR") else {
+        eprintln!("skipping the SingleStepTests, CHUCK_SST_DIR is not set");
+        return;
+    };
+
+    let dir = Path::new(&dir);
+    let failures = match env::var("CHUCK_SST_MODEL").as_deref() {
+        Ok("2a03") | Err(_) => run_set(dir, Ricoh2A03),
+        Ok("6502") => run_set(dir, Nmos6502),
+        Ok("65c02") => run_set(dir, Wdc65C02),
+        Ok(other) => panic!("unknown model `{other}`"),
+    };
+
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
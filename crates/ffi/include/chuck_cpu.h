@@ -0,0 +1,202 @@
+/* The C API of Chuck's 6502 CPU core, generated by cbindgen from crates/ffi. */
+
+#ifndef CHUCK_CPU_H
+#define CHUCK_CPU_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The version of this API, which is incremented whenever it is extended.
+#define CHUCK_CPU_API_VERSION 1
+
+// The `SYNC` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_SYNC (1 << 0)
+
+// The `IRQ` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_IRQ (1 << 1)
+
+// The `NMI` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_NMI (1 << 2)
+
+// The `RDY` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_RDY (1 << 3)
+
+// The `RES` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_RES (1 << 4)
+
+// The `SO` pin, see [`chuck_cpu_get_pins`].
+#define CHUCK_PIN_SO (1 << 5)
+
+// The NES's Ricoh 2A03, an NMOS 6502 without the decimal mode, see
+// [`chuck_cpu_new`].
+#define CHUCK_MODEL_RICOH_2A03 0
+
+// The original NMOS 6502, see [`chuck_cpu_new`].
+#define CHUCK_MODEL_NMOS_6502 1
+
+// The CMOS WDC 65C02S, see [`chuck_cpu_new`].
+#define CHUCK_MODEL_WDC_65C02 2
+
+// An opaque handle to a CPU, see [`chuck_cpu_new`].
+//
+// A handle may be moved between threads, but must not be used by two threads
+// at the same time.
+typedef struct ChuckCpu ChuckCpu;
+
+// A callback that services a read access of the CPU, returning the data at
+// the given address.
+typedef uint8_t (*ChuckRead)(void *user, uint16_t addr);
+
+// A callback that services a write access of the CPU, storing the given data
+// at the given address.
+typedef void (*ChuckWrite)(void *user, uint16_t addr, uint8_t data);
+
+// The registers of the CPU.
+typedef struct ChuckRegs {
+  // The program counter, `PC`.
+  uint16_t pc;
+  // The general purpose accumulator, `A`.
+  uint8_t a;
+  // The first index register, `X`.
+  uint8_t x;
+  // The second index register, `Y`.
+  uint8_t y;
+  // The stack pointer, `S`.
+  uint8_t sp;
+  // The flags register, `P`.
+  uint8_t p;
+} ChuckRegs;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create a new CPU emulating the given model, one of the `CHUCK_MODEL_*`
+// constants, with all registers zeroed.
+//
+// The CPU starts with the reset sequence, and must be freed again with
+// [`chuck_cpu_free`]. This returns a null pointer if the model is unknown.
+struct ChuckCpu *chuck_cpu_new(uint32_t model);
+
+// Free a CPU created by [`chuck_cpu_new`].
+//
+// # Safety
+//
+// `cpu` must be a valid handle or a null pointer (which does nothing), and
+// must not be used afterwards.
+void chuck_cpu_free(struct ChuckCpu *cpu);
+
+// Execute a single cycle of the CPU, servicing its bus access with the given
+// callbacks.
+//
+// The `user` pointer is passed to the callbacks as is.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+void chuck_cpu_step(struct ChuckCpu *cpu, ChuckRead read, ChuckWrite write, void *user);
+
+// Execute the given number of cycles of the CPU, servicing its bus accesses
+// with the given callbacks.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+void chuck_cpu_run(struct ChuckCpu *cpu,
+                   uint64_t cycles,
+                   ChuckRead read,
+                   ChuckWrite write,
+                   void *user);
+
+// Execute the CPU until the opcode of the next instruction is fetched,
+// servicing its bus accesses with the given callbacks.
+//
+// If the CPU is jammed, stopped, waiting for an interrupt or held in reset,
+// only a single cycle is executed. This returns the number of cycles that
+// were executed.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+uint32_t chuck_cpu_step_instruction(struct ChuckCpu *cpu,
+                                    ChuckRead read,
+                                    ChuckWrite write,
+                                    void *user);
+
+// Start the reset sequence of the CPU with the next cycle.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+void chuck_cpu_reset(struct ChuckCpu *cpu);
+
+// Copy the registers of the CPU into `regs`.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `regs` must be
+// valid.
+void chuck_cpu_get_regs(const struct ChuckCpu *cpu, struct ChuckRegs *regs);
+
+// Replace the registers of the CPU with `regs`.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `regs` must be
+// valid.
+void chuck_cpu_set_regs(struct ChuckCpu *cpu, const struct ChuckRegs *regs);
+
+// Get the I/O control pins of the CPU, a combination of the `CHUCK_PIN_*`
+// constants.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+uint8_t chuck_cpu_get_pins(const struct ChuckCpu *cpu);
+
+// Replace the I/O control pins of the CPU, a combination of the `CHUCK_PIN_*`
+// constants, for example to assert `IRQ`.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+void chuck_cpu_set_pins(struct ChuckCpu *cpu, uint8_t pins);
+
+// Get the number of cycles executed since the CPU was created.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+uint64_t chuck_cpu_cycles(const struct ChuckCpu *cpu);
+
+// Save the complete state of the CPU into `buf`, which holds `len` bytes.
+//
+// This returns the size of the state, which is only written if it fits into
+// the buffer. Passing a null buffer (and a zero length) queries the size. A
+// size of zero is returned if the state could not be saved.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `buf` must be
+// null or valid for writes of `len` bytes.
+size_t chuck_cpu_save(const struct ChuckCpu *cpu, uint8_t *buf, size_t len);
+
+// Load the complete state of the CPU from `buf`, which holds `len` bytes.
+//
+// This returns `false` (and leaves the CPU untouched) if the state is
+// malformed or was saved by a CPU with another instruction set.
+//
+// # Safety
+//
+// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `buf` must be
+// valid for reads of `len` bytes.
+bool chuck_cpu_load(struct ChuckCpu *cpu, const uint8_t *buf, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHUCK_CPU_H */
@@ -0,0 +1,408 @@
+//! A C API for Chuck's cycle-accurate 6502 CPU core.
+//!
+//! This crate builds a `cdylib` (`libchuck_cpu_ffi.so`, `chuck_cpu_ffi.dll`,
+//! ...) that exposes the CPU core to C (and any language with a C FFI), for
+//! example to embed it inside an existing emulator. The matching header is
+//! `include/chuck_cpu.h`, which is generated by `cbindgen` from this crate:
+//!
+//! ```sh
+//! cbindgen --config crates/ffi/cbindgen.toml --output crates/ffi/include/chuck_cpu.h crates/ffi
+//! ```
+//!
+//! The CPU is an opaque [`ChuckCpu`] handle, and its bus accesses are serviced
+//! by a pair of read and write callbacks, which receive a user-defined pointer
+//! to the memory of the system:
+//!
+//! ```c
+//! #include "chuck_cpu.h"
+//!
+//! static uint8_t mem_read(void *user, uint16_t addr) {
+//!     return ((uint8_t *)user)[addr];
+//! }
+//!
+//! static void mem_write(void *user, uint16_t addr, uint8_t data) {
+//!     ((uint8_t *)user)[addr] = data;
+//! }
+//!
+//! int main(void) {
+//!     static uint8_t ram[0x10000];
+//!     ChuckCpu *cpu = chuck_cpu_new(CHUCK_MODEL_RICOH_2A03);
+//!
+//!     chuck_cpu_run(cpu, 7, mem_read, mem_write, ram);
+//!     chuck_cpu_free(cpu);
+//! }
+//! ```
+//!
+//! # Stability
+//!
+//! Every type and function of this API only gains new (appended) variants or
+//! new functions, which is versioned by [`CHUCK_CPU_API_VERSION`]. State saved
+//! by [`chuck_cpu_save`] uses the versioned snapshot format of the CPU core,
+//! so it can still be loaded after the library is upgraded.
+//!
+//! # Safety
+//!
+//! Unless documented otherwise, every pointer that is passed to a function of
+//! this API must be non-null and valid, and every handle must have been created
+//! by [`chuck_cpu_new`] (and not freed yet).
+
+use std::ffi::c_void;
+use std::{ptr, slice};
+
+use chuck_cpu::{Cpu, Flags, Nmos6502, Pins, Registers, Ricoh2A03, Wdc65C02};
+
+/// The version of this API, which is incremented whenever it is extended.
+pub const CHUCK_CPU_API_VERSION: u32 = 1;
+
+/// The `SYNC` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_SYNC: u8 = 1 << 0;
+/// The `IRQ` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_IRQ: u8 = 1 << 1;
+/// The `NMI` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_NMI: u8 = 1 << 2;
+/// The `RDY` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_RDY: u8 = 1 << 3;
+/// The `RES` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_RES: u8 = 1 << 4;
+/// The `SO` pin, see [`chuck_cpu_get_pins`].
+pub const CHUCK_PIN_SO: u8 = 1 << 5;
+
+/// The NES's Ricoh 2A03, an NMOS 6502 without the decimal mode, see
+/// [`chuck_cpu_new`].
+pub const CHUCK_MODEL_RICOH_2A03: u32 = 0;
+/// The original NMOS 6502, see [`chuck_cpu_new`].
+pub const CHUCK_MODEL_NMOS_6502: u32 = 1;
+/// The CMOS WDC 65C02S, see [`chuck_cpu_new`].
+pub const CHUCK_MODEL_WDC_65C02: u32 = 2;
+
+/// The registers of the CPU.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChuckRegs {
+    /// The program counter, `PC`.
+    pub pc: u16,
+    /// The general purpose accumulator, `A`.
+    pub a: u8,
+    /// The first index register, `X`.
+    pub x: u8,
+    /// The second index register, `Y`.
+    pub y: u8,
+    /// The stack pointer, `S`.
+    pub sp: u8,
+    /// The flags register, `P`.
+    pub p: u8,
+}
+
+/// A callback that services a read access of the CPU, returning the data at
+/// the given address.
+pub type ChuckRead = extern "C" fn(user: *mut c_void, addr: u16) -> u8;
+
+/// A callback that services a write access of the CPU, storing the given data
+/// at the given address.
+pub type ChuckWrite = extern "C" fn(user: *mut c_void, addr: u16, data: u8);
+
+/// The CPU of the emulated model.
+enum Core {
+    Ricoh2A03(Cpu<Ricoh2A03>),
+    Nmos6502(Cpu<Nmos6502>),
+    Wdc65C02(Cpu<Wdc65C02>),
+}
+
+/// Evaluate an expression with the CPU of a [`Core`], whatever its model is.
+macro_rules! with_cpu {
+    ($core:expr, $cpu:ident => $body:expr) => {
+        match $core {
+            Core::Ricoh2A03($cpu) => $body,
+            Core::Nmos6502($cpu) => $body,
+            Core::Wdc65C02($cpu) => $body,
+        }
+    };
+}
+
+/// An opaque handle to a CPU, see [`chuck_cpu_new`].
+///
+/// A handle may be moved between threads, but must not be used by two threads
+/// at the same time.
+pub struct ChuckCpu {
+    core: Core,
+}
+
+/// The memory of the system behind the callbacks of a C caller.
+struct Callbacks {
+    read: ChuckRead,
+    write: ChuckWrite,
+    user: *mut c_void,
+}
+
+impl Callbacks {
+    /// Service a bus access of the CPU.
+    fn service(&self, bus: &mut chuck_cpu::Bus) {
+        if bus.write {
+            (self.write)(self.user, bus.addr, bus.data);
+        } else {
+            bus.data = (self.read)(self.user, bus.addr);
+        }
+    }
+}
+
+/// Create a new CPU emulating the given model, one of the `CHUCK_MODEL_*`
+/// constants, with all registers zeroed.
+///
+/// The CPU starts with the reset sequence, and must be freed again with
+/// [`chuck_cpu_free`]. This returns a null pointer if the model is unknown.
+#[no_mangle]
+pub extern "C" fn chuck_cpu_new(model: u32) -> *mut ChuckCpu {
+    let core = match model {
+        CHUCK_MODEL_RICOH_2A03 => Core::Ricoh2A03(Cpu::new()),
+        CHUCK_MODEL_NMOS_6502 => Core::Nmos6502(Cpu::with_model(Nmos6502)),
+        CHUCK_MODEL_WDC_65C02 => Core::Wdc65C02(Cpu::with_model(Wdc65C02)),
+        _ => return ptr::null_mut(),
+    };
+
+    Box::into_raw(Box::new(ChuckCpu { core }))
+}
+
+/// Free a CPU created by [`chuck_cpu_new`].
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle or a null pointer (which does nothing), and
+/// must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_free(cpu: *mut ChuckCpu) {
+    if !cpu.is_null() {
+        // SAFETY: The handle was created by `Box::into_raw` in `chuck_cpu_new`
+        // and, per the contract of this function, is not used again.
+        drop(unsafe { Box::from_raw(cpu) });
+    }
+}
+
+/// Execute a single cycle of the CPU, servicing its bus access with the given
+/// callbacks.
+///
+/// The `user` pointer is passed to the callbacks as is.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_step(
+    cpu: *mut ChuckCpu,
+    read: ChuckRead,
+    write: ChuckWrite,
+    user: *mut c_void,
+) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+    let mem = Callbacks { read, write, user };
+
+    with_cpu!(&mut cpu.core, cpu => {
+        cpu.step();
+        mem.service(&mut cpu.bus);
+    });
+}
+
+/// Execute the given number of cycles of the CPU, servicing its bus accesses
+/// with the given callbacks.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_run(
+    cpu: *mut ChuckCpu,
+    cycles: u64,
+    read: ChuckRead,
+    write: ChuckWrite,
+    user: *mut c_void,
+) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+    let mem = Callbacks { read, write, user };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.run_cycles(cycles, |bus| mem.service(bus)));
+}
+
+/// Execute the CPU until the opcode of the next instruction is fetched,
+/// servicing its bus accesses with the given callbacks.
+///
+/// If the CPU is jammed, stopped, waiting for an interrupt or held in reset,
+/// only a single cycle is executed. This returns the number of cycles that
+/// were executed.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_step_instruction(
+    cpu: *mut ChuckCpu,
+    read: ChuckRead,
+    write: ChuckWrite,
+    user: *mut c_void,
+) -> u32 {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+    let mem = Callbacks { read, write, user };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.step_instruction(|bus| mem.service(bus)))
+}
+
+/// Start the reset sequence of the CPU with the next cycle.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_reset(cpu: *mut ChuckCpu) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.reset());
+}
+
+/// Copy the registers of the CPU into `regs`.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `regs` must be
+/// valid.
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_get_regs(cpu: *const ChuckCpu, regs: *mut ChuckRegs) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &*cpu };
+    let Registers {
+        flags,
+        a,
+        x,
+        y,
+        sp,
+        pc,
+    } = with_cpu!(&cpu.core, cpu => cpu.regs.clone());
+
+    // SAFETY: The registers are valid per the contract of this function.
+    unsafe {
+        regs.write(ChuckRegs {
+            pc,
+            a,
+            x,
+            y,
+            sp,
+            p: flags.bits(),
+        });
+    }
+}
+
+/// Replace the registers of the CPU with `regs`.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `regs` must be
+/// valid.
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_set_regs(cpu: *mut ChuckCpu, regs: *const ChuckRegs) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+    // SAFETY: The registers are valid per the contract of this function.
+    let ChuckRegs { pc, a, x, y, sp, p } = unsafe { regs.read() };
+
+    let regs = Registers {
+        flags: Flags::from_bits_retain(p),
+        a,
+        x,
+        y,
+        sp,
+        pc,
+    };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.regs = regs);
+}
+
+/// Get the I/O control pins of the CPU, a combination of the `CHUCK_PIN_*`
+/// constants.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_get_pins(cpu: *const ChuckCpu) -> u8 {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &*cpu };
+
+    with_cpu!(&cpu.core, cpu => cpu.pins.bits())
+}
+
+/// Replace the I/O control pins of the CPU, a combination of the `CHUCK_PIN_*`
+/// constants, for example to assert `IRQ`.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_set_pins(cpu: *mut ChuckCpu, pins: u8) {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.pins = Pins::from_bits_truncate(pins));
+}
+
+/// Get the number of cycles executed since the CPU was created.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_cycles(cpu: *const ChuckCpu) -> u64 {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &*cpu };
+
+    with_cpu!(&cpu.core, cpu => cpu.cycles)
+}
+
+/// Save the complete state of the CPU into `buf`, which holds `len` bytes.
+///
+/// This returns the size of the state, which is only written if it fits into
+/// the buffer. Passing a null buffer (and a zero length) queries the size. A
+/// size of zero is returned if the state could not be saved.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `buf` must be
+/// null or valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_save(cpu: *const ChuckCpu, buf: *mut u8, len: usize) -> usize {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &*cpu };
+
+    let mut state = Vec::new();
+    if with_cpu!(&cpu.core, cpu => cpu.save(&mut state)).is_err() {
+        return 0;
+    }
+
+    if !buf.is_null() && len >= state.len() {
+        // SAFETY: The buffer is valid for `len` bytes per the contract of this
+        // function, which includes the first `state.len()` bytes.
+        unsafe { slice::from_raw_parts_mut(buf, state.len()) }.copy_from_slice(&state);
+    }
+
+    state.len()
+}
+
+/// Load the complete state of the CPU from `buf`, which holds `len` bytes.
+///
+/// This returns `false` (and leaves the CPU untouched) if the state is
+/// malformed or was saved by a CPU with another instruction set.
+///
+/// # Safety
+///
+/// `cpu` must be a valid handle, see [`chuck_cpu_new`], and `buf` must be
+/// valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chuck_cpu_load(cpu: *mut ChuckCpu, buf: *const u8, len: usize) -> bool {
+    // SAFETY: The handle is valid per the contract of this function.
+    let cpu = unsafe { &mut *cpu };
+    // SAFETY: The buffer is valid for `len` bytes per the contract of this
+    // function.
+    let mut state = unsafe { slice::from_raw_parts(buf, len) };
+
+    with_cpu!(&mut cpu.core, cpu => cpu.load(&mut state)).is_ok()
+}
@@ -0,0 +1,921 @@
+//! The window of the frontend, which runs the loaded game.
+//!
+//! The window is redrawn on every vertical blank of the display, when the
+//! console runs the frames which are due by then. The frames are timed by the
+//! frame rate of the console, so a display refreshing at another rate than
+//! the console shows some frames twice (or skips some).
+//!
+//! While fast-forwarding, the frames aren't timed, but run for most of the
+//! time between the redraws, and only one of every few frames is output.
+//!
+//! F10 rebinds the buttons of the first and then of the second controller,
+//! one button after another, as prompted by the title of the window: the
+//! next pressed key or button of the gamepad of the controller is bound to
+//! the button, while Backspace keeps its bindings and Escape stops early.
+//! The bindings are then saved into the configuration file.
+//!
+//! With the `audio` feature, [ and ] lower and raise the latency of the audio
+//! by 10 ms, printing how often the audio device ran out of samples since the
+//! last change, which is printed on quitting as well.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chuck_input::{Controller, Crosshair, Pointer, Port, TurboFile, Zapper};
+use chuck_lua::Script;
+use chuck_nes::gdb::Server;
+use chuck_nes::profile::Order;
+use chuck_nes::symbols::Symbols;
+use chuck_nes::{Nes, Region, HEIGHT, WIDTH};
+use chuck_video::palette::Palette;
+use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalSize, PhysicalPosition};
+use winit::event::{ElementState, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent};
+use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{Fullscreen, Window, WindowId};
+
+use crate::capture::{self, Recording};
+use crate::config::Config;
+use crate::game::Game;
+use crate::input::{Held, Keyboard, Rebinding};
+use crate::video::{self, Renderer};
+use crate::{Args, SoundModule};
+
+/// The number of frames the console may fall behind, e.g. while the window
+/// is dragged, before it skips them instead of catching up.
+const MAX_LAG: u32 = 4;
+
+/// The time spent running frames before every redraw while fast-forwarding,
+/// which leaves the rest of a refresh of a 60 Hz display for the rendering.
+const FAST_FORWARD_TIME: Duration = Duration::from_millis(12);
+
+/// The largest factor by which the frames are slowed down.
+const MAX_SLOWDOWN: u32 = 8;
+
+/// The step by which the hotkeys change the latency of the audio, in
+/// milliseconds.
+#[cfg(feature = "audio")]
+const LATENCY_STEP: u32 = 10;
+
+/// The number of shown frames whose average times are printed at once, with
+/// the `timing` feature.
+#[cfg(feature = "timing")]
+const TIMED_FRAMES: u32 = 60;
+
+/// The keys selecting the slots of the save states.
+const SLOTS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// The frontend.
+pub struct App {
+    /// The options given on the command line.
+    args: Args,
+    /// The configuration.
+    config: Config,
+    /// The palette coloring the pictures.
+    palette: Palette,
+    /// The window, once it was created.
+    window: Option<Arc<Window>>,
+    /// The renderer into the window.
+    renderer: Option<Renderer>,
+    /// The loaded game.
+    game: Option<Game>,
+    /// The picture of the last frame, as `0x00RRGGBB`.
+    picture: Vec<u32>,
+    /// The buttons held on the keyboard.
+    keyboard: Keyboard,
+    /// The gamepads, unless they can't be listened to.
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<crate::input::Gamepads>,
+    /// The audio output, unless there's no audio device.
+    #[cfg(feature = "audio")]
+    audio: Option<crate::audio::Output>,
+    /// The server for a debugger, if enabled.
+    debugger: Option<Server>,
+    /// A flag denoting if the current frame started, but the debugger halted
+    /// the console before it ended.
+    interrupted: bool,
+    /// The running script, if any.
+    script: Option<Script>,
+    /// The symbols of the program.
+    symbols: Symbols,
+    /// The recording of the frames, if recording.
+    recording: Option<Recording>,
+    /// The rebinding of the buttons, if rebinding.
+    rebinding: Option<Rebinding>,
+    /// The slot of the save states.
+    slot: u8,
+    /// The index of the next barcode of `--barcode` to read.
+    barcode: usize,
+    /// A flag denoting if the console is paused.
+    paused: bool,
+    /// A flag denoting if the console is fast-forwarded.
+    fast_forward: bool,
+    /// The factor by which the frames are slowed down, a power of two.
+    slowdown: u32,
+    /// The time the next frame is due.
+    next_frame: Instant,
+    /// The time of the last automatic save.
+    autosaved: Instant,
+    /// The error which closed the window.
+    error: Option<Box<dyn Error>>,
+    /// The total times of the subsystems of the console during the last few
+    /// shown frames, and the number of these frames.
+    #[cfg(feature = "timing")]
+    stats: (chuck_nes::timing::FrameStats, u32),
+}
+
+impl App {
+    /// Create the frontend with the given options, configuration, palette,
+    /// server for a debugger, script and symbols.
+    pub fn new(
+        args: Args,
+        config: Config,
+        palette: Palette,
+        debugger: Option<Server>,
+        script: Option<Script>,
+        symbols: Symbols,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "audio")]
+            audio: crate::audio::Output::open(Region::Ntsc.cpu_clock(), &config.audio)
+                .inspect_err(|error| eprintln!("no audio: {error}"))
+                .ok(),
+            keyboard: Keyboard::new(&config),
+            #[cfg(feature = "gamepad")]
+            gamepads: crate::input::Gamepads::new(&config)
+                .inspect_err(|error| eprintln!("no gamepads: {error}"))
+                .ok(),
+            args,
+            config,
+            palette,
+            window: None,
+            renderer: None,
+            game: None,
+            picture: vec![0; WIDTH * HEIGHT],
+            debugger,
+            interrupted: false,
+            script,
+            symbols,
+            recording: None,
+            rebinding: None,
+            slot: 0,
+            barcode: 0,
+            paused: false,
+            fast_forward: false,
+            slowdown: 1,
+            next_frame: Instant::now(),
+            autosaved: Instant::now(),
+            error: None,
+            #[cfg(feature = "timing")]
+            stats: Default::default(),
+        }
+    }
+
+    /// Return the error which closed the window, if any.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.error.map_or(Ok(()), Err)
+    }
+
+    /// Return the pixel aspect ratio of the pictures of the given region.
+    fn aspect(&self, region: Region) -> (u32, u32) {
+        if self.args.square_pixels {
+            (1, 1)
+        } else {
+            video::pixel_aspect(region)
+        }
+    }
+
+    /// Load the game at the given path, replacing the loaded one.
+    fn open(&mut self, path: &Path) {
+        let mut game = match Game::open(path, self.args.game_genie.as_deref()) {
+            Ok(game) => game,
+            Err(error) => return eprintln!("failed to load {}: {error}", path.display()),
+        };
+
+        self.close();
+        game.nes.set_overclock(self.args.overclock);
+        #[cfg(feature = "timing")]
+        game.nes.time_subsystems(true);
+        if self.args.profile.is_some() || self.args.flamegraph.is_some() {
+            game.nes.profile_cpu(true);
+        }
+        if let Some(offscreen) = self.args.zapper {
+            let mut zapper = Zapper::new();
+            zapper.set_offscreen(offscreen.into());
+            zapper.set_crosshair(self.args.crosshair.then(Crosshair::default));
+            game.nes.input_mut().plug(Port::Two, Box::new(zapper));
+        }
+        if let Some(path) = &self.args.turbo_file {
+            let mut turbo_file = TurboFile::new();
+            match File::open(path) {
+                Ok(file) => {
+                    if let Err(error) = turbo_file.load_data(&mut BufReader::new(file)) {
+                        eprintln!("failed to load the turbo file: {error}");
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => eprintln!("failed to load the turbo file: {error}"),
+            }
+            game.nes.input_mut().plug_expansion(Box::new(turbo_file));
+        }
+        game.nes
+            .plug_epsm(self.args.sound_module == Some(SoundModule::Epsm));
+        let region = game.nes.region();
+        let aspect = self.aspect(region);
+        if let Some(renderer) = &mut self.renderer {
+            renderer.set_aspect(aspect);
+        }
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            audio.set_input_rate(region.cpu_clock() / f64::from(self.slowdown));
+        }
+
+        if let Some(metadata) = game.auto_metadata() {
+            if !self.config.autosave.resume {
+                println!(
+                    "F6 resumes where you left off: {}",
+                    game.describe(&metadata)
+                );
+            } else if let Err(error) = game.load_auto() {
+                eprintln!("failed to resume: {error}");
+            } else {
+                println!("resumed where you left off: {}", game.describe(&metadata));
+            }
+        }
+
+        self.game = Some(game);
+        self.paused = false;
+        self.next_frame = Instant::now();
+        self.autosaved = Instant::now();
+        self.update_title();
+    }
+
+    /// Save the save RAM, the Turbo File and the automatic save state of the
+    /// loaded game, finish the recording of its frames, and write the profile
+    /// of its CPU if it's profiled.
+    fn close(&mut self) {
+        self.stop_recording();
+        self.save_turbo_file();
+        let Some(game) = &mut self.game else { return };
+        if let Err(error) = game.save_sram() {
+            eprintln!("failed to save the save ram: {error}");
+        }
+        for result in [game.save_auto(), game.finish_auto()] {
+            if let Err(error) = result {
+                eprintln!("failed to save the automatic save state: {error}");
+            }
+        }
+
+        let Some(profiler) = game.nes.profiler() else {
+            return;
+        };
+        if let Some(path) = &self.args.profile {
+            let write =
+                |file: &mut dyn Write| profiler.write_report(&self.symbols, Order::Inclusive, file);
+            if let Err(error) = write_file(path, write) {
+                eprintln!("failed to write the profile: {error}");
+            }
+        }
+        if let Some(path) = &self.args.flamegraph {
+            let write = |file: &mut dyn Write| profiler.write_folded(&self.symbols, file);
+            if let Err(error) = write_file(path, write) {
+                eprintln!("failed to write the flame graph: {error}");
+            }
+        }
+    }
+
+    /// Show the name of the game, the slot, the speed and if the console is
+    /// paused in the title of the window, or the next button to rebind.
+    fn update_title(&self) {
+        let Some(window) = &self.window else { return };
+        if let Some(rebinding) = self.rebinding {
+            let title = format!(
+                "Chuck - press the key or button for {} of controller {} \
+                 (Backspace keeps it, Escape stops)",
+                rebinding.button().name(),
+                rebinding.controller() + 1
+            );
+            return window.set_title(&title);
+        }
+
+        let title = self.game.as_ref().map_or_else(
+            || "Chuck - drop a ROM here".to_owned(),
+            |game| {
+                let speed = match self.slowdown {
+                    1 => String::new(),
+                    slowdown => format!(", 1/{slowdown} speed"),
+                };
+                let paused = if self.paused { ", paused" } else { "" };
+                format!(
+                    "{} (slot {}{speed}{paused}) - Chuck",
+                    game.name(),
+                    self.slot
+                )
+            },
+        );
+
+        window.set_title(&title);
+    }
+
+    /// Return the buttons held on the two controllers, given those held on
+    /// the gamepads, which are all released while rebinding them.
+    fn held(&self, gamepads: [Held; 2]) -> [Held; 2] {
+        let keys = self.keyboard.held();
+        if self.rebinding.is_some() {
+            [Held::default(); 2]
+        } else {
+            [keys[0].union(gamepads[0]), keys[1].union(gamepads[1])]
+        }
+    }
+
+    /// Return the buttons held on the gamepads, or rebind the next button to
+    /// a pressed button of a gamepad while rebinding.
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) -> [Held; 2] {
+        let Some(gamepads) = &mut self.gamepads else {
+            return [Held::default(); 2];
+        };
+
+        let held = gamepads.poll();
+        let pressed = gamepads.take_pressed();
+        if let (Some(rebinding), Some(input)) = (self.rebinding, pressed) {
+            let controller = rebinding.controller();
+            self.config
+                .bind_gamepad(controller, rebinding.button(), input);
+            self.next_rebinding();
+        }
+        held
+    }
+
+    /// Run the frames which are due, then show the last one.
+    fn redraw(&mut self) {
+        #[cfg(feature = "gamepad")]
+        let gamepads = self.poll_gamepads();
+        #[cfg(not(feature = "gamepad"))]
+        let gamepads = [Held::default(); 2];
+        let held = self.held(gamepads);
+        if let Some(game) = self.game.as_mut().filter(|_| !self.paused) {
+            let now = Instant::now();
+            let period = Duration::from_secs_f64(game.nes.region().frame_rate().recip());
+            let period = period * self.slowdown;
+            if self.fast_forward
+                || now.saturating_duration_since(self.next_frame) > period * MAX_LAG
+            {
+                self.next_frame = now;
+            }
+
+            let deadline = now + FAST_FORWARD_TIME;
+            loop {
+                if self.fast_forward {
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                } else if self.next_frame <= now {
+                    self.next_frame += period;
+                } else {
+                    break;
+                }
+
+                // Only one of every few frames is output while fast-forwarding.
+                let shown = !self.fast_forward
+                    || game.nes.ppu().frame() % u64::from(self.args.frame_skip) == 0;
+
+                let turbo = game.nes.ppu().frame() / u64::from(self.config.turbo_period) % 2 == 0;
+                let ports = game.nes.input_mut();
+                for (port, held) in [Port::One, Port::Two].into_iter().zip(held) {
+                    if let Some(controller) = ports.device_mut::<Controller>(port) {
+                        controller.set_buttons(held.pressed(turbo));
+                    }
+                }
+                if !self.interrupted {
+                    run_script(&mut self.script, |script| {
+                        script.before_frame(&mut game.nes)
+                    });
+                }
+
+                let frame = match &mut self.debugger {
+                    Some(server) => {
+                        if let Err(error) = server.poll(&mut game.nes) {
+                            eprintln!("lost the debugger: {error}");
+                        }
+                        if server.is_halted() {
+                            self.interrupted = true;
+                            break;
+                        }
+
+                        self.interrupted = false;
+                        match game.nes.debug_run_frame() {
+                            Ok(frame) => frame,
+                            Err(reason) => {
+                                self.interrupted = true;
+                                if let Err(error) = server.stop(&mut game.nes, reason) {
+                                    eprintln!("lost the debugger: {error}");
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    None if shown => game.nes.run_frame(),
+                    None => game.nes.run_frame_headless(),
+                };
+                if shown {
+                    self.palette.apply(frame.pixels, &mut self.picture);
+                }
+                #[cfg(feature = "audio")]
+                if let Some(audio) = self.audio.as_ref().filter(|_| !self.fast_forward) {
+                    audio.push(frame.samples);
+                }
+
+                run_script(&mut self.script, |script| script.after_frame(&mut game.nes));
+                if !shown {
+                    continue;
+                }
+                draw_overlays(self.script.as_ref(), &mut game.nes, &mut self.picture);
+
+                if let Some(recording) = &mut self.recording {
+                    if let Err(error) = recording.push(&self.picture) {
+                        eprintln!("stopped the recording: {error}");
+                        self.recording = None;
+                    }
+                }
+            }
+
+            self.save_periodically();
+        }
+
+        #[cfg(feature = "timing")]
+        let presented = Instant::now();
+        if let Some(renderer) = &mut self.renderer {
+            renderer.render(&self.picture);
+        }
+        #[cfg(feature = "timing")]
+        if let Some(game) = &mut self.game {
+            game.nes.record_presentation(presented.elapsed());
+            self.add_stats();
+        }
+    }
+
+    /// Save the save RAM once it needs to be flushed, the Turbo File once it
+    /// was written, and the automatic save state once its interval elapsed.
+    fn save_periodically(&mut self) {
+        self.save_turbo_file();
+        let Some(game) = &mut self.game else { return };
+        if game.nes.sram_needs_flush() {
+            if let Err(error) = game.save_sram() {
+                eprintln!("failed to save the save ram: {error}");
+            }
+        }
+
+        let interval = Duration::from_secs(self.config.autosave.interval.into());
+        if !interval.is_zero() && self.autosaved.elapsed() >= interval {
+            self.autosaved = Instant::now();
+            if let Err(error) = game.save_auto() {
+                eprintln!("failed to save the automatic save state: {error}");
+            }
+        }
+    }
+
+    /// Save the storage of the Turbo File into its file, if it changed.
+    fn save_turbo_file(&mut self) {
+        let Some(path) = &self.args.turbo_file else {
+            return;
+        };
+        let Some(turbo_file) = self
+            .game
+            .as_mut()
+            .and_then(|game| game.nes.input_mut().expansion_mut::<TurboFile>())
+            .filter(|turbo_file| turbo_file.is_dirty())
+        else {
+            return;
+        };
+
+        if let Err(error) = write_file(path, |file| turbo_file.save_data(file)) {
+            eprintln!("failed to save the turbo file: {error}");
+        }
+    }
+
+    /// Add the times of the subsystems of the console during the last frame
+    /// to the totals, and print their averages every few frames.
+    #[cfg(feature = "timing")]
+    fn add_stats(&mut self) {
+        let Some(stats) = self.game.as_ref().and_then(|game| game.nes.frame_stats()) else {
+            return;
+        };
+
+        let (total, frames) = &mut self.stats;
+        total.cpu += stats.cpu;
+        total.ppu += stats.ppu;
+        total.apu += stats.apu;
+        total.mapper += stats.mapper;
+        total.presentation += stats.presentation;
+        *frames += 1;
+        if *frames < TIMED_FRAMES {
+            return;
+        }
+
+        let average = |time: Duration| (time / *frames).as_secs_f64() * 1000.0;
+        eprintln!(
+            "per frame: cpu {:.2} ms, ppu {:.2} ms, apu {:.2} ms, mapper {:.2} ms, presentation {:.2} ms",
+            average(total.cpu),
+            average(total.ppu),
+            average(total.apu),
+            average(total.mapper),
+            average(total.presentation),
+        );
+        self.stats = Default::default();
+    }
+
+    /// Handle a hotkey.
+    fn hotkey(&mut self, event_loop: &ActiveEventLoop, code: KeyCode) {
+        if let Some(slot) = SLOTS.iter().position(|&key| key == code) {
+            self.slot = u8::try_from(slot).unwrap_or(0);
+            if let Some(game) = &self.game {
+                match game.state_metadata(self.slot) {
+                    Ok(metadata) => println!("slot {}: {}", self.slot, game.describe(&metadata)),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                        println!("slot {} is empty", self.slot);
+                    }
+                    Err(error) => eprintln!("failed to read slot {}: {error}", self.slot),
+                }
+            }
+            return self.update_title();
+        }
+
+        match code {
+            KeyCode::Escape => event_loop.exit(),
+            KeyCode::F11 => {
+                if let Some(window) = &self.window {
+                    let fullscreen = window.fullscreen().is_none();
+                    window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+                }
+            }
+            KeyCode::F10 => {
+                self.keyboard.release();
+                self.rebinding = Some(Rebinding::default());
+                self.update_title();
+            }
+            KeyCode::F4 => self.read_barcode(),
+            KeyCode::Minus => self.set_slowdown(self.slowdown * 2),
+            KeyCode::Equal => self.set_slowdown(self.slowdown / 2),
+            #[cfg(feature = "audio")]
+            KeyCode::BracketLeft => {
+                self.set_latency(self.config.audio.latency.saturating_sub(LATENCY_STEP));
+            }
+            #[cfg(feature = "audio")]
+            KeyCode::BracketRight => self.set_latency(self.config.audio.latency + LATENCY_STEP),
+            _ => {}
+        }
+
+        let Some(game) = &mut self.game else { return };
+        match code {
+            KeyCode::KeyP => {
+                self.paused = !self.paused;
+                self.next_frame = Instant::now();
+                #[cfg(feature = "audio")]
+                if let Some(audio) = &self.audio {
+                    audio.clear();
+                }
+                self.update_title();
+            }
+            KeyCode::F2 => game.nes.reset(),
+            KeyCode::F3 => game.nes.power_cycle(),
+            KeyCode::F5 => match game.save_state(self.slot) {
+                Ok(()) => println!("saved the state into slot {}", self.slot),
+                Err(error) => eprintln!("failed to save the state: {error}"),
+            },
+            KeyCode::F6 => match game.load_auto() {
+                Ok(metadata) => {
+                    println!("resumed where you left off: {}", game.describe(&metadata));
+                }
+                Err(error) => eprintln!("failed to resume: {error}"),
+            },
+            KeyCode::F7 => match game.load_state(self.slot) {
+                Ok(metadata) => println!(
+                    "loaded the state from slot {}: {}",
+                    self.slot,
+                    game.describe(&metadata)
+                ),
+                Err(error) => eprintln!("failed to load the state: {error}"),
+            },
+            KeyCode::F12 => {
+                let path = game.capture_path("png");
+                match capture::save_screenshot(&path, &self.picture) {
+                    Ok(()) => println!("saved a screenshot into {}", path.display()),
+                    Err(error) => eprintln!("failed to save the screenshot: {error}"),
+                }
+            }
+            KeyCode::F8 => {
+                let path = game.capture_path("png");
+                let write = |file: &mut dyn Write| game.nes.save_screenshot(&self.palette, file);
+                match write_file(&path, write) {
+                    Ok(()) => println!("saved a screenshot into {}", path.display()),
+                    Err(error) => eprintln!("failed to save the screenshot: {error}"),
+                }
+            }
+            KeyCode::F9 if self.recording.is_some() => self.stop_recording(),
+            KeyCode::F9 => {
+                let path = game.capture_path(if self.args.raw_frames { "rgb" } else { "png" });
+                let period = Duration::from_secs_f64(game.nes.region().frame_rate().recip());
+                match Recording::create(&path, self.args.raw_frames, period) {
+                    Ok(recording) => {
+                        println!("recording the frames into {}", path.display());
+                        self.recording = Some(recording);
+                    }
+                    Err(error) => eprintln!("failed to start the recording: {error}"),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Read the next barcode of `--barcode` with the barcode reader of the
+    /// cartridge, if it has one.
+    fn read_barcode(&mut self) {
+        let Some(game) = &mut self.game else { return };
+        let Some(reader) = game.nes.cartridge_mut().barcode_reader_mut() else {
+            return eprintln!("the cartridge has no barcode reader");
+        };
+        if self.args.barcode.is_empty() {
+            return eprintln!("there are no barcodes to read, see --barcode");
+        }
+
+        let index = self.barcode % self.args.barcode.len();
+        reader.scan(&self.args.barcode[index]);
+        println!(
+            "reading barcode {} of {}",
+            index + 1,
+            self.args.barcode.len()
+        );
+        self.barcode = index + 1;
+    }
+
+    /// Slow the frames down by the given factor, clamped to the supported
+    /// factors, which slows the audio down as well.
+    fn set_slowdown(&mut self, slowdown: u32) {
+        self.slowdown = slowdown.clamp(1, MAX_SLOWDOWN);
+        #[cfg(feature = "audio")]
+        if let (Some(audio), Some(game)) = (&self.audio, &self.game) {
+            audio.set_input_rate(game.nes.region().cpu_clock() / f64::from(self.slowdown));
+        }
+        self.update_title();
+    }
+
+    /// Change the latency of the audio, clamped to the supported latencies,
+    /// and print the statistics of the audio since the last change.
+    #[cfg(feature = "audio")]
+    fn set_latency(&mut self, latency: u32) {
+        use crate::config::Audio;
+
+        let latency = latency.clamp(Audio::MIN_LATENCY, Audio::MAX_LATENCY);
+        self.config.audio.latency = latency;
+        if let Some(audio) = &self.audio {
+            self.print_audio_stats();
+            audio.set_latency(latency);
+            println!("set the latency of the audio to {latency} ms");
+        }
+    }
+
+    /// Print the statistics of the buffer of the audio since they were last
+    /// printed.
+    #[cfg(feature = "audio")]
+    fn print_audio_stats(&self) {
+        let Some(audio) = &self.audio else { return };
+        let stats = audio.take_stats();
+        println!(
+            "the audio ran out {} times ({} samples) and overflowed {} times at {} ms",
+            stats.underruns, stats.missing, stats.overflows, self.config.audio.latency
+        );
+    }
+
+    /// Start or stop fast-forwarding.
+    fn set_fast_forward(&mut self, enabled: bool) {
+        self.fast_forward = enabled;
+        self.next_frame = Instant::now();
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            audio.clear();
+        }
+    }
+
+    /// Finish the recording of the frames, if recording.
+    fn stop_recording(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        match recording.finish() {
+            Ok(()) => println!("stopped the recording"),
+            Err(error) => eprintln!("failed to finish the recording: {error}"),
+        }
+    }
+
+    /// Go on to the next button of the rebinding, or finish it after the last
+    /// button, which saves the bindings into the configuration file.
+    fn next_rebinding(&mut self) {
+        self.rebinding = self.rebinding.and_then(Rebinding::next);
+        if self.rebinding.is_none() {
+            self.finish_rebinding();
+        }
+        self.update_title();
+    }
+
+    /// Finish the rebinding, which saves the bindings into the configuration
+    /// file.
+    fn finish_rebinding(&mut self) {
+        self.rebinding = None;
+        self.keyboard.set_bindings(&self.config);
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = &mut self.gamepads {
+            gamepads.set_bindings(&self.config);
+        }
+
+        let Some(path) = &self.args.config else {
+            return eprintln!("no configuration file to save the bindings into");
+        };
+        match self.config.save(path) {
+            Ok(()) => println!("saved the bindings into {}", path.display()),
+            Err(error) => eprintln!("failed to save the bindings: {error}"),
+        }
+    }
+
+    /// Return the Zapper of the loaded game, if it's plugged in.
+    fn zapper(&mut self) -> Option<&mut Zapper> {
+        let game = self.game.as_mut()?;
+        game.nes.input_mut().device_mut::<Zapper>(Port::Two)
+    }
+
+    /// Aim the Zapper at a position within the window, or off the screen.
+    fn aim(&mut self, position: Option<PhysicalPosition<f64>>) {
+        let pointer = position.zip(self.renderer.as_ref());
+        let pointer: Option<Pointer> =
+            pointer.map(|(position, renderer)| renderer.pointer(position));
+        if let Some(zapper) = self.zapper() {
+            zapper.set_pointer(pointer);
+        }
+    }
+
+    /// Aim the Zapper by a touch, which holds the trigger until it ends.
+    fn touch(&mut self, touch: &Touch) {
+        self.aim(Some(touch.location));
+        let touching = matches!(touch.phase, TouchPhase::Started | TouchPhase::Moved);
+        if let Some(zapper) = self.zapper() {
+            zapper.set_trigger(touching);
+        }
+    }
+
+    /// Handle a key press or release.
+    fn key(&mut self, event_loop: &ActiveEventLoop, event: &KeyEvent) {
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+
+        let pressed = event.state.is_pressed();
+        if let Some(rebinding) = self.rebinding {
+            if pressed && !event.repeat {
+                match code {
+                    KeyCode::Escape => {
+                        self.finish_rebinding();
+                        self.update_title();
+                    }
+                    KeyCode::Backspace => self.next_rebinding(),
+                    _ => {
+                        let controller = rebinding.controller();
+                        self.config.bind_key(controller, rebinding.button(), code);
+                        self.next_rebinding();
+                    }
+                }
+            }
+        } else if code == KeyCode::Tab {
+            if pressed != self.fast_forward {
+                self.set_fast_forward(pressed);
+            }
+        } else if !self.keyboard.key(code, pressed) && pressed && !event.repeat {
+            self.hotkey(event_loop, code);
+        }
+    }
+}
+
+/// Create the file at the given path, and write it with the given function.
+fn write_file(path: &Path, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write(&mut file)?;
+    file.flush()
+}
+
+/// Draw the drawings of the script and the crosshair of the Zapper, if any,
+/// on top of the picture.
+fn draw_overlays(script: Option<&Script>, nes: &mut Nes, picture: &mut [u32]) {
+    if let Some(script) = script {
+        script.overlay().draw(picture);
+    }
+    if let Some(zapper) = nes.input_mut().device_mut::<Zapper>(Port::Two) {
+        zapper.draw_crosshair(picture);
+    }
+}
+
+/// Run the script with the given function, which stops the script if it
+/// raises an error.
+fn run_script(
+    script: &mut Option<Script>,
+    f: impl FnOnce(&mut Script) -> Result<(), chuck_lua::Error>,
+) {
+    if let Some(error) = script.as_mut().and_then(|script| f(script).err()) {
+        eprintln!("stopped the script: {error}");
+        *script = None;
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let aspect = self.aspect(Region::Ntsc);
+        let size = video::size(self.args.scale, aspect);
+        let minimum = video::size(1, aspect);
+        let attributes = Window::default_attributes()
+            .with_inner_size(LogicalSize::new(size.width, size.height))
+            .with_min_inner_size(LogicalSize::new(minimum.width, minimum.height));
+
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(error) => {
+                self.error = Some(error.into());
+                return event_loop.exit();
+            }
+        };
+
+        match Renderer::new(Arc::clone(&window), aspect) {
+            Ok(renderer) => self.renderer = Some(renderer),
+            Err(error) => {
+                self.error = Some(error);
+                return event_loop.exit();
+            }
+        }
+
+        self.window = Some(Arc::clone(&window));
+        if let Some(path) = self.args.rom.clone() {
+            self.open(&path);
+        }
+        self.update_title();
+        window.request_redraw();
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.resize(size);
+                }
+            }
+            WindowEvent::DroppedFile(path) => self.open(&path),
+            WindowEvent::KeyboardInput { event, .. } => self.key(event_loop, &event),
+            WindowEvent::CursorMoved { position, .. } => self.aim(Some(position)),
+            WindowEvent::CursorLeft { .. } => self.aim(None),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if let Some(zapper) = self.zapper() {
+                    zapper.set_trigger(state == ElementState::Pressed);
+                }
+            }
+            WindowEvent::Touch(touch) => self.touch(&touch),
+            WindowEvent::Focused(false) => {
+                self.keyboard.release();
+                self.set_fast_forward(false);
+            }
+            WindowEvent::RedrawRequested => {
+                self.redraw();
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        self.close();
+        #[cfg(feature = "audio")]
+        self.print_audio_stats();
+    }
+}
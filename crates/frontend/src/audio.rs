@@ -0,0 +1,152 @@
+//! The playback of the audio of the console by the default audio device.
+//!
+//! The samples of every frame are resampled to the rate of the device, which
+//! pulls them from another thread. The dynamic rate control of the resampler
+//! keeps the buffer between them filled, since the frames are paced by the
+//! display rather than by the device.
+//!
+//! The size of the buffer of the device, the latency and the dynamic rate
+//! control are set by the configuration, see [`Audio`]. The latency can be
+//! changed while playing, and the underruns of the buffer are counted to
+//! tune it, see [`Stats`].
+
+use std::error::Error;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use chuck_audio::{Resampler, Stats};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    BufferSize, FromSample, SampleFormat, SizedSample, Stream, StreamConfig, SupportedBufferSize,
+};
+
+use crate::config::{Audio, Quality};
+
+/// The output to the audio device.
+pub struct Output {
+    /// The stream of the samples to the device, which plays while it isn't
+    /// dropped.
+    _stream: Stream,
+    /// The resampler, shared with the thread of the stream.
+    resampler: Arc<Mutex<Resampler>>,
+}
+
+impl Output {
+    /// Open the default audio device with the given settings, for samples at
+    /// the given rate (the CPU clock of the console).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there's no audio device or it can't be opened.
+    pub fn open(input_rate: f64, settings: &Audio) -> Result<Self, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio device")?;
+        let supported = device.default_output_config()?;
+        let mut config = supported.config();
+        if let Some(size) = settings.buffer_size {
+            config.buffer_size = BufferSize::Fixed(match *supported.buffer_size() {
+                SupportedBufferSize::Range { min, max } => size.clamp(min, max),
+                SupportedBufferSize::Unknown => size,
+            });
+        }
+
+        let mut resampler = Resampler::new(input_rate, config.sample_rate.0);
+        resampler.set_rate_control(settings.rate_control);
+        resampler.set_max_deviation(settings.max_deviation / 100.0);
+        resampler.set_latency(latency(settings.latency));
+        resampler.set_quality(match settings.quality {
+            Quality::Hold => chuck_audio::Quality::Hold,
+            Quality::Linear => chuck_audio::Quality::Linear,
+            Quality::Sinc => chuck_audio::Quality::Sinc,
+        });
+        let resampler = Arc::new(Mutex::new(resampler));
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => stream::<f32>(&device, &config, &resampler)?,
+            SampleFormat::I16 => stream::<i16>(&device, &config, &resampler)?,
+            SampleFormat::U16 => stream::<u16>(&device, &config, &resampler)?,
+            format => return Err(format!("unsupported sample format {format}").into()),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            resampler,
+        })
+    }
+
+    /// Play the samples of a frame.
+    pub fn push(&self, samples: &[f32]) {
+        self.resampler().push(samples);
+    }
+
+    /// Change the rate of the samples, for a console of another region.
+    pub fn set_input_rate(&self, rate: f64) {
+        let mut resampler = self.resampler();
+        resampler.set_input_rate(rate);
+        resampler.clear();
+    }
+
+    /// Change the latency, in milliseconds.
+    pub fn set_latency(&self, latency: u32) {
+        self.resampler().set_latency(self::latency(latency));
+    }
+
+    /// Return the statistics of the buffer since the last call, and reset
+    /// them.
+    pub fn take_stats(&self) -> Stats {
+        let mut resampler = self.resampler();
+        let stats = resampler.stats();
+        resampler.reset_stats();
+        stats
+    }
+
+    /// Drop the buffered samples, e.g. when the console is paused.
+    pub fn clear(&self) {
+        self.resampler().clear();
+    }
+
+    /// Lock the resampler.
+    fn resampler(&self) -> MutexGuard<'_, Resampler> {
+        self.resampler
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Convert a latency in milliseconds into seconds.
+fn latency(latency: u32) -> f32 {
+    f32::from(u16::try_from(latency).unwrap_or(u16::MAX)) / 1000.0
+}
+
+/// Build a stream of samples of the given type, playing the same samples on
+/// every channel.
+fn stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    resampler: &Arc<Mutex<Resampler>>,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let resampler = Arc::clone(resampler);
+    let channels = usize::from(config.channels);
+    let mut samples = Vec::new();
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            samples.resize(data.len() / channels, 0.0);
+            resampler
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .fill(&mut samples);
+
+            for (frame, &sample) in data.chunks_exact_mut(channels).zip(&samples) {
+                frame.fill(T::from_sample(sample));
+            }
+        },
+        |error| eprintln!("audio error: {error}"),
+        None,
+    )
+}
@@ -0,0 +1,88 @@
+//! The screenshots and recordings of the pictures shown in the window.
+//!
+//! The recordings are animated PNG files, see [`chuck_video::png`], or with
+//! `--raw-frames`, the raw RGB pixels of the frames, 3 bytes per pixel, for
+//! encoders of videos, e.g.:
+//!
+//! ```sh
+//! ffmpeg -f rawvideo -pixel_format rgb24 -video_size 256x240 \
+//!     -framerate 60.0988 -i game-0.rgb game.mp4
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use chuck_nes::{HEIGHT, WIDTH};
+use chuck_video::png::{self, Animation};
+
+/// Save a picture as a PNG file.
+///
+/// # Errors
+///
+/// Returns any error produced while writing the file.
+pub fn save_screenshot(path: &Path, picture: &[u32]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    png::write(&mut file, WIDTH, HEIGHT, picture)?;
+    file.flush()
+}
+
+/// A recording of the pictures of the frames.
+pub enum Recording {
+    /// An animated PNG file.
+    Animation(Animation<BufWriter<File>>),
+    /// A file of raw RGB pixels.
+    Raw(BufWriter<File>),
+}
+
+impl Recording {
+    /// Start a recording into the file at the given path, of frames with the
+    /// given period.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while creating the file.
+    pub fn create(path: &Path, raw: bool, period: Duration) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        if raw {
+            Ok(Self::Raw(file))
+        } else {
+            Animation::new(file, WIDTH, HEIGHT, period).map(Self::Animation)
+        }
+    }
+
+    /// Append the picture of a frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    pub fn push(&mut self, picture: &[u32]) -> io::Result<()> {
+        match self {
+            Self::Animation(animation) => animation.push(picture),
+            Self::Raw(file) => {
+                let bytes: Vec<_> = picture
+                    .iter()
+                    .flat_map(|pixel| {
+                        let [_, r, g, b] = pixel.to_be_bytes();
+                        [r, g, b]
+                    })
+                    .collect();
+                file.write_all(&bytes)
+            }
+        }
+    }
+
+    /// Finish the recording.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file, see
+    /// [`Animation::finish`].
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Animation(animation) => animation.finish()?.flush(),
+            Self::Raw(mut file) => file.flush(),
+        }
+    }
+}
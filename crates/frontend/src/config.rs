@@ -0,0 +1,343 @@
+//! The configuration of the frontend, a TOML file with the bindings of the
+//! buttons of the two controllers, see [`input`](crate::input):
+//!
+//! ```toml
+//! # The number of frames the turbo buttons stay pressed, and then released.
+//! turbo_period = 2
+//!
+//! # The first controller.
+//! [[controllers]]
+//! keys = { a = ["KeyX"], b = ["KeyZ"], start = ["Enter", "Space"] }
+//! gamepad = { a = ["East"], b = ["South"], turbo_a = ["North"] }
+//!
+//! # The second controller, which isn't bound to any key.
+//! [[controllers]]
+//! gamepad = { a = ["East"], b = ["South"] }
+//!
+//! # The playback of the audio.
+//! [audio]
+//! # The number of samples the audio device pulls at once, by default the
+//! # number the device prefers.
+//! buffer_size = 512
+//! # The duration of the buffered samples, in milliseconds.
+//! latency = 50
+//! # The adjustment of the rate of the samples to keep the latency, and by how
+//! # many percent it may change the rate.
+//! rate_control = true
+//! max_deviation = 0.5
+//! # The resampling: "hold", "linear" or "sinc".
+//! quality = "sinc"
+//!
+//! # The automatic save states.
+//! [autosave]
+//! # The time between the automatic saves, in seconds, or 0 to only save when
+//! # quitting.
+//! interval = 60
+//! # Load the automatic save state when the game is opened, instead of only
+//! # offering it.
+//! resume = false
+//! ```
+//!
+//! The buttons are `a`, `b`, `select`, `start`, `up`, `down`, `left`,
+//! `right`, `turbo_a` and `turbo_b`. The keys are named by their position on
+//! a US keyboard, like the [`KeyCode`]s of `winit`, e.g. `KeyZ` is the key
+//! left of `KeyX` on any layout. The buttons of the gamepads are named like
+//! the [`GamepadButton`]s, e.g. `South` is the lower button on the right.
+//!
+//! A small latency of the audio delays the sound less, but the audio device
+//! runs out of samples more often, which crackles. It should be a few times
+//! the duration of the buffer of the device, e.g. 50 ms for a desktop, but
+//! more for a slow machine. The latency can be changed while playing as
+//! well, see [`app`](crate::app), which prints how often the device ran out.
+//! A slow machine may resample with a cheaper quality as well, which aliases
+//! the highest tones, see [`Quality`].
+//!
+//! The automatic save state of a game is saved every `interval` and when the
+//! game is closed, and offered (or loaded with `resume`) when the game is
+//! opened again, so a crash loses at most the time of an interval, see
+//! [`game`](crate::game).
+//!
+//! The file is read from `--config`, otherwise from `chuck/config.toml` in
+//! the directory of the configurations of the user (e.g. `~/.config` on
+//! Linux and `%APPDATA%` on Windows). Without a file, the default bindings
+//! are used, and the file is written once the buttons are rebound, see
+//! [`app`](crate::app).
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::input::{Button, GamepadButton};
+
+/// The configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The number of frames the turbo buttons stay pressed, and then
+    /// released, so they're pressed 15 times per second on NTSC by default.
+    pub turbo_period: u32,
+    /// The bindings of the two controllers.
+    pub controllers: Vec<Bindings>,
+    /// The playback of the audio.
+    pub audio: Audio,
+    /// The automatic save states.
+    pub autosave: AutoSave,
+}
+
+/// The bindings of the buttons of a controller. A missing button isn't bound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    /// The keys of the buttons.
+    pub keys: BTreeMap<Button, Vec<KeyCode>>,
+    /// The buttons of the gamepad of the controller, e.g. the second gamepad
+    /// for the second controller.
+    pub gamepad: BTreeMap<Button, Vec<GamepadButton>>,
+}
+
+/// The settings of the playback of the audio.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Audio {
+    /// The number of samples the audio device pulls at once, if not the
+    /// number preferred by the device. It's clamped to the numbers supported
+    /// by the device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<u32>,
+    /// The duration of the buffered samples kept by the dynamic rate
+    /// control, in milliseconds.
+    pub latency: u32,
+    /// A flag denoting if the rate of the samples is adjusted to keep the
+    /// latency (the dynamic rate control).
+    pub rate_control: bool,
+    /// The largest change of the rate by the dynamic rate control, in
+    /// percent.
+    pub max_deviation: f64,
+    /// The quality of the resampling.
+    pub quality: Quality,
+}
+
+/// The quality of the resampling of the audio, from the cheapest to the most
+/// accurate, see `chuck_audio::Quality`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    /// A zero-order hold.
+    Hold,
+    /// A linear interpolation.
+    Linear,
+    /// A band-limiting by windowed sincs.
+    #[default]
+    Sinc,
+}
+
+/// The settings of the automatic save states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoSave {
+    /// The time between the automatic saves while the game runs, in seconds,
+    /// or 0 to only save when the game is closed.
+    pub interval: u32,
+    /// A flag denoting if the automatic save state is loaded when the game is
+    /// opened, instead of only offered.
+    pub resume: bool,
+}
+
+impl Default for AutoSave {
+    fn default() -> Self {
+        Self {
+            interval: 60,
+            resume: false,
+        }
+    }
+}
+
+impl Audio {
+    /// The lowest latency, in milliseconds.
+    pub const MIN_LATENCY: u32 = 5;
+    /// The highest latency, in milliseconds.
+    pub const MAX_LATENCY: u32 = 500;
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            buffer_size: None,
+            latency: 50,
+            rate_control: true,
+            max_deviation: 0.5,
+            quality: Quality::default(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let gamepad = BTreeMap::from([
+            (Button::A, vec![GamepadButton::East]),
+            (Button::B, vec![GamepadButton::South]),
+            (Button::Select, vec![GamepadButton::Select]),
+            (Button::Start, vec![GamepadButton::Start]),
+            (Button::Up, vec![GamepadButton::DPadUp]),
+            (Button::Down, vec![GamepadButton::DPadDown]),
+            (Button::Left, vec![GamepadButton::DPadLeft]),
+            (Button::Right, vec![GamepadButton::DPadRight]),
+            (Button::TurboA, vec![GamepadButton::North]),
+            (Button::TurboB, vec![GamepadButton::West]),
+        ]);
+        let keys = BTreeMap::from([
+            (Button::A, vec![KeyCode::KeyX]),
+            (Button::B, vec![KeyCode::KeyZ]),
+            (Button::Select, vec![KeyCode::ShiftRight]),
+            (Button::Start, vec![KeyCode::Enter]),
+            (Button::Up, vec![KeyCode::ArrowUp]),
+            (Button::Down, vec![KeyCode::ArrowDown]),
+            (Button::Left, vec![KeyCode::ArrowLeft]),
+            (Button::Right, vec![KeyCode::ArrowRight]),
+            (Button::TurboA, vec![KeyCode::KeyS]),
+            (Button::TurboB, vec![KeyCode::KeyA]),
+        ]);
+
+        Self {
+            turbo_period: 2,
+            controllers: vec![
+                Bindings {
+                    keys,
+                    gamepad: gamepad.clone(),
+                },
+                Bindings {
+                    keys: BTreeMap::new(),
+                    gamepad,
+                },
+            ],
+            audio: Audio::default(),
+            autosave: AutoSave::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Return the default path of the file, unless the directory of the
+    /// configurations of the user is unknown.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = || env::var_os("HOME").map(PathBuf::from);
+        let dir = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| home().map(|home| home.join(".config")))
+        };
+
+        dir.map(|dir| dir.join("chuck").join("config.toml"))
+    }
+
+    /// Read the file at the given path, or return the default configuration
+    /// if there's no file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidData`](io::ErrorKind::InvalidData)
+    /// if the file is malformed, has more than two controllers, a turbo
+    /// period of 0 or invalid settings of the audio, and any error produced
+    /// while reading the file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(error),
+        };
+
+        let mut config: Self = toml::from_str(&text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        if config.turbo_period == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the turbo period must be at least 1 frame",
+            ));
+        }
+        if config.controllers.len() > 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "there are at most two controllers",
+            ));
+        }
+
+        let audio = config.audio;
+        if !(Audio::MIN_LATENCY..=Audio::MAX_LATENCY).contains(&audio.latency) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the latency of the audio must be between {} and {} ms",
+                    Audio::MIN_LATENCY,
+                    Audio::MAX_LATENCY
+                ),
+            ));
+        }
+        if audio.buffer_size == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the buffer size of the audio must be at least 1 sample",
+            ));
+        }
+        if !(0.0..=10.0).contains(&audio.max_deviation) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the largest deviation of the audio rate must be between 0 and 10 %",
+            ));
+        }
+
+        config.controllers.resize_with(2, Bindings::default);
+        Ok(config)
+    }
+
+    /// Write the configuration into the file at the given path, creating its
+    /// directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    /// Bind a key to a button of the given controller, replacing the keys of
+    /// the button and unbinding the key from every other button.
+    pub fn bind_key(&mut self, controller: usize, button: Button, key: KeyCode) {
+        for bindings in &mut self.controllers {
+            for keys in bindings.keys.values_mut() {
+                keys.retain(|&bound| bound != key);
+            }
+            bindings.keys.retain(|_, keys| !keys.is_empty());
+        }
+
+        self.controllers[controller].keys.insert(button, vec![key]);
+    }
+
+    /// Bind a button of the gamepad of the given controller to a button of
+    /// the controller, replacing the buttons of the gamepad bound to it and
+    /// unbinding the button of the gamepad from every other button.
+    #[cfg(feature = "gamepad")]
+    pub fn bind_gamepad(&mut self, controller: usize, button: Button, input: GamepadButton) {
+        let gamepad = &mut self.controllers[controller].gamepad;
+        for inputs in gamepad.values_mut() {
+            inputs.retain(|&bound| bound != input);
+        }
+        gamepad.retain(|_, inputs| !inputs.is_empty());
+
+        gamepad.insert(button, vec![input]);
+    }
+}
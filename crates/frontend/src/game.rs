@@ -0,0 +1,247 @@
+//! A loaded game, with its save RAM and its save states.
+//!
+//! The files of a game are stored next to its ROM: the save RAM in a `.sav`
+//! file, see [`chuck_nes::sram`], the save states in `.st0` to `.st9` files,
+//! one for every slot, with a thumbnail and the time played, see
+//! [`chuck_nes::slot`], the automatic save state in a `.auto` file, and the
+//! screenshots and recordings in numbered files,
+//! e.g. `game-0.png`, see [`capture`](crate::capture).
+//!
+//! The automatic save state is written into a temporary file first, which
+//! then replaces the old one, so a crash while saving keeps the old state.
+//! The file is written and synced to the disk on another thread, so the
+//! frames don't wait for the disk.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use chuck_nes::mapper::{self, GameGenie};
+use chuck_nes::slot::{self, Metadata};
+use chuck_nes::{sram, Nes, Region};
+use chuck_rom::Rom;
+
+/// The extension of the automatic save state.
+const AUTO_EXTENSION: &str = "auto";
+
+/// A loaded game.
+pub struct Game {
+    /// The console running the game.
+    pub nes: Nes,
+    /// The path of the ROM.
+    path: PathBuf,
+    /// The hash of the ROM, see [`slot::game_hash`].
+    hash: u64,
+    /// The thread writing the automatic save state, if any.
+    autosave: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Game {
+    /// Load the ROM at the given path, and the save RAM saved next to it,
+    /// behind a Game Genie with the firmware at the given path, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM can't be read, is malformed or has an
+    /// unsupported mapper, if the firmware can't be read or is malformed, or
+    /// if the save RAM can't be read.
+    pub fn open(path: &Path, game_genie: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let rom = Rom::parse(&fs::read(path)?)?;
+        let mut cartridge = mapper::from_rom(&rom)?;
+        if let Some(firmware) = game_genie {
+            cartridge = Box::new(GameGenie::new(&fs::read(firmware)?, cartridge)?);
+        }
+        let mut nes = Nes::with_region(cartridge, Region::from(rom.header().region));
+
+        if nes.sram().is_some() {
+            match File::open(sram::path(path)) {
+                Ok(file) => nes.load_sram(&mut BufReader::new(file))?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        Ok(Self {
+            nes,
+            path: path.to_path_buf(),
+            hash: slot::game_hash(&rom),
+            autosave: None,
+        })
+    }
+
+    /// Return the name of the game, i.e. the name of its ROM file.
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .unwrap_or(self.path.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Save the save RAM, if it was written since it was last saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    pub fn save_sram(&mut self) -> io::Result<()> {
+        if !self.nes.is_sram_dirty() {
+            return Ok(());
+        }
+
+        let mut file = BufWriter::new(File::create(sram::path(&self.path))?);
+        self.nes.save_sram(&mut file)?;
+        file.flush()
+    }
+
+    /// Save the state of the console into the given slot, see
+    /// [`Nes::save_slot`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    pub fn save_state(&self, slot: u8) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(self.state_path(slot))?);
+        self.nes
+            .save_slot(&Metadata::new(&self.nes, self.hash), &mut file)?;
+        file.flush()
+    }
+
+    /// Load the state of the console from the given slot, returning its
+    /// metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while reading the file, see
+    /// [`Nes::load_slot`], which refuses the states of other games.
+    pub fn load_state(&mut self, slot: u8) -> io::Result<Metadata> {
+        let file = File::open(self.state_path(slot))?;
+        self.nes.load_slot(self.hash, &mut BufReader::new(file))
+    }
+
+    /// Return the metadata of the state in the given slot, without loading
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while reading the file, see
+    /// [`Metadata::read`].
+    pub fn state_metadata(&self, slot: u8) -> io::Result<Metadata> {
+        let file = File::open(self.state_path(slot))?;
+        Metadata::read(&mut BufReader::new(file))
+    }
+
+    /// Save the state of the console into the automatic save state, which
+    /// is written on another thread, see [`Game::finish_auto`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while saving the state, or while writing
+    /// the previous automatic save state, which keeps the one before it.
+    pub fn save_auto(&mut self) -> io::Result<()> {
+        // Waits for the previous save, which only takes longer than the
+        // interval between the saves on a very slow disk.
+        let previous = self.finish_auto();
+
+        let mut state = Vec::new();
+        self.nes
+            .save_slot(&Metadata::new(&self.nes, self.hash), &mut state)?;
+        let path = self.auto_path();
+        self.autosave = Some(thread::spawn(move || write_auto(&path, &state)));
+        previous
+    }
+
+    /// Wait until the automatic save state is written, if it's being
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file, which keeps the
+    /// previous automatic save state.
+    pub fn finish_auto(&mut self) -> io::Result<()> {
+        self.autosave.take().map_or(Ok(()), |thread| {
+            thread
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("the save thread panicked")))
+        })
+    }
+
+    /// Load the state of the console from the automatic save state, returning
+    /// its metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while reading the file, see
+    /// [`Nes::load_slot`], which refuses the states of other games.
+    pub fn load_auto(&mut self) -> io::Result<Metadata> {
+        let file = File::open(self.auto_path())?;
+        self.nes.load_slot(self.hash, &mut BufReader::new(file))
+    }
+
+    /// Return the metadata of the automatic save state, if there's one of
+    /// this game.
+    pub fn auto_metadata(&self) -> Option<Metadata> {
+        let file = File::open(self.auto_path()).ok()?;
+        Metadata::read(&mut BufReader::new(file))
+            .ok()
+            .filter(|metadata| metadata.game == self.hash)
+    }
+
+    /// Return the path of the next screenshot or recording with the given
+    /// extension, i.e. the first numbered file which doesn't exist yet.
+    pub fn capture_path(&self, extension: &str) -> PathBuf {
+        let name = self.name();
+        let mut number = 0;
+        loop {
+            let path = self
+                .path
+                .with_file_name(format!("{name}-{number}.{extension}"));
+            if !path.exists() {
+                return path;
+            }
+            number += 1;
+        }
+    }
+
+    /// Describe the state of a slot by its metadata, e.g. `played 0:12:34,
+    /// saved 5 minutes ago`, with a note if it was saved from another game.
+    pub fn describe(&self, metadata: &Metadata) -> String {
+        let played = metadata.play_time.as_secs();
+        let played = format!(
+            "{}:{:02}:{:02}",
+            played / 3600,
+            played / 60 % 60,
+            played % 60
+        );
+        let minutes = metadata.saved.elapsed().map_or(0, |age| age.as_secs() / 60);
+        let other = if metadata.game == self.hash {
+            ""
+        } else {
+            " from another game"
+        };
+
+        format!("played {played}, saved {minutes} minutes ago{other}")
+    }
+
+    /// Return the path of the save state of the given slot.
+    fn state_path(&self, slot: u8) -> PathBuf {
+        self.path
+            .with_extension(format!("{}{slot}", slot::EXTENSION))
+    }
+
+    /// Return the path of the automatic save state.
+    fn auto_path(&self) -> PathBuf {
+        self.path.with_extension(AUTO_EXTENSION)
+    }
+}
+
+/// Write the given automatic save state into the file at the given path, by
+/// way of a temporary file synced to the disk.
+fn write_auto(path: &Path, state: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension(format!("{AUTO_EXTENSION}.tmp"));
+    let mut file = File::create(&temporary)?;
+    file.write_all(state)?;
+    file.sync_all()?;
+    fs::rename(temporary, path)
+}
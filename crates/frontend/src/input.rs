@@ -0,0 +1,386 @@
+//! The buttons of the controllers, as pressed on the keyboard and on
+//! gamepads.
+//!
+//! The keys and the buttons of the gamepads are bound to the buttons of the
+//! two controllers by the configuration, see [`config`](crate::config). The
+//! first two connected gamepads control the first and the second controller.
+//! By default, the keyboard controls the first controller:
+//!
+//! | Button  | Key         | Gamepad           |
+//! |---------|-------------|-------------------|
+//! | A       | X           | East (B on Xbox)  |
+//! | B       | Z           | South (A on Xbox) |
+//! | Select  | Right Shift | Select            |
+//! | Start   | Enter       | Start             |
+//! | Pad     | Arrow keys  | D-pad             |
+//! | Turbo A | S           | North (Y on Xbox) |
+//! | Turbo B | A           | West (X on Xbox)  |
+//!
+//! While a turbo button is held, its button is pressed and released every
+//! few frames, see [`Held::pressed`].
+
+use std::collections::BTreeMap;
+
+use chuck_input::ButtonState;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::config::Config;
+
+/// A button of a controller, or a turbo button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Button {
+    /// The A button.
+    A,
+    /// The B button.
+    B,
+    /// The Select button.
+    Select,
+    /// The Start button.
+    Start,
+    /// Up on the pad.
+    Up,
+    /// Down on the pad.
+    Down,
+    /// Left on the pad.
+    Left,
+    /// Right on the pad.
+    Right,
+    /// The turbo A button.
+    TurboA,
+    /// The turbo B button.
+    TurboB,
+}
+
+impl Button {
+    /// The buttons, in the order in which they're rebound.
+    pub const ALL: [Self; 10] = [
+        Self::A,
+        Self::B,
+        Self::Select,
+        Self::Start,
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::TurboA,
+        Self::TurboB,
+    ];
+
+    /// Return the name of the button, e.g. for prompts.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+            Self::Select => "Select",
+            Self::Start => "Start",
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::TurboA => "Turbo A",
+            Self::TurboB => "Turbo B",
+        }
+    }
+}
+
+/// A button of a gamepad, named like the buttons of `gilrs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadButton {
+    /// The lower button on the right (A on Xbox).
+    South,
+    /// The right button on the right (B on Xbox).
+    East,
+    /// The upper button on the right (Y on Xbox).
+    North,
+    /// The left button on the right (X on Xbox).
+    West,
+    /// The C button of some gamepads.
+    C,
+    /// The Z button of some gamepads.
+    Z,
+    /// The left shoulder button.
+    LeftTrigger,
+    /// The left trigger.
+    LeftTrigger2,
+    /// The right shoulder button.
+    RightTrigger,
+    /// The right trigger.
+    RightTrigger2,
+    /// The Select (or Back) button.
+    Select,
+    /// The Start button.
+    Start,
+    /// The button of the vendor, e.g. the Xbox button.
+    Mode,
+    /// The button of the left stick.
+    LeftThumb,
+    /// The button of the right stick.
+    RightThumb,
+    /// Up on the D-pad.
+    DPadUp,
+    /// Down on the D-pad.
+    DPadDown,
+    /// Left on the D-pad.
+    DPadLeft,
+    /// Right on the D-pad.
+    DPadRight,
+}
+
+/// The buttons held on a controller.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Held {
+    /// The held buttons.
+    buttons: ButtonState,
+    /// The buttons whose turbo buttons are held.
+    turbo: ButtonState,
+}
+
+impl Held {
+    /// Hold a button.
+    fn press(&mut self, button: Button) {
+        match button {
+            Button::A => self.buttons |= ButtonState::A,
+            Button::B => self.buttons |= ButtonState::B,
+            Button::Select => self.buttons |= ButtonState::SELECT,
+            Button::Start => self.buttons |= ButtonState::START,
+            Button::Up => self.buttons |= ButtonState::UP,
+            Button::Down => self.buttons |= ButtonState::DOWN,
+            Button::Left => self.buttons |= ButtonState::LEFT,
+            Button::Right => self.buttons |= ButtonState::RIGHT,
+            Button::TurboA => self.turbo |= ButtonState::A,
+            Button::TurboB => self.turbo |= ButtonState::B,
+        }
+    }
+
+    /// Return the buttons held on either controller.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            buttons: self.buttons | other.buttons,
+            turbo: self.turbo | other.turbo,
+        }
+    }
+
+    /// Return the pressed buttons, including the buttons of the held turbo
+    /// buttons if they're pressed in the current frame, otherwise they're
+    /// released in it.
+    pub fn pressed(self, turbo: bool) -> ButtonState {
+        if turbo {
+            self.buttons | self.turbo
+        } else {
+            self.buttons
+        }
+    }
+}
+
+/// Return the pairs of inputs and buttons bound to them.
+fn pairs<T: Copy>(bindings: &BTreeMap<Button, Vec<T>>) -> Vec<(T, Button)> {
+    bindings
+        .iter()
+        .flat_map(|(&button, inputs)| inputs.iter().map(move |&input| (input, button)))
+        .collect()
+}
+
+/// The keys held on the keyboard.
+#[derive(Debug)]
+pub struct Keyboard {
+    /// The keys of the buttons of the two controllers.
+    bindings: [Vec<(KeyCode, Button)>; 2],
+    /// The held keys which are bound to buttons.
+    held: Vec<KeyCode>,
+}
+
+impl Keyboard {
+    /// Create the keyboard with the bindings of the given configuration.
+    pub fn new(config: &Config) -> Self {
+        let mut keyboard = Self {
+            bindings: [Vec::new(), Vec::new()],
+            held: Vec::new(),
+        };
+        keyboard.set_bindings(config);
+        keyboard
+    }
+
+    /// Replace the bindings with those of the given configuration, which
+    /// releases all keys.
+    pub fn set_bindings(&mut self, config: &Config) {
+        for (keys, bindings) in self.bindings.iter_mut().zip(&config.controllers) {
+            *keys = pairs(&bindings.keys);
+        }
+        self.release();
+    }
+
+    /// Press or release a key, returning `false` if it isn't bound to a
+    /// button.
+    pub fn key(&mut self, code: KeyCode, pressed: bool) -> bool {
+        if !self.bindings.iter().flatten().any(|&(key, _)| key == code) {
+            return false;
+        }
+
+        self.held.retain(|&key| key != code);
+        if pressed {
+            self.held.push(code);
+        }
+        true
+    }
+
+    /// Release all keys, e.g. when the window loses the focus and won't see
+    /// them released.
+    pub fn release(&mut self) {
+        self.held.clear();
+    }
+
+    /// Return the buttons held on the two controllers.
+    pub fn held(&self) -> [Held; 2] {
+        self.bindings.each_ref().map(|bindings| {
+            let mut held = Held::default();
+            for &(key, button) in bindings {
+                if self.held.contains(&key) {
+                    held.press(button);
+                }
+            }
+            held
+        })
+    }
+}
+
+/// The gamepads, see the [module documentation](self).
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    /// The connected gamepads.
+    gilrs: gilrs::Gilrs,
+    /// The buttons of the gamepads of the two controllers.
+    bindings: [Vec<(gilrs::Button, Button)>; 2],
+    /// The button last pressed on any gamepad, since it was taken.
+    pressed: Option<GamepadButton>,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    /// The buttons of `gilrs` for the buttons of a gamepad.
+    const BUTTONS: [(GamepadButton, gilrs::Button); 19] = [
+        (GamepadButton::South, gilrs::Button::South),
+        (GamepadButton::East, gilrs::Button::East),
+        (GamepadButton::North, gilrs::Button::North),
+        (GamepadButton::West, gilrs::Button::West),
+        (GamepadButton::C, gilrs::Button::C),
+        (GamepadButton::Z, gilrs::Button::Z),
+        (GamepadButton::LeftTrigger, gilrs::Button::LeftTrigger),
+        (GamepadButton::LeftTrigger2, gilrs::Button::LeftTrigger2),
+        (GamepadButton::RightTrigger, gilrs::Button::RightTrigger),
+        (GamepadButton::RightTrigger2, gilrs::Button::RightTrigger2),
+        (GamepadButton::Select, gilrs::Button::Select),
+        (GamepadButton::Start, gilrs::Button::Start),
+        (GamepadButton::Mode, gilrs::Button::Mode),
+        (GamepadButton::LeftThumb, gilrs::Button::LeftThumb),
+        (GamepadButton::RightThumb, gilrs::Button::RightThumb),
+        (GamepadButton::DPadUp, gilrs::Button::DPadUp),
+        (GamepadButton::DPadDown, gilrs::Button::DPadDown),
+        (GamepadButton::DPadLeft, gilrs::Button::DPadLeft),
+        (GamepadButton::DPadRight, gilrs::Button::DPadRight),
+    ];
+
+    /// Start listening to the gamepads, with the bindings of the given
+    /// configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the gamepads of the platform can't be listened to.
+    pub fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut gamepads = Self {
+            gilrs: gilrs::Gilrs::new()?,
+            bindings: [Vec::new(), Vec::new()],
+            pressed: None,
+        };
+        gamepads.set_bindings(config);
+        Ok(gamepads)
+    }
+
+    /// Replace the bindings with those of the given configuration.
+    pub fn set_bindings(&mut self, config: &Config) {
+        for (inputs, bindings) in self.bindings.iter_mut().zip(&config.controllers) {
+            *inputs = pairs(&bindings.gamepad)
+                .into_iter()
+                .filter_map(|(input, button)| {
+                    Self::BUTTONS
+                        .iter()
+                        .find(|&&(bound, _)| bound == input)
+                        .map(|&(_, input)| (input, button))
+                })
+                .collect();
+        }
+    }
+
+    /// Return the buttons held on the first two gamepads.
+    pub fn poll(&mut self) -> [Held; 2] {
+        // The state of the gamepads is updated by their events.
+        while let Some(event) = self.gilrs.next_event() {
+            if let gilrs::EventType::ButtonPressed(input, _) = event.event {
+                let button = Self::BUTTONS.iter().find(|&&(_, bound)| bound == input);
+                self.pressed = button.map(|&(button, _)| button).or(self.pressed);
+            }
+        }
+
+        let mut held = [Held::default(); 2];
+        for ((held, bindings), (_, gamepad)) in held
+            .iter_mut()
+            .zip(&self.bindings)
+            .zip(self.gilrs.gamepads())
+        {
+            for &(input, button) in bindings {
+                if gamepad.is_pressed(input) {
+                    held.press(button);
+                }
+            }
+        }
+
+        held
+    }
+
+    /// Return the button last pressed on any gamepad since it was last
+    /// taken, e.g. to rebind it.
+    pub fn take_pressed(&mut self) -> Option<GamepadButton> {
+        std::mem::take(&mut self.pressed)
+    }
+}
+
+/// The rebinding of the buttons of the two controllers, one button after
+/// another.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rebinding {
+    /// The controller whose buttons are rebound.
+    controller: usize,
+    /// The index of the button which is rebound next.
+    button: usize,
+}
+
+impl Rebinding {
+    /// Return the controller whose buttons are rebound.
+    pub const fn controller(self) -> usize {
+        self.controller
+    }
+
+    /// Return the button which is rebound next.
+    pub const fn button(self) -> Button {
+        Button::ALL[self.button]
+    }
+
+    /// Go on to the next button, returning `None` after the last button of
+    /// the second controller.
+    pub const fn next(self) -> Option<Self> {
+        if self.button + 1 < Button::ALL.len() {
+            Some(Self {
+                controller: self.controller,
+                button: self.button + 1,
+            })
+        } else if self.controller == 0 {
+            Some(Self {
+                controller: 1,
+                button: 0,
+            })
+        } else {
+            None
+        }
+    }
+}
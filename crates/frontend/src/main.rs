@@ -0,0 +1,314 @@
+//! Chuck's desktop frontend, which plays NES games in a window.
+//!
+//! ```sh
+//! cargo run --release -p chuck-frontend --features audio,gamepad -- game.nes
+//! ```
+//!
+//! A ROM is loaded from the command line, or by dropping it into the window.
+//! The save RAM of a game is saved next to its ROM, as are its save states,
+//! see [`game`]. The buttons of the controllers are listed in [`input`], and
+//! can be rebound in the configuration, see [`config`], or by F10.
+//!
+//! While fast-forwarding, the console runs as fast as possible and only one
+//! of every `--frame-skip` frames (4 by default) is shown, but every frame is
+//! emulated.
+//!
+//! With `--overclock 100`, the CPU runs for 100 extra scanlines after the
+//! start of every vertical blanking interval, which reduces the slowdown of
+//! games without changing the speed of their audio, see
+//! [`Nes::set_overclock`](chuck_nes::Nes::set_overclock). Some games with
+//! timed code break though.
+//!
+//! With `--zapper`, a Zapper is plugged into the second port, which is aimed
+//! by the mouse (or a touch) over the picture and shot by its left button
+//! (or the touch), see [`chuck_input::Zapper`]. `--crosshair` draws a
+//! crosshair at the aimed spot. The value of `--zapper` decides what a shot
+//! off the picture does: `shoot` (the default) pulls the trigger like a real
+//! Zapper aimed away from the TV (e.g. to reload), `ignore` doesn't pull it,
+//! and `clamp` aims at the nearest edge of the picture instead.
+//!
+//! With `--turbo-file save.tf`, an ASCII Turbo File is plugged into the
+//! expansion port, see [`chuck_input::TurboFile`], whose storage is loaded
+//! from the file and saved into it once a game wrote it. The file is shared
+//! by all games, like the storage of a real Turbo File.
+//!
+//! With `--sound-module epsm`, the EPSM is plugged into the expansion port,
+//! for homebrew games whose music uses its FM and SSG channels, see
+//! [`chuck_nes::epsm`].
+//!
+//! With `--game-genie genie.bin`, the games are plugged into a Game Genie
+//! with the firmware dumped into the file, which shows its screen to enter
+//! the codes before the game starts, see
+//! [`GameGenie`](chuck_nes::mapper::GameGenie).
+//!
+//! With `--barcode 4905040352507`, F4 reads the barcode with the barcode
+//! reader of the cartridge, e.g. of the Datach, see
+//! [`Barcode`](chuck_nes::mapper::Barcode). With more than one `--barcode`,
+//! F4 reads them in turn.
+//!
+//! The screenshots and recordings are saved next to the ROM as well, see
+//! [`capture`]. The shown picture includes the drawings of a script, unlike
+//! the picture of the PPU.
+//!
+//! | Hotkey | Action                                   |
+//! |--------|------------------------------------------|
+//! | F5     | Save the state into the selected slot    |
+//! | F6     | Load the automatic save state            |
+//! | F7     | Load the state from the selected slot    |
+//! | 0-9    | Select the slot of the save states       |
+//! | F2     | Press the reset button                   |
+//! | F3     | Power cycle the console                  |
+//! | F4     | Read the next barcode                    |
+//! | P      | Pause the console                        |
+//! | Tab    | Fast-forward while held                  |
+//! | -      | Slow the console down, to 1/8 speed      |
+//! | =      | Speed the console up, to full speed      |
+//! | [      | Lower the latency of the audio by 10 ms  |
+//! | ]      | Raise the latency of the audio by 10 ms  |
+//! | F12    | Save a screenshot of the shown picture   |
+//! | F8     | Save a screenshot of the PPU's picture   |
+//! | F9     | Start or stop recording the frames       |
+//! | F10    | Rebind the buttons of the controllers    |
+//! | F11    | Toggle the fullscreen mode               |
+//! | Escape | Quit                                     |
+//!
+//! # Debugging
+//!
+//! With `--gdb 127.0.0.1:6502`, a debugger speaking the remote protocol of
+//! GDB can attach to the console at the given address, see
+//! [`chuck_nes::gdb`]. The picture stands still while the debugger halts the
+//! console. With `--symbols game.dbg` (or a Mesen label file, `.mlb`), the
+//! labels and source lines of the program are known to the commands of
+//! `monitor`, see [`chuck_nes::symbols`].
+//!
+//! With `--profile report.txt`, the cycles of the CPU are attributed to the
+//! functions and instructions of the program, and written into the report
+//! when the game is closed, see [`chuck_nes::profile`]. The functions are
+//! named by the symbols, if any. With `--flamegraph game.folded`, the stacks
+//! of the functions are written as well, in the input format of flame
+//! graphs.
+//!
+//! # Accuracy report
+//!
+//! `chuck accuracy-report nes-test-roms.toml --roms path/to/nes-test-roms`
+//! runs the test ROMs of a manifest of the test runner and checks that they
+//! run deterministically, writing the results into a JSON file instead of
+//! opening the window, see [`report`].
+//!
+//! # Scripting
+//!
+//! With `--script bot.lua`, a Lua script runs along with the game, which
+//! reads and writes the memory, presses buttons and draws on top of the
+//! picture, see [`chuck_lua`]. The script stops at its first error.
+//!
+//! # Features
+//!
+//! The audio output (`audio`, by `cpal`) and the gamepads (`gamepad`, by
+//! `gilrs`) are optional, since they need the development packages of ALSA
+//! (`libasound2-dev`) and udev (`libudev-dev`) on Linux.
+//!
+//! With `timing`, the average times the CPU, the PPU, the APU, the cartridge
+//! and the presentation took per frame are printed every 60 frames, see
+//! [`chuck_nes::timing`].
+
+mod app;
+#[cfg(feature = "audio")]
+mod audio;
+mod capture;
+mod config;
+mod game;
+mod input;
+mod report;
+mod video;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chuck_lua::Script;
+use chuck_nes::gdb::Server;
+use chuck_nes::mapper::Barcode;
+use chuck_nes::symbols::Symbols;
+use chuck_video::palette::Palette;
+use clap::{Parser, Subcommand, ValueEnum};
+use winit::event_loop::EventLoop;
+
+use app::App;
+use config::Config;
+
+/// Play NES games.
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Args {
+    /// A command to run instead of playing, see the documentation.
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The ROM to play, otherwise a ROM can be dropped into the window.
+    rom: Option<PathBuf>,
+    /// The initial scale of the picture.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=16))]
+    scale: u32,
+    /// A palette file (`.pal`) to color the picture with, instead of the
+    /// default palette.
+    #[arg(long)]
+    palette: Option<PathBuf>,
+    /// Show the pixels as squares, instead of as wide as on a TV.
+    #[arg(long)]
+    square_pixels: bool,
+    /// Show only one of every given number of frames while fast-forwarding.
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=60))]
+    frame_skip: u32,
+    /// The number of extra scanlines the CPU runs for every frame, see the
+    /// documentation.
+    #[arg(long, value_name = "SCANLINES", default_value_t = 0)]
+    overclock: u16,
+    /// The address to listen at for a debugger, see the documentation.
+    #[arg(long, value_name = "ADDR")]
+    gdb: Option<SocketAddr>,
+    /// The symbols of the program, see the documentation.
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+    /// The file to write the report of the profiled CPU into.
+    #[arg(long, value_name = "FILE")]
+    profile: Option<PathBuf>,
+    /// The file to write the stacks of the profiled CPU into, for flame
+    /// graphs.
+    #[arg(long, value_name = "FILE")]
+    flamegraph: Option<PathBuf>,
+    /// A Lua script to run along with the game, see the documentation.
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+    /// The configuration file, see the documentation, instead of the default
+    /// one.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Record the raw RGB pixels of the frames instead of animated PNG
+    /// files, see the documentation.
+    #[arg(long)]
+    raw_frames: bool,
+    /// Plug a Zapper into the second port, aimed by the mouse, with what a
+    /// shot off the picture does, see the documentation.
+    #[arg(long, value_enum, value_name = "OFFSCREEN", num_args = 0..=1, default_missing_value = "shoot")]
+    zapper: Option<Offscreen>,
+    /// Draw a crosshair where the Zapper is aimed.
+    #[arg(long, requires = "zapper")]
+    crosshair: bool,
+    /// Plug an ASCII Turbo File into the expansion port, whose storage is
+    /// kept in the given file.
+    #[arg(long, value_name = "FILE")]
+    turbo_file: Option<PathBuf>,
+    /// Plug a sound module into the expansion port, see the documentation.
+    #[arg(long, value_enum, value_name = "MODULE")]
+    sound_module: Option<SoundModule>,
+    /// Plug the games into a Game Genie with the given firmware, see the
+    /// documentation.
+    #[arg(long, value_name = "FILE")]
+    game_genie: Option<PathBuf>,
+    /// The digits of an EAN-13 or EAN-8 barcode for the barcode reader of the
+    /// cartridge, which F4 reads in turn with the others.
+    #[arg(long, value_name = "DIGITS")]
+    barcode: Vec<Barcode>,
+}
+
+/// A command of the frontend.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the test ROMs of a manifest and check their determinism, writing
+    /// a JSON report.
+    AccuracyReport(report::Args),
+}
+
+/// What a shot of the Zapper off the picture does, see
+/// [`chuck_input::Offscreen`].
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Offscreen {
+    /// Pull the trigger, aimed off the screen.
+    Shoot,
+    /// Don't pull the trigger.
+    Ignore,
+    /// Aim at the nearest edge of the picture.
+    Clamp,
+}
+
+/// A sound module for the expansion port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SoundModule {
+    /// The Expansion Port Sound Module, see [`chuck_nes::epsm`].
+    Epsm,
+}
+
+impl From<Offscreen> for chuck_input::Offscreen {
+    fn from(offscreen: Offscreen) -> Self {
+        match offscreen {
+            Offscreen::Shoot => Self::Shoot,
+            Offscreen::Ignore => Self::Ignore,
+            Offscreen::Clamp => Self::Clamp,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    let result = match args.command.take() {
+        Some(Command::AccuracyReport(report)) => report::run(&report),
+        None => run(args).map(|()| true),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the frontend until its window is closed.
+fn run(mut args: Args) -> Result<(), Box<dyn Error>> {
+    args.config = args.config.or_else(Config::default_path);
+    let config = match &args.config {
+        Some(path) => Config::load(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?,
+        None => Config::default(),
+    };
+    let palette = match &args.palette {
+        Some(path) => Palette::parse(&fs::read(path)?)?,
+        None => Palette::default(),
+    };
+
+    let event_loop = EventLoop::new()?;
+    let symbols = match &args.symbols {
+        Some(path) => read_symbols(path)?,
+        None => Symbols::new(),
+    };
+    let mut debugger = args.gdb.map(Server::bind).transpose()?;
+    if let Some(server) = &mut debugger {
+        server.set_symbols(symbols.clone());
+    }
+    let script = match &args.script {
+        Some(path) => Some(Script::new(
+            &fs::read_to_string(path)?,
+            &path.display().to_string(),
+        )?),
+        None => None,
+    };
+    let mut app = App::new(args, config, palette, debugger, script, symbols);
+    event_loop.run_app(&mut app)?;
+
+    app.finish()
+}
+
+/// Read the symbols of the file at the given path, as ca65 debug info if its
+/// extension is `.dbg`, otherwise as a Mesen label file.
+fn read_symbols(path: &Path) -> io::Result<Symbols> {
+    let mut file = File::open(path)?;
+    if path.extension().is_some_and(|extension| extension == "dbg") {
+        Symbols::read_dbg(&mut file)
+    } else {
+        Symbols::read_mlb(&mut file)
+    }
+}
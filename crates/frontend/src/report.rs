@@ -0,0 +1,297 @@
+//! The accuracy report, which runs the test ROMs of a manifest of the test
+//! runner (see [`chuck_test_runner::manifest`]) and checks that every ROM
+//! runs deterministically, writing the results into a JSON file:
+//!
+//! ```sh
+//! chuck accuracy-report crates/test-runner/nes-test-roms.toml \
+//!     --roms path/to/nes-test-roms --output report.json
+//! ```
+//!
+//! The determinism check runs each ROM for `--frames` frames from the
+//! power-up and hashes the picture and the audio samples of every frame. The
+//! ROM then runs again on a new console (the replay), and once more from the
+//! save state of the first run after half of the frames (the resumption),
+//! where every hash must be the one of the first run. The first frame whose
+//! hash differs is reported as the divergence, e.g.:
+//!
+//! ```json
+//! {
+//!   "summary": { "tests": 2, "passed": 1, "failed": 0, "known_failures": 1, "fixed": 0, "diverged": 1 },
+//!   "tests": [
+//!     { "name": "apu_test/apu_test.nes", "rom": "apu_test/apu_test.nes", "outcome": "passed",
+//!       "determinism": { "frames": 600, "divergence": null } },
+//!     { "name": "branch timing", "rom": "branch_timing_tests/1.Branch_Basics.nes",
+//!       "outcome": "known_failure",
+//!       "determinism": { "frames": 600, "divergence": {
+//!         "run": "resumption", "frame": 301, "expected": "9f2a6c1de03b7a58", "actual": "03b7a589f2a6c1de" } } }
+//!   ]
+//! }
+//! ```
+//!
+//! Comparing the reports of two commits shows whether a change moved the
+//! accuracy forward or backward. The command fails if any test fails, apart
+//! from the known failures, or if any ROM diverges.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use chuck_nes::{Frame, Nes};
+use chuck_rom::Rom;
+use chuck_test_runner::manifest::{Manifest, Test};
+use chuck_test_runner::{run, run_parallel, Outcome};
+use serde::Serialize;
+
+/// The options of the accuracy report.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// The manifest declaring the test ROMs.
+    manifest: PathBuf,
+    /// The directory the paths of the ROMs are relative to, by default the
+    /// directory of the manifest.
+    #[arg(long, value_name = "DIR")]
+    roms: Option<PathBuf>,
+    /// The file to write the report into.
+    #[arg(long, value_name = "FILE", default_value = "accuracy-report.json")]
+    output: PathBuf,
+    /// The number of frames of the determinism check of every ROM.
+    #[arg(long, default_value_t = 600, value_parser = clap::value_parser!(u32).range(2..))]
+    frames: u32,
+    /// The number of tests to run in parallel, by default the number of
+    /// CPUs.
+    #[arg(long)]
+    jobs: Option<NonZeroUsize>,
+}
+
+/// The report.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    /// The numbers of the outcomes.
+    summary: Summary,
+    /// The results of the tests, in the order of the manifest.
+    tests: Vec<Entry<'a>>,
+}
+
+/// The numbers of the outcomes of the tests.
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    /// The number of tests.
+    tests: usize,
+    /// The number of passed tests.
+    passed: usize,
+    /// The number of failed tests, apart from the known failures.
+    failed: usize,
+    /// The number of tests which failed as expected.
+    known_failures: usize,
+    /// The number of known failures which passed.
+    fixed: usize,
+    /// The number of ROMs which didn't run deterministically.
+    diverged: usize,
+}
+
+/// The result of a test.
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    /// The name of the test.
+    name: &'a str,
+    /// The path of the ROM, relative to the directory of the ROMs.
+    rom: &'a Path,
+    /// The outcome: `passed`, `failed`, `known_failure` or `fixed`.
+    outcome: &'static str,
+    /// The description of the failure of a failed test.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<String>,
+    /// The determinism check, unless the ROM can't be loaded.
+    determinism: Option<Determinism>,
+}
+
+/// The determinism check of a ROM.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Determinism {
+    /// The number of frames run.
+    frames: u32,
+    /// The first frame whose hash differs, if any.
+    divergence: Option<Divergence>,
+}
+
+/// A frame whose hash differs from the one of the first run.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Divergence {
+    /// The run which diverged.
+    run: Run,
+    /// The number of the frame, the first being 1.
+    frame: u32,
+    /// The hash of the first run.
+    expected: Hash,
+    /// The hash of the diverged run.
+    actual: Hash,
+}
+
+/// The hash of a frame, see [`hash`], which is serialized as hexadecimal
+/// digits like the hashes of the manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hash(u64);
+
+impl Serialize for Hash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:016x}", self.0))
+    }
+}
+
+/// A run compared with the first run of a ROM.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Run {
+    /// The run on a new console.
+    Replay,
+    /// The run from the save state of the first run.
+    Resumption,
+}
+
+/// Run the tests and the determinism checks, and write the report, returning
+/// `false` if any test failed or any ROM diverged.
+pub fn run(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let manifest: Manifest = toml::from_str(&fs::read_to_string(&args.manifest)?)?;
+    let roms = args.roms.clone().unwrap_or_else(|| {
+        let dir = args.manifest.parent().unwrap_or(&args.manifest);
+        dir.to_path_buf()
+    });
+    let jobs = args
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    let tests: Vec<_> = manifest.tests.iter().collect();
+    println!("running {} tests", tests.len());
+    let results = run_parallel(
+        &tests,
+        jobs,
+        |test| {
+            let outcome = Outcome::new(test, run::run(test, &roms, None));
+            let determinism = determinism(&roms.join(&test.rom), args.frames).ok();
+            (outcome, determinism)
+        },
+        |index, (outcome, determinism)| {
+            let diverged = determinism.is_some_and(|check| check.divergence.is_some());
+            let suffix = if diverged { ", diverged" } else { "" };
+            println!(
+                "test {} ... {}{suffix}",
+                tests[index].name,
+                outcome.describe()
+            );
+        },
+    );
+
+    let mut summary = Summary {
+        tests: tests.len(),
+        ..Summary::default()
+    };
+    let entries = tests
+        .iter()
+        .zip(results)
+        .map(|(test, (outcome, determinism))| entry(test, outcome, determinism, &mut summary))
+        .collect();
+
+    let report = Report {
+        tests: entries,
+        summary,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+    fs::write(&args.output, json + "\n")?;
+
+    let summary = &report.summary;
+    println!(
+        "\n{} passed; {} failed; {} known failures; {} fixed; {} diverged (written into {})",
+        summary.passed,
+        summary.failed,
+        summary.known_failures,
+        summary.fixed,
+        summary.diverged,
+        args.output.display(),
+    );
+
+    Ok(summary.failed == 0 && summary.diverged == 0)
+}
+
+/// Return the entry of a test in the report, counting its outcome.
+fn entry<'a>(
+    test: &'a Test,
+    outcome: Outcome,
+    determinism: Option<Determinism>,
+    summary: &mut Summary,
+) -> Entry<'a> {
+    let (name, counter, failure) = match outcome {
+        Outcome::Passed => ("passed", &mut summary.passed, None),
+        Outcome::Failed(failure) => ("failed", &mut summary.failed, Some(failure)),
+        Outcome::KnownFailure => ("known_failure", &mut summary.known_failures, None),
+        Outcome::Fixed => ("fixed", &mut summary.fixed, None),
+    };
+    *counter += 1;
+    if determinism.is_some_and(|check| check.divergence.is_some()) {
+        summary.diverged += 1;
+    }
+
+    Entry {
+        name: &test.name,
+        rom: &test.rom,
+        outcome: name,
+        failure,
+        determinism,
+    }
+}
+
+/// Check that the ROM at the given path runs deterministically for the given
+/// number of frames, see the [module documentation](self).
+fn determinism(path: &Path, frames: u32) -> io::Result<Determinism> {
+    let rom = Rom::parse(&fs::read(path)?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    let console =
+        || Nes::from_rom(&rom).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+
+    let middle = frames / 2;
+    let mut nes = console()?;
+    let mut state = Vec::new();
+    let mut hashes = Vec::new();
+    for frame in 1..=frames {
+        hashes.push(hash(&nes.run_frame()));
+        if frame == middle {
+            nes.save_state(&mut state)?;
+        }
+    }
+
+    let mut resumption = console()?;
+    resumption.load_state(&mut state.as_slice())?;
+    let runs = [
+        (Run::Replay, 1, console()?),
+        (Run::Resumption, middle + 1, resumption),
+    ];
+    let divergence = runs.into_iter().find_map(|(run, start, mut nes)| {
+        let hashes = (1..).zip(&hashes).skip_while(|&(frame, _)| frame < start);
+        hashes
+            .map(|(frame, &expected)| (frame, expected, hash(&nes.run_frame())))
+            .find(|&(_, expected, actual)| actual != expected)
+            .map(|(frame, expected, actual)| Divergence {
+                run,
+                frame,
+                expected,
+                actual,
+            })
+    });
+
+    Ok(Determinism { frames, divergence })
+}
+
+/// Return the 64-bit FNV-1a hash of the picture and the audio samples of a
+/// frame.
+fn hash(frame: &Frame<'_>) -> Hash {
+    let samples = frame
+        .samples
+        .iter()
+        .flat_map(|sample| sample.to_bits().to_le_bytes());
+    Hash(samples.fold(run::hash(frame.pixels), |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    }))
+}
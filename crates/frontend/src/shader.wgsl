@@ -0,0 +1,23 @@
+// Draws the picture of the console by a triangle covering the viewport, with
+// the texture coordinates of its corners chosen so the picture exactly fills
+// the viewport.
+
+@group(0) @binding(0) var picture: texture_2d<f32>;
+@group(0) @binding(1) var nearest: sampler;
+
+struct Vertex {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vertex(@builtin(vertex_index) index: u32) -> Vertex {
+    // (0, 0), (2, 0) and (0, 2).
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return Vertex(vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0), uv);
+}
+
+@fragment
+fn fragment(vertex: Vertex) -> @location(0) vec4<f32> {
+    return textureSample(picture, nearest, vertex.uv);
+}
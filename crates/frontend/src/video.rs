@@ -0,0 +1,288 @@
+//! The rendering of the pictures of the console into the window.
+//!
+//! The picture is uploaded into a texture every frame, and drawn into the
+//! largest viewport within the window whose height is an integer multiple of
+//! the picture's, so every row of the picture is equally high. The width of
+//! the viewport corrects the aspect ratio of the pixels, which aren't square
+//! on a TV, so their columns may differ by a pixel of the window.
+
+use std::error::Error;
+use std::sync::Arc;
+
+use chuck_input::Pointer;
+use chuck_nes::{Region, HEIGHT, WIDTH};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::window::Window;
+
+/// The pixel aspect ratio of the pictures of the given region, as the width
+/// and the height of a pixel on a TV.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/wiki/Overscan>
+pub fn pixel_aspect(region: Region) -> (u32, u32) {
+    match region {
+        Region::Ntsc => (8, 7),
+        Region::Pal | Region::Dendy => (2_950_000, 2_128_137),
+    }
+}
+
+/// Return the size of the picture as shown at the given scale.
+pub fn size(scale: u32, (width, height): (u32, u32)) -> PhysicalSize<u32> {
+    let [columns, rows] = [WIDTH, HEIGHT].map(|n| u64::try_from(n).unwrap_or(0));
+    let scale = u64::from(scale);
+    let width = columns * scale * u64::from(width) / u64::from(height);
+
+    let [width, height] = [width, rows * scale].map(|n| u32::try_from(n).unwrap_or(u32::MAX));
+    PhysicalSize::new(width, height)
+}
+
+/// Return the viewport of the picture within a surface of the given size, as
+/// its position and its size.
+fn viewport(surface: PhysicalSize<u32>, aspect: (u32, u32)) -> [u32; 4] {
+    let [columns, rows] = [WIDTH, HEIGHT].map(|n| u64::try_from(n).unwrap_or(u64::MAX));
+    let (width, height) = aspect;
+    // The largest scale whose width, rounded down like by `size`, fits.
+    let fitting =
+        ((u64::from(surface.width) + 1) * u64::from(height) - 1) / (columns * u64::from(width));
+    let scale = fitting.min(u64::from(surface.height) / rows);
+    let scale = u32::try_from(scale).unwrap_or(0);
+
+    // A window too small for the picture shows it squeezed.
+    let size = if scale == 0 {
+        surface
+    } else {
+        size(scale, aspect)
+    };
+
+    [
+        (surface.width - size.width) / 2,
+        (surface.height - size.height) / 2,
+        size.width,
+        size.height,
+    ]
+}
+
+/// The renderer of the pictures into a window.
+pub struct Renderer {
+    /// The surface of the window.
+    surface: wgpu::Surface<'static>,
+    /// The GPU.
+    device: wgpu::Device,
+    /// The queue of the commands to the GPU.
+    queue: wgpu::Queue,
+    /// The configuration of the surface.
+    config: wgpu::SurfaceConfiguration,
+    /// The texture of the picture.
+    texture: wgpu::Texture,
+    /// The bindings of the texture and its sampler.
+    bind_group: wgpu::BindGroup,
+    /// The pipeline drawing the texture.
+    pipeline: wgpu::RenderPipeline,
+    /// The picture as the bytes of the texture.
+    bytes: Vec<u8>,
+    /// The pixel aspect ratio, see [`pixel_aspect`].
+    aspect: (u32, u32),
+}
+
+impl Renderer {
+    /// Create a renderer into the given window, which waits for its vertical
+    /// blank to show a picture.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no GPU can render into the window.
+    pub fn new(window: Arc<Window>, aspect: (u32, u32)) -> Result<Self, Box<dyn Error>> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let size = window.inner_size();
+        let surface = instance.create_surface(window)?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))?;
+
+        let mut config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .ok_or("the surface of the window is not supported by the GPU")?;
+        config.present_mode = wgpu::PresentMode::AutoVsync;
+        surface.configure(&device, &config);
+
+        // The colors of the palette are sRGB colors, which must be decoded
+        // when the surface encodes them.
+        let format = if config.format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("picture"),
+            size: extent(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("picture"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vertex"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fragment"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(config.format.into())],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        // The default sampler picks the nearest pixel.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("picture"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Ok(Self {
+            surface,
+            device,
+            queue,
+            config,
+            texture,
+            bind_group,
+            pipeline,
+            bytes: vec![0; WIDTH * HEIGHT * 4],
+            aspect,
+        })
+    }
+
+    /// Change the pixel aspect ratio, see [`pixel_aspect`].
+    pub fn set_aspect(&mut self, aspect: (u32, u32)) {
+        self.aspect = aspect;
+    }
+
+    /// Return the pointer at a position within the window, relative to the
+    /// shown picture.
+    pub fn pointer(&self, position: PhysicalPosition<f64>) -> Pointer {
+        let surface = PhysicalSize::new(self.config.width, self.config.height);
+        let [x, y, width, height] = viewport(surface, self.aspect).map(to_f32);
+        let position = position.cast::<f32>();
+
+        Pointer::new((position.x - x) / width, (position.y - y) / height)
+    }
+
+    /// Resize the surface to the new size of the window.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // A minimized window has no size.
+        if size.width > 0 && size.height > 0 {
+            self.config.width = size.width;
+            self.config.height = size.height;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Show the given picture, as `0x00RRGGBB`, once the window is due to be
+    /// refreshed.
+    pub fn render(&mut self, picture: &[u32]) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.config);
+                return;
+            }
+            // The picture is dropped, the next one is shown instead.
+            Err(_) => return,
+        };
+
+        for (bytes, rgb) in self.bytes.chunks_exact_mut(4).zip(picture) {
+            let [_, red, green, blue] = rgb.to_be_bytes();
+            bytes.copy_from_slice(&[red, green, blue, 0xff]);
+        }
+        self.queue.write_texture(
+            self.texture.as_image_copy(),
+            &self.bytes,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(extent().width * 4),
+                rows_per_image: None,
+            },
+            extent(),
+        );
+
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("picture"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let surface = PhysicalSize::new(self.config.width, self.config.height);
+            let [x, y, width, height] = viewport(surface, self.aspect).map(to_f32);
+            pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        self.queue.submit([encoder.finish()]);
+        frame.present();
+    }
+}
+
+/// Return the size of the texture of the picture.
+fn extent() -> wgpu::Extent3d {
+    let [width, height] = [WIDTH, HEIGHT].map(|n| u32::try_from(n).unwrap_or(0));
+    wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// Convert a coordinate of the surface, which is far smaller than `2^16`,
+/// into a float.
+fn to_f32(coordinate: u32) -> f32 {
+    f32::from(u16::try_from(coordinate).unwrap_or(u16::MAX))
+}
@@ -0,0 +1,116 @@
+//! The standard controller, with its 8 buttons in a parallel-in, serial-out
+//! shift register (a 4021).
+//!
+//! While the strobe (`OUT0`) is high, the shift register is continuously
+//! loaded with the state of the buttons, so every read returns the state of
+//! the A button. Once the strobe is low again, every read returns the next
+//! button on `D0`, in the order A, B, Select, Start, Up, Down, Left and
+//! Right. After all 8 buttons, an official controller returns 1 on all
+//! further reads.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Standard_controller>
+
+use std::io::{self, Read, Write};
+
+use crate::Device;
+
+bitflags::bitflags! {
+    /// The state of the buttons of a standard controller, a set bit being a
+    /// pressed button.
+    ///
+    /// The bits are in the order in which the buttons are read.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct ButtonState: u8 {
+        /// The A button.
+        const A = 1 << 0;
+        /// The B button.
+        const B = 1 << 1;
+        /// The Select button.
+        const SELECT = 1 << 2;
+        /// The Start button.
+        const START = 1 << 3;
+        /// Up on the control pad.
+        const UP = 1 << 4;
+        /// Down on the control pad.
+        const DOWN = 1 << 5;
+        /// Left on the control pad.
+        const LEFT = 1 << 6;
+        /// Right on the control pad.
+        const RIGHT = 1 << 7;
+    }
+}
+
+/// A standard controller.
+#[derive(Debug, Clone, Default)]
+pub struct Controller {
+    /// The state of the buttons, as given by the frontend.
+    buttons: ButtonState,
+    /// The strobe, i.e. `OUT0`.
+    strobe: bool,
+    /// The shift register, whose lowest bit is read next.
+    shift: u8,
+}
+
+impl Controller {
+    /// Create a controller without any button pressed.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the state of the buttons.
+    #[must_use]
+    pub const fn buttons(&self) -> ButtonState {
+        self.buttons
+    }
+
+    /// Set the state of the buttons, which is loaded into the shift register
+    /// by the next strobe.
+    pub fn set_buttons(&mut self, buttons: ButtonState) {
+        self.buttons = buttons;
+
+        if self.strobe {
+            self.shift = buttons.bits();
+        }
+    }
+}
+
+impl Device for Controller {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 != 0;
+
+        if self.strobe {
+            self.shift = self.buttons.bits();
+        }
+    }
+
+    fn read(&self) -> u8 {
+        self.shift & 1
+    }
+
+    fn clock(&mut self) {
+        if !self.strobe {
+            // The serial input of the shift register is tied high.
+            self.shift = (self.shift >> 1) | 0x80;
+        }
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(self.strobe), self.shift])
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 2];
+        reader.read_exact(&mut state)?;
+
+        self.strobe = state[0] != 0;
+        self.shift = state[1];
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
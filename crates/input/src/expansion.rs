@@ -0,0 +1,169 @@
+//! The devices of the Famicom's expansion port, like the third and fourth
+//! controllers of a Famicom.
+//!
+//! The expansion port carries all 3 bits of the `OUT` latch and the `/OE`
+//! lines of both registers, but other data lines than the controller ports:
+//! `D1` of `$4016`, and `D1`-`D4` of `$4017`. Its devices read the latch and
+//! answer on these lines when the CPU reads either register, e.g. keyboards
+//! scan their rows by `OUT1`-`OUT2` and return the keys on `D1`-`D4`, and
+//! paddles and barcode readers shift their bits out on `D1`.
+//!
+//! An [`ExpansionDevice`] sees these signals, and is plugged into the
+//! expansion port with [`Ports::plug_expansion`](crate::Ports::plug_expansion).
+//!
+//! ```
+//! # use chuck_input::{ButtonState, ExpansionControllers, Port, Ports};
+//! let mut ports = Ports::famicom();
+//! ports
+//!     .expansion_mut::<ExpansionControllers>()
+//!     .unwrap()
+//!     .set_buttons(1, ButtonState::A);
+//!
+//! // The fourth controller answers on `D1` of `$4017`.
+//! ports.write(1);
+//! ports.write(0);
+//! assert_eq!(ports.read(Port::Two) & 0b10, 0b10);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Expansion_port>
+
+use std::any::Any;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::{ButtonState, Controller, Device, Port, Screen};
+
+/// The data lines of the expansion port in a read of `$4016`, i.e. `D1`.
+pub const PORT_ONE_LINES: u8 = 0x02;
+
+/// The data lines of the expansion port in a read of `$4017`, i.e. `D1`-`D4`.
+pub const PORT_TWO_LINES: u8 = 0x1e;
+
+/// A device plugged into the expansion port of a Famicom.
+///
+/// The system calls [`ExpansionDevice::write`] for every write to `$4016`,
+/// and [`ExpansionDevice::read`] for every read of either register, followed
+/// by [`ExpansionDevice::clock`] once the `/OE` line of the register is
+/// released again.
+pub trait ExpansionDevice: Any + fmt::Debug + Send {
+    /// Write the `OUT` latch, i.e. bits 0-2 of a write to `$4016`.
+    fn write(&mut self, out: u8);
+
+    /// Return the data lines while the `/OE` of a port is asserted, in their
+    /// bits of the register, i.e. within [`PORT_ONE_LINES`] for port 1 and
+    /// [`PORT_TWO_LINES`] for port 2. The other bits are ignored.
+    fn read(&self, port: Port) -> u8;
+
+    /// Observe the release of the `/OE` of a port at the end of a read.
+    fn clock(&mut self, port: Port);
+
+    /// Observe the picture at the time of a read of a port, before
+    /// [`ExpansionDevice::read`].
+    ///
+    /// Does nothing by default, as only light guns depend on it.
+    fn observe(&mut self, _port: Port, _screen: &Screen<'_>) {}
+
+    /// Save the state of the device, but not the state of its buttons, which
+    /// is given by the frontend.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Load the state of the device saved by [`ExpansionDevice::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed.
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+
+    /// Clone the device into a new box, see the `Clone` implementation of
+    /// `Box<dyn ExpansionDevice>`.
+    fn boxed_clone(&self) -> Box<dyn ExpansionDevice>;
+}
+
+impl Clone for Box<dyn ExpansionDevice> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// The third and fourth controllers of a Famicom, plugged into its expansion
+/// port, which answer on `D1` of `$4016` and `$4017` respectively.
+#[derive(Debug, Clone, Default)]
+pub struct ExpansionControllers {
+    /// The controllers read with port 1 and port 2.
+    controllers: [Controller; 2],
+}
+
+impl ExpansionControllers {
+    /// Create the controllers without any button pressed.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the state of the buttons of the third (`0`) or the fourth
+    /// (`1`) controller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is neither `0` nor `1`.
+    #[must_use]
+    pub const fn buttons(&self, index: usize) -> ButtonState {
+        self.controllers[index].buttons()
+    }
+
+    /// Set the state of the buttons of the third (`0`) or the fourth (`1`)
+    /// controller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is neither `0` nor `1`.
+    pub fn set_buttons(&mut self, index: usize, buttons: ButtonState) {
+        self.controllers[index].set_buttons(buttons);
+    }
+
+    /// Return the controller read with a port.
+    const fn controller(&self, port: Port) -> &Controller {
+        &self.controllers[port.index()]
+    }
+}
+
+impl ExpansionDevice for ExpansionControllers {
+    fn write(&mut self, out: u8) {
+        for controller in &mut self.controllers {
+            controller.write(out);
+        }
+    }
+
+    fn read(&self, port: Port) -> u8 {
+        (self.controller(port).read() & 1) << 1
+    }
+
+    fn clock(&mut self, port: Port) {
+        self.controllers[port.index()].clock();
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for controller in &self.controllers {
+            controller.save(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        for controller in &mut self.controllers {
+            controller.load(reader)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn ExpansionDevice> {
+        Box::new(self.clone())
+    }
+}
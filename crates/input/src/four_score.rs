@@ -0,0 +1,131 @@
+//! The Four Score, an adapter for 4 standard controllers that is plugged into
+//! both controller ports.
+//!
+//! Each half of the Four Score joins two controllers and a signature into a
+//! 24-bit report: the 8 buttons of the first controller (1 or 2), then those
+//! of the second controller (3 or 4), then the signature, by which games tell
+//! the Four Score from two plain controllers. The signature reads as
+//! `0, 0, 0, 1, 0, 0, 0, 0` on port 1 and `0, 0, 1, 0, 0, 0, 0, 0` on port 2,
+//! and all further reads return 1.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Four_Score>
+
+use std::io::{self, Read, Write};
+
+use crate::{ButtonState, Device, Port};
+
+/// The bit filled into the shift register after the report.
+const FILL: u32 = 1 << 23;
+
+/// The half of a Four Score that is plugged into one of the controller ports.
+#[derive(Debug, Clone)]
+pub struct FourScore {
+    /// The state of the buttons of both controllers, as given by the
+    /// frontend.
+    buttons: [ButtonState; 2],
+    /// The signature of the port.
+    signature: u8,
+    /// The strobe, i.e. `OUT0`.
+    strobe: bool,
+    /// The shift register of the report, whose lowest bit is read next.
+    shift: u32,
+}
+
+impl FourScore {
+    /// Create the half of a Four Score that is plugged into the given port,
+    /// without any button pressed.
+    #[must_use]
+    pub const fn new(port: Port) -> Self {
+        let signature = match port {
+            Port::One => 0x08,
+            Port::Two => 0x04,
+        };
+
+        Self {
+            buttons: [ButtonState::empty(); 2],
+            signature,
+            strobe: false,
+            shift: 0,
+        }
+    }
+
+    /// Return the state of the buttons of the first (`0`) or the second
+    /// (`1`) controller of this half.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is neither `0` nor `1`.
+    #[must_use]
+    pub const fn buttons(&self, controller: usize) -> ButtonState {
+        self.buttons[controller]
+    }
+
+    /// Set the state of the buttons of the first (`0`) or the second (`1`)
+    /// controller of this half, which is loaded by the next strobe.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is neither `0` nor `1`.
+    pub fn set_buttons(&mut self, controller: usize, buttons: ButtonState) {
+        self.buttons[controller] = buttons;
+
+        if self.strobe {
+            self.reload();
+        }
+    }
+
+    /// Load the report into the shift register.
+    fn reload(&mut self) {
+        self.shift = u32::from(self.buttons[0].bits())
+            | (u32::from(self.buttons[1].bits()) << 8)
+            | (u32::from(self.signature) << 16);
+    }
+}
+
+impl Device for FourScore {
+    fn write(&mut self, out: u8) {
+        self.strobe = out & 1 != 0;
+
+        if self.strobe {
+            self.reload();
+        }
+    }
+
+    fn read(&self) -> u8 {
+        u8::from(self.shift & 1 != 0)
+    }
+
+    fn clock(&mut self) {
+        if !self.strobe {
+            self.shift = (self.shift >> 1) | FILL;
+        }
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(self.strobe)])?;
+        writer.write_all(&self.shift.to_le_bytes())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 5];
+        reader.read_exact(&mut state)?;
+
+        let shift = u32::from_le_bytes([state[1], state[2], state[3], state[4]]);
+        if shift >= FILL << 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid shift register in four score state",
+            ));
+        }
+
+        self.strobe = state[0] != 0;
+        self.shift = shift;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,432 @@
+//! The input devices of the NES, like its controllers, plugged into the two
+//! controller ports.
+//!
+//! The ports are wired to the 2A03 through 3 signals: the `OUT` latch, which
+//! is written by `$4016` (bit 0 is the strobe of the controllers), and the
+//! `/OE` lines of the two ports, which are asserted while the CPU reads
+//! `$4016` (port 1) or `$4017` (port 2). A read returns the data lines
+//! `D0`-`D4` of the port in the lower 5 bits, while the upper 3 bits aren't
+//! driven (open bus). The devices usually shift out their next bit when `/OE`
+//! is released again, at the end of the read.
+//!
+//! Every device is implemented as a [`Device`], which sees nothing but these
+//! signals, and the [`Ports`] connect them to the system. Light guns like the
+//! [`Zapper`] additionally see the picture on the [`Screen`], since they sense
+//! the light of the TV.
+//!
+//! The Famicom's expansion port holds an [`ExpansionDevice`], which sees the
+//! whole `OUT` latch and answers on other data lines, see [`expansion`],
+//! like the storage of a [`TurboFile`].
+//!
+//! Games for 4 players read the controllers 3 and 4 either through a
+//! [`FourScore`], which is plugged into both ports, or on `D1` from the
+//! Famicom's expansion port, see [`Ports::four_score`] and [`Ports::famicom`].
+//!
+//! ```
+//! # use chuck_input::{ButtonState, Controller, Port, Ports};
+//! let mut ports = Ports::new();
+//! ports.device_mut::<Controller>(Port::One).unwrap().set_buttons(ButtonState::A | ButtonState::START);
+//!
+//! // Strobe the controller, then shift in its 8 buttons.
+//! ports.write(1);
+//! ports.write(0);
+//!
+//! let mut buttons = 0;
+//! for i in 0..8 {
+//!     // The read cycle of an `LDA $4016`, and the next opcode fetch.
+//!     buttons |= (ports.read(Port::One) & 1) << i;
+//!     ports.step();
+//!     ports.step();
+//! }
+//!
+//! assert_eq!(buttons, 0b0000_1001);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Input_devices>
+//! - <https://www.nesdev.org/wiki/Controller_port_pinout>
+//! - <https://www.nesdev.org/wiki/Controller_port_registers>
+//! - <https://www.nesdev.org/wiki/Expansion_port>
+
+pub mod expansion;
+pub mod turbo_file;
+
+mod controller;
+mod four_score;
+mod zapper;
+
+pub use controller::{ButtonState, Controller};
+pub use expansion::{ExpansionControllers, ExpansionDevice};
+pub use four_score::FourScore;
+pub use turbo_file::TurboFile;
+pub use zapper::{Crosshair, Offscreen, Pointer, Zapper};
+
+use std::any::Any;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// The mask of the data lines `D0`-`D4` in a read of a port.
+pub const DATA_LINES: u8 = 0x1f;
+
+/// A device plugged into a controller port.
+///
+/// The system calls [`Device::write`] for every write to `$4016`, and
+/// [`Device::read`] for every read of the port's register, followed by
+/// [`Device::clock`] once the `/OE` line of the port is released again.
+pub trait Device: Any + fmt::Debug + Send {
+    /// Write the `OUT` latch, i.e. bits 0-2 of a write to `$4016`.
+    fn write(&mut self, out: u8);
+
+    /// Return the data lines `D0`-`D4` while `/OE` is asserted, in the lower
+    /// 5 bits.
+    fn read(&self) -> u8;
+
+    /// Observe the release of `/OE` at the end of a read, on which most
+    /// devices shift out their next bit.
+    fn clock(&mut self);
+
+    /// Observe the picture at the time of a read, before [`Device::read`].
+    ///
+    /// Does nothing by default, as only light guns depend on it.
+    fn observe(&mut self, _screen: &Screen<'_>) {}
+
+    /// Save the state of the device, but not the state of its buttons, which
+    /// is given by the frontend.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Load the state of the device saved by [`Device::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed.
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+
+    /// Clone the device into a new box, see the `Clone` implementation of
+    /// `Box<dyn Device>`.
+    fn boxed_clone(&self) -> Box<dyn Device>;
+}
+
+impl Clone for Box<dyn Device> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// The picture drawn by the PPU, as seen by a light gun.
+#[derive(Debug, Clone, Copy)]
+pub struct Screen<'a> {
+    /// The colors of the frame buffer, row by row, as indices into the NES's
+    /// palette (the lower 6 bits) and the emphasis bits (the upper 3 bits).
+    ///
+    /// The pixels the PPU has drawn in the current frame replace those of the
+    /// previous frame.
+    pub pixels: &'a [u16],
+    /// The scanline of the PPU, `0`-`261` on NTSC.
+    pub scanline: u16,
+    /// The dot of the PPU within the scanline, `0`-`340`.
+    pub dot: u16,
+    /// The number of scanlines of a frame, 262 on NTSC and 312 on PAL.
+    pub scanlines: u16,
+}
+
+impl Screen<'_> {
+    /// The width of the picture in pixels.
+    pub const WIDTH: u16 = 256;
+
+    /// The height of the picture in pixels.
+    pub const HEIGHT: u16 = 240;
+
+    /// Return the color of a pixel of the picture.
+    #[must_use]
+    pub fn pixel(&self, x: u16, y: u16) -> u16 {
+        self.pixels[usize::from(y) * usize::from(Self::WIDTH) + usize::from(x)]
+    }
+}
+
+/// One of the two controller ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    /// Port 1, read at `$4016`.
+    One,
+    /// Port 2, read at `$4017`.
+    Two,
+}
+
+impl Port {
+    /// Return the port read at the given address, i.e. `$4016` or `$4017`.
+    #[must_use]
+    pub const fn from_addr(addr: u16) -> Self {
+        if addr & 1 == 0 {
+            Self::One
+        } else {
+            Self::Two
+        }
+    }
+
+    /// Return the index of the port, `0` or `1`.
+    pub(crate) const fn index(self) -> usize {
+        match self {
+            Self::One => 0,
+            Self::Two => 1,
+        }
+    }
+}
+
+/// The two controller ports, and the expansion port of the Famicom.
+#[derive(Debug, Clone)]
+pub struct Ports {
+    /// The devices plugged into the ports.
+    devices: [Option<Box<dyn Device>>; 2],
+    /// The device plugged into the expansion port.
+    expansion: Option<Box<dyn ExpansionDevice>>,
+    /// The port whose `/OE` is asserted in the current cycle.
+    oe: Option<Port>,
+    /// The port whose `/OE` was asserted in the previous cycle.
+    previous: Option<Port>,
+}
+
+impl Ports {
+    /// Create the ports with a [`Controller`] plugged into each of them.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            devices: [
+                Some(Box::new(Controller::new())),
+                Some(Box::new(Controller::new())),
+            ],
+            expansion: None,
+            oe: None,
+            previous: None,
+        }
+    }
+
+    /// Create the ports with a [`FourScore`] plugged into both of them, for 4
+    /// controllers.
+    #[must_use]
+    pub fn four_score() -> Self {
+        Self {
+            devices: [
+                Some(Box::new(FourScore::new(Port::One))),
+                Some(Box::new(FourScore::new(Port::Two))),
+            ],
+            ..Self::new()
+        }
+    }
+
+    /// Create the ports of a Famicom with 4 controllers, i.e. with a
+    /// [`Controller`] plugged into each of the ports and two more into the
+    /// expansion port, see [`ExpansionControllers`].
+    #[must_use]
+    pub fn famicom() -> Self {
+        Self {
+            expansion: Some(Box::new(ExpansionControllers::new())),
+            ..Self::new()
+        }
+    }
+
+    /// Plug the given device into a port, returning the device that was
+    /// plugged in before.
+    pub fn plug(&mut self, port: Port, device: Box<dyn Device>) -> Option<Box<dyn Device>> {
+        self.devices[port.index()].replace(device)
+    }
+
+    /// Unplug the device of a port, returning it.
+    pub fn unplug(&mut self, port: Port) -> Option<Box<dyn Device>> {
+        self.devices[port.index()].take()
+    }
+
+    /// Return the device plugged into a port.
+    #[must_use]
+    pub fn device(&self, port: Port) -> Option<&dyn Device> {
+        self.devices[port.index()].as_deref()
+    }
+
+    /// Return the device plugged into a port as the given type, or `None` if
+    /// the port is empty or the device is of another type.
+    #[must_use]
+    pub fn device_mut<D: Device>(&mut self, port: Port) -> Option<&mut D> {
+        let device: &mut dyn Any = self.devices[port.index()].as_deref_mut()?;
+        device.downcast_mut()
+    }
+
+    /// Plug the given device into the expansion port, returning the device
+    /// that was plugged in before.
+    pub fn plug_expansion(
+        &mut self,
+        device: Box<dyn ExpansionDevice>,
+    ) -> Option<Box<dyn ExpansionDevice>> {
+        self.expansion.replace(device)
+    }
+
+    /// Unplug the device of the expansion port, returning it.
+    pub fn unplug_expansion(&mut self) -> Option<Box<dyn ExpansionDevice>> {
+        self.expansion.take()
+    }
+
+    /// Return the device plugged into the expansion port.
+    #[must_use]
+    pub fn expansion(&self) -> Option<&dyn ExpansionDevice> {
+        self.expansion.as_deref()
+    }
+
+    /// Return the device plugged into the expansion port as the given type,
+    /// see [`Ports::device_mut`].
+    #[must_use]
+    pub fn expansion_mut<D: ExpansionDevice>(&mut self) -> Option<&mut D> {
+        let device: &mut dyn Any = self.expansion.as_deref_mut()?;
+        device.downcast_mut()
+    }
+
+    /// Write the `OUT` latch of all devices, i.e. write to `$4016`.
+    pub fn write(&mut self, data: u8) {
+        for device in self.devices.iter_mut().flatten() {
+            device.write(data & 7);
+        }
+        if let Some(device) = &mut self.expansion {
+            device.write(data & 7);
+        }
+    }
+
+    /// Read a port, asserting its `/OE` in the current cycle, and return its
+    /// data lines `D0`-`D4`, see [`DATA_LINES`].
+    ///
+    /// An empty port reads as `0`.
+    pub fn read(&mut self, port: Port) -> u8 {
+        self.oe = Some(port);
+        self.peek(port)
+    }
+
+    /// Let the devices read with a port observe the picture before they are
+    /// read, see [`Device::observe`].
+    pub fn observe(&mut self, port: Port, screen: &Screen<'_>) {
+        if let Some(device) = &mut self.devices[port.index()] {
+            device.observe(screen);
+        }
+        if let Some(device) = &mut self.expansion {
+            device.observe(port, screen);
+        }
+    }
+
+    /// Return the data lines of a port without asserting its `/OE`, e.g. for
+    /// debuggers.
+    #[must_use]
+    pub fn peek(&self, port: Port) -> u8 {
+        let data = self.devices[port.index()]
+            .as_ref()
+            .map_or(0, |device| device.read());
+        let lines = match port {
+            Port::One => expansion::PORT_ONE_LINES,
+            Port::Two => expansion::PORT_TWO_LINES,
+        };
+        let expansion = self
+            .expansion
+            .as_ref()
+            .map_or(0, |device| device.read(port) & lines);
+
+        (data | expansion) & DATA_LINES
+    }
+
+    /// Complete a CPU cycle, releasing the `/OE` of a port that was read in
+    /// the previous cycle but not in this one.
+    ///
+    /// As `/OE` stays asserted over consecutive reads of the same port (e.g.
+    /// the reads repeated while the CPU is halted by a DMA), these only clock
+    /// the devices once.
+    pub fn step(&mut self) {
+        if let Some(port) = self.previous {
+            if self.oe != Some(port) {
+                if let Some(device) = &mut self.devices[port.index()] {
+                    device.clock();
+                }
+                if let Some(device) = &mut self.expansion {
+                    device.clock(port);
+                }
+            }
+        }
+
+        self.previous = self.oe.take();
+    }
+
+    /// Save the state of the ports and their devices, see [`Device::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let encode = |port| match port {
+            None => 0,
+            Some(Port::One) => 1,
+            Some(Port::Two) => 2,
+        };
+        writer.write_all(&[encode(self.oe), encode(self.previous)])?;
+
+        for device in &self.devices {
+            writer.write_all(&[u8::from(device.is_some())])?;
+
+            if let Some(device) = device {
+                device.save(writer)?;
+            }
+        }
+
+        writer.write_all(&[u8::from(self.expansion.is_some())])?;
+        if let Some(device) = &self.expansion {
+            device.save(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load the state of the ports saved by [`Ports::save`], which requires
+    /// the same devices to be plugged in.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed or another
+    /// set of ports is used.
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let decode = |byte| match byte {
+            0 => Ok(None),
+            1 => Ok(Some(Port::One)),
+            2 => Ok(Some(Port::Two)),
+            _ => Err(invalid("invalid port in input state")),
+        };
+
+        let mut ports = [0; 2];
+        reader.read_exact(&mut ports)?;
+        let oe = decode(ports[0])?;
+        let previous = decode(ports[1])?;
+
+        let mut loaded = self.clone();
+        let present = |reader: &mut dyn Read| {
+            let mut present = [0; 1];
+            reader.read_exact(&mut present).map(|()| present[0])
+        };
+
+        for device in &mut loaded.devices {
+            match (device, present(reader)?) {
+                (Some(device), 1) => device.load(reader)?,
+                (None, 0) => {}
+                _ => return Err(invalid("input state with other devices")),
+            }
+        }
+
+        match (&mut loaded.expansion, present(reader)?) {
+            (Some(device), 1) => device.load(reader)?,
+            (None, 0) => {}
+            _ => return Err(invalid("input state with other devices")),
+        }
+
+        loaded.oe = oe;
+        loaded.previous = previous;
+        *self = loaded;
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! The ASCII Turbo File, a battery-backed storage of 8 KiB for the Famicom's
+//! expansion port, which games use to save their progress.
+//!
+//! The storage is a stream of bits with a single address, which is driven by
+//! the `OUT` latch:
+//!
+//! | Bit    | Description                                                  |
+//! |--------|--------------------------------------------------------------|
+//! | `OUT0` | The bit to write.                                            |
+//! | `OUT1` | The reset: while low, the address is held at the first bit.  |
+//! | `OUT2` | The clock: its falling edge writes `OUT0` at the address and |
+//! |        | advances the address.                                        |
+//!
+//! The bit at the address is read on `D2` of `$4017`. Since every clock
+//! writes a bit, games read the storage by writing back the bit they read.
+//!
+//! ```
+//! # use chuck_input::{Port, Ports, TurboFile};
+//! let mut ports = Ports::new();
+//! ports.plug_expansion(Box::new(TurboFile::new()));
+//!
+//! // Reset the address, then write the bits 1 and 0.
+//! ports.write(0b000);
+//! for bit in [1, 0] {
+//!     ports.write(0b110 | bit);
+//!     ports.write(0b010 | bit);
+//! }
+//!
+//! ports.write(0b000);
+//! ports.write(0b010);
+//! assert_eq!(ports.read(Port::Two) & 0b100, 0b100);
+//!
+//! let turbo_file = ports.expansion_mut::<TurboFile>().unwrap();
+//! assert!(turbo_file.is_dirty());
+//! assert_eq!(turbo_file.data()[0], 0b01);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/ASCII_Turbo_File>
+
+use std::io::{self, Read, Write};
+
+use crate::{ExpansionDevice, Port};
+
+/// The size of the storage, in bytes.
+pub const SIZE: usize = 0x2000;
+
+/// The reset line of the address, `OUT1`.
+const RESET: u8 = 0x02;
+
+/// The clock of the writes, `OUT2`.
+const CLOCK: u8 = 0x04;
+
+/// An ASCII Turbo File plugged into the expansion port.
+#[derive(Debug, Clone)]
+pub struct TurboFile {
+    /// The storage, whose bits are addressed from the lowest bit of the first
+    /// byte.
+    data: Box<[u8]>,
+    /// The address of the current bit.
+    address: u16,
+    /// The last `OUT` latch.
+    out: u8,
+    /// A flag denoting if the storage changed since it was last saved or
+    /// loaded.
+    dirty: bool,
+}
+
+impl TurboFile {
+    /// Create a Turbo File whose storage is cleared.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            data: vec![0; SIZE].into_boxed_slice(),
+            address: 0,
+            out: 0,
+            dirty: false,
+        }
+    }
+
+    /// Return the storage.
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Check if the storage changed since it was last saved or loaded.
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Save the storage, i.e. its raw contents, e.g. into a file of the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save_data(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.data)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Load the storage saved by [`TurboFile::save_data`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the data isn't [`SIZE`] bytes long.
+    pub fn load_data(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut data = Vec::with_capacity(SIZE);
+        reader.read_to_end(&mut data)?;
+        if data.len() != SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "turbo file data of the wrong size",
+            ));
+        }
+
+        self.data = data.into_boxed_slice();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Return the byte of the current bit, and the mask of the bit.
+    fn bit(&self) -> (usize, u8) {
+        (usize::from(self.address / 8), 1 << (self.address % 8))
+    }
+}
+
+impl Default for TurboFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpansionDevice for TurboFile {
+    fn write(&mut self, out: u8) {
+        if out & RESET == 0 {
+            self.address = 0;
+        }
+
+        if self.out & CLOCK != 0 && out & CLOCK == 0 {
+            let (index, mask) = self.bit();
+            let byte = if out & 1 == 0 {
+                self.data[index] & !mask
+            } else {
+                self.data[index] | mask
+            };
+            self.dirty |= byte != self.data[index];
+            self.data[index] = byte;
+            // The address has 16 bits, one for every bit of the storage.
+            self.address = self.address.wrapping_add(1);
+        }
+
+        self.out = out;
+    }
+
+    fn read(&self, port: Port) -> u8 {
+        let (index, mask) = self.bit();
+        match port {
+            Port::One => 0,
+            Port::Two => u8::from(self.data[index] & mask != 0) << 2,
+        }
+    }
+
+    fn clock(&mut self, _port: Port) {}
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.address.to_le_bytes())?;
+        writer.write_all(&[self.out])?;
+        writer.write_all(&self.data)
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 3];
+        reader.read_exact(&mut state)?;
+        reader.read_exact(&mut self.data)?;
+
+        self.address = u16::from_le_bytes([state[0], state[1]]);
+        self.out = state[2];
+        // The file of the host is shared by every game, so only the writes
+        // of the game mark the storage as changed, not the state.
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn ExpansionDevice> {
+        Box::new(self.clone())
+    }
+}
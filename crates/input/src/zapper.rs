@@ -0,0 +1,332 @@
+//! The Zapper, a light gun with a photodiode and a trigger.
+//!
+//! The photodiode senses the light of the CRT's beam while it passes the
+//! aimed spot of the screen, and keeps sensing it for a short while, as the
+//! phosphors glow and the diode's circuit is slow to discharge. Games flash a
+//! white target for a frame and check for light while the beam is drawing
+//! it, so the light sense (`D3`) has to follow the PPU's position in the
+//! frame. The trigger (`D4`) reads as 1 while it is pulled.
+//!
+//! As nothing but the frontend knows where the Zapper is aimed, the light
+//! sense is computed from the picture drawn by the PPU, which the system
+//! passes to [`Device::observe`] before every read.
+//!
+//! Frontends aim the Zapper with any absolute pointing device, like a mouse,
+//! a touch screen or the pointer of libretro, by its [`Pointer`] over the
+//! shown picture. A pointer off the picture aims off the screen, where the
+//! [`Offscreen`] handling decides what a pulled trigger does, since games
+//! differ in what they expect: some reload when shot off the screen, while
+//! others count it as a miss. As the aimed spot is hard to tell without the
+//! gun in hand, the frontend may draw a [`Crosshair`] on the shown picture:
+//!
+//! ```
+//! # use chuck_input::{Crosshair, Offscreen, Pointer, Zapper};
+//! let mut zapper = Zapper::new();
+//! zapper.set_crosshair(Some(Crosshair::default()));
+//!
+//! // The pointer in the middle of the picture.
+//! zapper.set_pointer(Some(Pointer::new(0.5, 0.5)));
+//! assert_eq!(zapper.aim(), Some((128, 120)));
+//!
+//! let mut picture = vec![0; 256 * 240];
+//! zapper.draw_crosshair(&mut picture);
+//! assert_eq!(picture[120 * 256 + 124], Crosshair::default().color);
+//!
+//! // Shots off the screen don't pull the trigger, e.g. for games which
+//! // reload instead.
+//! zapper.set_offscreen(Offscreen::Ignore);
+//! zapper.set_pointer(Some(Pointer::new(1.5, 0.5)));
+//! zapper.set_trigger(true);
+//! assert_eq!(zapper.aim(), None);
+//! assert!(!zapper.is_pulled());
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Zapper>
+
+use std::io::{self, Read, Write};
+
+use crate::{Device, Screen};
+
+/// The number of dots of a scanline.
+const SCANLINE_DOTS: u32 = 341;
+
+/// The number of dots the photodiode keeps sensing a drawn pixel, about 20
+/// scanlines.
+const LATENCY: u32 = 20 * SCANLINE_DOTS;
+
+/// The distance in pixels from the aimed spot within which the photodiode
+/// senses light.
+const RADIUS: u16 = 2;
+
+/// The largest coordinate of the pointer of libretro.
+const LIBRETRO_MAX: f32 = 32_767.0;
+
+/// The absolute position of a pointing device over the shown picture, where
+/// `(0.0, 0.0)` is the top left corner of the picture and `(1.0, 1.0)` its
+/// bottom right corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pointer {
+    /// The horizontal position.
+    pub x: f32,
+    /// The vertical position.
+    pub y: f32,
+}
+
+impl Pointer {
+    /// Create a pointer at the given position.
+    #[must_use]
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// Create a pointer at a position of the pointer of libretro
+    /// (`RETRO_DEVICE_POINTER`), whose coordinates span the picture from
+    /// `-0x7fff` to `0x7fff`.
+    #[must_use]
+    pub fn from_libretro(x: i16, y: i16) -> Self {
+        let scale = |coordinate: i16| f32::midpoint(f32::from(coordinate) / LIBRETRO_MAX, 1.0);
+        Self::new(scale(x), scale(y))
+    }
+
+    /// Return the pixel `(x, y)` of the picture at the pointer, or `None` if
+    /// the pointer is off the picture.
+    #[must_use]
+    pub fn pixel(self) -> Option<(u16, u16)> {
+        let on_screen = (0.0..1.0).contains(&self.x) && (0.0..1.0).contains(&self.y);
+        on_screen.then(|| self.clamped_pixel())
+    }
+
+    /// Return the pixel `(x, y)` of the picture at the pointer, or the
+    /// nearest pixel at the edge of the picture if the pointer is off the
+    /// picture.
+    #[must_use]
+    pub fn clamped_pixel(self) -> (u16, u16) {
+        let clamp = |coordinate: f32, size: u16| {
+            let pixel = coordinate * f32::from(size);
+            // The last pixel starting at or before the position, which avoids
+            // the lossy cast of the float.
+            (0..size)
+                .rev()
+                .find(|&n| f32::from(n) <= pixel)
+                .unwrap_or(0)
+        };
+
+        (clamp(self.x, Screen::WIDTH), clamp(self.y, Screen::HEIGHT))
+    }
+}
+
+/// What a pulled trigger does while the Zapper is aimed off the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Offscreen {
+    /// The trigger is pulled and the photodiode senses no light, like a real
+    /// Zapper aimed away from the TV. Most games reload on it, if at all.
+    #[default]
+    Shoot,
+    /// The trigger stays released, so shots off the screen are ignored.
+    Ignore,
+    /// The Zapper is aimed at the nearest pixel at the edge of the screen
+    /// instead.
+    Clamp,
+}
+
+/// A crosshair drawn on the shown picture at the spot the Zapper is aimed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crosshair {
+    /// The color of the crosshair, as `0x00RRGGBB`.
+    pub color: u32,
+    /// The length of each of the 4 arms of the crosshair, in pixels.
+    pub size: u16,
+}
+
+impl Default for Crosshair {
+    fn default() -> Self {
+        Self {
+            color: 0x00ff_2020,
+            size: 4,
+        }
+    }
+}
+
+impl Crosshair {
+    /// Draw the crosshair centered on a pixel `(x, y)` of a picture, given
+    /// row by row as `0x00RRGGBB`. The aimed pixel itself is kept visible.
+    pub fn draw(&self, (x, y): (u16, u16), picture: &mut [u32]) {
+        let width = usize::from(Screen::WIDTH);
+        let mut plot = |x: Option<u16>, y: Option<u16>| {
+            if let (Some(x), Some(y)) = (x, y) {
+                if x < Screen::WIDTH && y < Screen::HEIGHT {
+                    picture[usize::from(y) * width + usize::from(x)] = self.color;
+                }
+            }
+        };
+
+        for arm in 1..=self.size {
+            plot(x.checked_sub(arm), Some(y));
+            plot(x.checked_add(arm), Some(y));
+            plot(Some(x), y.checked_sub(arm));
+            plot(Some(x), y.checked_add(arm));
+        }
+    }
+}
+
+/// A Zapper.
+#[derive(Debug, Clone, Default)]
+pub struct Zapper {
+    /// The aimed pixel, or `None` if the Zapper is aimed off the screen.
+    aim: Option<(u16, u16)>,
+    /// A flag denoting if the trigger is pulled.
+    trigger: bool,
+    /// A flag denoting if the photodiode senses light.
+    light: bool,
+    /// What a pulled trigger does while aimed off the screen.
+    offscreen: Offscreen,
+    /// The crosshair drawn at the aimed spot, if any.
+    crosshair: Option<Crosshair>,
+}
+
+impl Zapper {
+    /// Create a Zapper that is aimed off the screen.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the aimed pixel as `(x, y)`, or `None` if the Zapper is aimed
+    /// off the screen.
+    #[must_use]
+    pub const fn aim(&self) -> Option<(u16, u16)> {
+        self.aim
+    }
+
+    /// Aim the Zapper at a pixel `(x, y)` of the picture, or off the screen.
+    pub fn set_aim(&mut self, aim: Option<(u16, u16)>) {
+        self.aim = aim;
+    }
+
+    /// Aim the Zapper at the position of a pointing device over the shown
+    /// picture, where `None` (e.g. a mouse outside of the window) or a
+    /// position off the picture aims off the screen, see [`Offscreen`].
+    pub fn set_pointer(&mut self, pointer: Option<Pointer>) {
+        self.aim = match (pointer, self.offscreen) {
+            (Some(pointer), Offscreen::Clamp) => Some(pointer.clamped_pixel()),
+            (pointer, _) => pointer.and_then(Pointer::pixel),
+        };
+    }
+
+    /// Check if the trigger is held by the player, see [`Zapper::is_pulled`].
+    #[must_use]
+    pub const fn trigger(&self) -> bool {
+        self.trigger
+    }
+
+    /// Hold or release the trigger.
+    pub fn set_trigger(&mut self, trigger: bool) {
+        self.trigger = trigger;
+    }
+
+    /// Check if the trigger reads as pulled, i.e. if it's held, unless it's
+    /// ignored off the screen.
+    #[must_use]
+    pub fn is_pulled(&self) -> bool {
+        self.trigger && (self.aim.is_some() || self.offscreen != Offscreen::Ignore)
+    }
+
+    /// Return what a pulled trigger does while aimed off the screen.
+    #[must_use]
+    pub const fn offscreen(&self) -> Offscreen {
+        self.offscreen
+    }
+
+    /// Set what a pulled trigger does while aimed off the screen, which
+    /// applies from the next [`Zapper::set_pointer`].
+    pub fn set_offscreen(&mut self, offscreen: Offscreen) {
+        self.offscreen = offscreen;
+    }
+
+    /// Return the crosshair drawn at the aimed spot, if any.
+    #[must_use]
+    pub const fn crosshair(&self) -> Option<Crosshair> {
+        self.crosshair
+    }
+
+    /// Set the crosshair drawn at the aimed spot, or none.
+    pub fn set_crosshair(&mut self, crosshair: Option<Crosshair>) {
+        self.crosshair = crosshair;
+    }
+
+    /// Draw the crosshair at the aimed spot on a picture, given row by row as
+    /// `0x00RRGGBB`, unless there's no crosshair or the Zapper is aimed off
+    /// the screen.
+    pub fn draw_crosshair(&self, picture: &mut [u32]) {
+        if let (Some(crosshair), Some(aim)) = (self.crosshair, self.aim) {
+            crosshair.draw(aim, picture);
+        }
+    }
+
+    /// Check if the photodiode senses light, i.e. if the beam recently drew a
+    /// bright pixel around the aimed spot.
+    #[must_use]
+    pub const fn light(&self) -> bool {
+        self.light
+    }
+}
+
+impl Device for Zapper {
+    fn write(&mut self, _out: u8) {}
+
+    fn read(&self) -> u8 {
+        (u8::from(!self.light) << 3) | (u8::from(self.is_pulled()) << 4)
+    }
+
+    fn clock(&mut self) {}
+
+    fn observe(&mut self, screen: &Screen<'_>) {
+        let Some((x, y)) = self.aim else {
+            self.light = false;
+            return;
+        };
+
+        let frame_dots = u32::from(screen.scanlines) * SCANLINE_DOTS;
+        let beam = u32::from(screen.scanline) * SCANLINE_DOTS + u32::from(screen.dot);
+        let xs = x.saturating_sub(RADIUS)..=x.saturating_add(RADIUS).min(Screen::WIDTH - 1);
+        let ys = y.saturating_sub(RADIUS)..=y.saturating_add(RADIUS).min(Screen::HEIGHT - 1);
+
+        self.light = ys.into_iter().any(|y| {
+            xs.clone().any(|x| {
+                // Pixel `x` is drawn on dot `x + 1`, and pixels the beam
+                // hasn't reached yet are still those of the last frame.
+                let drawn = u32::from(y) * SCANLINE_DOTS + u32::from(x) + 1;
+                let age = (beam + frame_dots - drawn) % frame_dots;
+
+                age < LATENCY && is_bright(screen.pixel(x, y))
+            })
+        });
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(self.light)])
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 1];
+        reader.read_exact(&mut state)?;
+
+        self.light = state[0] != 0;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+}
+
+/// Check if a color of the frame buffer is bright enough for the photodiode,
+/// i.e. one of the two brightest rows of the palette, except its blacks.
+fn is_bright(color: u16) -> bool {
+    let hue = color & 0x0f;
+    let luma = (color >> 4) & 3;
+
+    luma >= 2 && hue <= 0x0d
+}
@@ -0,0 +1,44 @@
+//! The storage of the Turbo File, whose file on the host is shared by every
+//! game and must only change when a game writes it.
+
+use chuck_input::{ExpansionDevice, TurboFile};
+
+/// Write the given byte at the start of the storage, like a game does.
+fn write_byte(turbo_file: &mut TurboFile, byte: u8) {
+    turbo_file.write(0b000);
+    for i in 0..8 {
+        let bit = (byte >> i) & 1;
+        turbo_file.write(0b110 | bit);
+        turbo_file.write(0b010 | bit);
+    }
+}
+
+/// Save the storage into the file of the host if it changed, like a frontend
+/// does.
+fn flush(turbo_file: &mut TurboFile, host: &mut Vec<u8>) {
+    if turbo_file.is_dirty() {
+        host.clear();
+        turbo_file.save_data(host).unwrap();
+    }
+}
+
+#[test]
+fn load_state_keeps_host_file() {
+    let mut turbo_file = TurboFile::new();
+    write_byte(&mut turbo_file, 0xa5);
+    let mut host = Vec::new();
+    flush(&mut turbo_file, &mut host);
+
+    // A state of another game, with other contents.
+    let mut other = TurboFile::new();
+    write_byte(&mut other, 0x5a);
+    let mut state = Vec::new();
+    other.save(&mut state).unwrap();
+
+    turbo_file.load(&mut state.as_slice()).unwrap();
+    assert_eq!(turbo_file.data()[0], 0x5a);
+    assert!(!turbo_file.is_dirty());
+
+    flush(&mut turbo_file, &mut host);
+    assert_eq!(host[0], 0xa5);
+}
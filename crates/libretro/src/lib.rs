@@ -0,0 +1,696 @@
+//! A libretro core of Chuck's NES, for `RetroArch` and the other libretro
+//! frontends.
+//!
+//! This crate builds a `cdylib` (`libchuck_libretro.so`, `chuck_libretro.dll`,
+//! ...), which implements the `retro_*` functions of the libretro API. The
+//! frontend loads `.nes` files into it, runs it frame by frame, and saves its
+//! state. On Linux, the core is installed as `chuck_libretro.so` into the
+//! cores directory of the frontend, along with `chuck_libretro.info`:
+//!
+//! ```sh
+//! cargo build --release -p chuck-libretro
+//! cp target/release/libchuck_libretro.so ~/.config/retroarch/cores/chuck_libretro.so
+//! cp crates/libretro/chuck_libretro.info ~/.config/retroarch/cores/
+//! ```
+//!
+//! Every frame, the core polls the gamepads of the first two ports, runs a
+//! frame of the console, and passes its picture (colored by the default
+//! palette) and its audio (resampled to 48 kHz, see [`SAMPLE_RATE`]) to the
+//! frontend. The core also supports save states, cheats and the reset button.
+//!
+//! Either port may hold a Zapper instead of a controller, which is aimed and
+//! shot by the pointer of the frontend (`RETRO_DEVICE_POINTER`, e.g. the
+//! mouse or a touch) and draws a crosshair at the aimed spot. A shot off the
+//! picture pulls the trigger aimed off the screen, which reloads in most
+//! games, see [`chuck_input::Offscreen`].
+//!
+//! # Save RAM
+//!
+//! The frontend loads and saves the save RAM of the cartridge (`.srm` files)
+//! through a buffer of the core, see [`retro_get_memory_data`]. The save RAM
+//! of the console is loaded from the buffer on the first frame, and copied
+//! back to it after every frame that wrote it.
+//!
+//! # Safety
+//!
+//! The functions of this API are only called by the frontend, which follows
+//! the contract of `libretro.h`: every pointer that is passed to a function
+//! is valid unless documented otherwise.
+
+mod sys;
+
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::{ptr, slice};
+
+use chuck_audio::Resampler;
+use chuck_input::{ButtonState, Controller, Crosshair, Pointer, Port, Zapper};
+use chuck_nes::{cheat::Cheat, Nes, Region, HEIGHT, WIDTH};
+use chuck_rom::Rom;
+use chuck_video::palette::Palette;
+
+use sys::{
+    AudioSampleBatchFn, AudioSampleFn, ControllerDescription, ControllerInfo, EnvironmentFn,
+    GameGeometry, GameInfo, InputDescriptor, InputPollFn, InputStateFn, SystemAvInfo, SystemInfo,
+    SystemTiming, VideoRefreshFn,
+};
+
+/// The sample rate of the audio passed to the frontend, in Hz.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// The aspect ratio of the picture on a TV.
+const ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+/// The version of the core, as passed to the frontend.
+const VERSION: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+        Ok(version) => version,
+        Err(_) => panic!("invalid version"),
+    };
+
+/// The buttons of the controller, their gamepad inputs and their names.
+const BUTTONS: [(ButtonState, c_uint, &CStr); 8] = [
+    (ButtonState::A, sys::JOYPAD_A, c"A"),
+    (ButtonState::B, sys::JOYPAD_B, c"B"),
+    (ButtonState::SELECT, sys::JOYPAD_SELECT, c"Select"),
+    (ButtonState::START, sys::JOYPAD_START, c"Start"),
+    (ButtonState::UP, sys::JOYPAD_UP, c"Up"),
+    (ButtonState::DOWN, sys::JOYPAD_DOWN, c"Down"),
+    (ButtonState::LEFT, sys::JOYPAD_LEFT, c"Left"),
+    (ButtonState::RIGHT, sys::JOYPAD_RIGHT, c"Right"),
+];
+
+/// The controller ports and their libretro ports.
+const PORTS: [(Port, c_uint); 2] = [(Port::One, 0), (Port::Two, 1)];
+
+/// The devices which can be plugged into every port.
+static DEVICES: [ControllerDescription; 2] = [
+    ControllerDescription {
+        desc: name(c"Controller"),
+        id: sys::DEVICE_JOYPAD,
+    },
+    ControllerDescription {
+        desc: name(c"Zapper"),
+        id: sys::DEVICE_ZAPPER,
+    },
+];
+
+/// The devices of the two ports, ending with a zeroed entry.
+static CONTROLLERS: [ControllerInfo; 3] = [
+    ControllerInfo {
+        types: Some(&DEVICES[0]),
+        num_types: 2,
+    },
+    ControllerInfo {
+        types: Some(&DEVICES[0]),
+        num_types: 2,
+    },
+    ControllerInfo {
+        types: None,
+        num_types: 0,
+    },
+];
+
+/// Return the first character of a name, i.e. a pointer to it for the
+/// frontend.
+const fn name(name: &'static CStr) -> &'static u8 {
+    &name.to_bytes_with_nul()[0]
+}
+
+/// The callbacks of the frontend.
+#[derive(Clone, Copy)]
+struct Callbacks {
+    /// The callback to query and configure the frontend.
+    environment: Option<EnvironmentFn>,
+    /// The callback to show a picture.
+    video_refresh: Option<VideoRefreshFn>,
+    /// The callback to play audio samples.
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    /// The callback to poll the input devices.
+    input_poll: Option<InputPollFn>,
+    /// The callback to query the state of an input.
+    input_state: Option<InputStateFn>,
+}
+
+/// The callbacks set by the frontend.
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// A loaded game.
+struct Core {
+    /// The console.
+    nes: Nes,
+    /// The palette coloring the frames.
+    palette: Palette,
+    /// The picture of the last frame, as `0x00RRGGBB`.
+    picture: Vec<u32>,
+    /// The resampler of the audio.
+    resampler: Resampler,
+    /// The resampled audio of the last frame.
+    samples: Vec<f32>,
+    /// The audio of the last frame, as interleaved stereo samples.
+    audio: Vec<i16>,
+    /// The save RAM loaded and saved by the frontend.
+    sram: Vec<u8>,
+    /// A flag denoting if the save RAM was loaded into the console.
+    sram_loaded: bool,
+}
+
+impl Core {
+    /// Create a core running the given console.
+    fn new(nes: Nes) -> Self {
+        let mut resampler = Resampler::new(nes.region().cpu_clock(), SAMPLE_RATE);
+        // The frontend paces the frames and adjusts the rate itself.
+        resampler.set_rate_control(false);
+
+        Self {
+            sram: nes.sram().map(<[u8]>::to_vec).unwrap_or_default(),
+            nes,
+            palette: Palette::default(),
+            picture: vec![0; WIDTH * HEIGHT],
+            resampler,
+            samples: Vec::new(),
+            audio: Vec::new(),
+            sram_loaded: false,
+        }
+    }
+
+    /// Run a frame with the given buttons of both controllers, updating the
+    /// picture, the audio and the save RAM.
+    fn run_frame(&mut self, buttons: [ButtonState; 2]) {
+        if !self.sram_loaded {
+            // The buffer has the size of the save RAM, so it always loads.
+            let _ = self.nes.load_sram(&mut self.sram.as_slice());
+            self.sram_loaded = true;
+        }
+
+        let ports = self.nes.input_mut();
+        for ((port, _), buttons) in PORTS.into_iter().zip(buttons) {
+            if let Some(controller) = ports.device_mut::<Controller>(port) {
+                controller.set_buttons(buttons);
+            }
+        }
+
+        let frame = self.nes.run_frame();
+        self.palette.apply(frame.pixels, &mut self.picture);
+        self.resampler.push(frame.samples);
+        for (port, _) in PORTS {
+            if let Some(zapper) = self.nes.input_mut().device_mut::<Zapper>(port) {
+                zapper.draw_crosshair(&mut self.picture);
+            }
+        }
+
+        self.samples.resize(self.resampler.len(), 0.0);
+        self.resampler.fill(&mut self.samples);
+        self.audio.clear();
+        for &sample in &self.samples {
+            let sample = to_i16(sample);
+            self.audio.extend([sample, sample]);
+        }
+
+        if self.nes.is_sram_dirty() {
+            // The buffer has the size of the save RAM, so it always fits.
+            let _ = self.nes.save_sram(&mut self.sram.as_mut_slice());
+        }
+    }
+
+    /// Aim the Zappers plugged into the ports by the pointers of the ports,
+    /// and pull their triggers while pressed.
+    fn aim_zappers(&mut self, input_state: InputStateFn) {
+        for (port, id) in PORTS {
+            let Some(zapper) = self.nes.input_mut().device_mut::<Zapper>(port) else {
+                continue;
+            };
+
+            // SAFETY: The callback was set by the frontend.
+            let query = |input| unsafe { input_state(id, sys::DEVICE_POINTER, 0, input) };
+            let offscreen = query(sys::POINTER_IS_OFFSCREEN) != 0;
+            let pointer = Pointer::from_libretro(query(sys::POINTER_X), query(sys::POINTER_Y));
+            zapper.set_pointer((!offscreen).then_some(pointer));
+            zapper.set_trigger(query(sys::POINTER_PRESSED) != 0);
+        }
+    }
+
+    /// Pass the picture and the audio of the last frame to the frontend.
+    fn output(&self, callbacks: Callbacks) {
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let [width, height] = size();
+            let pitch = WIDTH * size_of::<u32>();
+            // SAFETY: The callback was set by the frontend, and the picture
+            // has the given size.
+            unsafe { video_refresh(self.picture.as_ptr().cast(), width, height, pitch) };
+        }
+
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            let mut audio = self.audio.as_slice();
+            while !audio.is_empty() {
+                // SAFETY: The callback was set by the frontend, and the audio
+                // has the given number of pairs of samples.
+                let frames = unsafe { audio_sample_batch(audio.as_ptr(), audio.len() / 2) };
+                if frames == 0 {
+                    break;
+                }
+
+                audio = audio.get(2 * frames..).unwrap_or_default();
+            }
+        }
+    }
+
+    /// Return the save state of the console.
+    fn state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        // Writing to a `Vec` can't fail.
+        let _ = self.nes.save_state(&mut state);
+        state
+    }
+}
+
+/// The loaded game, if any.
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+/// Return the callbacks set by the frontend.
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Return the callbacks set by the frontend, mutably.
+fn callbacks_mut() -> MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Return the loaded game.
+fn core() -> MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Return the width and the height of the picture.
+fn size() -> [c_uint; 2] {
+    [WIDTH, HEIGHT].map(|n| c_uint::try_from(n).unwrap_or(c_uint::MAX))
+}
+
+/// Convert a sample of the resampler, which is about within `-1.0..=1.0`, to
+/// a 16-bit sample.
+fn to_i16(sample: f32) -> i16 {
+    const MAGIC: f32 = 12_582_912.0;
+
+    // Adding 1.5 * 2^23 moves the clamped sample, rounded to an integer, into
+    // the low bits of the mantissa, as the difference to the bits of `MAGIC`.
+    let sample = (sample * 32767.0).clamp(-32768.0, 32767.0) + MAGIC;
+    let bits = sample.to_bits().wrapping_sub(MAGIC.to_bits());
+    i16::try_from(i32::from_ne_bytes(bits.to_ne_bytes())).unwrap_or(0)
+}
+
+/// Return the version of the libretro API implemented by the core.
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    sys::API_VERSION
+}
+
+/// Set the callback to query and configure the frontend.
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: Option<EnvironmentFn>) {
+    callbacks_mut().environment = callback;
+
+    if let Some(environment) = callback {
+        // SAFETY: The callback was set by the frontend, and the command takes
+        // an array of the devices of every port ending with a zeroed entry,
+        // which it only reads.
+        unsafe {
+            environment(
+                sys::ENVIRONMENT_SET_CONTROLLER_INFO,
+                ptr::from_ref(&CONTROLLERS).cast_mut().cast(),
+            )
+        };
+    }
+}
+
+/// Set the callback to show a picture.
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: Option<VideoRefreshFn>) {
+    callbacks_mut().video_refresh = callback;
+}
+
+/// Set the callback to play a single audio sample, which is unused since the
+/// core plays the audio of every frame at once, see
+/// [`retro_set_audio_sample_batch`].
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: Option<AudioSampleFn>) {}
+
+/// Set the callback to play audio samples.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: Option<AudioSampleBatchFn>) {
+    callbacks_mut().audio_sample_batch = callback;
+}
+
+/// Set the callback to poll the input devices.
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: Option<InputPollFn>) {
+    callbacks_mut().input_poll = callback;
+}
+
+/// Set the callback to query the state of an input.
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: Option<InputStateFn>) {
+    callbacks_mut().input_state = callback;
+}
+
+/// Initialize the core.
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+/// Deinitialize the core, unloading the game.
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+/// Store the information about the core.
+///
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    let system = SystemInfo {
+        library_name: c"Chuck".as_ptr(),
+        library_version: VERSION.as_ptr(),
+        valid_extensions: c"nes".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+
+    // SAFETY: The pointer is valid per the contract of this function.
+    unsafe { info.write(system) };
+}
+
+/// Store the size of the picture and the timing of the loaded game.
+///
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let region = core()
+        .as_ref()
+        .map_or(Region::Ntsc, |core| core.nes.region());
+    let [width, height] = size();
+    let av = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: width,
+            base_height: height,
+            max_width: width,
+            max_height: height,
+            aspect_ratio: ASPECT_RATIO,
+        },
+        timing: SystemTiming {
+            fps: region.frame_rate(),
+            sample_rate: f64::from(SAMPLE_RATE),
+        },
+    };
+
+    // SAFETY: The pointer is valid per the contract of this function.
+    unsafe { info.write(av) };
+}
+
+/// Plug a device into a port, which is either a gamepad (a controller), a
+/// Zapper or nothing.
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(port: c_uint, device: c_uint) {
+    let Some(&(port, _)) = PORTS.iter().find(|&&(_, id)| id == port) else {
+        return;
+    };
+
+    if let Some(core) = core().as_mut() {
+        let ports = core.nes.input_mut();
+        match device {
+            sys::DEVICE_NONE => {
+                ports.unplug(port);
+            }
+            sys::DEVICE_JOYPAD if ports.device_mut::<Controller>(port).is_none() => {
+                ports.plug(port, Box::new(Controller::new()));
+            }
+            sys::DEVICE_ZAPPER if ports.device_mut::<Zapper>(port).is_none() => {
+                let mut zapper = Zapper::new();
+                zapper.set_crosshair(Some(Crosshair::default()));
+                ports.plug(port, Box::new(zapper));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Press the reset button.
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        core.nes.reset();
+    }
+}
+
+/// Run a frame of the loaded game.
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+
+    if let Some(input_poll) = callbacks.input_poll {
+        // SAFETY: The callback was set by the frontend.
+        unsafe { input_poll() };
+    }
+
+    let buttons = PORTS.map(|(_, port)| {
+        let Some(input_state) = callbacks.input_state else {
+            return ButtonState::empty();
+        };
+
+        BUTTONS
+            .iter()
+            .filter(|&&(_, id, _)| {
+                // SAFETY: The callback was set by the frontend.
+                unsafe { input_state(port, sys::DEVICE_JOYPAD, 0, id) != 0 }
+            })
+            .fold(ButtonState::empty(), |buttons, &(button, _, _)| {
+                buttons | button
+            })
+    });
+
+    if let Some(core) = core().as_mut() {
+        if let Some(input_state) = callbacks.input_state {
+            core.aim_zappers(input_state);
+        }
+        core.run_frame(buttons);
+        core.output(callbacks);
+    }
+}
+
+/// Return the size of the save states of the loaded game.
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    core().as_ref().map_or(0, |core| core.state().len())
+}
+
+/// Save the state of the loaded game into the given buffer, returning
+/// `false` if it's too small.
+///
+/// # Safety
+///
+/// `data` must be valid for writes of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let Some(state) = core().as_ref().map(Core::state) else {
+        return false;
+    };
+
+    if state.len() > size {
+        return false;
+    }
+
+    // SAFETY: The buffer is valid per the contract of this function, and
+    // large enough for the state.
+    unsafe { ptr::copy_nonoverlapping(state.as_ptr(), data.cast(), state.len()) };
+    true
+}
+
+/// Load the state of the loaded game from the given buffer, returning
+/// `false` if it's malformed.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    // SAFETY: The buffer is valid per the contract of this function.
+    let mut state = unsafe { slice::from_raw_parts(data.cast::<u8>(), size) };
+    core()
+        .as_mut()
+        .is_some_and(|core| core.nes.load_state(&mut state).is_ok())
+}
+
+/// Remove all cheats.
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    if let Some(core) = core().as_mut() {
+        core.nes.cheats_mut().clear();
+    }
+}
+
+/// Add a cheat, which is one or more codes separated by `+`.
+///
+/// Codes which aren't valid are ignored. The index of the cheat is ignored
+/// too, since the frontend resets the cheats and adds them all again
+/// whenever they change.
+///
+/// # Safety
+///
+/// `code` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(_index: c_uint, enabled: bool, code: *const c_char) {
+    // SAFETY: The string is valid per the contract of this function.
+    let code = unsafe { CStr::from_ptr(code) }.to_string_lossy();
+    let codes: Vec<Cheat> = code
+        .split('+')
+        .filter_map(|code| code.parse().ok())
+        .collect();
+
+    if let Some(loaded) = core().as_mut() {
+        let cheats = loaded.nes.cheats_mut();
+        for cheat in codes {
+            let index = cheats.add(cheat);
+            cheats.set_enabled(index, enabled);
+        }
+    }
+}
+
+/// Load a game from its `.nes` file, returning `false` if the file is
+/// malformed, its mapper is unsupported, or the frontend doesn't support the
+/// pixel format of the core.
+///
+/// # Safety
+///
+/// `game` must be a valid pointer or a null pointer.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    // SAFETY: The pointer is valid or null per the contract of this function.
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+
+    if game.data.is_null() {
+        return false;
+    }
+
+    // SAFETY: The frontend passes the contents of the file, since the core
+    // doesn't need the full path, see `retro_get_system_info`.
+    let data = unsafe { slice::from_raw_parts(game.data.cast::<u8>(), game.size) };
+    let Some(nes) = Rom::parse(data)
+        .ok()
+        .and_then(|rom| Nes::from_rom(&rom).ok())
+    else {
+        return false;
+    };
+
+    let Some(environment) = callbacks().environment else {
+        return false;
+    };
+
+    let mut format = sys::PIXEL_FORMAT_XRGB8888;
+    // SAFETY: The callback was set by the frontend, and the command takes a
+    // pointer to the pixel format.
+    let supported = unsafe {
+        environment(
+            sys::ENVIRONMENT_SET_PIXEL_FORMAT,
+            ptr::from_mut(&mut format).cast(),
+        )
+    };
+    if !supported {
+        return false;
+    }
+
+    let mut descriptors: Vec<InputDescriptor> = PORTS
+        .iter()
+        .flat_map(|&(_, port)| {
+            BUTTONS.iter().map(move |&(_, id, name)| InputDescriptor {
+                port,
+                device: sys::DEVICE_JOYPAD,
+                index: 0,
+                id,
+                description: name.as_ptr(),
+            })
+        })
+        .collect();
+    descriptors.push(InputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+
+    // SAFETY: The callback was set by the frontend, and the command takes an
+    // array of descriptors ending with a zeroed one, which it copies.
+    unsafe {
+        environment(
+            sys::ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr().cast(),
+        )
+    };
+
+    *core() = Some(Core::new(nes));
+    true
+}
+
+/// Load a game of a special type, which the core doesn't support.
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+/// Unload the loaded game.
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+/// Return the region of the loaded game, where the Dendy counts as PAL for
+/// its 50 Hz.
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    let region = core().as_ref().map(|core| core.nes.region());
+    match region {
+        Some(Region::Pal | Region::Dendy) => sys::REGION_PAL,
+        Some(Region::Ntsc) | None => sys::REGION_NTSC,
+    }
+}
+
+/// Return a pointer to a memory of the loaded game, which is only the save
+/// RAM, see [the crate documentation](crate#save-ram), or a null pointer.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != sys::MEMORY_SAVE_RAM {
+        return ptr::null_mut();
+    }
+
+    core()
+        .as_mut()
+        .filter(|core| !core.sram.is_empty())
+        .map_or(ptr::null_mut(), |core| core.sram.as_mut_ptr().cast())
+}
+
+/// Return the size of a memory of the loaded game, see
+/// [`retro_get_memory_data`].
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    if id != sys::MEMORY_SAVE_RAM {
+        return 0;
+    }
+
+    core().as_ref().map_or(0, |core| core.sram.len())
+}
@@ -0,0 +1,177 @@
+//! The types and constants of the libretro API used by the core, as declared
+//! by `libretro.h`.
+
+use std::ffi::{c_char, c_uint, c_void};
+
+/// The version of the libretro API, `RETRO_API_VERSION`.
+pub const API_VERSION: c_uint = 1;
+
+/// `RETRO_DEVICE_NONE`, a port without a device.
+pub const DEVICE_NONE: c_uint = 0;
+/// `RETRO_DEVICE_JOYPAD`, a gamepad modeled after the SNES controller.
+pub const DEVICE_JOYPAD: c_uint = 1;
+/// `RETRO_DEVICE_POINTER`, an absolute pointer like a mouse or a touch.
+pub const DEVICE_POINTER: c_uint = 6;
+/// The Zapper, `RETRO_DEVICE_SUBCLASS(RETRO_DEVICE_POINTER, 0)`.
+pub const DEVICE_ZAPPER: c_uint = (1 << 8) | DEVICE_POINTER;
+
+/// `RETRO_DEVICE_ID_JOYPAD_B`.
+pub const JOYPAD_B: c_uint = 0;
+/// `RETRO_DEVICE_ID_JOYPAD_SELECT`.
+pub const JOYPAD_SELECT: c_uint = 2;
+/// `RETRO_DEVICE_ID_JOYPAD_START`.
+pub const JOYPAD_START: c_uint = 3;
+/// `RETRO_DEVICE_ID_JOYPAD_UP`.
+pub const JOYPAD_UP: c_uint = 4;
+/// `RETRO_DEVICE_ID_JOYPAD_DOWN`.
+pub const JOYPAD_DOWN: c_uint = 5;
+/// `RETRO_DEVICE_ID_JOYPAD_LEFT`.
+pub const JOYPAD_LEFT: c_uint = 6;
+/// `RETRO_DEVICE_ID_JOYPAD_RIGHT`.
+pub const JOYPAD_RIGHT: c_uint = 7;
+/// `RETRO_DEVICE_ID_JOYPAD_A`.
+pub const JOYPAD_A: c_uint = 8;
+
+/// `RETRO_DEVICE_ID_POINTER_X`, from `-0x7fff` (the left edge of the
+/// picture) to `0x7fff` (its right edge).
+pub const POINTER_X: c_uint = 0;
+/// `RETRO_DEVICE_ID_POINTER_Y`, from `-0x7fff` (the top edge of the picture)
+/// to `0x7fff` (its bottom edge).
+pub const POINTER_Y: c_uint = 1;
+/// `RETRO_DEVICE_ID_POINTER_PRESSED`.
+pub const POINTER_PRESSED: c_uint = 2;
+/// `RETRO_DEVICE_ID_POINTER_IS_OFFSCREEN`.
+pub const POINTER_IS_OFFSCREEN: c_uint = 15;
+
+/// `RETRO_REGION_NTSC`.
+pub const REGION_NTSC: c_uint = 0;
+/// `RETRO_REGION_PAL`.
+pub const REGION_PAL: c_uint = 1;
+
+/// `RETRO_MEMORY_SAVE_RAM`, the battery-backed RAM of the cartridge.
+pub const MEMORY_SAVE_RAM: c_uint = 0;
+
+/// `RETRO_ENVIRONMENT_SET_PIXEL_FORMAT`, with a pointer to a `c_int`.
+pub const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+/// `RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS`, with a pointer to an array of
+/// [`InputDescriptor`]s that ends with a zeroed one.
+pub const ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+
+/// `RETRO_ENVIRONMENT_SET_CONTROLLER_INFO`, with a pointer to an array of
+/// [`ControllerInfo`]s for every port that ends with a zeroed one.
+pub const ENVIRONMENT_SET_CONTROLLER_INFO: c_uint = 35;
+
+/// `RETRO_PIXEL_FORMAT_XRGB8888`, pixels of 32 bits as `0x00RRGGBB`.
+pub const PIXEL_FORMAT_XRGB8888: i32 = 1;
+
+/// `retro_environment_t`.
+pub type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+/// `retro_video_refresh_t`.
+pub type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+/// `retro_audio_sample_t`.
+pub type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+/// `retro_audio_sample_batch_t`, which returns the number of frames (pairs
+/// of samples) it consumed.
+pub type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+/// `retro_input_poll_t`.
+pub type InputPollFn = unsafe extern "C" fn();
+/// `retro_input_state_t`.
+pub type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+/// `struct retro_system_info`.
+#[repr(C)]
+pub struct SystemInfo {
+    /// The name of the core.
+    pub library_name: *const c_char,
+    /// The version of the core.
+    pub library_version: *const c_char,
+    /// The extensions of the loadable files, separated by `|`.
+    pub valid_extensions: *const c_char,
+    /// A flag denoting if the core loads the files itself.
+    pub need_fullpath: bool,
+    /// A flag denoting if the frontend must not extract archives.
+    pub block_extract: bool,
+}
+
+/// `struct retro_game_geometry`.
+#[repr(C)]
+pub struct GameGeometry {
+    /// The nominal width of the picture.
+    pub base_width: c_uint,
+    /// The nominal height of the picture.
+    pub base_height: c_uint,
+    /// The maximum width of the picture.
+    pub max_width: c_uint,
+    /// The maximum height of the picture.
+    pub max_height: c_uint,
+    /// The aspect ratio of the picture as shown.
+    pub aspect_ratio: f32,
+}
+
+/// `struct retro_system_timing`.
+#[repr(C)]
+pub struct SystemTiming {
+    /// The number of frames per second.
+    pub fps: f64,
+    /// The sample rate of the audio, in Hz.
+    pub sample_rate: f64,
+}
+
+/// `struct retro_system_av_info`.
+#[repr(C)]
+pub struct SystemAvInfo {
+    /// The size of the picture.
+    pub geometry: GameGeometry,
+    /// The timing of the video and the audio.
+    pub timing: SystemTiming,
+}
+
+/// `struct retro_game_info`.
+#[repr(C)]
+pub struct GameInfo {
+    /// The path of the file.
+    pub path: *const c_char,
+    /// The contents of the file, or a null pointer if the core loads it
+    /// itself.
+    pub data: *const c_void,
+    /// The size of the contents.
+    pub size: usize,
+    /// The metadata of the file.
+    pub meta: *const c_char,
+}
+
+/// `struct retro_input_descriptor`.
+#[repr(C)]
+pub struct InputDescriptor {
+    /// The port of the input.
+    pub port: c_uint,
+    /// The device of the input.
+    pub device: c_uint,
+    /// The index of the input, for analog sticks.
+    pub index: c_uint,
+    /// The id of the input, like [`JOYPAD_A`].
+    pub id: c_uint,
+    /// The description of the input shown to the player.
+    pub description: *const c_char,
+}
+
+/// `struct retro_controller_description`.
+#[repr(C)]
+pub struct ControllerDescription {
+    /// The name of the device, as a nul-terminated string.
+    pub desc: &'static u8,
+    /// The id of the device, like [`DEVICE_JOYPAD`].
+    pub id: c_uint,
+}
+
+/// `struct retro_controller_info`.
+#[repr(C)]
+pub struct ControllerInfo {
+    /// The devices which can be plugged into the port, or `None` after the
+    /// last port.
+    pub types: Option<&'static ControllerDescription>,
+    /// The number of devices.
+    pub num_types: c_uint,
+}
@@ -0,0 +1,277 @@
+//! The functions exposed to scripts, see the [crate documentation](crate).
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chuck_cpu::{Flags, Registers};
+use chuck_input::{ButtonState, Controller, Port};
+use chuck_nes::Nes;
+use mlua::{AnyUserData, Error, Function, Lua, Result, Table, Value};
+
+use crate::overlay::{self, CLEAR, WHITE};
+use crate::{Buttons, Shared, AFTER, BEFORE, NES};
+
+/// The names of the buttons in the tables of `joypad.get` and `joypad.set`.
+const BUTTONS: [(&str, ButtonState); 8] = [
+    ("A", ButtonState::A),
+    ("B", ButtonState::B),
+    ("select", ButtonState::SELECT),
+    ("start", ButtonState::START),
+    ("up", ButtonState::UP),
+    ("down", ButtonState::DOWN),
+    ("left", ButtonState::LEFT),
+    ("right", ButtonState::RIGHT),
+];
+
+/// Install the API into the globals of Lua.
+pub fn install(lua: &Lua, shared: &Rc<RefCell<Shared>>) -> Result<()> {
+    let globals = lua.globals();
+    globals.set("emu", emu(lua)?)?;
+    globals.set("memory", memory(lua)?)?;
+    globals.set("joypad", joypad(lua, shared)?)?;
+    globals.set("gui", gui(lua, shared)?)?;
+
+    Ok(())
+}
+
+/// Run the given function with the console, which is only accessible while
+/// a frame is run, see [`Script::before_frame`](crate::Script::before_frame).
+fn with_nes<R>(lua: &Lua, f: impl FnOnce(&mut Nes) -> R) -> Result<R> {
+    let Some(nes) = lua.named_registry_value::<Option<AnyUserData>>(NES)? else {
+        return Err(Error::runtime(
+            "the console is only accessible while a frame runs",
+        ));
+    };
+
+    nes.borrow_mut_scoped(f)
+}
+
+/// Return the `emu` table.
+fn emu(lua: &Lua) -> Result<Table> {
+    let emu = lua.create_table()?;
+    let coroutine: Table = lua.globals().get("coroutine")?;
+
+    emu.set("frameadvance", coroutine.get::<Function>("yield")?)?;
+    emu.set(
+        "framecount",
+        lua.create_function(|lua, ()| with_nes(lua, |nes| nes.ppu().frame()))?,
+    )?;
+    emu.set(
+        "registerbefore",
+        lua.create_function(|lua, f: Option<Function>| lua.set_named_registry_value(BEFORE, f))?,
+    )?;
+    emu.set(
+        "registerafter",
+        lua.create_function(|lua, f: Option<Function>| lua.set_named_registry_value(AFTER, f))?,
+    )?;
+    emu.set(
+        "softreset",
+        lua.create_function(|lua, ()| with_nes(lua, Nes::reset))?,
+    )?;
+    emu.set(
+        "poweron",
+        lua.create_function(|lua, ()| with_nes(lua, Nes::power_cycle))?,
+    )?;
+
+    Ok(emu)
+}
+
+/// Return the `memory` table.
+fn memory(lua: &Lua) -> Result<Table> {
+    let memory = lua.create_table()?;
+
+    memory.set(
+        "readbyte",
+        lua.create_function(|lua, addr: u16| with_nes(lua, |nes| nes.peek(addr)))?,
+    )?;
+    memory.set(
+        "readbytesigned",
+        lua.create_function(|lua, addr: u16| {
+            with_nes(lua, |nes| i8::from_le_bytes([nes.peek(addr)]))
+        })?,
+    )?;
+    memory.set(
+        "readword",
+        lua.create_function(|lua, addr: u16| {
+            with_nes(lua, |nes| {
+                u16::from_le_bytes([nes.peek(addr), nes.peek(addr.wrapping_add(1))])
+            })
+        })?,
+    )?;
+    memory.set(
+        "writebyte",
+        lua.create_function(|lua, (addr, value): (u16, i64)| {
+            with_nes(lua, |nes| nes.poke(addr, byte(value)))
+        })?,
+    )?;
+    memory.set(
+        "getregister",
+        lua.create_function(|lua, name: String| {
+            let regs = with_nes(lua, |nes| nes.cpu().regs.clone())?;
+            Ok(match name.as_str() {
+                "a" => u16::from(regs.a),
+                "x" => u16::from(regs.x),
+                "y" => u16::from(regs.y),
+                "s" => u16::from(regs.sp),
+                "p" => u16::from(regs.flags.bits() | 0x20),
+                "pc" => regs.pc,
+                _ => return Err(unknown_register(&name)),
+            })
+        })?,
+    )?;
+    memory.set(
+        "setregister",
+        lua.create_function(|lua, (name, value): (String, i64)| {
+            let mut regs = with_nes(lua, |nes| nes.cpu().regs.clone())?;
+            set_register(&mut regs, &name, value)?;
+            with_nes(lua, |nes| nes.set_registers(regs))
+        })?,
+    )?;
+
+    Ok(memory)
+}
+
+/// Set the register with the given name, as named by `memory.setregister`.
+fn set_register(regs: &mut Registers, name: &str, value: i64) -> Result<()> {
+    match name {
+        "a" => regs.a = byte(value),
+        "x" => regs.x = byte(value),
+        "y" => regs.y = byte(value),
+        "s" => regs.sp = byte(value),
+        "p" => regs.flags = Flags::from_bits_truncate(byte(value)),
+        "pc" => regs.pc = u16::try_from(value.rem_euclid(0x10000)).unwrap_or_default(),
+        _ => return Err(unknown_register(name)),
+    }
+
+    Ok(())
+}
+
+/// Return the error of an unknown register name.
+fn unknown_register(name: &str) -> Error {
+    Error::runtime(format!("unknown register `{name}`"))
+}
+
+/// Return the low byte of a value, like a write of the CPU would.
+fn byte(value: i64) -> u8 {
+    u8::try_from(value.rem_euclid(0x100)).unwrap_or_default()
+}
+
+/// Return the `joypad` table.
+fn joypad(lua: &Lua, shared: &Rc<RefCell<Shared>>) -> Result<Table> {
+    let joypad = lua.create_table()?;
+
+    joypad.set(
+        "get",
+        lua.create_function(|lua, port: u8| {
+            let port = self::port(port)?;
+            let buttons = with_nes(lua, |nes| {
+                let controller = nes.input_mut().device_mut::<Controller>(port);
+                controller.map_or_else(ButtonState::empty, |controller| controller.buttons())
+            })?;
+
+            let table = lua.create_table()?;
+            for (name, button) in BUTTONS {
+                table.set(name, buttons.contains(button))?;
+            }
+            Ok(table)
+        })?,
+    )?;
+
+    let shared = Rc::clone(shared);
+    joypad.set(
+        "set",
+        lua.create_function(move |lua, (port, table): (u8, Table)| {
+            let index = usize::from(port.saturating_sub(1));
+            let port = self::port(port)?;
+
+            let mut buttons = Buttons::default();
+            for (name, button) in BUTTONS {
+                match table.get::<Option<bool>>(name)? {
+                    Some(true) => buttons.pressed |= button,
+                    Some(false) => buttons.released |= button,
+                    None => {}
+                }
+            }
+
+            let mut shared = shared.borrow_mut();
+            let joypad = &mut shared.joypads[index];
+            joypad.pressed = (joypad.pressed - buttons.released) | buttons.pressed;
+            joypad.released = (joypad.released - buttons.pressed) | buttons.released;
+
+            let joypad = *joypad;
+            with_nes(lua, |nes| joypad.apply(nes, port))
+        })?,
+    )?;
+
+    Ok(joypad)
+}
+
+/// Return the controller port with the given number.
+fn port(number: u8) -> Result<Port> {
+    match number {
+        1 => Ok(Port::One),
+        2 => Ok(Port::Two),
+        _ => Err(Error::runtime(format!("invalid port {number}"))),
+    }
+}
+
+/// Return the `gui` table.
+fn gui(lua: &Lua, shared: &Rc<RefCell<Shared>>) -> Result<Table> {
+    let gui = lua.create_table()?;
+
+    let overlay = Rc::clone(shared);
+    gui.set(
+        "pixel",
+        lua.create_function(move |_, (x, y, color): (i64, i64, Value)| {
+            let color = overlay::color(&color, WHITE)?;
+            overlay.borrow_mut().overlay.pixel(x, y, color);
+            Ok(())
+        })?,
+    )?;
+
+    let overlay = Rc::clone(shared);
+    gui.set(
+        "line",
+        lua.create_function(
+            move |_, (x1, y1, x2, y2, color): (i64, i64, i64, i64, Value)| {
+                let color = overlay::color(&color, WHITE)?;
+                overlay.borrow_mut().overlay.line((x1, y1), (x2, y2), color);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    let overlay = Rc::clone(shared);
+    gui.set(
+        "box",
+        lua.create_function(
+            move |_, (x1, y1, x2, y2, fill, outline): (i64, i64, i64, i64, Value, Value)| {
+                let fill = overlay::color(&fill, CLEAR)?;
+                let outline = overlay::color(&outline, WHITE)?;
+                overlay
+                    .borrow_mut()
+                    .overlay
+                    .rect((x1, y1), (x2, y2), fill, outline);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    let overlay = Rc::clone(shared);
+    gui.set(
+        "text",
+        lua.create_function(
+            move |_, (x, y, text, color, background): (i64, i64, String, Value, Value)| {
+                let color = overlay::color(&color, WHITE)?;
+                let background = overlay::color(&background, CLEAR)?;
+                overlay
+                    .borrow_mut()
+                    .overlay
+                    .text((x, y), &text, color, background);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    Ok(gui)
+}
@@ -0,0 +1,255 @@
+//! Lua scripting of the console, with an API modeled on the one of FCEUX, so
+//! that the scripts of the TAS and randomizer communities run with few
+//! changes.
+//!
+//! A script runs as a coroutine, which runs until it advances to the next
+//! frame, and continues once the frame was emulated:
+//!
+//! ```lua
+//! while true do
+//!     gui.text(8, 8, "lives: " .. memory.readbyte(0x075a))
+//!     emu.frameadvance()
+//! end
+//! ```
+//!
+//! | Function                                  | Description                                          |
+//! |-------------------------------------------|------------------------------------------------------|
+//! | `emu.frameadvance()`                      | Wait until the next frame was emulated               |
+//! | `emu.framecount()`                        | The number of frames since power-up                  |
+//! | `emu.registerbefore(f)`                   | Call `f` before every frame (or stop with `nil`)     |
+//! | `emu.registerafter(f)`                    | Call `f` after every frame (or stop with `nil`)      |
+//! | `emu.softreset()`, `emu.poweron()`        | Press the reset button, or power cycle the console   |
+//! | `memory.readbyte(addr)`                   | Read the CPU bus without side effects                |
+//! | `memory.readbytesigned(addr)`             | The same, as a signed byte                           |
+//! | `memory.readword(addr)`                   | Read a little-endian word                            |
+//! | `memory.writebyte(addr, value)`           | Write the memory at an address, without side effects |
+//! | `memory.getregister(name)`                | The register `a`, `x`, `y`, `s`, `p` or `pc`         |
+//! | `memory.setregister(name, value)`         | Set a register                                       |
+//! | `joypad.get(port)`                        | The buttons of a controller, as a table              |
+//! | `joypad.set(port, buttons)`               | Press (`true`) or release (`false`) buttons          |
+//! | `gui.pixel(x, y, color)`                  | Draw a pixel                                         |
+//! | `gui.line(x1, y1, x2, y2, color)`         | Draw a line                                          |
+//! | `gui.box(x1, y1, x2, y2, fill, outline)`  | Draw a rectangle                                     |
+//! | `gui.text(x, y, text, color, background)` | Draw text, in a font of 8x8 pixels                   |
+//!
+//! The buttons are named `A`, `B`, `select`, `start`, `up`, `down`, `left` and
+//! `right`, and the ports are 1 and 2. The buttons set by a script are held
+//! during the next frame, over the input of the player. A color is a name
+//! (e.g. `"red"` or `"clear"`), `"#RRGGBB"`, `"#RRGGBBAA"` or `0xRRGGBBAA`.
+//! The drawings of a frame are shown on top of its picture, see
+//! [`Script::overlay`].
+//!
+//! # Link(s)
+//!
+//! - <https://fceux.com/web/help/LuaFunctionsList.html>
+
+mod api;
+mod overlay;
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+use chuck_input::{ButtonState, Controller, Port};
+use chuck_nes::Nes;
+use mlua::thread::ThreadStatus;
+use mlua::{Function, Lua, Thread};
+
+pub use mlua::Error;
+pub use overlay::Overlay;
+
+/// The key of the console in the registry of Lua, while the script runs.
+const NES: &str = "chuck.nes";
+
+/// The key of the function called before every frame in the registry.
+const BEFORE: &str = "chuck.before";
+
+/// The key of the function called after every frame in the registry.
+const AFTER: &str = "chuck.after";
+
+/// The buttons a script presses and releases on a controller.
+#[derive(Debug, Clone, Copy, Default)]
+struct Buttons {
+    /// The pressed buttons.
+    pressed: ButtonState,
+    /// The released buttons.
+    released: ButtonState,
+}
+
+impl Buttons {
+    /// Press and release the buttons of the controller in the given port.
+    fn apply(self, nes: &mut Nes, port: Port) {
+        if let Some(controller) = nes.input_mut().device_mut::<Controller>(port) {
+            let buttons = (controller.buttons() | self.pressed) - self.released;
+            controller.set_buttons(buttons);
+        }
+    }
+}
+
+/// The state shared by a script and its API.
+#[derive(Debug, Default)]
+struct Shared {
+    /// The drawings of the current frame.
+    overlay: Overlay,
+    /// The buttons set for the next frame, by port.
+    joypads: [Buttons; 2],
+}
+
+/// A running script.
+///
+/// The frontend calls [`Script::before_frame`] after setting the input of the
+/// players, runs the frame, then calls [`Script::after_frame`]:
+///
+/// ```
+/// # use chuck_lua::Script;
+/// # use chuck_nes::{mapper::{Mirroring, Nrom}, Nes};
+/// # let mut prg = vec![0; 0x4000];
+/// # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+/// # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+/// # let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+/// let mut script = Script::new(
+///     r#"
+///     while true do
+///         memory.writebyte(0x10, memory.readbyte(0x10) + 1)
+///         gui.pixel(0, 0, "red")
+///         emu.frameadvance()
+///     end
+///     "#,
+///     "counter",
+/// )?;
+///
+/// for _ in 0..3 {
+///     script.before_frame(&mut nes)?;
+///     nes.run_frame();
+///     script.after_frame(&mut nes)?;
+/// }
+///
+/// // Once before the first frame, and after every frame.
+/// assert_eq!(nes.ram()[0x10], 4);
+/// assert_eq!(script.overlay().pixels()[0], 0xffff_0000);
+/// # Ok::<(), chuck_lua::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Script {
+    /// The state of Lua.
+    lua: Lua,
+    /// The coroutine of the script's main chunk.
+    main: Thread,
+    /// A flag denoting if the main chunk started.
+    started: bool,
+    /// The state shared with the API.
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Script {
+    /// Load a script with the given source code, and the given name for its
+    /// error messages, e.g. the path of its file.
+    ///
+    /// The script only starts to run with the first frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script doesn't compile.
+    pub fn new(source: &str, name: &str) -> Result<Self, Error> {
+        let lua = Lua::new();
+        let shared = Rc::default();
+        api::install(&lua, &shared)?;
+
+        let chunk = lua
+            .load(source)
+            .set_name(format!("@{name}"))
+            .into_function()?;
+        let main = lua.create_thread(chunk)?;
+
+        Ok(Self {
+            lua,
+            main,
+            started: false,
+            shared,
+        })
+    }
+
+    /// Run the script before a frame: clear the drawings of the previous
+    /// frame, start the main chunk if this is the first frame, set the
+    /// buttons for the frame and call the function registered by
+    /// `emu.registerbefore`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error raised by the script.
+    pub fn before_frame(&mut self, nes: &mut Nes) -> Result<(), Error> {
+        let joypads = {
+            let mut shared = self.shared.borrow_mut();
+            shared.overlay.clear();
+            shared.joypads
+        };
+        for (port, buttons) in [Port::One, Port::Two].into_iter().zip(joypads) {
+            buttons.apply(nes, port);
+        }
+
+        self.with_nes(nes, |script| {
+            if !script.started {
+                script.started = true;
+                script.resume()?;
+            }
+
+            script.call(BEFORE)
+        })
+    }
+
+    /// Run the script after a frame: release the buttons set for the frame,
+    /// call the function registered by `emu.registerafter`, and continue the
+    /// main chunk until it advances to the next frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error raised by the script.
+    pub fn after_frame(&mut self, nes: &mut Nes) -> Result<(), Error> {
+        self.shared.borrow_mut().joypads = [Buttons::default(); 2];
+
+        self.with_nes(nes, |script| {
+            script.call(AFTER)?;
+            script.resume()
+        })
+    }
+
+    /// Return the drawings of the last frame, to be shown on top of its
+    /// picture.
+    #[must_use]
+    pub fn overlay(&self) -> Ref<'_, Overlay> {
+        Ref::map(self.shared.borrow(), |shared| &shared.overlay)
+    }
+
+    /// Run the given function with the console accessible to the API.
+    fn with_nes(
+        &mut self,
+        nes: &mut Nes,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let lua = self.lua.clone();
+        lua.scope(|scope| {
+            let nes = scope.create_any_userdata_ref_mut(nes)?;
+            lua.set_named_registry_value(NES, &nes)?;
+
+            let result = f(self);
+            lua.unset_named_registry_value(NES)?;
+            result
+        })
+    }
+
+    /// Continue the main chunk until it advances to the next frame, unless it
+    /// finished.
+    fn resume(&self) -> Result<(), Error> {
+        if self.main.status() == ThreadStatus::Resumable {
+            self.main.resume::<()>(())?;
+        }
+
+        Ok(())
+    }
+
+    /// Call the function registered under the given key, if any.
+    fn call(&self, key: &str) -> Result<(), Error> {
+        self.lua
+            .named_registry_value::<Option<Function>>(key)?
+            .map_or(Ok(()), |function| function.call(()))
+    }
+}
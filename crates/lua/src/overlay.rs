@@ -0,0 +1,196 @@
+//! The drawings of a script on top of the picture.
+
+use chuck_nes::{HEIGHT, WIDTH};
+use font8x8::legacy::BASIC_LEGACY;
+use mlua::{Error, Result, Value};
+
+/// A transparent color.
+pub const CLEAR: u32 = 0;
+
+/// The color white.
+pub const WHITE: u32 = 0xffff_ffff;
+
+/// The bound of the coordinates of lines, so that lines far outside of the
+/// picture are drawn in a reasonable time.
+const LIMIT: i64 = 1 << 12;
+
+/// The named colors, as `0xAARRGGBB`.
+const COLORS: [(&str, u32); 12] = [
+    ("clear", CLEAR),
+    ("white", WHITE),
+    ("black", 0xff00_0000),
+    ("gray", 0xff80_8080),
+    ("grey", 0xff80_8080),
+    ("red", 0xffff_0000),
+    ("green", 0xff00_ff00),
+    ("blue", 0xff00_00ff),
+    ("yellow", 0xffff_ff00),
+    ("cyan", 0xff00_ffff),
+    ("magenta", 0xffff_00ff),
+    ("orange", 0xffff_8000),
+];
+
+/// The drawings of a frame, which are cleared before the next frame.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    /// The pixels, as `0xAARRGGBB`.
+    pixels: Vec<u32>,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            pixels: vec![CLEAR; WIDTH * HEIGHT],
+        }
+    }
+}
+
+impl Overlay {
+    /// Return the pixels, row by row, as `0xAARRGGBB`, which are transparent
+    /// where nothing was drawn.
+    #[must_use]
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Draw the overlay on top of a picture, given row by row as
+    /// `0x00RRGGBB`.
+    pub fn draw(&self, picture: &mut [u32]) {
+        for (pixel, &color) in picture.iter_mut().zip(&self.pixels) {
+            if color != CLEAR {
+                *pixel = blend(color, *pixel | 0xff00_0000) & 0x00ff_ffff;
+            }
+        }
+    }
+
+    /// Remove all drawings.
+    pub(crate) fn clear(&mut self) {
+        self.pixels.fill(CLEAR);
+    }
+
+    /// Draw a pixel, unless it's outside of the picture.
+    pub(crate) fn pixel(&mut self, x: i64, y: i64, color: u32) {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return;
+        };
+
+        if x < WIDTH && y < HEIGHT {
+            let pixel = &mut self.pixels[y * WIDTH + x];
+            *pixel = blend(color, *pixel);
+        }
+    }
+
+    /// Draw a line between two points, including both.
+    pub(crate) fn line(&mut self, from: (i64, i64), to: (i64, i64), color: u32) {
+        let clamp = |(x, y): (i64, i64)| (x.clamp(-LIMIT, LIMIT), y.clamp(-LIMIT, LIMIT));
+        let (from, to) = (clamp(from), clamp(to));
+
+        // Bresenham's algorithm, for all octants.
+        let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+        let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut x, mut y) = from;
+        let mut error = dx + dy;
+
+        loop {
+            self.pixel(x, y, color);
+            if (x, y) == to {
+                break;
+            }
+
+            if 2 * error >= dy {
+                error += dy;
+                x += sx;
+            }
+            if 2 * error <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draw a rectangle between two corners, including both, filled with a
+    /// color inside its outline.
+    pub(crate) fn rect(&mut self, from: (i64, i64), to: (i64, i64), fill: u32, outline: u32) {
+        let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+        let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+        // Only the visible part is filled, for huge rectangles.
+        for y in (top + 1).max(0)..bottom.min(LIMIT) {
+            for x in (left + 1).max(0)..right.min(LIMIT) {
+                self.pixel(x, y, fill);
+            }
+        }
+
+        self.line((left, top), (right, top), outline);
+        self.line((left, bottom), (right, bottom), outline);
+        self.line((left, top + 1), (left, bottom - 1), outline);
+        self.line((right, top + 1), (right, bottom - 1), outline);
+    }
+
+    /// Draw text with the given top left corner, starting a new line with
+    /// every line break.
+    pub(crate) fn text(&mut self, at: (i64, i64), text: &str, color: u32, background: u32) {
+        for (row, line) in (0..).zip(text.lines()) {
+            for (column, char) in (0..).zip(line.chars()) {
+                let glyph = BASIC_LEGACY[usize::from(u8::try_from(char).unwrap_or(b'?') & 0x7f)];
+                let (x0, y0) = (at.0 + column * 8, at.1 + row * 8);
+
+                for (y, bits) in (y0..).zip(glyph) {
+                    for (x, bit) in (x0..).zip(0..8) {
+                        let color = if bits >> bit & 1 != 0 {
+                            color
+                        } else {
+                            background
+                        };
+                        self.pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Blend a color on top of another, both as `0xAARRGGBB`.
+fn blend(top: u32, bottom: u32) -> u32 {
+    let top_alpha = top >> 24;
+    let bottom_alpha = (bottom >> 24) * (255 - top_alpha) / 255;
+    let alpha = top_alpha + bottom_alpha;
+    if alpha == 0 {
+        return CLEAR;
+    }
+
+    [16, 8, 0].into_iter().fold(alpha << 24, |color, shift| {
+        let channel = |color: u32| (color >> shift) & 0xff;
+        let mixed = (channel(top) * top_alpha + channel(bottom) * bottom_alpha) / alpha;
+        color | mixed << shift
+    })
+}
+
+/// Parse a color given to the API, returning the given default for `nil`.
+pub fn color(value: &Value, default: u32) -> Result<u32> {
+    let invalid = || Error::runtime(format!("invalid color {value:?}"));
+
+    match value {
+        Value::Nil => Ok(default),
+        // 0xRRGGBBAA, like FCEUX.
+        &Value::Integer(rgba) => {
+            let rgba = u32::try_from(rgba).map_err(|_| invalid())?;
+            Ok(rgba.rotate_right(8))
+        }
+        Value::String(name) => {
+            let name = name.to_str()?;
+            if let Some(&(_, color)) = COLORS.iter().find(|(n, _)| name.eq_ignore_ascii_case(n)) {
+                return Ok(color);
+            }
+
+            let hex = name.strip_prefix('#').ok_or_else(invalid)?;
+            let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            match hex.len() {
+                6 => Ok(0xff00_0000 | value),
+                8 => Ok(value.rotate_right(8)),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
@@ -0,0 +1,131 @@
+//! A bare 6502 machine, just the CPU wired to 64 KiB of RAM.
+//!
+//! This is the smallest system the CPU of `chuck-cpu` can be transplanted
+//! into: the machine services the bus access of the CPU after every cycle,
+//! and drives its `IRQ` and `NMI` pins, see [`Machine::step`]. Any other
+//! emulator is wired up the same way, with memory-mapped devices instead of
+//! plain RAM.
+//!
+//! It's meant for running test programs assembled for a flat 64 KiB address
+//! space, like the functional tests of Klaus Dormann, which report their
+//! result by trapping in an endless loop, see [`Machine::run_until_trap`]:
+//!
+//! ```
+//! # use chuck_machine::Machine;
+//! let mut machine = Machine::new();
+//! // LDX #$05; DEX; BNE $0402; JMP $0405
+//! machine.load(0x0400, &[0xa2, 0x05, 0xca, 0xd0, 0xfd, 0x4c, 0x05, 0x04]);
+//! machine.start(0x0400);
+//!
+//! assert_eq!(machine.run_until_trap(1000), Some(0x0405));
+//! assert_eq!(machine.cpu.regs.x, 0);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/Klaus2m5/6502_65C02_functional_tests>
+
+use chuck_cpu::{Cpu, Cycle, Model, Nmos6502, Pins};
+
+/// The CPU and the RAM of the machine.
+#[derive(Debug, Clone)]
+pub struct Machine<M: Model = Nmos6502> {
+    /// The CPU.
+    pub cpu: Cpu<M>,
+    /// The RAM, which fills the whole address space of the CPU.
+    pub ram: Box<[u8; 0x10000]>,
+    /// The address of the feedback register of the interrupts, if any.
+    ///
+    /// The bits 0 and 1 of the byte at this address drive the `IRQ` and the
+    /// `NMI` pin of the CPU, an interrupt being requested while its bit is
+    /// set. This is the register the interrupt test of Klaus Dormann expects
+    /// (with `I_drive = 1`), so it can trigger interrupts by writes.
+    pub interrupt_port: Option<u16>,
+}
+
+impl Machine {
+    /// Create a new machine emulating an NMOS 6502, with zeroed RAM.
+    ///
+    /// The CPU starts with its reset sequence, which reads the reset vector
+    /// at `$FFFC` from the RAM, unless it's started at another address by
+    /// [`Machine::start`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_model(Nmos6502)
+    }
+}
+
+impl<M: Model> Machine<M> {
+    /// Create a new machine emulating the given model, see [`Machine::new`].
+    #[must_use]
+    pub fn with_model(model: M) -> Self {
+        Self {
+            cpu: Cpu::with_model(model),
+            // Allocated as a vector, as the array is too large for the stack.
+            ram: vec![0; 0x10000]
+                .try_into()
+                .unwrap_or_else(|_| unreachable!()),
+            interrupt_port: None,
+        }
+    }
+
+    /// Copy a binary image into the RAM, starting at the given address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image extends past the end of the address space.
+    pub fn load(&mut self, addr: u16, image: &[u8]) {
+        self.ram[usize::from(addr)..][..image.len()].copy_from_slice(image);
+    }
+
+    /// Restart the CPU at the given address, without a reset sequence.
+    ///
+    /// The registers are reset like by [`Cpu::builder`], so the CPU is about
+    /// to execute the instruction at the address. The RAM is kept.
+    pub fn start(&mut self, pc: u16)
+    where
+        M: Default,
+    {
+        self.cpu = Cpu::builder().model(M::default()).pc(pc).build();
+        self.cpu.bus.data = self.ram[usize::from(pc)];
+    }
+
+    /// Execute a single cycle of the CPU, servicing its bus access with the
+    /// RAM and then updating its interrupt pins from the
+    /// [`interrupt_port`](Self::interrupt_port).
+    pub fn step(&mut self) {
+        self.cpu.tick(&mut *self.ram);
+
+        if let Some(port) = self.interrupt_port {
+            let feedback = self.ram[usize::from(port)];
+            self.cpu.pins.set(Pins::IRQ, feedback & 0x01 != 0);
+            self.cpu.pins.set(Pins::NMI, feedback & 0x02 != 0);
+        }
+    }
+
+    /// Execute the CPU until it traps, i.e. an instruction jumps or branches
+    /// to itself, returning the address of the instruction.
+    ///
+    /// This returns `None` if the CPU didn't trap within the given number of
+    /// cycles, or jammed, which it never recovers from.
+    pub fn run_until_trap(&mut self, max_cycles: u64) -> Option<u16> {
+        let mut last = None;
+
+        for _ in 0..max_cycles {
+            self.step();
+
+            if self.cpu.is_jammed() {
+                return None;
+            }
+            if self.cpu.bus.kind == Cycle::Opcode {
+                let addr = self.cpu.bus.addr;
+                if last == Some(addr) {
+                    return Some(addr);
+                }
+                last = Some(addr);
+            }
+        }
+
+        None
+    }
+}
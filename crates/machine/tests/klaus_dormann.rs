@@ -0,0 +1,127 @@
+//! The functional tests of Klaus Dormann, which test every documented opcode
+//! and addressing mode, the decimal mode and the interrupts of the CPU.
+//!
+//! Every test is a binary image of the whole address space, which is started
+//! at `$0400` and traps in an endless loop once it's done. Whether the trap
+//! is the one of success is looked up in the listing of the test, i.e. the
+//! `.lst` file next to the `.bin` file, which marks it with the comment
+//! `test passed, no errors`.
+//!
+//! The binaries are not part of this repository, so these tests are skipped
+//! unless the `CHUCK_KLAUS_DORMANN_DIR` environment variable points to a
+//! directory with the binaries and the listings, like the `bin_files` of the
+//! repository of the tests. A test whose binary is missing is skipped too.
+//!
+//! ```no-run
+//! CHUCK_KLAUS_DORMANN_DIR=6502_65C02_functional_tests/bin_files cargo test --release --test klaus_dormann
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/Klaus2m5/6502_65C02_functional_tests>
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use chuck_cpu::{Model, Wdc65C02};
+use chuck_machine::Machine;
+
+/// The address at which every test is started.
+const START: u16 = 0x0400;
+
+/// The address of the feedback register of the interrupt test.
+const INTERRUPT_PORT: u16 = 0xbffc;
+
+/// The maximum number of cycles of a test, well above the ~100 million
+/// cycles of the longest one.
+const MAX_CYCLES: u64 = 500_000_000;
+
+/// Return the path of a file of the test set, or `None` if the tests are
+/// skipped.
+fn test_file(name: &str) -> Option<PathBuf> {
+    let Some(dir) = env::var_os("CHUCK_KLAUS_DORMANN_DIR") else {
+        eprintln!("skipping the test binaries, CHUCK_KLAUS_DORMANN_DIR is not set");
+        return None;
+    };
+
+    let path = PathBuf::from(dir).join(name);
+    if !path.exists() {
+        eprintln!("skipping {name}, it's not in CHUCK_KLAUS_DORMANN_DIR");
+        return None;
+    }
+
+    Some(path)
+}
+
+/// Return the addresses of the traps of success in a listing, i.e. of the
+/// lines like `3469 : 4c6934    jmp *    ;test passed, no errors`.
+fn success_traps(listing: &str) -> Vec<u16> {
+    listing
+        .lines()
+        .filter(|line| line.contains("test passed, no errors"))
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            match (tokens.next(), tokens.next()) {
+                (Some(addr), Some(":")) if addr.len() == 4 => u16::from_str_radix(addr, 16).ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Run a test on a machine, with the feedback register of the interrupts at
+/// the given address.
+fn run<M: Model + Default>(mut machine: Machine<M>, name: &str, interrupt_port: Option<u16>) {
+    let Some(path) = test_file(&format!("{name}.bin")) else {
+        return;
+    };
+
+    let image = fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+    let path = path.with_extension("lst");
+    let listing =
+        fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+    let successes = success_traps(&listing);
+    assert!(
+        !successes.is_empty(),
+        "{name}: no trap of success in the listing"
+    );
+
+    machine.load(0x0000, &image);
+    machine.interrupt_port = interrupt_port;
+    machine.start(START);
+
+    // The functional tests keep the number of the current test case at $0200.
+    let trap = machine.run_until_trap(MAX_CYCLES);
+    let regs = &machine.cpu.regs;
+    assert!(
+        trap.is_some_and(|trap| successes.contains(&trap)),
+        "{name}: trapped at {trap:04X?} after {} cycles, test case {:02X} (A = {:02X}, X = {:02X}, Y = {:02X}, P = {:02X})",
+        machine.cpu.cycles,
+        machine.ram[0x0200],
+        regs.a,
+        regs.x,
+        regs.y,
+        regs.flags.bits(),
+    );
+}
+
+#[test]
+fn functional_test() {
+    run(Machine::new(), "6502_functional_test", None);
+}
+
+#[test]
+fn interrupt_test() {
+    run(Machine::new(), "6502_interrupt_test", Some(INTERRUPT_PORT));
+}
+
+#[test]
+fn extended_opcodes_test() {
+    run(
+        Machine::with_model(Wdc65C02),
+        "65C02_extended_opcodes_test",
+        None,
+    );
+}
@@ -0,0 +1,124 @@
+//! Benchmarks measuring the cost of save states and of the rewind buffer,
+//! compared to the cost of emulating a frame.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::hint::black_box;
+
+use chuck_nes::rewind::Rewind;
+use chuck_nes::Nes;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+/// A program that renders and keeps changing a page of RAM.
+const PROGRAM: &str = "
+reset:
+    JSR init_ppu
+    LDA #$1E
+    STA $2001
+loop:
+    INC $00
+    LDX $00
+    INC $0300,X
+    JMP loop
+nmi:
+irq:
+    RTI
+";
+
+/// Create a console running the program, a few frames after power-up.
+fn console() -> Nes {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    for _ in 0..10 {
+        nes.run_frame();
+    }
+
+    nes
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+
+    group.bench_function("run_frame", |b| {
+        let mut nes = console();
+        b.iter(|| black_box(nes.run_frame().pixels[0]));
+    });
+
+    group.bench_function("save_state", |b| {
+        let nes = console();
+        let mut state = Vec::new();
+
+        b.iter(|| {
+            state.clear();
+            nes.save_state(&mut state).unwrap();
+            black_box(state.len())
+        });
+    });
+
+    group.bench_function("load_state", |b| {
+        let mut nes = console();
+        let mut state = Vec::new();
+        nes.save_state(&mut state).unwrap();
+
+        b.iter(|| {
+            nes.load_state(&mut state.as_slice()).unwrap();
+            black_box(nes.cpu().regs.pc)
+        });
+    });
+
+    group.bench_function("save_state_compressed", |b| {
+        let nes = console();
+        let mut state = Vec::new();
+
+        b.iter(|| {
+            state.clear();
+            nes.save_state_compressed(&mut state).unwrap();
+            black_box(state.len())
+        });
+    });
+
+    group.bench_function("load_state_compressed", |b| {
+        let mut nes = console();
+        let mut state = Vec::new();
+        nes.save_state_compressed(&mut state).unwrap();
+
+        b.iter(|| {
+            nes.load_state(&mut state.as_slice()).unwrap();
+            black_box(nes.cpu().regs.pc)
+        });
+    });
+
+    group.bench_function("capture", |b| {
+        // Alternate between two frames, so every snapshot differs from the
+        // previous one like the snapshots of consecutive frames.
+        let mut nes = [console(), console()];
+        nes[1].run_frame();
+        let mut rewind = Rewind::new(1, 64 << 20);
+        let mut i = 0;
+
+        b.iter(|| {
+            i ^= 1;
+            black_box(rewind.capture(&nes[i]))
+        });
+    });
+
+    group.bench_function("hold", |b| {
+        let mut nes = console();
+        let mut rewind = Rewind::new(1, 64 << 20);
+        for _ in 0..2 {
+            nes.run_frame();
+            rewind.capture(&nes);
+        }
+
+        b.iter_batched_ref(
+            || (nes.clone(), rewind.clone()),
+            |(nes, rewind)| rewind.hold(nes),
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
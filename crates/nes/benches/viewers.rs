@@ -0,0 +1,96 @@
+//! Benchmarks measuring the cost of the viewers of the PPU, with and without
+//! the cache of the decoded tiles, compared to the cost of emulating a frame.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::hint::black_box;
+
+use chuck_nes::chr::TileCache;
+use chuck_nes::Nes;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// A program that renders the nametables filled with a pattern of tiles.
+const PROGRAM: &str = "
+reset:
+    JSR init_ppu
+    LDA #$20
+    STA $2006
+    LDA #$00
+    STA $2006
+    LDY #$08
+fill:
+    STX $2007
+    INX
+    BNE fill
+    DEY
+    BNE fill
+    LDA #$00
+    STA $2005
+    STA $2005
+    LDA #$1E
+    STA $2001
+loop:
+    JMP loop
+nmi:
+irq:
+    RTI
+";
+
+/// Create a console running the program, a few frames after power-up.
+fn console() -> Nes {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    for _ in 0..10 {
+        nes.run_frame();
+    }
+
+    nes
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("viewers");
+
+    group.bench_function("run_frame", |b| {
+        let mut nes = console();
+        b.iter(|| black_box(nes.run_frame().pixels[0]));
+    });
+
+    group.bench_function("nametables", |b| {
+        let mut nes = console();
+        b.iter(|| {
+            let (ppu, peek) = nes.ppu_vram();
+            black_box(ppu.render_nametables(peek)[0])
+        });
+    });
+
+    group.bench_function("nametables_cached", |b| {
+        let mut nes = console();
+        let mut cache = TileCache::new();
+        b.iter(|| {
+            let (ppu, vram) = nes.ppu_vram_cached(&mut cache);
+            black_box(ppu.render_nametables(vram)[0])
+        });
+    });
+
+    group.bench_function("pattern_tables", |b| {
+        let mut nes = console();
+        b.iter(|| {
+            let (ppu, peek) = nes.ppu_vram();
+            black_box(ppu.render_pattern_tables(0, peek)[0])
+        });
+    });
+
+    group.bench_function("pattern_tables_cached", |b| {
+        let mut nes = console();
+        let mut cache = TileCache::new();
+        b.iter(|| {
+            let (ppu, vram) = nes.ppu_vram_cached(&mut cache);
+            black_box(ppu.render_pattern_tables(0, vram)[0])
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,269 @@
+//! The logging of the writes to the registers of the sound chips with the
+//! cycle on which they happen, to replay the music of a game without the
+//! game, e.g. in chiptune tools and on hardware players.
+//!
+//! The writes are logged once enabled by [`Nes::log_audio`]: the writes to
+//! the registers of the APU (`$4000`-`$4013`, `$4015` and `$4017`), and the
+//! writes to the registers of the sound chip of the cartridge, see
+//! [`Mapper::is_audio_register`], and of the EPSM (`$401C`-`$401F`) while
+//! it's plugged in, see [`epsm`](crate::epsm). The log is exported as a VGM file (see
+//! [`AudioLog::write_vgm`]) or as text (see [`AudioLog::write_text`]):
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::{Nes, Region};
+//! // A program that sets the volume of the first pulse channel forever.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..8].copy_from_slice(&[0xa9, 0x3f, 0x8d, 0x00, 0x40, 0x4c, 0x00, 0x80]);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.log_audio(true);
+//! nes.run_frame();
+//!
+//! let log = nes.audio_log().unwrap();
+//! let write = log.writes()[0];
+//! assert_eq!((write.addr, write.data), (0x4000, 0x3f));
+//!
+//! let mut vgm = Vec::new();
+//! log.write_vgm(Region::Ntsc, &mut vgm).unwrap();
+//! assert_eq!(&vgm[..4], b"Vgm ");
+//! assert_eq!(vgm.last(), Some(&0x66));
+//! ```
+//!
+//! The registers of the APU are write-only, so the log should be enabled
+//! before the music starts, e.g. at power-on, or a player misses the writes
+//! before it.
+//!
+//! # Link(s)
+//!
+//! - <https://vgmrips.net/wiki/VGM_Specification>
+
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::mapper::Mapper;
+#[cfg(doc)]
+use crate::Nes;
+use crate::Region;
+
+/// The rate of the samples of a VGM file, by which its waits are counted.
+const VGM_RATE: u64 = 44_100;
+
+/// The size of the header of a VGM file.
+const VGM_HEADER: usize = 0x100;
+
+/// The VGM command which writes a register of the APU.
+const VGM_APU_WRITE: u8 = 0xb4;
+
+/// The VGM command which ends the commands.
+const VGM_END: u8 = 0x66;
+
+/// A write to a register of a sound chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Write {
+    /// The CPU cycle of the write, counted from the start of the log.
+    pub cycle: u64,
+    /// The address of the write.
+    pub addr: u16,
+    /// The written value.
+    pub data: u8,
+}
+
+/// The log of the writes to the registers of the sound chips.
+#[derive(Debug, Clone, Default)]
+pub struct AudioLog {
+    /// The logged writes, in the order of their cycles.
+    writes: Vec<Write>,
+    /// The bytes of the samples of the DMC, i.e. the first byte read from
+    /// every address by the DMC.
+    samples: BTreeMap<u16, u8>,
+    /// The CPU cycles since the start of the log.
+    cycles: u64,
+}
+
+impl AudioLog {
+    /// Return the logged writes, in order.
+    #[must_use]
+    pub fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+
+    /// Return the number of CPU cycles since the start of the log, i.e. its
+    /// duration.
+    #[must_use]
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Discard the logged writes and samples, and start the log over at the
+    /// current cycle.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Write the log as text, one write per line: the cycle, and the address
+    /// and the value in hexadecimal, e.g. `1234 4000 3F`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn write_text(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        for write in &self.writes {
+            writeln!(
+                writer,
+                "{} {:04X} {:02X}",
+                write.cycle, write.addr, write.data
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the log as a VGM file (version 1.61) of a console of the given
+    /// region, whose waits are rounded to the samples of the file.
+    ///
+    /// The samples of the DMC are stored at the start of the file as they
+    /// were first read, so a sample whose bank was switched later plays
+    /// back with the bytes of the first bank. The format has no commands for
+    /// the sound chips of cartridges other than the FDS, nor for the YMF288
+    /// of the EPSM, so the writes to them are left out, see
+    /// [`AudioLog::write_text`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the file would be larger than 4 GiB.
+    pub fn write_vgm(&self, region: Region, writer: &mut dyn io::Write) -> io::Result<()> {
+        let mut vgm = vec![0; VGM_HEADER];
+        self.write_samples(&mut vgm)?;
+
+        let mut position = 0;
+        for write in &self.writes {
+            let Some(register) = write.addr.checked_sub(0x4000).filter(|&reg| reg < 0x18) else {
+                continue;
+            };
+
+            let sample = vgm_sample(region, write.cycle);
+            push_wait(&mut vgm, sample - position);
+            position = sample;
+            vgm.extend_from_slice(&[VGM_APU_WRITE, register.to_le_bytes()[0], write.data]);
+        }
+
+        let total = vgm_sample(region, self.cycles);
+        push_wait(&mut vgm, total - position);
+        vgm.push(VGM_END);
+
+        let length = u32::try_from(vgm.len()).map_err(|_| invalid("VGM file too large"))?;
+        let total = u32::try_from(total).map_err(|_| invalid("VGM file too long"))?;
+        let rate = match region {
+            Region::Ntsc => 60,
+            Region::Pal | Region::Dendy => 50,
+        };
+        let data = u32::try_from(VGM_HEADER - 0x34).unwrap_or_default();
+
+        vgm[..4].copy_from_slice(b"Vgm ");
+        for (offset, value) in [
+            (0x04, length - 4),
+            (0x08, 0x161),
+            (0x18, total),
+            (0x24, rate),
+            (0x34, data),
+            (0x84, cpu_clock(region)),
+        ] {
+            vgm[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        writer.write_all(&vgm)
+    }
+
+    /// Append the samples of the DMC as data blocks of the RAM of the APU,
+    /// one for every run of consecutive addresses.
+    fn write_samples(&self, vgm: &mut Vec<u8>) -> io::Result<()> {
+        let mut samples = self.samples.iter().peekable();
+        while let Some((&start, &byte)) = samples.next() {
+            let mut block = vec![byte];
+            let mut next = start.wrapping_add(1);
+            while let Some((_, &byte)) = samples.next_if(|&(&addr, _)| addr == next) {
+                block.push(byte);
+                next = next.wrapping_add(1);
+            }
+
+            let size = u32::try_from(block.len() + 2).map_err(|_| invalid("sample too large"))?;
+            vgm.extend_from_slice(&[0x67, 0x66, 0xc2]);
+            vgm.extend_from_slice(&size.to_le_bytes());
+            vgm.extend_from_slice(&start.to_le_bytes());
+            vgm.extend_from_slice(&block);
+        }
+
+        Ok(())
+    }
+
+    /// Log a write of the CPU, if it writes a register of a sound chip, where
+    /// the registers of the EPSM exist only if it's plugged in.
+    pub(crate) fn write(&mut self, cartridge: &dyn Mapper, epsm: bool, addr: u16, data: u8) {
+        let apu = matches!(addr, 0x4000..=0x4013 | 0x4015 | 0x4017);
+        let epsm = epsm && matches!(addr, 0x401c..=0x401f);
+        if apu || epsm || (addr >= 0x4020 && cartridge.is_audio_register(addr)) {
+            self.writes.push(Write {
+                cycle: self.cycles,
+                addr,
+                data,
+            });
+        }
+    }
+
+    /// Log a byte read by the DMC, unless a byte was read from its address
+    /// before.
+    pub(crate) fn sample(&mut self, addr: u16, data: u8) {
+        self.samples.entry(addr).or_insert(data);
+    }
+
+    /// Count a CPU cycle.
+    pub(crate) const fn clock(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+/// Return the master clock of the given region, in Hz, as a fraction, see
+/// [`Region::master_clock_fraction`].
+fn master_clock(region: Region) -> (u128, u128) {
+    let (numerator, denominator) = region.master_clock_fraction();
+    (u128::from(numerator), u128::from(denominator))
+}
+
+/// Return the CPU clock of the given region, in whole Hz.
+fn cpu_clock(region: Region) -> u32 {
+    let (numerator, denominator) = master_clock(region);
+    let clock = numerator / (denominator * u128::from(region.cpu_divider()));
+    u32::try_from(clock).unwrap_or(u32::MAX)
+}
+
+/// Return the sample of a VGM file in which the given CPU cycle is.
+fn vgm_sample(region: Region, cycle: u64) -> u64 {
+    let (numerator, denominator) = master_clock(region);
+    let master = u128::from(cycle) * u128::from(region.cpu_divider()) * denominator;
+    u64::try_from(master * u128::from(VGM_RATE) / numerator).unwrap_or(u64::MAX)
+}
+
+/// Append the commands which wait for the given number of samples.
+fn push_wait(vgm: &mut Vec<u8>, mut samples: u64) {
+    while samples > 0 {
+        let wait = samples.min(0xffff);
+        match wait {
+            735 => vgm.push(0x62),
+            882 => vgm.push(0x63),
+            1..=16 => vgm.push(0x6f + u8::try_from(wait).unwrap_or_default()),
+            _ => {
+                vgm.push(0x61);
+                vgm.extend_from_slice(&u16::try_from(wait).unwrap_or(u16::MAX).to_le_bytes());
+            }
+        }
+        samples -= wait;
+    }
+}
+
+/// Create an error denoting a log that can't be exported.
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,372 @@
+//! The memory maps of the CPU and PPU buses.
+//!
+//! ```no-run
+//! CPU                              PPU
+//! $0000-$07FF  RAM                 $0000-$1FFF  Pattern tables (cartridge)
+//! $0800-$1FFF  RAM mirrors         $2000-$2FFF  Nametables (CIRAM)
+//! $2000-$2007  PPU registers       $3000-$3EFF  Nametable mirrors
+//! $2008-$3FFF  PPU mirrors         $3F00-$3FFF  Palette RAM (internal)
+//! $4000-$4015  APU and OAM DMA
+//! $4016-$4017  Controller ports (and APU frame counter writes)
+//! $4018-$401B  Test mode (unused)
+//! $401C-$401F  EPSM, if plugged in
+//! $4020-$FFFF  Cartridge
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/CPU_memory_map>
+//! - <https://www.nesdev.org/wiki/PPU_memory_map>
+
+use chuck_input::{Port, Screen, DATA_LINES};
+use chuck_ppu::viewer::Vram;
+use chuck_ppu::{Access, Ppu};
+
+use crate::chr::{CachedVram, TileCache, Watcher as ChrWatcher};
+use crate::mapper::PpuBus;
+use crate::{dma, Nes};
+
+impl Nes {
+    /// Service the access on the CPU bus, which is the CPU's unless it is
+    /// halted by the DMA unit, see [`Dma`](crate::dma::Dma).
+    pub(crate) fn service_bus(&mut self) {
+        if !self.dma.is_halted() {
+            self.service_cpu();
+            self.dma.halt(self.cpu.bus.write);
+            return;
+        }
+
+        // The APU isn't clocked during the extra cycles of the overclocking.
+        let get = self.apu.is_apu_cycle() != self.overclock.swaps_dma_cycles();
+        match self.dma.access(get) {
+            Some(dma::Access::Read(addr)) => {
+                let data = self.read(addr);
+                self.dma.fill(data);
+            }
+            Some(dma::Access::Sample) => {
+                let addr = self.apu.dmc_address();
+                let data = self.read(addr);
+                if let Some(log) = &mut self.audio_log {
+                    log.sample(addr, data);
+                }
+                self.apu.fill_sample_buffer(data);
+            }
+            Some(dma::Access::Write(data)) => self.write(0x2004, data),
+            None => self.service_cpu(),
+        }
+    }
+
+    /// Service the bus access of the CPU.
+    fn service_cpu(&mut self) {
+        let addr = self.cpu.bus.addr;
+
+        if self.cpu.bus.write {
+            if let Some(events) = &mut self.events {
+                events.write(&self.ppu, addr, self.cpu.bus.data);
+            }
+            if let Some(log) = &mut self.audio_log {
+                let epsm = self.epsm.is_some();
+                log.write(&*self.cartridge, epsm, addr, self.cpu.bus.data);
+            }
+            self.write(addr, self.cpu.bus.data);
+        } else {
+            self.cpu.bus.data = self.read(addr);
+        }
+    }
+
+    /// Service the VRAM bus access of the PPU.
+    ///
+    /// The palette RAM is internal to the PPU, so the addresses `$3F00`-`$3FFF`
+    /// reach the nametables on the external bus.
+    pub(crate) fn service_ppu(&mut self) {
+        let addr = self.ppu.bus.addr & 0x3fff;
+        let (edge, held) = self.a12.observe(addr);
+        self.cartridge.ppu_bus(&PpuBus {
+            addr,
+            access: self.ppu.bus.access,
+            edge,
+            held,
+            scanline: self.ppu.scanline(),
+            dot: self.ppu.dot(),
+        });
+
+        match (self.ppu.bus.access, addr) {
+            (Access::Idle, _) => {}
+            (Access::Read, 0x0000..=0x1fff) => self.ppu.bus.data = self.cartridge.ppu_read(addr),
+            (Access::Read, _) => self.ppu.bus.data = self.ciram[self.ciram_index(addr)],
+            (Access::Write, 0x0000..=0x1fff) => {
+                self.cartridge.ppu_write(addr, self.ppu.bus.data);
+
+                for chr in [&mut self.chr, &mut self.viewer_chr].into_iter().flatten() {
+                    chr.write_ppu(&*self.cartridge, addr, self.ppu.bus.data);
+                }
+            }
+            (Access::Write, _) => self.ciram[self.ciram_index(addr)] = self.ppu.bus.data,
+        }
+    }
+
+    /// Read from the CPU bus.
+    ///
+    /// A read of an address that isn't driven by any device (open bus) reads
+    /// back the last value on the external data bus. The registers of the
+    /// 2A03 are read internally, so they don't drive the external data bus.
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            0x0000..=0x1fff => Some(self.ram[usize::from(addr & 0x07ff)]),
+            0x2000..=0x3fff => Some(self.ppu.read(addr)),
+            // Bit 5 of `SND_CHN` is not driven.
+            0x4015 => return self.apu.read(addr) | (self.open_bus & 0x20),
+            // Only the data lines of the controller ports are driven.
+            0x4016 | 0x4017 => {
+                let port = Port::from_addr(addr);
+                let screen = Screen {
+                    pixels: self.ppu.frame_buffer(),
+                    scanline: self.ppu.scanline(),
+                    dot: self.ppu.dot(),
+                    scanlines: self.ppu.region().scanlines(),
+                };
+                self.input.observe(port, &screen);
+
+                let data = self.input.read(port);
+                Some((self.open_bus & !DATA_LINES) | data)
+            }
+            0x4020..=0xffff => self.cartridge.cpu_read(addr),
+            _ => None,
+        };
+
+        // The cheat devices sit between the console and the cartridge, which
+        // they replace as the driver of the data bus.
+        if let Some(data) = data {
+            self.open_bus = self.cheats.apply(addr, data);
+        }
+
+        self.open_bus
+    }
+
+    /// Write to the CPU bus, which drives the external data bus for all
+    /// addresses.
+    fn write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+
+        if matches!(addr, 0x6000..=0x7fff) {
+            self.sram.write();
+        }
+
+        match addr {
+            0x0000..=0x1fff => self.ram[usize::from(addr & 0x07ff)] = data,
+            0x2000..=0x3fff => self.ppu.write(addr, data),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write(addr, data),
+            0x4014 => self.dma.start_oam(data),
+            0x4016 => {
+                if let Some(schedule) = &mut self.schedule {
+                    schedule.write(self.cpu.cycles, data, &mut self.input);
+                }
+                self.input.write(data);
+            }
+            0x401c..=0x401f => {
+                if let Some(epsm) = &mut self.epsm {
+                    epsm.write(addr, data);
+                }
+            }
+            0x4020..=0xffff => {
+                self.cartridge.cpu_write(addr, data);
+
+                for chr in [&mut self.chr, &mut self.viewer_chr].into_iter().flatten() {
+                    chr.write_cpu(&*self.cartridge);
+                }
+            }
+            _ => {}
+        }
+
+        if !self.frozen.is_empty() {
+            if let Some(&value) = self.frozen.get(&frozen_key(addr)) {
+                self.poke(addr, value);
+            }
+        }
+    }
+
+    /// Read from the CPU bus without the side effects of a read by the CPU,
+    /// e.g. for debuggers: `PPUDATA` isn't incremented, the value isn't
+    /// driven onto the data bus, and the board isn't clocked.
+    ///
+    /// The registers of the PPU, the APU and the controller ports are peeked
+    /// (see [`Ppu::peek`](chuck_ppu::Ppu::peek)), and the cartridge is peeked
+    /// (see [`Mapper::cpu_peek`](crate::mapper::Mapper::cpu_peek)). The cheats
+    /// are applied, just like for the CPU.
+    #[must_use]
+    pub fn peek(&self, addr: u16) -> u8 {
+        let data = match addr {
+            0x0000..=0x1fff => Some(self.ram[usize::from(addr & 0x07ff)]),
+            0x2000..=0x3fff => Some(self.ppu.peek(addr)),
+            0x4015 => return self.apu.peek(addr) | (self.open_bus & 0x20),
+            0x4016 | 0x4017 => {
+                let data = self.input.peek(Port::from_addr(addr));
+                Some((self.open_bus & !DATA_LINES) | data)
+            }
+            0x4020..=0xffff => self.cartridge.cpu_peek(addr),
+            _ => None,
+        };
+
+        data.map_or(self.open_bus, |data| self.cheats.apply(addr, data))
+    }
+
+    /// Write the memory at an address of the CPU bus without the side effects
+    /// of a write by the CPU, e.g. for debuggers and cheat engines.
+    ///
+    /// The RAM and the memory of the cartridge are written (see
+    /// [`Mapper::cpu_poke`](crate::mapper::Mapper::cpu_poke)), including its
+    /// PRG-ROM, while the registers of the chips and of the board are left
+    /// alone, so pokes of them are ignored. The value isn't driven onto the
+    /// data bus either.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::Nes;
+    /// let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// nes.poke(0x0812, 0x42);
+    /// assert_eq!(nes.peek(0x0012), 0x42);
+    ///
+    /// // The PRG-ROM is patched in place, so both of its mirrors change.
+    /// nes.poke(0xc000, 0x4c);
+    /// assert_eq!(nes.peek(0x8000), 0x4c);
+    /// ```
+    pub fn poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1fff => self.ram[usize::from(addr & 0x07ff)] = data,
+            0x4020..=0xffff => {
+                // The save RAM changed all the same.
+                if matches!(addr, 0x6000..=0x7fff) {
+                    self.sram.write();
+                }
+                self.cartridge.cpu_poke(addr, data);
+            }
+            _ => {}
+        }
+    }
+
+    /// Freeze an address of the CPU bus at the given value, which is poked
+    /// (see [`Nes::poke`]) right away and after every write of the CPU to the
+    /// address, e.g. to keep the lives of a game from running out.
+    ///
+    /// Unlike a [`Cheat`](crate::cheat::Cheat), which only changes what the CPU
+    /// reads, a freeze changes the memory itself. The mirrors of the RAM share
+    /// their freezes, and the frozen addresses are kept on power-up (when the
+    /// values are poked again), but aren't part of save states.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::Nes;
+    /// // A program that counts the byte at `$0010` up in a loop.
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[..5].copy_from_slice(&[0xe6, 0x10, 0x4c, 0x00, 0x80]);
+    /// prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+    ///
+    /// let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+    /// nes.freeze(0x0810, 0x03);
+    /// nes.run_frame();
+    /// assert_eq!(nes.peek(0x0010), 0x03);
+    ///
+    /// nes.unfreeze(0x0010);
+    /// nes.run_frame();
+    /// assert_ne!(nes.peek(0x0010), 0x03);
+    /// ```
+    pub fn freeze(&mut self, addr: u16, value: u8) {
+        self.frozen.insert(frozen_key(addr), value);
+        self.poke(addr, value);
+    }
+
+    /// Unfreeze an address of the CPU bus, see [`Nes::freeze`], returning the
+    /// value it was frozen at, if any.
+    pub fn unfreeze(&mut self, addr: u16) -> Option<u8> {
+        self.frozen.remove(&frozen_key(addr))
+    }
+
+    /// Return the frozen addresses and their values, in the order of the
+    /// addresses, where the RAM is at `$0000`-`$07FF`.
+    pub fn frozen(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.frozen.iter().map(|(&addr, &value)| (addr, value))
+    }
+
+    /// Poke the frozen addresses with their values again, e.g. after the
+    /// RAM was cleared on power-up.
+    pub(crate) fn poke_frozen(&mut self) {
+        let frozen = std::mem::take(&mut self.frozen);
+        for (&addr, &value) in &frozen {
+            self.poke(addr, value);
+        }
+        self.frozen = frozen;
+    }
+
+    /// Return the PPU along with a function which reads its VRAM bus without
+    /// side effects, for the viewers of [`chuck_ppu::viewer`]:
+    ///
+    /// ```
+    /// # use chuck_nes::{mapper::{Mirroring, Nrom}, Nes};
+    /// # use chuck_ppu::viewer::{NAMETABLES_HEIGHT, NAMETABLES_WIDTH};
+    /// # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// let (ppu, peek) = nes.ppu_vram();
+    /// let nametables = ppu.render_nametables(peek);
+    /// assert_eq!(nametables.len(), NAMETABLES_WIDTH * NAMETABLES_HEIGHT);
+    /// ```
+    pub fn ppu_vram(&mut self) -> (&Ppu, impl FnMut(u16) -> u8 + '_) {
+        let (cartridge, ciram) = (&mut self.cartridge, &self.ciram);
+        let peek = move |addr: u16| match addr & 0x3fff {
+            addr @ 0x0000..=0x1fff => cartridge.ppu_read(addr),
+            addr => ciram[usize::from(cartridge.mirroring().ciram_addr(addr))],
+        };
+
+        (&self.ppu, peek)
+    }
+
+    /// Return the PPU along with its VRAM bus like [`Nes::ppu_vram`], but
+    /// with the decoded tiles kept in the given cache, which renders the
+    /// viewers faster, see [`chr`](crate::chr).
+    ///
+    /// This watches the pattern tables on its own and takes their changes to
+    /// drop the stale tiles of the cache, so it's meant for a single cache
+    /// per console. The changes of [`Nes::watch_chr`] are collected apart
+    /// from these, so they're still left for [`Nes::take_chr_changes`].
+    ///
+    /// ```
+    /// # use chuck_nes::chr::TileCache;
+    /// # use chuck_nes::{mapper::{Mirroring, Nrom}, Nes};
+    /// # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], vec![0x55; 0x2000], Mirroring::Vertical)));
+    /// let mut cache = TileCache::new();
+    /// let (ppu, vram) = nes.ppu_vram_cached(&mut cache);
+    /// let cached = ppu.render_pattern_tables(0, vram);
+    ///
+    /// let (ppu, peek) = nes.ppu_vram();
+    /// assert_eq!(cached, ppu.render_pattern_tables(0, peek));
+    /// ```
+    pub fn ppu_vram_cached<'a>(
+        &'a mut self,
+        cache: &'a mut TileCache,
+    ) -> (&'a Ppu, impl Vram + 'a) {
+        let chr = self
+            .viewer_chr
+            .get_or_insert_with(|| Box::new(ChrWatcher::new(&*self.cartridge)));
+        cache.apply(&chr.take());
+
+        let vram = CachedVram {
+            cartridge: &mut *self.cartridge,
+            ciram: &self.ciram,
+            cache,
+        };
+        (&self.ppu, vram)
+    }
+
+    /// Return the index into the CIRAM of the given nametable address.
+    fn ciram_index(&self, addr: u16) -> usize {
+        usize::from(self.cartridge.mirroring().ciram_addr(addr))
+    }
+}
+
+/// Return the address under which an address of the CPU bus is frozen, which
+/// is the same for all mirrors of the RAM.
+const fn frozen_key(addr: u16) -> u16 {
+    if addr < 0x2000 {
+        addr & 0x07ff
+    } else {
+        addr
+    }
+}
@@ -0,0 +1,246 @@
+//! Cheat codes, as applied by the Game Genie and the Pro Action Replay.
+//!
+//! Both devices sit between the console and the cartridge, so the cheats are
+//! applied on the CPU bus rather than by the mappers: every [`Cheat`] replaces
+//! the value read from its address, which works for any board.
+//!
+//! - A Game Genie code of 6 letters replaces a byte of the PRG-ROM, one of 8
+//!   letters only replaces it if it has the code's compare value, which tells
+//!   the banks at the same address apart.
+//! - A Pro Action Replay code (`AAAAVV` or `AAAA:VV` in hex) freezes a byte of
+//!   RAM: the game may write the byte, but always reads the code's value.
+//!
+//! Cheats can also be given in the raw format `AAAA:VV` or `AAAA?CC:VV` (with
+//! a compare value), which is how they're displayed.
+//!
+//! ```
+//! # use chuck_nes::cheat::Cheat;
+//! let cheat: Cheat = "SXIOPO".parse()?;
+//! assert_eq!((cheat.addr, cheat.value, cheat.compare), (0x91d9, 0xad, None));
+//!
+//! let cheat: Cheat = "YEUZUGAA".parse()?;
+//! assert_eq!(cheat.to_string(), "ACB3?00:07");
+//!
+//! let cheat: Cheat = "075A:09".parse()?;
+//! assert_eq!(cheat, "075A09".parse()?);
+//! # Ok::<(), chuck_nes::cheat::InvalidCode>(())
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Game_Genie>
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The letters of Game Genie codes, in the order of the values they encode.
+const LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+/// A cheat, which replaces the value read from an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    /// The address.
+    pub addr: u16,
+    /// The value read instead.
+    pub value: u8,
+    /// The value the read must have to be replaced, if any.
+    pub compare: Option<u8>,
+}
+
+impl Cheat {
+    /// Decode a Game Genie code of 6 or 8 letters.
+    fn game_genie(code: &[u8]) -> Option<Self> {
+        if !matches!(code.len(), 6 | 8) {
+            return None;
+        }
+
+        let n: Vec<u16> = code
+            .iter()
+            .map(|letter| {
+                let index = LETTERS.iter().position(|l| l == letter)?;
+                u16::try_from(index).ok()
+            })
+            .collect::<Option<_>>()?;
+
+        let addr = 0x8000
+            | ((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8)
+            | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4)
+            | ((n[1] & 8) << 4)
+            | (n[4] & 7)
+            | (n[3] & 8);
+        let byte = |high: usize, low: usize, bit: usize| {
+            let byte = ((n[high] & 7) << 4) | ((n[low] & 8) << 4) | (n[low] & 7) | (n[bit] & 8);
+            byte.to_le_bytes()[0]
+        };
+
+        Some(if code.len() == 6 {
+            Self {
+                addr,
+                value: byte(1, 0, 5),
+                compare: None,
+            }
+        } else {
+            Self {
+                addr,
+                value: byte(1, 0, 7),
+                compare: Some(byte(7, 6, 5)),
+            }
+        })
+    }
+
+    /// Decode a Pro Action Replay code or a code in the raw format.
+    fn raw(code: &str) -> Option<Self> {
+        let hex = |digits: &str, len| {
+            (digits.len() == len && digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+                .then(|| u16::from_str_radix(digits, 16).ok())
+                .flatten()
+        };
+        let byte = |digits| hex(digits, 2).map(|byte| byte.to_le_bytes()[0]);
+
+        let (addr, value) = match code.split_once(':') {
+            Some(parts) => parts,
+            None => code.split_at_checked(4)?,
+        };
+        let (addr, compare) = match addr.split_once('?') {
+            Some((addr, compare)) => (addr, Some(byte(compare)?)),
+            None => (addr, None),
+        };
+
+        Some(Self {
+            addr: hex(addr, 4)?,
+            value: byte(value)?,
+            compare,
+        })
+    }
+
+    /// Return the value read from the address of the cheat, given the value
+    /// of the device at the address.
+    fn apply(self, data: u8) -> u8 {
+        match self.compare {
+            Some(compare) if compare != data => data,
+            _ => self.value,
+        }
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = InvalidCode;
+
+    /// Parse a Game Genie code, a Pro Action Replay code or a code in the raw
+    /// format, ignoring the case of the letters.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let upper = code.trim().to_ascii_uppercase();
+
+        Self::game_genie(upper.as_bytes())
+            .or_else(|| Self::raw(&upper))
+            .ok_or_else(|| InvalidCode {
+                code: code.to_owned(),
+            })
+    }
+}
+
+impl fmt::Display for Cheat {
+    /// Format the cheat in the raw format, `AAAA:VV` or `AAAA?CC:VV`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}", self.addr)?;
+
+        if let Some(compare) = self.compare {
+            write!(f, "?{compare:02X}")?;
+        }
+
+        write!(f, ":{:02X}", self.value)
+    }
+}
+
+/// An error returned for a code which is none of the supported formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCode {
+    /// The code.
+    pub code: String,
+}
+
+impl fmt::Display for InvalidCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cheat code {:?}", self.code)
+    }
+}
+
+impl std::error::Error for InvalidCode {}
+
+/// The cheats of a console, each of which can be enabled or disabled, see
+/// [`Nes::cheats_mut`](crate::Nes::cheats_mut).
+///
+/// The cheats are applied in the order they were added, so of two enabled
+/// cheats for the same address, the later one wins.
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    /// The cheats, and the flags denoting if they're enabled.
+    cheats: Vec<(Cheat, bool)>,
+}
+
+impl Cheats {
+    /// Add an enabled cheat, returning its index.
+    pub fn add(&mut self, cheat: Cheat) -> usize {
+        self.cheats.push((cheat, true));
+        self.cheats.len() - 1
+    }
+
+    /// Remove the cheat at the given index, shifting the later cheats down.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no cheat at the index.
+    pub fn remove(&mut self, index: usize) -> Cheat {
+        self.cheats.remove(index).0
+    }
+
+    /// Remove all cheats.
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    /// Enable or disable the cheat at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no cheat at the index.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        self.cheats[index].1 = enabled;
+    }
+
+    /// Check if the cheat at the given index is enabled, or `None` if there's
+    /// no such cheat.
+    #[must_use]
+    pub fn is_enabled(&self, index: usize) -> Option<bool> {
+        self.cheats.get(index).map(|&(_, enabled)| enabled)
+    }
+
+    /// Return the number of cheats.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    /// Check if there are no cheats.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Return an iterator over the cheats, and the flags denoting if they're
+    /// enabled.
+    pub fn iter(&self) -> impl Iterator<Item = (&Cheat, bool)> {
+        self.cheats.iter().map(|(cheat, enabled)| (cheat, *enabled))
+    }
+
+    /// Return the value read from the given address by the CPU, given the
+    /// value of the device at the address.
+    pub(crate) fn apply(&self, addr: u16, data: u8) -> u8 {
+        self.cheats
+            .iter()
+            .filter(|&&(cheat, enabled)| enabled && cheat.addr == addr)
+            .fold(data, |data, (cheat, _)| cheat.apply(data))
+    }
+}
@@ -0,0 +1,220 @@
+//! The changes of the pattern tables, for renderers which cache the decoded
+//! tiles of the CHR memory.
+//!
+//! A renderer may decode the tiles of the CHR memory of a board (see
+//! [`Mapper::chr`]) once and look them up by their offset, which the banks of
+//! the board map into the pattern tables (see [`banks`]). Once enabled by
+//! [`Nes::watch_chr`], the console collects the changes which make such a
+//! cache stale, i.e. the switches of the banks and the writes to the CHR-RAM,
+//! until they're taken with [`Nes::take_chr_changes`]:
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::{chr, Nes};
+//! // A program that writes `$FF` to the first byte of the CHR-RAM.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..8].copy_from_slice(&[0xa9, 0xff, 0x8d, 0x07, 0x20, 0x4c, 0x05, 0x80]);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.watch_chr(true);
+//!
+//! // Everything is stale at first.
+//! assert!(nes.take_chr_changes().unwrap().all);
+//!
+//! nes.run_frame();
+//! let changes = nes.take_chr_changes().unwrap();
+//! assert!(!changes.all && !changes.banks);
+//! assert_eq!(changes.tiles.into_iter().collect::<Vec<_>>(), [0]);
+//!
+//! // NROM maps its 8 KiB of CHR-RAM as they are.
+//! assert_eq!(chr::banks(nes.cartridge())[1], Some(0x400));
+//! ```
+//!
+//! The viewers of [`chuck_ppu::viewer`] keep the decoded tiles in a
+//! [`TileCache`], see [`Nes::ppu_vram_cached`], which drops the stale ones
+//! with the same changes, collected apart from the ones taken here. The
+//! `viewers` benchmark compares them with and without the cache.
+//!
+//! The PPU itself doesn't use the cache. It fetches the bit planes of every
+//! tile over its bus at the dots of the hardware, which boards such as the
+//! MMC2 and the MMC3 watch to switch banks and count scanlines, and shifts
+//! them out one bit per dot, so there's no decoding of whole rows to save.
+//! Chuck has no scanline renderer which could look up decoded tiles instead.
+//!
+//! [`Mapper::chr`]: crate::mapper::Mapper::chr
+
+use std::collections::BTreeSet;
+
+use chuck_ppu::viewer::{self, Vram};
+
+use crate::mapper::Mapper;
+#[cfg(doc)]
+use crate::Nes;
+
+/// The size of a window of the pattern tables, i.e. of the smallest bank.
+const WINDOW: u16 = 0x400;
+
+/// The size of a tile in the CHR memory.
+pub const TILE_SIZE: usize = 16;
+
+/// The changes of the pattern tables since they were last taken, see
+/// [`Nes::take_chr_changes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChrChanges {
+    /// A flag denoting if any of the CHR memory may have changed, so all the
+    /// cached tiles are stale, e.g. after the watching started or a save
+    /// state was loaded.
+    pub all: bool,
+    /// A flag denoting if the banks mapped into the pattern tables changed,
+    /// see [`banks`].
+    pub banks: bool,
+    /// The tiles of the CHR memory that were written, by their offset
+    /// divided by [`TILE_SIZE`].
+    pub tiles: BTreeSet<usize>,
+}
+
+/// Return the offsets into the CHR memory of the 1 KiB windows of the pattern
+/// tables with the current banks of a board, see
+/// [`Mapper::chr_offset`].
+#[must_use]
+pub fn banks(cartridge: &dyn Mapper) -> [Option<usize>; 8] {
+    std::array::from_fn(|i| {
+        let addr = u16::try_from(i).map_or(0, |i| i * WINDOW);
+        cartridge.chr_offset(addr)
+    })
+}
+
+/// The watcher of the pattern tables, which collects their changes.
+#[derive(Debug, Clone)]
+pub(crate) struct Watcher {
+    /// The banks of the board, as last seen.
+    banks: [Option<usize>; 8],
+    /// The changes since they were last taken.
+    changes: ChrChanges,
+}
+
+impl Watcher {
+    /// Start watching the pattern tables of a board, with all of them stale.
+    pub(crate) fn new(cartridge: &dyn Mapper) -> Self {
+        Self {
+            banks: banks(cartridge),
+            changes: ChrChanges {
+                all: true,
+                ..ChrChanges::default()
+            },
+        }
+    }
+
+    /// Note a write of the CPU to the board, which may have switched its
+    /// banks.
+    pub(crate) fn write_cpu(&mut self, cartridge: &dyn Mapper) {
+        let banks = banks(cartridge);
+        if banks != self.banks {
+            self.banks = banks;
+            self.changes.banks = true;
+        }
+    }
+
+    /// Note a write of the PPU to the pattern tables of a board, whose tile
+    /// is stale if the CHR memory took the value, i.e. unless it's CHR-ROM.
+    pub(crate) fn write_ppu(&mut self, cartridge: &dyn Mapper, addr: u16, data: u8) {
+        if let Some(offset) = cartridge.chr_offset(addr) {
+            if cartridge.chr().get(offset) == Some(&data) {
+                self.changes.tiles.insert(offset / TILE_SIZE);
+            }
+        }
+    }
+
+    /// Note that the whole board changed, e.g. as a save state was loaded.
+    pub(crate) fn reload(&mut self, cartridge: &dyn Mapper) {
+        self.banks = banks(cartridge);
+        self.changes.all = true;
+        self.changes.banks = true;
+    }
+
+    /// Take the changes since they were last taken.
+    pub(crate) fn take(&mut self) -> ChrChanges {
+        std::mem::take(&mut self.changes)
+    }
+}
+
+/// The decoded rows of a tile, from top to bottom, with the pixels (`0`-`3`)
+/// of each row from left to right.
+type Tile = [[u8; 8]; 8];
+
+/// A cache of the decoded tiles of the CHR memory of a board, keyed by their
+/// offset into the CHR memory, so a tile is decoded once for all the banks
+/// which map it, see [`Nes::ppu_vram_cached`].
+#[derive(Debug, Clone, Default)]
+pub struct TileCache {
+    /// The decoded tiles, by their offset divided by [`TILE_SIZE`], or `None`
+    /// for the tiles that aren't decoded yet or are stale.
+    tiles: Vec<Option<Tile>>,
+}
+
+impl TileCache {
+    /// Create an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the tiles which the given changes made stale.
+    pub fn apply(&mut self, changes: &ChrChanges) {
+        if changes.all {
+            self.tiles.clear();
+            return;
+        }
+
+        for &tile in &changes.tiles {
+            if let Some(tile) = self.tiles.get_mut(tile) {
+                *tile = None;
+            }
+        }
+    }
+
+    /// Return the given tile of a CHR memory, decoding it if it isn't cached,
+    /// or `None` if it's outside of the memory.
+    fn tile(&mut self, chr: &[u8], tile: usize) -> Option<&Tile> {
+        let planes = chr.get(tile * TILE_SIZE..(tile + 1) * TILE_SIZE)?;
+        if self.tiles.len() * TILE_SIZE < chr.len() {
+            self.tiles.resize(chr.len().div_ceil(TILE_SIZE), None);
+        }
+
+        let cached = self.tiles.get_mut(tile)?;
+        Some(cached.get_or_insert_with(|| {
+            std::array::from_fn(|row| viewer::decode_row(planes[row], planes[row + 8]))
+        }))
+    }
+}
+
+/// The VRAM bus of the viewers, whose tiles are kept in a [`TileCache`].
+pub(crate) struct CachedVram<'a> {
+    /// The board of the cartridge.
+    pub(crate) cartridge: &'a mut dyn Mapper,
+    /// The nametable RAM.
+    pub(crate) ciram: &'a [u8; 0x800],
+    /// The cache of the decoded tiles.
+    pub(crate) cache: &'a mut TileCache,
+}
+
+impl Vram for CachedVram<'_> {
+    fn peek(&mut self, addr: u16) -> u8 {
+        match addr & 0x3fff {
+            addr @ 0x0000..=0x1fff => self.cartridge.ppu_read(addr),
+            addr => self.ciram[usize::from(self.cartridge.mirroring().ciram_addr(addr))],
+        }
+    }
+
+    fn tile_row(&mut self, addr: u16) -> [u8; 8] {
+        // The banks have at least 1 KiB, so a tile is never split among them.
+        let offset = self.cartridge.chr_offset(addr & 0x1ff0);
+        let cached = offset.and_then(|offset| {
+            let tile = self.cache.tile(self.cartridge.chr(), offset / TILE_SIZE)?;
+            Some(tile[usize::from(addr & 7)])
+        });
+
+        cached.unwrap_or_else(|| viewer::decode_row(self.peek(addr), self.peek(addr | 8)))
+    }
+}
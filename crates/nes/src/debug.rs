@@ -0,0 +1,80 @@
+//! The debugging of the console's program with the hooks of its CPU, see
+//! [`chuck_cpu::debug`].
+//!
+//! The console runs with the hooks checked after every CPU cycle by
+//! [`Nes::debug_step`] or [`Nes::debug_run_frame`], and stops in the middle
+//! of the cycle that hit a hook, after its bus access was serviced:
+//!
+//! ```
+//! # use chuck_cpu::debug::Reason;
+//! # use chuck_nes::{mapper::{Mirroring, Nrom}, Nes};
+//! // A program that increments $10 forever, starting at $8000.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..5].copy_from_slice(&[0xe6, 0x10, 0x4c, 0x00, 0x80]);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.hooks_mut().add_write_watchpoint(0x10..=0x10);
+//!
+//! assert_eq!(nes.debug_run_frame().err(), Some(Reason::Write(0x10)));
+//! assert!(nes.debug_run_frame().is_err());
+//! ```
+
+use chuck_cpu::debug::{Hooks, Reason, StepOutcome};
+use chuck_cpu::{Cpu, Registers};
+
+use crate::{Frame, Nes};
+
+impl Nes {
+    /// Return the breakpoints and watchpoints of the CPU mutably.
+    pub fn hooks_mut(&mut self) -> &mut Hooks {
+        &mut self.cpu.hooks
+    }
+
+    /// Execute a single CPU cycle like [`Nes::step`], then check the hooks of
+    /// the CPU against the cycle, see [`Cpu::debug_step`].
+    pub fn debug_step(&mut self) -> StepOutcome {
+        self.clock(Cpu::debug_step)
+    }
+
+    /// Execute until the PPU starts the next frame like [`Nes::run_frame`],
+    /// unless the CPU hits one of its hooks first, which returns the reason
+    /// of the break instead.
+    ///
+    /// The next call then continues the interrupted frame, so the returned
+    /// frame holds all audio samples since the previous frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason of the break if the CPU hits a hook.
+    pub fn debug_run_frame(&mut self) -> Result<Frame<'_>, Reason> {
+        let (frame, phase) = self.interrupted.take().unwrap_or_else(|| {
+            self.start_frame();
+            (self.ppu.frame(), self.ppu.frame_phase())
+        });
+
+        while self.ppu.frame() == frame {
+            if let StepOutcome::Break(reason) = self.debug_step() {
+                self.interrupted = Some((frame, phase));
+                return Err(reason);
+            }
+        }
+
+        Ok(self.end_frame(phase))
+    }
+
+    /// Set the registers of the CPU.
+    ///
+    /// If the opcode of the next instruction was already fetched (see
+    /// [`Cpu::instruction_progress`]), it's fetched again from the new
+    /// program counter, so that the execution continues there.
+    pub fn set_registers(&mut self, regs: Registers) {
+        let pc = regs.pc;
+        self.cpu.regs = regs;
+
+        if self.cpu.instruction_progress().fetching {
+            self.cpu.bus.addr = pc;
+            self.cpu.bus.data = self.peek(pc);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! The configuration of the power-up state of the console.
+//!
+//! Real consoles differ from one power-up to the next in the contents of the
+//! RAM, the alignment of the clocks of the CPU and the PPU, and how far the
+//! frame counter of the APU already ran.
+//!
+//! The emulation is deterministic for any configuration, so a run is
+//! reproduced exactly, e.g. on another machine, by the same configuration.
+//! Hence it's part of save states, and movies record it too, see
+//! [`Movie::determinism`](crate::movie::Movie::determinism). Sweeping through
+//! the configurations shows whether a game (or a test ROM) depends on the
+//! power-up state:
+//!
+//! ```
+//! # use chuck_nes::determinism::{DeterminismConfig, RamInit};
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::Nes;
+//! # let cartridge = || Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical));
+//! let config = DeterminismConfig {
+//!     ram: RamInit::Random(42),
+//!     alignment: 4,
+//!     frame_counter: 10,
+//! };
+//!
+//! let mut nes = Nes::new(cartridge());
+//! nes.set_determinism(config);
+//! nes.power_cycle();
+//!
+//! let mut other = Nes::new(cartridge());
+//! other.set_determinism(config);
+//! other.power_cycle();
+//!
+//! assert_eq!(nes.peek(0x0123), other.peek(0x0123));
+//! assert_eq!(nes.run_frame().samples, other.run_frame().samples);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/CPU_power_up_state>
+//! - <https://www.nesdev.org/wiki/PPU_power_up_state>
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The configuration of the power-up state, which takes effect with the next
+/// power-up, see [`Nes::power_cycle`](crate::Nes::power_cycle).
+///
+/// The default is the power-up state of a new console: the RAM is cleared,
+/// and the CPU, the PPU and the frame counter start together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeterminismConfig {
+    /// The contents of the RAM and of the nametable RAM (CIRAM).
+    pub ram: RamInit,
+    /// The number of master clock cycles the PPU is ahead of the CPU, below
+    /// the number of master clock cycles per CPU cycle (12 on NTSC, 16 on PAL
+    /// and 15 on Dendy).
+    ///
+    /// The PPU is emulated a dot at a time, so the alignments within the
+    /// same dot (4 master clock cycles on NTSC, 5 on PAL and Dendy) only
+    /// differ in when the dots of PAL and Dendy are spread out among the CPU
+    /// cycles.
+    pub alignment: u8,
+    /// The number of CPU cycles the frame counter of the APU already ran
+    /// before the CPU's first cycle, see
+    /// [`Apu::skip_frame_counter`](chuck_apu::Apu::skip_frame_counter).
+    pub frame_counter: u16,
+}
+
+/// The contents of the RAM at power-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamInit {
+    /// Every byte has the same value, e.g. `$00` or `$FF`.
+    Fill(u8),
+    /// 4 bytes of `$00` and 4 bytes of `$FF` take turns, as seen on many
+    /// consoles and in FCEUX.
+    Pattern,
+    /// Random bytes, generated from the given seed.
+    Random(u64),
+}
+
+impl Default for RamInit {
+    fn default() -> Self {
+        Self::Fill(0x00)
+    }
+}
+
+impl RamInit {
+    /// Fill the given memories, where the random bytes continue from one
+    /// memory into the next.
+    pub(crate) fn fill<'a>(self, memories: impl IntoIterator<Item = &'a mut [u8]>) {
+        let mut state = match self {
+            Self::Random(seed) => seed,
+            Self::Fill(_) | Self::Pattern => 0,
+        };
+
+        for memory in memories {
+            for (i, byte) in memory.iter_mut().enumerate() {
+                *byte = match self {
+                    Self::Fill(value) => value,
+                    Self::Pattern if i & 4 == 0 => 0x00,
+                    Self::Pattern => 0xff,
+                    Self::Random(_) => splitmix64(&mut state).to_le_bytes()[0],
+                };
+            }
+        }
+    }
+}
+
+/// Return the next number of the `SplitMix64` generator, which is small and
+/// defined exactly, so the random bytes are the same on all machines.
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Formats the contents as `fill:$XX`, `pattern` or `random:SEED`, e.g. for
+/// the headers of movies.
+impl fmt::Display for RamInit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fill(value) => write!(f, "fill:${value:02X}"),
+            Self::Pattern => f.write_str("pattern"),
+            Self::Random(seed) => write!(f, "random:{seed}"),
+        }
+    }
+}
+
+impl FromStr for RamInit {
+    type Err = InvalidRamInit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "pattern" => Ok(Self::Pattern),
+            Some(("fill", value)) => {
+                let value = value.strip_prefix('$').ok_or(InvalidRamInit)?;
+                u8::from_str_radix(value, 16)
+                    .map(Self::Fill)
+                    .map_err(|_| InvalidRamInit)
+            }
+            Some(("random", seed)) => seed.parse().map(Self::Random).map_err(|_| InvalidRamInit),
+            _ => Err(InvalidRamInit),
+        }
+    }
+}
+
+/// An error returned for malformed contents of the RAM, see the `FromStr`
+/// implementation of [`RamInit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidRamInit;
+
+impl fmt::Display for InvalidRamInit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid RAM contents, expected fill:$XX, pattern or random:SEED")
+    }
+}
+
+impl std::error::Error for InvalidRamInit {}
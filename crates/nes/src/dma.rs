@@ -0,0 +1,221 @@
+//! The DMA unit of the 2A03, which copies a page of CPU memory to the PPU's
+//! OAM (`OAMDMA`, `$4014`) and fetches the samples of the APU's DMC.
+//!
+//! The DMA unit halts the CPU through its `RDY` pin, which only stops the CPU
+//! on a read cycle, so the CPU might continue for a few write cycles after the
+//! DMA was requested. The first cycle on which the CPU is stopped is the halt
+//! cycle, whose read is repeated once the DMA is done.
+//!
+//! The CPU cycles alternate between get cycles, on which the DMA unit can
+//! read, and put cycles, on which it can write. Every byte is read on a get
+//! cycle and written to `OAMDATA` (`$2004`) on the following put cycle, so a
+//! DMA takes 513 cycles if the first cycle after the halt cycle is a get
+//! cycle, and 514 cycles with an additional alignment cycle otherwise. The
+//! CPU's read is repeated on all cycles on which the DMA unit doesn't use the
+//! bus.
+//!
+//! ```no-run
+//! Write $4014  Halt     (Align)  Get  Put  ...  Get  Put  Read
+//!           |  CPU read  CPU read  $xx00 $2004    $xxFF $2004  CPU read
+//! ```
+//!
+//! A sample fetch of the DMC needs a halt cycle and a dummy cycle before it
+//! reads on a get cycle, so it usually takes 4 cycles, or 3 if the CPU was
+//! writing when it was requested. During an OAM DMA, its cycles count as the
+//! halt and dummy cycles, and the sample fetch takes precedence over the read
+//! of the OAM DMA, which then needs another alignment cycle (2 cycles in
+//! total).
+//!
+//! ```no-run
+//! DMC request  Halt     Dummy     (Align)   Get        Read
+//!           |  CPU read  CPU read  CPU read  Sample     CPU read
+//! ```
+//!
+//! As the CPU's read is repeated, reads with side effects (like those of
+//! `PPUSTATUS` or `PPUDATA`) have them several times if a DMA hits them.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/DMA>
+//! - <https://www.nesdev.org/wiki/PPU_registers#OAMDMA>
+//! - <https://www.nesdev.org/wiki/APU_DMC#Memory_reader>
+
+use std::io::{self, Read, Write};
+
+/// The number of bytes copied by an OAM DMA.
+const OAM_SIZE: u16 = 256;
+
+/// The number of cycles a sample fetch waits after its request, i.e. the
+/// halt and the dummy cycle.
+const DMC_WAIT: u8 = 2;
+
+/// A bus access of the DMA unit, see [`Dma::access`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Read from the given address of the CPU bus, for the OAM DMA.
+    Read(u16),
+    /// Read the next byte of the DMC's sample, see
+    /// [`Apu::dmc_address`](chuck_apu::Apu::dmc_address).
+    Sample,
+    /// Write the given byte to `OAMDATA`.
+    Write(u8),
+}
+
+/// The state of an OAM DMA.
+#[derive(Debug, Clone, Copy)]
+struct Oam {
+    /// The page of the CPU memory that is copied.
+    page: u8,
+    /// The number of bytes copied so far.
+    copied: u16,
+    /// The byte read on the last get cycle, until it is written.
+    data: Option<u8>,
+}
+
+/// The DMA unit.
+#[derive(Debug, Clone, Default)]
+pub struct Dma {
+    /// The OAM DMA, if one is in progress.
+    oam: Option<Oam>,
+    /// The number of cycles the requested sample fetch still waits before it
+    /// can read, if the DMC requested one.
+    dmc: Option<u8>,
+    /// A flag denoting if the CPU is halted.
+    halted: bool,
+}
+
+impl Dma {
+    /// Start an OAM DMA from the given page, i.e. write to `OAMDMA`.
+    pub fn start_oam(&mut self, page: u8) {
+        self.oam = Some(Oam {
+            page,
+            copied: 0,
+            data: None,
+        });
+    }
+
+    /// Follow the DMA request of the DMC, see
+    /// [`Pins::DMA`](chuck_apu::Pins::DMA), which requests a sample fetch, or
+    /// aborts the pending one if the DMC was disabled in the meantime.
+    pub fn request_dmc(&mut self, request: bool) {
+        match (request, self.dmc) {
+            (true, None) => self.dmc = Some(DMC_WAIT),
+            (false, Some(_)) => self.dmc = None,
+            _ => {}
+        }
+    }
+
+    /// Check if the CPU is halted, i.e. if the DMA unit pulls the CPU's `RDY`
+    /// pin and owns the bus during the following cycles, until the CPU has
+    /// repeated its read after the DMA.
+    pub const fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Notify the DMA unit about the access of a cycle in which the CPU owns
+    /// the bus, on which the CPU is halted if a DMA is pending and it is
+    /// reading.
+    pub fn halt(&mut self, write: bool) {
+        self.halted = (self.oam.is_some() || self.dmc.is_some()) && !write;
+
+        if self.halted {
+            self.wait_dmc();
+        }
+    }
+
+    /// Return the bus access of the DMA unit during a cycle in which the CPU
+    /// is halted, with the given kind of cycle.
+    ///
+    /// Returns `None` if the DMA unit doesn't use the bus, so the CPU repeats
+    /// its read. If the DMA is complete, the CPU is released after this read.
+    pub fn access(&mut self, get: bool) -> Option<Access> {
+        if get && self.dmc == Some(0) {
+            self.dmc = None;
+            return Some(Access::Sample);
+        }
+        self.wait_dmc();
+
+        let Some(oam) = &mut self.oam else {
+            self.halted = self.dmc.is_some();
+            return None;
+        };
+
+        match (get, oam.data) {
+            (true, None) => Some(Access::Read(u16::from(oam.page) << 8 | oam.copied)),
+            (false, Some(data)) => {
+                oam.data = None;
+                oam.copied += 1;
+
+                if oam.copied == OAM_SIZE {
+                    self.oam = None;
+                }
+
+                Some(Access::Write(data))
+            }
+            _ => None,
+        }
+    }
+
+    /// Latch the byte read by the last [`Access::Read`].
+    pub fn fill(&mut self, data: u8) {
+        if let Some(oam) = &mut self.oam {
+            oam.data = Some(data);
+        }
+    }
+
+    /// Save the state of the DMA unit.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let oam = self.oam.unwrap_or(Oam {
+            page: 0,
+            copied: 0,
+            data: None,
+        });
+        let [copied_low, copied_high] = oam.copied.to_le_bytes();
+
+        writer.write_all(&[
+            u8::from(self.oam.is_some()),
+            oam.page,
+            copied_low,
+            copied_high,
+            u8::from(oam.data.is_some()),
+            oam.data.unwrap_or(0),
+            u8::from(self.dmc.is_some()),
+            self.dmc.unwrap_or(0),
+            u8::from(self.halted),
+        ])
+    }
+
+    /// Load the state of the DMA unit saved by [`Dma::save`].
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 9];
+        reader.read_exact(&mut state)?;
+        let [oam, page, copied_low, copied_high, some_data, data, dmc, wait, halted] = state;
+
+        let copied = u16::from_le_bytes([copied_low, copied_high]);
+        if copied >= OAM_SIZE || wait > DMC_WAIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid dma state",
+            ));
+        }
+
+        *self = Self {
+            oam: (oam != 0).then_some(Oam {
+                page,
+                copied,
+                data: (some_data != 0).then_some(data),
+            }),
+            dmc: (dmc != 0).then_some(wait),
+            halted: halted != 0,
+        };
+        Ok(())
+    }
+
+    /// Count a cycle of the CPU being halted towards the wait of the sample
+    /// fetch.
+    fn wait_dmc(&mut self) {
+        if let Some(wait) = &mut self.dmc {
+            *wait = wait.saturating_sub(1);
+        }
+    }
+}
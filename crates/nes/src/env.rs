@@ -0,0 +1,211 @@
+//! A headless environment for agents playing a game, in the style of the
+//! environments of reinforcement learning (like Gym's).
+//!
+//! An [`Env`] runs an episode of the game one step at a time: every step
+//! holds the buttons chosen by the agent for a frame (or a few, see
+//! [`Env::set_frames_per_step`]) and returns what the agent observes, i.e.
+//! the picture and the values of some addresses of the CPU bus, e.g. the
+//! score or the lives kept in the RAM of the game.
+//!
+//! Every episode restarts from the same save state, so it's exactly as
+//! deterministic as the console: the same buttons lead to the same
+//! observations. The start is either the power-up, e.g. with the seeded
+//! contents of the RAM of [`Env::reset_with_seed`], or any later point of the
+//! game, see [`Env::mark_start`]:
+//!
+//! ```
+//! # use chuck_input::ButtonState;
+//! # use chuck_nes::env::Env;
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::{Nes, HEIGHT, WIDTH};
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! # let nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! let mut env = Env::new(nes);
+//! env.set_watched(&[0x0075, 0x07fa]);
+//! assert_eq!(env.reset()?.ram.len(), 2);
+//!
+//! let (observation, frames) = env.step([ButtonState::RIGHT, ButtonState::empty()]);
+//! assert_eq!(observation.pixels.len(), WIDTH * HEIGHT);
+//! assert_eq!(frames, 1);
+//! let ram = observation.ram.to_vec();
+//!
+//! // Another episode with the same buttons observes the same.
+//! env.reset()?;
+//! let (observation, _) = env.step([ButtonState::RIGHT, ButtonState::empty()]);
+//! assert_eq!(observation.ram, ram);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::num::NonZeroU32;
+
+use chuck_input::ButtonState;
+
+use crate::determinism::{DeterminismConfig, RamInit};
+use crate::movie::Input;
+use crate::Nes;
+
+/// What an agent observes of the console after a step.
+#[derive(Debug, Clone, Copy)]
+pub struct Observation<'a> {
+    /// The colors of the picture, row by row, see
+    /// [`Frame::pixels`](crate::Frame::pixels).
+    pub pixels: &'a [u16],
+    /// The values of the watched addresses, in the order of
+    /// [`Env::watched`].
+    pub ram: &'a [u8],
+}
+
+/// An environment running episodes of a game, see the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Env {
+    /// The console.
+    nes: Nes,
+    /// The save state every episode starts from.
+    start: Vec<u8>,
+    /// The watched addresses of the CPU bus.
+    watched: Vec<u16>,
+    /// The values of the watched addresses, as of the last observation.
+    values: Vec<u8>,
+    /// The number of frames every step runs.
+    frames_per_step: NonZeroU32,
+    /// The number of frames since the start of the episode.
+    frames: u64,
+}
+
+impl Env {
+    /// Create an environment of the given console, whose episodes start from
+    /// its current state.
+    #[must_use]
+    pub fn new(nes: Nes) -> Self {
+        Self {
+            start: nes.state(),
+            nes,
+            watched: Vec::new(),
+            values: Vec::new(),
+            frames_per_step: NonZeroU32::MIN,
+            frames: 0,
+        }
+    }
+
+    /// Return the console, e.g. to inspect it beyond the observations.
+    #[must_use]
+    pub const fn nes(&self) -> &Nes {
+        &self.nes
+    }
+
+    /// Return the console mutably, e.g. to play it up to the point where the
+    /// episodes should start, see [`Env::mark_start`].
+    pub fn nes_mut(&mut self) -> &mut Nes {
+        &mut self.nes
+    }
+
+    /// Return the watched addresses of the CPU bus.
+    #[must_use]
+    pub fn watched(&self) -> &[u16] {
+        &self.watched
+    }
+
+    /// Set the watched addresses of the CPU bus, whose values are observed
+    /// after every step, see [`Nes::peek`].
+    pub fn set_watched(&mut self, addrs: &[u16]) {
+        self.watched = addrs.to_vec();
+    }
+
+    /// Return the number of frames every step runs.
+    #[must_use]
+    pub const fn frames_per_step(&self) -> NonZeroU32 {
+        self.frames_per_step
+    }
+
+    /// Set the number of frames every step runs with the same buttons held,
+    /// of which only the last one is observed (the others run headless, see
+    /// [`Nes::run_frame_headless`]).
+    pub fn set_frames_per_step(&mut self, frames: NonZeroU32) {
+        self.frames_per_step = frames;
+    }
+
+    /// Return the number of frames since the start of the episode.
+    #[must_use]
+    pub const fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Start the episodes from the current state of the console, which
+    /// starts a new episode.
+    pub fn mark_start(&mut self) {
+        self.start = self.nes.state();
+        self.frames = 0;
+    }
+
+    /// Start a new episode, by restoring the console to the start of the
+    /// episodes, and return the first observation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`], leaving the
+    /// console untouched, if the console no longer matches the save state of
+    /// the start, e.g. after the EPSM was plugged in through
+    /// [`Env::nes_mut`] (see [`Nes::load_state`]).
+    pub fn reset(&mut self) -> io::Result<Observation<'_>> {
+        self.nes.load_state(&mut self.start.as_slice())?;
+        self.frames = 0;
+        Ok(self.observe())
+    }
+
+    /// Start the episodes from the power-up of the console, with the contents
+    /// of its RAM generated from the given seed (see [`RamInit::Random`]),
+    /// and return the first observation.
+    ///
+    /// The other parts of the power-up state are kept, see
+    /// [`Nes::determinism`].
+    pub fn reset_with_seed(&mut self, seed: u64) -> Observation<'_> {
+        self.nes.set_determinism(DeterminismConfig {
+            ram: RamInit::Random(seed),
+            ..self.nes.determinism()
+        });
+        self.nes.power_cycle();
+        self.mark_start();
+        self.observe()
+    }
+
+    /// Hold the given buttons of the two controllers for a step, and return
+    /// the observation after it and the number of frames since the start of
+    /// the episode.
+    ///
+    /// The buttons of a port without a controller are ignored, see
+    /// [`Input::apply`].
+    pub fn step(&mut self, buttons: [ButtonState; 2]) -> (Observation<'_>, u64) {
+        let [first, second] = buttons;
+        let input = Input {
+            buttons: [first, second, ButtonState::empty(), ButtonState::empty()],
+            ..Input::default()
+        };
+        input.apply(&mut self.nes);
+
+        for _ in 1..self.frames_per_step.get() {
+            self.nes.run_frame_headless();
+        }
+        self.nes.run_frame();
+
+        self.frames += u64::from(self.frames_per_step.get());
+        let frames = self.frames;
+        (self.observe(), frames)
+    }
+
+    /// Return the observation of the console.
+    fn observe(&mut self) -> Observation<'_> {
+        let nes = &self.nes;
+        self.values.clear();
+        self.values
+            .extend(self.watched.iter().map(|&addr| nes.peek(addr)));
+
+        Observation {
+            pixels: self.nes.ppu().frame_buffer(),
+            ram: &self.values,
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! The Expansion Port Sound Module (EPSM), a homebrew sound module.
+//!
+//! The EPSM is plugged into the expansion port of the console, and its
+//! YMF288 adds six FM channels and the three square wave channels of an SSG
+//! compatible with the YM2149.
+//!
+//! The chip has two ports of 256 registers each, which are written by first
+//! writing the number of the register and then its value:
+//!
+//! ```no-run
+//! $401C  Address of the first port   The SSG ($00-$0D) and the channels 1-3
+//! $401D  Data of the first port
+//! $401E  Address of the second port  The channels 4-6
+//! $401F  Data of the second port
+//! ```
+//!
+//! The registers of the SSG are the ones of the YM2149, and only exist in the
+//! first port:
+//!
+//! ```no-run
+//! $00-$05  Tone periods     The 12-bit periods of the channels A, B and C
+//! $06      Noise period     ---PPPPP
+//! $07      Mixer            --NNNTTT  Disable the noise and tone per channel
+//! $08-$0A  Volumes          ---EVVVV  The envelope or the fixed volume
+//! $0B-$0C  Envelope period  The 16-bit period of the envelope
+//! $0D      Envelope shape   ----CAAH  Continue, attack, alternate, hold
+//! ```
+//!
+//! The registers of the FM channels are the same in both ports, for the
+//! channels 1-3 and 4-6, except for the key on. The registers of the four
+//! operators of a channel are selected by the lowest two bits (the channel
+//! within the port) and the next two bits (the operator, in the order 1, 3,
+//! 2, 4):
+//!
+//! ```no-run
+//! $28      Key on           OOOO-PCC  Operators 4-1, port and channel
+//! $30-$3F  Detune/multiple  -DDDMMMM
+//! $40-$4F  Total level      -TTTTTTT
+//! $50-$5F  Key scale/attack KK-AAAAA
+//! $60-$6F  Decay rate       ---DDDDD
+//! $70-$7F  Sustain rate     ---SSSSS
+//! $80-$8F  Sustain/release  LLLLRRRR  The sustain level and release rate
+//! $A0-$A2  Frequency        The low 8 bits of the F-number
+//! $A4-$A6  Block/frequency  --BBBFFF  Latched until $A0-$A2 are written
+//! $B0-$B2  Feedback/algorithm --FFFAAA
+//! $B4-$B6  Output           LR------  The left and right outputs
+//! ```
+//!
+//! The chip runs on its own 8 MHz clock, so it's clocked for the time of its
+//! clock passed in every CPU cycle. Its outputs join the expansion audio of
+//! the cartridge, see [`Nes::plug_epsm`], as the [`Epsm::CHANNELS`] following
+//! the ones of the cartridge in the [`mixer`](crate::mixer).
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::Nes;
+//! // A program that plays a tone on the first channel of the SSG, at full
+//! // volume with only the tone enabled.
+//! let mut prg = vec![0; 0x4000];
+//! let mut code = Vec::new();
+//! for (reg, data) in [(0x00, 0xfe), (0x07, 0x3e), (0x08, 0x0f)] {
+//!     code.extend([0xa9, reg, 0x8d, 0x1c, 0x40, 0xa9, data, 0x8d, 0x1d, 0x40]);
+//! }
+//! code.extend([0x4c, 0x1e, 0x80]);
+//! prg[..code.len()].copy_from_slice(&code);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.plug_epsm(true);
+//! nes.capture_channels(true);
+//! nes.log_audio(true);
+//! let frame = nes.run_frame();
+//! assert!(frame.channels.iter().any(|channels| channels.expansion > 0.0));
+//! assert!(frame.channels.iter().any(|channels| channels.expansion == 0.0));
+//!
+//! // The writes to the EPSM are in the audio log too.
+//! let writes = nes.audio_log().unwrap().writes();
+//! assert_eq!((writes[0].addr, writes[0].data), (0x401c, 0x00));
+//! ```
+//!
+//! The left and right outputs of an FM channel are mixed into its mono
+//! output. The rhythm sounds of the YMF288, which are played from its
+//! internal ROM, the LFO, the SSG-type envelopes, the special mode of the
+//! third channel and the timers aren't emulated.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Expansion_Port_Sound_Module>
+//!
+//! [`Nes::plug_epsm`]: crate::Nes::plug_epsm
+
+mod fm;
+mod ssg;
+
+use std::io::{self, Read, Write};
+
+use crate::Region;
+use fm::Fm;
+use ssg::Ssg;
+
+/// The frequency of the clock of the EPSM, in Hz.
+const CLOCK: u64 = 8_000_000;
+
+/// The level of an FM channel at its largest output and of an SSG channel at
+/// full volume, about as loud as a pulse channel of the APU at full volume.
+const LEVEL: f32 = 0.15;
+
+/// The number of cycles of the clock of the EPSM per tick of its counters.
+const TICK: u64 = 16;
+
+/// The number of ticks per clock of the SSG, see [`Ssg::clock`].
+const SSG_TICKS: u8 = 2;
+
+/// The number of ticks per sample of the FM channels, see [`Fm::clock`].
+const FM_TICKS: u8 = 9;
+
+/// The EPSM, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Epsm {
+    /// The registers of both ports.
+    registers: Box<[u8; 0x200]>,
+    /// The selected registers of both ports.
+    address: [u8; 2],
+    /// The FM channels.
+    fm: Fm,
+    /// The SSG.
+    ssg: Ssg,
+    /// The length of a CPU cycle, in units of time in which both it and a
+    /// tick of the counters are whole numbers.
+    cycle: u64,
+    /// The length of a tick of the counters, in the units of the `cycle`.
+    tick: u64,
+    /// The time since the last tick, in the units of the `cycle`.
+    phase: u64,
+    /// The number of ticks, modulo the ticks of both the SSG and the FM
+    /// channels.
+    ticks: u8,
+}
+
+impl Epsm {
+    /// The names of the audio channels, see [`Epsm::channel_audio`].
+    pub const CHANNELS: [&str; 9] = [
+        "FM 1", "FM 2", "FM 3", "FM 4", "FM 5", "FM 6", "SSG A", "SSG B", "SSG C",
+    ];
+
+    /// Create an EPSM plugged into a console of the given region, which is
+    /// powered on.
+    #[must_use]
+    pub fn new(region: Region) -> Self {
+        // The master clock of the console as a fraction, per CPU cycle.
+        let (numerator, denominator) = region.master_clock_fraction();
+
+        let mut registers = Box::new([0; 0x200]);
+        // Both outputs of the FM channels are enabled at power-on.
+        for port in [0, 0x100] {
+            registers[port + 0xb4..port + 0xb7].fill(0xc0);
+        }
+
+        Self {
+            registers,
+            address: [0; 2],
+            fm: Fm::default(),
+            ssg: Ssg::default(),
+            cycle: CLOCK * u64::from(region.cpu_divider()) * denominator,
+            tick: TICK * numerator,
+            phase: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Return the value of a register of a port, `0` or `1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the port is neither `0` nor `1`.
+    #[must_use]
+    pub fn register(&self, port: usize, reg: u8) -> u8 {
+        assert!(port < 2, "invalid port");
+        self.registers[port * 0x100 + usize::from(reg)]
+    }
+
+    /// Write one of the registers at `$401C`-`$401F`.
+    pub(crate) fn write(&mut self, addr: u16, data: u8) {
+        let port = usize::from(addr & 0x02 != 0);
+        if addr & 0x01 == 0 {
+            self.address[port] = data;
+            return;
+        }
+
+        let reg = self.address[port];
+        self.registers[port * 0x100 + usize::from(reg)] = data;
+        if port == 0 && reg < 0x10 {
+            self.ssg.write(&self.registers[..0x10], reg);
+        } else {
+            self.fm.write(&*self.registers, port, reg);
+        }
+    }
+
+    /// Execute a single CPU cycle, clocking the chip for the cycles of its
+    /// own clock passed.
+    pub(crate) fn clock(&mut self) {
+        self.phase += self.cycle;
+        while self.phase >= self.tick {
+            self.phase -= self.tick;
+            self.ticks = (self.ticks + 1) % (SSG_TICKS * FM_TICKS);
+
+            if self.ticks.is_multiple_of(SSG_TICKS) {
+                self.ssg.clock(&self.registers[..0x10]);
+            }
+            if self.ticks.is_multiple_of(FM_TICKS) {
+                self.fm.clock(&*self.registers);
+            }
+        }
+    }
+
+    /// Return the output of all channels, in the units of
+    /// [`Mapper::audio`](crate::mapper::Mapper::audio).
+    #[must_use]
+    pub fn output(&self) -> f32 {
+        (0..Self::CHANNELS.len())
+            .map(|channel| self.channel_audio(channel))
+            .sum()
+    }
+
+    /// Return the output of one of the [`Epsm::CHANNELS`] like
+    /// [`Epsm::output`], or `0.0` if there's no such channel.
+    #[must_use]
+    pub fn channel_audio(&self, channel: usize) -> f32 {
+        match channel {
+            0..6 => self.fm.output(&*self.registers, channel) * LEVEL,
+            6..9 => self.ssg.output(&self.registers[..0x10], channel - 6) * LEVEL,
+            _ => 0.0,
+        }
+    }
+
+    /// Save the state of the chip.
+    pub(crate) fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&*self.registers)?;
+        writer.write_all(&self.address)?;
+        writer.write_all(&self.phase.to_le_bytes())?;
+        writer.write_all(&[self.ticks])?;
+        self.fm.save(writer)?;
+        self.ssg.save(writer)
+    }
+
+    /// Load the state of the chip saved by [`Epsm::save`].
+    pub(crate) fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut registers = Box::new([0; 0x200]);
+        reader.read_exact(&mut *registers)?;
+        let mut state = [0; 11];
+        reader.read_exact(&mut state)?;
+        let [a0, a1, phase @ .., ticks] = state;
+
+        let phase = u64::from_le_bytes(phase);
+        if phase >= self.tick || ticks >= SSG_TICKS * FM_TICKS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid epsm clock",
+            ));
+        }
+
+        let mut fm = Fm::default();
+        fm.load(reader)?;
+        let mut ssg = Ssg::default();
+        ssg.load(reader)?;
+
+        self.registers = registers;
+        self.address = [a0, a1];
+        self.phase = phase;
+        self.ticks = ticks;
+        self.fm = fm;
+        self.ssg = ssg;
+        Ok(())
+    }
+}
@@ -0,0 +1,501 @@
+//! The FM synthesis of the YMF288: six channels of four operators each, a
+//! sine oscillator whose phase is modulated by the other operators and whose
+//! level follows an envelope.
+//!
+//! The parameters of the channels and their operators are kept in the
+//! registers, see the [module documentation](super) of the EPSM, while the
+//! state of their oscillators and envelopes is kept here.
+//!
+//! The LFO, the SSG-type envelopes, the special mode of the third channel and
+//! the timers aren't emulated.
+
+use std::f32::consts::TAU;
+use std::io::{self, Read, Write};
+
+/// The slots of the registers of the operators 1 to 4.
+const SLOTS: [usize; 4] = [0, 2, 1, 3];
+
+/// The operators modulating the operators 2 to 4 per algorithm, where bit
+/// `n` is the operator `n + 1`, and the carriers (the operators heard) in the
+/// last element.
+const ALGORITHMS: [[u8; 4]; 8] = [
+    [0b0001, 0b0010, 0b0100, 0b1000],
+    [0b0000, 0b0011, 0b0100, 0b1000],
+    [0b0000, 0b0010, 0b0101, 0b1000],
+    [0b0001, 0b0000, 0b0110, 0b1000],
+    [0b0001, 0b0000, 0b0100, 0b1010],
+    [0b0001, 0b0001, 0b0001, 0b1110],
+    [0b0001, 0b0000, 0b0000, 0b1110],
+    [0b0000, 0b0000, 0b0000, 0b1111],
+];
+
+/// The detune of the phase increment per key code and detune.
+const DETUNE: [[u8; 4]; 32] = [
+    [0, 0, 1, 2],
+    [0, 0, 1, 2],
+    [0, 0, 1, 2],
+    [0, 0, 1, 2],
+    [0, 1, 2, 2],
+    [0, 1, 2, 3],
+    [0, 1, 2, 3],
+    [0, 1, 2, 3],
+    [0, 1, 2, 4],
+    [0, 1, 3, 4],
+    [0, 1, 3, 4],
+    [0, 1, 3, 5],
+    [0, 2, 4, 5],
+    [0, 2, 4, 6],
+    [0, 2, 4, 6],
+    [0, 2, 5, 7],
+    [0, 2, 5, 8],
+    [0, 3, 6, 8],
+    [0, 3, 6, 9],
+    [0, 3, 7, 10],
+    [0, 4, 8, 11],
+    [0, 4, 8, 12],
+    [0, 4, 9, 13],
+    [0, 5, 10, 14],
+    [0, 5, 11, 16],
+    [0, 6, 12, 17],
+    [0, 6, 13, 19],
+    [0, 7, 14, 20],
+    [0, 8, 16, 22],
+    [0, 8, 16, 22],
+    [0, 8, 16, 22],
+    [0, 8, 16, 22],
+];
+
+/// The increments of the envelopes of the rates below 48 per rate modulo 4,
+/// for the steps of the envelope counter at which they change.
+const INCREMENTS: [[u8; 8]; 4] = [
+    [0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 1, 0, 1, 1, 1, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 1],
+];
+
+/// The doubled increments of the envelopes of the rates from 48 to 59 per
+/// rate modulo 4, for the steps of the envelope counter.
+const FAST_INCREMENTS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 1, 0, 0, 0, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1],
+];
+
+/// The largest attenuation of an envelope, which is silent.
+const SILENT: u16 = 0x3ff;
+
+/// The largest output of an operator or a channel.
+const MAX_OUTPUT: f32 = 8191.0;
+
+/// The stage of an envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Rising to the full level after the key on.
+    Attack,
+    /// Falling to the sustain level.
+    Decay,
+    /// Falling further while the key is held.
+    Sustain,
+    /// Falling to silence after the key off.
+    Release,
+}
+
+impl Stage {
+    /// The stages, in the order of their numbers in a save state.
+    const ALL: [Self; 4] = [Self::Attack, Self::Decay, Self::Sustain, Self::Release];
+
+    /// Return the number of the stage in a save state.
+    fn number(self) -> u8 {
+        let index = Self::ALL.iter().position(|&stage| stage == self);
+        index
+            .and_then(|index| u8::try_from(index).ok())
+            .unwrap_or(0)
+    }
+
+    /// Return the stage of the given number in a save state.
+    fn from_number(number: u8) -> io::Result<Self> {
+        Self::ALL
+            .get(usize::from(number))
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid fm envelope stage"))
+    }
+}
+
+/// An operator, whose parameters are kept in the registers.
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    /// The 20-bit phase of the oscillator.
+    phase: u32,
+    /// The 10-bit attenuation of the envelope, where `0` is the full level.
+    attenuation: u16,
+    /// The stage of the envelope.
+    stage: Stage,
+    /// A flag denoting if the key is on.
+    on: bool,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Self {
+            phase: 0,
+            attenuation: SILENT,
+            stage: Stage::Release,
+            on: false,
+        }
+    }
+}
+
+impl Operator {
+    /// Clock the envelope with the given registers of the operator (see
+    /// [`operator_registers`]), the key code of its channel and the envelope
+    /// counter.
+    fn clock_envelope(&mut self, regs: [u8; 5], keycode: u8, counter: u32) {
+        let [_, attack, decay, sustain, release] = regs;
+        // The highest sustain level is the lowest level of the envelope.
+        let sustain_level = match release >> 4 {
+            15 => 31 << 5,
+            level => u16::from(level) << 5,
+        };
+
+        if self.stage == Stage::Attack && self.attenuation == 0 {
+            self.stage = Stage::Decay;
+        }
+        if self.stage == Stage::Decay && self.attenuation >= sustain_level {
+            self.stage = Stage::Sustain;
+        }
+
+        let key_scale = attack >> 6;
+        let rate = match self.stage {
+            Stage::Attack => rate(attack & 0x1f, keycode, key_scale),
+            Stage::Decay => rate(decay & 0x1f, keycode, key_scale),
+            Stage::Sustain => rate(sustain & 0x1f, keycode, key_scale),
+            Stage::Release => rate(((release & 0x0f) << 1) | 1, keycode, key_scale),
+        };
+        let increment = increment(rate, counter);
+
+        if self.stage == Stage::Attack {
+            if rate >= 62 {
+                self.attenuation = 0;
+            } else if increment > 0 {
+                let attenuation = i32::from(self.attenuation);
+                let next = attenuation + ((!attenuation * i32::from(increment)) >> 4);
+                self.attenuation = u16::try_from(next.max(0)).unwrap_or_default();
+            }
+        } else {
+            self.attenuation = (self.attenuation + increment).min(SILENT);
+        }
+    }
+
+    /// Return the output of the operator, given the modulation of its phase,
+    /// in units of the 10 bits of the phase, and its total level.
+    fn output(self, modulation: f32, total_level: u8) -> f32 {
+        let attenuation = self.attenuation + (u16::from(total_level & 0x7f) << 3);
+        if attenuation >= SILENT {
+            return 0.0;
+        }
+
+        let phase = f32::from(u16::try_from(self.phase >> 10).unwrap_or_default()) + modulation;
+        // The attenuation is in steps of 3/32 dB, so 64 steps halve the level.
+        let gain = (-f32::from(attenuation) / 64.0).exp2();
+        (phase / 1024.0 * TAU).sin() * gain * MAX_OUTPUT
+    }
+}
+
+/// Return the rate of an envelope, `0`-`63`, given its rate in the
+/// registers, the key code of the channel and the key scale.
+fn rate(rate: u8, keycode: u8, key_scale: u8) -> u8 {
+    if rate == 0 {
+        0
+    } else {
+        (2 * rate + (keycode >> (3 - key_scale))).min(63)
+    }
+}
+
+/// Return the increment of the attenuation of an envelope at the given rate
+/// and step of the envelope counter.
+fn increment(rate: u8, counter: u32) -> u16 {
+    let step = |counter: u32| usize::try_from(counter & 7).unwrap_or_default();
+    match rate {
+        0..=47 => {
+            let shift = 11 - u32::from(rate / 4);
+            if counter & ((1 << shift) - 1) == 0 {
+                u16::from(INCREMENTS[usize::from(rate % 4)][step(counter >> shift)])
+            } else {
+                0
+            }
+        }
+        48..=59 => {
+            let doubled = FAST_INCREMENTS[usize::from(rate % 4)][step(counter)];
+            (1 + u16::from(doubled)) << (rate / 4 - 12)
+        }
+        _ => 8,
+    }
+}
+
+/// An FM channel.
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    /// The 11-bit F-number of the frequency.
+    fnum: u16,
+    /// The 3-bit block (octave) of the frequency.
+    block: u8,
+    /// The operators, in the order of their registers.
+    operators: [Operator; 4],
+    /// The last two outputs of the operator 1, which modulate its phase.
+    feedback: [f32; 2],
+    /// The output of the last sample.
+    output: f32,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            fnum: 0,
+            block: 0,
+            operators: [Operator::default(); 4],
+            feedback: [0.0; 2],
+            output: 0.0,
+        }
+    }
+}
+
+impl Channel {
+    /// Return the 5-bit key code of the frequency, for the detune and the
+    /// key scale.
+    fn keycode(&self) -> u8 {
+        let high = self.fnum & 0x400 != 0;
+        let low = (self.fnum >> 7) & 0x07;
+        let half = if high { low != 0 } else { low == 0x07 };
+        (self.block << 2) | (u8::from(high) << 1) | u8::from(half)
+    }
+
+    /// Return the phase increment of an operator with the given detune and
+    /// multiple.
+    fn increment(&self, detune_multiple: u8) -> u32 {
+        let base = (u32::from(self.fnum) << self.block) >> 1;
+        let detune = DETUNE[usize::from(self.keycode())][usize::from((detune_multiple >> 4) & 3)];
+        let base = if detune_multiple & 0x40 == 0 {
+            base + u32::from(detune)
+        } else {
+            base.wrapping_sub(u32::from(detune))
+        } & 0x1_ffff;
+
+        match detune_multiple & 0x0f {
+            0 => base >> 1,
+            multiple => base * u32::from(multiple),
+        }
+    }
+
+    /// Produce a sample with the registers of the operators (see
+    /// [`operator_registers`]), their total levels and the feedback and
+    /// algorithm.
+    fn sample(&mut self, regs: &[[u8; 5]; 4], total_levels: [u8; 4], control: u8) {
+        let feedback = (control >> 3) & 0x07;
+        let [modulators @ .., carriers] = ALGORITHMS[usize::from(control & 0x07)];
+        let modulation = if feedback == 0 {
+            0.0
+        } else {
+            (self.feedback[0] + self.feedback[1]) / f32::from(1_u16 << (10 - feedback))
+        };
+
+        let mut outputs = [0.0; 4];
+        for op in 0..4 {
+            let modulation = if op == 0 {
+                modulation
+            } else {
+                let mask = modulators[op - 1];
+                let sum: f32 = (0..op)
+                    .filter(|&other| mask & (1 << other) != 0)
+                    .map(|other| outputs[other])
+                    .sum();
+                sum / 2.0
+            };
+            outputs[op] = self.operators[SLOTS[op]].output(modulation, total_levels[SLOTS[op]]);
+        }
+        self.feedback = [self.feedback[1], outputs[0]];
+
+        let output: f32 = (0..4)
+            .filter(|&op| carriers & (1 << op) != 0)
+            .map(|op| outputs[op])
+            .sum();
+        self.output = output.clamp(-MAX_OUTPUT - 1.0, MAX_OUTPUT);
+
+        let increments = regs.map(|regs| self.increment(regs[0]));
+        for (operator, increment) in self.operators.iter_mut().zip(increments) {
+            operator.phase = (operator.phase + increment) & 0xf_ffff;
+        }
+    }
+}
+
+/// Return the index of the register of the given channel, `0`-`5`, in the
+/// registers of both ports.
+const fn register(channel: usize, reg: usize) -> usize {
+    (channel / 3) * 0x100 + reg + channel % 3
+}
+
+/// Return the registers of the operators of a channel, in the order of their
+/// registers: the detune and multiple, the key scale and attack rate, the
+/// decay rate, the sustain rate, and the sustain level and release rate.
+fn operator_registers(registers: &[u8], channel: usize) -> [[u8; 5]; 4] {
+    [0, 1, 2, 3].map(|slot| {
+        [0x30, 0x50, 0x60, 0x70, 0x80].map(|reg| registers[register(channel, reg + 4 * slot)])
+    })
+}
+
+/// The FM synthesis, whose registers are kept by the [`Epsm`](super::Epsm).
+#[derive(Debug, Clone, Default)]
+pub struct Fm {
+    /// The channels.
+    channels: [Channel; 6],
+    /// The counter of the envelopes, which are clocked every third sample.
+    counter: u32,
+    /// The number of samples since the envelopes were clocked.
+    divider: u8,
+}
+
+impl Fm {
+    /// Take a write to one of the given registers of both ports, after it
+    /// was written, i.e. a key on or a change of the frequency.
+    pub fn write(&mut self, registers: &[u8], port: usize, reg: u8) {
+        match (port, reg) {
+            (0, 0x28) => self.key(registers, registers[0x28]),
+            (_, 0xa0..=0xa2) => {
+                let index = port * 3 + usize::from(reg - 0xa0);
+                let [low, high] = [0xa0, 0xa4].map(|reg| registers[register(index, reg)]);
+                let channel = &mut self.channels[index];
+                channel.fnum = u16::from_le_bytes([low, high & 0x07]);
+                channel.block = (high >> 3) & 0x07;
+            }
+            _ => {}
+        }
+    }
+
+    /// Switch the keys of the operators of a channel on or off.
+    fn key(&mut self, registers: &[u8], data: u8) {
+        let channel = match data & 0x07 {
+            0x03 | 0x07 => return,
+            select => usize::from((select >> 2) * 3 + (select & 0x03)),
+        };
+        let regs = operator_registers(registers, channel);
+
+        let channel = &mut self.channels[channel];
+        let keycode = channel.keycode();
+        for (op, &slot) in SLOTS.iter().enumerate() {
+            let operator = &mut channel.operators[slot];
+            let on = data & (0x10 << op) != 0;
+            if on && !operator.on {
+                operator.stage = Stage::Attack;
+                operator.phase = 0;
+                let attack = regs[slot][1];
+                if rate(attack & 0x1f, keycode, attack >> 6) >= 62 {
+                    operator.attenuation = 0;
+                }
+            } else if !on && operator.on {
+                operator.stage = Stage::Release;
+            }
+            operator.on = on;
+        }
+    }
+
+    /// Produce a sample of all channels with the given registers of both
+    /// ports, at a 144th of the master clock.
+    pub fn clock(&mut self, registers: &[u8]) {
+        self.divider += 1;
+        let envelopes = self.divider == 3;
+        if envelopes {
+            self.divider = 0;
+            self.counter = self.counter.wrapping_add(1) & 0x0fff;
+        }
+
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let regs = operator_registers(registers, index);
+            if envelopes {
+                let keycode = channel.keycode();
+                for (operator, &regs) in channel.operators.iter_mut().zip(&regs) {
+                    operator.clock_envelope(regs, keycode, self.counter);
+                }
+            }
+
+            let total_levels = [0, 1, 2, 3].map(|slot| registers[register(index, 0x40 + 4 * slot)]);
+            channel.sample(&regs, total_levels, registers[register(index, 0xb0)]);
+        }
+    }
+
+    /// Return the output of a channel with the given registers of both
+    /// ports, from `-1.0` to `1.0`, which is the average of its left and
+    /// right outputs.
+    pub fn output(&self, registers: &[u8], channel: usize) -> f32 {
+        let control = registers[register(channel, 0xb4)];
+        let sides = u8::from(control & 0x80 != 0) + u8::from(control & 0x40 != 0);
+        self.channels[channel].output / (MAX_OUTPUT + 1.0) * f32::from(sides) / 2.0
+    }
+
+    /// Save the state of the channels.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.counter.to_le_bytes())?;
+        writer.write_all(&[self.divider])?;
+        for channel in &self.channels {
+            writer.write_all(&channel.fnum.to_le_bytes())?;
+            writer.write_all(&[channel.block])?;
+            for value in [channel.feedback[0], channel.feedback[1], channel.output] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            for operator in &channel.operators {
+                writer.write_all(&operator.phase.to_le_bytes())?;
+                writer.write_all(&operator.attenuation.to_le_bytes())?;
+                writer.write_all(&[operator.stage.number(), u8::from(operator.on)])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the state of the channels saved by [`Fm::save`].
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid fm state");
+
+        let mut state = [0; 5];
+        reader.read_exact(&mut state)?;
+        let [c0, c1, c2, c3, divider] = state;
+        let counter = u32::from_le_bytes([c0, c1, c2, c3]);
+        if counter > 0x0fff || divider > 2 {
+            return Err(invalid());
+        }
+
+        let mut channels = [Channel::default(); 6];
+        for channel in &mut channels {
+            let mut bytes = [0; 15];
+            reader.read_exact(&mut bytes)?;
+            let float =
+                |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+            let [feedback0, feedback1, output] = [3, 7, 11].map(float);
+            if ![feedback0, feedback1, output]
+                .iter()
+                .all(|value| value.abs() <= MAX_OUTPUT + 1.0)
+            {
+                return Err(invalid());
+            }
+            channel.fnum = u16::from_le_bytes([bytes[0], bytes[1]]) & 0x07ff;
+            channel.block = bytes[2] & 0x07;
+            channel.feedback = [feedback0, feedback1];
+            channel.output = output;
+
+            for operator in &mut channel.operators {
+                let mut bytes = [0; 8];
+                reader.read_exact(&mut bytes)?;
+                *operator = Operator {
+                    phase: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0xf_ffff,
+                    attenuation: u16::from_le_bytes([bytes[4], bytes[5]]).min(SILENT),
+                    stage: Stage::from_number(bytes[6])?,
+                    on: bytes[7] != 0,
+                };
+            }
+        }
+
+        self.counter = counter;
+        self.divider = divider;
+        self.channels = channels;
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! The SSG of the YMF288, which is compatible with the YM2149: three square
+//! wave channels, which share a noise generator and an envelope generator.
+//!
+//! The SSG is clocked at a quarter of the master clock, and its counters
+//! count at an eighth of that, i.e. at 250 kHz, see [`Ssg::clock`]. Its
+//! registers are the first 16 of the first port, see the [module
+//! documentation](super) of the EPSM.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Sunsoft_5B_audio>
+
+use std::io::{self, Read, Write};
+
+/// A tone generator, which toggles its output at the end of every period.
+#[derive(Debug, Clone, Copy, Default)]
+struct Tone {
+    /// The counter of the period, counting up.
+    counter: u16,
+    /// The level of the square wave.
+    high: bool,
+}
+
+/// The noise generator, a 17-bit linear feedback shift register which is
+/// shifted at half the rate of the tone generators.
+#[derive(Debug, Clone, Copy)]
+struct Noise {
+    /// The counter of the period, counting up.
+    counter: u8,
+    /// A flag denoting if the shift register is shifted with the next clock.
+    odd: bool,
+    /// The shift register, whose lowest bit is the output.
+    lfsr: u32,
+}
+
+/// The envelope generator, which steps through 32 levels per period.
+#[derive(Debug, Clone, Copy, Default)]
+struct Envelope {
+    /// The counter of the period, counting up.
+    counter: u16,
+    /// The step within the current cycle, `0`-`31`.
+    step: u8,
+    /// A flag denoting if the levels rise in the current cycle.
+    attack: bool,
+    /// A flag denoting if the envelope has ended and holds its level.
+    holding: bool,
+}
+
+impl Envelope {
+    /// Restart the envelope, for a write to the shape register.
+    const fn restart(&mut self, shape: u8) {
+        *self = Self {
+            counter: 0,
+            step: 0,
+            attack: shape & 0x04 != 0,
+            holding: false,
+        };
+    }
+
+    /// Clock the envelope with the given shape, at the end of its period.
+    const fn step(&mut self, shape: u8) {
+        if self.holding {
+            return;
+        }
+        if self.step < 31 {
+            self.step += 1;
+            return;
+        }
+
+        if shape & 0x08 == 0 {
+            // Without continuing, the envelope ends silent.
+            self.attack = false;
+            self.holding = true;
+        } else {
+            if shape & 0x02 != 0 {
+                self.attack = !self.attack;
+            }
+            if shape & 0x01 == 0 {
+                self.step = 0;
+            } else {
+                self.holding = true;
+            }
+        }
+    }
+
+    /// Return the level of the envelope, `0`-`31`.
+    const fn level(self) -> u8 {
+        if self.attack {
+            self.step
+        } else {
+            31 - self.step
+        }
+    }
+}
+
+/// The SSG, whose registers are kept by the [`Epsm`](super::Epsm).
+#[derive(Debug, Clone)]
+pub struct Ssg {
+    /// The tone generators of the channels A, B and C.
+    tones: [Tone; 3],
+    /// The noise generator.
+    noise: Noise,
+    /// The envelope generator.
+    envelope: Envelope,
+}
+
+impl Default for Ssg {
+    fn default() -> Self {
+        Self {
+            tones: [Tone::default(); 3],
+            noise: Noise {
+                counter: 0,
+                odd: false,
+                lfsr: 1,
+            },
+            envelope: Envelope::default(),
+        }
+    }
+}
+
+impl Ssg {
+    /// Take a write to one of the given registers, after it was written.
+    pub const fn write(&mut self, registers: &[u8], reg: u8) {
+        if reg == 0x0d {
+            self.envelope.restart(registers[0x0d]);
+        }
+    }
+
+    /// Clock the counters with the given registers, at 250 kHz.
+    pub fn clock(&mut self, registers: &[u8]) {
+        for (channel, tone) in self.tones.iter_mut().enumerate() {
+            let period = u16::from_le_bytes([registers[2 * channel], registers[2 * channel + 1]]);
+            tone.counter += 1;
+            if tone.counter >= (period & 0x0fff).max(1) {
+                tone.counter = 0;
+                tone.high = !tone.high;
+            }
+        }
+
+        let noise = &mut self.noise;
+        noise.odd = !noise.odd;
+        if noise.odd {
+            noise.counter += 1;
+            if noise.counter >= (registers[0x06] & 0x1f).max(1) {
+                noise.counter = 0;
+                let bit = (noise.lfsr ^ (noise.lfsr >> 3)) & 1;
+                noise.lfsr = (noise.lfsr >> 1) | (bit << 16);
+            }
+        }
+
+        let envelope = &mut self.envelope;
+        envelope.counter = envelope.counter.wrapping_add(1);
+        if envelope.counter >= u16::from_le_bytes([registers[0x0b], registers[0x0c]]).max(1) {
+            envelope.counter = 0;
+            envelope.step(registers[0x0d]);
+        }
+    }
+
+    /// Return the output of a channel with the given registers, from `0.0`
+    /// to `1.0`.
+    pub fn output(&self, registers: &[u8], channel: usize) -> f32 {
+        let mixer = registers[0x07];
+        let tone = self.tones[channel].high || mixer & (0x01 << channel) != 0;
+        let noise = self.noise.lfsr & 1 != 0 || mixer & (0x08 << channel) != 0;
+        if !tone || !noise {
+            return 0.0;
+        }
+
+        let volume = registers[0x08 + channel];
+        let level = match volume & 0x0f {
+            _ if volume & 0x10 != 0 => self.envelope.level(),
+            0 => 0,
+            volume => (volume << 1) | 1,
+        };
+
+        // The levels are about 1.5 dB apart.
+        if level == 0 {
+            0.0
+        } else {
+            ((f32::from(level) - 31.0) / 4.0).exp2()
+        }
+    }
+
+    /// Save the state of the generators.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for tone in &self.tones {
+            writer.write_all(&tone.counter.to_le_bytes())?;
+            writer.write_all(&[u8::from(tone.high)])?;
+        }
+
+        let noise = &self.noise;
+        writer.write_all(&[noise.counter, u8::from(noise.odd)])?;
+        writer.write_all(&noise.lfsr.to_le_bytes())?;
+
+        let envelope = &self.envelope;
+        writer.write_all(&envelope.counter.to_le_bytes())?;
+        let flags = u8::from(envelope.attack) | (u8::from(envelope.holding) << 1);
+        writer.write_all(&[envelope.step, flags])
+    }
+
+    /// Load the state of the generators saved by [`Ssg::save`].
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 19];
+        reader.read_exact(&mut state)?;
+        let [tones @ .., noise_counter, odd, l0, l1, l2, l3, e0, e1, step, flags] = state;
+
+        let lfsr = u32::from_le_bytes([l0, l1, l2, l3]);
+        if lfsr == 0 || lfsr >> 17 != 0 || step > 31 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid ssg state",
+            ));
+        }
+
+        for (tone, bytes) in self.tones.iter_mut().zip(tones.chunks_exact(3)) {
+            *tone = Tone {
+                counter: u16::from_le_bytes([bytes[0], bytes[1]]),
+                high: bytes[2] != 0,
+            };
+        }
+        self.noise = Noise {
+            counter: noise_counter,
+            odd: odd != 0,
+            lfsr,
+        };
+        self.envelope = Envelope {
+            counter: u16::from_le_bytes([e0, e1]),
+            step,
+            attack: flags & 1 != 0,
+            holding: flags & 2 != 0,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! The recording of the events of a frame at the scanline and dot on which
+//! they happen, for event viewers which show them on a grid of the dots of
+//! the frame, to find the timing of raster effects.
+//!
+//! The events are recorded once enabled by [`Nes::record_events`], and the
+//! events of a frame are returned with it, see [`Frame::events`]:
+//!
+//! ```
+//! # use chuck_nes::events::{Event, Kind};
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::Nes;
+//! // A program that enables the NMI forever, whose handler returns at once.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..9].copy_from_slice(&[0xa9, 0x80, 0x8d, 0x00, 0x20, 0x4c, 0x02, 0x80, 0x40]);
+//! prg[0x3ffa..0x3ffe].copy_from_slice(&[0x08, 0x80, 0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.record_events(true);
+//!
+//! // The PPU ignores the writes during the first frame.
+//! nes.run_frame();
+//! let frame = nes.run_frame();
+//!
+//! let write = Kind::Write { addr: 0x2000, data: 0x80 };
+//! assert_eq!(frame.events[0].kind, write);
+//! assert!(frame.events.contains(&Event { scanline: 241, dot: 1, kind: Kind::Nmi }));
+//! ```
+//!
+//! [`Frame::events`]: crate::Frame::events
+//!
+//! # Link(s)
+//!
+//! - <https://www.mesen.ca/docs/debugging/eventviewer.html>
+
+use chuck_ppu::{Pins as PpuPins, Ppu};
+
+use crate::mapper::Mapper;
+#[cfg(doc)]
+use crate::Nes;
+
+bitflags::bitflags! {
+    /// The lines whose rises are recorded.
+    #[derive(Debug, Clone, Copy, Default)]
+    struct Lines: u8 {
+        /// The `/INT` pin of the PPU.
+        const NMI = 1 << 0;
+        /// The sprite zero hit flag of `PPUSTATUS`.
+        const SPRITE_ZERO = 1 << 1;
+        /// The `IRQ` output of the APU.
+        const APU_IRQ = 1 << 2;
+        /// The `IRQ` output of the cartridge.
+        const MAPPER_IRQ = 1 << 3;
+    }
+}
+
+/// An event, at the position of the PPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// The scanline of the event, see
+    /// [`Ppu::scanline`](chuck_ppu::Ppu::scanline).
+    pub scanline: u16,
+    /// The dot of the event, on which the PPU set its outputs, or for the
+    /// writes of the CPU, the first dot that sees the write.
+    pub dot: u16,
+    /// The kind of the event.
+    pub kind: Kind,
+}
+
+/// The kind of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A write of the CPU to a register of the PPU (`$2000`-`$3FFF`) or to
+    /// `OAMDMA` (`$4014`).
+    Write {
+        /// The address of the write, including the mirrors of the registers.
+        addr: u16,
+        /// The written value.
+        data: u8,
+    },
+    /// The PPU asserted the `NMI` of the CPU.
+    Nmi,
+    /// The PPU set the sprite zero hit flag.
+    SpriteZeroHit,
+    /// The APU asserted the `IRQ` of the CPU.
+    ApuIrq,
+    /// The cartridge asserted the `IRQ` of the CPU.
+    MapperIrq,
+    /// The IRQ counter of the cartridge was clocked, see
+    /// [`Mapper::irq_clocks`].
+    MapperIrqClock,
+}
+
+/// The recorder of the events of the current frame.
+#[derive(Debug, Clone)]
+pub(crate) struct Recorder {
+    /// The events of the frame.
+    events: Vec<Event>,
+    /// The levels of the observed lines.
+    lines: Lines,
+    /// The last number of clocks of the IRQ counter of the cartridge.
+    irq_clocks: u64,
+}
+
+impl Recorder {
+    /// Create a recorder for the given cartridge.
+    pub(crate) fn new(cartridge: &dyn Mapper) -> Self {
+        Self {
+            events: Vec::new(),
+            lines: Lines::empty(),
+            irq_clocks: cartridge.irq_clocks(),
+        }
+    }
+
+    /// Return the events of the current frame.
+    pub(crate) fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Discard the events of the previous frame.
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Record a write of the CPU at the current position of the PPU, if it
+    /// writes a register of the PPU or `OAMDMA`.
+    pub(crate) fn write(&mut self, ppu: &Ppu, addr: u16, data: u8) {
+        if matches!(addr, 0x2000..=0x3fff | 0x4014) {
+            self.push(ppu.scanline(), ppu.dot(), Kind::Write { addr, data });
+        }
+    }
+
+    /// Observe the outputs of the PPU and the cartridge after a dot, which
+    /// started at the given scanline and dot.
+    pub(crate) fn observe_dot(
+        &mut self,
+        (scanline, dot): (u16, u16),
+        ppu: &Ppu,
+        cartridge: &dyn Mapper,
+    ) {
+        let nmi = ppu.pins.contains(PpuPins::INT);
+        self.rise(scanline, dot, Lines::NMI, nmi, Kind::Nmi);
+
+        let sprite_zero = ppu.peek(0x2002) & 0x40 != 0;
+        self.rise(
+            scanline,
+            dot,
+            Lines::SPRITE_ZERO,
+            sprite_zero,
+            Kind::SpriteZeroHit,
+        );
+
+        self.observe_cartridge(scanline, dot, cartridge);
+    }
+
+    /// Observe the interrupt outputs of the APU and the cartridge after a CPU
+    /// cycle.
+    pub(crate) fn observe_cycle(&mut self, ppu: &Ppu, apu_irq: bool, cartridge: &dyn Mapper) {
+        let (scanline, dot) = (ppu.scanline(), ppu.dot());
+        self.rise(scanline, dot, Lines::APU_IRQ, apu_irq, Kind::ApuIrq);
+        self.rise(
+            scanline,
+            dot,
+            Lines::MAPPER_IRQ,
+            cartridge.irq(),
+            Kind::MapperIrq,
+        );
+
+        self.observe_cartridge(scanline, dot, cartridge);
+    }
+
+    /// Record the clocks of the IRQ counter of the cartridge since the last
+    /// observation.
+    fn observe_cartridge(&mut self, scanline: u16, dot: u16, cartridge: &dyn Mapper) {
+        let clocks = cartridge.irq_clocks();
+        for _ in 0..clocks.wrapping_sub(self.irq_clocks) {
+            self.push(scanline, dot, Kind::MapperIrqClock);
+        }
+        self.irq_clocks = clocks;
+    }
+
+    /// Record an event if the given line rises.
+    fn rise(&mut self, scanline: u16, dot: u16, line: Lines, level: bool, kind: Kind) {
+        if level && !self.lines.contains(line) {
+            self.push(scanline, dot, kind);
+        }
+        self.lines.set(line, level);
+    }
+
+    /// Record an event.
+    fn push(&mut self, scanline: u16, dot: u16, kind: Kind) {
+        self.events.push(Event {
+            scanline,
+            dot,
+            kind,
+        });
+    }
+}
@@ -0,0 +1,858 @@
+//! A server for the remote serial protocol of GDB, so that a debugger
+//! speaking it (e.g. GDB or LLDB, with the programs built by cc65 or
+//! llvm-mos) can attach to the console over TCP.
+//!
+//! The server doesn't run the console itself: the frontend polls it for the
+//! packets of the debugger before every frame, and runs the frame with the
+//! hooks of the CPU unless the debugger halted the console, reporting any
+//! break of the CPU to the server:
+//!
+//! ```no_run
+//! # use chuck_nes::{gdb::Server, mapper::{Mirroring, Nrom}, Nes};
+//! # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+//! let mut server = Server::bind("127.0.0.1:6502")?;
+//!
+//! loop {
+//!     server.poll(&mut nes)?;
+//!     if server.is_halted() {
+//!         // Keep showing the last picture.
+//!         continue;
+//!     }
+//!
+//!     match nes.debug_run_frame() {
+//!         Ok(frame) => { /* Show the picture and play the samples. */ }
+//!         Err(reason) => server.stop(&mut nes, reason)?,
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! The console is halted when the debugger attaches, and always stops at the
+//! start of an instruction, i.e. once its opcode is fetched: watchpoints stop
+//! after the instruction that accessed the watched address. The breakpoints
+//! (`Z0` and `Z1`) and watchpoints (`Z2`-`Z4`) of the debugger are mapped
+//! onto the hooks of the CPU, see [`chuck_cpu::debug`], so the program in
+//! the ROM is never patched.
+//!
+//! The 6502 is described to the debugger as the registers `a`, `x`, `y`, `sp`
+//! and `p` of 8 bits (with bit 5 of `p` set, like it's pushed by `PHP`) and
+//! `pc` of 16 bits, numbered in this order. The memory is the CPU's address
+//! space, which is read and written without side effects (see [`Nes::peek`]
+//! and [`Nes::poke`]), so e.g. writes of the program's code patch the PRG-ROM.
+//!
+//! The debugger doesn't know the symbols of programs that aren't built for
+//! it, e.g. by ca65, but the server resolves them once given (see
+//! [`Server::set_symbols`]) for the commands sent with `monitor`:
+//!
+//! | Command                  | Action                                             |
+//! |--------------------------|----------------------------------------------------|
+//! | `reset`                  | Press the reset button                             |
+//! | `break <label>`          | Add a breakpoint at the address of a label         |
+//! | `delete <label>`         | Remove the breakpoint of a label                   |
+//! | `where`                  | Show the label and the source line of `pc`         |
+//! | `disas [<addr>/<label>]` | Disassemble 8 instructions, from `pc` by default   |
+//!
+//! A label of a bank of the PRG-ROM resolves to its address while the bank is
+//! mapped, see [`symbols`](crate::symbols), so a breakpoint at it also hits in
+//! the other banks later mapped there.
+//!
+//! # Link(s)
+//!
+//! - <https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html>
+
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::str;
+
+use chuck_cpu::debug::{Reason, StepOutcome};
+use chuck_cpu::{disasm, Flags};
+
+use crate::symbols::Symbols;
+use crate::Nes;
+
+/// The description of the registers, for `qXfer:features:read`.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chuck.6502">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8" regnum="1"/>
+    <reg name="y" bitsize="8" type="uint8" regnum="2"/>
+    <reg name="sp" bitsize="8" type="uint8" regnum="3"/>
+    <reg name="p" bitsize="8" type="uint8" regnum="4"/>
+    <reg name="pc" bitsize="16" type="code_ptr" regnum="5"/>
+  </feature>
+</target>
+"#;
+
+/// The maximum size of the packets received by the server.
+const PACKET_SIZE: usize = 0x1000;
+
+/// The stop reply of a console stopped by a trap, i.e. a breakpoint, a
+/// watchpoint or a single step.
+const SIGTRAP: &str = "S05";
+
+/// The stop reply of a console stopped by the debugger.
+const SIGINT: &str = "S02";
+
+/// The byte sent by the debugger to halt the console.
+const INTERRUPT: u8 = 0x03;
+
+/// The help of the commands sent with `monitor`.
+const HELP: &str =
+    "commands: reset, break <label>, delete <label>, where, disas [<addr>/<label>]\n";
+
+/// The number of instructions disassembled by `monitor disas`.
+const DISASSEMBLY_LEN: u16 = 8;
+
+/// The lowercase hexadecimal digits.
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// A server for a single debugger.
+#[derive(Debug)]
+pub struct Server {
+    /// The socket accepting the connection of the debugger.
+    listener: TcpListener,
+    /// The attached debugger.
+    client: Option<Client>,
+    /// The symbols of the program.
+    symbols: Symbols,
+}
+
+impl Server {
+    /// Create a server listening at the given address, which accepts a
+    /// debugger with the next [`Server::poll`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of binding to the address.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            client: None,
+            symbols: Symbols::new(),
+        })
+    }
+
+    /// Set the symbols of the program, for the commands sent with `monitor`.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Return the address the server listens at.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of the socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Check if a debugger is attached.
+    #[must_use]
+    pub fn is_attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Check if the console is halted by the debugger, in which case it must
+    /// not run until the debugger resumes it.
+    #[must_use]
+    pub fn is_halted(&self) -> bool {
+        self.client.as_ref().is_some_and(|client| client.halted)
+    }
+
+    /// Accept a debugger if none is attached, then handle the packets it sent
+    /// since the last poll, without blocking.
+    ///
+    /// When the debugger detaches, its breakpoints and watchpoints are removed
+    /// and the console resumes.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of the connection, which then detaches the debugger.
+    pub fn poll(&mut self, nes: &mut Nes) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => self.client = Some(Client::new(stream, nes)?),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let Some(client) = &mut self.client else {
+            return Ok(());
+        };
+
+        match client.poll(nes, &self.symbols) {
+            Ok(true) => Ok(()),
+            result => {
+                client.detach(nes);
+                self.client = None;
+                result.map(|_| ())
+            }
+        }
+    }
+
+    /// Halt the console after a break of its CPU (see
+    /// [`Nes::debug_run_frame`]), and report it to the debugger. Without a
+    /// debugger, this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of the connection, which then detaches the debugger.
+    pub fn stop(&mut self, nes: &mut Nes, reason: Reason) -> io::Result<()> {
+        let Some(client) = &mut self.client else {
+            return Ok(());
+        };
+
+        client.halt(nes);
+        client.stop = client.stop_reply(Some(reason));
+        client.reply(&client.stop.clone());
+
+        let result = client.flush();
+        if result.is_err() {
+            client.detach(nes);
+            self.client = None;
+        }
+
+        result
+    }
+}
+
+/// The kinds of watchpoints, by the access that hits them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watch {
+    /// A write.
+    Write,
+    /// A read.
+    Read,
+    /// A read or a write.
+    Access,
+}
+
+/// The connection to a debugger.
+#[derive(Debug)]
+struct Client {
+    /// The socket of the connection.
+    stream: TcpStream,
+    /// The bytes received but not yet handled, i.e. an incomplete packet.
+    input: Vec<u8>,
+    /// A flag denoting if the rest of a packet larger than [`PACKET_SIZE`] is
+    /// dropped as it's received.
+    dropping: bool,
+    /// The bytes not yet sent.
+    output: Vec<u8>,
+    /// The last packet sent, which is sent again if the debugger requests it.
+    last: Vec<u8>,
+    /// A flag denoting if the packets are acknowledged, which the debugger
+    /// may turn off.
+    ack: bool,
+    /// A flag denoting if the console is halted.
+    halted: bool,
+    /// The stop reply of the last stop.
+    stop: String,
+    /// The addresses of the breakpoints.
+    breakpoints: Vec<u16>,
+    /// The watchpoints.
+    watchpoints: Vec<(Watch, RangeInclusive<u16>)>,
+}
+
+impl Client {
+    /// Attach a debugger, halting the console.
+    fn new(stream: TcpStream, nes: &mut Nes) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        let mut client = Self {
+            stream,
+            input: Vec::new(),
+            dropping: false,
+            output: Vec::new(),
+            last: Vec::new(),
+            ack: true,
+            halted: false,
+            stop: SIGTRAP.to_owned(),
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+        };
+
+        client.halt(nes);
+        Ok(client)
+    }
+
+    /// Remove the hooks of the debugger and resume the console.
+    fn detach(&mut self, nes: &mut Nes) {
+        let hooks = nes.hooks_mut();
+        for &addr in &self.breakpoints {
+            hooks.remove_breakpoint(addr);
+        }
+        for (_, range) in &self.watchpoints {
+            hooks.remove_watchpoint(range);
+        }
+
+        self.halted = false;
+    }
+
+    /// Receive and handle the packets of the debugger, and send the replies,
+    /// returning `false` once the debugger detached.
+    fn poll(&mut self, nes: &mut Nes, symbols: &Symbols) -> io::Result<bool> {
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let attached = self.receive(nes, symbols);
+        self.flush()?;
+        Ok(attached)
+    }
+
+    /// Send as many of the unsent bytes as possible without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => drop(self.output.drain(..n)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle the complete packets of the received bytes, returning `false`
+    /// once the debugger detached.
+    ///
+    /// A packet is sent as `$data#xx`, with the checksum `xx` of the data,
+    /// and acknowledged with `+` (or `-` to request it again). Packets larger
+    /// than [`PACKET_SIZE`] are rejected with `-` and dropped, without waiting
+    /// for their end.
+    fn receive(&mut self, nes: &mut Nes, symbols: &Symbols) -> bool {
+        let input = std::mem::take(&mut self.input);
+        let mut rest = &input[..];
+
+        if self.dropping {
+            match rest.iter().position(|&byte| byte == b'#') {
+                Some(end) => {
+                    self.dropping = false;
+                    rest = &rest[end + 1..];
+                }
+                None => rest = &[],
+            }
+        }
+
+        while let Some((&byte, tail)) = rest.split_first() {
+            match byte {
+                b'$' => {
+                    let end = tail.iter().position(|&byte| byte == b'#');
+                    // The packet, `$data#xx`, has 4 bytes besides its data.
+                    if end.unwrap_or(tail.len()) > PACKET_SIZE - 4 {
+                        if self.ack {
+                            self.output.push(b'-');
+                        }
+                        // The checksum is skipped like any byte outside of a
+                        // packet.
+                        rest = end.map_or(&[], |end| &tail[end + 1..]);
+                        self.dropping = end.is_none();
+                        continue;
+                    }
+
+                    let Some(end) = end else {
+                        break;
+                    };
+                    let Some(checksum) = tail.get(end + 1..end + 3) else {
+                        break;
+                    };
+
+                    let data = &tail[..end];
+                    rest = &tail[end + 3..];
+
+                    if unhex(checksum) != Some(vec![self::checksum(data)]) {
+                        if self.ack {
+                            self.output.push(b'-');
+                        }
+                        continue;
+                    }
+
+                    if self.ack {
+                        self.output.push(b'+');
+                    }
+                    if !self.handle(nes, symbols, data) {
+                        return false;
+                    }
+                    continue;
+                }
+                b'-' => self.output.extend_from_slice(&self.last),
+                INTERRUPT if !self.halted => {
+                    self.halt(nes);
+                    SIGINT.clone_into(&mut self.stop);
+                    self.reply(SIGINT);
+                }
+                _ => {}
+            }
+
+            rest = tail;
+        }
+
+        self.input = rest.to_vec();
+        true
+    }
+
+    /// Send a packet with the given data.
+    fn reply(&mut self, data: &str) {
+        let mut packet = vec![b'$'];
+        for &byte in data.as_bytes() {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                packet.extend([b'}', byte ^ 0x20]);
+            } else {
+                packet.push(byte);
+            }
+        }
+
+        let checksum = checksum(&packet[1..]);
+        packet.push(b'#');
+        packet.extend(hex(&[checksum]).bytes());
+
+        self.output.extend_from_slice(&packet);
+        self.last = packet;
+    }
+
+    /// Handle a packet, returning `false` if it detached the debugger.
+    fn handle(&mut self, nes: &mut Nes, symbols: &Symbols, packet: &[u8]) -> bool {
+        // Only the unsupported packets with binary data aren't ASCII.
+        let Some((&command, args)) = packet.split_first() else {
+            self.reply("");
+            return true;
+        };
+        let args = str::from_utf8(args).unwrap_or_default();
+
+        let reply = match command {
+            b'?' => self.stop.clone(),
+            b'g' => hex(&registers(nes)),
+            b'G' => ok(unhex(args).and_then(|regs| set_registers(nes, &regs, 0))),
+            b'p' => read_register(nes, args).unwrap_or_else(|| "E01".to_owned()),
+            b'P' => ok(args.split_once('=').and_then(|(index, value)| {
+                let offset = offset(usize::from_str_radix(index, 16).ok()?)?;
+                set_registers(nes, &unhex(value)?, offset)
+            })),
+            b'm' => read_memory(nes, args).unwrap_or_else(|| "E01".to_owned()),
+            b'M' => ok(write_memory(nes, args)),
+            b'c' | b'C' | b's' | b'S' => {
+                self.resume(nes, command, args);
+                return true;
+            }
+            b'v' => {
+                if args == "Cont?" {
+                    "vCont;c;C;s;S".to_owned()
+                } else if let Some(action) = args.strip_prefix("Cont;") {
+                    let command = action.bytes().next().unwrap_or(b'c');
+                    self.resume(nes, command.to_ascii_lowercase(), "");
+                    return true;
+                } else {
+                    String::new()
+                }
+            }
+            b'Z' | b'z' => self.hook(nes, command == b'Z', args).to_owned(),
+            b'q' | b'Q' => self.query(nes, symbols, args),
+            b'H' | b'T' => "OK".to_owned(),
+            b'D' => {
+                self.reply("OK");
+                return false;
+            }
+            b'k' => return false,
+            _ => String::new(),
+        };
+
+        self.reply(&reply);
+        true
+    }
+
+    /// Handle a query packet (`q` or `Q`).
+    fn query(&mut self, nes: &mut Nes, symbols: &Symbols, query: &str) -> String {
+        if query.starts_with("Supported") {
+            format!("PacketSize={PACKET_SIZE:x};QStartNoAckMode+;qXfer:features:read+")
+        } else if query == "StartNoAckMode" {
+            self.ack = false;
+            "OK".to_owned()
+        } else if query == "Attached" {
+            "1".to_owned()
+        } else if query == "fThreadInfo" {
+            "m1".to_owned()
+        } else if query == "sThreadInfo" {
+            "l".to_owned()
+        } else if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| {
+                let offset = usize::from_str_radix(offset, 16).ok()?;
+                Some((offset, usize::from_str_radix(length, 16).ok()?))
+            }) else {
+                return "E01".to_owned();
+            };
+
+            match TARGET_XML.get(offset..).unwrap_or_default() {
+                data if data.len() <= length => format!("l{data}"),
+                data => format!("m{}", &data[..length]),
+            }
+        } else if let Some(command) = query.strip_prefix("Rcmd,") {
+            let command = unhex(command).and_then(|command| String::from_utf8(command).ok());
+            let output = self.monitor(nes, symbols, command.as_deref().unwrap_or_default());
+            if !output.is_empty() {
+                self.reply(&format!("O{}", hex(output.as_bytes())));
+            }
+
+            "OK".to_owned()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Run a command sent with `monitor`, returning its output.
+    fn monitor(&mut self, nes: &mut Nes, symbols: &Symbols, command: &str) -> String {
+        let mut words = command.split_whitespace();
+        let (command, arg) = (words.next(), words.next());
+        let pc = nes.cpu().regs.pc;
+
+        match (command, arg) {
+            (Some("reset"), None) => {
+                nes.reset();
+                self.halt(nes);
+                String::new()
+            }
+            (Some(command @ ("break" | "delete")), Some(label)) => {
+                let Some(addr) = symbols.addr(nes.cartridge(), label) else {
+                    return format!("no label {label} is mapped\n");
+                };
+
+                if command == "break" {
+                    self.add_breakpoint(nes, addr);
+                    format!("breakpoint at ${addr:04X}\n")
+                } else {
+                    self.remove_breakpoint(nes, addr);
+                    String::new()
+                }
+            }
+            (Some("where"), None) => {
+                let cartridge = nes.cartridge();
+                let label = symbols.label(cartridge, pc).unwrap_or("?");
+                let source = symbols
+                    .source(cartridge, pc)
+                    .map_or_else(String::new, |source| format!(" at {source}"));
+                format!("${pc:04X} {label}{source}\n")
+            }
+            (Some("disas"), addr) => {
+                let addr = addr.map_or(Some(pc), |addr| {
+                    parse_addr(addr.trim_start_matches('$'))
+                        .or_else(|| symbols.addr(nes.cartridge(), addr))
+                });
+                addr.map_or_else(
+                    || "bad address\n".to_owned(),
+                    |addr| disassemble(nes, symbols, addr),
+                )
+            }
+            _ => HELP.to_owned(),
+        }
+    }
+
+    /// Continue (`c`) or single-step (`s`) the console, from the given
+    /// address if any, ignoring the signal of `C` and `S`.
+    fn resume(&mut self, nes: &mut Nes, command: u8, args: &str) {
+        let addr = match command {
+            b'c' | b's' => args,
+            _ => args.split_once(';').map_or("", |(_, addr)| addr),
+        };
+        if let Some(addr) = parse_addr(addr) {
+            let mut regs = nes.cpu().regs.clone();
+            regs.pc = addr;
+            nes.set_registers(regs);
+        }
+
+        if command.eq_ignore_ascii_case(&b'c') {
+            self.halted = false;
+            return;
+        }
+
+        // The opcode may be fetched repeatedly while the DMA unit halts the
+        // CPU, so the step only ends with a fetch after the decode.
+        let mut decoded = false;
+        let mut watch = None;
+        loop {
+            let outcome = nes.debug_step();
+            let fetching = nes.cpu().instruction_progress().fetching;
+            decoded |= !fetching;
+
+            if let StepOutcome::Break(reason @ (Reason::Read(_) | Reason::Write(_))) = outcome {
+                watch.get_or_insert(reason);
+            }
+            if (decoded && fetching) || nes.cpu().is_jammed() {
+                break;
+            }
+        }
+
+        self.halted = true;
+        self.stop = self.stop_reply(watch);
+        self.reply(&self.stop.clone());
+    }
+
+    /// Halt the console at the start of the next instruction.
+    fn halt(&mut self, nes: &mut Nes) {
+        while !nes.cpu().instruction_progress().fetching && !nes.cpu().is_jammed() {
+            nes.step();
+        }
+
+        self.halted = true;
+    }
+
+    /// Return the stop reply of a stop by a trap with the given reason.
+    fn stop_reply(&self, reason: Option<Reason>) -> String {
+        let (addr, write) = match reason {
+            Some(Reason::Read(addr)) => (addr, false),
+            Some(Reason::Write(addr)) => (addr, true),
+            _ => return SIGTRAP.to_owned(),
+        };
+
+        let kind = self
+            .watchpoints
+            .iter()
+            .filter(|(_, range)| range.contains(&addr))
+            .map(|&(kind, _)| kind)
+            .find(|&kind| kind == Watch::Access || (kind == Watch::Write) == write);
+
+        match kind {
+            Some(Watch::Write) => format!("T05watch:{addr:04x};"),
+            Some(Watch::Read) => format!("T05rwatch:{addr:04x};"),
+            Some(Watch::Access) => format!("T05awatch:{addr:04x};"),
+            None => SIGTRAP.to_owned(),
+        }
+    }
+
+    /// Insert or remove a breakpoint or watchpoint (`Z` or `z`), returning
+    /// the reply.
+    fn hook(&mut self, nes: &mut Nes, insert: bool, args: &str) -> &'static str {
+        let mut fields = args.split(';').next().unwrap_or_default().split(',');
+        let (Some(kind), Some(addr), Some(len)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return "E01";
+        };
+        let (Some(addr), Ok(len)) = (parse_addr(addr), u16::from_str_radix(len, 16)) else {
+            return "E01";
+        };
+
+        let watch = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.add_breakpoint(nes, addr);
+                } else {
+                    self.remove_breakpoint(nes, addr);
+                }
+
+                return "OK";
+            }
+            "2" => Watch::Write,
+            "3" => Watch::Read,
+            "4" => Watch::Access,
+            _ => return "",
+        };
+
+        let Some(end) = len.checked_sub(1).and_then(|len| addr.checked_add(len)) else {
+            return "E01";
+        };
+        let range = addr..=end;
+
+        if insert {
+            self.watchpoints.push((watch, range.clone()));
+        } else if let Some(index) = self
+            .watchpoints
+            .iter()
+            .position(|watchpoint| *watchpoint == (watch, range.clone()))
+        {
+            self.watchpoints.swap_remove(index);
+        }
+
+        // The hooks only remove all watchpoints of a range at once.
+        let hooks = nes.hooks_mut();
+        hooks.remove_watchpoint(&range);
+        for (kind, _) in self.watchpoints.iter().filter(|(_, r)| *r == range) {
+            if matches!(kind, Watch::Read | Watch::Access) {
+                hooks.add_read_watchpoint(range.clone());
+            }
+            if matches!(kind, Watch::Write | Watch::Access) {
+                hooks.add_write_watchpoint(range.clone());
+            }
+        }
+
+        "OK"
+    }
+
+    /// Add a breakpoint at the given address.
+    fn add_breakpoint(&mut self, nes: &mut Nes, addr: u16) {
+        self.breakpoints.push(addr);
+        nes.hooks_mut().add_breakpoint(addr);
+    }
+
+    /// Remove a breakpoint at the given address, keeping the hook while
+    /// another one is set there.
+    fn remove_breakpoint(&mut self, nes: &mut Nes, addr: u16) {
+        if let Some(index) = self.breakpoints.iter().position(|&a| a == addr) {
+            self.breakpoints.swap_remove(index);
+            if !self.breakpoints.contains(&addr) {
+                nes.hooks_mut().remove_breakpoint(addr);
+            }
+        }
+    }
+}
+
+/// Disassemble the instructions from the given address with their labels,
+/// for `monitor disas`.
+fn disassemble(nes: &Nes, symbols: &Symbols, addr: u16) -> String {
+    // Each instruction has at most 3 bytes.
+    let bytes: Vec<_> = (0..DISASSEMBLY_LEN * 3)
+        .map(|offset| nes.peek(addr.wrapping_add(offset)))
+        .collect();
+    let peek = |a: u16| bytes[usize::from(a.wrapping_sub(addr))];
+
+    let (variant, cartridge) = (nes.cpu().variant(), nes.cartridge());
+    let mut output = String::new();
+    let mut next = addr;
+    for _ in 0..DISASSEMBLY_LEN {
+        let instruction = disasm::disassemble(variant, next, peek);
+        let labeled = instruction.labeled(|addr| symbols.label(cartridge, addr));
+
+        // Writing to a `String` can't fail.
+        if let Some(label) = symbols.label(cartridge, next) {
+            let _ = writeln!(output, "{label}:");
+        }
+        let _ = writeln!(output, "  ${next:04X}  {labeled}");
+        next = instruction.next_addr();
+    }
+
+    output
+}
+
+/// Return the registers in the order of their numbers, least significant
+/// byte first.
+fn registers(nes: &Nes) -> [u8; 7] {
+    let regs = &nes.cpu().regs;
+    let [pcl, pch] = regs.pc.to_le_bytes();
+
+    [
+        regs.a,
+        regs.x,
+        regs.y,
+        regs.sp,
+        regs.flags.bits() | 0x20,
+        pcl,
+        pch,
+    ]
+}
+
+/// Overwrite the registers, as returned by [`registers`], from the given
+/// offset with the given bytes.
+fn set_registers(nes: &mut Nes, bytes: &[u8], offset: usize) -> Option<()> {
+    let mut all = registers(nes);
+    all.get_mut(offset..offset + bytes.len())?
+        .copy_from_slice(bytes);
+
+    let mut regs = nes.cpu().regs.clone();
+    let [a, x, y, sp, p, pcl, pch] = all;
+    (regs.a, regs.x, regs.y, regs.sp) = (a, x, y, sp);
+    regs.flags = Flags::from_bits_truncate(p);
+    regs.pc = u16::from_le_bytes([pcl, pch]);
+
+    nes.set_registers(regs);
+    Some(())
+}
+
+/// Return the offset of a register number in the bytes of the registers.
+fn offset(register: usize) -> Option<usize> {
+    (register <= 5).then_some(register)
+}
+
+/// Read a register (`p`).
+fn read_register(nes: &Nes, args: &str) -> Option<String> {
+    let offset = offset(usize::from_str_radix(args, 16).ok()?)?;
+    let len = if offset == 5 { 2 } else { 1 };
+
+    Some(hex(&registers(nes)[offset..offset + len]))
+}
+
+/// Read memory (`m`).
+fn read_memory(nes: &Nes, args: &str) -> Option<String> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = parse_addr(addr)?;
+    let len = usize::from_str_radix(len, 16)
+        .ok()?
+        .min(PACKET_SIZE / 2 - 2);
+
+    let bytes: Vec<_> = (addr..=u16::MAX)
+        .take(len)
+        .map(|addr| nes.peek(addr))
+        .collect();
+    Some(hex(&bytes))
+}
+
+/// Write memory (`M`).
+fn write_memory(nes: &mut Nes, args: &str) -> Option<()> {
+    let (location, data) = args.split_once(':')?;
+    let (addr, len) = location.split_once(',')?;
+    let (addr, data) = (parse_addr(addr)?, unhex(data)?);
+    if usize::from_str_radix(len, 16).ok()? != data.len() {
+        return None;
+    }
+
+    for (addr, byte) in (addr..=u16::MAX).zip(data) {
+        nes.poke(addr, byte);
+    }
+
+    Some(())
+}
+
+/// Return the reply of a packet that succeeded (`OK`) or failed.
+fn ok(result: Option<()>) -> String {
+    result.map_or("E01", |()| "OK").to_owned()
+}
+
+/// Parse an address of the CPU bus.
+fn parse_addr(text: &str) -> Option<u16> {
+    u16::from_str_radix(text, 16).ok()
+}
+
+/// Return the checksum of a packet's data, the sum of its bytes.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Encode bytes in hexadecimal.
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&byte| {
+            [
+                DIGITS[usize::from(byte >> 4)],
+                DIGITS[usize::from(byte & 0xf)],
+            ]
+        })
+        .map(char::from)
+        .collect()
+}
+
+/// Decode bytes in hexadecimal, of either case.
+fn unhex(text: impl AsRef<[u8]>) -> Option<Vec<u8>> {
+    text.as_ref()
+        .chunks(2)
+        .map(|pair| {
+            let pair = str::from_utf8(pair).ok().filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
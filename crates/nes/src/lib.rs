@@ -0,0 +1,983 @@
+//! The NES itself, i.e. Chuck's CPU, PPU and APU wired together.
+//!
+//! # Modularity
+//!
+//! The cores of Chuck only communicate through their pins and buses, so this
+//! crate is the only place that knows how the NES is put together: it owns
+//! the chips, the console's RAM and nametable RAM (CIRAM), the cartridge and
+//! the devices plugged into the controller ports,
+//! implements the memory maps of the CPU and PPU buses, and drives all chips
+//! from a single master clock.
+//!
+//! On an NTSC console, the master clock (21.477 MHz) is divided by 12 for the
+//! CPU and by 4 for the PPU, so the PPU executes exactly 3 dots per CPU cycle.
+//! The APU is part of the CPU's chip and is clocked with every CPU cycle. PAL
+//! consoles and the Dendy have other clocks and chips, see [`Region`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Cycle_reference_chart>
+//! - <https://www.nesdev.org/wiki/CPU_memory_map>
+//! - <https://www.nesdev.org/wiki/PPU_memory_map>
+
+pub mod audio_log;
+mod bus;
+pub mod cheat;
+pub mod chr;
+#[cfg(feature = "debug")]
+mod debug;
+pub mod determinism;
+mod dma;
+pub mod env;
+pub mod epsm;
+pub mod events;
+#[cfg(feature = "debug")]
+pub mod gdb;
+pub mod mapper;
+pub mod mixer;
+pub mod movie;
+pub mod netplay;
+pub mod nsf;
+mod overclock;
+pub mod parallel;
+pub mod profile;
+pub mod record;
+mod region;
+pub mod rewind;
+pub mod slot;
+pub mod sram;
+mod state;
+pub mod symbols;
+#[cfg(feature = "timing")]
+pub mod timing;
+
+pub use chuck_ppu::{HEIGHT, WIDTH};
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use chuck_apu::{Apu, Pins as ApuPins};
+use chuck_cpu::{Cpu, Pins as CpuPins};
+use chuck_input::Ports;
+use chuck_ppu::{Pins as PpuPins, Ppu};
+use chuck_rom::Rom;
+use chuck_video::palette::Palette;
+use chuck_video::png;
+
+use audio_log::AudioLog;
+use cheat::Cheats;
+use chr::{ChrChanges, Watcher as ChrWatcher};
+use determinism::DeterminismConfig;
+use dma::Dma;
+use epsm::Epsm;
+use events::{Event, Recorder};
+use mapper::{Mapper, UnsupportedMapper, A12};
+use mixer::Mixer;
+use movie::Schedule;
+use overclock::Overclock;
+use profile::Profiler;
+use sram::{FlushPolicy, Tracker};
+#[cfg(feature = "timing")]
+use timing::{FrameStats, Subsystem, Timer};
+
+pub use region::Region;
+
+/// The number of PPU dots executed within a CPU cycle before its bus access.
+///
+/// This is the alignment of the CPU and PPU clocks, which the timing of the
+/// `PPUSTATUS` reads, the `NMI` and the scanline counters of mappers like the
+/// MMC3 depend on.
+const ACCESS_DOT: u8 = 2;
+
+/// The output of a frame, see [`Nes::run_frame`].
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    /// The colors of the picture, row by row, see
+    /// [`Ppu::frame_buffer`](chuck_ppu::Ppu::frame_buffer), which is the
+    /// frame buffer itself and holds the complete picture of the frame.
+    pub pixels: &'a [u16],
+    /// The generation of the picture, see
+    /// [`Ppu::generation`](chuck_ppu::Ppu::generation), which is the same as
+    /// the one of the previous frame if the frame wasn't output, see
+    /// [`Nes::run_frame_headless`].
+    pub generation: u64,
+    /// The audio samples of the frame, one per CPU cycle (about 1.79 MHz on
+    /// NTSC, see [`Region::cpu_clock`]), including the expansion audio of the
+    /// cartridge, see [`Apu::sample_with`](chuck_apu::Apu::sample_with), and
+    /// mixed with the volumes of the [`Nes::mixer`].
+    pub samples: &'a [f32],
+    /// The audio samples of the individual channels, one per CPU cycle like
+    /// the `samples`, if they're captured, see [`Nes::capture_channels`].
+    pub channels: &'a [Channels],
+    /// The left and right audio samples, one pair per CPU cycle like the
+    /// `samples`, whose average they are, if they're captured, see
+    /// [`Nes::capture_stereo`].
+    pub stereo: &'a [[f32; 2]],
+    /// The events of the frame, if they're recorded, see
+    /// [`Nes::record_events`].
+    pub events: &'a [Event],
+    /// The phase of the color subcarrier at the start of the frame, for
+    /// composite video filters, see
+    /// [`Ppu::frame_phase`](chuck_ppu::Ppu::frame_phase).
+    pub phase: u8,
+}
+
+/// The audio samples of the individual channels of a CPU cycle, in the units
+/// of the mixed samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Channels {
+    /// The channels of the APU, each mixed on its own, see
+    /// [`Output::channels`](chuck_apu::Output::channels).
+    pub apu: [f32; 5],
+    /// The expansion audio of the cartridge, and of the EPSM if it's
+    /// plugged in, see [`Nes::plug_epsm`].
+    pub expansion: f32,
+}
+
+/// The Nintendo Entertainment System.
+///
+/// ```
+/// # use chuck_nes::{mapper::{Mirroring, Nrom}, Nes, HEIGHT, WIDTH};
+/// // A program that loops forever, starting at $8000.
+/// let mut prg = vec![0; 0x4000];
+/// prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+/// prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+///
+/// let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+/// let frame = nes.run_frame();
+///
+/// assert_eq!(frame.pixels.len(), WIDTH * HEIGHT);
+/// assert_eq!(frame.samples.len(), 29_781);
+/// assert_eq!(nes.cpu().regs.pc & 0xfff0, 0x8000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Nes {
+    /// The region of the console.
+    region: Region,
+    /// The number of master clock cycles of the PPU's next dot that already
+    /// passed, on PAL consoles, whose CPU cycles are 3.2 dots long.
+    phase: u8,
+    /// The 2A03's CPU core.
+    cpu: Cpu,
+    /// The 2A03's APU.
+    apu: Apu,
+    /// The 2C02 PPU.
+    ppu: Ppu,
+    /// The 2 KiB of RAM of the CPU.
+    ram: Box<[u8; 0x800]>,
+    /// The 2 KiB of nametable RAM of the PPU.
+    ciram: Box<[u8; 0x800]>,
+    /// The board of the inserted cartridge.
+    cartridge: Box<dyn Mapper>,
+    /// The watcher of the `A12` line of the PPU bus, for the board.
+    a12: A12,
+    /// The 2A03's DMA unit.
+    dma: Dma,
+    /// The controller ports.
+    input: Ports,
+    /// The changes of the buttons scheduled within the current frame, if
+    /// any.
+    schedule: Option<Box<Schedule>>,
+    /// The value last driven onto the external data bus of the CPU, which is
+    /// read back from the addresses that no device drives (open bus).
+    open_bus: u8,
+    /// The writes to the save RAM since it was last saved.
+    sram: Tracker,
+    /// The audio samples of the current frame.
+    samples: Vec<f32>,
+    /// The audio samples of the individual channels of the current frame, if
+    /// they're captured.
+    channels: Option<Vec<Channels>>,
+    /// The left and right audio samples of the current frame, if they're
+    /// captured.
+    stereo: Option<Vec<[f32; 2]>>,
+    /// The volumes of the audio channels.
+    mixer: Mixer,
+    /// The cheats applied to the reads of the CPU bus.
+    cheats: Cheats,
+    /// The frozen addresses of the CPU bus and their values, see
+    /// [`Nes::freeze`].
+    frozen: BTreeMap<u16, u8>,
+    /// The recorder of the events of the current frame, if they're recorded.
+    events: Option<Recorder>,
+    /// The log of the writes to the registers of the sound chips, if they're
+    /// logged.
+    audio_log: Option<Box<AudioLog>>,
+    /// The EPSM, if it's plugged into the expansion port.
+    epsm: Option<Box<Epsm>>,
+    /// The profiler of the CPU, if it's profiled.
+    profiler: Option<Box<Profiler>>,
+    /// The watcher of the pattern tables, if they're watched.
+    chr: Option<Box<ChrWatcher>>,
+    /// The watcher of the pattern tables for the cache of the viewers, see
+    /// [`Nes::ppu_vram_cached`], apart from the one of
+    /// [`Nes::take_chr_changes`].
+    viewer_chr: Option<Box<ChrWatcher>>,
+    /// The extra scanlines of the overclocking.
+    overclock: Overclock,
+    /// The configuration of the power-up state.
+    determinism: DeterminismConfig,
+    /// The timer of the subsystems, if they're timed.
+    #[cfg(feature = "timing")]
+    timer: Option<Box<Timer>>,
+    /// The number and phase of the frame that was interrupted by a break of
+    /// the CPU, see [`Nes::debug_run_frame`].
+    #[cfg(feature = "debug")]
+    interrupted: Option<(u64, u8)>,
+}
+
+impl Nes {
+    /// Create a new NTSC console with the given cartridge inserted, and power
+    /// it on.
+    #[must_use]
+    pub fn new(cartridge: Box<dyn Mapper>) -> Self {
+        Self::with_region(cartridge, Region::Ntsc)
+    }
+
+    /// Create a new console of the given region with the given cartridge
+    /// inserted, and power it on.
+    #[must_use]
+    pub fn with_region(cartridge: Box<dyn Mapper>, region: Region) -> Self {
+        Self {
+            region,
+            phase: 0,
+            cpu: Cpu::new(),
+            apu: Apu::with_region(region.apu()),
+            ppu: Ppu::with_region(region.ppu()),
+            ram: Box::new([0; 0x800]),
+            ciram: Box::new([0; 0x800]),
+            cartridge,
+            a12: A12::default(),
+            dma: Dma::default(),
+            input: Ports::new(),
+            schedule: None,
+            open_bus: 0,
+            sram: Tracker::default(),
+            samples: Vec::new(),
+            channels: None,
+            stereo: None,
+            mixer: Mixer::default(),
+            cheats: Cheats::default(),
+            frozen: BTreeMap::new(),
+            events: None,
+            audio_log: None,
+            epsm: None,
+            profiler: None,
+            chr: None,
+            viewer_chr: None,
+            overclock: Overclock::default(),
+            determinism: DeterminismConfig::default(),
+            #[cfg(feature = "timing")]
+            timer: None,
+            #[cfg(feature = "debug")]
+            interrupted: None,
+        }
+    }
+
+    /// Create a new console with the cartridge of the given ROM inserted, see
+    /// [`mapper::from_rom`], of the region the ROM was made for.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mapper of the ROM isn't supported.
+    pub fn from_rom(rom: &Rom) -> Result<Self, UnsupportedMapper> {
+        let region = Region::from(rom.header().region);
+        mapper::from_rom(rom).map(|cartridge| Self::with_region(cartridge, region))
+    }
+
+    /// Press and release the reset button, which holds the CPU and the PPU in
+    /// reset for a cycle, and silences the APU's channels.
+    ///
+    /// The CPU then runs its reset sequence, while the PPU ignores the writes
+    /// to most of its registers until the next frame, see
+    /// [`Pins::RST`](chuck_ppu::Pins::RST). The RAM and the cartridge keep
+    /// their state.
+    pub fn reset(&mut self) {
+        self.cpu.pins.insert(CpuPins::RES);
+        self.ppu.pins.insert(PpuPins::RST);
+        self.apu.write(0x4015, 0);
+
+        self.step();
+
+        self.cpu.pins.remove(CpuPins::RES);
+        self.ppu.pins.remove(PpuPins::RST);
+    }
+
+    /// Turn the console off and on again, which puts the chips and the RAM
+    /// into their power-up state, as configured by [`Nes::set_determinism`].
+    ///
+    /// The cartridge keeps the state of its board (which games initialize
+    /// anyway) and of its RAM, as do the input devices, the cheats, the
+    /// frozen addresses (see [`Nes::freeze`]) and the debugging state of the
+    /// CPU, e.g. its hooks.
+    pub fn power_cycle(&mut self) {
+        self.phase = 0;
+        self.cpu.pins = CpuPins::empty();
+        self.cpu.regs = Cpu::new().regs;
+        self.cpu.reset();
+        self.apu = Apu::with_region(self.region.apu());
+        self.apu.skip_frame_counter(self.determinism.frame_counter);
+        self.ppu.power_cycle();
+        let memories = [&mut self.ram[..], &mut self.ciram[..]];
+        self.determinism.ram.fill(memories);
+        self.dma = Dma::default();
+        self.open_bus = 0;
+        self.overclock.stop();
+        if let Some(epsm) = &mut self.epsm {
+            **epsm = Epsm::new(self.region);
+        }
+
+        // The PPU runs ahead by the whole dots of the alignment.
+        let alignment = self.determinism.alignment;
+        self.phase = alignment % self.region.ppu_divider();
+        for _ in 0..alignment / self.region.ppu_divider() {
+            self.step_ppu();
+        }
+
+        self.poke_frozen();
+    }
+
+    /// Return the configuration of the power-up state.
+    #[must_use]
+    pub const fn determinism(&self) -> DeterminismConfig {
+        self.determinism
+    }
+
+    /// Set the configuration of the power-up state, which takes effect with
+    /// the next [`Nes::power_cycle`], so e.g. a movie recorded with it is
+    /// played back on a new console after a power cycle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the alignment isn't below the number of master clock cycles
+    /// per CPU cycle of the region, see [`DeterminismConfig::alignment`].
+    pub fn set_determinism(&mut self, config: DeterminismConfig) {
+        assert!(
+            config.alignment < self.region.cpu_divider(),
+            "alignment out of range"
+        );
+        self.determinism = config;
+    }
+
+    /// Execute a single CPU cycle, i.e. 12 master clock cycles on NTSC.
+    ///
+    /// The CPU places its bus access at the start of the cycle, but the data
+    /// is only transferred towards its end, so the access is serviced after
+    /// the first 2 of the PPU's 3 dots (or 4 dots on every fifth CPU cycle on
+    /// PAL), unless the CPU is halted by the DMA
+    /// unit, which uses the bus instead. The APU and the cartridge are clocked
+    /// after the access, and finally the interrupt outputs of the chips, the
+    /// cartridge and the DMA unit's `RDY` are wired into the CPU's pins for
+    /// its next cycle.
+    ///
+    /// During the extra scanlines of the overclocking, only the CPU runs, see
+    /// [`Nes::set_overclock`].
+    pub fn step(&mut self) {
+        self.clock(Cpu::step);
+    }
+
+    /// Execute a single CPU cycle like [`Nes::step`], with the given function
+    /// executing the cycle of the CPU itself.
+    #[inline]
+    fn clock<T>(&mut self, step_cpu: impl FnOnce(&mut Cpu) -> T) -> T {
+        self.apply_changes();
+        if self.overclock.is_running() {
+            return self.clock_overclocked(step_cpu);
+        }
+
+        self.phase += self.region.cpu_divider();
+        let dots = self.phase / self.region.ppu_divider();
+        self.phase %= self.region.ppu_divider();
+
+        #[cfg(feature = "timing")]
+        let mut lap = self.timer.as_mut().and_then(|timer| timer.lap());
+        let output = step_cpu(&mut self.cpu);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.observe(&self.cpu, &*self.cartridge);
+        }
+        #[cfg(feature = "timing")]
+        timing::split(&mut lap, Subsystem::Cpu);
+
+        for _ in 0..ACCESS_DOT {
+            self.step_ppu();
+        }
+        #[cfg(feature = "timing")]
+        timing::split(&mut lap, Subsystem::Ppu);
+
+        self.service_bus();
+        self.input.step();
+        #[cfg(feature = "timing")]
+        timing::split(&mut lap, Subsystem::Cpu);
+        self.cartridge.clock();
+        let mut expansion = self.cartridge.audio();
+        if let Some(epsm) = &mut self.epsm {
+            epsm.clock();
+            expansion += epsm.output();
+        }
+        #[cfg(feature = "timing")]
+        timing::split(&mut lap, Subsystem::Mapper);
+        self.apu.step();
+        if let Some(log) = &mut self.audio_log {
+            log.clock();
+        }
+        self.dma.request_dmc(self.apu.pins.contains(ApuPins::DMA));
+
+        let apu = self.apu.output();
+        let cartridge = &*self.cartridge;
+        let epsm = self.epsm.as_deref();
+        // The channels of the EPSM follow the ones of the cartridge.
+        let channel = |channel| {
+            let first = cartridge.audio_channels().len();
+            match epsm {
+                Some(epsm) if channel >= first => epsm.channel_audio(channel - first),
+                _ => cartridge.channel_audio(channel),
+            }
+        };
+        let sample = if let Some(stereo) = &mut self.stereo {
+            let [left, right] = self.mixer.mix_stereo(apu, expansion, channel);
+            stereo.push([left, right]);
+            f32::midpoint(left, right)
+        } else {
+            self.mixer.mix(apu, expansion, channel)
+        };
+        self.samples.push(sample);
+        if let Some(channels) = &mut self.channels {
+            channels.push(Channels {
+                apu: apu.channels(),
+                expansion,
+            });
+        }
+        #[cfg(feature = "timing")]
+        timing::split(&mut lap, Subsystem::Apu);
+
+        for _ in ACCESS_DOT..dots {
+            self.step_ppu();
+        }
+        #[cfg(feature = "timing")]
+        {
+            timing::split(&mut lap, Subsystem::Ppu);
+            if let (Some(timer), Some(lap)) = (&mut self.timer, lap) {
+                timer.add(&lap);
+            }
+        }
+
+        self.cpu
+            .pins
+            .set(CpuPins::NMI, self.ppu.pins.contains(PpuPins::INT));
+
+        let apu_irq = self.apu.pins.contains(ApuPins::IRQ);
+        self.cpu
+            .pins
+            .set(CpuPins::IRQ, apu_irq || self.cartridge.irq());
+        if let Some(events) = &mut self.events {
+            events.observe_cycle(&self.ppu, apu_irq, &*self.cartridge);
+        }
+        self.cpu.pins.set(CpuPins::RDY, self.dma.is_halted());
+
+        output
+    }
+
+    /// Execute a CPU cycle of the extra scanlines of the overclocking, which
+    /// only clocks the CPU and services its bus access, while the PPU stalls
+    /// (see [`Ppu::stall`]) for 3 dots.
+    fn clock_overclocked<T>(&mut self, step_cpu: impl FnOnce(&mut Cpu) -> T) -> T {
+        let output = step_cpu(&mut self.cpu);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.observe(&self.cpu, &*self.cartridge);
+        }
+
+        for _ in 0..ACCESS_DOT {
+            self.stall_ppu();
+        }
+        self.service_bus();
+        self.stall_ppu();
+        self.overclock.cycle();
+
+        self.cpu
+            .pins
+            .set(CpuPins::NMI, self.ppu.pins.contains(PpuPins::INT));
+        let apu_irq = self.apu.pins.contains(ApuPins::IRQ);
+        self.cpu
+            .pins
+            .set(CpuPins::IRQ, apu_irq || self.cartridge.irq());
+        self.cpu.pins.set(CpuPins::RDY, self.dma.is_halted());
+
+        output
+    }
+
+    /// Stall the PPU for a dot, servicing its bus access.
+    fn stall_ppu(&mut self) {
+        self.ppu.stall();
+        self.service_ppu();
+    }
+
+    /// Execute a single dot of the PPU, servicing its bus access, and start
+    /// the extra scanlines of the overclocking after the first dot of the
+    /// vertical blanking interval.
+    fn step_ppu(&mut self) {
+        let at = (self.ppu.scanline(), self.ppu.dot());
+        self.ppu.step();
+        self.service_ppu();
+
+        if at == (self.region.ppu().vblank_line(), 1) {
+            self.overclock.start(self.region);
+        }
+
+        if let Some(events) = &mut self.events {
+            events.observe_dot(at, &self.ppu, &*self.cartridge);
+        }
+    }
+
+    /// Execute until the PPU starts the next frame, returning the picture of
+    /// the completed frame and the audio samples produced since the previous
+    /// call.
+    pub fn run_frame(&mut self) -> Frame<'_> {
+        let phase = self.execute_frame();
+        self.end_frame(phase)
+    }
+
+    /// Execute a frame like [`Nes::run_frame`], without storing its picture,
+    /// e.g. for the frames skipped while fast-forwarding.
+    ///
+    /// The frame is emulated just the same, only the pixels aren't output,
+    /// so the returned picture is the last one output, see
+    /// [`Ppu::set_output`](chuck_ppu::Ppu::set_output).
+    pub fn run_frame_headless(&mut self) -> Frame<'_> {
+        self.ppu.set_output(false);
+        let phase = self.execute_frame();
+        self.ppu.set_output(true);
+        self.end_frame(phase)
+    }
+
+    /// Execute the frames until the given number of frames since power-up
+    /// (see [`Ppu::frame`](chuck_ppu::Ppu::frame)) is reached, as fast as
+    /// possible, returning the output of the last one, or `None` if the
+    /// number is already reached.
+    ///
+    /// Only the last frame is output, the others run headless, see
+    /// [`Nes::run_frame_headless`]. The audio samples of the last frame are
+    /// the only ones returned.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::Nes;
+    /// # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// assert!(nes.run_to_frame(10).is_some());
+    /// assert_eq!(nes.ppu().frame(), 10);
+    /// assert!(nes.run_to_frame(5).is_none());
+    /// ```
+    pub fn run_to_frame(&mut self, frame: u64) -> Option<Frame<'_>> {
+        if self.ppu.frame() >= frame {
+            return None;
+        }
+
+        while self.ppu.frame() + 1 < frame {
+            self.run_frame_headless();
+        }
+
+        Some(self.run_frame())
+    }
+
+    /// Execute until the PPU starts the next frame, returning the phase of the
+    /// completed frame.
+    fn execute_frame(&mut self) -> u8 {
+        self.start_frame();
+
+        let frame = self.ppu.frame();
+        let phase = self.ppu.frame_phase();
+        #[cfg(feature = "timing")]
+        let span = timing::frame_span(frame);
+        while self.ppu.frame() == frame {
+            self.step();
+        }
+
+        #[cfg(feature = "timing")]
+        if let Some(timer) = &mut self.timer {
+            timer.end_frame(&span);
+        }
+        phase
+    }
+
+    /// Discard the audio samples of the previous frame.
+    fn start_frame(&mut self) {
+        self.samples.clear();
+        if let Some(channels) = &mut self.channels {
+            channels.clear();
+        }
+        if let Some(stereo) = &mut self.stereo {
+            stereo.clear();
+        }
+        if let Some(events) = &mut self.events {
+            events.clear();
+        }
+    }
+
+    /// Return the output of the frame that just completed, which started with
+    /// the given phase.
+    fn end_frame(&mut self, phase: u8) -> Frame<'_> {
+        self.sram.end_frame();
+        self.finish_changes();
+
+        Frame {
+            pixels: self.ppu.frame_buffer(),
+            generation: self.ppu.generation(),
+            samples: &self.samples,
+            channels: self.channels.as_deref().unwrap_or_default(),
+            stereo: self.stereo.as_deref().unwrap_or_default(),
+            events: self.events.as_ref().map_or(&[], Recorder::events),
+            phase,
+        }
+    }
+
+    /// Enable or disable capturing the audio samples of the individual
+    /// channels, see [`Frame::channels`]. This is disabled by default, since
+    /// it's only needed to record or analyze the channels.
+    pub fn capture_channels(&mut self, enabled: bool) {
+        self.channels = enabled.then(Vec::new);
+    }
+
+    /// Enable or disable capturing the left and right audio samples, see
+    /// [`Frame::stereo`]. This is disabled by default, since the console's
+    /// audio is mono, unless the channels are panned, see [`mixer`].
+    pub fn capture_stereo(&mut self, enabled: bool) {
+        self.stereo = enabled.then(Vec::new);
+    }
+
+    /// Return the volumes of the audio channels, see [`mixer`].
+    #[must_use]
+    pub const fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// Return the volumes of the audio channels mutably, e.g. to mute them,
+    /// see [`mixer`].
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    /// Enable or disable recording the events of the frames, see
+    /// [`Frame::events`]. This is disabled by default, since it's only
+    /// needed by event viewers.
+    pub fn record_events(&mut self, enabled: bool) {
+        self.events = enabled.then(|| Recorder::new(&*self.cartridge));
+    }
+
+    /// Enable or disable logging the writes to the registers of the sound
+    /// chips, see [`audio_log`]. This is disabled by default, since it's only
+    /// needed to export the music.
+    ///
+    /// Enabling the logging again discards the logged writes.
+    pub fn log_audio(&mut self, enabled: bool) {
+        self.audio_log = enabled.then(Box::default);
+    }
+
+    /// Return the log of the writes to the registers of the sound chips, if
+    /// they're logged.
+    #[must_use]
+    pub fn audio_log(&self) -> Option<&AudioLog> {
+        self.audio_log.as_deref()
+    }
+
+    /// Return the log of the writes to the registers of the sound chips
+    /// mutably, if they're logged, e.g. to clear it.
+    pub fn audio_log_mut(&mut self) -> Option<&mut AudioLog> {
+        self.audio_log.as_deref_mut()
+    }
+
+    /// Plug the EPSM into the expansion port, or unplug it, see [`epsm`].
+    /// Homebrew games whose music needs it write it at `$401C`-`$401F`,
+    /// which are unused otherwise.
+    ///
+    /// Plugging the EPSM in again powers it on again.
+    pub fn plug_epsm(&mut self, plugged: bool) {
+        self.epsm = plugged.then(|| Box::new(Epsm::new(self.region)));
+    }
+
+    /// Return the EPSM, if it's plugged into the expansion port.
+    #[must_use]
+    pub fn epsm(&self) -> Option<&Epsm> {
+        self.epsm.as_deref()
+    }
+
+    /// Return the names of the channels of the expansion audio, see
+    /// [`Channel::Expansion`](mixer::Channel::Expansion): the ones of the
+    /// cartridge (see [`Mapper::audio_channels`]), followed by the ones of
+    /// the EPSM if it's plugged in.
+    #[must_use]
+    pub fn audio_channels(&self) -> Vec<&'static str> {
+        let mut channels = self.cartridge.audio_channels().to_vec();
+        if self.epsm.is_some() {
+            channels.extend(Epsm::CHANNELS);
+        }
+        channels
+    }
+
+    /// Enable or disable profiling the CPU, see [`profile`]. This is disabled
+    /// by default, since it's only needed to optimize programs.
+    ///
+    /// Enabling the profiling again discards the counted cycles.
+    pub fn profile_cpu(&mut self, enabled: bool) {
+        self.profiler = enabled.then(Box::default);
+    }
+
+    /// Return the profiler of the CPU, if it's profiled.
+    #[must_use]
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Return the profiler of the CPU mutably, if it's profiled, e.g. to
+    /// clear it.
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_deref_mut()
+    }
+
+    /// Enable or disable watching the pattern tables for the changes which
+    /// make cached tiles stale, see [`chr`]. This is disabled by default,
+    /// since it's only needed by renderers which cache tiles.
+    ///
+    /// Enabling the watching again discards the collected changes.
+    pub fn watch_chr(&mut self, enabled: bool) {
+        self.chr = enabled.then(|| Box::new(ChrWatcher::new(&*self.cartridge)));
+    }
+
+    /// Take the changes of the pattern tables since they were last taken, if
+    /// they're watched, see [`Nes::watch_chr`].
+    ///
+    /// Changes made through [`Nes::cartridge_mut`] aren't noticed. The cache
+    /// of [`Nes::ppu_vram_cached`] collects its own changes, so it takes none
+    /// of these.
+    pub fn take_chr_changes(&mut self) -> Option<ChrChanges> {
+        self.chr.as_mut().map(|chr| chr.take())
+    }
+
+    /// Enable or disable timing the subsystems of the console, see [`timing`].
+    /// This is disabled by default, since it's only needed to optimize the
+    /// emulator.
+    #[cfg(feature = "timing")]
+    pub fn time_subsystems(&mut self, enabled: bool) {
+        self.timer = enabled.then(Box::default);
+    }
+
+    /// Return the times of the subsystems during the last frame, if they're
+    /// timed and a frame completed since, see [`Nes::time_subsystems`].
+    #[cfg(feature = "timing")]
+    #[must_use]
+    pub fn frame_stats(&self) -> Option<&FrameStats> {
+        self.timer.as_ref().and_then(|timer| timer.last())
+    }
+
+    /// Report the time the frontend took to present the last frame, e.g. to
+    /// convert its colors and upload it, which completes the
+    /// [`Nes::frame_stats`].
+    #[cfg(feature = "timing")]
+    pub fn record_presentation(&mut self, time: std::time::Duration) {
+        if let Some(timer) = &mut self.timer {
+            timer.record_presentation(time);
+        }
+    }
+
+    /// Overclock the CPU by the given number of extra scanlines after the
+    /// start of every vertical blanking interval, during which only the CPU
+    /// runs, e.g. to reduce the slowdown of games. The NMI handler of a game
+    /// gets the extra time, while the rest of the frame, the audio and the
+    /// DMA keep their timing. This is disabled (0) by default, since it
+    /// breaks games which depend on the length of the vertical blanking
+    /// interval.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::Nes;
+    /// # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// nes.set_overclock(100);
+    /// nes.run_frame();
+    /// let cycles = nes.cpu().cycles;
+    /// let frame = nes.run_frame();
+    ///
+    /// // The frame has the samples of a frame without overclocking, but the
+    /// // CPU ran for about 100 scanlines of 113.67 cycles more.
+    /// assert_eq!(frame.samples.len(), 29_781);
+    /// assert_eq!(nes.cpu().cycles - cycles, 29_781 + 11_368);
+    /// ```
+    pub fn set_overclock(&mut self, scanlines: u16) {
+        self.overclock.set_scanlines(scanlines);
+    }
+
+    /// Return the number of extra scanlines of the overclocking, see
+    /// [`Nes::set_overclock`].
+    #[must_use]
+    pub const fn overclock(&self) -> u16 {
+        self.overclock.scanlines()
+    }
+
+    /// Return the region of the console.
+    #[must_use]
+    pub const fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Return the CPU.
+    #[must_use]
+    pub const fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// Return the APU.
+    #[must_use]
+    pub const fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    /// Return the PPU.
+    #[must_use]
+    pub const fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    /// Save the picture of the last frame as a PNG file, in the colors of the
+    /// given palette, e.g. to keep the picture of a failed test.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::Nes;
+    /// # use chuck_video::palette::Palette;
+    /// # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// nes.run_frame();
+    /// let mut file = Vec::new();
+    /// nes.save_screenshot(&Palette::default(), &mut file).unwrap();
+    /// assert_eq!(file[1..4], *b"PNG");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save_screenshot(&self, palette: &Palette, mut writer: &mut dyn Write) -> io::Result<()> {
+        let mut picture = vec![0; WIDTH * HEIGHT];
+        palette.apply(self.ppu.frame_buffer(), &mut picture);
+        png::write(&mut writer, WIDTH, HEIGHT, &picture)
+    }
+
+    /// Return the 2 KiB of RAM of the CPU.
+    #[must_use]
+    pub fn ram(&self) -> &[u8; 0x800] {
+        &self.ram
+    }
+
+    /// Return the 2 KiB of nametable RAM (CIRAM) of the PPU, which is
+    /// arranged in the nametables by the cartridge, see
+    /// [`Mapper::mirroring`].
+    #[must_use]
+    pub fn ciram(&self) -> &[u8; 0x800] {
+        &self.ciram
+    }
+
+    /// Return the controller ports.
+    #[must_use]
+    pub const fn input(&self) -> &Ports {
+        &self.input
+    }
+
+    /// Return the controller ports mutably, e.g. to plug in devices or to set
+    /// the state of their buttons, or to replace them with those of a
+    /// session for 4 players, see [`Ports::four_score`].
+    pub fn input_mut(&mut self) -> &mut Ports {
+        &mut self.input
+    }
+
+    /// Return the battery-backed PRG-RAM (save RAM) of the cartridge, or
+    /// `None` if it has none.
+    #[must_use]
+    pub fn sram(&self) -> Option<&[u8]> {
+        self.cartridge.battery_ram()
+    }
+
+    /// Check if the save RAM was written since it was last saved or loaded.
+    #[must_use]
+    pub fn is_sram_dirty(&self) -> bool {
+        self.sram.dirty && self.sram().is_some()
+    }
+
+    /// Check if the save RAM should be saved now, according to the flush
+    /// policy.
+    #[must_use]
+    pub fn sram_needs_flush(&self) -> bool {
+        self.sram.needs_flush() && self.sram().is_some()
+    }
+
+    /// Return the policy for flushing the save RAM.
+    #[must_use]
+    pub const fn sram_flush_policy(&self) -> FlushPolicy {
+        self.sram.policy
+    }
+
+    /// Set the policy for flushing the save RAM, see
+    /// [`Nes::sram_needs_flush`].
+    pub fn set_sram_flush_policy(&mut self, policy: FlushPolicy) {
+        self.sram.policy = policy;
+    }
+
+    /// Save the save RAM in the format of `.sav` files, i.e. its raw contents,
+    /// which saves nothing if the cartridge has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save_sram(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        if let Some(ram) = self.cartridge.battery_ram() {
+            writer.write_all(ram)?;
+        }
+
+        self.sram.clean();
+        Ok(())
+    }
+
+    /// Load the save RAM from a `.sav` file, see [`Nes::save_sram`].
+    ///
+    /// A file shorter than the save RAM only fills its beginning, for files
+    /// of other emulators that only save the first 8 KiB.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the file is larger than the save RAM.
+    pub fn load_sram(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let ram = self.cartridge.battery_ram_mut().unwrap_or_default();
+        if data.len() > ram.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "save ram file larger than the save ram",
+            ));
+        }
+
+        ram[..data.len()].copy_from_slice(&data);
+        self.sram.clean();
+        Ok(())
+    }
+
+    /// Return the cheats.
+    #[must_use]
+    pub const fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    /// Return the cheats mutably, e.g. to add, enable or disable them, which
+    /// takes effect with the next read of the CPU bus.
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    /// Return the board of the inserted cartridge.
+    #[must_use]
+    pub fn cartridge(&self) -> &dyn Mapper {
+        &*self.cartridge
+    }
+
+    /// Return the board of the inserted cartridge, mutably.
+    #[must_use]
+    pub fn cartridge_mut(&mut self) -> &mut dyn Mapper {
+        &mut *self.cartridge
+    }
+}
@@ -0,0 +1,471 @@
+//! The mappers, i.e. the boards of the cartridges.
+//!
+//! A cartridge is connected to both the CPU bus (`$4020`-`$FFFF`) and the PPU
+//! bus (`$0000`-`$3EFF`), and its board decides what is mapped where: a fixed
+//! PRG-ROM and CHR-ROM for the simplest boards, or bank switching, RAM,
+//! interrupts and even audio for the more complex ones. Every board is
+//! implemented as a [`Mapper`], which the system calls on every access of
+//! either bus, much like the chips only see the accesses on their pins.
+//!
+//! While the nametable RAM (CIRAM) is part of the console, its `A10` pin (and
+//! thereby the arrangement of the nametables) is controlled by the cartridge,
+//! see [`Mapper::mirroring`].
+//!
+//! Besides the accesses, boards may watch the PPU bus on every dot, see
+//! [`PpuBus`]: the fetches of the PPU (e.g. the MMC2 latches the banks on the
+//! fetches of certain tiles, and the MMC5 detects scanlines by repeated
+//! nametable fetches) and the changes of `A12` with the time the line was
+//! held before (e.g. the MMC3 counts the rises of `A12` after it was low for
+//! a while).
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Mapper>
+//! - <https://www.nesdev.org/wiki/Cartridge_connector>
+//! - <https://www.nesdev.org/wiki/Mirroring#Nametable_Mirroring>
+
+mod axrom;
+mod cnrom;
+mod datach;
+mod game_genie;
+mod gxrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+mod vrc6;
+
+pub use axrom::Axrom;
+pub use cnrom::Cnrom;
+pub use datach::barcode::{Barcode, BarcodeReader, InvalidBarcode};
+pub use datach::Datach;
+pub use game_genie::{GameGenie, FIRMWARE_SIZE};
+pub use gxrom::Gxrom;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+pub use uxrom::Uxrom;
+pub use vrc6::Vrc6;
+
+pub(crate) use vrc6::audio::Audio as Vrc6Audio;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_ppu::Access;
+use chuck_rom::Rom;
+
+/// The arrangement of the console's 2 KiB of nametable RAM (CIRAM) within the
+/// four nametables of the PPU's address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /// The nametables are arranged vertically, i.e. `$2000` mirrors `$2400`
+    /// and `$2800` mirrors `$2C00`, for vertical scrolling.
+    Horizontal,
+    /// The nametables are arranged horizontally, i.e. `$2000` mirrors `$2800`
+    /// and `$2400` mirrors `$2C00`, for horizontal scrolling.
+    Vertical,
+    /// All nametables mirror the first 1 KiB of the CIRAM.
+    SingleLow,
+    /// All nametables mirror the second 1 KiB of the CIRAM.
+    SingleHigh,
+}
+
+impl Mirroring {
+    /// Return the address within the CIRAM of the given nametable address.
+    #[must_use]
+    pub const fn ciram_addr(self, addr: u16) -> u16 {
+        match self {
+            Self::Horizontal => ((addr >> 1) & 0x0400) | (addr & 0x03ff),
+            Self::Vertical => addr & 0x07ff,
+            Self::SingleLow => addr & 0x03ff,
+            Self::SingleHigh => 0x0400 | (addr & 0x03ff),
+        }
+    }
+}
+
+impl From<chuck_rom::Mirroring> for Mirroring {
+    /// Convert the hardwired arrangement of a ROM's header.
+    ///
+    /// The four-screen arrangement needs extra VRAM on the cartridge, which
+    /// isn't supported, so it falls back to the vertical arrangement.
+    fn from(mirroring: chuck_rom::Mirroring) -> Self {
+        match mirroring {
+            chuck_rom::Mirroring::Horizontal => Self::Horizontal,
+            chuck_rom::Mirroring::Vertical | chuck_rom::Mirroring::FourScreen => Self::Vertical,
+        }
+    }
+}
+
+/// The bus conflicts of a board, i.e. what a register sees when it is written
+/// at an address of the PRG-ROM.
+///
+/// The simplest boards don't disable their PRG-ROM during writes, so both the
+/// CPU and the ROM drive the data bus. As a driven 0 wins on the bus, the
+/// register receives the AND of the written value and the byte of the ROM,
+/// and games for these boards write values that match the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BusConflicts {
+    /// The register receives the written value, as if the ROM were disabled.
+    #[default]
+    Disabled,
+    /// The register receives the AND of the written value and the ROM byte.
+    And,
+}
+
+impl BusConflicts {
+    /// Return the bus conflicts specified by the submapper number of a NES 2.0
+    /// header, which are only emulated if explicitly specified.
+    #[must_use]
+    pub const fn from_submapper(submapper: u8) -> Self {
+        if submapper == 2 {
+            Self::And
+        } else {
+            Self::Disabled
+        }
+    }
+
+    /// Return the value a register receives for the given written value and
+    /// the byte of the ROM at the written address.
+    #[must_use]
+    pub const fn apply(self, data: u8, rom: u8) -> u8 {
+        match self {
+            Self::Disabled => data,
+            Self::And => data & rom,
+        }
+    }
+}
+
+/// A change of the `A12` line of the PPU bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// `A12` rose, i.e. the PPU accesses the upper pattern table.
+    Rise,
+    /// `A12` fell.
+    Fall,
+}
+
+/// The PPU bus on a dot, as watched by a board, see [`Mapper::ppu_bus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpuBus {
+    /// The address on the bus, `$0000`-`$3FFF`, which the PPU holds between
+    /// its accesses.
+    pub addr: u16,
+    /// The access of the dot, where the reads are the fetches of the PPU.
+    pub access: Access,
+    /// The change of `A12` on this dot, if it changed.
+    pub edge: Option<Edge>,
+    /// The number of dots `A12` was held at its last level before this dot,
+    /// e.g. how long it was low before it rose, saturating at `u32::MAX`.
+    pub held: u32,
+    /// The scanline of the PPU, see [`Ppu::scanline`](chuck_ppu::Ppu::scanline).
+    pub scanline: u16,
+    /// The dot of the PPU within the scanline, see
+    /// [`Ppu::dot`](chuck_ppu::Ppu::dot).
+    pub dot: u16,
+}
+
+/// The watcher of the `A12` line of the PPU bus, which times its changes for
+/// [`PpuBus`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct A12 {
+    /// A flag denoting if `A12` is high.
+    high: bool,
+    /// The number of dots `A12` was held at its level.
+    dots: u32,
+}
+
+impl A12 {
+    /// Observe the address on the PPU bus on a dot, returning the change of
+    /// `A12` and the number of dots it was held at its last level.
+    pub(crate) fn observe(&mut self, addr: u16) -> (Option<Edge>, u32) {
+        let high = addr & 0x1000 != 0;
+        let held = self.dots;
+
+        if high == self.high {
+            self.dots = self.dots.saturating_add(1);
+            return (None, held);
+        }
+
+        self.high = high;
+        self.dots = 1;
+        (Some(if high { Edge::Rise } else { Edge::Fall }), held)
+    }
+
+    /// Save the state of the watcher.
+    pub(crate) fn save(self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(self.high)])?;
+        writer.write_all(&self.dots.to_le_bytes())
+    }
+
+    /// Load the state of the watcher saved by [`A12::save`].
+    pub(crate) fn load(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut state = [0; 5];
+        reader.read_exact(&mut state)?;
+
+        Ok(Self {
+            high: state[0] != 0,
+            dots: u32::from_le_bytes([state[1], state[2], state[3], state[4]]),
+        })
+    }
+}
+
+/// The board of a cartridge.
+///
+/// The system calls [`Mapper::cpu_read`] or [`Mapper::cpu_write`] for every
+/// access of the CPU to `$4020`-`$FFFF`, and [`Mapper::ppu_read`] or
+/// [`Mapper::ppu_write`] for every access of the PPU to the pattern tables,
+/// exactly on the cycle (or dot) of the access. Debuggers use
+/// [`Mapper::cpu_peek`] and [`Mapper::cpu_poke`] instead, which touch only
+/// the memory of the board.
+pub trait Mapper: fmt::Debug + Send {
+    /// Read from the CPU bus at `$4020`-`$FFFF` without side effects,
+    /// returning `None` if the cartridge doesn't drive the data bus at the
+    /// given address.
+    fn cpu_peek(&self, addr: u16) -> Option<u8>;
+
+    /// Read from the CPU bus at `$4020`-`$FFFF`, returning `None` if the
+    /// cartridge doesn't drive the data bus at the given address, which then
+    /// reads back the last value on the bus.
+    ///
+    /// This is [`Mapper::cpu_peek`] by default, for boards whose reads have no
+    /// side effects.
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        self.cpu_peek(addr)
+    }
+
+    /// Write to the CPU bus at `$4020`-`$FFFF`.
+    fn cpu_write(&mut self, addr: u16, data: u8);
+
+    /// Write the memory that [`Mapper::cpu_peek`] reads at the given address,
+    /// i.e. the PRG-RAM or the byte of the PRG-ROM in the current bank, without
+    /// the side effects of a write of the CPU on the registers of the board
+    /// and regardless of its write protection. Addresses that read no memory
+    /// are ignored.
+    ///
+    /// The PRG-ROM isn't part of the state of the board, so a patched byte
+    /// stays patched until the cartridge is removed. The clones of a board
+    /// share its ROMs, see [`Mapper::boxed_clone`], so the first patch of a
+    /// shared PRG-ROM copies it.
+    fn cpu_poke(&mut self, addr: u16, data: u8);
+
+    /// Return the offset into the PRG-ROM that the given address of the CPU
+    /// bus reads with the current banks, or `None` if it doesn't read the
+    /// PRG-ROM, e.g. to resolve the labels of a program across banks, see
+    /// [`symbols`](crate::symbols).
+    fn prg_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// Read from the pattern tables of the PPU bus, at `$0000`-`$1FFF`.
+    fn ppu_read(&mut self, addr: u16) -> u8;
+
+    /// Write to the pattern tables of the PPU bus, at `$0000`-`$1FFF`.
+    fn ppu_write(&mut self, addr: u16, data: u8);
+
+    /// Return the offset into the CHR memory (see [`Mapper::chr`]) that the
+    /// given address of the pattern tables reads with the current banks, or
+    /// `None` if it doesn't read the CHR memory, e.g. for renderers which
+    /// cache decoded tiles, see [`chr`](crate::chr).
+    ///
+    /// The banks must have at least 1 KiB, and may only change in
+    /// [`Mapper::cpu_write`] and [`Mapper::load`], while the CHR-RAM may only
+    /// change in [`Mapper::ppu_write`] and [`Mapper::load`], which is when the
+    /// console looks for changes.
+    fn chr_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// Return the CHR memory of the board, i.e. its CHR-ROM or CHR-RAM, which
+    /// [`Mapper::chr_offset`] indexes.
+    fn chr(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Watch the PPU bus, for boards that observe its fetches or its `A12`
+    /// line, see [`PpuBus`].
+    ///
+    /// This is called on every dot before [`Mapper::ppu_read`] or
+    /// [`Mapper::ppu_write`], including the dots without access, as the PPU
+    /// holds the address on the bus between accesses.
+    fn ppu_bus(&mut self, _bus: &PpuBus) {}
+
+    /// Return the current arrangement of the nametables.
+    fn mirroring(&self) -> Mirroring;
+
+    /// Check if the cartridge asserts the CPU's `IRQ` line.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Return the number of times the IRQ counter of the board was clocked
+    /// (wrapping around), for boards with one, e.g. for the event viewers of
+    /// [`events`](crate::events).
+    ///
+    /// The number isn't part of the state of the board.
+    fn irq_clocks(&self) -> u64 {
+        0
+    }
+
+    /// Execute a single CPU cycle, for boards that count cycles (e.g. for
+    /// interrupts) or need to know that time passed between two writes.
+    fn clock(&mut self) {}
+
+    /// Return the current output of the board's sound chip, in the units of
+    /// [`Output::mix`](chuck_apu::Output::mix), for boards with expansion
+    /// audio.
+    fn audio(&self) -> f32 {
+        0.0
+    }
+
+    /// Return the names of the channels of the board's sound chip, whose
+    /// volumes the [`Mixer`](crate::mixer::Mixer) sets, for boards with
+    /// expansion audio.
+    fn audio_channels(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Return the current output of a channel of the board's sound chip, see
+    /// [`Mapper::audio_channels`], in the units of [`Mapper::audio`], or `0.0`
+    /// if there's no such channel. The outputs of the channels add up to the
+    /// output of the sound chip.
+    fn channel_audio(&self, _channel: usize) -> f32 {
+        0.0
+    }
+
+    /// Check if a write to the given address (`$4020`-`$FFFF`) reaches a
+    /// register of the board's sound chip, for boards with expansion audio,
+    /// see [`audio_log`](crate::audio_log).
+    fn is_audio_register(&self, _addr: u16) -> bool {
+        false
+    }
+
+    /// Return the barcode reader of the board mutably, for boards with one,
+    /// e.g. to read the barcode of a card, see [`Datach`].
+    fn barcode_reader_mut(&mut self) -> Option<&mut BarcodeReader> {
+        None
+    }
+
+    /// Return the battery-backed PRG-RAM of the board, whose contents are
+    /// kept while the console is off, or `None` if it has none.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Return the battery-backed PRG-RAM of the board mutably, see
+    /// [`Mapper::battery_ram`].
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Save the state of the board, i.e. its registers and RAM, but not its
+    /// ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Load the state of the board saved by [`Mapper::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed.
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()>;
+
+    /// Clone the board into a new box, see the `Clone` implementation of
+    /// `Box<dyn Mapper>`.
+    ///
+    /// The clone shares the PRG-ROM and the CHR memory of the board, which
+    /// are only copied once either board writes them, i.e. the CHR-RAM or a
+    /// patch by [`Mapper::cpu_poke`].
+    fn boxed_clone(&self) -> Box<dyn Mapper>;
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// An error returned for a ROM whose board isn't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedMapper {
+    /// The mapper number of the ROM.
+    pub mapper: u16,
+    /// The submapper number of the ROM.
+    pub submapper: u8,
+}
+
+impl fmt::Display for UnsupportedMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported mapper {}", self.mapper)?;
+
+        if self.submapper != 0 {
+            write!(f, ".{}", self.submapper)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnsupportedMapper {}
+
+/// Create the board of the given ROM.
+///
+/// # Errors
+///
+/// Returns an error if the mapper of the ROM isn't supported.
+pub fn from_rom(rom: &Rom) -> Result<Box<dyn Mapper>, UnsupportedMapper> {
+    let header = rom.header();
+
+    match header.mapper {
+        0 => Ok(Box::new(Nrom::from_rom(rom))),
+        1 => Ok(Box::new(Mmc1::from_rom(rom))),
+        2 => Ok(Box::new(Uxrom::from_rom(rom))),
+        3 => Ok(Box::new(Cnrom::from_rom(rom))),
+        // The MMC6 and the MC-ACC differ in their PRG-RAM and IRQ counter.
+        4 if matches!(header.submapper, 0 | 4) => Ok(Box::new(Mmc3::from_rom(rom))),
+        7 => Ok(Box::new(Axrom::from_rom(rom))),
+        24 | 26 => Ok(Box::new(Vrc6::from_rom(rom))),
+        66 => Ok(Box::new(Gxrom::from_rom(rom))),
+        157 => Ok(Box::new(Datach::from_rom(rom))),
+        mapper => Err(UnsupportedMapper {
+            mapper,
+            submapper: header.submapper,
+        }),
+    }
+}
+
+/// Return the size of the PRG-RAM of a ROM, volatile or not.
+fn prg_ram_size(rom: &Rom) -> usize {
+    rom.header().prg_ram_size + rom.header().prg_nvram_size
+}
+
+/// Check if the PRG-RAM of a ROM is battery-backed, i.e. if it has any
+/// non-volatile PRG-RAM.
+fn has_battery(rom: &Rom) -> bool {
+    rom.header().prg_nvram_size != 0
+}
+
+/// Return the CHR memory of a ROM, i.e. its CHR-ROM, or zeroed CHR-RAM if it
+/// has none, together with a flag denoting if it is CHR-RAM.
+fn chr_memory(rom: &Rom) -> (Arc<[u8]>, bool) {
+    if rom.chr().is_empty() {
+        let size = rom.header().chr_ram_size + rom.header().chr_nvram_size;
+        (vec![0; size.max(0x2000)].into(), true)
+    } else {
+        (rom.chr().into(), false)
+    }
+}
+
+/// Return the index into a banked memory of the given address, within the
+/// given bank of the given size.
+///
+/// The bank number wraps around the size of the memory, as the unused upper
+/// bits of a bank register aren't connected.
+fn bank(memory: &[u8], bank: usize, size: usize, addr: u16) -> usize {
+    (bank * size + usize::from(addr) % size) % memory.len()
+}
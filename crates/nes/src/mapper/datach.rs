@@ -0,0 +1,298 @@
+//! The Bandai Datach Joint ROM System (mapper 157), a base unit with a
+//! barcode reader, into which the sub-cartridges of the games are plugged.
+//!
+//! The base unit has a Bandai LZ93D50 and a 24C02 EEPROM, the sub-cartridge
+//! the PRG-ROM and, for some games, an X24C01 EEPROM. A ROM of mapper 157 is
+//! the dump of a sub-cartridge, so its board is the base unit with the
+//! sub-cartridge plugged in. The registers are selected by the lowest four
+//! address lines:
+//!
+//! ```no-run
+//! $8000-$8003      X24C01 clock ----C---  SCL of the X24C01
+//! $8008            PRG bank     ----PPPP  16 KiB bank at $8000
+//! $8009            Mirroring    ------MM  Vertical, horizontal, single screen
+//! $800A            IRQ control  -------E  Enable, reload and acknowledge
+//! $800B-$800C      IRQ latch    LLLLLLLL  The 16-bit reload value
+//! $800D            EEPROM       -DC-----  SDA of both, SCL of the 24C02
+//! $6000-$7FFF (R)  Input        ---EB---  SDA of the EEPROMs, barcode
+//! ```
+//!
+//! Most games show nothing useful until a card is read, see
+//! [`Mapper::barcode_reader_mut`] and [`BarcodeReader`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/INES_Mapper_157>
+//! - <https://www.nesdev.org/wiki/Bandai_FCG_board>
+
+pub mod barcode;
+mod eeprom;
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{bank, prg_ram_size, Mapper, Mirroring};
+use barcode::BarcodeReader;
+use eeprom::{Chip, Eeprom};
+
+/// The IRQ counter of the LZ93D50, which counts CPU cycles down.
+#[derive(Debug, Clone, Default)]
+struct Counter {
+    /// The reload value of the counter.
+    latch: u16,
+    /// The value of the counter.
+    value: u16,
+    /// A flag denoting if the counter is enabled.
+    enabled: bool,
+    /// A flag denoting if the IRQ is asserted.
+    irq: bool,
+    /// The number of clocks, see [`Mapper::irq_clocks`].
+    clocks: u64,
+}
+
+impl Counter {
+    /// Execute a single CPU cycle, asserting the IRQ when the counter is 0
+    /// before it's decremented.
+    fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        self.clocks = self.clocks.wrapping_add(1);
+        if self.value == 0 {
+            self.irq = true;
+        }
+        self.value = self.value.wrapping_sub(1);
+    }
+}
+
+/// The Datach base unit, with a sub-cartridge plugged in.
+///
+/// The PRG-ROM is mapped as a switchable 16 KiB bank, followed by the last
+/// 16 KiB fixed at `$C000`. The pattern tables are 8 KiB of CHR-RAM.
+#[derive(Debug, Clone)]
+pub struct Datach {
+    /// The PRG-ROM of the sub-cartridge.
+    prg: Arc<[u8]>,
+    /// The CHR-RAM.
+    chr: Arc<[u8]>,
+    /// The memory of the 24C02, followed by the memory of the X24C01, if
+    /// any.
+    memory: Box<[u8]>,
+
+    /// The PRG-ROM bank register.
+    prg_bank: u8,
+    /// The mirroring register.
+    mirroring: u8,
+    /// The IRQ counter.
+    counter: Counter,
+    /// The 24C02 of the base unit.
+    eeprom: Eeprom,
+    /// The X24C01 of the sub-cartridge, if any.
+    external: Option<Eeprom>,
+    /// The barcode reader.
+    reader: BarcodeReader,
+}
+
+impl Datach {
+    /// Create the board of the given ROM, whose sub-cartridge has an X24C01
+    /// if the ROM has more than 256 bytes of PRG-RAM, as the EEPROMs hold the
+    /// saves.
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let external = prg_ram_size(rom) > Chip::C24C02.size();
+        let size = Chip::C24C02.size() + if external { Chip::X24C01.size() } else { 0 };
+
+        Self {
+            prg: rom.prg().into(),
+            chr: vec![0; 0x2000].into(),
+            memory: vec![0; size].into_boxed_slice(),
+            prg_bank: 0,
+            mirroring: 0,
+            counter: Counter::default(),
+            eeprom: Eeprom::new(Chip::C24C02),
+            external: external.then(|| Eeprom::new(Chip::X24C01)),
+            reader: BarcodeReader::default(),
+        }
+    }
+
+    /// Return the index into the PRG-ROM of the given address.
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank_number = if addr < 0xc000 {
+            usize::from(self.prg_bank)
+        } else {
+            self.prg.len() / 0x4000 - 1
+        };
+
+        bank(&self.prg, bank_number, 0x4000, addr)
+    }
+
+    /// Return the input at `$6000`-`$7FFF`: the `SDA` of the EEPROMs, which
+    /// pull it low together, and the barcode reader.
+    fn input(&self) -> u8 {
+        let sda = self.eeprom.output() && self.external.as_ref().is_none_or(Eeprom::output);
+        (u8::from(sda) << 4) | self.reader.output()
+    }
+}
+
+impl Mapper for Datach {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff => Some(self.input()),
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if addr < 0x8000 {
+            return;
+        }
+
+        let (memory, external) = self.memory.split_at_mut(Chip::C24C02.size());
+        match addr & 0x0f {
+            0x00..=0x03 => {
+                if let Some(eeprom) = &mut self.external {
+                    eeprom.set_scl(external, data & 0x08 != 0);
+                }
+            }
+            0x08 => self.prg_bank = data & 0x0f,
+            0x09 => self.mirroring = data & 0x03,
+            0x0a => {
+                let counter = &mut self.counter;
+                counter.enabled = data & 1 != 0;
+                counter.value = counter.latch;
+                counter.irq = false;
+            }
+            0x0b => self.counter.latch = (self.counter.latch & 0xff00) | u16::from(data),
+            0x0c => self.counter.latch = (self.counter.latch & 0x00ff) | (u16::from(data) << 8),
+            0x0d => {
+                let sda = data & 0x40 != 0;
+                self.eeprom.write(memory, data & 0x20 != 0, sda);
+                if let Some(eeprom) = &mut self.external {
+                    eeprom.set_sda(external, sda);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        if addr >= 0x8000 {
+            let i = self.prg_index(addr);
+            Arc::make_mut(&mut self.prg)[i] = data;
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| self.prg_index(addr))
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr & 0x1fff)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        Arc::make_mut(&mut self.chr)[usize::from(addr & 0x1fff)] = data;
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(usize::from(addr & 0x1fff))
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleLow,
+            _ => Mirroring::SingleHigh,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.counter.irq
+    }
+
+    fn irq_clocks(&self) -> u64 {
+        self.counter.clocks
+    }
+
+    fn clock(&mut self) {
+        self.counter.clock();
+        self.reader.clock();
+    }
+
+    fn barcode_reader_mut(&mut self) -> Option<&mut BarcodeReader> {
+        Some(&mut self.reader)
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        Some(&self.memory)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.memory)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let counter = &self.counter;
+        writer.write_all(&[self.prg_bank, self.mirroring])?;
+        writer.write_all(&counter.latch.to_le_bytes())?;
+        writer.write_all(&counter.value.to_le_bytes())?;
+        writer.write_all(&[u8::from(counter.enabled), u8::from(counter.irq)])?;
+
+        self.eeprom.save(writer)?;
+        if let Some(eeprom) = &self.external {
+            eeprom.save(writer)?;
+        }
+        self.reader.save(writer)?;
+
+        writer.write_all(&self.memory)?;
+        writer.write_all(&self.chr)
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut regs = [0; 8];
+        reader.read_exact(&mut regs)?;
+
+        let mut eeprom = self.eeprom.clone();
+        eeprom.load(reader)?;
+        let mut external = self.external.clone();
+        if let Some(external) = &mut external {
+            external.load(reader)?;
+        }
+        let mut barcode_reader = BarcodeReader::default();
+        barcode_reader.load(reader)?;
+
+        let mut memory = vec![0; self.memory.len()];
+        reader.read_exact(&mut memory)?;
+        let mut chr = vec![0; self.chr.len()];
+        reader.read_exact(&mut chr)?;
+
+        self.prg_bank = regs[0] & 0x0f;
+        self.mirroring = regs[1] & 0x03;
+        self.counter = Counter {
+            latch: u16::from_le_bytes([regs[2], regs[3]]),
+            value: u16::from_le_bytes([regs[4], regs[5]]),
+            enabled: regs[6] != 0,
+            irq: regs[7] != 0,
+            clocks: self.counter.clocks,
+        };
+        self.eeprom = eeprom;
+        self.external = external;
+        self.reader = barcode_reader;
+        self.memory = memory.into_boxed_slice();
+        self.chr = chr.into();
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
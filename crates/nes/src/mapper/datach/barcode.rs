@@ -0,0 +1,294 @@
+//! The barcode reader of the Datach, which reads the EAN-13 and EAN-8
+//! barcodes of the cards swiped through it.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/INES_Mapper_157>
+//! - <https://en.wikipedia.org/wiki/International_Article_Number>
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// The number of CPU cycles the reader sends a module for.
+const MODULE_CYCLES: u32 = 1000;
+
+/// The number of spaces the reader sends before a barcode.
+const LEAD: usize = 33;
+
+/// The number of spaces the reader sends after a barcode.
+const TRAIL: usize = 32;
+
+/// The modules of the digits on the left side, with an odd parity (the
+/// `L` code), where a set bit is a bar, from the highest of 7 bits.
+const LEFT_ODD: [u8; 10] = [0x0d, 0x19, 0x13, 0x3d, 0x23, 0x31, 0x2f, 0x3b, 0x37, 0x0b];
+
+/// The parities of the 6 digits on the left side of an EAN-13 barcode, which
+/// encode its first digit, where a set bit is an even parity (the `G` code),
+/// from the highest of 6 bits.
+const PARITIES: [u8; 10] = [0x00, 0x0b, 0x0d, 0x0e, 0x13, 0x19, 0x1c, 0x15, 0x16, 0x1a];
+
+/// A barcode, given by its modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barcode {
+    /// The modules, where `true` is a bar, without the white around them.
+    modules: Vec<bool>,
+}
+
+impl Barcode {
+    /// Create a barcode of the given modules, where `true` is a bar, without
+    /// the white around them.
+    #[must_use]
+    pub const fn from_modules(modules: Vec<bool>) -> Self {
+        Self { modules }
+    }
+
+    /// Read an EAN-13 or EAN-8 barcode from a scanline of a picture of it,
+    /// i.e. the brightness of its pixels, which must cross all bars.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scanline has too little contrast, or not the
+    /// bars of an EAN-13 or EAN-8 barcode.
+    pub fn from_scanline(pixels: &[u8]) -> Result<Self, InvalidBarcode> {
+        let (min, max) = pixels
+            .iter()
+            .fold((u8::MAX, u8::MIN), |(min, max), &pixel| {
+                (min.min(pixel), max.max(pixel))
+            });
+        if max.saturating_sub(min) < 0x20 {
+            return Err(InvalidBarcode::Scanline);
+        }
+
+        let threshold = u8::midpoint(min, max);
+        let dark: Vec<bool> = pixels.iter().map(|&pixel| pixel < threshold).collect();
+        let first = dark.iter().position(|&dark| dark);
+        let last = dark.iter().rposition(|&dark| dark);
+        let (Some(first), Some(last)) = (first, last) else {
+            return Err(InvalidBarcode::Scanline);
+        };
+
+        let span = &dark[first..=last];
+        let bars = span
+            .iter()
+            .zip(span.iter().skip(1))
+            .filter(|&(&bar, &next)| bar && !next)
+            .count()
+            + 1;
+        let count = match bars {
+            30 => 95,
+            22 => 67,
+            _ => return Err(InvalidBarcode::Scanline),
+        };
+        if span.len() < count {
+            return Err(InvalidBarcode::Scanline);
+        }
+
+        // Every module is sampled at its center.
+        let modules = (0..count)
+            .map(|module| span[(2 * module + 1) * span.len() / (2 * count)])
+            .collect();
+        Ok(Self::from_modules(modules))
+    }
+
+    /// Return the modules, where `true` is a bar.
+    #[must_use]
+    pub fn modules(&self) -> &[bool] {
+        &self.modules
+    }
+
+    /// Encode the digits of an EAN-13 or EAN-8 barcode, with its check
+    /// digit.
+    fn encode(digits: &[u8]) -> Self {
+        let (first, digits) = if digits.len() == 13 {
+            (PARITIES[usize::from(digits[0])], &digits[1..])
+        } else {
+            (0, digits)
+        };
+        let half = digits.len() / 2;
+
+        let mut modules = vec![true, false, true];
+        let mut push = |code: u8| modules.extend((0..7).rev().map(|bit| code & (1 << bit) != 0));
+        for (i, &digit) in digits[..half].iter().enumerate() {
+            let odd = LEFT_ODD[usize::from(digit)];
+            // The G code is the reversed complement of the L code.
+            let even = (!odd & 0x7f).reverse_bits() >> 1;
+            push(if first & (0x20 >> i) == 0 { odd } else { even });
+        }
+        modules.extend([false, true, false, true, false]);
+        for &digit in &digits[half..] {
+            modules.extend(
+                (0..7)
+                    .rev()
+                    .map(|bit| LEFT_ODD[usize::from(digit)] & (1 << bit) == 0),
+            );
+        }
+        modules.extend([true, false, true]);
+
+        Self::from_modules(modules)
+    }
+}
+
+impl FromStr for Barcode {
+    type Err = InvalidBarcode;
+
+    /// Parse the digits of an EAN-13 or EAN-8 barcode, computing the check
+    /// digit of 12 or 7 digits, or checking it for 13 or 8 digits.
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidBarcode::Digits(code.to_owned());
+        let mut digits = code
+            .trim()
+            .chars()
+            .map(|digit| {
+                digit
+                    .to_digit(10)
+                    .and_then(|digit| u8::try_from(digit).ok())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+
+        let payload = match digits.len() {
+            12 | 13 => 12,
+            7 | 8 => 7,
+            _ => return Err(invalid()),
+        };
+        let check = check_digit(&digits[..payload]);
+        if digits.get(payload).is_some_and(|&digit| digit != check) {
+            return Err(invalid());
+        }
+        digits.truncate(payload);
+        digits.push(check);
+
+        Ok(Self::encode(&digits))
+    }
+}
+
+/// Return the check digit of the digits of an EAN barcode without it, whose
+/// digits are weighted by 3 and 1 alternately from the last one.
+fn check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .zip([3, 1].into_iter().cycle())
+        .map(|(&digit, weight)| u32::from(digit) * weight)
+        .sum();
+    u8::try_from((10 - sum % 10) % 10).unwrap_or_default()
+}
+
+/// An error returned for a barcode which isn't an EAN-13 or EAN-8 barcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidBarcode {
+    /// The digits of the barcode aren't 7, 8, 12 or 13 digits, or their check
+    /// digit is wrong.
+    Digits(String),
+    /// The scanline doesn't cross the bars of a barcode.
+    Scanline,
+}
+
+impl fmt::Display for InvalidBarcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Digits(code) => write!(f, "invalid barcode {code:?}"),
+            Self::Scanline => f.write_str("no barcode in the scanline"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidBarcode {}
+
+/// The barcode reader.
+///
+/// The reader sends the bars and spaces of a barcode (its modules) one by
+/// one, each for 1000 CPU cycles, framed by the white of the card. A barcode
+/// is given by its digits (see [`Barcode::from_str`]), by its modules (see
+/// [`Barcode::from_modules`]), or by a scanline of a picture of it (see
+/// [`Barcode::from_scanline`]):
+///
+/// ```
+/// # use chuck_nes::mapper::Barcode;
+/// let barcode: Barcode = "4905040352507".parse()?;
+/// assert_eq!(barcode.modules().len(), 95);
+///
+/// // A picture of the barcode, 3 pixels per module with a white margin.
+/// let mut scanline = vec![0xff; 20];
+/// for &bar in barcode.modules() {
+///     scanline.extend([if bar { 0x10 } else { 0xf0 }; 3]);
+/// }
+/// scanline.extend([0xff; 20]);
+/// assert_eq!(Barcode::from_scanline(&scanline)?, barcode);
+///
+/// // The check digit is added to 12 digits, and must match for 13 digits.
+/// assert_eq!("490504035250".parse::<Barcode>()?, barcode);
+/// assert!("4905040352508".parse::<Barcode>().is_err());
+/// # Ok::<(), chuck_nes::mapper::InvalidBarcode>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BarcodeReader {
+    /// The levels the reader sends, where `true` is white, or nothing if no
+    /// barcode is read.
+    levels: Vec<bool>,
+    /// The number of CPU cycles since the barcode started.
+    cycles: u32,
+}
+
+impl BarcodeReader {
+    /// Read a barcode, replacing the barcode being read, if any.
+    pub fn scan(&mut self, barcode: &Barcode) {
+        self.levels = [true; LEAD]
+            .into_iter()
+            .chain(barcode.modules.iter().map(|&bar| !bar))
+            .chain([true; TRAIL])
+            .collect();
+        self.cycles = 0;
+    }
+
+    /// Check if a barcode is being read.
+    #[must_use]
+    pub fn is_scanning(&self) -> bool {
+        !self.levels.is_empty()
+    }
+
+    /// Return the output of the reader, on bit 3 while white.
+    pub(crate) fn output(&self) -> u8 {
+        let module = usize::try_from(self.cycles / MODULE_CYCLES).unwrap_or(usize::MAX);
+        self.levels
+            .get(module)
+            .map_or(0, |&white| u8::from(white) << 3)
+    }
+
+    /// Execute a single CPU cycle.
+    pub(crate) fn clock(&mut self) {
+        if self.levels.is_empty() {
+            return;
+        }
+
+        self.cycles += 1;
+        let modules = u32::try_from(self.levels.len()).unwrap_or(u32::MAX);
+        if self.cycles / MODULE_CYCLES >= modules {
+            self.levels.clear();
+            self.cycles = 0;
+        }
+    }
+
+    /// Save the state of the reader.
+    pub(crate) fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let length = u16::try_from(self.levels.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "barcode too long"))?;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&self.cycles.to_le_bytes())?;
+        let levels: Vec<u8> = self.levels.iter().map(|&white| u8::from(white)).collect();
+        writer.write_all(&levels)
+    }
+
+    /// Load the state of the reader saved by [`BarcodeReader::save`].
+    pub(crate) fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 6];
+        reader.read_exact(&mut state)?;
+        let mut levels = vec![0; usize::from(u16::from_le_bytes([state[0], state[1]]))];
+        reader.read_exact(&mut levels)?;
+
+        self.levels = levels.into_iter().map(|level| level != 0).collect();
+        self.cycles = u32::from_le_bytes([state[2], state[3], state[4], state[5]]);
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+//! The serial EEPROMs of the Datach: the 24C02 of the base unit (256 bytes)
+//! and the X24C01 of some sub-cartridges (128 bytes), which are written and
+//! read bit by bit over the two lines of an I²C bus, the clock (`SCL`) and
+//! the data (`SDA`).
+//!
+//! A transfer starts when `SDA` falls while `SCL` is high, and stops when
+//! `SDA` rises while `SCL` is high. Otherwise, the bits on `SDA` are taken by
+//! the rises of `SCL`, 8 per byte, each byte followed by an acknowledge. The
+//! 24C02 expects a device address (`1010---R`) and then a word address, most
+//! significant bit first, while the X24C01 expects a 7-bit word address
+//! followed by the read bit, least significant bit first.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Bandai_FCG_board#Serial_EEPROM>
+
+use std::io::{self, Read, Write};
+
+/// The kind of an EEPROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip {
+    /// The X24C01, 128 bytes.
+    X24C01,
+    /// The 24C02, 256 bytes.
+    C24C02,
+}
+
+impl Chip {
+    /// Return the size of the memory of the chip.
+    pub const fn size(self) -> usize {
+        match self {
+            Self::X24C01 => 0x80,
+            Self::C24C02 => 0x100,
+        }
+    }
+}
+
+/// The step of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// No transfer.
+    Idle,
+    /// The device address of the 24C02.
+    Device,
+    /// The word address.
+    Address,
+    /// A byte read from the memory.
+    Read,
+    /// A byte written into the memory.
+    Write,
+    /// The acknowledge of the chip.
+    SendAck,
+    /// The acknowledge of the console, after a byte was read.
+    WaitAck,
+}
+
+impl Mode {
+    /// The modes, in the order of their numbers in a save state.
+    const ALL: [Self; 7] = [
+        Self::Idle,
+        Self::Device,
+        Self::Address,
+        Self::Read,
+        Self::Write,
+        Self::SendAck,
+        Self::WaitAck,
+    ];
+
+    /// Return the number of the mode in a save state.
+    fn number(self) -> u8 {
+        let index = Self::ALL.iter().position(|&mode| mode == self);
+        index
+            .and_then(|index| u8::try_from(index).ok())
+            .unwrap_or(0)
+    }
+
+    /// Return the mode of the given number in a save state.
+    fn from_number(number: u8) -> io::Result<Self> {
+        Self::ALL
+            .get(usize::from(number))
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid eeprom mode"))
+    }
+}
+
+/// A serial EEPROM, whose memory is kept by the board.
+#[derive(Debug, Clone)]
+pub struct Eeprom {
+    /// The kind of the chip.
+    chip: Chip,
+    /// The current step of the transfer.
+    mode: Mode,
+    /// The step after the acknowledge.
+    next: Mode,
+    /// The number of bits of the current byte.
+    bits: u8,
+    /// The device address.
+    device: u8,
+    /// The word address.
+    address: u8,
+    /// The byte read or written.
+    byte: u8,
+    /// The level the chip drives onto `SDA`.
+    output: bool,
+    /// The last level of `SCL`.
+    scl: bool,
+    /// The last level of `SDA`.
+    sda: bool,
+}
+
+impl Eeprom {
+    /// Create an idle chip of the given kind.
+    pub const fn new(chip: Chip) -> Self {
+        Self {
+            chip,
+            mode: Mode::Idle,
+            next: Mode::Idle,
+            bits: 0,
+            device: 0,
+            address: 0,
+            byte: 0,
+            output: true,
+            scl: false,
+            sda: false,
+        }
+    }
+
+    /// Return the level the chip drives onto `SDA`, which is pulled up while
+    /// the chip doesn't drive it.
+    pub const fn output(&self) -> bool {
+        self.output
+    }
+
+    /// Set the level of `SCL`, holding `SDA`.
+    pub fn set_scl(&mut self, memory: &mut [u8], scl: bool) {
+        self.write(memory, scl, self.sda);
+    }
+
+    /// Set the level of `SDA`, holding `SCL`.
+    pub fn set_sda(&mut self, memory: &mut [u8], sda: bool) {
+        self.write(memory, self.scl, sda);
+    }
+
+    /// Set the levels of both lines, transferring the bits between the bus
+    /// and the given memory.
+    pub fn write(&mut self, memory: &mut [u8], scl: bool, sda: bool) {
+        if self.scl && scl && self.sda && !sda {
+            self.mode = match self.chip {
+                Chip::X24C01 => Mode::Address,
+                Chip::C24C02 => Mode::Device,
+            };
+            if self.chip == Chip::X24C01 {
+                self.address = 0;
+            }
+            self.bits = 0;
+            self.output = true;
+        } else if self.scl && scl && !self.sda && sda {
+            self.mode = Mode::Idle;
+            self.output = true;
+        } else if !self.scl && scl {
+            self.rise(memory, sda);
+        } else if self.scl && !scl {
+            self.fall(memory);
+        }
+
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    /// Take a rise of `SCL`, with the given level of `SDA`.
+    fn rise(&mut self, memory: &[u8], sda: bool) {
+        match self.mode {
+            Mode::Device => self.device = self.shift(self.device, sda),
+            Mode::Address if self.chip == Chip::C24C02 => {
+                self.address = self.shift(self.address, sda);
+            }
+            Mode::Address if self.bits < 7 => self.address = self.shift(self.address, sda),
+            Mode::Address => {
+                // The eighth bit of the X24C01 is the read bit.
+                self.bits = 8;
+                self.next = if sda { Mode::Read } else { Mode::Write };
+                self.byte = memory[self.index(memory)];
+            }
+            Mode::Read if self.bits < 8 => {
+                self.output = self.byte & self.mask() != 0;
+                self.bits += 1;
+            }
+            Mode::Idle | Mode::Read => {}
+            Mode::Write => self.byte = self.shift(self.byte, sda),
+            Mode::SendAck => self.output = false,
+            Mode::WaitAck => {
+                self.next = if sda { Mode::Idle } else { Mode::Read };
+                self.byte = memory[self.index(memory)];
+            }
+        }
+    }
+
+    /// Take a fall of `SCL`.
+    fn fall(&mut self, memory: &mut [u8]) {
+        match self.mode {
+            Mode::Device if self.bits == 8 => {
+                if self.device & 0xf0 == 0xa0 {
+                    let next = if self.device & 1 == 0 {
+                        Mode::Address
+                    } else {
+                        self.byte = memory[self.index(memory)];
+                        Mode::Read
+                    };
+                    self.acknowledge(next);
+                } else {
+                    self.mode = Mode::Idle;
+                    self.output = true;
+                }
+            }
+            Mode::Address if self.bits == 8 => {
+                let next = match self.chip {
+                    Chip::X24C01 => self.next,
+                    Chip::C24C02 => Mode::Write,
+                };
+                self.acknowledge(next);
+            }
+            Mode::Read if self.bits == 8 => {
+                self.mode = Mode::WaitAck;
+                self.advance(memory);
+            }
+            Mode::Write if self.bits == 8 => {
+                let index = self.index(memory);
+                memory[index] = self.byte;
+                self.advance(memory);
+                self.acknowledge(Mode::Write);
+            }
+            Mode::SendAck | Mode::WaitAck => {
+                self.mode = self.next;
+                self.bits = 0;
+                self.output = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Acknowledge a byte, and continue with the given step.
+    fn acknowledge(&mut self, next: Mode) {
+        self.mode = Mode::SendAck;
+        self.next = next;
+        self.bits = 0;
+        self.output = true;
+    }
+
+    /// Advance the word address to the next byte, wrapping around the
+    /// memory.
+    fn advance(&mut self, memory: &[u8]) {
+        let next = (self.index(memory) + 1) % memory.len();
+        self.address = u8::try_from(next).unwrap_or_default();
+    }
+
+    /// Return the index into the memory of the word address.
+    fn index(&self, memory: &[u8]) -> usize {
+        usize::from(self.address) % memory.len()
+    }
+
+    /// Return the mask of the current bit of a byte, in the bit order of the
+    /// chip.
+    const fn mask(&self) -> u8 {
+        match self.chip {
+            Chip::X24C01 => 1 << self.bits,
+            Chip::C24C02 => 0x80 >> self.bits,
+        }
+    }
+
+    /// Shift a bit into a byte, in the bit order of the chip.
+    fn shift(&mut self, byte: u8, bit: bool) -> u8 {
+        if self.bits >= 8 {
+            return byte;
+        }
+
+        let mask = self.mask();
+        self.bits += 1;
+        if bit {
+            byte | mask
+        } else {
+            byte & !mask
+        }
+    }
+
+    /// Save the state of the transfer.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let lines = u8::from(self.output) | (u8::from(self.scl) << 1) | (u8::from(self.sda) << 2);
+        writer.write_all(&[
+            self.mode.number(),
+            self.next.number(),
+            self.bits,
+            self.device,
+            self.address,
+            self.byte,
+            lines,
+        ])
+    }
+
+    /// Load the state of the transfer saved by [`Eeprom::save`].
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 7];
+        reader.read_exact(&mut state)?;
+        let [mode, next, bits, device, address, byte, lines] = state;
+
+        *self = Self {
+            chip: self.chip,
+            mode: Mode::from_number(mode)?,
+            next: Mode::from_number(next)?,
+            bits: bits.min(8),
+            device,
+            address,
+            byte,
+            output: lines & 1 != 0,
+            scl: lines & 2 != 0,
+            sda: lines & 4 != 0,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! The Game Genie, a cheat device plugged between the console and the
+//! cartridge, emulated as a board in front of the board of the cartridge.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Game_Genie>
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use super::{BarcodeReader, Mapper, Mirroring, PpuBus};
+
+/// The size of the PRG-ROM of the firmware, which is mirrored at
+/// `$8000`-`$FFFF`.
+const PRG_SIZE: usize = 0x1000;
+
+/// The size of the CHR-ROM of the firmware, which is mirrored at
+/// `$0000`-`$1FFF`.
+const CHR_SIZE: usize = 0x100;
+
+/// The size of the firmware, its PRG-ROM followed by its CHR-ROM.
+pub const FIRMWARE_SIZE: usize = PRG_SIZE + CHR_SIZE;
+
+/// A code in the registers of the Game Genie.
+#[derive(Debug, Clone, Copy, Default)]
+struct Code {
+    /// The address of the code.
+    addr: u16,
+    /// The compare value.
+    compare: u8,
+    /// The replacement value.
+    value: u8,
+}
+
+/// The Game Genie, in front of the board of a cartridge.
+///
+/// At power-on, the Game Genie maps its own firmware at `$8000`-`$FFFF` and
+/// its own pattern tables at `$0000`-`$1FFF`, which show the screen to enter
+/// the codes. The firmware writes the decoded codes into the registers of the
+/// device, and then writes `0` to `$8000`, which maps the cartridge:
+///
+/// | Address         | Description                                          |
+/// |-----------------|------------------------------------------------------|
+/// | `$8000`         | The control: `xDDDCCCG`, see below.                  |
+/// | `$8001 + 4 * n` | The high byte of the address of code `n` (0 to 2),   |
+/// |                 | whose bit 7 is always set.                           |
+/// | `$8002 + 4 * n` | The low byte of the address of code `n`.             |
+/// | `$8003 + 4 * n` | The compare value of code `n`.                       |
+/// | `$8004 + 4 * n` | The replacement value of code `n`.                   |
+///
+/// Bits 4 to 6 of the control (`D`) disable the codes 0 to 2, and bits 1 to
+/// 3 (`C`) enable their compare values. Once the cartridge is mapped, a read
+/// of the address of an enabled code returns its value instead of the byte
+/// of the cartridge, or only if that byte has the compare value. The codes
+/// stay until the console is powered off, just like on the device.
+///
+/// The firmware isn't part of Chuck, it has to be dumped from a device: 4 KiB
+/// of PRG-ROM followed by 256 bytes of CHR-ROM, see [`FIRMWARE_SIZE`].
+///
+/// ```
+/// # use chuck_nes::mapper::{GameGenie, Mapper, Mirroring, Nrom, FIRMWARE_SIZE};
+/// let cartridge = Nrom::new(vec![0xea; 0x8000], Vec::new(), Mirroring::Vertical);
+/// let mut firmware = vec![0; FIRMWARE_SIZE];
+/// firmware[0] = 0x4c;
+///
+/// let mut game_genie = GameGenie::new(&firmware, Box::new(cartridge))?;
+/// assert_eq!(game_genie.cpu_read(0x8000), Some(0x4c));
+///
+/// // Replace the byte at $9000 with $42, without a compare value.
+/// for (addr, data) in [(0x8001, 0x90), (0x8002, 0x00), (0x8004, 0x42)] {
+///     game_genie.cpu_write(addr, data);
+/// }
+/// game_genie.cpu_write(0x8000, 0x61);
+/// game_genie.cpu_write(0x8000, 0x00);
+///
+/// assert_eq!(game_genie.cpu_read(0x9000), Some(0x42));
+/// assert_eq!(game_genie.cpu_read(0x9001), Some(0xea));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// The software cheats of [`cheat`](crate::cheat) need no firmware, and apply
+/// any number of codes.
+#[derive(Debug, Clone)]
+pub struct GameGenie {
+    /// The PRG-ROM of the firmware.
+    prg: Arc<[u8]>,
+    /// The CHR-ROM of the firmware.
+    chr: Arc<[u8]>,
+    /// The board of the cartridge.
+    cartridge: Box<dyn Mapper>,
+    /// A flag denoting if the cartridge is mapped, i.e. the codes were
+    /// entered.
+    mapped: bool,
+    /// The last non-zero control.
+    control: u8,
+    /// The registers of the codes.
+    codes: [Code; 3],
+}
+
+impl GameGenie {
+    /// Create a Game Genie with the given firmware, in front of the given
+    /// board, see [`GameGenie`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the
+    /// firmware isn't [`FIRMWARE_SIZE`] bytes long.
+    pub fn new(firmware: &[u8], cartridge: Box<dyn Mapper>) -> io::Result<Self> {
+        if firmware.len() != FIRMWARE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "game genie firmware of the wrong size",
+            ));
+        }
+
+        let (prg, chr) = firmware.split_at(PRG_SIZE);
+        Ok(Self {
+            prg: prg.into(),
+            chr: chr.into(),
+            cartridge,
+            mapped: false,
+            control: 0,
+            codes: [Code::default(); 3],
+        })
+    }
+
+    /// Return the board of the cartridge.
+    #[must_use]
+    pub fn cartridge(&self) -> &dyn Mapper {
+        &*self.cartridge
+    }
+
+    /// Check if the cartridge is mapped, i.e. if the codes were entered.
+    #[must_use]
+    pub const fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    /// Return the code enabled at the given address, if any.
+    fn code(&self, addr: u16) -> Option<(usize, &Code)> {
+        self.codes
+            .iter()
+            .enumerate()
+            .find(|&(n, code)| code.addr == addr && self.control & (0x10 << n) == 0)
+    }
+
+    /// Apply the codes to a read of the cartridge at the given address.
+    fn patch(&self, addr: u16, data: Option<u8>) -> Option<u8> {
+        let Some((n, code)) = self.code(addr) else {
+            return data;
+        };
+
+        if self.control & (0x02 << n) == 0 || data == Some(code.compare) {
+            Some(code.value)
+        } else {
+            data
+        }
+    }
+
+    /// Write a register of the Game Genie.
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000 if data == 0 => self.mapped = true,
+            0x8000 => self.control = data,
+            0x8001..=0x800c => {
+                let index = usize::from(addr - 0x8001);
+                let code = &mut self.codes[index / 4];
+                match index % 4 {
+                    0 => code.addr = u16::from_le_bytes([code.addr.to_le_bytes()[0], data | 0x80]),
+                    1 => code.addr = (code.addr & 0xff00) | u16::from(data),
+                    2 => code.compare = data,
+                    _ => code.value = data,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for GameGenie {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match (self.mapped, addr) {
+            (false, 0x8000..=0xffff) => Some(self.prg[usize::from(addr) % PRG_SIZE]),
+            (false, _) => self.cartridge.cpu_peek(addr),
+            (true, _) => self.patch(addr, self.cartridge.cpu_peek(addr)),
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match (self.mapped, addr) {
+            (false, 0x8000..=0xffff) => Some(self.prg[usize::from(addr) % PRG_SIZE]),
+            (false, _) => self.cartridge.cpu_read(addr),
+            (true, _) => {
+                let data = self.cartridge.cpu_read(addr);
+                self.patch(addr, data)
+            }
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match (self.mapped, addr) {
+            (false, 0x8000..=0xffff) => self.write_register(addr, data),
+            _ => self.cartridge.cpu_write(addr, data),
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        if self.mapped || addr < 0x8000 {
+            self.cartridge.cpu_poke(addr, data);
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (self.mapped || addr < 0x8000)
+            .then(|| self.cartridge.prg_offset(addr))
+            .flatten()
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        if self.mapped {
+            self.cartridge.ppu_read(addr)
+        } else {
+            self.chr[usize::from(addr) % CHR_SIZE]
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.mapped {
+            self.cartridge.ppu_write(addr, data);
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        self.mapped
+            .then(|| self.cartridge.chr_offset(addr))
+            .flatten()
+    }
+
+    fn chr(&self) -> &[u8] {
+        self.cartridge.chr()
+    }
+
+    fn ppu_bus(&mut self, bus: &PpuBus) {
+        self.cartridge.ppu_bus(bus);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.cartridge.mirroring()
+    }
+
+    fn irq(&self) -> bool {
+        self.cartridge.irq()
+    }
+
+    fn irq_clocks(&self) -> u64 {
+        self.cartridge.irq_clocks()
+    }
+
+    fn clock(&mut self) {
+        self.cartridge.clock();
+    }
+
+    fn audio(&self) -> f32 {
+        self.cartridge.audio()
+    }
+
+    fn audio_channels(&self) -> &'static [&'static str] {
+        self.cartridge.audio_channels()
+    }
+
+    fn channel_audio(&self, channel: usize) -> f32 {
+        self.cartridge.channel_audio(channel)
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        (self.mapped || addr < 0x8000) && self.cartridge.is_audio_register(addr)
+    }
+
+    fn barcode_reader_mut(&mut self) -> Option<&mut BarcodeReader> {
+        self.cartridge.barcode_reader_mut()
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.cartridge.battery_ram()
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.cartridge.battery_ram_mut()
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[u8::from(self.mapped), self.control])?;
+        for code in &self.codes {
+            writer.write_all(&code.addr.to_le_bytes())?;
+            writer.write_all(&[code.compare, code.value])?;
+        }
+
+        self.cartridge.save(writer)
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut state = [0; 2];
+        reader.read_exact(&mut state)?;
+        let mut codes = [Code::default(); 3];
+        for code in &mut codes {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            *code = Code {
+                addr: u16::from_le_bytes([bytes[0], bytes[1]]),
+                compare: bytes[2],
+                value: bytes[3],
+            };
+        }
+        self.cartridge.load(reader)?;
+        self.mapped = state[0] != 0;
+        self.control = state[1];
+        self.codes = codes;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,294 @@
+//! The MMC1 boards (mapper 1), i.e. the `SxROM` boards.
+//!
+//! The registers of the MMC1 are loaded serially: every write to
+//! `$8000`-`$FFFF` shifts bit 0 of the data into a 5-bit shift register, and
+//! the fifth write copies it into the register selected by bits 13-14 of its
+//! address. Writing a value with bit 7 set resets the shift register instead.
+//!
+//! ```no-run
+//! $8000-$9FFF  Control    ---CPPMM  CHR mode, PRG mode, mirroring
+//! $A000-$BFFF  CHR bank 0 ---CCCCC  4 KiB (or 8 KiB) bank at $0000
+//! $C000-$DFFF  CHR bank 1 ---CCCCC  4 KiB bank at $1000
+//! $E000-$FFFF  PRG bank   ---RPPPP  PRG-RAM disable, 16 KiB (or 32 KiB) bank
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/MMC1>
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{bank, chr_memory, has_battery, prg_ram_size, Mapper, Mirroring};
+
+/// The MMC1 boards.
+///
+/// The larger boards use the upper bits of the CHR bank registers for other
+/// purposes, which are decoded from the sizes of the memories: bit 4 selects
+/// the 256 KiB half of a 512 KiB PRG-ROM (`SUROM`), and bits 2-3 select the
+/// 8 KiB bank of a 16 or 32 KiB PRG-RAM (`SOROM`, `SXROM`).
+#[derive(Debug, Clone)]
+pub struct Mmc1 {
+    /// The PRG-ROM.
+    prg: Arc<[u8]>,
+    /// The PRG-RAM, which might be empty.
+    prg_ram: Box<[u8]>,
+    /// A flag denoting if the PRG-RAM is battery-backed.
+    battery: bool,
+    /// The CHR-ROM, or the CHR-RAM.
+    chr: Arc<[u8]>,
+    /// A flag denoting if the CHR memory is writable, i.e. CHR-RAM.
+    chr_ram: bool,
+
+    /// The shift register, which is loaded from bit 4 downwards.
+    shift: u8,
+    /// The number of bits written into the shift register.
+    count: u8,
+    /// The control register.
+    control: u8,
+    /// The two CHR bank registers.
+    chr_banks: [u8; 2],
+    /// The PRG bank register.
+    prg_bank: u8,
+
+    /// The writes of the serial port on the current (bit 0) and the previous
+    /// cycle (bit 1), as a write right after another one is ignored.
+    writes: u8,
+}
+
+impl Mmc1 {
+    /// Create the board of the given ROM.
+    ///
+    /// The control register starts with the last PRG-ROM bank fixed at
+    /// `$C000`, so that the reset vector is always found.
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let (chr, chr_ram) = chr_memory(rom);
+
+        Self {
+            prg: rom.prg().into(),
+            prg_ram: vec![0; prg_ram_size(rom)].into_boxed_slice(),
+            battery: has_battery(rom),
+            chr,
+            chr_ram,
+            shift: 0,
+            count: 0,
+            control: 0x0c,
+            chr_banks: [0; 2],
+            prg_bank: 0,
+            writes: 0,
+        }
+    }
+
+    /// Write a bit into the shift register, loading a register with the fifth
+    /// bit.
+    fn write_serial(&mut self, addr: u16, data: u8) {
+        if data & 0x80 != 0 {
+            self.shift = 0;
+            self.count = 0;
+            self.control |= 0x0c;
+            return;
+        }
+
+        self.shift = (self.shift >> 1) | ((data & 1) << 4);
+        self.count += 1;
+
+        if self.count == 5 {
+            match addr {
+                0x8000..=0x9fff => self.control = self.shift,
+                0xa000..=0xbfff => self.chr_banks[0] = self.shift,
+                0xc000..=0xdfff => self.chr_banks[1] = self.shift,
+                _ => self.prg_bank = self.shift,
+            }
+
+            self.shift = 0;
+            self.count = 0;
+        }
+    }
+
+    /// Return the index into the PRG-ROM of the given address.
+    fn prg_index(&self, addr: u16) -> usize {
+        let outer = if self.prg.len() > 0x40000 {
+            usize::from(self.chr_banks[0] & 0x10)
+        } else {
+            0
+        };
+        let selected = usize::from(self.prg_bank & 0x0f);
+        let high = addr >= 0xc000;
+
+        let inner = match (self.control >> 2) & 3 {
+            0 | 1 => (selected & !1) | usize::from(high),
+            2 if high => selected,
+            2 => 0,
+            _ if high => 0x0f,
+            _ => selected,
+        };
+
+        bank(&self.prg, outer | inner, 0x4000, addr)
+    }
+
+    /// Return the index into the PRG-RAM of the given address, or `None` if
+    /// the PRG-RAM is missing or disabled.
+    fn prg_ram_index(&self, addr: u16) -> Option<usize> {
+        if self.prg_ram.is_empty() || self.prg_bank & 0x10 != 0 {
+            return None;
+        }
+
+        let selected = usize::from(self.chr_banks[0] >> 2) & 3;
+        Some(bank(&self.prg_ram, selected, 0x2000, addr))
+    }
+
+    /// Return the index into the CHR memory of the given address.
+    fn chr_index(&self, addr: u16) -> usize {
+        if self.control & 0x10 == 0 {
+            bank(&self.chr, usize::from(self.chr_banks[0] >> 1), 0x2000, addr)
+        } else {
+            let selected = self.chr_banks[usize::from(addr >> 12) & 1];
+            bank(&self.chr, usize::from(selected), 0x1000, addr)
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff => self.prg_ram_index(addr).map(|i| self.prg_ram[i]),
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            0x8000..=0xffff => {
+                // The second write of a read-modify-write instruction comes
+                // right after the first, and is ignored.
+                if self.writes & 2 == 0 {
+                    self.write_serial(addr, data);
+                }
+                self.writes |= 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            0x8000..=0xffff => {
+                let i = self.prg_index(addr);
+                Arc::make_mut(&mut self.prg)[i] = data;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| self.prg_index(addr))
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_index(addr))
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_ram {
+            let i = self.chr_index(addr);
+            Arc::make_mut(&mut self.chr)[i] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 3 {
+            0 => Mirroring::SingleLow,
+            1 => Mirroring::SingleHigh,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn clock(&mut self) {
+        self.writes = (self.writes << 1) & 2;
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&*self.prg_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut *self.prg_ram)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[
+            self.shift,
+            self.count,
+            self.control,
+            self.chr_banks[0],
+            self.chr_banks[1],
+            self.prg_bank,
+            self.writes,
+        ])?;
+        writer.write_all(&self.prg_ram)?;
+
+        if self.chr_ram {
+            writer.write_all(&self.chr)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut regs = [0; 7];
+        reader.read_exact(&mut regs)?;
+        let [shift, count, control, chr0, chr1, prg_bank, writes] = regs;
+        if count >= 5 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid shift count in mmc1 state",
+            ));
+        }
+
+        let mut prg_ram = vec![0; self.prg_ram.len()];
+        reader.read_exact(&mut prg_ram)?;
+
+        if self.chr_ram {
+            let mut chr = vec![0; self.chr.len()];
+            reader.read_exact(&mut chr)?;
+            self.chr = chr.into();
+        }
+
+        self.shift = shift;
+        self.count = count;
+        self.control = control;
+        self.chr_banks = [chr0, chr1];
+        self.prg_bank = prg_bank;
+        self.writes = writes & 3;
+        self.prg_ram = prg_ram.into_boxed_slice();
+
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
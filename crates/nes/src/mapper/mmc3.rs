@@ -0,0 +1,379 @@
+//! The MMC3 boards (mapper 4), i.e. the `TxROM` boards.
+//!
+//! The MMC3 switches 8 KiB PRG-ROM banks and 1 or 2 KiB CHR banks through a
+//! pair of registers, and counts scanlines by watching the `A12` line of the
+//! PPU bus, which rises once per scanline when the background and the sprites
+//! use different pattern tables.
+//!
+//! ```no-run
+//! $8000-$9FFE  Bank select   CP---RRR  CHR A12 inversion, PRG mode, register
+//! $8001-$9FFF  Bank data     DDDDDDDD  The bank of the selected register
+//! $A000-$BFFE  Mirroring     -------M  Vertical (0) or horizontal (1)
+//! $A001-$BFFF  PRG-RAM       EW------  Enable, write protection
+//! $C000-$DFFE  IRQ latch     DDDDDDDD  The reload value of the counter
+//! $C001-$DFFF  IRQ reload    --------  Reload the counter on the next clock
+//! $E000-$FFFE  IRQ disable   --------  Disable and acknowledge the IRQ
+//! $E001-$FFFF  IRQ enable    --------
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/MMC3>
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{bank, chr_memory, has_battery, prg_ram_size, Edge, Mapper, Mirroring, PpuBus};
+
+/// The number of PPU dots `A12` must stay low for its next rise to clock the
+/// IRQ counter.
+///
+/// The MMC3 only counts a rise after `A12` was low for 3 falling edges of M2,
+/// which filters out the short low pulses between the pattern fetches of the
+/// sprites (or the background) of a scanline.
+const A12_FILTER: u32 = 10;
+
+/// The revision of the MMC3, which decides when the IRQ counter fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Revision {
+    /// The MMC3A (NES 2.0 submapper 4), whose counter only fires when it
+    /// is decremented to 0, or reloaded with 0 after a write to `$C001`.
+    Old,
+    /// The MMC3B and MMC3C, whose counter fires whenever it is 0 after being
+    /// clocked, i.e. on every scanline for a latch of 0.
+    New,
+}
+
+/// The scanline counter of the MMC3, which is clocked by the rises of `A12`.
+#[derive(Debug, Clone)]
+struct Counter {
+    /// The revision of the MMC3.
+    revision: Revision,
+    /// The reload value of the counter.
+    latch: u8,
+    /// The value of the counter.
+    value: u8,
+    /// A flag denoting if the counter is reloaded on its next clock.
+    reload: bool,
+    /// A flag denoting if the IRQ is enabled.
+    enabled: bool,
+    /// A flag denoting if the IRQ is asserted.
+    irq: bool,
+    /// The number of clocks, see [`Mapper::irq_clocks`].
+    clocks: u64,
+}
+
+impl Counter {
+    /// Clock the counter, asserting the IRQ if it fires.
+    fn clock(&mut self) {
+        let (before, reload) = (self.value, self.reload);
+        self.clocks = self.clocks.wrapping_add(1);
+
+        if self.value == 0 || self.reload {
+            self.value = self.latch;
+            self.reload = false;
+        } else {
+            self.value -= 1;
+        }
+
+        // The old revision only fires on the transition to 0, so a latch of
+        // 0 only fires once after the counter is reloaded explicitly.
+        let fire = match self.revision {
+            Revision::Old => self.value == 0 && (before != 0 || reload),
+            Revision::New => self.value == 0,
+        };
+
+        if fire && self.enabled {
+            self.irq = true;
+        }
+    }
+}
+
+/// The MMC3 boards.
+///
+/// The PRG-ROM is mapped in 8 KiB banks, two of which are switchable, and
+/// the last one is always fixed at `$E000`. The CHR memory is mapped in two 2
+/// KiB and four 1 KiB banks, with either half of the pattern tables in front.
+#[derive(Debug, Clone)]
+pub struct Mmc3 {
+    /// The PRG-ROM.
+    prg: Arc<[u8]>,
+    /// The PRG-RAM, which might be empty.
+    prg_ram: Box<[u8]>,
+    /// A flag denoting if the PRG-RAM is battery-backed.
+    battery: bool,
+    /// The CHR-ROM, or the CHR-RAM.
+    chr: Arc<[u8]>,
+    /// A flag denoting if the CHR memory is writable, i.e. CHR-RAM.
+    chr_ram: bool,
+    /// A flag denoting if the board provides four nametables, in which case
+    /// the mirroring register has no effect.
+    four_screen: bool,
+
+    /// The bank select register.
+    select: u8,
+    /// The bank registers `R0`-`R7`.
+    banks: [u8; 8],
+    /// The mirroring register.
+    mirroring: u8,
+    /// The PRG-RAM protect register.
+    protect: u8,
+
+    /// The scanline counter.
+    counter: Counter,
+}
+
+impl Mmc3 {
+    /// Create the board of the given ROM.
+    ///
+    /// The revision of the MMC3 is taken from the submapper number of a NES
+    /// 2.0 header, an iNES header implying one of the newer revisions.
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let header = rom.header();
+        let (chr, chr_ram) = chr_memory(rom);
+
+        Self {
+            prg: rom.prg().into(),
+            prg_ram: vec![0; prg_ram_size(rom)].into_boxed_slice(),
+            battery: has_battery(rom),
+            chr,
+            chr_ram,
+            four_screen: header.mirroring == chuck_rom::Mirroring::FourScreen,
+            select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: 0,
+            protect: 0x80,
+            counter: Counter {
+                revision: if header.submapper == 4 {
+                    Revision::Old
+                } else {
+                    Revision::New
+                },
+                latch: 0,
+                value: 0,
+                reload: false,
+                enabled: false,
+                irq: false,
+                clocks: 0,
+            },
+        }
+    }
+
+    /// Write one of the registers, selected by bits 13-14 and bit 0 of the
+    /// given address.
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match (addr & 0xe000, addr & 1 != 0) {
+            (0x8000, false) => self.select = data,
+            (0x8000, true) => self.banks[usize::from(self.select & 7)] = data,
+            (0xa000, false) => self.mirroring = data,
+            (0xa000, true) => self.protect = data,
+            (0xc000, false) => self.counter.latch = data,
+            (0xc000, true) => {
+                self.counter.value = 0;
+                self.counter.reload = true;
+            }
+            (_, false) => {
+                self.counter.enabled = false;
+                self.counter.irq = false;
+            }
+            (_, true) => self.counter.enabled = true,
+        }
+    }
+
+    /// Return the index into the PRG-ROM of the given address.
+    fn prg_index(&self, addr: u16) -> usize {
+        let last = self.prg.len() / 0x2000 - 1;
+        let swapped = self.select & 0x40 != 0;
+
+        let selected = match (addr >> 13) & 3 {
+            0 if swapped => last - 1,
+            0 => usize::from(self.banks[6]),
+            1 => usize::from(self.banks[7]),
+            2 if swapped => usize::from(self.banks[6]),
+            2 => last - 1,
+            _ => last,
+        };
+
+        bank(&self.prg, selected, 0x2000, addr)
+    }
+
+    /// Return the index into the PRG-RAM of the given address, or `None` if
+    /// the PRG-RAM is missing or disabled.
+    fn prg_ram_index(&self, addr: u16) -> Option<usize> {
+        if self.prg_ram.is_empty() || self.protect & 0x80 == 0 {
+            return None;
+        }
+
+        Some(bank(&self.prg_ram, 0, 0x2000, addr))
+    }
+
+    /// Return the index into the CHR memory of the given address.
+    fn chr_index(&self, addr: u16) -> usize {
+        // The inversion swaps the 2 KiB banks and the 1 KiB banks.
+        let addr = if self.select & 0x80 == 0 {
+            addr
+        } else {
+            addr ^ 0x1000
+        };
+
+        let selected = match addr >> 10 {
+            0 => self.banks[0] & !1,
+            1 => self.banks[0] | 1,
+            2 => self.banks[1] & !1,
+            3 => self.banks[1] | 1,
+            slot => self.banks[usize::from(slot & 3) + 2],
+        };
+
+        bank(&self.chr, usize::from(selected), 0x0400, addr)
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff => self.prg_ram_index(addr).map(|i| self.prg_ram[i]),
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff if self.protect & 0x40 == 0 => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            0x8000..=0xffff => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            0x8000..=0xffff => {
+                let i = self.prg_index(addr);
+                Arc::make_mut(&mut self.prg)[i] = data;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| self.prg_index(addr))
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_index(addr))
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_ram {
+            let i = self.chr_index(addr);
+            Arc::make_mut(&mut self.chr)[i] = data;
+        }
+    }
+
+    fn ppu_bus(&mut self, bus: &PpuBus) {
+        if bus.edge == Some(Edge::Rise) && bus.held >= A12_FILTER {
+            self.counter.clock();
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.four_screen || self.mirroring & 1 == 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.counter.irq
+    }
+
+    fn irq_clocks(&self) -> u64 {
+        self.counter.clocks
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&*self.prg_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut *self.prg_ram)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[self.select])?;
+        writer.write_all(&self.banks)?;
+        writer.write_all(&[
+            self.mirroring,
+            self.protect,
+            self.counter.latch,
+            self.counter.value,
+            u8::from(self.counter.reload)
+                | (u8::from(self.counter.enabled) << 1)
+                | (u8::from(self.counter.irq) << 2),
+        ])?;
+        writer.write_all(&self.prg_ram)?;
+
+        if self.chr_ram {
+            writer.write_all(&self.chr)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut select = [0; 1];
+        reader.read_exact(&mut select)?;
+        let mut banks = [0; 8];
+        reader.read_exact(&mut banks)?;
+        let mut regs = [0; 5];
+        reader.read_exact(&mut regs)?;
+        let [mirroring, protect, latch, value, flags] = regs;
+
+        let mut prg_ram = vec![0; self.prg_ram.len()];
+        reader.read_exact(&mut prg_ram)?;
+
+        if self.chr_ram {
+            let mut chr = vec![0; self.chr.len()];
+            reader.read_exact(&mut chr)?;
+            self.chr = chr.into();
+        }
+
+        self.select = select[0];
+        self.banks = banks;
+        self.mirroring = mirroring;
+        self.protect = protect;
+        self.counter.latch = latch;
+        self.counter.value = value;
+        self.counter.reload = flags & 1 != 0;
+        self.counter.enabled = flags & 2 != 0;
+        self.counter.irq = flags & 4 != 0;
+        self.prg_ram = prg_ram.into_boxed_slice();
+
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
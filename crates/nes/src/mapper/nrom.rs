@@ -0,0 +1,172 @@
+//! The NROM board (mapper 0), without any bank switching.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/NROM>
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{chr_memory, has_battery, prg_ram_size, Mapper, Mirroring};
+
+/// The NROM board.
+///
+/// The PRG-ROM (16 or 32 KiB) is mapped at `$8000`-`$FFFF`, a 16 KiB ROM
+/// being mirrored at `$C000`. The CHR-ROM (8 KiB) is mapped at `$0000`-`$1FFF`
+/// of the PPU, or CHR-RAM if the cartridge has no CHR-ROM. Any PRG-RAM (like
+/// the Family BASIC cartridge's) is mapped at `$6000`-`$7FFF`, which many test
+/// ROMs use to report their results.
+#[derive(Debug, Clone)]
+pub struct Nrom {
+    /// The PRG-ROM.
+    prg: Arc<[u8]>,
+    /// The PRG-RAM, which might be empty.
+    prg_ram: Box<[u8]>,
+    /// A flag denoting if the PRG-RAM is battery-backed.
+    battery: bool,
+    /// The CHR-ROM, or the CHR-RAM.
+    chr: Arc<[u8]>,
+    /// A flag denoting if the CHR memory is writable, i.e. CHR-RAM.
+    chr_ram: bool,
+    /// The nametable arrangement, which is soldered on the board.
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    /// Create a board from its PRG-ROM and CHR-ROM, with 8 KiB of PRG-RAM.
+    ///
+    /// An empty CHR-ROM is replaced with 8 KiB of CHR-RAM.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the PRG-ROM is empty.
+    #[must_use]
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>, mirroring: Mirroring) -> Self {
+        assert!(!prg.is_empty(), "the PRG-ROM of a board must not be empty");
+
+        let chr_ram = chr.is_empty();
+        let chr = if chr_ram { vec![0; 0x2000] } else { chr };
+
+        Self {
+            prg: prg.into(),
+            prg_ram: vec![0; 0x2000].into_boxed_slice(),
+            battery: false,
+            chr: chr.into(),
+            chr_ram,
+            mirroring,
+        }
+    }
+
+    /// Create the board of the given ROM.
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let (chr, chr_ram) = chr_memory(rom);
+
+        Self {
+            prg: rom.prg().into(),
+            prg_ram: vec![0; prg_ram_size(rom).min(0x2000)].into_boxed_slice(),
+            battery: has_battery(rom),
+            chr,
+            chr_ram,
+            mirroring: rom.header().mirroring.into(),
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                Some(self.prg_ram[usize::from(addr & 0x1fff) % self.prg_ram.len()])
+            }
+            0x8000..=0xffff => Some(self.prg[usize::from(addr & 0x7fff) % self.prg.len()]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if let (0x6000..=0x7fff, false) = (addr, self.prg_ram.is_empty()) {
+            let len = self.prg_ram.len();
+            self.prg_ram[usize::from(addr & 0x1fff) % len] = data;
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[usize::from(addr & 0x1fff) % len] = data;
+            }
+            0x8000..=0xffff => {
+                let len = self.prg.len();
+                Arc::make_mut(&mut self.prg)[usize::from(addr & 0x7fff) % len] = data;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| usize::from(addr & 0x7fff) % self.prg.len())
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(usize::from(addr) % self.chr.len())
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            Arc::make_mut(&mut self.chr)[usize::from(addr) % len] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&*self.prg_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut *self.prg_ram)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.prg_ram)?;
+
+        if self.chr_ram {
+            writer.write_all(&self.chr)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut prg_ram = vec![0; self.prg_ram.len()];
+        reader.read_exact(&mut prg_ram)?;
+
+        if self.chr_ram {
+            let mut chr = vec![0; self.chr.len()];
+            reader.read_exact(&mut chr)?;
+            self.chr = chr.into();
+        }
+
+        self.prg_ram = prg_ram.into_boxed_slice();
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,188 @@
+//! The `UxROM` boards (mapper 2), e.g. `UNROM` and `UOROM`.
+//!
+//! ```no-run
+//! $8000-$FFFF  Bank select  PPPPPPPP  16 KiB PRG-ROM bank at $8000
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/UxROM>
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{bank, chr_memory, has_battery, prg_ram_size, BusConflicts, Mapper, Mirroring};
+
+/// The `UxROM` boards.
+///
+/// A 16 KiB PRG-ROM bank is switchable at `$8000`, while the last one is
+/// fixed at `$C000`. The CHR memory (usually 8 KiB of CHR-RAM) isn't banked.
+#[derive(Debug, Clone)]
+pub struct Uxrom {
+    /// The PRG-ROM.
+    prg: Arc<[u8]>,
+    /// The PRG-RAM, which might be empty.
+    prg_ram: Box<[u8]>,
+    /// A flag denoting if the PRG-RAM is battery-backed.
+    battery: bool,
+    /// The CHR-ROM, or the CHR-RAM.
+    chr: Arc<[u8]>,
+    /// A flag denoting if the CHR memory is writable, i.e. CHR-RAM.
+    chr_ram: bool,
+    /// The nametable arrangement, which is soldered on the board.
+    mirroring: Mirroring,
+    /// The bus conflicts of the board.
+    conflicts: BusConflicts,
+
+    /// The bank select register.
+    select: u8,
+}
+
+impl Uxrom {
+    /// Create the board of the given ROM.
+    ///
+    /// Bus conflicts are emulated if the submapper number asks for them, see
+    /// [`BusConflicts::from_submapper`].
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let (chr, chr_ram) = chr_memory(rom);
+
+        Self {
+            prg: rom.prg().into(),
+            prg_ram: vec![0; prg_ram_size(rom).min(0x2000)].into_boxed_slice(),
+            battery: has_battery(rom),
+            chr,
+            chr_ram,
+            mirroring: rom.header().mirroring.into(),
+            conflicts: BusConflicts::from_submapper(rom.header().submapper),
+            select: 0,
+        }
+    }
+
+    /// Use the given bus conflicts instead of the ones of the ROM.
+    #[must_use]
+    pub fn with_bus_conflicts(mut self, conflicts: BusConflicts) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+
+    /// Return the index into the PRG-ROM of the given address.
+    fn prg_index(&self, addr: u16) -> usize {
+        let selected = if addr >= 0xc000 {
+            self.prg.len() / 0x4000 - 1
+        } else {
+            usize::from(self.select)
+        };
+
+        bank(&self.prg, selected, 0x4000, addr)
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                Some(self.prg_ram[bank(&self.prg_ram, 0, 0x2000, addr)])
+            }
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                let i = bank(&self.prg_ram, 0, 0x2000, addr);
+                self.prg_ram[i] = data;
+            }
+            0x8000..=0xffff => {
+                self.select = self.conflicts.apply(data, self.prg[self.prg_index(addr)]);
+            }
+            _ => {}
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff if !self.prg_ram.is_empty() => {
+                let i = bank(&self.prg_ram, 0, 0x2000, addr);
+                self.prg_ram[i] = data;
+            }
+            0x8000..=0xffff => {
+                let i = self.prg_index(addr);
+                Arc::make_mut(&mut self.prg)[i] = data;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| self.prg_index(addr))
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(usize::from(addr) % self.chr.len())
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[usize::from(addr) % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            Arc::make_mut(&mut self.chr)[usize::from(addr) % len] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&*self.prg_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut *self.prg_ram)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[self.select])?;
+        writer.write_all(&self.prg_ram)?;
+
+        if self.chr_ram {
+            writer.write_all(&self.chr)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut select = [0; 1];
+        reader.read_exact(&mut select)?;
+
+        let mut prg_ram = vec![0; self.prg_ram.len()];
+        reader.read_exact(&mut prg_ram)?;
+
+        if self.chr_ram {
+            let mut chr = vec![0; self.chr.len()];
+            reader.read_exact(&mut chr)?;
+            self.chr = chr.into();
+        }
+
+        self.select = select[0];
+        self.prg_ram = prg_ram.into_boxed_slice();
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,399 @@
+//! The Konami VRC6 boards (mappers 24 and 26), with their expansion audio.
+//!
+//! The registers are selected by bits 12-15 and the two lowest address lines
+//! of the VRC6, which are connected to `A0` and `A1` on the `VRC6a` (mapper
+//! 24), but swapped on the `VRC6b` (mapper 26).
+//!
+//! ```no-run
+//! $8000-$8003  PRG bank    ----PPPP  16 KiB bank at $8000
+//! $9000-$B002  Audio                 See the audio module
+//! $B003        Banking     R-ANMMCC  PRG-RAM enable, mirroring, CHR mode
+//! $C000-$C003  PRG bank    ---PPPPP  8 KiB bank at $C000
+//! $D000-$D003  CHR banks   CCCCCCCC  R0-R3
+//! $E000-$E003  CHR banks   CCCCCCCC  R4-R7
+//! $F000        IRQ latch   LLLLLLLL  The reload value of the counter
+//! $F001        IRQ control -----MEA  Cycle mode, enable, enable after ack
+//! $F002        IRQ ack     --------  Acknowledge the IRQ
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/VRC6>
+//! - <https://www.nesdev.org/wiki/VRC_IRQ>
+
+pub mod audio;
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use chuck_rom::Rom;
+
+use super::{bank, chr_memory, has_battery, prg_ram_size, Mapper, Mirroring};
+use audio::Audio;
+
+/// The number of PPU dots of a scanline, which the prescaler of the IRQ
+/// counter counts down by 3 per CPU cycle.
+const SCANLINE_DOTS: i16 = 341;
+
+/// The IRQ counter of the VRC6, which counts scanlines or CPU cycles.
+#[derive(Debug, Clone)]
+struct Counter {
+    /// The reload value of the counter.
+    latch: u8,
+    /// The value of the counter, counting up to `$FF`.
+    value: u8,
+    /// The prescaler, which approximates the scanlines in CPU cycles.
+    prescaler: i16,
+    /// The control register.
+    control: u8,
+    /// A flag denoting if the IRQ is asserted.
+    irq: bool,
+    /// The number of clocks, see [`Mapper::irq_clocks`].
+    clocks: u64,
+}
+
+impl Counter {
+    /// Write the control register, which acknowledges the IRQ.
+    fn write_control(&mut self, data: u8) {
+        self.control = data & 7;
+        self.irq = false;
+
+        if self.control & 2 != 0 {
+            self.value = self.latch;
+            self.prescaler = SCANLINE_DOTS;
+        }
+    }
+
+    /// Acknowledge the IRQ, enabling the counter if it is enabled after an
+    /// acknowledge.
+    fn acknowledge(&mut self) {
+        self.irq = false;
+        self.control = (self.control & !2) | ((self.control & 1) << 1);
+    }
+
+    /// Execute a single CPU cycle.
+    fn clock(&mut self) {
+        if self.control & 2 == 0 {
+            return;
+        }
+
+        if self.control & 4 == 0 {
+            self.prescaler -= 3;
+
+            if self.prescaler > 0 {
+                return;
+            }
+            self.prescaler += SCANLINE_DOTS;
+        }
+
+        self.clocks = self.clocks.wrapping_add(1);
+        if self.value == 0xff {
+            self.value = self.latch;
+            self.irq = true;
+        } else {
+            self.value += 1;
+        }
+    }
+}
+
+/// The VRC6 boards.
+///
+/// The PRG-ROM is mapped as a 16 KiB and an 8 KiB switchable bank, followed
+/// by the last 8 KiB fixed at `$E000`. The CHR-ROM is mapped in 1 KiB or 2
+/// KiB banks. The nametables always come from the CIRAM, as in all known
+/// games, although the VRC6 could also map them to the CHR-ROM.
+#[derive(Debug, Clone)]
+pub struct Vrc6 {
+    /// The PRG-ROM.
+    prg: Arc<[u8]>,
+    /// The PRG-RAM, which might be empty.
+    prg_ram: Box<[u8]>,
+    /// A flag denoting if the PRG-RAM is battery-backed.
+    battery: bool,
+    /// The CHR-ROM, or the CHR-RAM.
+    chr: Arc<[u8]>,
+    /// A flag denoting if the CHR memory is writable, i.e. CHR-RAM.
+    chr_ram: bool,
+    /// A flag denoting if the lowest address lines are swapped, i.e. the
+    /// `VRC6b`.
+    swapped: bool,
+
+    /// The 16 KiB and the 8 KiB PRG-ROM bank registers.
+    prg_banks: [u8; 2],
+    /// The CHR bank registers `R0`-`R7`.
+    chr_banks: [u8; 8],
+    /// The banking control register.
+    banking: u8,
+    /// The IRQ counter.
+    counter: Counter,
+    /// The expansion audio.
+    audio: Audio,
+}
+
+impl Vrc6 {
+    /// Create the board of the given ROM, swapping the address lines for
+    /// mapper 26.
+    #[must_use]
+    pub fn from_rom(rom: &Rom) -> Self {
+        let (chr, chr_ram) = chr_memory(rom);
+
+        Self {
+            prg: rom.prg().into(),
+            prg_ram: vec![0; prg_ram_size(rom)].into_boxed_slice(),
+            battery: has_battery(rom),
+            chr,
+            chr_ram,
+            swapped: rom.header().mapper == 26,
+            prg_banks: [0; 2],
+            chr_banks: [0; 8],
+            banking: 0,
+            counter: Counter {
+                latch: 0,
+                value: 0,
+                prescaler: SCANLINE_DOTS,
+                control: 0,
+                irq: false,
+                clocks: 0,
+            },
+            audio: Audio::default(),
+        }
+    }
+
+    /// Return the register within the group of the given address, i.e. the
+    /// state of the VRC6's `A0` and `A1` pins.
+    fn register(&self, addr: u16) -> u16 {
+        if self.swapped {
+            ((addr & 1) << 1) | ((addr >> 1) & 1)
+        } else {
+            addr & 3
+        }
+    }
+
+    /// Return the index into the PRG-ROM of the given address.
+    fn prg_index(&self, addr: u16) -> usize {
+        match addr {
+            0x8000..=0xbfff => bank(&self.prg, usize::from(self.prg_banks[0]), 0x4000, addr),
+            0xc000..=0xdfff => bank(&self.prg, usize::from(self.prg_banks[1]), 0x2000, addr),
+            _ => bank(&self.prg, self.prg.len() / 0x2000 - 1, 0x2000, addr),
+        }
+    }
+
+    /// Return the index into the PRG-RAM of the given address, or `None` if
+    /// the PRG-RAM is missing or disabled.
+    fn prg_ram_index(&self, addr: u16) -> Option<usize> {
+        if self.prg_ram.is_empty() || self.banking & 0x80 == 0 {
+            return None;
+        }
+
+        Some(bank(&self.prg_ram, 0, 0x2000, addr))
+    }
+
+    /// Return the index into the CHR memory of the given address.
+    fn chr_index(&self, addr: u16) -> usize {
+        let slot = usize::from(addr >> 10) & 7;
+
+        // In the 2 KiB modes, the lowest bank bit either comes from A10, or
+        // both halves of a bank use the same 1 KiB.
+        let half = |bank: u8| {
+            if self.banking & 0x20 == 0 {
+                bank
+            } else {
+                (bank & !1) | u8::from(slot & 1 != 0)
+            }
+        };
+
+        let selected = match self.banking & 3 {
+            0 => self.chr_banks[slot],
+            1 => half(self.chr_banks[slot >> 1]),
+            _ if slot < 4 => self.chr_banks[slot],
+            _ => half(self.chr_banks[4 + ((slot - 4) >> 1)]),
+        };
+
+        bank(&self.chr, usize::from(selected), 0x0400, addr)
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7fff => self.prg_ram_index(addr).map(|i| self.prg_ram[i]),
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        let reg = self.register(addr);
+
+        match (addr & 0xf000, reg) {
+            (0x6000 | 0x7000, _) => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            (0x8000, _) => self.prg_banks[0] = data & 0x0f,
+            (0xb000, 3) => self.banking = data,
+            (0x9000..=0xb000, _) => self.audio.write((addr >> 12) - 9, reg, data),
+            (0xc000, _) => self.prg_banks[1] = data & 0x1f,
+            (0xd000, _) => self.chr_banks[usize::from(reg)] = data,
+            (0xe000, _) => self.chr_banks[usize::from(reg) + 4] = data,
+            (0xf000, 0) => self.counter.latch = data,
+            (0xf000, 1) => self.counter.write_control(data),
+            (0xf000, 2) => self.counter.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn cpu_poke(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7fff => {
+                if let Some(i) = self.prg_ram_index(addr) {
+                    self.prg_ram[i] = data;
+                }
+            }
+            0x8000..=0xffff => {
+                let i = self.prg_index(addr);
+                Arc::make_mut(&mut self.prg)[i] = data;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        (addr >= 0x8000).then(|| self.prg_index(addr))
+    }
+
+    fn chr_offset(&self, addr: u16) -> Option<usize> {
+        Some(self.chr_index(addr))
+    }
+
+    fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_index(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        if self.chr_ram {
+            let i = self.chr_index(addr);
+            Arc::make_mut(&mut self.chr)[i] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.banking >> 2) & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleLow,
+            _ => Mirroring::SingleHigh,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.counter.irq
+    }
+
+    fn irq_clocks(&self) -> u64 {
+        self.counter.clocks
+    }
+
+    fn clock(&mut self) {
+        self.counter.clock();
+        self.audio.clock();
+    }
+
+    fn audio(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn audio_channels(&self) -> &'static [&'static str] {
+        &Audio::CHANNELS
+    }
+
+    fn channel_audio(&self, channel: usize) -> f32 {
+        self.audio.channel_output(channel)
+    }
+
+    fn is_audio_register(&self, addr: u16) -> bool {
+        match (addr & 0xf000, self.register(addr)) {
+            (0xb000, 3) => false,
+            (0x9000..=0xb000, _) => true,
+            _ => false,
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.battery.then_some(&*self.prg_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.battery.then_some(&mut *self.prg_ram)
+    }
+
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.prg_banks)?;
+        writer.write_all(&self.chr_banks)?;
+        writer.write_all(&[self.banking])?;
+
+        let counter = &self.counter;
+        writer.write_all(&[counter.latch, counter.value, counter.control])?;
+        writer.write_all(&counter.prescaler.to_le_bytes())?;
+        writer.write_all(&[u8::from(counter.irq)])?;
+
+        self.audio.save(writer)?;
+        writer.write_all(&self.prg_ram)?;
+
+        if self.chr_ram {
+            writer.write_all(&self.chr)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut regs = [0; 17];
+        reader.read_exact(&mut regs)?;
+
+        let prescaler = i16::from_le_bytes([regs[14], regs[15]]);
+        if !(1..=SCANLINE_DOTS).contains(&prescaler) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid prescaler in vrc6 state",
+            ));
+        }
+
+        let mut audio = Audio::default();
+        audio.load(reader)?;
+
+        let mut prg_ram = vec![0; self.prg_ram.len()];
+        reader.read_exact(&mut prg_ram)?;
+
+        if self.chr_ram {
+            let mut chr = vec![0; self.chr.len()];
+            reader.read_exact(&mut chr)?;
+            self.chr = chr.into();
+        }
+
+        self.prg_banks = [regs[0], regs[1]];
+        self.chr_banks.copy_from_slice(&regs[2..10]);
+        self.banking = regs[10];
+        self.counter = Counter {
+            latch: regs[11],
+            value: regs[12],
+            prescaler,
+            control: regs[13] & 7,
+            irq: regs[16] != 0,
+            clocks: self.counter.clocks,
+        };
+        self.audio = audio;
+        self.prg_ram = prg_ram.into_boxed_slice();
+
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
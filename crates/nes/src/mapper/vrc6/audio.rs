@@ -0,0 +1,298 @@
+//! The expansion audio of the VRC6: two pulse channels and a sawtooth
+//! channel, whose timers are clocked by every CPU cycle.
+//!
+//! ```no-run
+//! $9000/$A000  Pulse control  MDDDVVVV  Constant mode, duty, volume
+//! $9001/$A001  Pulse period   PPPPPPPP  Low 8 bits of the period
+//! $9002/$A002  Pulse period   E---PPPP  Enable, high 4 bits of the period
+//! $9003        Frequency      -----ABH  Shift periods by 8 or 4, halt all
+//! $B000        Saw rate       --RRRRRR  Accumulator rate
+//! $B001        Saw period     PPPPPPPP  Low 8 bits of the period
+//! $B002        Saw period     E---PPPP  Enable, high 4 bits of the period
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/VRC6_audio>
+
+use std::io::{self, Read, Write};
+
+/// The level of a single step of the channels' outputs, see
+/// [`Audio::output`].
+///
+/// This makes a VRC6 pulse channel at full volume about as loud as a pulse
+/// channel of the APU at full volume.
+const LEVEL: f32 = 0.01;
+
+/// A 12-bit timer, which is clocked with every CPU cycle.
+#[derive(Debug, Clone, Copy, Default)]
+struct Timer {
+    /// The period of the timer.
+    period: u16,
+    /// The counter of the timer, counting down to 0.
+    counter: u16,
+}
+
+impl Timer {
+    /// Write the low 8 bits of the period.
+    fn write_low(&mut self, data: u8) {
+        self.period = (self.period & 0x0f00) | u16::from(data);
+    }
+
+    /// Write the high 4 bits of the period.
+    fn write_high(&mut self, data: u8) {
+        self.period = (self.period & 0x00ff) | (u16::from(data & 0x0f) << 8);
+    }
+
+    /// Clock the timer with the period shifted right by the given amount,
+    /// returning `true` if it expired.
+    fn clock(&mut self, shift: u8) -> bool {
+        if self.counter == 0 {
+            self.counter = self.period >> shift;
+            true
+        } else {
+            self.counter -= 1;
+            false
+        }
+    }
+}
+
+/// A pulse channel of the VRC6, with 8 duty cycles of 16 steps.
+#[derive(Debug, Clone, Copy, Default)]
+struct Pulse {
+    /// The control register.
+    control: u8,
+    /// A flag denoting if the channel is enabled.
+    enabled: bool,
+    /// The timer of the duty cycle.
+    timer: Timer,
+    /// The current step of the duty cycle, counting down from 15.
+    step: u8,
+}
+
+impl Pulse {
+    /// Write one of the registers of the channel.
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => self.control = data,
+            1 => self.timer.write_low(data),
+            _ => {
+                self.timer.write_high(data);
+                self.enabled = data & 0x80 != 0;
+
+                // Disabling the channel resets the duty cycle.
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    /// Clock the timer of the channel.
+    fn clock(&mut self, shift: u8) {
+        if self.enabled && self.timer.clock(shift) {
+            self.step = self.step.wrapping_sub(1) & 15;
+        }
+    }
+
+    /// Return the output of the channel, `0`-`15`.
+    fn output(self) -> u8 {
+        let constant = self.control & 0x80 != 0;
+        let duty = (self.control >> 4) & 7;
+
+        if self.enabled && (constant || self.step <= duty) {
+            self.control & 0x0f
+        } else {
+            0
+        }
+    }
+}
+
+/// The sawtooth channel of the VRC6, which adds its rate to an accumulator on
+/// every other of 14 steps.
+#[derive(Debug, Clone, Copy, Default)]
+struct Saw {
+    /// The rate added to the accumulator, `0`-`63`.
+    rate: u8,
+    /// A flag denoting if the channel is enabled.
+    enabled: bool,
+    /// The timer of the steps.
+    timer: Timer,
+    /// The current step, `0`-`13`.
+    step: u8,
+    /// The accumulator, whose high 5 bits are output.
+    accumulator: u8,
+}
+
+impl Saw {
+    /// Write one of the registers of the channel.
+    fn write(&mut self, reg: u16, data: u8) {
+        match reg {
+            0 => self.rate = data & 0x3f,
+            1 => self.timer.write_low(data),
+            _ => {
+                self.timer.write_high(data);
+                self.enabled = data & 0x80 != 0;
+
+                // Disabling the channel resets the accumulator.
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    /// Clock the timer of the channel.
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled || !self.timer.clock(shift) {
+            return;
+        }
+
+        self.step += 1;
+
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 1 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    /// Return the output of the channel, `0`-`31`.
+    fn output(self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+/// The expansion audio of the VRC6.
+#[derive(Debug, Clone, Default)]
+pub struct Audio {
+    /// The two pulse channels.
+    pulse: [Pulse; 2],
+    /// The sawtooth channel.
+    saw: Saw,
+    /// The frequency control register.
+    frequency: u8,
+}
+
+impl Audio {
+    /// The names of the channels, see [`Audio::channel_output`].
+    pub const CHANNELS: [&'static str; 3] = ["VRC6 pulse 1", "VRC6 pulse 2", "VRC6 sawtooth"];
+
+    /// Write one of the audio registers, selected by the channel (`0` and `1`
+    /// for the pulse channels, `2` for the sawtooth channel) and the register
+    /// within it (`0`-`3`).
+    pub fn write(&mut self, channel: u16, reg: u16, data: u8) {
+        match (channel, reg) {
+            (0, 3) => self.frequency = data,
+            (_, 3) => {}
+            (0 | 1, _) => self.pulse[usize::from(channel)].write(reg, data),
+            _ => self.saw.write(reg, data),
+        }
+    }
+
+    /// Clock the timers of the channels, unless they are halted.
+    pub fn clock(&mut self) {
+        if self.frequency & 1 != 0 {
+            return;
+        }
+
+        let shift = match self.frequency & 6 {
+            0 => 0,
+            2 => 4,
+            _ => 8,
+        };
+
+        for pulse in &mut self.pulse {
+            pulse.clock(shift);
+        }
+        self.saw.clock(shift);
+    }
+
+    /// Return the mixed output of the channels, which are mixed linearly.
+    pub fn output(&self) -> f32 {
+        let sum = self.pulse[0].output() + self.pulse[1].output() + self.saw.output();
+        f32::from(sum) * LEVEL
+    }
+
+    /// Return the output of a single channel (`0` and `1` for the pulse
+    /// channels, `2` for the sawtooth channel) in the units of
+    /// [`Audio::output`], or `0.0` if there's no such channel.
+    pub fn channel_output(&self, channel: usize) -> f32 {
+        let output = match channel {
+            0 | 1 => self.pulse[channel].output(),
+            2 => self.saw.output(),
+            _ => 0,
+        };
+        f32::from(output) * LEVEL
+    }
+
+    /// Save the state of the channels.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for pulse in &self.pulse {
+            writer.write_all(&[pulse.control, u8::from(pulse.enabled), pulse.step])?;
+            save_timer(writer, pulse.timer)?;
+        }
+
+        let saw = &self.saw;
+        writer.write_all(&[saw.rate, u8::from(saw.enabled), saw.step, saw.accumulator])?;
+        save_timer(writer, saw.timer)?;
+
+        writer.write_all(&[self.frequency])
+    }
+
+    /// Load the state of the channels saved by [`Audio::save`].
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut pulse = [Pulse::default(); 2];
+
+        for pulse in &mut pulse {
+            let mut regs = [0; 3];
+            reader.read_exact(&mut regs)?;
+            let [control, enabled, step] = regs;
+
+            *pulse = Pulse {
+                control,
+                enabled: enabled != 0,
+                timer: load_timer(reader)?,
+                step: step & 15,
+            };
+        }
+
+        let mut regs = [0; 4];
+        reader.read_exact(&mut regs)?;
+        let [rate, enabled, step, accumulator] = regs;
+        let saw = Saw {
+            rate: rate & 0x3f,
+            enabled: enabled != 0,
+            timer: load_timer(reader)?,
+            step: step % 14,
+            accumulator,
+        };
+
+        let mut frequency = [0; 1];
+        reader.read_exact(&mut frequency)?;
+
+        self.pulse = pulse;
+        self.saw = saw;
+        self.frequency = frequency[0];
+        Ok(())
+    }
+}
+
+/// Save the state of a timer.
+fn save_timer(writer: &mut dyn Write, timer: Timer) -> io::Result<()> {
+    writer.write_all(&timer.period.to_le_bytes())?;
+    writer.write_all(&timer.counter.to_le_bytes())
+}
+
+/// Load the state of a timer saved by [`save_timer`].
+fn load_timer(reader: &mut dyn Read) -> io::Result<Timer> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(Timer {
+        period: u16::from_le_bytes([bytes[0], bytes[1]]) & 0x0fff,
+        counter: u16::from_le_bytes([bytes[2], bytes[3]]) & 0x0fff,
+    })
+}
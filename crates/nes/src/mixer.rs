@@ -0,0 +1,341 @@
+//! The volumes of the audio channels of the APU and of the expansion audio
+//! of the cartridge and the EPSM, and their placement in the stereo field.
+//!
+//! The volumes are e.g. for muting or soloing channels while listening to
+//! music or debugging it.
+//!
+//! The volumes only apply to the mixed audio samples, see
+//! [`Frame::samples`]: the channels are emulated as before, so e.g. their
+//! length counters still count down, and they're not part of the state of
+//! the console. The samples of the individual channels (see
+//! [`Frame::channels`]) aren't affected either.
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::mixer::Channel;
+//! # use chuck_nes::Nes;
+//! // A program that plays a square wave on the first pulse channel.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..23].copy_from_slice(&[
+//!     0xa9, 0x01, 0x8d, 0x15, 0x40, 0xa9, 0xbf, 0x8d, 0x00, 0x40, 0xa9, 0xfd, 0x8d, 0x02, 0x40,
+//!     0xa9, 0x08, 0x8d, 0x03, 0x40, 0x4c, 0x14, 0x80,
+//! ]);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! assert!(nes.run_frame().samples.iter().any(|&sample| sample > 0.0));
+//!
+//! // The channel still plays, but isn't heard.
+//! nes.mixer_mut().solo(Channel::Noise);
+//! assert!(nes.run_frame().samples.iter().all(|&sample| sample == 0.0));
+//! assert!(nes.apu().peek(0x4015) & 0x01 != 0);
+//! ```
+//!
+//! # Stereo
+//!
+//! The console's audio is mono, so every channel is centered by default. A
+//! channel can be panned to the left or right though, see [`Volume::pan`],
+//! which only applies to the stereo samples, see [`Frame::stereo`]: the left
+//! and right samples are mixed like the mono samples, just with the gains of
+//! the left and right side, so the mono samples are their average, and
+//! exactly as heard on the console while every channel is centered.
+//!
+//! [`Mixer::famicom_stereo`] spreads the channels of the expansion sound chip
+//! to the sides, while the APU stays centered.
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::mixer::Channel;
+//! # use chuck_nes::Nes;
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..23].copy_from_slice(&[
+//! #     0xa9, 0x01, 0x8d, 0x15, 0x40, 0xa9, 0xbf, 0x8d, 0x00, 0x40, 0xa9, 0xfd, 0x8d, 0x02, 0x40,
+//! #     0xa9, 0x08, 0x8d, 0x03, 0x40, 0x4c, 0x14, 0x80,
+//! # ]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! // The square wave on the first pulse channel, only heard on the left.
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.capture_stereo(true);
+//! nes.mixer_mut().solo(Channel::Pulse1);
+//! nes.mixer_mut().set_pan(Channel::Pulse1, -1.0);
+//!
+//! let frame = nes.run_frame();
+//! assert!(frame.stereo.iter().any(|&[left, _]| left > 0.0));
+//! assert!(frame.stereo.iter().all(|&[_, right]| right == 0.0));
+//! for (&[left, right], &sample) in frame.stereo.iter().zip(frame.samples) {
+//!     assert_eq!(sample, f32::midpoint(left, right));
+//! }
+//! ```
+//!
+//! [`Frame::samples`]: crate::Frame::samples
+//! [`Frame::channels`]: crate::Frame::channels
+//! [`Frame::stereo`]: crate::Frame::stereo
+
+use chuck_apu::Output;
+
+#[cfg(doc)]
+use crate::mapper::Mapper;
+
+/// The largest number of channels of the expansion audio, e.g. the 3
+/// channels of the VRC6 followed by the 9 of the EPSM, see
+/// [`Nes::audio_channels`](crate::Nes::audio_channels).
+pub const EXPANSION_CHANNELS: usize = 16;
+
+/// An audio channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// The first pulse channel of the APU.
+    Pulse1,
+    /// The second pulse channel of the APU.
+    Pulse2,
+    /// The triangle channel of the APU.
+    Triangle,
+    /// The noise channel of the APU.
+    Noise,
+    /// The delta modulation channel of the APU.
+    Dmc,
+    /// A channel of the expansion sound chip of the cartridge (see
+    /// [`Mapper::audio_channels`]), or of the EPSM following them, below
+    /// [`EXPANSION_CHANNELS`].
+    Expansion(usize),
+}
+
+impl Channel {
+    /// The channels of the APU.
+    pub const APU: [Self; 5] = [
+        Self::Pulse1,
+        Self::Pulse2,
+        Self::Triangle,
+        Self::Noise,
+        Self::Dmc,
+    ];
+
+    /// Return the index of the volume of the channel in the mixer.
+    const fn index(self) -> usize {
+        match self {
+            Self::Pulse1 => 0,
+            Self::Pulse2 => 1,
+            Self::Triangle => 2,
+            Self::Noise => 3,
+            Self::Dmc => 4,
+            Self::Expansion(index) => 5 + index,
+        }
+    }
+}
+
+/// The volume of a channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Volume {
+    /// A flag denoting if the channel is heard, so it can be muted without
+    /// losing its level.
+    pub enabled: bool,
+    /// The level of the channel, which scales its output, so `1.0` is as
+    /// loud as on the console.
+    pub level: f32,
+    /// The placement of the channel in the stereo field, from `-1.0` (only
+    /// on the left) over `0.0` (centered, as on the console) to `1.0` (only on
+    /// the right).
+    ///
+    /// The channel keeps its full level on the side it's panned to, and is
+    /// attenuated linearly on the other side.
+    pub pan: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: 1.0,
+            pan: 0.0,
+        }
+    }
+}
+
+impl Volume {
+    /// Return the factor by which the output of the channel is scaled.
+    #[must_use]
+    pub const fn gain(self) -> f32 {
+        if self.enabled {
+            self.level
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the factors by which the output of the channel is scaled on
+    /// the left and on the right side.
+    #[must_use]
+    pub fn stereo_gains(self) -> [f32; 2] {
+        let pan = self.pan.clamp(-1.0, 1.0);
+        let gain = self.gain();
+        [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)]
+    }
+}
+
+/// The volumes of all channels, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mixer {
+    /// The volumes of the channels of the APU, in the order of
+    /// [`Channel::APU`], and then of the expansion sound chip.
+    volumes: [Volume; 5 + EXPANSION_CHANNELS],
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            volumes: [Volume::default(); 5 + EXPANSION_CHANNELS],
+        }
+    }
+}
+
+impl Mixer {
+    /// Return the volume of a channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is an expansion channel beyond
+    /// [`EXPANSION_CHANNELS`].
+    #[must_use]
+    pub const fn volume(&self, channel: Channel) -> Volume {
+        self.volumes[channel.index()]
+    }
+
+    /// Set the volume of a channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is an expansion channel beyond
+    /// [`EXPANSION_CHANNELS`].
+    pub fn set_volume(&mut self, channel: Channel, volume: Volume) {
+        self.volumes[channel.index()] = volume;
+    }
+
+    /// Enable (unmute) or disable (mute) a channel, keeping its level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is an expansion channel beyond
+    /// [`EXPANSION_CHANNELS`].
+    pub fn set_enabled(&mut self, channel: Channel, enabled: bool) {
+        self.volumes[channel.index()].enabled = enabled;
+    }
+
+    /// Enable only the given channel, and disable all others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is an expansion channel beyond
+    /// [`EXPANSION_CHANNELS`].
+    pub fn solo(&mut self, channel: Channel) {
+        for volume in &mut self.volumes {
+            volume.enabled = false;
+        }
+        self.set_enabled(channel, true);
+    }
+
+    /// Place a channel in the stereo field, see [`Volume::pan`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is an expansion channel beyond
+    /// [`EXPANSION_CHANNELS`].
+    pub fn set_pan(&mut self, channel: Channel, pan: f32) {
+        self.volumes[channel.index()].pan = pan;
+    }
+
+    /// Center all channels, like on the console.
+    pub fn center_all(&mut self) {
+        for volume in &mut self.volumes {
+            volume.pan = 0.0;
+        }
+    }
+
+    /// Spread the channels of the expansion sound chip in the stereo field,
+    /// like the stereo modifications of a Famicom, which take the expansion
+    /// audio from the cartridge before it's mixed with the APU: the APU is
+    /// centered, while the expansion channels alternate between the left
+    /// (the even ones) and the right side (the odd ones), half-way panned so
+    /// they're still heard on both sides.
+    pub fn famicom_stereo(&mut self) {
+        for channel in Channel::APU {
+            self.set_pan(channel, 0.0);
+        }
+        for index in 0..EXPANSION_CHANNELS {
+            let pan = if index % 2 == 0 { -0.5 } else { 0.5 };
+            self.set_pan(Channel::Expansion(index), pan);
+        }
+    }
+
+    /// Check if every channel is centered, so the stereo samples are the
+    /// mono samples on both sides.
+    #[must_use]
+    pub fn is_centered(&self) -> bool {
+        self.volumes.iter().all(|volume| volume.pan == 0.0)
+    }
+
+    /// Enable all channels, e.g. to undo [`Mixer::solo`].
+    pub fn enable_all(&mut self) {
+        for volume in &mut self.volumes {
+            volume.enabled = true;
+        }
+    }
+
+    /// Mix the outputs of the APU and the expansion audio with the volumes,
+    /// given the output of the expansion sound chip and a function returning
+    /// the outputs of its channels, which is only called if their volumes
+    /// were changed.
+    ///
+    /// Unless every channel is centered, this is the average of the stereo
+    /// samples, see [`Mixer::mix_stereo`].
+    pub(crate) fn mix(&self, apu: Output, expansion: f32, channel: impl Fn(usize) -> f32) -> f32 {
+        if !self.is_centered() {
+            let [left, right] = self.mix_stereo(apu, expansion, channel);
+            return f32::midpoint(left, right);
+        }
+
+        self.mix_with(apu, expansion, channel, Volume::gain)
+    }
+
+    /// Mix the outputs of the APU and the expansion audio like
+    /// [`Mixer::mix`], into the left and right samples.
+    pub(crate) fn mix_stereo(
+        &self,
+        apu: Output,
+        expansion: f32,
+        channel: impl Fn(usize) -> f32,
+    ) -> [f32; 2] {
+        if self.is_centered() {
+            let sample = self.mix_with(apu, expansion, channel, Volume::gain);
+            return [sample; 2];
+        }
+
+        [0, 1].map(|side| {
+            self.mix_with(apu, expansion, &channel, |volume| {
+                volume.stereo_gains()[side]
+            })
+        })
+    }
+
+    /// Mix the outputs of the APU and the expansion audio with the gains
+    /// returned by the given function for the volumes of the channels.
+    fn mix_with(
+        &self,
+        apu: Output,
+        expansion: f32,
+        channel: impl Fn(usize) -> f32,
+        gain: impl Fn(Volume) -> f32,
+    ) -> f32 {
+        let [pulse1, pulse2, triangle, noise, dmc, ref volumes @ ..] = self.volumes;
+        let expansion = if volumes.iter().all(|&volume| volume == Volume::default()) {
+            expansion
+        } else {
+            let channels = volumes.iter().enumerate();
+            channels
+                .map(|(index, &volume)| gain(volume) * channel(index))
+                .sum()
+        };
+
+        let gains = [pulse1, pulse2, triangle, noise, dmc].map(gain);
+        apu.mix_scaled(gains) + expansion
+    }
+}
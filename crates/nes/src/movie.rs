@@ -0,0 +1,659 @@
+//! Movies, i.e. recordings of the input of every frame, which replay a game
+//! exactly as it was played, e.g. for tool-assisted speedruns (TAS).
+//!
+//! A movie starts with the console's power-up, so it's played back on a newly
+//! created console of its region, with its input devices plugged in, see
+//! [`Movie::ports`], and powered up like while the movie was recorded, see
+//! [`Movie::determinism`]. Then the input of every frame is applied before
+//! the frame is run, see [`Input::apply`], along with the changes within the
+//! frame, see [`Movie::apply`]. Since the emulation is deterministic, the
+//! console goes through the exact same frames as while the movie was
+//! recorded.
+//!
+//! Movies are read and written in FCEUX's text format, `.fm2`, which has a
+//! header of `key value` lines followed by a line per frame:
+//!
+//! ```no-run
+//! version 3
+//! emuVersion 0
+//! rerecordCount 0
+//! palFlag 0
+//! romFilename game
+//! romChecksum base64:AAAAAAAAAAAAAAAAAAAAAA==
+//! guid 00000000-0000-0000-0000-000000000000
+//! fourscore 0
+//! port0 1
+//! port1 1
+//! port2 0
+//! |0|........|........||
+//! |0|....T...|........||
+//! |1|R......A|........||
+//! ```
+//!
+//! A movie recorded with another power-up state than the default also has the
+//! keys `ramInit` (e.g. `random:42`, see [`RamInit`]), `alignment` and
+//! `frameCounter`, see [`DeterminismConfig`].
+//!
+//! Every frame holds its commands (1 for the reset button, 2 for a power
+//! cycle) and the buttons of the controllers in the order `RLDUTSBA`, a `.`
+//! being a released button. With a Four Score, there are 4 controllers.
+//!
+//! The buttons may also change within a frame, see [`Change`], e.g. for games
+//! reading the controllers several times per frame. The changes are an
+//! extension of the format, `subframe` keys with the frame, the timing of the
+//! change (`cycle` or `strobe` and its number, see [`Timing`]) and the
+//! buttons, which FCEUX ignores:
+//!
+//! ```no-run
+//! subframe 2 strobe 1 |.......A|........|
+//! subframe 2 cycle 20000 |........|........|
+//! ```
+//!
+//! ```
+//! # use chuck_input::ButtonState;
+//! # use chuck_nes::determinism::{DeterminismConfig, RamInit};
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::movie::{Input, Movie};
+//! # use chuck_nes::Nes;
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! # let cartridge = || Box::new(Nrom::new(prg.clone(), Vec::new(), Mirroring::Vertical));
+//! let mut movie = Movie::new();
+//!
+//! // Record from a power-up with random RAM, while pressing Start on every
+//! // other frame.
+//! let mut nes = Nes::new(cartridge());
+//! nes.set_determinism(DeterminismConfig {
+//!     ram: RamInit::Random(7),
+//!     ..DeterminismConfig::default()
+//! });
+//! nes.power_cycle();
+//! movie.determinism = nes.determinism();
+//! for frame in 0..10 {
+//!     let mut input = Input::default();
+//!     input.buttons[0].set(ButtonState::START, frame % 2 == 0);
+//!
+//!     input.apply(&mut nes);
+//!     movie.push(input);
+//!     nes.run_frame();
+//! }
+//!
+//! let mut file = Vec::new();
+//! movie.write_fm2(&mut file)?;
+//! let movie = Movie::read_fm2(&mut file.as_slice())?;
+//!
+//! // Play back.
+//! let mut replay = Nes::new(cartridge());
+//! *replay.input_mut() = movie.ports();
+//! replay.set_determinism(movie.determinism);
+//! replay.power_cycle();
+//! for input in movie.inputs() {
+//!     input.apply(&mut replay);
+//!     replay.run_frame();
+//! }
+//!
+//! assert_eq!(replay.ppu().frame_buffer(), nes.ppu().frame_buffer());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://fceux.com/web/help/fm2.html>
+//!
+//! [`RamInit`]: crate::determinism::RamInit
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Write as _};
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use chuck_input::{ButtonState, Controller, FourScore, Port, Ports};
+
+use crate::determinism::DeterminismConfig;
+use crate::{Nes, Region};
+
+/// The version of the FM2 format.
+const VERSION: u32 = 3;
+
+/// The buttons of a controller in the order of the FM2 format, from the
+/// highest bit of [`ButtonState`] to the lowest.
+const BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+/// The device number of an empty port in the FM2 format.
+const NONE: u8 = 0;
+
+/// The device number of a standard controller in the FM2 format.
+const GAMEPAD: u8 = 1;
+
+bitflags::bitflags! {
+    /// The commands of a frame, which are executed before the frame.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Commands: u8 {
+        /// Press the reset button, see [`Nes::reset`].
+        const RESET = 1 << 0;
+        /// Turn the console off and on again, see [`Nes::power_cycle`].
+        const POWER = 1 << 1;
+        /// Insert a disk into the Famicom Disk System, which is ignored.
+        const FDS_INSERT = 1 << 2;
+        /// Select the side of the disk in the Famicom Disk System, which is
+        /// ignored.
+        const FDS_SELECT = 1 << 3;
+        /// Insert a coin into a Vs. System, which is ignored.
+        const VS_COIN = 1 << 4;
+    }
+}
+
+/// The input of a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Input {
+    /// The commands executed before the frame.
+    pub commands: Commands,
+    /// The buttons of the controllers, of which only the first two are used
+    /// without a Four Score.
+    pub buttons: [ButtonState; 4],
+}
+
+impl Input {
+    /// Apply the input to a console before its next frame, executing the
+    /// commands and setting the buttons of its controllers.
+    ///
+    /// The buttons of a port without a [`Controller`] or a [`FourScore`] are
+    /// ignored.
+    pub fn apply(&self, nes: &mut Nes) {
+        if self.commands.contains(Commands::POWER) {
+            nes.power_cycle();
+        } else if self.commands.contains(Commands::RESET) {
+            nes.reset();
+        }
+
+        set_buttons(nes.input_mut(), self.buttons);
+    }
+}
+
+/// When a change of the buttons within a frame takes effect, see [`Change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Before the given CPU cycle, counted from the start of the frame.
+    Cycle(u32),
+    /// Right before the given strobe of the controllers in the frame, counted
+    /// from 0, i.e. the given write to `$4016` which sets `OUT0` after a
+    /// write which cleared it. The controllers then latch the buttons of the
+    /// change, wherever the game strobes them.
+    Strobe(u32),
+}
+
+/// A change of the buttons of the controllers within a frame.
+///
+/// The changes of a frame take effect in their order, each once it's due and
+/// the previous one took effect, like [`Input::apply`] sets the buttons. The
+/// changes which weren't due by the end of the frame take effect then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    /// When the change takes effect.
+    pub at: Timing,
+    /// The buttons of the controllers, see [`Input::buttons`].
+    pub buttons: [ButtonState; 4],
+}
+
+/// The changes of the buttons scheduled for the current frame, see
+/// [`Nes::schedule_changes`].
+#[derive(Debug, Clone)]
+pub(crate) struct Schedule {
+    /// The changes which didn't take effect yet.
+    changes: VecDeque<Change>,
+    /// The CPU cycle at the start of the frame.
+    start: u64,
+    /// The number of strobes in the frame.
+    strobes: u32,
+    /// The last written `OUT0`.
+    strobe: bool,
+}
+
+impl Schedule {
+    /// Apply the changes due before the given CPU cycle.
+    pub(crate) fn cycle(&mut self, cycles: u64, ports: &mut Ports) {
+        self.apply(cycles, self.strobes, ports);
+    }
+
+    /// Observe a write to `$4016` in the given CPU cycle, applying the changes
+    /// due before the strobe it starts, if any.
+    pub(crate) fn write(&mut self, cycles: u64, data: u8, ports: &mut Ports) {
+        let strobe = data & 1 != 0;
+        if strobe && !self.strobe {
+            self.apply(cycles, self.strobes + 1, ports);
+            self.strobes += 1;
+        }
+        self.strobe = strobe;
+    }
+
+    /// Apply the changes which are left at the end of the frame.
+    pub(crate) fn finish(self, ports: &mut Ports) {
+        if let Some(change) = self.changes.back() {
+            set_buttons(ports, change.buttons);
+        }
+    }
+
+    /// Apply the changes due before the given CPU cycle, once the given
+    /// number of strobes started.
+    fn apply(&mut self, cycles: u64, strobes: u32, ports: &mut Ports) {
+        let elapsed = cycles - self.start;
+        while let Some(change) = self.changes.front() {
+            let due = match change.at {
+                Timing::Cycle(cycle) => elapsed >= u64::from(cycle),
+                Timing::Strobe(strobe) => strobe < strobes,
+            };
+            if !due {
+                break;
+            }
+
+            set_buttons(ports, change.buttons);
+            self.changes.pop_front();
+        }
+    }
+}
+
+impl Nes {
+    /// Schedule changes of the buttons within the next frame, see [`Change`],
+    /// whose cycles are counted from now on, so it's called right before the
+    /// frame is run.
+    ///
+    /// The changes aren't part of the save states, since they only last for
+    /// the frame.
+    pub fn schedule_changes(&mut self, changes: &[Change]) {
+        self.schedule = (!changes.is_empty()).then(|| {
+            Box::new(Schedule {
+                changes: changes.iter().copied().collect(),
+                start: self.cpu.cycles,
+                strobes: 0,
+                strobe: false,
+            })
+        });
+    }
+
+    /// Apply the changes of the buttons due before the next CPU cycle, if
+    /// any are scheduled.
+    pub(crate) fn apply_changes(&mut self) {
+        if let Some(schedule) = &mut self.schedule {
+            schedule.cycle(self.cpu.cycles, &mut self.input);
+        }
+    }
+
+    /// Apply the changes of the buttons which are left at the end of the
+    /// frame.
+    pub(crate) fn finish_changes(&mut self) {
+        if let Some(schedule) = self.schedule.take() {
+            schedule.finish(&mut self.input);
+        }
+    }
+}
+
+/// Set the buttons of the controllers, ignoring the ports without a
+/// [`Controller`] or a [`FourScore`].
+fn set_buttons(ports: &mut Ports, buttons: [ButtonState; 4]) {
+    for (port, index) in [(Port::One, 0), (Port::Two, 1)] {
+        if let Some(controller) = ports.device_mut::<Controller>(port) {
+            controller.set_buttons(buttons[index]);
+        } else if let Some(four_score) = ports.device_mut::<FourScore>(port) {
+            four_score.set_buttons(0, buttons[index]);
+            four_score.set_buttons(1, buttons[index + 2]);
+        }
+    }
+}
+
+/// A movie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// The version of the emulator that recorded the movie, which is `0` for
+    /// Chuck.
+    pub emu_version: u32,
+    /// The number of times the movie was re-recorded, see
+    /// [`Movie::truncate`].
+    pub rerecord_count: u32,
+    /// A flag denoting if the movie was recorded on a PAL console.
+    pub pal: bool,
+    /// The name of the ROM file, without its extension.
+    pub rom_filename: String,
+    /// The checksum of the ROM, as written by FCEUX (the MD5 hash of the ROM,
+    /// encoded in base64), which is only kept.
+    pub rom_checksum: String,
+    /// The unique identifier of the movie.
+    pub guid: String,
+    /// A flag denoting if a Four Score is plugged in.
+    pub four_score: bool,
+    /// Flags denoting if a controller is plugged into each port, without a
+    /// Four Score.
+    pub controllers: [bool; 2],
+    /// The configuration of the power-up state of the console, which is set
+    /// before the power cycle that starts the playback, see
+    /// [`Nes::set_determinism`].
+    pub determinism: DeterminismConfig,
+    /// The comments, e.g. `author Name`.
+    pub comments: Vec<String>,
+    /// The subtitles, each being the frame and the text.
+    pub subtitles: Vec<String>,
+    /// The input of every frame.
+    inputs: Vec<Input>,
+    /// The changes of the buttons within the frames, by frame.
+    changes: BTreeMap<usize, Vec<Change>>,
+}
+
+impl Movie {
+    /// Create an empty movie of an NTSC console with two controllers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            emu_version: 0,
+            rerecord_count: 0,
+            pal: false,
+            rom_filename: String::new(),
+            rom_checksum: String::new(),
+            guid: "00000000-0000-0000-0000-000000000000".to_owned(),
+            four_score: false,
+            controllers: [true, true],
+            determinism: DeterminismConfig::default(),
+            comments: Vec::new(),
+            subtitles: Vec::new(),
+            inputs: Vec::new(),
+            changes: BTreeMap::new(),
+        }
+    }
+
+    /// Return the region of the console the movie was recorded on, where
+    /// PAL movies are played back on PAL consoles, not on the Dendy.
+    #[must_use]
+    pub const fn region(&self) -> Region {
+        if self.pal {
+            Region::Pal
+        } else {
+            Region::Ntsc
+        }
+    }
+
+    /// Return controller ports with the devices the movie was recorded with.
+    #[must_use]
+    pub fn ports(&self) -> Ports {
+        if self.four_score {
+            return Ports::four_score();
+        }
+
+        let mut ports = Ports::new();
+        for (port, controller) in [Port::One, Port::Two].into_iter().zip(self.controllers) {
+            if !controller {
+                ports.unplug(port);
+            }
+        }
+
+        ports
+    }
+
+    /// Return the input of every frame.
+    #[must_use]
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs
+    }
+
+    /// Append the input of the next frame.
+    pub fn push(&mut self, input: Input) {
+        self.inputs.push(input);
+    }
+
+    /// Return the changes of the buttons within the given frame.
+    #[must_use]
+    pub fn changes(&self, frame: usize) -> &[Change] {
+        self.changes.get(&frame).map_or(&[], Vec::as_slice)
+    }
+
+    /// Append a change of the buttons within the given frame, after its other
+    /// changes.
+    pub fn push_change(&mut self, frame: usize, change: Change) {
+        self.changes.entry(frame).or_default().push(change);
+    }
+
+    /// Apply the input of the given frame to a console before the frame, and
+    /// schedule the changes within the frame, see [`Input::apply`] and
+    /// [`Nes::schedule_changes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the movie has no input of the frame.
+    pub fn apply(&self, frame: usize, nes: &mut Nes) {
+        self.inputs[frame].apply(nes);
+        nes.schedule_changes(self.changes(frame));
+    }
+
+    /// Drop the input of the frames from the given frame on, to re-record
+    /// them, which counts as a re-record.
+    pub fn truncate(&mut self, frames: usize) {
+        self.inputs.truncate(frames);
+        self.changes.split_off(&frames);
+        self.rerecord_count = self.rerecord_count.saturating_add(1);
+    }
+
+    /// Write the buttons of the controllers of the movie, a field each,
+    /// which is empty for an unplugged controller.
+    fn write_buttons(&self, text: &mut String, buttons: [ButtonState; 4]) {
+        let controllers: &[usize] = if self.four_score {
+            &[0, 1, 2, 3]
+        } else {
+            &[0, 1]
+        };
+        for &controller in controllers {
+            if self.four_score || self.controllers[controller] {
+                text.extend(format_buttons(buttons[controller]));
+            }
+            text.push('|');
+        }
+    }
+
+    /// Read a movie in the FM2 format.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the movie is malformed, is in the
+    /// binary variant of the format, or uses devices other than controllers.
+    pub fn read_fm2(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut movie = Self::new();
+        let mut ports = [GAMEPAD, GAMEPAD];
+
+        for (number, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            let invalid = |message| invalid(&format!("line {}: {message}", number + 1));
+
+            if line.starts_with('|') {
+                let controllers = if movie.four_score { 4 } else { 2 };
+                let input = parse_input(line, controllers).ok_or_else(|| invalid("bad input"))?;
+                movie.inputs.push(input);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let number = || value.parse::<u32>().map_err(|_| invalid("bad number"));
+
+            match key {
+                "version" if number()? != VERSION => return Err(invalid("unsupported version")),
+                "emuVersion" => movie.emu_version = number()?,
+                "rerecordCount" => movie.rerecord_count = number()?,
+                "palFlag" => movie.pal = number()? != 0,
+                "romFilename" => value.clone_into(&mut movie.rom_filename),
+                "romChecksum" => value.clone_into(&mut movie.rom_checksum),
+                "guid" => value.clone_into(&mut movie.guid),
+                "fourscore" => movie.four_score = number()? != 0,
+                "port0" | "port1" => {
+                    let device = u8::try_from(number()?).unwrap_or(u8::MAX);
+                    if device != NONE && device != GAMEPAD {
+                        return Err(invalid("unsupported input device"));
+                    }
+                    ports[usize::from(key == "port1")] = device;
+                }
+                "ramInit" => {
+                    movie.determinism.ram = value.parse().map_err(|_| invalid("bad RAM init"))?;
+                }
+                "alignment" => {
+                    let alignment = u8::try_from(number()?).map_err(|_| invalid("bad number"))?;
+                    movie.determinism.alignment = alignment;
+                }
+                "frameCounter" => {
+                    let cycles = u16::try_from(number()?).map_err(|_| invalid("bad number"))?;
+                    movie.determinism.frame_counter = cycles;
+                }
+                "binary" if number()? != 0 => return Err(invalid("binary movies unsupported")),
+                "comment" => movie.comments.push(value.to_owned()),
+                "subtitle" => movie.subtitles.push(value.to_owned()),
+                "subframe" => {
+                    let controllers = if movie.four_score { 4 } else { 2 };
+                    let (frame, change) = parse_change(value, controllers)
+                        .ok_or_else(|| invalid("bad subframe change"))?;
+                    movie.push_change(frame, change);
+                }
+                // The other keys, like the expansion port (`port2`), don't
+                // affect the playback.
+                _ => {}
+            }
+        }
+
+        if movie.determinism.alignment >= movie.region().cpu_divider() {
+            return Err(invalid("alignment out of range"));
+        }
+
+        movie.controllers = ports.map(|device| device == GAMEPAD);
+        Ok(movie)
+    }
+
+    /// Write the movie in the FM2 format.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn write_fm2(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut text = String::new();
+        // Writing to a `String` can't fail.
+        let mut line = |key: &str, value: &dyn fmt::Display| {
+            let _ = writeln!(text, "{key} {value}");
+        };
+
+        line("version", &VERSION);
+        line("emuVersion", &self.emu_version);
+        line("rerecordCount", &self.rerecord_count);
+        line("palFlag", &u8::from(self.pal));
+        line("romFilename", &self.rom_filename);
+        line("romChecksum", &self.rom_checksum);
+        line("guid", &self.guid);
+        line("fourscore", &u8::from(self.four_score));
+        for (key, controller) in ["port0", "port1"].into_iter().zip(self.controllers) {
+            let device = if controller && !self.four_score {
+                GAMEPAD
+            } else {
+                NONE
+            };
+            line(key, &device);
+        }
+        line("port2", &0);
+        let determinism = self.determinism;
+        if determinism != DeterminismConfig::default() {
+            line("ramInit", &determinism.ram);
+            line("alignment", &determinism.alignment);
+            line("frameCounter", &determinism.frame_counter);
+        }
+        for comment in &self.comments {
+            line("comment", comment);
+        }
+        for subtitle in &self.subtitles {
+            line("subtitle", subtitle);
+        }
+        for (frame, changes) in &self.changes {
+            for change in changes {
+                let (timing, number) = match change.at {
+                    Timing::Cycle(cycle) => ("cycle", cycle),
+                    Timing::Strobe(strobe) => ("strobe", strobe),
+                };
+                let _ = write!(text, "subframe {frame} {timing} {number} |");
+                self.write_buttons(&mut text, change.buttons);
+                text.push('\n');
+            }
+        }
+
+        for input in &self.inputs {
+            let _ = write!(text, "|{}|", input.commands.bits());
+            self.write_buttons(&mut text, input.buttons);
+
+            // The expansion port is empty.
+            text.push_str("|\n");
+        }
+
+        writer.write_all(text.as_bytes())
+    }
+}
+
+/// Parse the input of a frame, with the given number of controllers.
+fn parse_input(line: &str, controllers: usize) -> Option<Input> {
+    let mut fields = line.strip_prefix('|')?.split('|');
+    let commands = Commands::from_bits_retain(fields.next()?.trim().parse().ok()?);
+
+    Some(Input {
+        commands,
+        buttons: parse_buttons(fields, controllers)?,
+    })
+}
+
+/// Parse a change of the buttons within a frame, i.e. the value of a
+/// `subframe` key, with the given number of controllers, returning its frame
+/// and the change.
+fn parse_change(value: &str, controllers: usize) -> Option<(usize, Change)> {
+    let mut parts = value.splitn(4, ' ');
+    let frame = parts.next()?.parse().ok()?;
+    let timing = parts.next()?;
+    let number = parts.next()?.parse().ok()?;
+    let at = match timing {
+        "cycle" => Timing::Cycle(number),
+        "strobe" => Timing::Strobe(number),
+        _ => return None,
+    };
+
+    let fields = parts.next()?.strip_prefix('|')?.split('|');
+    let buttons = parse_buttons(fields, controllers)?;
+    Some((frame, Change { at, buttons }))
+}
+
+/// Parse the buttons of the given number of controllers, a field each.
+fn parse_buttons<'a>(
+    mut fields: impl Iterator<Item = &'a str>,
+    controllers: usize,
+) -> Option<[ButtonState; 4]> {
+    let mut buttons = [ButtonState::empty(); 4];
+    for buttons in &mut buttons[..controllers] {
+        let field = fields.next()?.as_bytes();
+        if field.is_empty() {
+            continue;
+        }
+
+        if field.len() != BUTTONS.len() {
+            return None;
+        }
+
+        // A button is pressed unless it's a `.` or a space.
+        let bits = field
+            .iter()
+            .fold(0, |bits, &c| (bits << 1) | u8::from(c != b'.' && c != b' '));
+        *buttons = ButtonState::from_bits_retain(bits);
+    }
+
+    Some(buttons)
+}
+
+/// Format the buttons of a controller.
+fn format_buttons(buttons: ButtonState) -> impl Iterator<Item = char> {
+    BUTTONS.iter().enumerate().map(move |(i, &button)| {
+        if buttons.bits() & (0x80 >> i) == 0 {
+            '.'
+        } else {
+            char::from(button)
+        }
+    })
+}
+
+/// Return an error for a malformed movie.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
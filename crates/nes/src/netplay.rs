@@ -0,0 +1,495 @@
+//! Netplay, i.e. two players playing together on different machines, each
+//! running their own console and controlling one of its controllers.
+//!
+//! Both consoles must start in the same state, e.g. newly created with the
+//! same cartridge, and only exchange their input: since the emulation is
+//! deterministic, they go through the same frames if they get the same input.
+//! The input of the local player is delayed by a few frames, which hides the
+//! latency of the connection if it's short enough. Otherwise, the console
+//! doesn't wait for the other player's input, but predicts it by repeating
+//! their last input, and runs ahead. Once the input arrives and differs from
+//! the prediction, the console rolls back to the snapshot of the first
+//! mispredicted frame and runs the frames since then again with the actual
+//! input, all within a single frame shown to the player.
+//!
+//! Alternatively, the consoles run in lockstep, see [`Mode::Lockstep`]: a
+//! console waits for the other player's input of every frame before running
+//! it, so it never predicts nor rolls back, and doesn't even save its state
+//! for every frame. The input delay alone has to hide the latency then, or
+//! the game stutters, but this is much cheaper on slow machines, and
+//! separates the bugs of the emulation, which make the consoles diverge in
+//! any mode, from those of the rollbacks.
+//!
+//! The consoles also exchange checksums of their states every few frames to
+//! detect that they diverged (desync), e.g. because they didn't start in the
+//! same state, see [`Session::desync`].
+//!
+//! A [`Session`] doesn't do any networking itself: the frontend sends the
+//! messages returned by [`Session::messages`] to the other player over any
+//! transport, e.g. UDP, and passes the messages it receives to
+//! [`Session::receive`]. Messages may be lost, duplicated or reordered, since
+//! the input not yet acknowledged by the other player is sent again with
+//! every frame.
+//!
+//! ```
+//! # use chuck_input::{ButtonState, Port};
+//! # use chuck_nes::{mapper::{Mirroring, Nrom}, netplay::Session, Nes};
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! # let cartridge = || Box::new(Nrom::new(prg.clone(), Vec::new(), Mirroring::Vertical));
+//! // Two players in one process, with 2 frames of input delay.
+//! let mut consoles = [Nes::new(cartridge()), Nes::new(cartridge())];
+//! let mut sessions = [Session::new(Port::One, 2), Session::new(Port::Two, 2)];
+//!
+//! for _ in 0..60 {
+//!     for (nes, session) in consoles.iter_mut().zip(&mut sessions) {
+//!         session.advance(nes, ButtonState::A);
+//!     }
+//!
+//!     let [one, two] = &mut sessions;
+//!     for message in one.messages() {
+//!         two.receive(&message)?;
+//!     }
+//!     for message in two.messages() {
+//!         one.receive(&message)?;
+//!     }
+//! }
+//!
+//! assert_eq!(sessions[0].frame(), 60);
+//! assert_eq!(sessions[0].desync(), None);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://words.infil.net/w02-netcode.html>
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+
+use chuck_input::{ButtonState, Controller, Port};
+
+use crate::{Frame, Nes};
+
+/// The maximum number of frames the console runs ahead of the other player's
+/// input, which bounds the number of frames run again on a rollback.
+const MAX_PREDICTION: u32 = 8;
+
+/// The number of frames between two checksums.
+const CHECKSUM_INTERVAL: u32 = 30;
+
+/// The maximum number of checksums kept while waiting for the other player's
+/// checksum of the same frame.
+const CHECKSUMS: usize = 32;
+
+/// The tag of a message with the input of a player.
+const INPUT: u8 = 0;
+
+/// The tag of a message with the checksum of a state.
+const CHECKSUM: u8 = 1;
+
+/// A frame that was run.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    /// The input of the other player the frame was run with.
+    remote: ButtonState,
+    /// The save state of the console at the start of the frame, which is only
+    /// saved in lockstep if its checksum is computed.
+    state: Vec<u8>,
+}
+
+/// How a console deals with the other player's input arriving late.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Predict the other player's input and run ahead, rolling back once the
+    /// prediction turns out to be wrong.
+    #[default]
+    Rollback,
+    /// Wait for the other player's input, only running frames whose input
+    /// of both players is known.
+    Lockstep,
+}
+
+/// A netplay session, as seen by one of the two players.
+///
+/// All frames are numbered from the start of the session.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The port of the local player's controller.
+    port: Port,
+    /// How late input of the other player is dealt with.
+    mode: Mode,
+    /// The number of frames the local input is delayed by.
+    delay: u32,
+    /// The number of frames run.
+    frame: u32,
+    /// The first frame still kept.
+    origin: u32,
+    /// The number of frames whose local input the other player received.
+    acked: u32,
+    /// The input of the local player for every frame from the origin on.
+    local: VecDeque<ButtonState>,
+    /// The input of the other player for every frame from the origin on, as
+    /// far as it's known.
+    remote: VecDeque<ButtonState>,
+    /// The last known input of the other player, which is the prediction for
+    /// the following frames.
+    latest: ButtonState,
+    /// The frames run from the origin on.
+    history: VecDeque<Snapshot>,
+    /// The first frame that was run with a mispredicted input, if any.
+    rollback: Option<u32>,
+    /// The next frame to compute the checksum of.
+    next_checksum: u32,
+    /// The local checksums, by frame.
+    checksums: BTreeMap<u32, u64>,
+    /// The other player's checksums, by frame.
+    remote_checksums: BTreeMap<u32, u64>,
+    /// The first frame whose checksums differ, if any.
+    desync: Option<u32>,
+    /// The messages to send to the other player.
+    outgoing: VecDeque<Vec<u8>>,
+}
+
+impl Session {
+    /// Create a session in which the local player controls the controller in
+    /// the given port, and the other player the controller in the other port,
+    /// with the local input delayed by `delay` frames.
+    ///
+    /// Both players must use the same delay.
+    #[must_use]
+    pub fn new(port: Port, delay: u32) -> Self {
+        Self::with_mode(port, delay, Mode::Rollback)
+    }
+
+    /// Create a session like [`Session::new`], which deals with late input
+    /// of the other player in the given way.
+    ///
+    /// Both players must use the same mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics in lockstep if the local input isn't delayed, since then the
+    /// consoles would wait for each other forever.
+    #[must_use]
+    pub fn with_mode(port: Port, delay: u32, mode: Mode) -> Self {
+        assert!(
+            mode == Mode::Rollback || delay > 0,
+            "lockstep needs an input delay"
+        );
+
+        Self {
+            port,
+            mode,
+            delay,
+            frame: 0,
+            origin: 0,
+            acked: 0,
+            local: (0..delay).map(|_| ButtonState::empty()).collect(),
+            remote: VecDeque::new(),
+            latest: ButtonState::empty(),
+            history: VecDeque::new(),
+            rollback: None,
+            next_checksum: 0,
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            desync: None,
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    /// Return the port of the local player's controller.
+    #[must_use]
+    pub fn port(&self) -> Port {
+        self.port
+    }
+
+    /// Return how late input of the other player is dealt with.
+    #[must_use]
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Return the number of frames the local input is delayed by.
+    #[must_use]
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    /// Return the number of frames run.
+    #[must_use]
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Return the number of frames whose input of the other player is known.
+    #[must_use]
+    pub fn confirmed(&self) -> u32 {
+        self.origin + u32::try_from(self.remote.len()).unwrap_or(u32::MAX)
+    }
+
+    /// Return the first frame at whose start the states of the two consoles
+    /// differed, if they diverged.
+    #[must_use]
+    pub fn desync(&self) -> Option<u32> {
+        self.desync
+    }
+
+    /// Run the next frame of the console, with the given buttons of the local
+    /// player pressed `delay` frames later, after rolling back and running
+    /// the mispredicted frames again.
+    ///
+    /// Returns the output of the frame, or `None` without running it if the
+    /// console is too far ahead of the other player's input (or at all ahead
+    /// of it in lockstep), in which case the frame should be run again later.
+    /// The frames run again on a rollback only update the picture, their
+    /// audio is dropped.
+    pub fn advance<'a>(&mut self, nes: &'a mut Nes, buttons: ButtonState) -> Option<Frame<'a>> {
+        let prediction = match self.mode {
+            Mode::Rollback => MAX_PREDICTION,
+            Mode::Lockstep => 0,
+        };
+        if self.frame >= self.confirmed() + prediction {
+            self.send_input();
+            return None;
+        }
+
+        if let Some(frame) = self.rollback.take() {
+            self.run_again(nes, frame);
+        }
+
+        // In lockstep, a state is only needed for its checksum.
+        let state = if self.mode == Mode::Rollback || self.frame == self.next_checksum {
+            nes.state()
+        } else {
+            Vec::new()
+        };
+        self.local.push_back(buttons);
+        self.history.push_back(Snapshot {
+            remote: self.predict(self.frame),
+            state,
+        });
+
+        self.compute_checksums();
+        self.trim();
+        self.send_input();
+
+        self.apply(nes, self.frame);
+        self.frame += 1;
+        Some(nes.run_frame())
+    }
+
+    /// Receive a message sent by the other player.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the message
+    /// is malformed, which leaves the session untouched.
+    pub fn receive(&mut self, message: &[u8]) -> io::Result<()> {
+        match message.split_first() {
+            Some((&INPUT, body)) => {
+                let (ack, body) = read_u32(body)?;
+                let (start, body) = read_u32(body)?;
+
+                let sent = self.origin + u32::try_from(self.local.len()).unwrap_or(u32::MAX);
+                self.acked = self.acked.max(ack.min(sent));
+
+                for (frame, &bits) in (start..).zip(body) {
+                    let confirmed = self.confirmed();
+                    if frame > confirmed {
+                        break;
+                    }
+
+                    if frame == confirmed {
+                        self.confirm(frame, ButtonState::from_bits_retain(bits));
+                    }
+                }
+
+                Ok(())
+            }
+            Some((&CHECKSUM, body)) => {
+                let (frame, body) = read_u32(body)?;
+                let checksum = body
+                    .first_chunk()
+                    .map(|bytes| u64::from_le_bytes(*bytes))
+                    .ok_or_else(|| invalid("truncated netplay message"))?;
+
+                insert(&mut self.remote_checksums, frame, checksum);
+                self.compare_checksums(frame);
+                Ok(())
+            }
+            _ => Err(invalid("unknown netplay message")),
+        }
+    }
+
+    /// Return the messages to send to the other player, removing them from
+    /// the session.
+    pub fn messages(&mut self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.outgoing.drain(..)
+    }
+
+    /// Return the index of a frame kept in the session.
+    fn index(&self, frame: u32) -> usize {
+        usize::try_from(frame - self.origin).unwrap_or(usize::MAX)
+    }
+
+    /// Return the input of the other player for a frame, or the prediction of
+    /// it if it isn't known yet.
+    fn predict(&self, frame: u32) -> ButtonState {
+        if frame < self.confirmed() {
+            self.remote[self.index(frame)]
+        } else {
+            self.latest
+        }
+    }
+
+    /// Note the input of the other player for the next frame whose input
+    /// wasn't known, scheduling a rollback if the frame was already run with
+    /// another input.
+    fn confirm(&mut self, frame: u32, buttons: ButtonState) {
+        self.remote.push_back(buttons);
+        self.latest = buttons;
+
+        if frame < self.frame && self.history[self.index(frame)].remote != buttons {
+            self.rollback = Some(self.rollback.map_or(frame, |first| first.min(frame)));
+        }
+    }
+
+    /// Set the buttons of both controllers for a frame.
+    fn apply(&self, nes: &mut Nes, frame: u32) {
+        let index = self.index(frame);
+        let (local, remote) = (self.local[index], self.history[index].remote);
+        let buttons = match self.port {
+            Port::One => [local, remote],
+            Port::Two => [remote, local],
+        };
+
+        let ports = nes.input_mut();
+        for (port, buttons) in [Port::One, Port::Two].into_iter().zip(buttons) {
+            if let Some(controller) = ports.device_mut::<Controller>(port) {
+                controller.set_buttons(buttons);
+            }
+        }
+    }
+
+    /// Roll back to the start of the given frame, and run the frames from it
+    /// to the current frame again with the updated input.
+    fn run_again(&mut self, nes: &mut Nes, from: u32) {
+        let state = &self.history[self.index(from)].state;
+        // The state was saved by the same console, so it always loads.
+        if nes.load_state(&mut state.as_slice()).is_err() {
+            return;
+        }
+
+        for frame in from..self.frame {
+            let index = self.index(frame);
+            if frame != from {
+                self.history[index].state = nes.state();
+            }
+            self.history[index].remote = self.predict(frame);
+
+            self.apply(nes, frame);
+            nes.run_frame();
+        }
+    }
+
+    /// Compute the checksums of the states whose frames were all run with
+    /// known input, and send them to the other player.
+    fn compute_checksums(&mut self) {
+        while self.next_checksum <= self.confirmed().min(self.frame) {
+            let frame = self.next_checksum;
+            let checksum = checksum(&self.history[self.index(frame)].state);
+
+            insert(&mut self.checksums, frame, checksum);
+            self.compare_checksums(frame);
+
+            let mut message = vec![CHECKSUM];
+            message.extend(frame.to_le_bytes());
+            message.extend(checksum.to_le_bytes());
+            self.outgoing.push_back(message);
+
+            self.next_checksum += CHECKSUM_INTERVAL;
+        }
+    }
+
+    /// Compare the checksums of both players for a frame, if both are known.
+    fn compare_checksums(&mut self, frame: u32) {
+        let (Some(&local), Some(&remote)) = (
+            self.checksums.get(&frame),
+            self.remote_checksums.get(&frame),
+        ) else {
+            return;
+        };
+
+        if local != remote && self.desync.is_none_or(|first| frame < first) {
+            self.desync = Some(frame);
+        }
+
+        self.checksums.remove(&frame);
+        self.remote_checksums.remove(&frame);
+    }
+
+    /// Drop the frames that can't be rolled back to anymore, and whose local
+    /// input the other player received.
+    fn trim(&mut self) {
+        let origin = self.acked.min(self.confirmed()).min(self.frame);
+
+        while self.origin < origin {
+            self.local.pop_front();
+            self.remote.pop_front();
+            self.history.pop_front();
+            self.origin += 1;
+        }
+    }
+
+    /// Send the local input the other player didn't receive yet, along with
+    /// the number of frames whose input of the other player is known,
+    /// replacing any such message not sent yet.
+    fn send_input(&mut self) {
+        self.outgoing.retain(|message| message[0] != INPUT);
+
+        let mut message = vec![INPUT];
+        message.extend(self.confirmed().to_le_bytes());
+        message.extend(self.acked.to_le_bytes());
+        message.extend(
+            self.local
+                .range(self.index(self.acked)..)
+                .map(ButtonState::bits),
+        );
+        self.outgoing.push_back(message);
+    }
+}
+
+/// Insert a checksum, dropping the oldest ones beyond [`CHECKSUMS`].
+fn insert(checksums: &mut BTreeMap<u32, u64>, frame: u32, checksum: u64) {
+    checksums.insert(frame, checksum);
+
+    while checksums.len() > CHECKSUMS {
+        checksums.pop_first();
+    }
+}
+
+/// Return the checksum of some bytes like a save state, i.e. their 64-bit
+/// FNV-1a hash, which unlike the hashers of the standard library is the same
+/// on all machines.
+pub(crate) fn checksum<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u64 {
+    bytes
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Read a little-endian `u32` from the start of a message.
+fn read_u32(message: &[u8]) -> io::Result<(u32, &[u8])> {
+    message
+        .split_first_chunk()
+        .map(|(bytes, rest)| (u32::from_le_bytes(*bytes), rest))
+        .ok_or_else(|| invalid("truncated netplay message"))
+}
+
+/// Return an error for a malformed message.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
@@ -0,0 +1,442 @@
+//! A player of NES music files, see [`chuck_rom::nsf`].
+//!
+//! The program of a music file is run on the CPU and APU cores, without a PPU
+//! or a cartridge. Instead, the player maps the program into `$8000`-`$FFFF`
+//! (in 4 KiB banks, if it's bank switched), together with 8 KiB of RAM at
+//! `$6000`-`$7FFF`, and a small driver routine, which calls the `INIT` routine
+//! once for the selected track and then the `PLAY` routine at the rate the
+//! file asks for.
+//!
+//! ```no-run
+//! $0000-$07FF  RAM
+//! $4000-$4017  APU
+//! $4100-$4112  Driver (internal)
+//! $5FF8-$5FFF  Bank registers, if bank switched
+//! $6000-$7FFF  RAM
+//! $8000-$FFFF  Program, with the vectors pointing at the driver
+//! $9000-$B002  VRC6 audio, if used
+//! ```
+//!
+//! Of the expansion sound chips, only the VRC6 is emulated, the others are
+//! silent. The DMC's sample fetches don't halt the CPU, which only a few
+//! tracks would notice.
+//!
+//! ```
+//! # use chuck_nes::nsf::Player;
+//! # use chuck_rom::nsf::Nsf;
+//! let mut file = b"NESM\x1a\x01\x01\x01\x00\x80\x00\x80\x10\x80".to_vec();
+//! file.resize(0x80, 0);
+//! file[0x6e..0x70].copy_from_slice(&16_639u16.to_le_bytes());
+//! // INIT plays a square wave, PLAY counts its calls at $0000.
+//! file.extend([
+//!     0xa9, 0xbf, 0x8d, 0x00, 0x40, 0xa9, 0xfd, 0x8d, 0x02, 0x40, 0xa9, 0x00, 0x8d, 0x03, 0x40,
+//!     0x60, 0xe6, 0x00, 0x60,
+//! ]);
+//!
+//! let mut player = Player::new(&Nsf::parse(&file).unwrap());
+//! assert_eq!(player.tracks(), 1);
+//!
+//! for _ in 0..60 {
+//!     let samples = player.run_frame();
+//!     assert!(samples.len() >= 29_780);
+//! }
+//!
+//! assert!((59..=60).contains(&player.ram()[0]));
+//! assert!(player.elapsed().as_secs_f64() > 0.99);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/NSF>
+//! - <https://www.nesdev.org/wiki/NSF#Initializing_a_tune>
+
+use std::time::Duration;
+
+use chuck_apu::{Apu, Pins as ApuPins};
+use chuck_cpu::Cpu;
+use chuck_rom::nsf::{Expansion, Nsf};
+
+use crate::mapper::Vrc6Audio;
+use crate::mixer::Mixer;
+use crate::Region;
+
+/// The address of the driver.
+const DRIVER: u16 = 0x4100;
+
+/// The address of the flag which the driver polls, whose bit 7 is set once
+/// the `PLAY` routine is due.
+const PLAY_FLAG: u16 = 0x41f0;
+
+/// The size of a bank of the program.
+const BANK_SIZE: usize = 0x1000;
+
+/// The banks of a program which isn't bank switched.
+const LINEAR: [u8; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+/// The driver, which calls the `INIT` routine with the track in `A` and the
+/// region in `X`, and then the `PLAY` routine whenever it's due. The
+/// interrupt handlers return immediately.
+///
+/// ```no-run
+/// $4100  LDA #track
+/// $4102  LDX #region
+/// $4104  JSR init
+/// $4107  BIT $41F0
+/// $410A  BPL $4107
+/// $410C  JSR play
+/// $410F  JMP $4107
+/// $4112  RTI
+/// ```
+const fn driver(track: u8, region: u8, init: u16, play: u16) -> [u8; 19] {
+    let [init_low, init_high] = init.to_le_bytes();
+    let [play_low, play_high] = play.to_le_bytes();
+
+    [
+        0xa9, track, 0xa2, region, 0x20, init_low, init_high, 0x2c, 0xf0, 0x41, 0x10, 0xfb, 0x20,
+        play_low, play_high, 0x4c, 0x07, 0x41, 0x40,
+    ]
+}
+
+/// The address of the `RTI` of the driver, the handler of the interrupts.
+const RTI: u16 = DRIVER + 0x12;
+
+/// A player of a music file.
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// The music file.
+    nsf: Nsf,
+    /// The region of the console.
+    region: Region,
+    /// The selected track.
+    track: u8,
+    /// The 2A03's CPU core.
+    cpu: Cpu,
+    /// The 2A03's APU.
+    apu: Apu,
+    /// The 2 KiB of RAM of the CPU.
+    ram: Box<[u8; 0x800]>,
+    /// The 8 KiB of RAM at `$6000`-`$7FFF`.
+    wram: Box<[u8; 0x2000]>,
+    /// The program, padded to its load address within its first bank.
+    prg: Box<[u8]>,
+    /// The banks of the program mapped into `$8000`-`$FFFF`.
+    banks: [u8; 8],
+    /// The driver.
+    driver: [u8; 19],
+    /// The expansion audio of the VRC6, if it's used.
+    vrc6: Option<Vrc6Audio>,
+    /// The time since the `PLAY` routine was last due, in microseconds.
+    timer: f64,
+    /// A flag denoting if the `PLAY` routine is due.
+    play: bool,
+    /// The number of master clock cycles of the current frame which are left
+    /// over for the next frame.
+    phase: u32,
+    /// The number of CPU cycles since the track was selected.
+    cycles: u64,
+    /// The value last driven onto the data bus of the CPU, see the open bus
+    /// of [`Nes`](crate::Nes).
+    open_bus: u8,
+    /// The audio samples of the current frame.
+    samples: Vec<f32>,
+    /// The volumes of the audio channels.
+    mixer: Mixer,
+}
+
+impl Player {
+    /// Create a player of the given music file, in the region the music was
+    /// made for, and start its first track.
+    #[must_use]
+    pub fn new(nsf: &Nsf) -> Self {
+        Self::with_region(nsf, Region::from(nsf.region))
+    }
+
+    /// Create a player of the given music file in the given region, and
+    /// start its first track.
+    ///
+    /// The music is told it's played on PAL for both PAL and the Dendy, whose
+    /// frame rates are the same.
+    #[must_use]
+    pub fn with_region(nsf: &Nsf, region: Region) -> Self {
+        let padding = if nsf.banks.is_some() {
+            usize::from(nsf.load & 0x0fff)
+        } else {
+            usize::from(nsf.load) - 0x8000
+        };
+
+        let mut prg = vec![0; padding];
+        prg.extend_from_slice(&nsf.data);
+        prg.resize(prg.len().next_multiple_of(BANK_SIZE).max(BANK_SIZE), 0);
+
+        let mut player = Self {
+            nsf: nsf.clone(),
+            region,
+            track: 0,
+            cpu: Cpu::new(),
+            apu: Apu::with_region(region.apu()),
+            ram: Box::new([0; 0x800]),
+            wram: Box::new([0; 0x2000]),
+            prg: prg.into_boxed_slice(),
+            banks: [0; 8],
+            driver: [0; 19],
+            vrc6: None,
+            timer: 0.0,
+            play: false,
+            phase: 0,
+            cycles: 0,
+            open_bus: 0,
+            samples: Vec::new(),
+            mixer: Mixer::default(),
+        };
+
+        player.select(nsf.start.min(nsf.songs.saturating_sub(1)));
+        player
+    }
+
+    /// Select a track, counting from 0, and start it from the beginning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the music file has no such track.
+    pub fn select(&mut self, track: u8) {
+        assert!(track < self.nsf.songs, "track {track} doesn't exist");
+
+        let region = u8::from(self.region != Region::Ntsc);
+        self.track = track;
+        self.driver = driver(track, region, self.nsf.init, self.nsf.play);
+
+        self.cpu = Cpu::new();
+        self.apu = Apu::with_region(self.region.apu());
+        self.ram.fill(0);
+        self.wram.fill(0);
+        self.banks = self.nsf.banks.unwrap_or(LINEAR);
+        self.vrc6 = self
+            .nsf
+            .expansion
+            .contains(Expansion::VRC6)
+            .then(Vrc6Audio::default);
+        self.timer = 0.0;
+        self.play = false;
+        self.phase = 0;
+        self.cycles = 0;
+
+        // The APU is initialized as the music expects, with the channels
+        // silenced and enabled, and the frame counter's IRQ inhibited.
+        for addr in 0x4000..=0x4013 {
+            self.apu.write(addr, 0);
+        }
+        self.apu.write(0x4015, 0x00);
+        self.apu.write(0x4015, 0x0f);
+        self.apu.write(0x4017, 0x40);
+    }
+
+    /// Return the selected track, counting from 0.
+    #[must_use]
+    pub const fn track(&self) -> u8 {
+        self.track
+    }
+
+    /// Return the number of tracks.
+    #[must_use]
+    pub const fn tracks(&self) -> u8 {
+        self.nsf.songs
+    }
+
+    /// Return the music file.
+    #[must_use]
+    pub const fn nsf(&self) -> &Nsf {
+        &self.nsf
+    }
+
+    /// Return the region of the console.
+    #[must_use]
+    pub const fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Execute a single CPU cycle.
+    pub fn step(&mut self) {
+        self.cpu.step();
+
+        let addr = self.cpu.bus.addr;
+        if self.cpu.bus.write {
+            self.write(addr, self.cpu.bus.data);
+        } else {
+            self.cpu.bus.data = self.read(addr);
+        }
+
+        self.apu.step();
+        if self.apu.pins.contains(ApuPins::DMA) {
+            let data = self.read(self.apu.dmc_address());
+            self.apu.fill_sample_buffer(data);
+        }
+
+        let expansion = self.vrc6.as_mut().map_or(0.0, |vrc6| {
+            vrc6.clock();
+            vrc6.output()
+        });
+        let vrc6 = self.vrc6.as_ref();
+        let sample = self.mixer.mix(self.apu.output(), expansion, |channel| {
+            vrc6.map_or(0.0, |vrc6| vrc6.channel_output(channel))
+        });
+        self.samples.push(sample);
+
+        self.timer += 1e6 / self.region.cpu_clock();
+        let speed = f64::from(self.speed());
+        if self.timer >= speed {
+            self.timer -= speed;
+            self.play = true;
+        }
+
+        self.cycles += 1;
+    }
+
+    /// Execute for the duration of a video frame of the console, returning
+    /// the audio samples produced since the previous call, see
+    /// [`Frame::samples`](crate::Frame::samples).
+    pub fn run_frame(&mut self) -> &[f32] {
+        self.samples.clear();
+
+        let divider = u32::from(self.region.cpu_divider());
+        let dots = u32::from(self.region.ppu().scanlines()) * 341;
+        self.phase += dots * u32::from(self.region.ppu_divider());
+
+        while self.phase >= divider {
+            self.step();
+            self.phase -= divider;
+        }
+
+        &self.samples
+    }
+
+    /// Return the time the selected track has been playing.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        let [low, high] = [self.cycles & 0xffff_ffff, self.cycles >> 32]
+            .map(|half| f64::from(u32::try_from(half).unwrap_or(u32::MAX)));
+        Duration::from_secs_f64(high.mul_add(4_294_967_296.0, low) / self.region.cpu_clock())
+    }
+
+    /// Return the length of the selected track before it fades out, if the
+    /// music file declares it.
+    #[must_use]
+    pub fn length(&self) -> Option<Duration> {
+        self.nsf.tracks.get(usize::from(self.track))?.length
+    }
+
+    /// Return the duration of the fade out of the selected track, if the music
+    /// file declares it.
+    #[must_use]
+    pub fn fade(&self) -> Option<Duration> {
+        self.nsf.tracks.get(usize::from(self.track))?.fade
+    }
+
+    /// Check if the selected track has been played for its length and its
+    /// fade out, which is never the case if its length isn't known.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.length()
+            .is_some_and(|length| self.elapsed() >= length + self.fade().unwrap_or_default())
+    }
+
+    /// Return the CPU.
+    #[must_use]
+    pub const fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// Return the APU.
+    #[must_use]
+    pub const fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    /// Return the names of the channels of the expansion sound chip, whose
+    /// volumes the mixer sets, see [`Player::mixer`].
+    #[must_use]
+    pub fn audio_channels(&self) -> &'static [&'static str] {
+        if self.vrc6.is_some() {
+            &Vrc6Audio::CHANNELS
+        } else {
+            &[]
+        }
+    }
+
+    /// Return the volumes of the audio channels, see [`mixer`](crate::mixer).
+    #[must_use]
+    pub const fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    /// Return the volumes of the audio channels mutably, e.g. to solo one.
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.mixer
+    }
+
+    /// Return the 2 KiB of RAM of the CPU.
+    #[must_use]
+    pub fn ram(&self) -> &[u8; 0x800] {
+        &self.ram
+    }
+
+    /// Return the rate of the `PLAY` routine in the region, in microseconds.
+    fn speed(&self) -> u16 {
+        let [ntsc, pal, dendy] = self.nsf.speeds;
+        match self.region {
+            Region::Ntsc => ntsc,
+            Region::Pal => pal,
+            Region::Dendy => dendy,
+        }
+    }
+
+    /// Return the index into the program of an address within
+    /// `$8000`-`$FFFF`.
+    fn prg_index(&self, addr: u16) -> usize {
+        let bank = usize::from(self.banks[usize::from((addr >> 12) & 7)]);
+        let banks = self.prg.len() / BANK_SIZE;
+        (bank % banks) * BANK_SIZE + usize::from(addr & 0x0fff)
+    }
+
+    /// Read from the CPU bus.
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            0x0000..=0x1fff => Some(self.ram[usize::from(addr & 0x07ff)]),
+            0x4015 => return self.apu.read(addr) | (self.open_bus & 0x20),
+            PLAY_FLAG => Some(u8::from(std::mem::take(&mut self.play)) << 7),
+            DRIVER..=0x4112 => Some(self.driver[usize::from(addr - DRIVER)]),
+            0x6000..=0x7fff => Some(self.wram[usize::from(addr & 0x1fff)]),
+            // The vectors point at the driver.
+            0xfffa | 0xfffe => Some(RTI.to_le_bytes()[0]),
+            0xfffc => Some(DRIVER.to_le_bytes()[0]),
+            0xfffb | 0xfffd | 0xffff => Some(DRIVER.to_le_bytes()[1]),
+            0x8000..=0xffff => Some(self.prg[self.prg_index(addr)]),
+            _ => None,
+        };
+
+        if let Some(data) = data {
+            self.open_bus = data;
+        }
+
+        self.open_bus
+    }
+
+    /// Write to the CPU bus.
+    fn write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+
+        match addr {
+            0x0000..=0x1fff => self.ram[usize::from(addr & 0x07ff)] = data,
+            0x4000..=0x4017 => self.apu.write(addr, data),
+            0x5ff8..=0x5fff if self.nsf.banks.is_some() => {
+                self.banks[usize::from(addr & 7)] = data;
+            }
+            0x6000..=0x7fff => self.wram[usize::from(addr & 0x1fff)] = data,
+            0x9000..=0xb002 => {
+                if let Some(vrc6) = &mut self.vrc6 {
+                    vrc6.write((addr >> 12) - 9, addr & 3, data);
+                }
+            }
+            _ => {}
+        }
+    }
+}
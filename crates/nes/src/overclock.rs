@@ -0,0 +1,87 @@
+//! The overclocking of the CPU by extra scanlines after the start of the
+//! vertical blanking interval, which gives games more time per frame to
+//! reduce their slowdown.
+//!
+//! During the extra scanlines, only the CPU runs: the PPU stands still on the
+//! second dot of the vertical blanking interval, just after it asserted the
+//! `NMI`, while it still completes the accesses of `PPUDATA`. So the game
+//! sees a longer vertical blanking interval, while the rendered part of the
+//! frame and its raster effects keep their timing. The
+//! APU and the cartridge aren't clocked either, so the audio neither changes
+//! its pitch nor gets more samples, and the DMC doesn't fetch more samples.
+//!
+//! The extra cycles are an even number, so the get and put cycles of the DMA
+//! unit keep alternating during and after them.
+//!
+//! # Link(s)
+//!
+//! - <https://www.mesen.ca/docs/configuration/emulation.html>
+
+use crate::Region;
+
+/// The number of dots of a scanline.
+const DOTS: u32 = 341;
+
+/// The state of the overclocking, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct Overclock {
+    /// The number of extra scanlines of every frame.
+    scanlines: u16,
+    /// The number of cycles of the extra scanlines of the current frame which
+    /// haven't run yet.
+    remaining: u32,
+}
+
+impl Overclock {
+    /// Return the number of extra scanlines of every frame.
+    pub const fn scanlines(&self) -> u16 {
+        self.scanlines
+    }
+
+    /// Set the number of extra scanlines of every frame, which takes effect
+    /// with the next frame.
+    pub fn set_scanlines(&mut self, scanlines: u16) {
+        self.scanlines = scanlines;
+    }
+
+    /// Start the extra scanlines of the current frame, each as long as a
+    /// scanline of the PPU of the given region.
+    pub fn start(&mut self, region: Region) {
+        let dots = u32::from(self.scanlines) * DOTS * u32::from(region.ppu_divider());
+        let cycles = dots.div_ceil(u32::from(region.cpu_divider()));
+        self.remaining = cycles + (cycles & 1);
+    }
+
+    /// Check if the extra scanlines of the current frame are running.
+    pub const fn is_running(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Check if the next extra cycle swaps the get and put cycles of the DMA
+    /// unit, since the APU, whose cycles they follow, isn't clocked.
+    pub const fn swaps_dma_cycles(&self) -> bool {
+        self.remaining & 1 == 1
+    }
+
+    /// Count an extra cycle that ran.
+    pub fn cycle(&mut self) {
+        self.remaining -= 1;
+    }
+
+    /// Return the number of extra cycles of the current frame which haven't
+    /// run yet, for save states.
+    pub const fn remaining(&self) -> u32 {
+        self.remaining
+    }
+
+    /// Set the number of extra cycles of the current frame which haven't run
+    /// yet, from a save state.
+    pub fn set_remaining(&mut self, remaining: u32) {
+        self.remaining = remaining;
+    }
+
+    /// Stop the extra scanlines of the current frame, e.g. on power-up.
+    pub fn stop(&mut self) {
+        self.remaining = 0;
+    }
+}
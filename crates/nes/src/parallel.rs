@@ -0,0 +1,336 @@
+//! Running many independent consoles at once, spread across threads, e.g.
+//! for fuzzing or reinforcement learning.
+//!
+//! A console shares no state with any other, not even through statics, so
+//! consoles on different threads only ever see their own emulation. Every
+//! console is `Send` for it, which is checked at compile time. Cloning a
+//! console copies its memories and chips, but the clones share the ROMs of
+//! the cartridge (see [`Mapper::boxed_clone`]), so a [`ParallelRunner`]
+//! usually starts with the clones of a single console:
+//!
+//! ```
+//! # use chuck_input::ButtonState;
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::movie::Input;
+//! # use chuck_nes::parallel::ParallelRunner;
+//! # use chuck_nes::Nes;
+//! # let nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+//! let mut runner = ParallelRunner::replicate(&nes, 64);
+//!
+//! // Every console presses other buttons.
+//! let inputs: Vec<_> = (0..64)
+//!     .map(|index| {
+//!         let mut input = Input::default();
+//!         input.buttons[0] = ButtonState::from_bits_truncate(index);
+//!         input
+//!     })
+//!     .collect();
+//! let frames = runner.run_frame(&inputs, |_, nes| nes.ppu().frame());
+//!
+//! assert_eq!(frames, [1; 64]);
+//! ```
+//!
+//! [`Mapper::boxed_clone`]: crate::mapper::Mapper::boxed_clone
+
+use std::any::Any;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::movie::Input;
+use crate::Nes;
+
+/// Fails to compile unless the consoles can be moved to other threads.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Nes>();
+};
+
+/// A job run by a worker thread.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The results of a group of consoles: the index of the group, its consoles
+/// and either the results of the function or the payload of its panic.
+type Group<T> = (usize, Vec<Nes>, Result<Vec<T>, Box<dyn Any + Send>>);
+
+/// A batch of consoles, which are stepped together across threads, see the
+/// [module documentation](self).
+///
+/// The runner keeps its worker threads from one call to the next, where each
+/// thread steps a group of the consoles. The threads are spawned by the
+/// first call needing them, and exit once the runner is dropped. The
+/// `_scoped` methods spawn threads for the call instead, so their functions
+/// may borrow local state.
+#[derive(Debug)]
+pub struct ParallelRunner {
+    /// The consoles.
+    consoles: Vec<Nes>,
+    /// The number of threads the consoles are spread across.
+    threads: NonZeroUsize,
+    /// The queues of the jobs of the worker threads spawned so far.
+    workers: Vec<Sender<Job>>,
+}
+
+impl ParallelRunner {
+    /// Create a runner of the given consoles, with a thread per core of the
+    /// machine, see [`thread::available_parallelism`].
+    #[must_use]
+    pub fn new(consoles: Vec<Nes>) -> Self {
+        Self {
+            consoles,
+            threads: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            workers: Vec::new(),
+        }
+    }
+
+    /// Create a runner of the given number of clones of a console.
+    #[must_use]
+    pub fn replicate(nes: &Nes, count: usize) -> Self {
+        Self::new(vec![nes.clone(); count])
+    }
+
+    /// Return the number of threads the consoles are spread across.
+    #[must_use]
+    pub const fn threads(&self) -> NonZeroUsize {
+        self.threads
+    }
+
+    /// Set the number of threads the consoles are spread across, where a
+    /// single thread steps them on the calling thread. The worker threads
+    /// beyond the number exit.
+    pub fn set_threads(&mut self, threads: NonZeroUsize) {
+        self.threads = threads;
+        self.workers.truncate(threads.get());
+    }
+
+    /// Return the number of consoles.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.consoles.len()
+    }
+
+    /// Check if there are no consoles.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.consoles.is_empty()
+    }
+
+    /// Return the consoles.
+    #[must_use]
+    pub fn consoles(&self) -> &[Nes] {
+        &self.consoles
+    }
+
+    /// Return the consoles mutably, e.g. to restore their save states.
+    pub fn consoles_mut(&mut self) -> &mut [Nes] {
+        &mut self.consoles
+    }
+
+    /// Return the consoles, dropping the runner.
+    #[must_use]
+    pub fn into_consoles(self) -> Vec<Nes> {
+        self.consoles
+    }
+
+    /// Call the given function with the index of every console and the
+    /// console, across the threads, returning the results in the order of the
+    /// consoles.
+    ///
+    /// The function runs on the persistent worker threads of the runner, which
+    /// outlive the call, so both the function and its results must be
+    /// `'static`. A function borrowing local state, e.g. a corpus or a policy,
+    /// either shares it as an [`Arc`], or runs on threads spawned for the
+    /// call, see [`ParallelRunner::map_scoped`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function panics on any thread, with the same payload,
+    /// once every thread finished its consoles.
+    pub fn map<T, F>(&mut self, f: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(usize, &mut Nes) -> T + Send + Sync + 'static,
+    {
+        let threads = self.threads.get().min(self.consoles.len());
+        if threads <= 1 {
+            let consoles = self.consoles.iter_mut().enumerate();
+            return consoles.map(|(index, nes)| f(index, nes)).collect();
+        }
+
+        while self.workers.len() < threads {
+            let (sender, jobs) = mpsc::channel::<Job>();
+            thread::spawn(move || jobs.into_iter().for_each(|job| job()));
+            self.workers.push(sender);
+        }
+
+        let len = self.consoles.len();
+        let size = len.div_ceil(threads);
+        let groups = len.div_ceil(size);
+        let f = Arc::new(f);
+        let (sender, results) = mpsc::channel::<Group<T>>();
+        for group in (0..groups).rev() {
+            let mut consoles = self.consoles.split_off(group * size);
+            let (f, sender) = (Arc::clone(&f), sender.clone());
+            let job = move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let consoles = consoles.iter_mut().enumerate();
+                    consoles
+                        .map(|(index, nes)| f(group * size + index, nes))
+                        .collect()
+                }));
+                sender
+                    .send((group, consoles, result))
+                    .expect("the runner waits for the results of every group");
+            };
+            self.workers[group]
+                .send(Box::new(job))
+                .expect("the worker threads only exit with the runner");
+        }
+
+        let mut groups: Vec<_> = results.iter().take(groups).collect();
+        groups.sort_unstable_by_key(|&(group, ..)| group);
+
+        let mut outputs = Vec::with_capacity(len);
+        let mut panicked = None;
+        for (_, mut consoles, result) in groups {
+            self.consoles.append(&mut consoles);
+            match result {
+                Ok(results) => outputs.extend(results),
+                Err(payload) => panicked = panicked.or(Some(payload)),
+            }
+        }
+
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+        outputs
+    }
+
+    /// Call the given function with the index of every console and the
+    /// console like [`ParallelRunner::map`], but on threads spawned for the
+    /// call, so the function may borrow local state.
+    ///
+    /// Spawning the threads takes some time on every call, which matters for
+    /// functions running little more than a frame.
+    ///
+    /// ```
+    /// # use chuck_nes::mapper::{Mirroring, Nrom};
+    /// # use chuck_nes::parallel::ParallelRunner;
+    /// # use chuck_nes::Nes;
+    /// # let nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+    /// let mut runner = ParallelRunner::replicate(&nes, 8);
+    ///
+    /// // Every console starts from its own RAM.
+    /// let corpus: Vec<u8> = (0..8).collect();
+    /// runner.map_scoped(|index, nes| nes.poke(0x0000, corpus[index]));
+    /// assert_eq!(runner.map_scoped(|_, nes| nes.peek(0x0000)), corpus);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function panics on any thread, with the same payload.
+    pub fn map_scoped<T: Send>(&mut self, f: impl Fn(usize, &mut Nes) -> T + Sync) -> Vec<T> {
+        let threads = self.threads.get().min(self.consoles.len());
+        if threads <= 1 {
+            let consoles = self.consoles.iter_mut().enumerate();
+            return consoles.map(|(index, nes)| f(index, nes)).collect();
+        }
+
+        let size = self.consoles.len().div_ceil(threads);
+        let f = &f;
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .consoles
+                .chunks_mut(size)
+                .enumerate()
+                .map(|(chunk, consoles)| {
+                    scope.spawn(move || {
+                        let consoles = consoles.iter_mut().enumerate();
+                        consoles
+                            .map(|(index, nes)| f(chunk * size + index, nes))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))
+                })
+                .collect()
+        })
+    }
+
+    /// Apply an input to every console, run a frame on it (see
+    /// [`Nes::run_frame`]) and then observe it with the given function, e.g.
+    /// its picture or RAM, returning the observations in the order of the
+    /// consoles.
+    ///
+    /// Like [`ParallelRunner::map`], the function must be `'static`, see
+    /// [`ParallelRunner::run_frame_scoped`] for one borrowing local state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of inputs isn't the number of consoles, or if
+    /// the function panics on any thread.
+    pub fn run_frame<T: Send + 'static>(
+        &mut self,
+        inputs: &[Input],
+        observe: impl Fn(usize, &Nes) -> T + Send + Sync + 'static,
+    ) -> Vec<T> {
+        assert_eq!(
+            inputs.len(),
+            self.consoles.len(),
+            "there must be an input for every console"
+        );
+
+        let inputs: Arc<[Input]> = inputs.into();
+        self.map(move |index, nes| {
+            inputs[index].apply(nes);
+            nes.run_frame();
+            observe(index, nes)
+        })
+    }
+
+    /// Apply an input to every console, run a frame on it and then observe
+    /// it like [`ParallelRunner::run_frame`], but on threads spawned for the
+    /// call, see [`ParallelRunner::map_scoped`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of inputs isn't the number of consoles, or if
+    /// the function panics on any thread.
+    pub fn run_frame_scoped<T: Send>(
+        &mut self,
+        inputs: &[Input],
+        observe: impl Fn(usize, &Nes) -> T + Sync,
+    ) -> Vec<T> {
+        assert_eq!(
+            inputs.len(),
+            self.consoles.len(),
+            "there must be an input for every console"
+        );
+
+        self.map_scoped(|index, nes| {
+            inputs[index].apply(nes);
+            nes.run_frame();
+            observe(index, nes)
+        })
+    }
+}
+
+impl Clone for ParallelRunner {
+    /// Clone the consoles into a runner with its own worker threads.
+    fn clone(&self) -> Self {
+        Self {
+            consoles: self.consoles.clone(),
+            threads: self.threads,
+            workers: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! The profiling of the program of the console, which attributes the cycles
+//! of the CPU to the instructions and to the functions executing them, to
+//! find where the cycles of a frame are going.
+//!
+//! The cycles are counted once enabled by [`Nes::profile_cpu`], including
+//! the cycles in which the DMA unit halts the CPU, which are attributed to
+//! the halted instruction, e.g. the 513 cycles of an OAM DMA to the write to
+//! `OAMDMA`.
+//!
+//! A function is entered by `JSR`, `BRK` or an interrupt, at the address the
+//! CPU jumps to, and left once the stack pointer is back at its value before
+//! the entry, usually by `RTS` or `RTI`. So the functions also unwind when a
+//! program drops return addresses from the stack, e.g. with `TXS` to reset
+//! the stack. The handler of the reset is the outermost function, which is
+//! never left.
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::profile::Order;
+//! # use chuck_nes::symbols::Symbols;
+//! # use chuck_nes::Nes;
+//! // A program that calls a subroutine forever.
+//! let mut prg = vec![0; 0x4000];
+//! prg[..8].copy_from_slice(&[0x20, 0x06, 0x80, 0x4c, 0x00, 0x80, 0xea, 0x60]);
+//! prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//!
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.profile_cpu(true);
+//! nes.run_frame();
+//!
+//! let profiler = nes.profiler().unwrap();
+//! let [main, sub] = &profiler.functions(Order::Inclusive)[..] else {
+//!     panic!("two functions expected");
+//! };
+//! assert_eq!((main.entry.addr, sub.entry.addr), (0x8000, 0x8006));
+//! assert!(sub.calls > 1000);
+//!
+//! // `NOP` and `RTS` take 8 cycles, except for the current call.
+//! assert!(sub.calls * 8 - sub.exclusive <= 8);
+//! assert!(main.inclusive > main.exclusive + sub.exclusive - 8);
+//!
+//! // The stacks of the functions, for flame graphs.
+//! let symbols = Symbols::read_mlb(&mut "P:0000:main\nP:0006:sub\n".as_bytes())?;
+//! let mut folded = Vec::new();
+//! profiler.write_folded(&symbols, &mut folded)?;
+//! assert!(String::from_utf8(folded).unwrap().contains("\nmain;sub "));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/brendangregg/FlameGraph#2-fold-stacks>
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use chuck_cpu::{Cpu, Cycle};
+
+use crate::mapper::Mapper;
+use crate::symbols::Symbols;
+#[cfg(doc)]
+use crate::Nes;
+
+/// The opcode of `JSR`.
+const JSR: u8 = 0x20;
+
+/// The opcode of `BRK`, which the CPU also executes for interrupts.
+const BRK: u8 = 0x00;
+
+/// The address of the reset vector.
+const RES_VECTOR: u16 = 0xfffc;
+
+/// The maximum number of nested functions, beyond which the calls are
+/// attributed to the caller.
+const MAX_DEPTH: usize = 256;
+
+/// The stack pointer of the outermost function, which is higher than any
+/// stack pointer, so that it's never left.
+const OUTERMOST: u16 = 0x100;
+
+/// An address of the CPU bus, as mapped when it was executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Site {
+    /// The address.
+    pub addr: u16,
+    /// The offset into the PRG-ROM at the address, if it read the PRG-ROM,
+    /// which tells the banks mapped at the address apart.
+    pub prg_offset: Option<usize>,
+}
+
+impl Site {
+    /// Return the name of the site: its label, if any, or its address.
+    fn name(self, symbols: &Symbols) -> String {
+        symbols
+            .label_at(self.addr, self.prg_offset)
+            .map_or_else(|| format!("${:04X}", self.addr), str::to_owned)
+    }
+}
+
+/// The cycles of a function, see [`Profiler::functions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Function {
+    /// The address the function is entered at.
+    pub entry: Site,
+    /// The number of times the function was entered.
+    pub calls: u64,
+    /// The number of cycles executed by the function, including the
+    /// functions it called.
+    pub inclusive: u64,
+    /// The number of cycles executed by the function itself.
+    pub exclusive: u64,
+}
+
+/// The order of the functions of a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// The most cycles including the called functions first.
+    Inclusive,
+    /// The most cycles of the function itself first.
+    Exclusive,
+    /// The most calls first.
+    Calls,
+}
+
+/// A function on a stack of calls, which is a node of the tree of calls.
+#[derive(Debug, Clone)]
+struct Node {
+    /// The entry of the function.
+    entry: Site,
+    /// The index of the node of the caller.
+    parent: usize,
+    /// The indices of the nodes of the called functions.
+    children: Vec<usize>,
+    /// The number of times the function was entered from the caller.
+    calls: u64,
+    /// The number of cycles executed by the function itself.
+    cycles: u64,
+}
+
+/// A function being executed.
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    /// The index of the node of the function.
+    node: usize,
+    /// The stack pointer before the entry, which the function restores when
+    /// it's left.
+    sp: u16,
+}
+
+/// The profiler of the CPU, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    /// The number of cycles.
+    cycles: u64,
+    /// The number of cycles per instruction.
+    instructions: HashMap<Site, u64>,
+    /// The tree of calls, whose root is the caller of the outermost
+    /// functions.
+    nodes: Vec<Node>,
+    /// The functions being executed.
+    calls: Vec<Call>,
+    /// The current instruction, unless none was fetched yet.
+    site: Option<Site>,
+    /// The number of cycles of the current instruction so far.
+    pending: u64,
+    /// A flag denoting if the last cycle was an opcode fetch.
+    fetching: bool,
+    /// The last interrupt vector read by the CPU.
+    vector: u16,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            cycles: 0,
+            instructions: HashMap::new(),
+            nodes: vec![Node {
+                entry: Site::default(),
+                parent: 0,
+                children: Vec::new(),
+                calls: 0,
+                cycles: 0,
+            }],
+            calls: Vec::new(),
+            site: None,
+            pending: 0,
+            fetching: false,
+            vector: RES_VECTOR,
+        }
+    }
+}
+
+impl Function {
+    /// Return the function of the given entry, among the given functions.
+    fn of(functions: &mut HashMap<Site, Self>, entry: Site) -> &mut Self {
+        functions.entry(entry).or_insert(Self {
+            entry,
+            calls: 0,
+            inclusive: 0,
+            exclusive: 0,
+        })
+    }
+}
+
+impl Profiler {
+    /// Return the number of cycles since the profiling was enabled or
+    /// cleared.
+    ///
+    /// The cycles of the current instruction are only attributed once it
+    /// completes, so they're missing from the other counts.
+    #[must_use]
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Return the executed instructions with their numbers of cycles, the
+    /// most cycles first.
+    #[must_use]
+    pub fn instructions(&self) -> Vec<(Site, u64)> {
+        let mut instructions: Vec<_> = self
+            .instructions
+            .iter()
+            .map(|(&site, &cycles)| (site, cycles))
+            .collect();
+        instructions.sort_by_key(|&(site, cycles)| (u64::MAX - cycles, site.addr));
+        instructions
+    }
+
+    /// Return the entered functions with their numbers of cycles, in the
+    /// given order.
+    #[must_use]
+    pub fn functions(&self, order: Order) -> Vec<Function> {
+        let mut functions: HashMap<Site, Function> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            let function = Function::of(&mut functions, node.entry);
+            function.calls += node.calls;
+            function.exclusive += node.cycles;
+
+            // A recursive function only counts the cycles once.
+            let mut callers = HashSet::new();
+            for caller in self.stack(index) {
+                if callers.insert(caller) {
+                    Function::of(&mut functions, caller).inclusive += node.cycles;
+                }
+            }
+        }
+
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by_key(|function| {
+            let key = match order {
+                Order::Inclusive => function.inclusive,
+                Order::Exclusive => function.exclusive,
+                Order::Calls => function.calls,
+            };
+            (u64::MAX - key, function.entry.addr)
+        });
+        functions
+    }
+
+    /// Write a report of the cycles of the functions in the given order,
+    /// followed by the cycles of the instructions, named by the given
+    /// symbols.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn write_report(
+        &self,
+        symbols: &Symbols,
+        order: Order,
+        writer: &mut dyn Write,
+    ) -> io::Result<()> {
+        let percent = |cycles: u64| {
+            let permille = cycles.saturating_mul(1000) / self.cycles.max(1);
+            format!("{}.{}%", permille / 10, permille % 10)
+        };
+
+        writeln!(writer, "{} cycles", self.cycles)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{:>12} {:>6} {:>12} {:>6} {:>10}  function",
+            "inclusive", "", "exclusive", "", "calls"
+        )?;
+        for function in self.functions(order) {
+            writeln!(
+                writer,
+                "{:>12} {:>6} {:>12} {:>6} {:>10}  {}",
+                function.inclusive,
+                percent(function.inclusive),
+                function.exclusive,
+                percent(function.exclusive),
+                function.calls,
+                function.entry.name(symbols),
+            )?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "{:>12} {:>6}  address  label", "cycles", "")?;
+        for (site, cycles) in self.instructions() {
+            let label = symbols.label_at(site.addr, site.prg_offset);
+            writeln!(
+                writer,
+                "{cycles:>12} {:>6}  ${:04X}    {}",
+                percent(cycles),
+                site.addr,
+                label.unwrap_or_default(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the cycles of the stacks of functions in the folded format of
+    /// flame graphs, a line `outer;inner cycles` per stack, with the
+    /// functions named by the given symbols.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn write_folded(&self, symbols: &Symbols, writer: &mut dyn Write) -> io::Result<()> {
+        // The cycles before the first reset, or of the functions entered
+        // before the profiling was enabled, have no function.
+        if self.nodes[0].cycles > 0 {
+            writeln!(writer, "- {}", self.nodes[0].cycles)?;
+        }
+
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.cycles == 0 {
+                continue;
+            }
+
+            let mut names: Vec<_> = self.stack(index).map(|site| site.name(symbols)).collect();
+            names.reverse();
+            writeln!(writer, "{} {}", names.join(";"), node.cycles)?;
+        }
+
+        Ok(())
+    }
+
+    /// Discard the counted cycles.
+    ///
+    /// The functions being executed are kept, so that the cycles of their
+    /// callees are still attributed to them.
+    pub fn clear(&mut self) {
+        self.cycles = 0;
+        self.instructions.clear();
+        self.pending = 0;
+        for node in &mut self.nodes {
+            node.calls = 0;
+            node.cycles = 0;
+        }
+    }
+
+    /// Observe a cycle of the CPU, once it placed its bus access.
+    pub(crate) fn observe(&mut self, cpu: &Cpu, cartridge: &dyn Mapper) {
+        self.cycles += 1;
+        if cpu.bus.kind == Cycle::Vector && cpu.bus.addr & 1 == 0 {
+            self.vector = cpu.bus.addr;
+        }
+
+        // The fetch is repeated while the DMA unit halts the CPU.
+        let progress = cpu.instruction_progress();
+        let started = progress.fetching && !self.fetching;
+        self.fetching = progress.fetching;
+
+        if started {
+            self.attribute();
+
+            let sp = u16::from(cpu.regs.sp);
+            let addr = cpu.bus.addr;
+            self.site = Some(Site {
+                addr,
+                prg_offset: cartridge.prg_offset(addr),
+            });
+
+            match progress.opcode {
+                JSR => self.enter(sp + 2),
+                BRK if self.vector == RES_VECTOR => {
+                    self.calls.clear();
+                    self.enter(OUTERMOST);
+                }
+                BRK => self.enter(sp + 3),
+                _ => {}
+            }
+            while self.calls.last().is_some_and(|call| sp >= call.sp) {
+                self.calls.pop();
+            }
+        }
+
+        self.pending += 1;
+    }
+
+    /// Attribute the cycles of the current instruction.
+    fn attribute(&mut self) {
+        let node = self.calls.last().map_or(0, |call| call.node);
+        self.nodes[node].cycles += self.pending;
+        if let Some(site) = self.site {
+            *self.instructions.entry(site).or_default() += self.pending;
+        }
+        self.pending = 0;
+    }
+
+    /// Enter the function at the current instruction, which restores the
+    /// given stack pointer when it's left.
+    fn enter(&mut self, sp: u16) {
+        let Some(entry) = self.site.filter(|_| self.calls.len() < MAX_DEPTH) else {
+            return;
+        };
+
+        let parent = self.calls.last().map_or(0, |call| call.node);
+        let node = self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].entry == entry)
+            .unwrap_or_else(|| {
+                self.nodes.push(Node {
+                    entry,
+                    parent,
+                    children: Vec::new(),
+                    calls: 0,
+                    cycles: 0,
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent].children.push(node);
+                node
+            });
+
+        self.nodes[node].calls += 1;
+        self.calls.push(Call { node, sp });
+    }
+
+    /// Return the entries of the functions on the stack of the given node,
+    /// innermost first.
+    fn stack(&self, mut node: usize) -> impl Iterator<Item = Site> + '_ {
+        std::iter::from_fn(move || {
+            (node != 0).then(|| {
+                let entry = self.nodes[node].entry;
+                node = self.nodes[node].parent;
+                entry
+            })
+        })
+    }
+}
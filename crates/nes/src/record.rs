@@ -0,0 +1,246 @@
+//! Recording of the audio output into WAV files, either the mixed output or
+//! the individual channels.
+//!
+//! Every track of a [`Recorder`] is resampled on its own, with the filters of
+//! the NES's audio output, see [`chuck_audio::Resampler`], and written into
+//! its own WAV file. Recording the channels requires capturing them, see
+//! [`Nes::capture_channels`], and recording the stereo mix into a stereo file
+//! requires capturing the stereo samples, see [`Nes::capture_stereo`], which
+//! are panned like the mix, see [`mixer`](crate::mixer).
+//!
+//! ```
+//! # use std::io::Cursor;
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::record::{Recorder, Track};
+//! # use chuck_nes::Nes;
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! nes.capture_channels(true);
+//!
+//! let mut recorder = Recorder::new(nes.region(), 48_000);
+//! recorder.add(Track::Mix, Cursor::new(Vec::new()))?;
+//! recorder.add(Track::Triangle, Cursor::new(Vec::new()))?;
+//!
+//! // About a second of audio.
+//! for _ in 0..60 {
+//!     recorder.record(&nes.run_frame())?;
+//! }
+//!
+//! for (track, file) in recorder.finish()? {
+//!     let samples = (file.into_inner().len() - 58) / 4;
+//!     assert!((47_900..48_000).contains(&samples), "{}", track.name());
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io::{self, Seek, Write};
+
+use chuck_audio::wav::Wav;
+use chuck_audio::Resampler;
+
+#[cfg(doc)]
+use crate::Nes;
+use crate::{Channels, Frame, Region};
+
+/// A recorded track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    /// The mixed output, including the expansion audio.
+    Mix,
+    /// The mixed output in stereo, see [`Frame::stereo`].
+    Stereo,
+    /// The first pulse channel.
+    Pulse1,
+    /// The second pulse channel.
+    Pulse2,
+    /// The triangle channel.
+    Triangle,
+    /// The noise channel.
+    Noise,
+    /// The delta modulation channel.
+    Dmc,
+    /// The expansion audio of the cartridge.
+    Expansion,
+}
+
+impl Track {
+    /// All tracks.
+    pub const ALL: [Self; 8] = [
+        Self::Mix,
+        Self::Stereo,
+        Self::Pulse1,
+        Self::Pulse2,
+        Self::Triangle,
+        Self::Noise,
+        Self::Dmc,
+        Self::Expansion,
+    ];
+
+    /// Return the name of the track, e.g. for the name of its file.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Mix => "mix",
+            Self::Stereo => "stereo",
+            Self::Pulse1 => "pulse1",
+            Self::Pulse2 => "pulse2",
+            Self::Triangle => "triangle",
+            Self::Noise => "noise",
+            Self::Dmc => "dmc",
+            Self::Expansion => "expansion",
+        }
+    }
+
+    /// Return the sample of the track's channel, or silence for the mixes,
+    /// which aren't channels.
+    fn channel(self, channels: &Channels) -> f32 {
+        match self {
+            Self::Mix | Self::Stereo => 0.0,
+            Self::Pulse1 => channels.apu[0],
+            Self::Pulse2 => channels.apu[1],
+            Self::Triangle => channels.apu[2],
+            Self::Noise => channels.apu[3],
+            Self::Dmc => channels.apu[4],
+            Self::Expansion => channels.expansion,
+        }
+    }
+}
+
+/// A track being recorded.
+#[derive(Debug)]
+struct Recording<W: Write + Seek> {
+    /// The track.
+    track: Track,
+    /// The resampler of the track, or of its left side in stereo.
+    resampler: Resampler,
+    /// The resampler of the right side of the track, in stereo.
+    right: Option<Resampler>,
+    /// The WAV file.
+    wav: Wav<W>,
+}
+
+/// A recorder of the audio output into WAV files.
+#[derive(Debug)]
+pub struct Recorder<W: Write + Seek> {
+    /// The region of the recorded console, i.e. its sample rate.
+    region: Region,
+    /// The sample rate of the files, in Hz.
+    rate: u32,
+    /// The tracks being recorded.
+    recordings: Vec<Recording<W>>,
+    /// The samples of a channel, or the resampled samples of a track.
+    buffer: Vec<f32>,
+    /// The resampled samples of the right side of a track, in stereo.
+    right: Vec<f32>,
+}
+
+impl<W: Write + Seek> Recorder<W> {
+    /// Create a recorder of a console of the given region, without any
+    /// tracks, writing WAV files with the given sample rate, in Hz.
+    #[must_use]
+    pub fn new(region: Region, rate: u32) -> Self {
+        Self {
+            region,
+            rate,
+            recordings: Vec::new(),
+            buffer: Vec::new(),
+            right: Vec::new(),
+        }
+    }
+
+    /// Start recording a track into the given file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header of the file can't be written.
+    pub fn add(&mut self, track: Track, writer: W) -> io::Result<()> {
+        let mut resampler = Resampler::new(self.region.cpu_clock(), self.rate);
+        resampler.set_rate_control(false);
+
+        let stereo = track == Track::Stereo;
+        self.recordings.push(Recording {
+            track,
+            right: stereo.then(|| resampler.clone()),
+            resampler,
+            wav: Wav::with_channels(writer, self.rate, if stereo { 2 } else { 1 })?,
+        });
+
+        Ok(())
+    }
+
+    /// Record the audio samples of a frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file can't be written, or if a channel or the
+    /// stereo mix is recorded but weren't captured by the frame.
+    pub fn record(&mut self, frame: &Frame<'_>) -> io::Result<()> {
+        for recording in &mut self.recordings {
+            let captured = match recording.track {
+                Track::Mix => true,
+                Track::Stereo => frame.stereo.len() == frame.samples.len(),
+                _ => frame.channels.len() == frame.samples.len(),
+            };
+            if !captured {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the channels weren't captured",
+                ));
+            }
+
+            match (recording.track, &mut recording.right) {
+                (Track::Mix, _) => recording.resampler.push(frame.samples),
+                (Track::Stereo, Some(right)) => {
+                    for (side, resampler) in
+                        [&mut recording.resampler, right].into_iter().enumerate()
+                    {
+                        self.buffer.clear();
+                        self.buffer
+                            .extend(frame.stereo.iter().map(|pair| pair[side]));
+                        resampler.push(&self.buffer);
+                    }
+                }
+                (track, _) => {
+                    self.buffer.clear();
+                    self.buffer.extend(
+                        frame
+                            .channels
+                            .iter()
+                            .map(|channels| track.channel(channels)),
+                    );
+                    recording.resampler.push(&self.buffer);
+                }
+            }
+
+            self.buffer.resize(recording.resampler.len(), 0.0);
+            recording.resampler.fill(&mut self.buffer);
+            if let Some(right) = &mut recording.right {
+                // Both sides are resampled alike, so they're equally long.
+                self.right.resize(self.buffer.len(), 0.0);
+                right.fill(&mut self.right);
+                let pairs = self.buffer.iter().zip(&self.right);
+                let interleaved: Vec<f32> =
+                    pairs.flat_map(|(&left, &right)| [left, right]).collect();
+                recording.wav.write(&interleaved)?;
+            } else {
+                recording.wav.write(&self.buffer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Complete the files, returning them together with their tracks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file can't be written.
+    pub fn finish(self) -> io::Result<Vec<(Track, W)>> {
+        self.recordings
+            .into_iter()
+            .map(|recording| Ok((recording.track, recording.wav.finish()?)))
+            .collect()
+    }
+}
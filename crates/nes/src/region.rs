@@ -0,0 +1,119 @@
+//! The regions of the NES, whose consoles are clocked differently.
+
+use chuck_apu::Region as ApuRegion;
+use chuck_ppu::Region as PpuRegion;
+
+/// The region of a console, which determines the clocks and the timing of
+/// its chips.
+///
+/// | Region | Master clock | CPU clock         | PPU clock       | Scanlines | Frame rate |
+/// |--------|--------------|-------------------|-----------------|-----------|------------|
+/// | NTSC   | 21.477 MHz   | ÷ 12 = 1.790 MHz  | ÷ 4 = 5.369 MHz | 262       | 60.099 Hz  |
+/// | PAL    | 26.602 MHz   | ÷ 16 = 1.663 MHz  | ÷ 5 = 5.320 MHz | 312       | 50.007 Hz  |
+/// | Dendy  | 26.602 MHz   | ÷ 15 = 1.773 MHz  | ÷ 5 = 5.320 MHz | 312       | 50.007 Hz  |
+///
+/// So the PPU executes 3 dots per CPU cycle on NTSC and on the Dendy, but
+/// 3.2 dots on PAL. The PAL console also has a 2A07 instead of a 2A03, whose
+/// APU has other timings, see [`chuck_apu::Region`], while the Dendy's clone
+/// of the 2A03 behaves like the NTSC one.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/wiki/Cycle_reference_chart>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The NTSC NES and the Famicom.
+    Ntsc,
+    /// The PAL NES.
+    Pal,
+    /// The Dendy, a Famicom clone with PAL video.
+    Dendy,
+}
+
+impl Region {
+    /// Return the frequency of the master clock, in Hz.
+    #[must_use]
+    pub const fn master_clock(self) -> f64 {
+        match self {
+            Self::Ntsc => 236.25e6 / 11.0,
+            Self::Pal | Self::Dendy => 26.601_712_5e6,
+        }
+    }
+
+    /// Return the frequency of the CPU clock, in Hz, i.e. the rate of the
+    /// audio samples of the console.
+    #[must_use]
+    pub fn cpu_clock(self) -> f64 {
+        self.master_clock() / f64::from(self.cpu_divider())
+    }
+
+    /// Return the average number of frames per second.
+    ///
+    /// On NTSC, this takes into account that every other frame is one dot
+    /// shorter while rendering is enabled.
+    #[must_use]
+    pub fn frame_rate(self) -> f64 {
+        let dots = f64::from(self.ppu().scanlines()) * 341.0;
+        let dots = match self {
+            Self::Ntsc => dots - 0.5,
+            Self::Pal | Self::Dendy => dots,
+        };
+
+        self.master_clock() / f64::from(self.ppu_divider()) / dots
+    }
+
+    /// Return the frequency of the master clock, in Hz, as a fraction, i.e.
+    /// the numerator and the denominator, for exact timings.
+    pub(crate) const fn master_clock_fraction(self) -> (u64, u64) {
+        match self {
+            Self::Ntsc => (236_250_000, 11),
+            Self::Pal | Self::Dendy => (53_203_425, 2),
+        }
+    }
+
+    /// Return the number of master clock cycles per CPU cycle.
+    pub(crate) const fn cpu_divider(self) -> u8 {
+        match self {
+            Self::Ntsc => 12,
+            Self::Pal => 16,
+            Self::Dendy => 15,
+        }
+    }
+
+    /// Return the number of master clock cycles per PPU dot.
+    pub(crate) const fn ppu_divider(self) -> u8 {
+        match self {
+            Self::Ntsc => 4,
+            Self::Pal | Self::Dendy => 5,
+        }
+    }
+
+    /// Return the region of the PPU.
+    pub(crate) const fn ppu(self) -> PpuRegion {
+        match self {
+            Self::Ntsc => PpuRegion::Ntsc,
+            Self::Pal => PpuRegion::Pal,
+            Self::Dendy => PpuRegion::Dendy,
+        }
+    }
+
+    /// Return the region of the APU.
+    pub(crate) const fn apu(self) -> ApuRegion {
+        match self {
+            Self::Ntsc | Self::Dendy => ApuRegion::Ntsc,
+            Self::Pal => ApuRegion::Pal,
+        }
+    }
+}
+
+impl From<chuck_rom::Region> for Region {
+    /// Return the region to run a ROM made for the given region in, which is
+    /// NTSC for the ROMs made for all regions.
+    fn from(region: chuck_rom::Region) -> Self {
+        match region {
+            chuck_rom::Region::Ntsc | chuck_rom::Region::Multi => Self::Ntsc,
+            chuck_rom::Region::Pal => Self::Pal,
+            chuck_rom::Region::Dendy => Self::Dendy,
+        }
+    }
+}
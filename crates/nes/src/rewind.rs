@@ -0,0 +1,313 @@
+//! Rewinding, i.e. stepping back in time through snapshots of the console.
+//!
+//! A [`Rewind`] captures a save state of the console every few frames and
+//! keeps as many of them as fit into its capacity, dropping the oldest ones
+//! first. Only the newest snapshot is kept as a whole: every older snapshot
+//! is stored as its difference (delta) to the next newer one, i.e. the runs
+//! of bytes that changed in between. From one frame to the next, most of the
+//! RAM and frame buffer stays the same, so a delta is usually a small
+//! fraction of a save state.
+//!
+//! ```
+//! # use chuck_nes::{mapper::{Mirroring, Nrom}, rewind::Rewind, Nes};
+//! # let mut prg = vec![0; 0x4000];
+//! # prg[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+//! # prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+//! let mut nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+//! // Capture every other frame, keeping up to 16 MiB of snapshots.
+//! let mut rewind = Rewind::new(2, 16 << 20);
+//!
+//! for _ in 0..10 {
+//!     nes.run_frame();
+//!     rewind.capture(&nes);
+//! }
+//!
+//! assert_eq!(rewind.len(), 5);
+//!
+//! // While the rewind button is held, step back instead of running frames.
+//! let frame = nes.ppu().frame();
+//! while rewind.hold(&mut nes) {}
+//! assert_eq!(nes.ppu().frame(), frame - 8);
+//! ```
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::Nes;
+
+/// The snapshots of a console, to step back in time.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    /// The number of frames between two snapshots.
+    interval: u32,
+    /// The maximum number of bytes taken by the snapshots.
+    capacity: usize,
+    /// The number of frames since the last snapshot.
+    frames: u32,
+    /// The console of the first snapshot, into which the snapshots are
+    /// loaded by [`Rewind::pop`].
+    console: Option<Nes>,
+    /// The save state of the newest snapshot, or nothing if there's none.
+    newest: Vec<u8>,
+    /// The older snapshots from oldest to newest, each as its delta to the
+    /// next newer snapshot.
+    deltas: VecDeque<Box<[u8]>>,
+    /// The number of bytes taken by the deltas.
+    size: usize,
+}
+
+impl Rewind {
+    /// Create an empty rewind buffer, capturing a snapshot every `interval`
+    /// frames and keeping up to `capacity` bytes of snapshots.
+    ///
+    /// The newest snapshot is always kept, even if it alone exceeds the
+    /// capacity.
+    #[must_use]
+    pub fn new(interval: u32, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity,
+            frames: 0,
+            console: None,
+            newest: Vec::new(),
+            deltas: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    /// Return the number of frames between two snapshots.
+    #[must_use]
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Return the maximum number of bytes taken by the snapshots.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return the number of bytes currently taken by the snapshots.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size + self.newest.len()
+    }
+
+    /// Return the number of snapshots.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(!self.newest.is_empty())
+    }
+
+    /// Check if there are no snapshots to step back to.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_empty()
+    }
+
+    /// Drop all snapshots, e.g. when another cartridge is inserted or other
+    /// input devices are plugged in, which the snapshots don't match.
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.console = None;
+        self.newest.clear();
+        self.deltas.clear();
+        self.size = 0;
+    }
+
+    /// Note the end of a frame of the given console, capturing a snapshot
+    /// of it if the interval since the last one passed.
+    ///
+    /// Returns `true` if a snapshot was captured.
+    pub fn capture(&mut self, nes: &Nes) -> bool {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return false;
+        }
+
+        self.frames = 0;
+        let state = nes.state();
+
+        if !self.newest.is_empty() {
+            let delta = encode(&self.newest, &state);
+            self.size += delta.len();
+            self.deltas.push_back(delta);
+        }
+
+        self.newest = state;
+        self.console.get_or_insert_with(|| nes.clone());
+
+        while self.size() > self.capacity {
+            let Some(delta) = self.deltas.pop_front() else {
+                break;
+            };
+
+            self.size -= delta.len();
+        }
+
+        true
+    }
+
+    /// Remove the newest snapshot, and return a console restored from it.
+    ///
+    /// Returns `None` if there are no snapshots.
+    pub fn pop(&mut self) -> Option<Nes> {
+        let mut nes = self.console.clone()?;
+        self.restore(&mut nes).then_some(nes)
+    }
+
+    /// Step back in time while rewinding is held, by restoring the newest
+    /// snapshot into the given console and removing it.
+    ///
+    /// This is called instead of [`Nes::run_frame`] for every frame shown
+    /// while the player holds the rewind button, so the game runs backwards
+    /// at [`Rewind::interval`] times its speed. The restored console shows
+    /// the picture of the frame the snapshot was captured at, but plays no
+    /// audio.
+    ///
+    /// Returns `false` if there are no snapshots left, which leaves the
+    /// console untouched.
+    pub fn hold(&mut self, nes: &mut Nes) -> bool {
+        self.restore(nes)
+    }
+
+    /// Restore the newest snapshot into the given console and remove it.
+    fn restore(&mut self, nes: &mut Nes) -> bool {
+        if self.newest.is_empty() || nes.load_state(&mut self.newest.as_slice()).is_err() {
+            return false;
+        }
+
+        self.frames = 0;
+        match self.deltas.pop_back() {
+            Some(delta) => {
+                self.size -= delta.len();
+                self.newest = decode(&self.newest, &delta);
+            }
+            None => self.newest.clear(),
+        }
+
+        true
+    }
+}
+
+/// The number of unchanged bytes that end a run of changed bytes. Shorter
+/// runs of unchanged bytes, like the high bytes of the changed pixels of the
+/// frame buffer, are cheaper to store as part of the changed bytes.
+const GAP: usize = 2;
+
+/// Append a LEB128 encoded value.
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value.to_le_bytes()[0] | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value.to_le_bytes()[0]);
+}
+
+/// Read a LEB128 encoded value.
+fn read_varint(input: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    while let Some((&byte, rest)) = input.split_first() {
+        *input = rest;
+        value |= usize::from(byte & 0x7f) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    value
+}
+
+/// Encode the `old` snapshot as its delta to the `new` snapshot: the length
+/// of `old`, followed by pairs of runs of unchanged bytes and runs of changed
+/// bytes, which are stored XOR-ed with the bytes of `new`. A run of changed
+/// bytes may include unchanged bytes, see [`GAP`].
+fn encode(old: &[u8], new: &[u8]) -> Box<[u8]> {
+    // Compare with `new` cut or padded with zeros to the length of `old`.
+    let new = new.get(..old.len()).map_or_else(
+        || {
+            let mut padded = new.to_vec();
+            padded.resize(old.len(), 0);
+            Cow::Owned(padded)
+        },
+        Cow::Borrowed,
+    );
+
+    let mut delta = Vec::new();
+    write_varint(&mut delta, old.len());
+
+    let mut i = 0;
+    while i < old.len() {
+        let same = common_prefix(&old[i..], &new[i..]);
+        write_varint(&mut delta, same);
+        i += same;
+
+        let start = i;
+        let mut end = i;
+        while i < old.len() && i - end < GAP {
+            if old[i] != new[i] {
+                end = i + 1;
+            }
+            i += 1;
+        }
+
+        i = end;
+        write_varint(&mut delta, end - start);
+        delta.extend(
+            old[start..end]
+                .iter()
+                .zip(&new[start..end])
+                .map(|(old, new)| old ^ new),
+        );
+    }
+
+    delta.into_boxed_slice()
+}
+
+/// Return the number of equal bytes at the start of both slices.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    // Comparing whole chunks is a lot faster than comparing single bytes.
+    let chunks = a
+        .chunks_exact(16)
+        .zip(b.chunks_exact(16))
+        .take_while(|(a, b)| a == b)
+        .count()
+        * 16;
+
+    chunks
+        + a[chunks..]
+            .iter()
+            .zip(&b[chunks..])
+            .take_while(|(a, b)| a == b)
+            .count()
+}
+
+/// Decode the snapshot encoded by [`encode`] from its delta to the `new`
+/// snapshot.
+fn decode(new: &[u8], mut delta: &[u8]) -> Vec<u8> {
+    let byte = |i: usize| new.get(i).copied().unwrap_or(0);
+    let len = read_varint(&mut delta);
+    let mut old = Vec::with_capacity(len);
+
+    while old.len() < len && !delta.is_empty() {
+        let start = old.len();
+        let end = start + read_varint(&mut delta);
+        match new.get(start..end) {
+            Some(same) => old.extend_from_slice(same),
+            None => old.extend((start..end).map(byte)),
+        }
+
+        let start = old.len();
+        let changed = read_varint(&mut delta).min(delta.len());
+        let (xor, rest) = delta.split_at(changed);
+        old.extend(xor.iter().zip(start..).map(|(xor, i)| xor ^ byte(i)));
+        delta = rest;
+    }
+
+    old
+}
@@ -0,0 +1,252 @@
+//! The save states of the slots of a frontend, which carry the metadata to
+//! pick a slot: a thumbnail of the picture, the time of the save, the game
+//! and the time played.
+//!
+//! A slot file starts with its metadata, so a frontend reads the metadata of
+//! all slots (see [`Metadata::read`]) without decompressing and decoding the
+//! save states behind them:
+//!
+//! | Part      | Description                                                |
+//! |-----------|------------------------------------------------------------|
+//! | Header    | The magic bytes, `SLT\x1a`, and the format version.        |
+//! | Game      | The hash of the ROM, see [`game_hash`].                    |
+//! | Saved     | The time of the save, in seconds since the UNIX epoch.     |
+//! | Play time | The time played, in milliseconds.                          |
+//! | Thumbnail | The length of the compressed thumbnail, and the thumbnail, |
+//! |           | compressed like a save state.                              |
+//! | State     | The compressed save state, see                             |
+//! |           | [`Nes::save_state_compressed`].                            |
+//!
+//! The numbers are little-endian, 8 bytes each and 4 for the length. A slot
+//! only loads into a console running the game it was saved from:
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::slot::{Metadata, THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH};
+//! # use chuck_nes::Nes;
+//! # use std::io;
+//! # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+//! nes.run_frame();
+//!
+//! let mut slot = Vec::new();
+//! nes.save_slot(&Metadata::new(&nes, 42), &mut slot).unwrap();
+//!
+//! let metadata = Metadata::read(&mut slot.as_slice()).unwrap();
+//! assert_eq!(metadata.game, 42);
+//! assert_eq!(metadata.thumbnail.len(), THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT);
+//!
+//! let err = nes.load_slot(7, &mut slot.as_slice()).unwrap_err();
+//! assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+//! assert_eq!(nes.load_slot(42, &mut slot.as_slice()).unwrap(), metadata);
+//! ```
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chuck_rom::Rom;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::netplay::checksum;
+use crate::{Nes, HEIGHT, WIDTH};
+
+/// The magic bytes that start a slot file.
+const MAGIC: [u8; 4] = *b"SLT\x1a";
+
+/// The current version of the slot format.
+const VERSION: u8 = 1;
+
+/// The width of a thumbnail, half the width of the picture.
+pub const THUMBNAIL_WIDTH: usize = WIDTH / 2;
+
+/// The height of a thumbnail, half the height of the picture.
+pub const THUMBNAIL_HEIGHT: usize = HEIGHT / 2;
+
+/// The extension of the slot files, followed by the number of the slot.
+pub const EXTENSION: &str = "st";
+
+/// Return the hash of a ROM which identifies its game, i.e. the 64-bit
+/// FNV-1a hash of its PRG-ROM and its CHR-ROM, which doesn't depend on the
+/// header.
+#[must_use]
+pub fn game_hash(rom: &Rom) -> u64 {
+    checksum(rom.prg().iter().chain(rom.chr()))
+}
+
+/// The metadata of a slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// The hash of the ROM of the game, see [`game_hash`].
+    pub game: u64,
+    /// The time of the save, to the second.
+    pub saved: SystemTime,
+    /// The time played, to the millisecond.
+    pub play_time: Duration,
+    /// The picture at the time of the save, at half its width and height
+    /// ([`THUMBNAIL_WIDTH`] x [`THUMBNAIL_HEIGHT`]), with the colors of
+    /// [`Ppu::frame_buffer`](chuck_ppu::Ppu::frame_buffer).
+    pub thumbnail: Vec<u16>,
+}
+
+impl Metadata {
+    /// Return the metadata of a save of the given console now, whose time
+    /// played is the time of its frames since it was powered on.
+    #[must_use]
+    pub fn new(nes: &Nes, game: u64) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let frame = Duration::from_secs_f64(nes.region().frame_rate().recip());
+        let frames = u32::try_from(nes.ppu().frame()).unwrap_or(u32::MAX);
+
+        Self {
+            game,
+            saved: UNIX_EPOCH + Duration::from_secs(seconds),
+            play_time: frame.saturating_mul(frames),
+            thumbnail: thumbnail(nes.ppu().frame_buffer()),
+        }
+    }
+
+    /// Read the metadata at the start of a slot file, leaving the reader at
+    /// the save state behind it.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the file isn't a slot file of this
+    /// version.
+    pub fn read(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(invalid("invalid slot magic"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid("unsupported slot version"));
+        }
+
+        let game = read_u64(reader)?;
+        let saved = read_u64(reader)?;
+        let play_time = read_u64(reader)?;
+        let mut length = [0; 4];
+        reader.read_exact(&mut length)?;
+
+        let length = u64::from(u32::from_le_bytes(length));
+        let mut decoder = DeflateDecoder::new((&mut *reader).take(length));
+        let mut thumbnail = vec![0; THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 2];
+        decoder
+            .read_exact(&mut thumbnail)
+            .map_err(|err| match err.kind() {
+                // The compressed thumbnail is corrupt, or too short for the
+                // thumbnail, as opposed to the reader ending before it.
+                io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+                    invalid("invalid slot thumbnail")
+                }
+                io::ErrorKind::UnexpectedEof if decoder.get_ref().limit() == 0 => {
+                    invalid("invalid slot thumbnail")
+                }
+                _ => err,
+            })?;
+
+        // The rest of the compressed thumbnail has to be skipped, if any.
+        io::copy(decoder.get_mut(), &mut io::sink())?;
+
+        Ok(Self {
+            game,
+            saved: UNIX_EPOCH + Duration::from_secs(saved),
+            play_time: Duration::from_millis(play_time),
+            thumbnail: thumbnail
+                .chunks_exact(2)
+                .map(|color| u16::from_le_bytes([color[0], color[1]]))
+                .collect(),
+        })
+    }
+
+    /// Write the metadata at the start of a slot file.
+    fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let seconds = self
+            .saved
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let millis = u64::try_from(self.play_time.as_millis()).unwrap_or(u64::MAX);
+
+        let mut thumbnail = DeflateEncoder::new(Vec::new(), Compression::fast());
+        for color in &self.thumbnail {
+            thumbnail.write_all(&color.to_le_bytes())?;
+        }
+        let thumbnail = thumbnail.finish()?;
+        let length = u32::try_from(thumbnail.len()).map_err(|_| invalid("thumbnail too large"))?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for number in [self.game, seconds, millis] {
+            writer.write_all(&number.to_le_bytes())?;
+        }
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&thumbnail)
+    }
+}
+
+impl Nes {
+    /// Save the state of the console into a slot file with the given
+    /// metadata, see the [module documentation](crate::slot).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the thumbnail doesn't have the size
+    /// of [`THUMBNAIL_WIDTH`] x [`THUMBNAIL_HEIGHT`].
+    pub fn save_slot(&self, metadata: &Metadata, writer: &mut dyn Write) -> io::Result<()> {
+        if metadata.thumbnail.len() != THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT {
+            return Err(invalid("invalid thumbnail size"));
+        }
+
+        metadata.write(writer)?;
+        self.save_state_compressed(writer)
+    }
+
+    /// Load the state of the console from a slot file saved by
+    /// [`Nes::save_slot`] for the given game (see [`game_hash`]), returning
+    /// the metadata of the slot.
+    ///
+    /// The console is left untouched if the slot is malformed or of another
+    /// game.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the slot was saved for another game,
+    /// or its metadata or state is malformed, see [`Nes::load_state`].
+    pub fn load_slot(&mut self, game: u64, reader: &mut dyn Read) -> io::Result<Metadata> {
+        let metadata = Metadata::read(reader)?;
+        if metadata.game != game {
+            return Err(invalid("save state of another game"));
+        }
+
+        self.load_state(reader)?;
+        Ok(metadata)
+    }
+}
+
+/// Return the thumbnail of a picture, which keeps the top left pixel of every
+/// 2x2 square, as the colors are indices into the palette.
+fn thumbnail(pixels: &[u16]) -> Vec<u16> {
+    pixels
+        .chunks_exact(WIDTH)
+        .step_by(2)
+        .flat_map(|row| row.iter().step_by(2).copied())
+        .collect()
+}
+
+/// Read a little-endian 64-bit number.
+fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Create an error denoting a malformed slot file.
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
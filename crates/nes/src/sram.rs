@@ -0,0 +1,83 @@
+//! The persistence of the battery-backed PRG-RAM (save RAM) of a cartridge.
+//!
+//! The save RAM is stored in `.sav` files, which contain nothing but the raw
+//! contents of the RAM (usually 8 KiB), as in other emulators. Since games
+//! write their saves over several frames, the save RAM is best written to
+//! its file once the game stopped writing it for a while, see
+//! [`FlushPolicy`].
+
+use std::path::{Path, PathBuf};
+
+/// The extension of the files holding the save RAM.
+pub const EXTENSION: &str = "sav";
+
+/// Return the path of the save RAM file of the ROM at the given path, i.e.
+/// the same path with the `.sav` extension.
+#[must_use]
+pub fn path(rom: &Path) -> PathBuf {
+    rom.with_extension(EXTENSION)
+}
+
+/// When the save RAM should be written to its file, see
+/// [`Nes::sram_needs_flush`](crate::Nes::sram_needs_flush).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Only when the frontend decides to, e.g. when the game is closed.
+    Manual,
+    /// Once the save RAM was written but then left alone for the given number
+    /// of frames.
+    Idle(u32),
+    /// Every given number of frames while the save RAM is dirty.
+    Periodic(u32),
+}
+
+impl Default for FlushPolicy {
+    /// Flush a second after the last write.
+    fn default() -> Self {
+        Self::Idle(60)
+    }
+}
+
+/// The state of the save RAM since it was last saved.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Tracker {
+    /// The policy for flushing the save RAM.
+    pub(crate) policy: FlushPolicy,
+    /// A flag denoting if the save RAM was written since it was last saved
+    /// or loaded.
+    pub(crate) dirty: bool,
+    /// The number of frames since the save RAM was last written.
+    idle: u32,
+    /// The number of frames since the save RAM was last saved or loaded.
+    unsaved: u32,
+}
+
+impl Tracker {
+    /// Note a write to the save RAM.
+    pub(crate) fn write(&mut self) {
+        self.dirty = true;
+        self.idle = 0;
+    }
+
+    /// Note the end of a frame.
+    pub(crate) fn end_frame(&mut self) {
+        self.idle = self.idle.saturating_add(1);
+        self.unsaved = self.unsaved.saturating_add(1);
+    }
+
+    /// Note that the save RAM was saved or loaded.
+    pub(crate) fn clean(&mut self) {
+        self.dirty = false;
+        self.unsaved = 0;
+    }
+
+    /// Check if the save RAM should be flushed according to the policy.
+    pub(crate) fn needs_flush(&self) -> bool {
+        self.dirty
+            && match self.policy {
+                FlushPolicy::Manual => false,
+                FlushPolicy::Idle(frames) => self.idle >= frames,
+                FlushPolicy::Periodic(frames) => self.unsaved >= frames,
+            }
+    }
+}
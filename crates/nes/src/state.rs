@@ -0,0 +1,335 @@
+//! The save states of the whole console.
+//!
+//! A save state consists of a header followed by the states of all parts of
+//! the console, in this order:
+//!
+//! | Part      | Description                                            |
+//! |-----------|--------------------------------------------------------|
+//! | Header    | The magic bytes, `STA\x1a`, and the format version.    |
+//! | Clock     | The region, the phase of the PPU's clock and the       |
+//! |           | remaining extra cycles of the overclocking.            |
+//! | Power-up  | The configuration of the power-up state, see           |
+//! |           | [`determinism`](crate::determinism).                   |
+//! | CPU       | A snapshot of the CPU, see `Cpu::save`.                |
+//! | PPU       | The PPU, including its frame buffer.                   |
+//! | APU       | The APU.                                               |
+//! | DMA       | The DMA unit.                                          |
+//! | RAM       | The 2 KiB of RAM and the 2 KiB of CIRAM.               |
+//! | Open bus  | The last value on the external data bus.               |
+//! | Input     | The controller ports and their devices.                |
+//! | Cartridge | The `A12` line of the PPU bus as watched for the board |
+//! |           | (see [`PpuBus`](crate::mapper::PpuBus)), and the       |
+//! |           | registers and RAM of the board.                        |
+//! | EPSM      | Whether the EPSM is plugged in, and its registers and  |
+//! |           | sound generators, see [`epsm`](crate::epsm).           |
+//!
+//! Unlike the CPU's snapshots, the parts have a fixed layout, so a save state
+//! can only be loaded by the version of Chuck that saved it, and only into a
+//! console of the same region with the same cartridge and input devices, and
+//! with the EPSM plugged in only if it was when the state was saved.
+//!
+//! A save state may also be compressed, see [`Nes::save_state_compressed`].
+//! It's compressed with Deflate rather than zstd: `flate2` already compresses
+//! the thumbnails of the slots and the PNG screenshots, and its Rust backend
+//! builds without a C toolchain, unlike the bindings of zstd. At its fastest
+//! level, compressing and decompressing a save state take about 0.1 ms each
+//! (see the `snapshot` benchmark), so zstd would save little time, and the
+//! states kept in memory aren't compressed anyway.
+
+use std::io::{self, Read, Write};
+
+use chuck_apu::Apu;
+use chuck_cpu::Cpu;
+use chuck_input::Ports;
+use chuck_ppu::Ppu;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::determinism::{DeterminismConfig, RamInit};
+use crate::dma::Dma;
+use crate::epsm::Epsm;
+use crate::mapper::{Mapper, A12};
+use crate::{Nes, Region};
+
+/// The magic bytes that start a save state.
+const MAGIC: [u8; 4] = *b"STA\x1a";
+
+/// The magic bytes that start a compressed save state.
+const COMPRESSED_MAGIC: [u8; 4] = *b"STZ\x1a";
+
+/// The current version of the save state format.
+const VERSION: u8 = 8;
+
+impl Nes {
+    /// Save the complete state of the console, including the state of any
+    /// instruction or DMA in progress, so a console restored with
+    /// [`Nes::load_state`] continues on the exact same cycle.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save_state(&self, mut writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&[encode_region(self.region), self.phase])?;
+        writer.write_all(&self.overclock.remaining().to_le_bytes())?;
+        writer.write_all(&encode_determinism(self.determinism))?;
+
+        self.cpu.save(&mut writer)?;
+        self.ppu.save(writer)?;
+        self.apu.save(writer)?;
+        self.dma.save(writer)?;
+        writer.write_all(&*self.ram)?;
+        writer.write_all(&*self.ciram)?;
+        writer.write_all(&[self.open_bus])?;
+        self.input.save(writer)?;
+        self.a12.save(writer)?;
+        self.cartridge.save(writer)?;
+
+        writer.write_all(&[u8::from(self.epsm.is_some())])?;
+        if let Some(epsm) = &self.epsm {
+            epsm.save(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Save the complete state of the console like [`Nes::save_state`], but
+    /// compressed.
+    ///
+    /// A compressed save state starts with the magic bytes `STZ\x1a`,
+    /// followed by the save state compressed with Deflate at its fastest
+    /// level. Most of a save state is memory which rarely uses all of its
+    /// values, e.g. the frame buffer and the RAM, so it shrinks to a fraction
+    /// of its size, while both compressing and decompressing it take well
+    /// below a millisecond. Such files are much smaller, e.g. for the slots of
+    /// a frontend, while the rewind buffer and netplay, which keep their
+    /// states in memory, use the uncompressed ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save_state_compressed(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&COMPRESSED_MAGIC)?;
+        let mut encoder = DeflateEncoder::new(writer, Compression::fast());
+        self.save_state(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Return the save state of the console.
+    pub(crate) fn state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        self.save_state(&mut state)
+            .expect("writing to a Vec is infallible");
+        state
+    }
+
+    /// Load the complete state of the console saved by [`Nes::save_state`],
+    /// or by [`Nes::save_state_compressed`].
+    ///
+    /// The console is left untouched if the state is malformed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed, was saved by
+    /// another version, or doesn't match the region, the cartridge or the
+    /// input devices.
+    pub fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        match magic {
+            MAGIC => self.load_uncompressed(reader),
+            COMPRESSED_MAGIC => {
+                let mut decoder = DeflateDecoder::new(reader);
+                decoder.read_exact(&mut magic)?;
+                if magic != MAGIC {
+                    return Err(invalid("invalid save state magic"));
+                }
+
+                self.load_uncompressed(&mut decoder)
+            }
+            _ => Err(invalid("invalid save state magic")),
+        }
+    }
+
+    /// Load the state of the console saved by [`Nes::save_state`], after its
+    /// magic bytes.
+    fn load_uncompressed(&mut self, mut reader: &mut dyn Read) -> io::Result<()> {
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+
+        if version[0] != VERSION {
+            return Err(invalid("unsupported save state version"));
+        }
+
+        let mut clock = [0; 6];
+        reader.read_exact(&mut clock)?;
+
+        if clock[0] != encode_region(self.region) {
+            return Err(invalid("save state of another region"));
+        }
+
+        if clock[1] >= self.region.ppu_divider() {
+            return Err(invalid("invalid clock phase in save state"));
+        }
+
+        let mut determinism = [0; 12];
+        reader.read_exact(&mut determinism)?;
+        let determinism = decode_determinism(determinism)
+            .filter(|config| config.alignment < self.region.cpu_divider())
+            .ok_or_else(|| invalid("invalid power-up configuration in save state"))?;
+
+        // The parts are loaded into copies first, so the console is left
+        // untouched if any of them is malformed.
+        let mut parts = Parts {
+            cpu: self.cpu.clone(),
+            ppu: self.ppu.clone(),
+            apu: self.apu.clone(),
+            dma: self.dma.clone(),
+            ram: Box::new([0; 0x800]),
+            ciram: Box::new([0; 0x800]),
+            open_bus: 0,
+            input: self.input.clone(),
+            a12: A12::default(),
+            cartridge: self.cartridge.boxed_clone(),
+            epsm: self.epsm.clone(),
+        };
+
+        parts.cpu.load(&mut reader)?;
+        parts.ppu.load(reader)?;
+        parts.apu.load(reader)?;
+        parts.dma.load(reader)?;
+        reader.read_exact(&mut *parts.ram)?;
+        reader.read_exact(&mut *parts.ciram)?;
+
+        let mut open_bus = [0; 1];
+        reader.read_exact(&mut open_bus)?;
+        parts.open_bus = open_bus[0];
+
+        parts.input.load(reader)?;
+        parts.a12 = A12::load(reader)?;
+        parts.cartridge.load(reader)?;
+
+        let mut epsm = [0; 1];
+        reader.read_exact(&mut epsm)?;
+        if epsm[0] > 1 || (epsm[0] == 1) != parts.epsm.is_some() {
+            return Err(invalid("save state with another expansion port"));
+        }
+        if let Some(epsm) = &mut parts.epsm {
+            epsm.load(reader)?;
+        }
+
+        self.phase = clock[1];
+        let remaining = u32::from_le_bytes([clock[2], clock[3], clock[4], clock[5]]);
+        self.overclock.set_remaining(remaining);
+        self.determinism = determinism;
+        parts.commit(self);
+
+        self.samples.clear();
+        // The save RAM of the state has to be flushed like any other write.
+        self.sram.write();
+        for chr in [&mut self.chr, &mut self.viewer_chr].into_iter().flatten() {
+            chr.reload(&*self.cartridge);
+        }
+        Ok(())
+    }
+}
+
+/// The parts of the console loaded from a save state, which are only
+/// committed to the console once all of them were loaded.
+struct Parts {
+    /// The CPU.
+    cpu: Cpu,
+    /// The PPU.
+    ppu: Ppu,
+    /// The APU.
+    apu: Apu,
+    /// The DMA unit.
+    dma: Dma,
+    /// The RAM.
+    ram: Box<[u8; 0x800]>,
+    /// The CIRAM.
+    ciram: Box<[u8; 0x800]>,
+    /// The last value on the external data bus.
+    open_bus: u8,
+    /// The controller ports.
+    input: Ports,
+    /// The `A12` line of the PPU bus.
+    a12: A12,
+    /// The cartridge.
+    cartridge: Box<dyn Mapper>,
+    /// The EPSM, if plugged in.
+    epsm: Option<Box<Epsm>>,
+}
+
+impl Parts {
+    /// Replace the parts of the given console.
+    fn commit(self, nes: &mut Nes) {
+        nes.cpu = self.cpu;
+        nes.ppu = self.ppu;
+        nes.apu = self.apu;
+        nes.dma = self.dma;
+        nes.ram = self.ram;
+        nes.ciram = self.ciram;
+        nes.open_bus = self.open_bus;
+        nes.input = self.input;
+        nes.a12 = self.a12;
+        nes.cartridge = self.cartridge;
+        nes.epsm = self.epsm;
+    }
+}
+
+/// Encode the region of the console.
+const fn encode_region(region: Region) -> u8 {
+    match region {
+        Region::Ntsc => 0,
+        Region::Pal => 1,
+        Region::Dendy => 2,
+    }
+}
+
+/// Encode the configuration of the power-up state, as the kind of the
+/// contents of the RAM and their value or seed, the alignment and the start
+/// of the frame counter.
+fn encode_determinism(config: DeterminismConfig) -> [u8; 12] {
+    let (kind, value) = match config.ram {
+        RamInit::Fill(value) => (0, u64::from(value)),
+        RamInit::Pattern => (1, 0),
+        RamInit::Random(seed) => (2, seed),
+    };
+
+    let mut bytes = [0; 12];
+    bytes[0] = kind;
+    bytes[1..9].copy_from_slice(&value.to_le_bytes());
+    bytes[9] = config.alignment;
+    bytes[10..].copy_from_slice(&config.frame_counter.to_le_bytes());
+    bytes
+}
+
+/// Decode the configuration of the power-up state encoded by
+/// [`encode_determinism`].
+fn decode_determinism(bytes: [u8; 12]) -> Option<DeterminismConfig> {
+    let [kind, value @ .., alignment, low, high] = bytes;
+    let value = u64::from_le_bytes(value);
+
+    let ram = match kind {
+        0 => RamInit::Fill(u8::try_from(value).ok()?),
+        1 => RamInit::Pattern,
+        2 => RamInit::Random(value),
+        _ => return None,
+    };
+
+    Some(DeterminismConfig {
+        ram,
+        alignment,
+        frame_counter: u16::from_le_bytes([low, high]),
+    })
+}
+
+/// Create an error denoting a malformed save state.
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
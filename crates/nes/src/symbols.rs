@@ -0,0 +1,451 @@
+//! The symbols of a program, i.e. the labels of its addresses and the source
+//! lines of its instructions, for disassemblies and debuggers.
+//!
+//! The symbols are read from the label files of Mesen (`.mlb`) or from the
+//! debug info written by ld65 (`.dbg`, with `--dbgfile`), which also holds
+//! the source lines. A Mesen label file has a line `type:address:label` per
+//! label, optionally followed by `:comment`:
+//!
+//! ```no-run
+//! P:0000:reset
+//! P:3FFA-3FFF:vectors:the interrupt vectors
+//! R:0010:frame_count
+//! S:0000:save_slot
+//! ```
+//!
+//! The labels of the PRG-ROM (`P`) are located by their offset into it, so
+//! a label of a bank only names an address while the bank is mapped there,
+//! see [`Mapper::prg_offset`]. The other labels name an address of the CPU
+//! bus: the internal RAM (`R`), the save and work RAM (`S` and `W`, from
+//! `$6000`) and the registers (`G`). A label of a range only names its first
+//! address.
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::symbols::Symbols;
+//! # use chuck_nes::Nes;
+//! let file = "P:0000:reset\nR:0010:frame_count\n";
+//! let symbols = Symbols::read_mlb(&mut file.as_bytes())?;
+//!
+//! // The PRG-ROM of 16 KiB is mirrored at `$C000`.
+//! let nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+//! assert_eq!(symbols.label(nes.cartridge(), 0xc000), Some("reset"));
+//! assert_eq!(symbols.label(nes.cartridge(), 0x0010), Some("frame_count"));
+//! assert_eq!(symbols.addr(nes.cartridge(), "reset"), Some(0x8000));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Of the debug info, the labels (`sym` with `type=lab`) and the lines of
+//! the source files other than macro expansions are read. The segments in
+//! `$8000`-`$FFFF` that are written to the output file are located in the
+//! PRG-ROM, for which the output file must be the ROM, with a header of 16
+//! bytes.
+//!
+//! # Link(s)
+//!
+//! - <https://www.mesen.ca/docs/debugging/debuggerintegration.html>
+//! - <https://cc65.github.io/doc/debugging.html>
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+
+use crate::mapper::Mapper;
+
+/// The size of the header of the ROM at the start of the output file of
+/// ld65, which precedes the PRG-ROM.
+const HEADER_SIZE: usize = 16;
+
+/// The start of the save and work RAM on the CPU bus.
+const PRG_RAM: usize = 0x6000;
+
+/// A location of a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// An address of the CPU bus.
+    Cpu(u16),
+    /// An offset into the PRG-ROM.
+    Prg(usize),
+}
+
+impl Location {
+    /// Return the distance of the given address after the location, or
+    /// `None` if the address is before the location or if the location
+    /// isn't mapped into the CPU bus.
+    fn distance(self, cartridge: &dyn Mapper, addr: u16) -> Option<usize> {
+        match self {
+            Self::Cpu(start) => addr.checked_sub(start).map(usize::from),
+            Self::Prg(start) => cartridge.prg_offset(addr)?.checked_sub(start),
+        }
+    }
+}
+
+/// A label of an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// The name of the label.
+    pub name: String,
+    /// The location of the label.
+    pub location: Location,
+}
+
+/// A line of a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Source<'a> {
+    /// The name of the file, as given to the assembler or the compiler.
+    pub file: &'a str,
+    /// The number of the line, starting at 1.
+    pub line: u32,
+}
+
+impl fmt::Display for Source<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// The bytes generated by a line of a source file.
+#[derive(Debug, Clone)]
+struct Line {
+    /// The location of the first byte.
+    location: Location,
+    /// The number of bytes.
+    size: usize,
+    /// The index of the file into the names of the files.
+    file: usize,
+    /// The number of the line.
+    number: u32,
+    /// A flag denoting if the line is one of a C source file, rather than
+    /// of the assembly generated for it.
+    external: bool,
+}
+
+/// The symbols of a program, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    /// The labels, in the order of the file.
+    labels: Vec<Label>,
+    /// The indices into the labels of the first label of every location.
+    locations: HashMap<Location, usize>,
+    /// The names of the source files.
+    files: Vec<String>,
+    /// The lines of the source files.
+    lines: Vec<Line>,
+}
+
+impl Symbols {
+    /// Create an empty set of symbols.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the labels of a Mesen label file (`.mlb`).
+    ///
+    /// The labels of the memories other than the CPU bus and the PRG-ROM,
+    /// e.g. of the CHR-ROM, and the lines with only a comment are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if a line is malformed.
+    pub fn read_mlb(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut symbols = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.splitn(4, ':');
+            let (Some(kind), Some(range), Some(name)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid(&format!("line {}: missing label", number + 1)));
+            };
+            let start = range.split_once('-').map_or(range, |(start, _)| start);
+            let Ok(start) = usize::from_str_radix(start, 16) else {
+                return Err(invalid(&format!("line {}: bad address", number + 1)));
+            };
+
+            let location = match kind {
+                "P" | "NesPrgRom" => Some(Location::Prg(start)),
+                "R" | "NesInternalRam" | "G" | "NesMemory" | "Register" => cpu(start),
+                "S" | "W" | "NesSaveRam" | "NesWorkRam" => cpu(PRG_RAM + start),
+                _ => None,
+            };
+            if let (Some(location), false) = (location, name.is_empty()) {
+                symbols.push_label(name, location);
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// Read the labels and source lines of the debug info of ld65 (`.dbg`).
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if a record is malformed or refers to
+    /// a missing record, or if the version of the format is unsupported.
+    pub fn read_dbg(reader: &mut dyn Read) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut files = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut lines = Vec::new();
+        let mut labels = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let invalid = |message| invalid(&format!("line {}: {message}", number + 1));
+            let line = line.trim_end_matches('\r');
+            let (kind, fields) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let fields = Fields::parse(fields).ok_or_else(|| invalid("bad fields"))?;
+            let field = |key| fields.number(key).ok_or_else(|| invalid("bad number"));
+
+            match kind {
+                "version" if field("major")? != 2 => return Err(invalid("unsupported version")),
+                "file" => {
+                    let name = fields.get("name").ok_or_else(|| invalid("missing name"))?;
+                    files.insert(field("id")?, name);
+                }
+                "seg" => {
+                    let segment = Segment {
+                        start: field("start")?,
+                        offset: fields.number("ooffs"),
+                    };
+                    segments.insert(field("id")?, segment);
+                }
+                "span" => {
+                    let span = (field("seg")?, field("start")?, field("size")?);
+                    spans.insert(field("id")?, span);
+                }
+                // The lines of type 2 are the lines of macros, which are
+                // expanded at the lines that use them.
+                "line" if fields.get("type") != Some("2") => {
+                    let Some(ids) = fields.get("span") else {
+                        continue;
+                    };
+                    let ids = ids
+                        .split('+')
+                        .map(parse_number)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid("bad span"))?;
+                    let line = u32::try_from(field("line")?).unwrap_or(u32::MAX);
+                    let external = fields.get("type") == Some("1");
+                    lines.push((field("file")?, line, external, ids, number + 1));
+                }
+                "sym" if fields.get("type") == Some("lab") => {
+                    let name = fields.get("name").ok_or_else(|| invalid("missing name"))?;
+                    labels.push((name, field("val")?, fields.number("seg")));
+                }
+                _ => {}
+            }
+        }
+
+        let mut symbols = Self::new();
+        for (name, value, segment) in labels {
+            let location = segment.and_then(|id| segments.get(&id)).map_or_else(
+                || cpu(value),
+                |segment| segment.location(value.wrapping_sub(segment.start)),
+            );
+            if let Some(location) = location {
+                symbols.push_label(name, location);
+            }
+        }
+
+        let mut indices = HashMap::new();
+        for (file, line, external, ids, number) in lines {
+            let invalid = |message| invalid(&format!("line {number}: {message}"));
+            let name = files.get(&file).ok_or_else(|| invalid("missing file"))?;
+            let file = *indices.entry(file).or_insert_with(|| {
+                symbols.files.push((*name).to_owned());
+                symbols.files.len() - 1
+            });
+
+            for id in ids {
+                let &(segment, start, size) =
+                    spans.get(&id).ok_or_else(|| invalid("missing span"))?;
+                let segment = segments
+                    .get(&segment)
+                    .ok_or_else(|| invalid("missing segment"))?;
+                if let Some(location) = segment.location(start) {
+                    symbols.lines.push(Line {
+                        location,
+                        size,
+                        file,
+                        number: line,
+                        external,
+                    });
+                }
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// Return the labels, in the order of their file.
+    #[must_use]
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Return the label of the given address of the CPU bus with the current
+    /// banks of the given cartridge, if any.
+    ///
+    /// A label of the PRG-ROM takes precedence over one of the CPU bus.
+    #[must_use]
+    pub fn label(&self, cartridge: &dyn Mapper, addr: u16) -> Option<&str> {
+        self.label_at(addr, cartridge.prg_offset(addr))
+    }
+
+    /// Return the label of the given address of the CPU bus, which read the
+    /// given offset into the PRG-ROM if any, e.g. when it was recorded by the
+    /// [`profile`](crate::profile) while another bank is mapped now.
+    #[must_use]
+    pub fn label_at(&self, addr: u16, prg_offset: Option<usize>) -> Option<&str> {
+        prg_offset
+            .map(Location::Prg)
+            .into_iter()
+            .chain([Location::Cpu(addr)])
+            .find_map(|location| self.locations.get(&location))
+            .map(|&index| self.labels[index].name.as_str())
+    }
+
+    /// Return the address of the CPU bus of the label with the given name,
+    /// or `None` if there is no such label or if its bank isn't mapped by
+    /// the given cartridge.
+    ///
+    /// A bank mapped at several addresses returns the lowest one.
+    #[must_use]
+    pub fn addr(&self, cartridge: &dyn Mapper, name: &str) -> Option<u16> {
+        let label = self.labels.iter().find(|label| label.name == name)?;
+
+        match label.location {
+            Location::Cpu(addr) => Some(addr),
+            Location::Prg(offset) => {
+                (0x8000..=0xffff).find(|&addr| cartridge.prg_offset(addr) == Some(offset))
+            }
+        }
+    }
+
+    /// Return the source line of the instruction at the given address of the
+    /// CPU bus with the current banks of the given cartridge, if any.
+    ///
+    /// Of the lines generating the byte at the address, the one generating
+    /// the fewest bytes is returned, e.g. a line of C source code over the
+    /// line of a function for its assembly.
+    #[must_use]
+    pub fn source(&self, cartridge: &dyn Mapper, addr: u16) -> Option<Source<'_>> {
+        self.lines
+            .iter()
+            .filter(|line| {
+                line.location
+                    .distance(cartridge, addr)
+                    .is_some_and(|distance| distance < line.size)
+            })
+            .min_by_key(|line| (line.size, !line.external))
+            .map(|line| Source {
+                file: &self.files[line.file],
+                line: line.number,
+            })
+    }
+
+    /// Add a label.
+    fn push_label(&mut self, name: &str, location: Location) {
+        self.locations.entry(location).or_insert(self.labels.len());
+        self.labels.push(Label {
+            name: name.to_owned(),
+            location,
+        });
+    }
+}
+
+/// A segment of the debug info of ld65.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    /// The address of the segment on the CPU bus.
+    start: usize,
+    /// The offset of the segment into the output file, if it's written to
+    /// it.
+    offset: Option<usize>,
+}
+
+impl Segment {
+    /// Return the location of the given offset into the segment.
+    fn location(self, offset: usize) -> Option<Location> {
+        let start = self.start.checked_add(offset)?;
+
+        match self.offset.and_then(|file| file.checked_sub(HEADER_SIZE)) {
+            Some(prg) if start >= 0x8000 => Some(Location::Prg(prg.checked_add(offset)?)),
+            _ => cpu(start),
+        }
+    }
+}
+
+/// The `key=value` fields of a record of the debug info of ld65, separated
+/// by commas.
+#[derive(Debug)]
+struct Fields<'a>(Vec<(&'a str, &'a str)>);
+
+impl<'a> Fields<'a> {
+    /// Parse the fields, whose values are numbers, names or strings in
+    /// double quotes, which may contain commas.
+    fn parse(mut text: &'a str) -> Option<Self> {
+        let mut fields = Vec::new();
+
+        while !text.is_empty() {
+            let (key, rest) = text.split_once('=')?;
+            let (value, rest) = match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let (value, rest) = quoted.split_once('"')?;
+                    (value, rest.strip_prefix(',').unwrap_or(rest))
+                }
+                None => rest.split_once(',').unwrap_or((rest, "")),
+            };
+
+            fields.push((key.trim(), value));
+            text = rest.trim();
+        }
+
+        Some(Self(fields))
+    }
+
+    /// Return the value of the given key.
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.0
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, value)| value)
+    }
+
+    /// Return the value of the given key, as a number.
+    fn number(&self, key: &str) -> Option<usize> {
+        parse_number(self.get(key)?)
+    }
+}
+
+/// Parse a decimal number, or a hexadecimal one with the prefix `0x`.
+fn parse_number(text: &str) -> Option<usize> {
+    text.strip_prefix("0x").map_or_else(
+        || text.parse().ok(),
+        |hex| usize::from_str_radix(hex, 16).ok(),
+    )
+}
+
+/// Return the location of the given address of the CPU bus, or `None` if
+/// it's out of the bus.
+fn cpu(addr: usize) -> Option<Location> {
+    u16::try_from(addr).ok().map(Location::Cpu)
+}
+
+/// Return an error for malformed symbols.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
@@ -0,0 +1,184 @@
+//! The time the host spends on the chips of the console per frame, for
+//! optimizing the emulator.
+//!
+//! While enabled (see [`Nes::time_subsystems`](crate::Nes::time_subsystems)),
+//! one of every few CPU cycles is timed, split into the time of the CPU
+//! (including servicing its bus access), of the PPU's dots, of the APU
+//! (including the DMA unit and the mixing) and of the cartridge's board (its
+//! clock and expansion audio). The totals of a frame are extrapolated from
+//! these samples, which keeps the reads of the host's clock cheap enough to
+//! not skew the numbers much. The extra scanlines of the overclocking aren't
+//! timed.
+//!
+//! Every frame is a `frame` span of [`tracing`] (at the debug level), with
+//! the times of the subsystems recorded as fields in microseconds, so any
+//! subscriber can collect them:
+//!
+//! ```
+//! # use chuck_nes::mapper::{Mirroring, Nrom};
+//! # use chuck_nes::Nes;
+//! # let mut nes = Nes::new(Box::new(Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical)));
+//! nes.time_subsystems(true);
+//! nes.run_frame();
+//!
+//! let stats = nes.frame_stats().unwrap();
+//! assert!(!stats.cpu.is_zero() && !stats.ppu.is_zero());
+//! assert_eq!(stats.emulation(), stats.cpu + stats.ppu + stats.apu + stats.mapper);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+
+/// Every how many CPU cycles one is timed.
+const INTERVAL: u32 = 16;
+
+/// The time spent on the subsystems of the console during a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// The time of the CPU, including servicing its bus accesses.
+    pub cpu: Duration,
+    /// The time of the PPU.
+    pub ppu: Duration,
+    /// The time of the APU, the DMA unit and the mixing of the audio.
+    pub apu: Duration,
+    /// The time of the board of the cartridge, i.e. its clock and its
+    /// expansion audio.
+    pub mapper: Duration,
+    /// The time the frontend took to present the frame, as reported by
+    /// [`Nes::record_presentation`](crate::Nes::record_presentation).
+    pub presentation: Duration,
+}
+
+impl FrameStats {
+    /// Return the time spent emulating the frame, i.e. the time of all
+    /// subsystems but the presentation.
+    #[must_use]
+    pub fn emulation(&self) -> Duration {
+        self.cpu + self.ppu + self.apu + self.mapper
+    }
+}
+
+/// A subsystem of the console, whose time is measured.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Subsystem {
+    /// The CPU.
+    Cpu,
+    /// The PPU.
+    Ppu,
+    /// The APU.
+    Apu,
+    /// The board of the cartridge.
+    Mapper,
+}
+
+/// The times of the subsystems during a timed CPU cycle.
+#[derive(Debug)]
+pub(crate) struct Lap {
+    /// The time the last subsystem was done.
+    split: Instant,
+    /// The times of the subsystems, in the order of [`Subsystem`].
+    times: [Duration; 4],
+}
+
+/// Add the time since the last subsystem was done to the given one, if the
+/// CPU cycle is timed.
+pub(crate) fn split(lap: &mut Option<Lap>, subsystem: Subsystem) {
+    if let Some(lap) = lap {
+        let now = Instant::now();
+        lap.times[subsystem as usize] += now - lap.split;
+        lap.split = now;
+    }
+}
+
+/// The timer of the subsystems.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Timer {
+    /// The number of CPU cycles until the next timed one.
+    countdown: u32,
+    /// The number of CPU cycles of the current frame.
+    cycles: u32,
+    /// The number of timed CPU cycles of the current frame.
+    timed: u32,
+    /// The times of the subsystems during the timed CPU cycles of the current
+    /// frame, in the order of [`Subsystem`].
+    times: [Duration; 4],
+    /// The times of the last frame.
+    last: Option<FrameStats>,
+}
+
+impl Timer {
+    /// Start a CPU cycle, returning its lap if it's timed.
+    pub(crate) fn lap(&mut self) -> Option<Lap> {
+        self.cycles += 1;
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return None;
+        }
+
+        self.countdown = INTERVAL - 1;
+        Some(Lap {
+            split: Instant::now(),
+            times: [Duration::ZERO; 4],
+        })
+    }
+
+    /// Add the times of a timed CPU cycle.
+    pub(crate) fn add(&mut self, lap: &Lap) {
+        self.timed += 1;
+        for (time, lap) in self.times.iter_mut().zip(lap.times) {
+            *time += lap;
+        }
+    }
+
+    /// Finish the frame, extrapolating the times of its CPU cycles from the
+    /// timed ones, and record them into its span.
+    pub(crate) fn end_frame(&mut self, span: &EnteredSpan) {
+        let scale = f64::from(self.cycles) / f64::from(self.timed.max(1));
+        let [cpu, ppu, apu, mapper] = self.times.map(|time| time.mul_f64(scale));
+        let stats = FrameStats {
+            cpu,
+            ppu,
+            apu,
+            mapper,
+            presentation: Duration::ZERO,
+        };
+
+        span.record("cpu_us", stats.cpu.as_micros());
+        span.record("ppu_us", stats.ppu.as_micros());
+        span.record("apu_us", stats.apu.as_micros());
+        span.record("mapper_us", stats.mapper.as_micros());
+
+        self.last = Some(stats);
+        self.cycles = 0;
+        self.timed = 0;
+        self.times = [Duration::ZERO; 4];
+    }
+
+    /// Return the times of the last frame, if any.
+    pub(crate) const fn last(&self) -> Option<&FrameStats> {
+        self.last.as_ref()
+    }
+
+    /// Set the time the frontend took to present the last frame.
+    pub(crate) fn record_presentation(&mut self, time: Duration) {
+        if let Some(last) = &mut self.last {
+            last.presentation = time;
+        }
+        tracing::debug!(presentation_us = time.as_micros(), "presented a frame");
+    }
+}
+
+/// Enter the span of a frame.
+pub(crate) fn frame_span(frame: u64) -> EnteredSpan {
+    tracing::debug_span!(
+        "frame",
+        frame,
+        cpu_us = Empty,
+        ppu_us = Empty,
+        apu_us = Empty,
+        mapper_us = Empty
+    )
+    .entered()
+}
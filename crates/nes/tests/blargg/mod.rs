@@ -0,0 +1,68 @@
+//! The result protocol of blargg's test ROMs, which report their result in
+//! the PRG-RAM.
+//!
+//! `$6001`-`$6003` hold a signature once the result is valid, `$6000` the
+//! status (`$80` while the test is running, and `0` if it passed), and `$6004`
+//! a text describing it.
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/christopherpow/nes-test-roms/blob/master/README.md>
+
+use std::env;
+use std::ffi::OsString;
+
+use chuck_nes::Nes;
+use chuck_rom::Rom;
+
+/// The signature at `$6001`-`$6003` once the result is valid.
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+/// The number of frames after which a test is considered to hang.
+const MAX_FRAMES: usize = 1800;
+
+/// Return the directory of a test set given by the environment variable, or
+/// `None` (with a note) if it isn't set, so the tests are skipped.
+pub fn test_dir(var: &str) -> Option<OsString> {
+    let dir = env::var_os(var);
+
+    if dir.is_none() {
+        eprintln!("skipping the test ROMs, {var} is not set");
+    }
+
+    dir
+}
+
+/// Run a test ROM until it reports its result, returning a description of the
+/// failure.
+pub fn run(rom: &[u8]) -> Result<(), String> {
+    let rom = Rom::parse(rom).map_err(|err| err.to_string())?;
+    let mut nes = Nes::from_rom(&rom).map_err(|err| err.to_string())?;
+
+    for _ in 0..MAX_FRAMES {
+        nes.run_frame();
+
+        let cartridge = nes.cartridge_mut();
+        let mut read = |addr| cartridge.cpu_read(addr).unwrap_or(0);
+
+        if [read(0x6001), read(0x6002), read(0x6003)] != SIGNATURE {
+            continue;
+        }
+
+        match read(0x6000) {
+            0x80 => {}
+            0x00 => return Ok(()),
+            status => {
+                let text: String = (0x6004..0x7000)
+                    .map(&mut read)
+                    .take_while(|&byte| byte != 0)
+                    .map(char::from)
+                    .collect();
+
+                return Err(format!("status {status:02x}: {}", text.trim()));
+            }
+        }
+    }
+
+    Err(format!("no result after {MAX_FRAMES} frames"))
+}
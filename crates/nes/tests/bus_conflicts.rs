@@ -0,0 +1,84 @@
+//! The bus conflicts of the discrete boards (mappers 2, 3, 7 and 66): a write
+//! to their bank select register, which the ROM drives at the same time,
+//! must select the bank of the AND of the written value and the ROM byte if
+//! the submapper number asks for it.
+
+use chuck_nes::mapper::{self, Mapper};
+use chuck_rom::Rom;
+
+/// The address written to, at the start of every PRG-ROM bank.
+const ADDR: u16 = 0x8001;
+
+/// The byte of the ROM at [`ADDR`].
+const ROM_BYTE: u8 = 0x15;
+
+/// The value written, which disagrees with [`ROM_BYTE`]: it selects bank 3
+/// (and CHR bank 3 on `GxROM`), and its AND with the ROM byte bank 1.
+const DATA: u8 = 0x33;
+
+/// Create the board of a NES 2.0 ROM with the given mapper and submapper
+/// numbers, and the given numbers of 16 KiB PRG-ROM and 8 KiB CHR-ROM banks.
+///
+/// Every bank of the given size starts with its index, followed by
+/// [`ROM_BYTE`] in the PRG-ROM.
+fn board(
+    mapper: u8,
+    submapper: u8,
+    prg_banks: u8,
+    prg_size: usize,
+    chr_banks: u8,
+) -> Box<dyn Mapper> {
+    let mut file = vec![0; 16];
+    file[..4].copy_from_slice(b"NES\x1a");
+    file[4] = prg_banks;
+    file[5] = chr_banks;
+    file[6] = mapper << 4;
+    file[7] = (mapper & 0xf0) | 0x08;
+    file[8] = submapper << 4;
+
+    let mut prg = vec![0; usize::from(prg_banks) * 0x4000];
+    for (i, bank) in prg.chunks_mut(prg_size).enumerate() {
+        bank[0] = u8::try_from(i).unwrap();
+        bank[1] = ROM_BYTE;
+    }
+    file.append(&mut prg);
+
+    let mut chr = vec![0; usize::from(chr_banks) * 0x2000];
+    for (i, bank) in chr.chunks_mut(0x2000).enumerate() {
+        bank[0] = u8::try_from(i).unwrap();
+    }
+    file.append(&mut chr);
+
+    mapper::from_rom(&Rom::parse(&file).unwrap()).unwrap()
+}
+
+/// Write [`DATA`] to [`ADDR`], and return the index of the PRG-ROM bank at
+/// `$8000` and of the CHR bank at `$0000`.
+fn select(mut board: Box<dyn Mapper>) -> (u8, u8) {
+    board.cpu_write(ADDR, DATA);
+    (board.cpu_read(0x8000).unwrap(), board.ppu_read(0x0000))
+}
+
+#[test]
+fn uxrom() {
+    assert_eq!(select(board(2, 2, 4, 0x4000, 0)).0, 1);
+    assert_eq!(select(board(2, 0, 4, 0x4000, 0)).0, 3);
+}
+
+#[test]
+fn cnrom() {
+    assert_eq!(select(board(3, 2, 2, 0x8000, 4)).1, 1);
+    assert_eq!(select(board(3, 0, 2, 0x8000, 4)).1, 3);
+}
+
+#[test]
+fn axrom() {
+    assert_eq!(select(board(7, 2, 8, 0x8000, 0)).0, 1);
+    assert_eq!(select(board(7, 0, 8, 0x8000, 0)).0, 3);
+}
+
+#[test]
+fn gxrom() {
+    assert_eq!(select(board(66, 2, 8, 0x8000, 4)), (1, 1));
+    assert_eq!(select(board(66, 0, 8, 0x8000, 4)), (3, 3));
+}
@@ -0,0 +1,72 @@
+//! The cache of the decoded tiles of the viewers, which must show the same
+//! pictures as the viewers reading the VRAM bus, also once the program wrote
+//! the CHR-RAM behind the cached tiles.
+
+mod common;
+
+use chuck_nes::chr::TileCache;
+use chuck_nes::Nes;
+
+/// A program that fills the first 256 bytes of the pattern tables, then loops
+/// forever.
+const PROGRAM: &str = "
+reset:
+    JSR init_ppu
+loop:
+    JMP loop
+nmi:
+irq:
+    RTI
+";
+
+/// Return the pattern tables rendered with the cache, and without it.
+fn pattern_tables(nes: &mut Nes, cache: &mut TileCache) -> (Vec<u16>, Vec<u16>) {
+    let (ppu, vram) = nes.ppu_vram_cached(cache);
+    let cached = ppu.render_pattern_tables(0, vram);
+    let (ppu, peek) = nes.ppu_vram();
+    (cached, ppu.render_pattern_tables(0, peek))
+}
+
+#[test]
+fn drop_stale_tiles() {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    let mut cache = TileCache::new();
+
+    let (cached, expected) = pattern_tables(&mut nes, &mut cache);
+    assert_eq!(cached, expected);
+    let blank = cached;
+
+    for _ in 0..4 {
+        nes.run_frame();
+    }
+
+    let (cached, expected) = pattern_tables(&mut nes, &mut cache);
+    assert_ne!(cached, blank);
+    assert_eq!(cached, expected);
+
+    // A restored state may change any tile.
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    let mut restored = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    assert_eq!(pattern_tables(&mut restored, &mut cache).0, blank);
+    restored.load_state(&mut state.as_slice()).unwrap();
+    assert_eq!(pattern_tables(&mut restored, &mut cache).0, expected);
+}
+
+#[test]
+fn keep_changes_for_other_consumers() {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    let mut cache = TileCache::new();
+    nes.watch_chr(true);
+    assert!(nes.take_chr_changes().unwrap().all);
+
+    for _ in 0..4 {
+        nes.run_frame();
+        let (cached, expected) = pattern_tables(&mut nes, &mut cache);
+        assert_eq!(cached, expected);
+    }
+
+    // The cache took its own changes, not the ones of the watching.
+    let changes = nes.take_chr_changes().unwrap();
+    assert_eq!(changes.tiles.len(), 16);
+}
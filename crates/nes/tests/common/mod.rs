@@ -0,0 +1,52 @@
+//! The cartridge of the test programs, which are assembled from their source
+//! into a 32 KiB NROM board with CHR-RAM.
+//!
+//! A program starts at `$8000` and defines the labels `reset`, `nmi` and
+//! `irq` of its vectors. It may call the subroutines of the cartridge:
+//!
+//! - `init_ppu`, which waits for the PPU to warm up, then fills the first
+//!   256 bytes of the pattern tables with their index and the palette with
+//!   the colors `$00`-`$1F`, leaving the PPU address at `$3F20`.
+
+use chuck_cpu::asm;
+use chuck_nes::mapper::{Mirroring, Nrom};
+
+/// The subroutines of the cartridge, after the program.
+const SUBROUTINES: &str = "
+init_ppu:
+    BIT $2002
+init_ppu_vblank:
+    BIT $2002
+    BPL init_ppu_vblank
+init_ppu_vblank2:
+    BIT $2002
+    BPL init_ppu_vblank2
+
+    LDA #$00
+    STA $2006
+    STA $2006
+    LDX #$00
+init_ppu_patterns:
+    STX $2007
+    INX
+    BNE init_ppu_patterns
+
+    LDA #$3F
+    STA $2006
+    LDA #$00
+    STA $2006
+    LDX #$00
+init_ppu_palette:
+    STX $2007
+    INX
+    CPX #$20
+    BNE init_ppu_palette
+    RTS
+";
+
+/// Assemble a program into the cartridge, with vertical mirroring.
+pub fn cartridge(program: &str) -> Nrom {
+    let source =
+        format!(".org $8000\n{program}\n{SUBROUTINES}\n.org $FFFA\n.word nmi, reset, irq\n");
+    Nrom::new(asm!(&source), Vec::new(), Mirroring::Vertical)
+}
@@ -0,0 +1,84 @@
+//! The Datach: the 24C02 of the base unit must keep the bytes written over
+//! its serial bus, and the barcode reader must send the modules of a barcode
+//! after the white before it.
+
+use chuck_nes::mapper::{Barcode, Datach, Mapper};
+use chuck_rom::Rom;
+
+/// Return a Datach with 32 KiB of PRG-ROM and no PRG-RAM.
+fn datach() -> Datach {
+    let mut file = b"NES\x1a\x02\x00\xd0\x90".to_vec();
+    file.resize(16 + 0x8000, 0);
+    Datach::from_rom(&Rom::parse(&file).unwrap())
+}
+
+/// Drive the serial bus of the 24C02 through a sequence of levels of `SCL`
+/// and `SDA`.
+fn drive(datach: &mut Datach, levels: &[(bool, bool)]) {
+    for &(scl, sda) in levels {
+        datach.cpu_write(0x800d, (u8::from(sda) << 6) | (u8::from(scl) << 5));
+    }
+}
+
+/// Send a start, followed by the given bytes with their acknowledges.
+fn start(datach: &mut Datach, bytes: &[u8]) {
+    drive(
+        datach,
+        &[(false, true), (true, true), (true, false), (false, false)],
+    );
+    for &byte in bytes {
+        for bit in (0..8).rev() {
+            let sda = byte & (1 << bit) != 0;
+            drive(datach, &[(false, sda), (true, sda), (false, sda)]);
+        }
+        drive(datach, &[(false, true), (true, true)]);
+        assert_eq!(datach.cpu_read(0x6000).unwrap() & 0x10, 0, "no acknowledge");
+        drive(datach, &[(false, true)]);
+    }
+}
+
+/// Send a stop.
+fn stop(datach: &mut Datach) {
+    drive(datach, &[(false, false), (true, false), (true, true)]);
+}
+
+#[test]
+fn write_and_read_eeprom() {
+    let mut datach = datach();
+
+    start(&mut datach, &[0xa0, 0x12, 0x5a, 0xc3]);
+    stop(&mut datach);
+    assert_eq!(&datach.battery_ram().unwrap()[0x12..0x14], [0x5a, 0xc3]);
+
+    // A random read: the word address, then a read after a repeated start.
+    start(&mut datach, &[0xa0, 0x13]);
+    start(&mut datach, &[0xa1]);
+    let mut byte = 0;
+    for _ in 0..8 {
+        drive(&mut datach, &[(false, true), (true, true)]);
+        byte = (byte << 1) | ((datach.cpu_read(0x6000).unwrap() >> 4) & 1);
+        drive(&mut datach, &[(false, true)]);
+    }
+    stop(&mut datach);
+    assert_eq!(byte, 0xc3);
+}
+
+#[test]
+fn read_barcode() {
+    let mut datach = datach();
+    let barcode: Barcode = "4905040352507".parse().unwrap();
+    datach.barcode_reader_mut().unwrap().scan(&barcode);
+
+    let mut levels = Vec::new();
+    while datach.barcode_reader_mut().unwrap().is_scanning() {
+        levels.push(datach.cpu_read(0x6000).unwrap() & 0x08 == 0);
+        for _ in 0..1000 {
+            datach.clock();
+        }
+    }
+
+    assert_eq!(levels.len(), 33 + 95 + 32);
+    assert!(levels[..33].iter().all(|&bar| !bar));
+    assert_eq!(&levels[33..128], barcode.modules());
+    assert_eq!(datach.cpu_read(0x6000).unwrap() & 0x08, 0);
+}
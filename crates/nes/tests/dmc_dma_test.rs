@@ -0,0 +1,58 @@
+//! The `dmc_dma_during_read4` ROMs, which test the reads repeated by the CPU
+//! while it is halted for a sample fetch of the DMC, and their side effects on
+//! `PPUDATA`, `PPUSTATUS` and the controller ports.
+//!
+//! Every ROM reports its result in the PRG-RAM, see [`blargg`].
+//!
+//! The ROMs are not part of this repository, so these tests are skipped unless
+//! the `CHUCK_DMC_DMA_TEST_DIR` environment variable points to a directory
+//! with the ROMs of the test set.
+//!
+//! ```no-run
+//! CHUCK_DMC_DMA_TEST_DIR=dmc_dma_during_read4 \
+//!     cargo test --release --test dmc_dma_test
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/christopherpow/nes-test-roms/tree/master/dmc_dma_during_read4>
+
+mod blargg;
+
+use std::fs;
+use std::path::Path;
+
+/// The ROMs of the test set.
+const ROMS: [&str; 5] = [
+    "dma_2007_read.nes",
+    "dma_2007_write.nes",
+    "dma_4016_read.nes",
+    "double_2007_read.nes",
+    "read_write_2007.nes",
+];
+
+#[test]
+fn dmc_dma_tests() {
+    let Some(dir) = blargg::test_dir("CHUCK_DMC_DMA_TEST_DIR") else {
+        return;
+    };
+
+    let dir = Path::new(&dir);
+    let mut failures = Vec::new();
+
+    for name in ROMS {
+        let path = dir.join(name);
+        let rom = fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+        if let Err(msg) = blargg::run(&rom) {
+            failures.push(format!("{name}: {msg}"));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
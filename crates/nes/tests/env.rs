@@ -0,0 +1,68 @@
+//! The episodes of an environment, which must be reproducible from their
+//! start and their seed.
+
+mod common;
+
+use std::io;
+use std::num::NonZeroU32;
+
+use chuck_input::ButtonState;
+use chuck_nes::env::Env;
+use chuck_nes::Nes;
+
+/// A program that counts the frames in the RAM at `$0000`, by waiting for
+/// the vertical blanking in a loop.
+const PROGRAM: &str = "
+reset:
+    BIT $2002
+    BPL reset
+    INC $00
+    JMP reset
+nmi:
+irq:
+    RTI
+";
+
+/// Create a console with the program.
+fn console() -> Nes {
+    Nes::new(Box::new(common::cartridge(PROGRAM)))
+}
+
+#[test]
+fn reproducible_episodes() {
+    let mut env = Env::new(console());
+    env.set_watched(&[0x0000, 0x0123]);
+    env.set_frames_per_step(NonZeroU32::new(3).unwrap());
+
+    let buttons = [ButtonState::A, ButtonState::empty()];
+    let first = env.reset_with_seed(1).ram.to_vec();
+    let (observation, frames) = env.step(buttons);
+    assert_eq!(frames, 3);
+    assert_eq!(observation.ram[0], first[0].wrapping_add(3));
+
+    // A reset restarts from the power-up of the seed.
+    assert_eq!(env.reset().unwrap().ram, first);
+    assert_eq!(env.frames(), 0);
+    assert_eq!(env.reset_with_seed(1).ram, first);
+    assert_ne!(env.reset_with_seed(2).ram, first);
+
+    // Or from any marked start.
+    env.step(buttons);
+    env.mark_start();
+    let start = env.reset().unwrap().ram.to_vec();
+    let (observation, frames) = env.step(buttons);
+    assert_eq!(frames, 3);
+    assert_eq!(observation.ram[0], start[0].wrapping_add(3));
+    assert_eq!(env.reset().unwrap().ram, start);
+}
+
+#[test]
+fn mismatched_start() {
+    let mut env = Env::new(console());
+    env.step([ButtonState::A, ButtonState::empty()]);
+    env.nes_mut().plug_epsm(true);
+
+    let err = env.reset().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(env.frames(), 1);
+}
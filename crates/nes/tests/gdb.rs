@@ -0,0 +1,241 @@
+//! A debugger attached to the GDB server over a loopback connection, which
+//! inspects the console and runs it to its breakpoints and watchpoints.
+
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use chuck_nes::gdb::Server;
+use chuck_nes::mapper::{Mirroring, Nrom};
+use chuck_nes::symbols::Symbols;
+use chuck_nes::Nes;
+
+/// The program at `$8000`.
+#[rustfmt::skip]
+const PROGRAM: [u8; 9] = [
+    0xa9, 0x42,       // LDA #$42
+    0x85, 0x10,       // STA $10
+    0xe6, 0x11,       // INC $11
+    0x4c, 0x00, 0x80, // JMP $8000
+];
+
+/// The debug info of the program, as written by ld65.
+const DEBUG_INFO: &str = r#"version	major=2,minor=0
+file	id=0,name="main.s",size=120,mtime=0x00000000,mod=0
+line	id=0,file=0,line=3,span=0
+line	id=1,file=0,line=5,span=1
+seg	id=0,name="CODE",start=0x008000,size=0x0009,addrsize=absolute,type=ro,oname="game.nes",ooffs=16
+span	id=0,seg=0,start=0,size=2
+span	id=1,seg=0,start=4,size=2
+sym	id=0,name="reset",addrsize=absolute,scope=0,def=0,val=0x8000,seg=0,type=lab
+sym	id=1,name="count",addrsize=absolute,scope=0,def=1,val=0x8004,seg=0,type=lab
+sym	id=2,name="value",addrsize=zeropage,scope=0,def=1,val=0x10,type=lab
+"#;
+
+/// The number of polls after which the server is considered unresponsive.
+const POLLS: usize = 1000;
+
+/// A debugger connected to a server.
+struct Debugger {
+    server: Server,
+    nes: Nes,
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+impl Debugger {
+    fn attach() -> Self {
+        let mut prg = vec![0; 0x4000];
+        prg[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+        prg[0x3ffc..0x3ffe].copy_from_slice(&[0x00, 0x80]);
+        let nes = Nes::new(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        Self {
+            server,
+            nes,
+            stream,
+            received: Vec::new(),
+        }
+    }
+
+    /// Send a packet without waiting for a reply.
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0, u8::wrapping_add);
+        let packet = format!("${data}#{checksum:02x}");
+        self.stream.write_all(packet.as_bytes()).unwrap();
+    }
+
+    /// Send a packet and return the reply.
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+
+    /// Run the console like a frontend until the server sends a packet, and
+    /// return its data.
+    fn reply(&mut self) -> String {
+        for _ in 0..POLLS {
+            self.server.poll(&mut self.nes).unwrap();
+            if !self.server.is_halted() {
+                if let Err(reason) = self.nes.debug_run_frame() {
+                    self.server.stop(&mut self.nes, reason).unwrap();
+                }
+            }
+
+            let mut buf = [0; 256];
+            match self.stream.read(&mut buf) {
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => panic!("{err}"),
+            }
+
+            let text = String::from_utf8(self.received.clone()).unwrap();
+            let text = text.trim_start_matches('+');
+            if let Some((data, rest)) = text.strip_prefix('$').and_then(|p| p.split_once('#')) {
+                if rest.len() >= 2 {
+                    let data = data.to_owned();
+                    self.received = rest.as_bytes()[2..].to_vec();
+                    return data;
+                }
+            }
+        }
+
+        panic!("no reply from the server");
+    }
+
+    /// Send a command with `monitor` and return its output.
+    fn monitor(&mut self, command: &str) -> String {
+        let mut packet = "qRcmd,".to_owned();
+        for byte in command.bytes() {
+            write!(packet, "{byte:02x}").unwrap();
+        }
+        self.send(&packet);
+
+        // The output is sent in `O` packets before the final `OK`.
+        let mut output = Vec::new();
+        loop {
+            let reply = self.reply();
+            if reply == "OK" {
+                return String::from_utf8(output).unwrap();
+            }
+
+            let hex = reply.strip_prefix('O').unwrap();
+            output.extend(
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()),
+            );
+        }
+    }
+}
+
+#[test]
+fn inspect() {
+    let mut gdb = Debugger::attach();
+
+    assert!(gdb
+        .request("qSupported:swbreak+")
+        .contains("PacketSize=1000"));
+    assert!(gdb
+        .request("qXfer:features:read:target.xml:0,1000")
+        .starts_with("l<?xml"));
+    assert!(gdb.server.is_halted());
+
+    // Halted at the first instruction.
+    assert_eq!(gdb.request("p5"), "0080");
+    assert_eq!(gdb.request("P0=99"), "OK");
+    assert_eq!(gdb.request("g"), "990000fd240080");
+
+    assert_eq!(gdb.request("M0200,2:beef"), "OK");
+    assert_eq!(gdb.nes.ram()[0x200..0x202], [0xbe, 0xef]);
+    assert_eq!(gdb.request("m0200,2"), "beef");
+    assert_eq!(gdb.request("m8000,2"), "a942");
+}
+
+#[test]
+fn run_to_hooks() {
+    let mut gdb = Debugger::attach();
+
+    assert_eq!(gdb.request("s"), "S05");
+    assert_eq!(gdb.request("p0"), "42");
+    assert_eq!(gdb.request("p5"), "0280");
+
+    assert_eq!(gdb.request("Z0,8004,1"), "OK");
+    assert_eq!(gdb.request("c"), "S05");
+    assert_eq!(gdb.request("p5"), "0480");
+    assert_eq!(gdb.request("m10,1"), "42");
+    assert_eq!(gdb.request("z0,8004,1"), "OK");
+
+    // The watchpoint stops after the instruction.
+    assert_eq!(gdb.request("Z2,11,1"), "OK");
+    assert_eq!(gdb.request("c"), "T05watch:0011;");
+    assert_eq!(gdb.request("p5"), "0680");
+    assert_eq!(gdb.request("m11,1"), "01");
+    assert_eq!(gdb.request("z2,11,1"), "OK");
+
+    gdb.send("c");
+    gdb.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb.reply(), "S02");
+
+    assert_eq!(gdb.request("Z3,10,1"), "OK");
+    assert_eq!(gdb.request("D"), "OK");
+    gdb.server.poll(&mut gdb.nes).unwrap();
+    assert!(!gdb.server.is_attached());
+    assert!(gdb.nes.debug_run_frame().is_ok());
+}
+
+#[test]
+fn monitor_symbols() {
+    let mut gdb = Debugger::attach();
+    let symbols = Symbols::read_dbg(&mut DEBUG_INFO.as_bytes()).unwrap();
+    gdb.server.set_symbols(symbols);
+
+    assert_eq!(gdb.monitor("where"), "$8000 reset at main.s:3\n");
+    assert_eq!(gdb.monitor("break count"), "breakpoint at $8004\n");
+    assert_eq!(gdb.request("c"), "S05");
+    assert_eq!(gdb.monitor("where"), "$8004 count at main.s:5\n");
+
+    let disassembly = gdb.monitor("disas reset");
+    assert!(disassembly.starts_with("reset:\n  $8000  LDA #$42\n  $8002  STA value\ncount:\n"));
+    assert!(disassembly.contains("  $8006  JMP reset\n"));
+
+    assert_eq!(gdb.monitor("delete count"), "");
+    assert_eq!(gdb.monitor("break missing"), "no label missing is mapped\n");
+    assert!(gdb.monitor("help").starts_with("commands: reset"));
+}
+
+#[test]
+fn oversized_packet() {
+    let mut gdb = Debugger::attach();
+
+    // A packet larger than the advertised size is rejected before its end
+    // arrives, and its remaining bytes are dropped.
+    let data = "0".repeat(0x2000);
+    let checksum = data.bytes().fold(0, u8::wrapping_add);
+    gdb.stream.write_all(b"$").unwrap();
+    gdb.stream.write_all(&data.as_bytes()[..0x1800]).unwrap();
+    for _ in 0..POLLS {
+        gdb.server.poll(&mut gdb.nes).unwrap();
+
+        let mut buf = [0; 256];
+        match gdb.stream.read(&mut buf) {
+            Ok(n) => gdb.received.extend_from_slice(&buf[..n]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => panic!("{err}"),
+        }
+        if !gdb.received.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(gdb.received, b"-");
+
+    gdb.received.clear();
+    gdb.stream.write_all(&data.as_bytes()[0x1800..]).unwrap();
+    write!(gdb.stream, "#{checksum:02x}").unwrap();
+    assert_eq!(gdb.request("p5"), "0080");
+}
@@ -0,0 +1,28 @@
+//! The generations of the pictures in the frame buffer, which must never
+//! repeat, so that an integration caching the uploads of the pictures by
+//! their generation never skips a new one.
+
+mod common;
+
+use chuck_nes::Nes;
+
+/// A program that loops forever without enabling rendering.
+const PROGRAM: &str = "
+reset:
+nmi:
+irq:
+    JMP reset
+";
+
+#[test]
+fn power_cycle() {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    nes.run_frame();
+    let generation = nes.run_frame().generation;
+
+    nes.power_cycle();
+    assert_eq!(nes.ppu().generation(), generation + 1);
+
+    let next = nes.run_frame().generation;
+    assert!(next > generation + 1, "{next} after {generation}");
+}
@@ -0,0 +1,64 @@
+//! The serial port of the MMC1, which ignores a write on the cycle after
+//! another one, such as the second write of a read-modify-write instruction.
+
+use chuck_cpu::asm;
+use chuck_nes::Nes;
+use chuck_rom::Rom;
+
+/// A program that resets the shift register, loads the PRG bank register
+/// with an `INC` of a ROM byte of `$01` followed by four writes of `$01`,
+/// `$00`, `$00` and `$00`, and stores the first byte of the selected bank.
+const PROGRAM: &str = "
+.org $C000
+.byte $03
+.org $C010
+reset:
+    SEI
+    LDX #$FF
+    TXS
+    LDA #$80
+    STA $8000
+    INC $E000
+    LDA #$01
+    STA $E000
+    LSR A
+    STA $E000
+    STA $E000
+    STA $E000
+    LDA $8000
+    STA $00
+loop:
+    JMP loop
+nmi:
+irq:
+    RTI
+.org $E000
+.byte $01
+.org $FFFA
+.word nmi, reset, irq
+";
+
+/// Return the first byte of the PRG bank selected by the program, once it
+/// ran, from a ROM whose four banks start with their index.
+fn selected_bank() -> u8 {
+    let mut rom = b"NES\x1a\x04\x00\x10\x00".to_vec();
+    rom.resize(16, 0);
+    for bank in 0..3 {
+        let mut prg = vec![0; 0x4000];
+        prg[0] = bank;
+        rom.append(&mut prg);
+    }
+    rom.append(&mut asm!(PROGRAM));
+
+    let mut nes = Nes::from_rom(&Rom::parse(&rom).unwrap()).unwrap();
+    nes.run_frame();
+    nes.ram()[0]
+}
+
+#[test]
+fn read_modify_write() {
+    // Only the first write of the `INC` is shifted in, so the last of the four
+    // writes after it selects bank %00011. If its second write ($02) landed
+    // too, the third one would load %00101, i.e. bank 1 of the four.
+    assert_eq!(selected_bank(), 3);
+}
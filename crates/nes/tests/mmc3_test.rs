@@ -0,0 +1,169 @@
+//! The `mmc3_test` ROMs, which test the scanline counter of the MMC3 and its
+//! timing relative to the PPU.
+//!
+//! Every ROM reports its result in the PRG-RAM, see [`blargg`].
+//!
+//! The ROMs are not part of this repository, so these tests are skipped unless
+//! the `CHUCK_MMC3_TEST_DIR` environment variable points to a directory with
+//! the single ROMs of the test set (`1-clocking.nes` to `6-MMC3_alt.nes`).
+//!
+//! ```no-run
+//! CHUCK_MMC3_TEST_DIR=mmc3_test_2/rom_singles \
+//!     cargo test --release --test mmc3_test
+//! ```
+//!
+//! The `mmc3_test.yml` workflow runs them with the ROMs of the link below.
+//! The differences between the revisions which `5-MMC3.nes` and
+//! `6-MMC3_alt.nes` check are also checked by a program of this test, which
+//! counts the IRQs of the scanline counter with a latch of 0 and 1.
+//!
+//! # Link(s)
+//!
+//! - <https://github.com/christopherpow/nes-test-roms/tree/master/mmc3_test_2>
+
+mod blargg;
+
+use std::fs;
+use std::path::Path;
+
+use chuck_cpu::asm;
+use chuck_nes::Nes;
+use chuck_rom::Rom;
+
+/// The ROMs of the test set, and if they test the MMC3A.
+const ROMS: [(&str, bool); 6] = [
+    ("1-clocking.nes", false),
+    ("2-details.nes", false),
+    ("3-A12_clocking.nes", false),
+    ("4-scanline_timing.nes", false),
+    ("5-MMC3.nes", false),
+    ("6-MMC3_alt.nes", true),
+];
+
+/// Turn an iNES header into a NES 2.0 header of the MMC3A (submapper 4),
+/// whose IRQ counter behaves differently.
+fn as_mmc3a(rom: &mut [u8]) {
+    rom[7] = (rom[7] & 0xf0) | 0x08;
+    rom[8] = 0x40;
+    rom[9..16].fill(0);
+    // 8 KiB of PRG-RAM, and 8 KiB of CHR-RAM if there is no CHR-ROM.
+    rom[10] = 0x07;
+    rom[11] = if rom[5] == 0 { 0x07 } else { 0 };
+}
+
+/// A program that renders with the sprites at `$1000`, so `A12` rises once
+/// per scanline, disables the frame IRQ of the APU, and counts the IRQs of
+/// the scanline counter at `$00`, which is started with the latch at `$FFF9`.
+const PROGRAM: &str = "
+.org $C000
+reset:
+    SEI
+    LDX #$FF
+    TXS
+    LDA #$40
+    STA $4017
+vblank:
+    BIT $2002
+    BPL vblank
+vblank2:
+    BIT $2002
+    BPL vblank2
+
+    LDA $FFF9
+    STA $C000
+    STA $C001
+    STA $E001
+    LDA #$00
+    STA $00
+    LDA #$08
+    STA $2000
+    LDA #$18
+    STA $2001
+    CLI
+loop:
+    JMP loop
+
+irq:
+    INC $00
+    STA $E000
+    STA $E001
+nmi:
+    RTI
+";
+
+/// Return the number of IRQs in each of the first frames after the counter
+/// of the given revision was started with the given latch.
+fn count_irqs(mmc3a: bool, latch: u8) -> [u8; 4] {
+    let mut code = asm!(&format!(
+        "{PROGRAM}\n.org $FFF9\n.byte {latch}\n.word nmi, reset, irq\n"
+    ));
+    let mut rom = b"NES\x1a\x02\x00\x40\x00".to_vec();
+    rom.resize(16 + 0x4000, 0);
+    rom.append(&mut code);
+    if mmc3a {
+        as_mmc3a(&mut rom);
+    }
+
+    let mut nes = Nes::from_rom(&Rom::parse(&rom).unwrap()).unwrap();
+    // The program waits for 2 vertical blanking intervals.
+    for _ in 0..3 {
+        nes.run_frame();
+    }
+
+    let mut counts = [0; 4];
+    let mut count = nes.ram()[0];
+    for frame in &mut counts {
+        nes.run_frame();
+        *frame = nes.ram()[0].wrapping_sub(count);
+        count = nes.ram()[0];
+    }
+    counts
+}
+
+#[test]
+fn revisions() {
+    // With a latch of 0, the newer revisions fire on every scanline (and the
+    // pre-render scanline), but the MMC3A only once after the counter was
+    // cleared by $C001, which is before the frames counted.
+    assert_eq!(count_irqs(false, 0), [241; 4]);
+    assert_eq!(count_irqs(true, 0), [0; 4]);
+
+    // Otherwise, both revisions fire once the counter is decremented to 0.
+    for mmc3a in [false, true] {
+        let counts = count_irqs(mmc3a, 1);
+        assert!(
+            counts.iter().all(|&count| (120..=121).contains(&count)),
+            "{counts:?}"
+        );
+    }
+}
+
+#[test]
+fn mmc3_tests() {
+    let Some(dir) = blargg::test_dir("CHUCK_MMC3_TEST_DIR") else {
+        return;
+    };
+
+    let dir = Path::new(&dir);
+    let mut failures = Vec::new();
+
+    for (name, mmc3a) in ROMS {
+        let path = dir.join(name);
+        let mut rom = fs::read(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+        if mmc3a {
+            as_mmc3a(&mut rom);
+        }
+
+        if let Err(msg) = blargg::run(&rom) {
+            failures.push(format!("{name}: {msg}"));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
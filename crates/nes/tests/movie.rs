@@ -0,0 +1,253 @@
+//! The playback of movies, which must be deterministic: replaying a movie on
+//! a new console must produce the exact same frames as while it was recorded.
+//!
+//! The program scrolls the screen by the buttons of the first controller,
+//! which it reads in its NMI handler, so every frame depends on the input of
+//! all previous frames.
+
+mod common;
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use chuck_input::ButtonState;
+use chuck_nes::determinism::{DeterminismConfig, RamInit};
+use chuck_nes::movie::{Change, Commands, Input, Movie, Timing};
+use chuck_nes::Nes;
+
+/// The program.
+const PROGRAM: &str = "
+reset:
+    SEI
+    CLD
+    LDX #$FF
+    TXS
+    JSR init_ppu
+
+    ; Enable the NMI and rendering, then loop forever.
+    LDA #$80
+    STA $2000
+    LDA #$1E
+    STA $2001
+loop:
+    JMP loop
+
+; Read the controller and scroll by its buttons.
+nmi:
+    PHA
+    LDA #$01
+    STA $4016
+    LDA #$00
+    STA $4016
+    LDX #$08
+read:
+    LDA $4016
+    LSR A
+    ROL $01
+    DEX
+    BNE read
+    LDA $00
+    CLC
+    ADC $01
+    STA $00
+    STA $2005
+    STA $2005
+    PLA
+irq:
+    RTI
+";
+
+/// A program that reads the first controller twice in its NMI handler, into
+/// `$01` and `$02`.
+const TWICE: &str = "
+reset:
+    JSR init_ppu
+    LDA #$80
+    STA $2000
+loop:
+    JMP loop
+
+nmi:
+    LDX #$00
+poll:
+    LDA #$01
+    STA $4016
+    LDA #$00
+    STA $4016
+    LDY #$08
+read:
+    LDA $4016
+    LSR A
+    ROL $01,X
+    DEY
+    BNE read
+    INX
+    CPX #$02
+    BNE poll
+irq:
+    RTI
+";
+
+/// The number of frames of the movie.
+const FRAMES: usize = 200;
+
+/// Record a movie with pseudo-random buttons from a power-up with the given
+/// configuration, pressing the reset button and power cycling the console
+/// once, and return it with the hash of its frames.
+fn record(determinism: DeterminismConfig) -> (Movie, u64) {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    nes.set_determinism(determinism);
+    nes.power_cycle();
+    let mut movie = Movie::new();
+    movie.determinism = determinism;
+    let mut hasher = DefaultHasher::new();
+    let mut seed = 1u32;
+
+    for frame in 0..FRAMES {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+
+        let mut input = Input::default();
+        input.buttons[0] = ButtonState::from_bits_retain(seed.to_le_bytes()[2]);
+        input.commands.set(Commands::RESET, frame == 60);
+        input.commands.set(Commands::POWER, frame == 120);
+
+        input.apply(&mut nes);
+        movie.push(input);
+        nes.run_frame().pixels.hash(&mut hasher);
+    }
+
+    (movie, hasher.finish())
+}
+
+/// Play a movie back on a new console, returning the hash of its frames.
+fn play(movie: &Movie) -> u64 {
+    let mut nes = Nes::with_region(Box::new(common::cartridge(PROGRAM)), movie.region());
+    *nes.input_mut() = movie.ports();
+    nes.set_determinism(movie.determinism);
+    nes.power_cycle();
+    let mut hasher = DefaultHasher::new();
+
+    for input in movie.inputs() {
+        input.apply(&mut nes);
+        nes.run_frame().pixels.hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+#[test]
+fn replay_deterministically() {
+    let (movie, recorded) = record(DeterminismConfig::default());
+
+    let mut file = Vec::new();
+    movie.write_fm2(&mut file).unwrap();
+    let parsed = Movie::read_fm2(&mut file.as_slice()).unwrap();
+    assert_eq!(parsed, movie);
+    assert_eq!(parsed.inputs().len(), FRAMES);
+
+    assert_eq!(play(&parsed), recorded);
+    assert_eq!(play(&parsed), recorded);
+
+    // The frames depend on the input.
+    let mut altered = Movie::new();
+    for (frame, &input) in movie.inputs().iter().enumerate() {
+        let mut input = input;
+        if frame == FRAMES / 2 {
+            input.buttons[0].toggle(ButtonState::RIGHT);
+        }
+        altered.push(input);
+    }
+    assert_ne!(play(&altered), recorded);
+}
+
+#[test]
+fn replay_power_up_state() {
+    let determinism = DeterminismConfig {
+        ram: RamInit::Random(1234),
+        alignment: 9,
+        frame_counter: 5000,
+    };
+    let (movie, recorded) = record(determinism);
+
+    let mut file = Vec::new();
+    movie.write_fm2(&mut file).unwrap();
+    let text = String::from_utf8(file.clone()).unwrap();
+    assert!(text.contains("ramInit random:1234\nalignment 9\nframeCounter 5000\n"));
+
+    let parsed = Movie::read_fm2(&mut file.as_slice()).unwrap();
+    assert_eq!(parsed.determinism, determinism);
+    assert_eq!(play(&parsed), recorded);
+
+    // The program scrolls by the random byte at `$0000`.
+    let mut cleared = parsed;
+    cleared.determinism = DeterminismConfig::default();
+    assert_ne!(play(&cleared), recorded);
+}
+
+#[test]
+fn read_fceux_movies() {
+    let file = "version 3\nemuVersion 22020\nrerecordCount 12\npalFlag 0\n\
+                romFilename game\nromChecksum base64:ZDlnWKO9y1ozWkCAFBNbmg==\n\
+                guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B\nfourscore 0\n\
+                port0 1\nport1 0\nport2 0\ncomment author Someone\n\
+                |0|........|||\n|0|....T...|||\n|1|R......A|||\n";
+    let movie = Movie::read_fm2(&mut file.as_bytes()).unwrap();
+
+    assert_eq!(movie.rerecord_count, 12);
+    assert_eq!(movie.controllers, [true, false]);
+    assert_eq!(movie.comments, ["author Someone"]);
+
+    let inputs = movie.inputs();
+    assert_eq!(inputs.len(), 3);
+    assert_eq!(inputs[1].buttons[0], ButtonState::START);
+    assert_eq!(inputs[2].commands, Commands::RESET);
+    assert_eq!(inputs[2].buttons[0], ButtonState::RIGHT | ButtonState::A);
+
+    let mut written = Vec::new();
+    movie.write_fm2(&mut written).unwrap();
+    assert!(String::from_utf8(written)
+        .unwrap()
+        .ends_with("|1|R......A|||\n"));
+}
+
+#[test]
+fn change_buttons_within_frames() {
+    let mut movie = Movie::new();
+    for _ in 0..10 {
+        movie.push(Input::default());
+    }
+    let change = |at, buttons| Change {
+        at,
+        buttons: [
+            buttons,
+            ButtonState::empty(),
+            ButtonState::empty(),
+            ButtonState::empty(),
+        ],
+    };
+    movie.push_change(8, change(Timing::Strobe(1), ButtonState::A));
+    movie.push_change(9, change(Timing::Cycle(0), ButtonState::B));
+    movie.push_change(9, change(Timing::Strobe(1), ButtonState::START));
+
+    let mut file = Vec::new();
+    movie.write_fm2(&mut file).unwrap();
+    let text = String::from_utf8(file.clone()).unwrap();
+    assert!(text.contains("subframe 8 strobe 1 |.......A|........|\n"));
+    assert!(text.contains("subframe 9 cycle 0 |......B.|........|\n"));
+    let parsed = Movie::read_fm2(&mut file.as_slice()).unwrap();
+    assert_eq!(parsed, movie);
+
+    let mut nes = Nes::new(Box::new(common::cartridge(TWICE)));
+    let mut polls = Vec::new();
+    for frame in 0..parsed.inputs().len() {
+        parsed.apply(frame, &mut nes);
+        nes.run_frame();
+        polls.push([nes.peek(0x01), nes.peek(0x02)]);
+    }
+
+    // The second poll of a frame latches the change at the second strobe,
+    // and the program shifts in the buttons from A to Right.
+    let read = |buttons: ButtonState| buttons.bits().reverse_bits();
+    assert_eq!(polls[7], [0, 0]);
+    assert_eq!(polls[8], [0, read(ButtonState::A)]);
+    assert_eq!(polls[9], [read(ButtonState::B), read(ButtonState::START)]);
+}
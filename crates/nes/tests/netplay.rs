@@ -0,0 +1,256 @@
+//! Netplay sessions of two consoles connected by a laggy network that loses
+//! messages, which must still end up in the same state as a console that got
+//! the input of both players right away.
+//!
+//! The program scrolls the screen horizontally by the buttons of the first
+//! controller, and vertically by the buttons of the second, which it reads in
+//! its NMI handler, so every frame depends on the input of all previous
+//! frames.
+
+mod common;
+
+use chuck_input::{ButtonState, Controller, Port};
+use chuck_nes::netplay::{Mode, Session};
+use chuck_nes::Nes;
+
+/// The program.
+const PROGRAM: &str = "
+reset:
+    SEI
+    CLD
+    LDX #$FF
+    TXS
+    JSR init_ppu
+
+    ; Enable the NMI and rendering, then loop forever.
+    LDA #$80
+    STA $2000
+    LDA #$1E
+    STA $2001
+loop:
+    JMP loop
+
+; Read both controllers and scroll by their buttons.
+nmi:
+    PHA
+    LDA #$01
+    STA $4016
+    LDA #$00
+    STA $4016
+    LDX #$08
+read:
+    LDA $4016
+    LSR A
+    ROL $01
+    LDA $4017
+    LSR A
+    ROL $02
+    DEX
+    BNE read
+    LDA $00
+    CLC
+    ADC $01
+    STA $00
+    STA $2005
+    LDA $03
+    CLC
+    ADC $02
+    STA $03
+    STA $2005
+    PLA
+irq:
+    RTI
+";
+
+/// The number of frames the local input is delayed by.
+const DELAY: u32 = 2;
+
+/// The number of frames a message takes to arrive at the other player.
+const LATENCY: u32 = 5;
+
+/// Every how many messages one is lost.
+const LOSS: usize = 7;
+
+/// Create a powered-on console with the program.
+fn console() -> Nes {
+    Nes::new(Box::new(common::cartridge(PROGRAM)))
+}
+
+/// Return the save state of a console.
+fn state(nes: &Nes) -> Vec<u8> {
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    state
+}
+
+/// A player with their console.
+struct Player {
+    nes: Nes,
+    session: Session,
+    /// The buttons the player pressed on the frames run, which apply `DELAY`
+    /// frames later.
+    inputs: Vec<ButtonState>,
+    /// The state of the generator of the buttons.
+    seed: u32,
+}
+
+impl Player {
+    fn new(port: Port, mode: Mode) -> Self {
+        Self {
+            nes: console(),
+            session: Session::with_mode(port, DELAY, mode),
+            inputs: Vec::new(),
+            seed: match port {
+                Port::One => 1,
+                Port::Two => 2,
+            },
+        }
+    }
+
+    /// Advance with pseudo-random buttons, which change every few frames.
+    fn advance(&mut self) {
+        let frame = self.session.frame();
+        let buttons = ButtonState::from_bits_retain(self.seed.to_le_bytes()[2]);
+
+        if self.session.advance(&mut self.nes, buttons).is_some() {
+            self.inputs.push(buttons);
+
+            if frame.is_multiple_of(4) {
+                self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            }
+        }
+    }
+
+    /// Return the buttons of the player for a frame.
+    fn buttons(&self, frame: u32) -> ButtonState {
+        frame
+            .checked_sub(DELAY)
+            .map_or(ButtonState::empty(), |frame| {
+                self.inputs[usize::try_from(frame).unwrap()]
+            })
+    }
+}
+
+/// A network between the two players.
+#[derive(Default)]
+struct Network {
+    /// The messages in flight: the tick they arrive, the receiving player and
+    /// the message.
+    messages: Vec<(u32, usize, Vec<u8>)>,
+    /// The number of messages sent.
+    sent: usize,
+}
+
+impl Network {
+    /// Send the messages of both players, which arrive after the given
+    /// number of ticks, unless they're lost.
+    fn send(&mut self, players: &mut [Player; 2], tick: u32, latency: u32) {
+        for (from, player) in players.iter_mut().enumerate() {
+            for message in player.session.messages() {
+                self.sent += 1;
+                if !self.sent.is_multiple_of(LOSS) {
+                    self.messages.push((tick + latency, 1 - from, message));
+                }
+            }
+        }
+    }
+
+    /// Deliver the messages that arrived by the given tick.
+    fn deliver(&mut self, players: &mut [Player; 2], tick: u32) {
+        self.messages.retain(|(arrival, to, message)| {
+            let arrived = *arrival <= tick;
+            if arrived {
+                players[*to].session.receive(message).unwrap();
+            }
+            !arrived
+        });
+    }
+}
+
+/// Run a session with a laggy network that loses messages, then deliver all
+/// messages and check that both consoles end up in the same state as a
+/// console that got the input right away, returning the number of frames run.
+fn converge(mode: Mode) -> u32 {
+    let mut players = [Player::new(Port::One, mode), Player::new(Port::Two, mode)];
+    let mut network = Network::default();
+
+    for tick in 0..60 {
+        for player in &mut players {
+            player.advance();
+        }
+
+        network.send(&mut players, tick, LATENCY);
+        network.deliver(&mut players, tick);
+    }
+
+    // Let all input arrive, and run the console that stalled the most up to
+    // the other one.
+    network.send(&mut players, 0, 0);
+    network.deliver(&mut players, u32::MAX);
+    while players[0].session.frame() != players[1].session.frame() {
+        let behind = usize::from(players[1].session.frame() < players[0].session.frame());
+        players[behind].advance();
+    }
+
+    // Roll back and run one more frame with the input of both players known.
+    network.send(&mut players, 0, 0);
+    network.deliver(&mut players, u32::MAX);
+    for player in &mut players {
+        player.advance();
+    }
+
+    let frames = players[0].session.frame();
+    assert_eq!(players[1].session.frame(), frames);
+
+    let mut expected = console();
+    for frame in 0..frames {
+        for (port, player) in [Port::One, Port::Two].into_iter().zip(&players) {
+            let controller = expected.input_mut().device_mut::<Controller>(port);
+            controller.unwrap().set_buttons(player.buttons(frame));
+        }
+        expected.run_frame();
+    }
+
+    for player in &players {
+        assert_eq!(player.session.desync(), None);
+        assert!(state(&player.nes) == state(&expected));
+    }
+
+    frames
+}
+
+#[test]
+fn converge_despite_lag_and_loss() {
+    let frames = converge(Mode::Rollback);
+    assert!(frames > 50, "{frames} frames run");
+}
+
+#[test]
+fn converge_in_lockstep() {
+    // The consoles wait for the input delayed by less than the latency.
+    let frames = converge(Mode::Lockstep);
+    assert!((10..40).contains(&frames), "{frames} frames run");
+}
+
+#[test]
+fn detect_desyncs() {
+    let mut players = [
+        Player::new(Port::One, Mode::Rollback),
+        Player::new(Port::Two, Mode::Rollback),
+    ];
+    players[1].nes.run_frame();
+    let mut network = Network::default();
+
+    for tick in 0..3 {
+        for player in &mut players {
+            player.advance();
+        }
+
+        network.send(&mut players, tick, 0);
+        network.deliver(&mut players, tick);
+    }
+
+    for player in &players {
+        assert_eq!(player.session.desync(), Some(0));
+    }
+}
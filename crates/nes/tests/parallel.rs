@@ -0,0 +1,122 @@
+//! The consoles of a parallel runner, which must run exactly like the same
+//! consoles on a single thread.
+
+mod common;
+
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+
+use chuck_input::ButtonState;
+use chuck_nes::movie::Input;
+use chuck_nes::parallel::ParallelRunner;
+use chuck_nes::Nes;
+
+/// The number of consoles.
+const CONSOLES: usize = 12;
+
+/// The number of frames run.
+const FRAMES: usize = 8;
+
+/// A program that reads the buttons of the first controller into the RAM at
+/// `$0000` in a loop, the first button (A) in the highest bit.
+const PROGRAM: &str = "
+reset:
+    LDA #$01
+    STA $4016
+    LDA #$00
+    STA $4016
+    LDX #$08
+read:
+    LDA $4016
+    LSR A
+    ROL $01
+    DEX
+    BNE read
+    LDA $01
+    STA $00
+    JMP reset
+nmi:
+irq:
+    RTI
+";
+
+/// Create a console with the program.
+fn console() -> Nes {
+    Nes::new(Box::new(common::cartridge(PROGRAM)))
+}
+
+/// Return the input of a console in a frame.
+fn input(index: usize, frame: usize) -> Input {
+    let mut input = Input::default();
+    input.buttons[0] = ButtonState::from_bits_truncate((index * 31 + frame * 7).to_le_bytes()[0]);
+    input
+}
+
+#[test]
+fn isolated_consoles() {
+    let mut runner = ParallelRunner::replicate(&console(), CONSOLES);
+    runner.set_threads(NonZeroUsize::new(4).unwrap());
+
+    for frame in 0..FRAMES {
+        let inputs: Vec<_> = (0..CONSOLES).map(|index| input(index, frame)).collect();
+        let observations = runner.run_frame(&inputs, |index, nes| (index, nes.ram()[0]));
+
+        for (index, &(observed, buttons)) in observations.iter().enumerate() {
+            assert_eq!(observed, index);
+            let pressed = inputs[index].buttons[0].bits();
+            assert_eq!(buttons, pressed.reverse_bits(), "console {index}");
+        }
+    }
+
+    // Every console runs exactly like it would on its own.
+    for (index, nes) in runner.consoles().iter().enumerate() {
+        let mut single = console();
+        for frame in 0..FRAMES {
+            input(index, frame).apply(&mut single);
+            single.run_frame();
+        }
+
+        assert_eq!(nes.ram(), single.ram(), "console {index}");
+        assert_eq!(nes.cpu().cycles, single.cpu().cycles, "console {index}");
+    }
+}
+
+#[test]
+fn panic_keeps_consoles() {
+    let mut runner = ParallelRunner::replicate(&console(), CONSOLES);
+    runner.set_threads(NonZeroUsize::new(3).unwrap());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        runner.map(|index, _| assert_ne!(index, 5, "console 5"));
+    }));
+    assert!(result.is_err());
+
+    // The consoles are kept in their order, and the threads keep running.
+    assert_eq!(runner.len(), CONSOLES);
+    let indices = runner.map(|index, nes| {
+        nes.run_frame();
+        index
+    });
+    assert_eq!(indices, (0..CONSOLES).collect::<Vec<_>>());
+}
+
+#[test]
+fn scoped_threads() {
+    let mut pooled = ParallelRunner::replicate(&console(), CONSOLES);
+    let mut scoped = pooled.clone();
+    for runner in [&mut pooled, &mut scoped] {
+        runner.set_threads(NonZeroUsize::new(4).unwrap());
+    }
+
+    // The scoped threads borrow the state of the caller.
+    let masks: Vec<u8> = (0..CONSOLES).map(|index| !(1 << (index % 8))).collect();
+    for frame in 0..FRAMES {
+        let inputs: Vec<_> = (0..CONSOLES).map(|index| input(index, frame)).collect();
+        let expected = pooled.run_frame(&inputs, |_, nes| nes.ram()[0]);
+        let observed = scoped.run_frame_scoped(&inputs, |index, nes| nes.ram()[0] & masks[index]);
+
+        for (index, (observed, expected)) in observed.into_iter().zip(expected).enumerate() {
+            assert_eq!(observed, expected & masks[index], "console {index}");
+        }
+    }
+}
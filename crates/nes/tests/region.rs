@@ -0,0 +1,57 @@
+//! The timing of the consoles of the regions: the length of their frames and
+//! of their vertical blanking intervals, in CPU cycles.
+
+mod common;
+
+use chuck_nes::{Nes, Region};
+
+/// The number of frames measured.
+const FRAMES: usize = 30;
+
+/// A program that loops forever without enabling rendering.
+const PROGRAM: &str = "
+reset:
+nmi:
+irq:
+    JMP reset
+";
+
+/// Create a console of the given region with the program.
+fn console(region: Region) -> Nes {
+    Nes::with_region(Box::new(common::cartridge(PROGRAM)), region)
+}
+
+#[test]
+fn frame_timing() {
+    // The number of scanlines of a frame and in vertical blanking, and the
+    // number of PPU dots per 5 CPU cycles.
+    for (region, scanlines, vblank, dots) in [
+        (Region::Ntsc, 262, 20, 15),
+        (Region::Pal, 312, 70, 16),
+        (Region::Dendy, 312, 20, 15),
+    ] {
+        let mut nes = console(region);
+        nes.run_frame();
+
+        let mut cycles = 0;
+        for _ in 0..FRAMES {
+            cycles += nes.run_frame().samples.len();
+        }
+
+        assert_eq!(cycles * dots, FRAMES * scanlines * 341 * 5, "{region:?}");
+
+        // The vertical blanking flag is set from the second dot of its first
+        // scanline to the second dot of the pre-render scanline.
+        let mut blanking: usize = 0;
+        let frame = nes.ppu().frame();
+        while nes.ppu().frame() == frame {
+            nes.step();
+            if nes.ppu().peek(0x2002) & 0x80 != 0 {
+                blanking += 1;
+            }
+        }
+
+        let expected = vblank * 341 * 5 / dots;
+        assert!(blanking.abs_diff(expected) <= 1, "{region:?}: {blanking}");
+    }
+}
@@ -0,0 +1,242 @@
+//! The save states of the whole console, which must resume on the exact same
+//! cycle: the frames after a restored state must be identical to those of the
+//! console that saved it.
+//!
+//! The program keeps every part of the console busy: it renders with a
+//! changing scroll, copies the sprites with an OAM DMA and strobes the
+//! controllers in its NMI handler, and plays a pulse channel and a looping
+//! DMC sample, whose fetches halt the CPU.
+//!
+//! The rewind buffer, which is built on save states, must restore the same
+//! consoles.
+
+mod common;
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+
+use chuck_input::{ButtonState, Controller, Port};
+use chuck_nes::determinism::{DeterminismConfig, RamInit};
+use chuck_nes::rewind::Rewind;
+use chuck_nes::Nes;
+
+/// The program.
+const PROGRAM: &str = "
+reset:
+    SEI
+    CLD
+    LDX #$FF
+    TXS
+    JSR init_ppu
+
+    ; Play the pulse channel and a looping sample.
+    LDA #$BF
+    STA $4000
+    LDA #$FD
+    STA $4002
+    LDA #$08
+    STA $4003
+    LDA #$4F
+    STA $4010
+    LDA #$FF
+    STA $4013
+    LDA #$11
+    STA $4015
+
+    ; Enable the NMI and rendering.
+    LDA #$80
+    STA $2000
+    LDA #$1E
+    STA $2001
+
+    ; Scroll, move the sprites and read the controller forever.
+loop:
+    INC $00
+    LDA $00
+    STA $2005
+    STA $2005
+    LDX $00
+    STA $0200,X
+    LDA $4016
+    STA $02
+    JMP loop
+
+; Copy the sprites and strobe the controllers.
+nmi:
+    PHA
+    LDA #$02
+    STA $4014
+    LDA #$01
+    STA $4016
+    LDA #$00
+    STA $4016
+    INC $01
+    PLA
+irq:
+    RTI
+";
+
+/// The number of frames compared after a state is restored.
+const FRAMES: usize = 4;
+
+/// Create a console with the program, and a controller with A and Right
+/// pressed.
+fn console() -> Nes {
+    let mut nes = Nes::new(Box::new(common::cartridge(PROGRAM)));
+    nes.input_mut()
+        .device_mut::<Controller>(Port::One)
+        .unwrap()
+        .set_buttons(ButtonState::A | ButtonState::RIGHT);
+    nes
+}
+
+/// Return a hash of the next frames, and of the RAM after them.
+fn hash_frames(nes: &mut Nes) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    for _ in 0..FRAMES {
+        let frame = nes.run_frame();
+        frame.pixels.hash(&mut hasher);
+
+        for sample in frame.samples {
+            sample.to_bits().hash(&mut hasher);
+        }
+    }
+
+    nes.ram().hash(&mut hasher);
+    nes.cpu().regs.pc.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn resume_exactly() {
+    let mut nes = console();
+    for _ in 0..10 {
+        nes.run_frame();
+    }
+
+    // Save at several points of a frame, usually in the middle of an
+    // instruction, a scanline or a DMA.
+    for cycles in [0, 1, 2, 513, 2_273, 7_919, 15_000, 27_000] {
+        for _ in 0..cycles {
+            nes.step();
+        }
+
+        let mut state = Vec::new();
+        nes.save_state(&mut state).unwrap();
+
+        // Restore into a console in another state.
+        let mut restored = console();
+        restored.run_frame();
+        restored.load_state(&mut state.as_slice()).unwrap();
+
+        let mut expected = nes.clone();
+        assert_eq!(
+            hash_frames(&mut restored),
+            hash_frames(&mut expected),
+            "restored after {cycles} cycles"
+        );
+    }
+}
+
+#[test]
+fn restore_power_up_state() {
+    let mut nes = console();
+    nes.set_determinism(DeterminismConfig {
+        ram: RamInit::Pattern,
+        alignment: 7,
+        frame_counter: 123,
+    });
+    nes.power_cycle();
+    nes.run_frame();
+
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    let mut restored = console();
+    restored.load_state(&mut state.as_slice()).unwrap();
+    assert_eq!(restored.determinism(), nes.determinism());
+
+    // The next power-up has the configuration of the state.
+    nes.power_cycle();
+    restored.power_cycle();
+    assert_eq!(hash_frames(&mut restored), hash_frames(&mut nes));
+}
+
+#[test]
+fn compress_states() {
+    let mut nes = console();
+    for _ in 0..10 {
+        nes.run_frame();
+    }
+
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    let mut compressed = Vec::new();
+    nes.save_state_compressed(&mut compressed).unwrap();
+    assert!(
+        compressed.len() * 4 < state.len(),
+        "{} bytes",
+        compressed.len()
+    );
+
+    let mut restored = console();
+    restored.load_state(&mut compressed.as_slice()).unwrap();
+    assert_eq!(hash_frames(&mut restored), hash_frames(&mut nes));
+}
+
+#[test]
+fn reject_malformed_states() {
+    let mut nes = console();
+    nes.run_frame();
+
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+
+    let mut magic = state.clone();
+    magic[0] ^= 0xff;
+    let err = nes.load_state(&mut magic.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut version = state.clone();
+    version[4] = 0xff;
+    let err = nes.load_state(&mut version.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // A truncated state leaves the console untouched.
+    let mut truncated = nes.clone();
+    let err = truncated
+        .load_state(&mut &state[..state.len() - 1])
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(hash_frames(&mut truncated), hash_frames(&mut nes));
+}
+
+#[test]
+fn rewind_in_reverse() {
+    let mut nes = console();
+    let mut rewind = Rewind::new(3, usize::MAX);
+    let mut captured = Vec::new();
+
+    for _ in 0..30 {
+        nes.run_frame();
+        if rewind.capture(&nes) {
+            captured.push(nes.clone());
+        }
+    }
+
+    let mut state = Vec::new();
+    nes.save_state(&mut state).unwrap();
+    assert_eq!(captured.len(), 10);
+    assert_eq!(rewind.len(), 10);
+    // Even though most pixels change while scrolling, the deltas are smaller
+    // than the save states.
+    assert!(rewind.size() < captured.len() * state.len());
+
+    while let Some(mut expected) = captured.pop() {
+        let mut restored = rewind.pop().unwrap();
+        assert_eq!(hash_frames(&mut restored), hash_frames(&mut expected));
+    }
+
+    assert!(rewind.is_empty());
+    assert!(!rewind.hold(&mut nes));
+}
@@ -0,0 +1,84 @@
+//! The metadata of the slot files, whose malformed thumbnails must be told
+//! apart from the errors of the reader.
+
+use std::io::{self, Read};
+
+use chuck_nes::mapper::{Mirroring, Nrom};
+use chuck_nes::slot::Metadata;
+use chuck_nes::Nes;
+
+/// The offset of the length of the compressed thumbnail in a slot file.
+const LENGTH: usize = 29;
+
+/// A reader which fails with an error of the given kind after the given
+/// bytes.
+struct Failing<'a> {
+    /// The bytes read before the error.
+    bytes: &'a [u8],
+    /// The kind of the error.
+    kind: io::ErrorKind,
+}
+
+impl Read for Failing<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.bytes.is_empty() {
+            return Err(self.kind.into());
+        }
+        self.bytes.read(buf)
+    }
+}
+
+/// Return a slot file of a console which ran a frame.
+fn slot() -> Vec<u8> {
+    let cartridge = Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical);
+    let mut nes = Nes::new(Box::new(cartridge));
+    nes.run_frame();
+
+    let mut slot = Vec::new();
+    nes.save_slot(&Metadata::new(&nes, 42), &mut slot).unwrap();
+    slot
+}
+
+/// Return the length of the compressed thumbnail of a slot file.
+fn thumbnail_length(slot: &[u8]) -> usize {
+    let length = slot[LENGTH..LENGTH + 4].try_into().unwrap();
+    usize::try_from(u32::from_le_bytes(length)).unwrap()
+}
+
+#[test]
+fn reader_errors() {
+    let slot = slot();
+    // The middle of the compressed thumbnail.
+    let middle = LENGTH + 4 + thumbnail_length(&slot) / 2;
+
+    // The errors of the reader within the thumbnail are returned unchanged.
+    for kind in [io::ErrorKind::ConnectionReset, io::ErrorKind::PermissionDenied] {
+        let mut reader = Failing {
+            bytes: &slot[..middle],
+            kind,
+        };
+        assert_eq!(Metadata::read(&mut reader).unwrap_err().kind(), kind);
+    }
+
+    // Including the end of a truncated file.
+    let err = Metadata::read(&mut &slot[..middle]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn malformed_thumbnail() {
+    let slot = slot();
+    let start = LENGTH + 4;
+
+    // A corrupt compressed thumbnail.
+    let mut corrupt = slot.clone();
+    corrupt[start..start + 4].fill(0xff);
+    let err = Metadata::read(&mut corrupt.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // A compressed thumbnail which ends before the thumbnail.
+    let mut short = slot;
+    short[LENGTH..start].copy_from_slice(&4_u32.to_le_bytes());
+    let err = Metadata::read(&mut short.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,91 @@
+//! The I/O latch of the PPU, i.e. the capacitance of its register data bus.
+//!
+//! Reading a write-only register returns the value last driven onto the bus,
+//! but the bits decay to 0 if they aren't driven for a while (about 600 ms).
+//! Some readable registers only drive part of the bus, `PPUSTATUS` its upper 3
+//! bits and the palette RAM its lower 6 bits, so the other bits are read from
+//! the latch and continue to decay.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/Open_bus_behavior#PPU_open_bus>
+//! - <https://www.nesdev.org/wiki/PPU_registers#Ports>
+
+use std::io::{self, Read, Write};
+
+/// The number of frames after which an undriven bit decays, about 600 ms.
+const DECAY_FRAMES: u64 = 36;
+
+/// The I/O latch.
+#[derive(Debug, Clone, Default)]
+pub struct Latch {
+    /// The value of the latch.
+    value: u8,
+    /// The frames in which the bits were driven the last time.
+    driven: [u64; 8],
+}
+
+impl Latch {
+    /// Return the value of the latch.
+    pub const fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Drive the bits of the given mask onto the bus in the given frame.
+    pub fn drive(&mut self, data: u8, mask: u8, frame: u64) {
+        self.value = (self.value & !mask) | (data & mask);
+
+        for (bit, driven) in self.driven.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *driven = frame;
+            }
+        }
+    }
+
+    /// Let the bits decay that weren't driven for a while, as of the given
+    /// frame.
+    pub fn decay(&mut self, frame: u64) {
+        for (bit, &driven) in self.driven.iter().enumerate() {
+            if frame - driven >= DECAY_FRAMES {
+                self.value &= !(1 << bit);
+            }
+        }
+    }
+
+    /// Save the state of the latch.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&[self.value])?;
+
+        for driven in self.driven {
+            writer.write_all(&driven.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Load the state of the latch saved by [`Latch::save`], as of the given
+    /// frame.
+    pub fn load(reader: &mut dyn Read, frame: u64) -> io::Result<Self> {
+        let mut value = [0; 1];
+        reader.read_exact(&mut value)?;
+
+        let mut driven = [0; 8];
+        for driven in &mut driven {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            *driven = u64::from_le_bytes(bytes);
+
+            if *driven > frame {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid latch frame in ppu state",
+                ));
+            }
+        }
+
+        Ok(Self {
+            value: value[0],
+            driven,
+        })
+    }
+}
@@ -0,0 +1,911 @@
+//! A dot-accurate implementation of the NES's 2C02 Picture Processing Unit.
+//!
+//! # Modularity
+//!
+//! Like Chuck's CPU core, this PPU is completely decoupled from the rest of
+//! the emulator. It only communicates through its pins: the CPU-facing
+//! register interface (`/CS`, `R/W`, `A0`-`A2` and `D0`-`D7` on the chip), the
+//! VRAM bus on which it fetches from the pattern tables and nametables of the
+//! cartridge (`AD0`-`AD7`, `A8`-`A13`, `ALE`, `/RD` and `/WR`), and the `/INT`
+//! output which is wired to the CPU's `NMI` pin.
+//!
+//! This allows the PPU to be tested in isolation with nothing but an array as
+//! its VRAM, exactly like the CPU, and keeps the cartridge (mapper) logic out
+//! of the PPU, where it can observe every address placed onto the VRAM bus.
+//!
+//! # Regions
+//!
+//! Besides the 2C02 of NTSC consoles, the PPU emulates the timing of the 2C07
+//! of PAL consoles and of the UA6538 of the Dendy, see [`Region`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU>
+//! - <https://www.nesdev.org/wiki/PPU_rendering>
+//! - <https://www.nesdev.org/wiki/PPU_pinout>
+//! - <https://www.nesdev.org/w/images/default/4/4f/Ppu.svg>
+
+mod latch;
+mod render;
+mod snapshot;
+mod sprite;
+pub mod viewer;
+
+use latch::Latch;
+use sprite::{Evaluation, Sprite};
+
+bitflags::bitflags! {
+    /// The control pins of a 2C02 PPU.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/PPU_pinout>
+    #[derive(Debug, Clone, Copy)]
+    pub struct Pins: u8 {
+        /// The interrupt output pin, `/INT`.
+        ///
+        /// This pin is set by the PPU while the vertical blanking flag and
+        /// the `NMI` enable of `PPUCTRL` are both set. On the NES, it is wired
+        /// to the `NMI` pin of the CPU.
+        const INT = 1 << 0;
+        /// The reset input pin, `/RST`.
+        ///
+        /// This pin can be set externally to reset the PPU. While set, the
+        /// registers are held cleared, and once disabled again, the writes to
+        /// `PPUCTRL`, `PPUMASK`, `PPUSCROLL` and `PPUADDR` are ignored until
+        /// the pre-render scanline, exactly like after power-up.
+        const RST = 1 << 1;
+    }
+}
+
+bitflags::bitflags! {
+    /// The PPU control register, `PPUCTRL` (`$2000`).
+    ///
+    /// The two nametable select bits are not part of this register, they are
+    /// written directly into the temporary VRAM address.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/PPU_registers#PPUCTRL>
+    #[derive(Debug, Clone, Copy)]
+    struct Ctrl: u8 {
+        /// Increment the VRAM address by 32 instead of 1 per `PPUDATA` access.
+        const INCREMENT = 1 << 2;
+        /// Use the pattern table at `$1000` for 8x8 sprites.
+        const SPRITE_TABLE = 1 << 3;
+        /// Use the pattern table at `$1000` for the background.
+        const BG_TABLE = 1 << 4;
+        /// Use 8x16 sprites instead of 8x8 sprites.
+        const SPRITE_SIZE = 1 << 5;
+        /// Set the `/INT` pin at the start of vertical blanking.
+        const NMI = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// The PPU mask register, `PPUMASK` (`$2001`).
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/PPU_registers#PPUMASK>
+    #[derive(Debug, Clone, Copy)]
+    struct Mask: u8 {
+        /// Show the colors in greyscale.
+        const GREYSCALE = 1 << 0;
+        /// Show the background in the leftmost 8 pixels.
+        const BG_LEFT = 1 << 1;
+        /// Show the sprites in the leftmost 8 pixels.
+        const SPRITES_LEFT = 1 << 2;
+        /// Show the background.
+        const BG = 1 << 3;
+        /// Show the sprites.
+        const SPRITES = 1 << 4;
+        /// Emphasize the red color (green on PAL and the Dendy).
+        const RED = 1 << 5;
+        /// Emphasize the green color (red on PAL and the Dendy).
+        const GREEN = 1 << 6;
+        /// Emphasize the blue color.
+        const BLUE = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// The PPU status register, `PPUSTATUS` (`$2002`).
+    ///
+    /// The lower 5 bits are not driven by the PPU, they read back the I/O
+    /// latch instead.
+    ///
+    /// # Link(s)
+    ///
+    /// - <https://www.nesdev.org/wiki/PPU_registers#PPUSTATUS>
+    #[derive(Debug, Clone, Copy)]
+    struct Status: u8 {
+        /// More than 8 sprites were found on a scanline, or rather, the buggy
+        /// sprite evaluation thinks so.
+        const OVERFLOW = 1 << 5;
+        /// An opaque pixel of sprite zero overlapped an opaque background
+        /// pixel.
+        const SPRITE_ZERO = 1 << 6;
+        /// The vertical blanking interval has started.
+        const VBLANK = 1 << 7;
+    }
+}
+
+/// The memory access state of the VRAM bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// No access, the address is only latched (`ALE`) or held on the bus.
+    Idle,
+    /// A read, the data of the address must be placed onto the data bus.
+    ///
+    /// This corresponds to the pin labeled `/RD` on a 2C02.
+    Read,
+    /// A write, the data on the data bus must be stored at the address.
+    ///
+    /// This corresponds to the pin labeled `/WR` on a 2C02.
+    Write,
+}
+
+/// The VRAM bus of a 2C02 PPU.
+///
+/// On the chip, the lower 8 bits of the address share their pins with the
+/// data bus and are latched externally, which is why every fetch takes two
+/// dots: one to place the address, one to access the data.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/wiki/PPU_pinout>
+#[derive(Debug, Clone)]
+pub struct Bus {
+    /// The 14-bit address bus.
+    ///
+    /// This corresponds to the pins labeled `AD0`-`AD7` and `A8`-`A13` on a
+    /// 2C02. The address is held on the bus between accesses, which is
+    /// observed by some mappers (e.g. through `A12`).
+    pub addr: u16,
+    /// The 8-bit data bus.
+    ///
+    /// This corresponds to the pins labeled `AD0`-`AD7` on a 2C02.
+    pub data: u8,
+    /// The memory access of the current dot.
+    pub access: Access,
+}
+
+/// The fetches of the PPU whose data arrives with the next dot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fetch {
+    /// No data is pending, or it is discarded.
+    None,
+    /// The nametable byte of the next background tile.
+    Nametable,
+    /// The attribute byte of the next background tile.
+    Attribute,
+    /// The low pattern byte of the next background tile.
+    PatternLow,
+    /// The high pattern byte of the next background tile.
+    PatternHigh,
+    /// The low pattern byte of a sprite of the next scanline.
+    SpriteLow(u8),
+    /// The high pattern byte of a sprite of the next scanline.
+    SpriteHigh(u8),
+    /// The data of a `PPUDATA` read, for the read buffer.
+    Data,
+}
+
+/// The destination of the rendered pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// The pixels are stored in the frame buffer.
+    FrameBuffer,
+    /// The pixels are discarded, see [`Ppu::set_output`].
+    Discarded,
+}
+
+/// The number of dots of a scanline.
+const DOTS: u16 = 341;
+
+/// The number of scanlines after the start of the vertical blanking interval
+/// at which the 2C07 starts refreshing its OAM.
+const OAM_REFRESH_DELAY: u16 = 24;
+
+/// The timing of the PPU, which differs between the PPUs of the regions.
+///
+/// # Link(s)
+///
+/// - <https://www.nesdev.org/wiki/Cycle_reference_chart>
+/// - <https://www.nesdev.org/wiki/Dendy>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The 2C02 of NTSC consoles and the Famicom, with 262 scanlines per
+    /// frame, 20 of them in vertical blanking. The pre-render scanline is one
+    /// dot shorter on odd frames while rendering is enabled.
+    Ntsc,
+    /// The 2C07 of PAL consoles, with 312 scanlines per frame, 70 of them in
+    /// vertical blanking. Since its OAM would decay during the long vertical
+    /// blanking, it is refreshed like during rendering from 24 scanlines after
+    /// its start, so the OAM can only be written before.
+    Pal,
+    /// The UA6538 of the Dendy, a Famicom clone with PAL video, with 312
+    /// scanlines per frame. Like on the 2C02, only the last 20 scanlines
+    /// before the pre-render scanline are in vertical blanking.
+    Dendy,
+}
+
+impl Region {
+    /// Return the number of scanlines of a frame.
+    #[must_use]
+    pub const fn scanlines(self) -> u16 {
+        match self {
+            Self::Ntsc => 262,
+            Self::Pal | Self::Dendy => 312,
+        }
+    }
+
+    /// Return the scanline on which the vertical blanking interval starts.
+    #[must_use]
+    pub const fn vblank_line(self) -> u16 {
+        match self {
+            Self::Ntsc | Self::Pal => 241,
+            Self::Dendy => 291,
+        }
+    }
+
+    /// Return the pre-render scanline, the last one of a frame, which fetches
+    /// the first tiles of the next frame.
+    #[must_use]
+    pub const fn prerender_line(self) -> u16 {
+        self.scanlines() - 1
+    }
+}
+
+/// The width of the picture, in pixels.
+pub const WIDTH: usize = 256;
+
+/// The height of the picture, in pixels.
+pub const HEIGHT: usize = 240;
+
+/// The 2C02 Picture Processing Unit (PPU) of the NES.
+///
+/// The PPU is driven one dot (PPU cycle) at a time through [`Ppu::step`]. Like
+/// the CPU's steps, every step places a memory access onto the [`Bus`], which
+/// must then be serviced by the caller before the next step. The CPU accesses
+/// the registers of the PPU between the steps, through [`Ppu::read`] and
+/// [`Ppu::write`].
+///
+/// ```
+/// # use chuck_ppu::{Access, Ppu, WIDTH};
+/// let mut ppu = Ppu::new();
+/// let mut vram = [0; 0x4000];
+///
+/// // Wait for the PPU to accept writes, then fill the palette.
+/// while ppu.frame() == 0 {
+///     ppu.step();
+/// }
+///
+/// ppu.write(0x2006, 0x3f);
+/// ppu.write(0x2006, 0x00);
+/// ppu.write(0x2007, 0x21);
+/// ppu.write(0x2006, 0x00);
+/// ppu.write(0x2006, 0x00);
+///
+/// while ppu.frame() == 1 {
+///     ppu.step();
+///
+///     match ppu.bus.access {
+///         Access::Read => ppu.bus.data = vram[usize::from(ppu.bus.addr)],
+///         Access::Write => vram[usize::from(ppu.bus.addr)] = ppu.bus.data,
+///         Access::Idle => {}
+///     }
+/// }
+///
+/// // With rendering disabled, the backdrop color is shown.
+/// assert_eq!(ppu.frame_buffer()[WIDTH * 100], 0x21);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ppu {
+    /// The control pins.
+    pub pins: Pins,
+    /// The VRAM bus.
+    pub bus: Bus,
+    /// The timing of the PPU.
+    region: Region,
+
+    /// The control register, `PPUCTRL`.
+    ctrl: Ctrl,
+    /// The mask register, `PPUMASK`.
+    mask: Mask,
+    /// The status register, `PPUSTATUS`.
+    status: Status,
+    /// A flag denoting if the registers accept writes, which they don't until
+    /// the pre-render scanline after power-up or reset.
+    ready: bool,
+    /// A flag denoting if the vertical blanking flag is not set by the next
+    /// dot, because `PPUSTATUS` was read just before.
+    suppress: bool,
+
+    /// The current VRAM address, `v`.
+    v: u16,
+    /// The temporary VRAM address, `t`, which is copied into `v`.
+    t: u16,
+    /// The fine X scroll, `x`.
+    x: u8,
+    /// The write toggle of `PPUSCROLL` and `PPUADDR`, `w`.
+    w: bool,
+    /// The read buffer of `PPUDATA`.
+    buffer: u8,
+    /// The I/O latch, i.e. the value last driven onto the register data bus.
+    latch: Latch,
+    /// The pending `PPUDATA` access, if any, as `(access, address, data)`.
+    data: Option<(Access, u16, u8)>,
+    /// The fetch whose data arrives with the next dot.
+    pending: Fetch,
+
+    /// The address of `OAMDATA` within the primary OAM, `OAMADDR`.
+    oam_addr: u8,
+    /// The primary Object Attribute Memory (OAM), with 64 sprites.
+    oam: [u8; 256],
+    /// The secondary OAM, with the 8 sprites of the next scanline.
+    secondary: [u8; 32],
+    /// The sprites found for the next scanline.
+    found: Evaluation,
+    /// The sprites of the current scanline.
+    sprites: [Sprite; 8],
+    /// The sprites found for the current scanline.
+    line: Evaluation,
+    /// The palette RAM, which is internal to the PPU.
+    palette: [u8; 32],
+
+    /// The nametable byte of the next background tile.
+    nt: u8,
+    /// The two attribute bits of the next background tile.
+    at: u8,
+    /// The low pattern byte of the next background tile.
+    pattern_low: u8,
+    /// The high pattern byte of the next background tile.
+    pattern_high: u8,
+    /// The shift registers of the two background pattern planes.
+    bg_shift: [u16; 2],
+    /// The shift registers of the two background attribute bits.
+    at_shift: [u16; 2],
+
+    /// The current scanline, of the next dot.
+    scanline: u16,
+    /// The current dot of the scanline, the next one to be executed.
+    dot: u16,
+    /// The number of dots of the current pre-render scanline, which is one
+    /// less on odd frames while rendering is enabled.
+    prerender_dots: u16,
+    /// The number of frames since power-up.
+    frame: u64,
+    /// The number of dots since power-up at the start of the current frame,
+    /// modulo 3, see [`Ppu::frame_phase`].
+    phase: u8,
+    /// The rendered colors, see [`Ppu::frame_buffer`].
+    pixels: Box<[u16]>,
+    /// The number of pictures completed in the frame buffer, see
+    /// [`Ppu::generation`].
+    generation: u64,
+    /// The destination of the rendered pixels.
+    output: Output,
+}
+
+impl Ppu {
+    /// Create a new NTSC PPU (2C02) in its power-up state.
+    ///
+    /// The writes to `PPUCTRL`, `PPUMASK`, `PPUSCROLL` and `PPUADDR` are
+    /// ignored until the pre-render scanline of the first frame, like on the
+    /// real hardware.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_region(Region::Ntsc)
+    }
+
+    /// Create a new PPU with the timing of the given region in its power-up
+    /// state, see [`Ppu::new`].
+    #[must_use]
+    pub fn with_region(region: Region) -> Self {
+        Self {
+            pins: Pins::empty(),
+            bus: Bus {
+                addr: 0,
+                data: 0,
+                access: Access::Idle,
+            },
+            region,
+            ctrl: Ctrl::empty(),
+            mask: Mask::empty(),
+            status: Status::empty(),
+            ready: false,
+            suppress: false,
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            buffer: 0,
+            latch: Latch::default(),
+            data: None,
+            pending: Fetch::None,
+            oam_addr: 0,
+            oam: [0; 256],
+            secondary: [0xff; 32],
+            found: Evaluation::default(),
+            sprites: [Sprite::default(); 8],
+            line: Evaluation::default(),
+            palette: [0; 32],
+            nt: 0,
+            at: 0,
+            pattern_low: 0,
+            pattern_high: 0,
+            bg_shift: [0; 2],
+            at_shift: [0; 2],
+            scanline: 0,
+            dot: 0,
+            prerender_dots: DOTS,
+            frame: 0,
+            phase: 0,
+            pixels: vec![0; WIDTH * HEIGHT].into_boxed_slice(),
+            generation: 0,
+            output: Output::FrameBuffer,
+        }
+    }
+
+    /// Power cycle the PPU, returning it to the power-up state of its region,
+    /// see [`Ppu::with_region`].
+    ///
+    /// The cleared frame buffer is another picture, so unlike a new PPU, the
+    /// [`Ppu::generation`] advances instead of starting over, and never
+    /// repeats one handed out before.
+    pub fn power_cycle(&mut self) {
+        let generation = self.generation + 1;
+        *self = Self::with_region(self.region);
+        self.generation = generation;
+    }
+
+    /// Execute a single dot of the PPU.
+    ///
+    /// This completes the previous read placed onto the VRAM bus, then places
+    /// the next access. While the `/RST` pin is set, the PPU is held in reset.
+    pub fn step(&mut self) {
+        if self.pins.contains(Pins::RST) {
+            self.reset();
+        }
+
+        self.complete();
+        self.bus.access = Access::Idle;
+
+        let prerender = self.scanline == self.region.prerender_line();
+        if self.is_rendering_enabled() && (self.scanline < 240 || prerender) {
+            self.render();
+        } else if self.scanline < 240 {
+            self.render_disabled();
+        }
+
+        if self.scanline == self.region.vblank_line() && self.dot == 1 {
+            if !self.suppress {
+                self.status.insert(Status::VBLANK);
+            }
+            self.suppress = false;
+        }
+
+        if prerender && self.dot == 1 {
+            self.status = Status::empty();
+            self.ready = true;
+        }
+
+        if self.bus.access == Access::Idle && !self.is_rendering() {
+            self.access_data();
+        }
+
+        self.update_int();
+        self.advance();
+    }
+
+    /// Execute a dot outside of rendering without advancing the PPU to the
+    /// next dot, as if the dot lasted longer, e.g. for the extra scanlines of
+    /// an overclocked CPU.
+    ///
+    /// Like on every dot outside of rendering, this completes the `PPUDATA`
+    /// accesses of the CPU, but nothing else happens, e.g. no flags are set.
+    pub fn stall(&mut self) {
+        if self.pins.contains(Pins::RST) {
+            self.reset();
+        }
+
+        self.complete();
+        self.bus.access = Access::Idle;
+        if !self.is_rendering() {
+            self.access_data();
+        }
+
+        self.update_int();
+    }
+
+    /// Read one of the registers, as the CPU does at the given address.
+    ///
+    /// Only the lowest 3 bits of the address are decoded, so the registers
+    /// are mirrored every 8 bytes. The write-only registers read back the I/O
+    /// latch, whose bits decay if they aren't driven for a while.
+    pub fn read(&mut self, addr: u16) -> u8 {
+        let latch = self.latch.value();
+
+        let (data, driven) = match addr & 7 {
+            2 => {
+                let data = self.status.bits() | (latch & 0x1f);
+                self.status.remove(Status::VBLANK);
+                self.w = false;
+
+                // Reading on the dot before the flag is set, suppresses it.
+                if self.scanline == self.region.vblank_line() && self.dot == 1 {
+                    self.suppress = true;
+                }
+
+                self.update_int();
+                (data, 0xe0)
+            }
+            4 => (self.oam_data(), 0xff),
+            7 => {
+                let addr = self.v & 0x3fff;
+                let read = if addr >= 0x3f00 {
+                    // The buffer is filled with the nametable byte "under"
+                    // the palette instead.
+                    self.data = Some((Access::Read, addr & 0x2fff, 0));
+                    (self.palette_color(addr) | (latch & 0xc0), 0x3f)
+                } else {
+                    self.data = Some((Access::Read, addr, 0));
+                    (self.buffer, 0xff)
+                };
+
+                self.increment();
+                read
+            }
+            _ => (latch, 0),
+        };
+
+        self.latch.drive(data, driven, self.frame);
+        data
+    }
+
+    /// Read one of the registers without any side effects, e.g. for debuggers.
+    ///
+    /// Unlike [`Ppu::read`], a `PPUDATA` read returns the data at the VRAM
+    /// address instead of the read buffer for the palette only.
+    #[must_use]
+    pub fn peek(&self, addr: u16) -> u8 {
+        let latch = self.latch.value();
+
+        match addr & 7 {
+            2 => self.status.bits() | (latch & 0x1f),
+            4 => self.oam[usize::from(self.oam_addr)],
+            7 if self.v & 0x3fff >= 0x3f00 => self.palette_color(self.v) | (latch & 0xc0),
+            7 => self.buffer,
+            _ => latch,
+        }
+    }
+
+    /// Write one of the registers, as the CPU does at the given address.
+    ///
+    /// Only the lowest 3 bits of the address are decoded, so the registers
+    /// are mirrored every 8 bytes.
+    pub fn write(&mut self, addr: u16, data: u8) {
+        self.latch.drive(data, 0xff, self.frame);
+
+        match addr & 7 {
+            0 if self.ready => {
+                self.ctrl = Ctrl::from_bits_truncate(data);
+                self.t = (self.t & !0x0c00) | (u16::from(data & 3) << 10);
+                self.update_int();
+            }
+            1 if self.ready => self.mask = Mask::from_bits_retain(data),
+            3 => self.oam_addr = data,
+            4 if self.is_rendering() || self.is_refreshing_oam() => {
+                // The write is ignored, but the high 6 bits are incremented.
+                self.oam_addr = self.oam_addr.wrapping_add(4);
+            }
+            4 => {
+                self.oam[usize::from(self.oam_addr)] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            5 if self.ready => {
+                if self.w {
+                    self.t = (self.t & !0x73e0)
+                        | (u16::from(data & 0x07) << 12)
+                        | (u16::from(data & 0xf8) << 2);
+                } else {
+                    self.t = (self.t & !0x001f) | u16::from(data >> 3);
+                    self.x = data & 0x07;
+                }
+                self.w = !self.w;
+            }
+            6 if self.ready => {
+                if self.w {
+                    self.t = (self.t & 0xff00) | u16::from(data);
+                    self.v = self.t;
+                } else {
+                    self.t = (self.t & 0x00ff) | (u16::from(data & 0x3f) << 8);
+                }
+                self.w = !self.w;
+            }
+            7 => {
+                let addr = self.v & 0x3fff;
+                if addr >= 0x3f00 {
+                    self.palette[palette_index(addr)] = data & 0x3f;
+                } else {
+                    self.data = Some((Access::Write, addr, data));
+                }
+
+                self.increment();
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the current scanline, `0`-`239` for the visible picture, `241`
+    /// for the start of vertical blanking and `261` for the pre-render line on
+    /// NTSC, see [`Region`].
+    ///
+    /// This is the scanline of the next dot executed by [`Ppu::step`].
+    #[must_use]
+    pub const fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    /// Return the current dot of the scanline, `0`-`340`.
+    ///
+    /// This is the next dot executed by [`Ppu::step`].
+    #[must_use]
+    pub const fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// Return the timing of the PPU.
+    #[must_use]
+    pub const fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Return the number of frames since power-up, which is incremented when
+    /// the first dot of a frame is reached.
+    #[must_use]
+    pub const fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Return the phase of the color subcarrier of the NTSC video signal at
+    /// the start of the current frame, `0`-`2`.
+    ///
+    /// Every dot takes two thirds of a cycle of the color subcarrier, so the
+    /// phase is the number of dots since power-up modulo 3. Since a frame
+    /// doesn't consist of a multiple of 3 dots, the phase changes from frame
+    /// to frame, which makes the artifacts of the video signal crawl.
+    #[must_use]
+    pub const fn frame_phase(&self) -> u8 {
+        self.phase
+    }
+
+    /// Return the colors of the last rendered frame, row by row.
+    ///
+    /// Every color is an index into the NES's palette (the lower 6 bits) and
+    /// the emphasis bits of `PPUMASK` (the upper 3 bits), which are converted
+    /// into RGB values by the video output. The index is already reduced to
+    /// the grey column of the palette in greyscale mode, and the emphasis bits
+    /// are always in the order red (bit 6), green (bit 7) and blue (bit 8),
+    /// even on the PPUs which have the red and green bits of `PPUMASK`
+    /// swapped.
+    ///
+    /// The slice is the frame buffer itself, which the PPU renders into, so
+    /// an integration reads it without a copy, e.g. to upload it into a
+    /// texture. The PPU can't run while the slice is borrowed. Once the PPU
+    /// ran again, the pixels of the scanlines rendered since then are already
+    /// of the next picture, so the buffer holds a complete picture from the
+    /// end of the last visible scanline to the start of the next frame, e.g.
+    /// right after the console ran a frame. A new complete picture is denoted
+    /// by a new [`Ppu::generation`].
+    #[must_use]
+    pub fn frame_buffer(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    /// Return the generation of the picture in the frame buffer, which
+    /// changes once a picture is completed in the frame buffer, a state is
+    /// loaded or the PPU is power cycled, e.g. to upload the frame buffer only if it changed.
+    ///
+    /// The generation stays the same while the output is disabled (see
+    /// [`Ppu::set_output`]), since the frame buffer isn't written then. It
+    /// isn't part of the save states.
+    ///
+    /// ```
+    /// # use chuck_ppu::Ppu;
+    /// let mut ppu = Ppu::new();
+    /// let generation = ppu.generation();
+    /// while ppu.frame() == 0 {
+    ///     ppu.step();
+    /// }
+    ///
+    /// assert_eq!(ppu.generation(), generation + 1);
+    /// ```
+    #[must_use]
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Enable or disable storing the rendered pixels in the frame buffer,
+    /// e.g. to skip the output of frames which aren't shown. This is enabled
+    /// by default.
+    ///
+    /// The PPU renders just the same without the output, e.g. it still
+    /// detects the sprite zero hits, so only the frame buffer keeps the
+    /// pixels stored before.
+    pub fn set_output(&mut self, enabled: bool) {
+        self.output = if enabled {
+            Output::FrameBuffer
+        } else {
+            Output::Discarded
+        };
+    }
+
+    /// Return the primary Object Attribute Memory (OAM), for debuggers.
+    #[must_use]
+    pub const fn oam(&self) -> &[u8; 256] {
+        &self.oam
+    }
+
+    /// Return the palette RAM, for debuggers.
+    #[must_use]
+    pub const fn palette(&self) -> &[u8; 32] {
+        &self.palette
+    }
+
+    /// Check if the rendering of the background or the sprites is enabled.
+    #[must_use]
+    pub const fn is_rendering_enabled(&self) -> bool {
+        self.mask.intersects(Mask::BG.union(Mask::SPRITES))
+    }
+
+    /// Check if the PPU is currently rendering, i.e. rendering is enabled and
+    /// the current scanline is a visible or the pre-render one.
+    ///
+    /// While rendering, the VRAM address is used to fetch the tiles and the
+    /// OAM is used to evaluate the sprites.
+    #[must_use]
+    pub const fn is_rendering(&self) -> bool {
+        self.is_rendering_enabled()
+            && (self.scanline < 240 || self.scanline == self.region.prerender_line())
+    }
+
+    /// Check if the 2C07 is currently refreshing its OAM during vertical
+    /// blanking, which ignores OAM writes like rendering does.
+    const fn is_refreshing_oam(&self) -> bool {
+        matches!(self.region, Region::Pal)
+            && self.scanline >= self.region.vblank_line() + OAM_REFRESH_DELAY
+            && self.scanline < self.region.prerender_line()
+    }
+
+    /// Reset the registers, see [`Pins::RST`].
+    fn reset(&mut self) {
+        self.ctrl = Ctrl::empty();
+        self.mask = Mask::empty();
+        self.ready = false;
+        self.t = 0;
+        self.x = 0;
+        self.w = false;
+        self.buffer = 0;
+    }
+
+    /// Complete the read of the previous dot, latching its data.
+    fn complete(&mut self) {
+        let data = self.bus.data;
+
+        match std::mem::replace(&mut self.pending, Fetch::None) {
+            Fetch::None => {}
+            Fetch::Nametable => self.nt = data,
+            Fetch::Attribute => {
+                // Select the two bits of the quadrant of the tile.
+                let shift = ((self.v >> 4) & 4) | (self.v & 2);
+                self.at = (data >> shift) & 3;
+            }
+            Fetch::PatternLow => self.pattern_low = data,
+            Fetch::PatternHigh => self.pattern_high = data,
+            Fetch::SpriteLow(i) => self.sprites[usize::from(i)].set_pattern(0, data),
+            Fetch::SpriteHigh(i) => self.sprites[usize::from(i)].set_pattern(1, data),
+            Fetch::Data => self.buffer = data,
+        }
+    }
+
+    /// Place the pending `PPUDATA` access onto the bus, or the VRAM address.
+    ///
+    /// Outside of rendering, the VRAM address is continuously output on the
+    /// address bus, which is why writing `PPUADDR` can be seen by mappers.
+    fn access_data(&mut self) {
+        match self.data.take() {
+            Some((access, addr, data)) => {
+                self.bus.addr = addr;
+                self.bus.access = access;
+
+                if access == Access::Write {
+                    self.bus.data = data;
+                } else {
+                    self.pending = Fetch::Data;
+                }
+            }
+            None => self.bus.addr = self.v & 0x3fff,
+        }
+    }
+
+    /// Increment the VRAM address after a `PPUDATA` access.
+    ///
+    /// While rendering, this increments both the coarse X and Y scroll
+    /// instead, as the rendering logic is used.
+    fn increment(&mut self) {
+        if self.is_rendering() {
+            self.increment_x();
+            self.increment_y();
+        } else if self.ctrl.contains(Ctrl::INCREMENT) {
+            self.v = self.v.wrapping_add(32) & 0x7fff;
+        } else {
+            self.v = self.v.wrapping_add(1) & 0x7fff;
+        }
+    }
+
+    /// Update the `/INT` pin from the vertical blanking flag.
+    fn update_int(&mut self) {
+        let int = self.status.contains(Status::VBLANK) && self.ctrl.contains(Ctrl::NMI);
+        self.pins.set(Pins::INT, int);
+    }
+
+    /// Advance to the next dot, skipping the last dot of the pre-render line
+    /// on odd frames while rendering is enabled on NTSC.
+    ///
+    /// The skip is decided two dots before the end of the line, so enabling
+    /// rendering just before the skipped dot doesn't shorten the frame.
+    fn advance(&mut self) {
+        self.dot += 1;
+
+        let dots = if self.scanline == self.region.prerender_line() {
+            if self.dot == DOTS - 2 {
+                let odd = self.frame & 1 == 1 && matches!(self.region, Region::Ntsc);
+                self.prerender_dots = DOTS - u16::from(odd && self.is_rendering_enabled());
+            }
+            self.prerender_dots
+        } else {
+            DOTS
+        };
+
+        if self.dot == dots {
+            self.dot = 0;
+            self.scanline += 1;
+
+            if self.scanline == self.region.scanlines() {
+                let dots = (self.scanline - 1) * (DOTS % 3) + self.prerender_dots;
+                self.phase = (self.phase + (dots % 3).to_le_bytes()[0]) % 3;
+                self.scanline = 0;
+                self.frame += 1;
+                self.latch.decay(self.frame);
+            }
+        }
+    }
+
+    /// Return the color of the palette RAM at the given address.
+    fn palette_color(&self, addr: u16) -> u8 {
+        let color = self.palette[palette_index(addr)];
+
+        if self.mask.contains(Mask::GREYSCALE) {
+            color & 0x30
+        } else {
+            color
+        }
+    }
+}
+
+/// Return the index into the palette RAM of the given address.
+///
+/// The backdrop colors of the sprite palettes mirror those of the background
+/// palettes.
+fn palette_index(addr: u16) -> usize {
+    let index = usize::from(addr) & 0x1f;
+
+    if index & 0x13 == 0x10 {
+        index & !0x10
+    } else {
+        index
+    }
+}
@@ -0,0 +1,234 @@
+//! The background pipeline and the pixel output of the PPU.
+//!
+//! Every 8 dots of a scanline, the PPU fetches the nametable byte, the
+//! attribute byte and both pattern bytes of a background tile, two dots per
+//! fetch. The pattern and attribute bits are then loaded into shift registers,
+//! which are shifted once per dot, and the bits selected by the fine X scroll
+//! form the background pixel.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU_rendering>
+//! - <https://www.nesdev.org/wiki/PPU_scrolling>
+//! - <https://www.nesdev.org/w/images/default/4/4f/Ppu.svg>
+
+use crate::{Access, Ctrl, Fetch, Mask, Output, Ppu, Region, Status, WIDTH};
+
+impl Ppu {
+    /// Execute a dot of a visible or the pre-render scanline, with rendering
+    /// enabled.
+    pub(crate) fn render(&mut self) {
+        let dot = self.dot;
+
+        if matches!(dot, 2..=257 | 322..=337) {
+            self.shift();
+
+            // The tiles are loaded on dots 9, 17, ..., 257, 329 and 337.
+            if (dot - 1).is_multiple_of(8) {
+                self.load_tile();
+            }
+        }
+
+        match dot {
+            1..=256 | 321..=336 => self.fetch_bg(),
+            337..=340 => self.fetch_nametable(),
+            _ => {}
+        }
+
+        match dot {
+            256 => self.increment_y(),
+            257 => self.v = (self.v & !0x041f) | (self.t & 0x041f),
+            280..=304 if self.scanline == self.region.prerender_line() => {
+                self.v = (self.v & !0x7be0) | (self.t & 0x7be0);
+            }
+            _ => {}
+        }
+
+        self.render_sprites();
+
+        if self.scanline < 240 && matches!(dot, 1..=256) {
+            let pixel = self.pixel(dot - 1);
+            if self.output == Output::FrameBuffer {
+                self.output(dot - 1, pixel);
+            }
+        }
+    }
+
+    /// Execute a dot of a visible scanline, with rendering disabled.
+    ///
+    /// The backdrop color is shown, unless the VRAM address points into the
+    /// palette RAM, in which case that color is shown instead.
+    pub(crate) fn render_disabled(&mut self) {
+        if self.output == Output::FrameBuffer && matches!(self.dot, 1..=256) {
+            let addr = if self.v & 0x3fff >= 0x3f00 {
+                self.v
+            } else {
+                0x3f00
+            };
+
+            self.output(self.dot - 1, addr);
+        }
+    }
+
+    /// Place the background fetch of the current dot onto the bus.
+    fn fetch_bg(&mut self) {
+        let fine_y = self.v >> 12;
+        let table = if self.ctrl.contains(Ctrl::BG_TABLE) {
+            0x1000
+        } else {
+            0x0000
+        };
+        let pattern = table | (u16::from(self.nt) << 4) | fine_y;
+
+        match (self.dot - 1) % 8 {
+            0 | 1 => self.fetch(0x2000 | (self.v & 0x0fff), Fetch::Nametable),
+            2 | 3 => {
+                let addr =
+                    0x23c0 | (self.v & 0x0c00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                self.fetch(addr, Fetch::Attribute);
+            }
+            4 | 5 => self.fetch(pattern, Fetch::PatternLow),
+            6 => self.fetch(pattern | 8, Fetch::PatternHigh),
+            _ => {
+                self.fetch(pattern | 8, Fetch::PatternHigh);
+                self.increment_x();
+            }
+        }
+    }
+
+    /// Place the unused nametable fetches at the end of a scanline onto the
+    /// bus, which some mappers use to detect scanlines.
+    fn fetch_nametable(&mut self) {
+        self.fetch(0x2000 | (self.v & 0x0fff), Fetch::None);
+    }
+
+    /// Place a fetch onto the bus, latching the address on the first dot and
+    /// reading on the second.
+    pub(crate) fn fetch(&mut self, addr: u16, fetch: Fetch) {
+        self.bus.addr = addr;
+
+        if self.dot.is_multiple_of(2) {
+            self.bus.access = Access::Read;
+            self.pending = fetch;
+        }
+    }
+
+    /// Shift the background shift registers.
+    fn shift(&mut self) {
+        for shift in self.bg_shift.iter_mut().chain(&mut self.at_shift) {
+            *shift <<= 1;
+        }
+    }
+
+    /// Load the fetched tile into the low bytes of the shift registers.
+    fn load_tile(&mut self) {
+        let [low, high] = &mut self.bg_shift;
+        *low = (*low & 0xff00) | u16::from(self.pattern_low);
+        *high = (*high & 0xff00) | u16::from(self.pattern_high);
+
+        let [low, high] = &mut self.at_shift;
+        *low = (*low & 0xff00) | if self.at & 1 == 0 { 0x00 } else { 0xff };
+        *high = (*high & 0xff00) | if self.at & 2 == 0 { 0x00 } else { 0xff };
+    }
+
+    /// Increment the coarse X scroll of the VRAM address, switching the
+    /// horizontal nametable when it wraps around.
+    pub(crate) fn increment_x(&mut self) {
+        if self.v & 0x001f == 31 {
+            self.v = (self.v & !0x001f) ^ 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    /// Increment the fine Y scroll of the VRAM address, carrying into the
+    /// coarse Y scroll and switching the vertical nametable after row 29.
+    pub(crate) fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let y = match (self.v & 0x03e0) >> 5 {
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            // Rows 30 and 31 are the attribute table, which wraps around
+            // without switching the nametable.
+            31 => 0,
+            y => y + 1,
+        };
+        self.v = (self.v & !0x03e0) | (y << 5);
+    }
+
+    /// Compose the pixel at the given X position of the current scanline,
+    /// returning its palette RAM address.
+    fn pixel(&mut self, x: u16) -> u16 {
+        let bg = if self.mask.contains(Mask::BG) && (x >= 8 || self.mask.contains(Mask::BG_LEFT)) {
+            let bit = 15 - self.x;
+            let pick = |[low, high]: [u16; 2]| ((low >> bit) & 1) | (((high >> bit) & 1) << 1);
+            let pattern = pick(self.bg_shift);
+
+            if pattern == 0 {
+                0
+            } else {
+                (pick(self.at_shift) << 2) | pattern
+            }
+        } else {
+            0
+        };
+
+        let sprite = if self.mask.contains(Mask::SPRITES)
+            && (x >= 8 || self.mask.contains(Mask::SPRITES_LEFT))
+        {
+            self.sprite_pixel(x)
+        } else {
+            None
+        };
+
+        match sprite {
+            None => 0x3f00 | bg,
+            Some(sprite) => {
+                if sprite.zero && bg != 0 && x != 255 {
+                    self.status.insert(Status::SPRITE_ZERO);
+                }
+
+                if bg == 0 || !sprite.behind {
+                    0x3f10 | sprite.color
+                } else {
+                    0x3f00 | bg
+                }
+            }
+        }
+    }
+
+    /// Return the emphasis bits of `PPUMASK` in the order red, green and
+    /// blue, since the PAL and Dendy PPUs have the red and green bits swapped.
+    fn emphasis(&self) -> u8 {
+        let bits = self.mask.bits() >> 5;
+
+        match self.region {
+            Region::Ntsc => bits,
+            Region::Pal | Region::Dendy => (bits & 4) | ((bits & 1) << 1) | ((bits >> 1) & 1),
+        }
+    }
+
+    /// Store a color of the palette RAM in the frame buffer, at the given X
+    /// position of the current scanline.
+    fn output(&mut self, x: u16, addr: u16) {
+        let index = usize::from(self.scanline) * WIDTH + usize::from(x);
+        self.pixels[index] = self.color(addr);
+
+        if index == self.pixels.len() - 1 {
+            self.generation += 1;
+        }
+    }
+
+    /// Return the color of the palette RAM at the given address with the
+    /// emphasis bits, in the format of the frame buffer.
+    pub(crate) fn color(&self, addr: u16) -> u16 {
+        u16::from(self.palette_color(addr)) | (u16::from(self.emphasis()) << 6)
+    }
+}
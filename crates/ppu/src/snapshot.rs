@@ -0,0 +1,247 @@
+//! The serialization of the complete state of the PPU, including the frame
+//! buffer, so a PPU restored mid-scanline continues on the exact same dot.
+//!
+//! The fields are saved in a fixed layout of little-endian values, which is
+//! versioned by the container of the whole console.
+
+use std::io::{self, Read, Write};
+
+use crate::latch::Latch;
+use crate::sprite::{Evaluation, Sprite};
+use crate::{Access, Bus, Ctrl, Fetch, Mask, Pins, Ppu, Status, DOTS, HEIGHT, WIDTH};
+
+/// Create an error denoting a malformed state.
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read the given number of bytes.
+fn bytes<const N: usize>(reader: &mut dyn Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read a little-endian 16-bit value.
+fn read_u16(reader: &mut dyn Read) -> io::Result<u16> {
+    bytes(reader).map(u16::from_le_bytes)
+}
+
+/// Encode a memory access of the VRAM bus.
+const fn encode_access(access: Access) -> u8 {
+    match access {
+        Access::Idle => 0,
+        Access::Read => 1,
+        Access::Write => 2,
+    }
+}
+
+/// Decode a memory access of the VRAM bus.
+fn decode_access(byte: u8) -> io::Result<Access> {
+    match byte {
+        0 => Ok(Access::Idle),
+        1 => Ok(Access::Read),
+        2 => Ok(Access::Write),
+        _ => Err(invalid("invalid vram access in ppu state")),
+    }
+}
+
+/// Encode a pending fetch as its kind and sprite index.
+const fn encode_fetch(fetch: Fetch) -> [u8; 2] {
+    match fetch {
+        Fetch::None => [0, 0],
+        Fetch::Nametable => [1, 0],
+        Fetch::Attribute => [2, 0],
+        Fetch::PatternLow => [3, 0],
+        Fetch::PatternHigh => [4, 0],
+        Fetch::SpriteLow(i) => [5, i],
+        Fetch::SpriteHigh(i) => [6, i],
+        Fetch::Data => [7, 0],
+    }
+}
+
+/// Decode a pending fetch.
+fn decode_fetch([kind, i]: [u8; 2]) -> io::Result<Fetch> {
+    match (kind, i) {
+        (0, _) => Ok(Fetch::None),
+        (1, _) => Ok(Fetch::Nametable),
+        (2, _) => Ok(Fetch::Attribute),
+        (3, _) => Ok(Fetch::PatternLow),
+        (4, _) => Ok(Fetch::PatternHigh),
+        (5, 0..=7) => Ok(Fetch::SpriteLow(i)),
+        (6, 0..=7) => Ok(Fetch::SpriteHigh(i)),
+        (7, _) => Ok(Fetch::Data),
+        _ => Err(invalid("invalid fetch in ppu state")),
+    }
+}
+
+impl Ppu {
+    /// Save the complete state of the PPU, including the frame buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given writer.
+    pub fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut state = Vec::with_capacity(512 + WIDTH * HEIGHT * 2);
+
+        state.extend(self.frame.to_le_bytes());
+        state.extend(self.scanline.to_le_bytes());
+        state.extend(self.dot.to_le_bytes());
+        state.extend(self.prerender_dots.to_le_bytes());
+        state.push(self.phase);
+
+        state.push(self.pins.bits());
+        state.extend(self.bus.addr.to_le_bytes());
+        state.push(self.bus.data);
+        state.push(encode_access(self.bus.access));
+
+        state.extend([self.ctrl.bits(), self.mask.bits(), self.status.bits()]);
+        state.extend([u8::from(self.ready), u8::from(self.suppress)]);
+        state.extend(self.v.to_le_bytes());
+        state.extend(self.t.to_le_bytes());
+        state.extend([self.x, u8::from(self.w), self.buffer]);
+        self.latch.save(&mut state)?;
+
+        let (access, addr, data) = self.data.unwrap_or((Access::Idle, 0, 0));
+        state.push(self.data.map_or(0, |_| encode_access(access) + 1));
+        state.extend(addr.to_le_bytes());
+        state.push(data);
+        state.extend(encode_fetch(self.pending));
+
+        state.push(self.oam_addr);
+        state.extend(self.oam);
+        state.extend(self.secondary);
+        state.extend(self.found.to_bytes());
+        for sprite in self.sprites {
+            state.extend(sprite.to_bytes());
+        }
+        state.extend(self.line.to_bytes());
+        state.extend(self.palette);
+
+        state.extend([self.nt, self.at, self.pattern_low, self.pattern_high]);
+        for shift in self.bg_shift.iter().chain(&self.at_shift) {
+            state.extend(shift.to_le_bytes());
+        }
+
+        for pixel in &self.pixels {
+            state.extend(pixel.to_le_bytes());
+        }
+
+        writer.write_all(&state)
+    }
+
+    /// Load the complete state of the PPU saved by [`Ppu::save`].
+    ///
+    /// The state of the PPU is left untouched if the state is malformed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced by the given reader, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the state is malformed.
+    pub fn load(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let frame = u64::from_le_bytes(bytes(reader)?);
+        let scanline = read_u16(reader)?;
+        let dot = read_u16(reader)?;
+        let prerender_dots = read_u16(reader)?;
+        let [phase] = bytes(reader)?;
+
+        if scanline >= self.region.scanlines()
+            || dot >= DOTS
+            || !(DOTS - 1..=DOTS).contains(&prerender_dots)
+            || phase > 2
+        {
+            return Err(invalid("invalid position in ppu state"));
+        }
+
+        let pins = Pins::from_bits_retain(bytes::<1>(reader)?[0]);
+        let bus = Bus {
+            addr: read_u16(reader)? & 0x3fff,
+            data: bytes::<1>(reader)?[0],
+            access: decode_access(bytes::<1>(reader)?[0])?,
+        };
+
+        let [ctrl, mask, status, ready, suppress] = bytes(reader)?;
+        let vram_addr = read_u16(reader)? & 0x7fff;
+        let temp_addr = read_u16(reader)? & 0x7fff;
+        let [fine_x, toggle, buffer] = bytes(reader)?;
+        let latch = Latch::load(reader, frame)?;
+
+        let [data_access] = bytes(reader)?;
+        let data_addr = read_u16(reader)? & 0x3fff;
+        let [data_data] = bytes(reader)?;
+        let data = match data_access {
+            0 => None,
+            access => Some((decode_access(access - 1)?, data_addr, data_data)),
+        };
+        let pending = decode_fetch(bytes(reader)?)?;
+
+        let [oam_addr] = bytes(reader)?;
+        let oam = bytes(reader)?;
+        let secondary = bytes(reader)?;
+        let found = Evaluation::from_bytes(bytes(reader)?);
+        let mut sprites = [Sprite::default(); 8];
+        for sprite in &mut sprites {
+            *sprite = Sprite::from_bytes(bytes(reader)?);
+        }
+        let line = Evaluation::from_bytes(bytes(reader)?);
+        let palette: [u8; 32] = bytes(reader)?;
+
+        let (Some(found), Some(line)) = (found, line) else {
+            return Err(invalid("invalid sprite evaluation in ppu state"));
+        };
+
+        let [nt, at, pattern_low, pattern_high] = bytes(reader)?;
+        let bg_shift = [read_u16(reader)?, read_u16(reader)?];
+        let at_shift = [read_u16(reader)?, read_u16(reader)?];
+
+        let mut pixels = vec![0; WIDTH * HEIGHT * 2];
+        reader.read_exact(&mut pixels)?;
+        let pixels = pixels
+            .chunks_exact(2)
+            .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]) & 0x01ff)
+            .collect();
+
+        *self = Self {
+            pins,
+            bus,
+            region: self.region,
+            ctrl: Ctrl::from_bits_truncate(ctrl),
+            mask: Mask::from_bits_retain(mask),
+            status: Status::from_bits_truncate(status),
+            ready: ready != 0,
+            suppress: suppress != 0,
+            v: vram_addr,
+            t: temp_addr,
+            x: fine_x & 7,
+            w: toggle != 0,
+            buffer,
+            latch,
+            data,
+            pending,
+            oam_addr,
+            oam,
+            secondary,
+            found,
+            sprites,
+            line,
+            palette: palette.map(|color| color & 0x3f),
+            nt,
+            at: at & 3,
+            pattern_low,
+            pattern_high,
+            bg_shift,
+            at_shift,
+            scanline,
+            dot,
+            prerender_dots,
+            frame,
+            phase,
+            pixels,
+            // The frame buffer of the state is another picture.
+            generation: self.generation + 1,
+            output: self.output,
+        };
+
+        Ok(())
+    }
+}
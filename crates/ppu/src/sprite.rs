@@ -0,0 +1,278 @@
+//! The sprite evaluation and the sprite pipeline of the PPU.
+//!
+//! During every visible scanline, the PPU clears the secondary OAM (dots
+//! 1-64), then searches the primary OAM for the (up to 8) sprites on the next
+//! scanline and copies them into the secondary OAM (dots 65-256). The pattern
+//! bytes of these sprites are fetched during dots 257-320, and the sprites are
+//! drawn on the next scanline.
+//!
+//! The evaluation is performed all at once, on the last dot of its interval,
+//! but it emulates the hardware bug of the overflow detection: after 8 sprites
+//! are found, the search increments both the sprite index and the byte offset
+//! within the sprite, which causes false positives and negatives.
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU_sprite_evaluation>
+//! - <https://www.nesdev.org/wiki/PPU_OAM>
+//! - <https://www.nesdev.org/wiki/PPU_sprite_priority>
+
+use crate::{Ctrl, Fetch, Ppu, Status};
+
+/// A sprite of the current scanline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sprite {
+    /// The X position of the left edge of the sprite.
+    x: u8,
+    /// The attributes of the sprite, i.e. its palette, priority and flipping.
+    attr: u8,
+    /// The two pattern bytes of the row of the sprite, flipped horizontally
+    /// if necessary so that the leftmost pixel is the highest bit.
+    pattern: [u8; 2],
+}
+
+impl Sprite {
+    /// Return the state of the sprite as bytes.
+    pub const fn to_bytes(self) -> [u8; 4] {
+        [self.x, self.attr, self.pattern[0], self.pattern[1]]
+    }
+
+    /// Create a sprite from the bytes returned by [`Sprite::to_bytes`].
+    pub const fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self {
+            x: bytes[0],
+            attr: bytes[1],
+            pattern: [bytes[2], bytes[3]],
+        }
+    }
+
+    /// Set one of the pattern bytes of the row of the sprite.
+    pub fn set_pattern(&mut self, plane: usize, data: u8) {
+        self.pattern[plane] = if self.attr & 0x40 == 0 {
+            data
+        } else {
+            data.reverse_bits()
+        };
+    }
+}
+
+/// The result of a sprite evaluation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Evaluation {
+    /// The number of sprites found, at most 8.
+    count: u8,
+    /// A flag denoting if sprite zero is the first sprite found.
+    zero: bool,
+}
+
+impl Evaluation {
+    /// Return the state of the evaluation as bytes.
+    pub fn to_bytes(self) -> [u8; 2] {
+        [self.count, u8::from(self.zero)]
+    }
+
+    /// Create an evaluation from the bytes returned by
+    /// [`Evaluation::to_bytes`], or `None` if they are invalid.
+    pub const fn from_bytes(bytes: [u8; 2]) -> Option<Self> {
+        if bytes[0] > 8 {
+            return None;
+        }
+
+        Some(Self {
+            count: bytes[0],
+            zero: bytes[1] != 0,
+        })
+    }
+}
+
+/// An opaque pixel of a sprite.
+#[derive(Debug, Clone, Copy)]
+pub struct SpritePixel {
+    /// The palette and pattern bits of the pixel, i.e. the address within the
+    /// sprite palettes.
+    pub color: u16,
+    /// A flag denoting if the sprite is drawn behind the background.
+    pub behind: bool,
+    /// A flag denoting if the pixel is part of sprite zero.
+    pub zero: bool,
+}
+
+impl Ppu {
+    /// Execute the sprite work of a dot of a visible or the pre-render
+    /// scanline, with rendering enabled.
+    pub(crate) fn render_sprites(&mut self) {
+        match self.dot {
+            64 if self.scanline != self.region.prerender_line() => self.secondary = [0xff; 32],
+            256 if self.scanline == self.region.prerender_line() => {
+                // No sprites are evaluated for the first scanline.
+                self.secondary = [0xff; 32];
+                self.found = Evaluation::default();
+            }
+            256 => self.evaluate(),
+            257..=320 => {
+                self.oam_addr = 0;
+                self.fetch_sprite();
+            }
+            _ => {}
+        }
+    }
+
+    /// Return the value of `OAMDATA`.
+    ///
+    /// While the secondary OAM is cleared, the PPU reads `$FF` instead.
+    pub(crate) fn oam_data(&self) -> u8 {
+        if self.is_rendering()
+            && self.scanline != self.region.prerender_line()
+            && matches!(self.dot, 1..=64)
+        {
+            return 0xff;
+        }
+
+        let data = self.oam[usize::from(self.oam_addr)];
+
+        // The unimplemented bits of the attribute bytes read back as zero.
+        if self.oam_addr & 3 == 2 {
+            data & 0xe3
+        } else {
+            data
+        }
+    }
+
+    /// Return the height of the sprites, in pixels.
+    pub(crate) fn sprite_height(&self) -> u16 {
+        if self.ctrl.contains(Ctrl::SPRITE_SIZE) {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Check if a sprite at the given Y position is on the next scanline.
+    fn is_in_range(&self, y: u8) -> bool {
+        self.scanline.wrapping_sub(u16::from(y)) < self.sprite_height()
+    }
+
+    /// Evaluate the sprites of the next scanline into the secondary OAM.
+    fn evaluate(&mut self) {
+        let mut n = 0;
+        self.found = Evaluation::default();
+
+        while n < 64 && self.found.count < 8 {
+            let sprite = &self.oam[n * 4..n * 4 + 4];
+            let slot = usize::from(self.found.count) * 4;
+            self.secondary[slot] = sprite[0];
+
+            if self.is_in_range(sprite[0]) {
+                self.secondary[slot..slot + 4].copy_from_slice(sprite);
+                self.found.zero |= n == 0;
+                self.found.count += 1;
+            }
+
+            n += 1;
+        }
+
+        // The buggy overflow detection, which increments the byte offset `m`
+        // along with the sprite index `n` for every sprite out of range.
+        let mut m = 0;
+        while n < 64 {
+            if self.is_in_range(self.oam[n * 4 + m]) {
+                self.status.insert(Status::OVERFLOW);
+                break;
+            }
+
+            n += 1;
+            m = (m + 1) & 3;
+        }
+    }
+
+    /// Place the sprite fetch of the current dot onto the bus.
+    ///
+    /// Each of the 8 sprites takes 8 dots: two unused nametable fetches, then
+    /// both pattern bytes. The pattern bytes of the unused slots are fetched
+    /// from tile `$FF`, but are discarded.
+    fn fetch_sprite(&mut self) {
+        let slot = (self.dot - 257) / 8;
+        let index = usize::from(slot) * 4;
+        let [y, tile, attr, x] = [0, 1, 2, 3].map(|offset| self.secondary[index + offset]);
+        let used = slot < u16::from(self.found.count);
+
+        let height = self.sprite_height();
+        let mut row = self.scanline.wrapping_sub(u16::from(y)) % height;
+        if attr & 0x80 != 0 {
+            row = height - 1 - row;
+        }
+
+        let (table, tile) = if height == 16 {
+            (
+                u16::from(tile & 1) << 12,
+                u16::from(tile & 0xfe) + (row >> 3),
+            )
+        } else if self.ctrl.contains(Ctrl::SPRITE_TABLE) {
+            (0x1000, u16::from(tile))
+        } else {
+            (0x0000, u16::from(tile))
+        };
+        let pattern = table | (tile << 4) | (row & 7);
+
+        // The slot numbers are below 8, so they fit into a byte.
+        let slot = slot.to_le_bytes()[0];
+
+        match (self.dot - 257) % 8 {
+            0 => {
+                let sprite = &mut self.sprites[usize::from(slot)];
+                sprite.x = x;
+                sprite.attr = attr;
+                sprite.pattern = [0; 2];
+
+                if slot == 0 {
+                    self.line = self.found;
+                }
+
+                self.fetch(0x2000 | (self.v & 0x0fff), Fetch::None);
+            }
+            1..=3 => self.fetch(0x2000 | (self.v & 0x0fff), Fetch::None),
+            4 | 5 => self.fetch(
+                pattern,
+                if used {
+                    Fetch::SpriteLow(slot)
+                } else {
+                    Fetch::None
+                },
+            ),
+            _ => self.fetch(
+                pattern | 8,
+                if used {
+                    Fetch::SpriteHigh(slot)
+                } else {
+                    Fetch::None
+                },
+            ),
+        }
+    }
+
+    /// Return the opaque sprite pixel at the given X position of the current
+    /// scanline, if any.
+    ///
+    /// The first sprite in OAM order with an opaque pixel wins, regardless of
+    /// its priority, which is then resolved against the background.
+    pub(crate) fn sprite_pixel(&self, x: u16) -> Option<SpritePixel> {
+        let sprites = &self.sprites[..usize::from(self.line.count)];
+
+        sprites.iter().enumerate().find_map(|(i, sprite)| {
+            let offset = x.wrapping_sub(u16::from(sprite.x));
+            if offset >= 8 {
+                return None;
+            }
+
+            let bit = 7 - offset;
+            let [low, high] = sprite.pattern.map(|plane| u16::from(plane >> bit) & 1);
+            let pattern = low | (high << 1);
+
+            (pattern != 0).then(|| SpritePixel {
+                color: (u16::from(sprite.attr & 3) << 2) | pattern,
+                behind: sprite.attr & 0x20 != 0,
+                zero: i == 0 && self.line.zero,
+            })
+        })
+    }
+}
@@ -0,0 +1,247 @@
+//! The pictures of the debug viewers of the PPU's memory: the nametables, the
+//! pattern tables, the sprites of the OAM and the palette RAM.
+//!
+//! The viewers show the memory as the PPU would render it at the current dot,
+//! with the pattern tables selected by `PPUCTRL` and the colors of the
+//! palette RAM, so they can be drawn at any time, e.g. in the middle of a
+//! frame after a breakpoint. The colors have the format of
+//! [`Ppu::frame_buffer`], so they are converted into RGB values just like the
+//! picture, e.g. by the palettes of the video output.
+//!
+//! The PPU doesn't contain its VRAM, so every viewer which shows tiles reads
+//! the VRAM bus through the given [`Vram`], usually a function which must
+//! read it without side effects:
+//!
+//! ```
+//! # use chuck_ppu::Ppu;
+//! # use chuck_ppu::viewer::{PATTERN_TABLES_HEIGHT, PATTERN_TABLES_WIDTH};
+//! let mut ppu = Ppu::new();
+//! let mut vram = [0; 0x4000];
+//!
+//! // Set the color 1 of the first background palette.
+//! while ppu.frame() == 0 {
+//!     ppu.step();
+//! }
+//! ppu.write(0x2006, 0x3f);
+//! ppu.write(0x2006, 0x01);
+//! ppu.write(0x2007, 0x16);
+//!
+//! // The low plane of the first row of tile 0 is opaque.
+//! vram[0x0000] = 0xff;
+//!
+//! let pixels = ppu.render_pattern_tables(0, |addr| vram[usize::from(addr)]);
+//! assert_eq!(pixels.len(), PATTERN_TABLES_WIDTH * PATTERN_TABLES_HEIGHT);
+//! assert_eq!(pixels[..9], [0x16, 0x16, 0x16, 0x16, 0x16, 0x16, 0x16, 0x16, 0x00]);
+//! assert_eq!(ppu.palette_snapshot()[1], 0x16);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU_pattern_tables>
+//! - <https://www.nesdev.org/wiki/PPU_nametables>
+//! - <https://www.nesdev.org/wiki/PPU_attribute_tables>
+//! - <https://www.nesdev.org/wiki/PPU_OAM>
+
+use crate::{Ctrl, Ppu};
+
+/// The width of the picture of the nametables, in pixels.
+pub const NAMETABLES_WIDTH: usize = 2 * 256;
+
+/// The height of the picture of the nametables, in pixels.
+pub const NAMETABLES_HEIGHT: usize = 2 * 240;
+
+/// The width of the picture of the pattern tables, in pixels.
+pub const PATTERN_TABLES_WIDTH: usize = 2 * 128;
+
+/// The height of the picture of the pattern tables, in pixels.
+pub const PATTERN_TABLES_HEIGHT: usize = 128;
+
+/// The width of the picture of the sprites, in pixels.
+pub const OAM_WIDTH: usize = 8 * 8;
+
+/// The height of the picture of the sprites, in pixels.
+pub const OAM_HEIGHT: usize = 8 * 16;
+
+/// The address of the backdrop color in the palette RAM.
+const BACKDROP: u16 = 0x3f00;
+
+/// The VRAM bus as read by the viewers, without side effects.
+///
+/// Every function which reads a byte of the bus is one, while a cache of
+/// decoded tiles may implement [`Vram::tile_row`] as well, which saves the
+/// viewers from extracting the pixels from the bit planes of every row.
+pub trait Vram {
+    /// Read the byte at an address of the bus, `$0000`-`$3FFF`.
+    fn peek(&mut self, addr: u16) -> u8;
+
+    /// Return the pixels (`0`-`3`) of a row of a tile, from left to right,
+    /// whose low bit plane is at the given address of the pattern tables.
+    ///
+    /// This decodes the bit planes read with [`Vram::peek`] by default.
+    fn tile_row(&mut self, addr: u16) -> [u8; 8] {
+        decode_row(self.peek(addr), self.peek(addr | 8))
+    }
+}
+
+impl<F: FnMut(u16) -> u8> Vram for F {
+    fn peek(&mut self, addr: u16) -> u8 {
+        self(addr)
+    }
+}
+
+/// Return the pixels (`0`-`3`) of a row of a tile, from left to right, given
+/// its low and high bit planes.
+#[must_use]
+pub fn decode_row(low: u8, high: u8) -> [u8; 8] {
+    std::array::from_fn(|x| {
+        let bit = 7 - x;
+        ((low >> bit) & 1) | (((high >> bit) & 1) << 1)
+    })
+}
+
+impl Ppu {
+    /// Render the four nametables at `$2000`-`$2FFF` with their attributes,
+    /// in the order top left, top right, bottom left and bottom right, as
+    /// they appear after the mirroring of the cartridge.
+    ///
+    /// The picture has a size of [`NAMETABLES_WIDTH`] x
+    /// [`NAMETABLES_HEIGHT`], and shows the background tiles of the pattern
+    /// table selected by `PPUCTRL`.
+    #[must_use]
+    pub fn render_nametables(&self, mut vram: impl Vram) -> Vec<u16> {
+        let mut pixels = vec![0; NAMETABLES_WIDTH * NAMETABLES_HEIGHT];
+        let table = if self.ctrl.contains(Ctrl::BG_TABLE) {
+            0x1000
+        } else {
+            0x0000
+        };
+
+        for nametable in 0..4 {
+            let base = 0x2000 + nametable * 0x400;
+            let (left, top) = (
+                usize::from(nametable & 1) * 256,
+                usize::from(nametable >> 1) * 240,
+            );
+
+            for (tile_y, tile_x) in (0..30).flat_map(|y| (0..32).map(move |x| (y, x))) {
+                let tile = vram.peek(base + tile_y * 32 + tile_x);
+                let attribute = vram.peek(base + 0x3c0 + (tile_y / 4) * 8 + tile_x / 4);
+                let shift = ((tile_y & 2) << 1) | (tile_x & 2);
+                let palette = (attribute >> shift) & 3;
+
+                let x = left + usize::from(tile_x) * 8;
+                let y = top + usize::from(tile_y) * 8;
+                for row in 0..8 {
+                    let start = (y + usize::from(row)) * NAMETABLES_WIDTH + x;
+                    let colors = self.tile_row(&mut vram, table, tile, row, palette);
+                    pixels[start..start + 8].copy_from_slice(&colors);
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Render both pattern tables side by side, the one at `$0000` on the
+    /// left, with the colors of the given palette: `0`-`3` for the
+    /// background palettes and `4`-`7` for the sprite palettes.
+    ///
+    /// The picture has a size of [`PATTERN_TABLES_WIDTH`] x
+    /// [`PATTERN_TABLES_HEIGHT`], with the tiles arranged in 16 rows of 16
+    /// tiles per table.
+    #[must_use]
+    pub fn render_pattern_tables(&self, palette: u8, mut vram: impl Vram) -> Vec<u16> {
+        let mut pixels = vec![0; PATTERN_TABLES_WIDTH * PATTERN_TABLES_HEIGHT];
+
+        for (table, tile) in (0..2).flat_map(|table| (0..=255).map(move |tile| (table, tile))) {
+            let x = usize::from(table) * 128 + usize::from(tile & 15) * 8;
+            let y = usize::from(tile >> 4) * 8;
+
+            for row in 0..8 {
+                let start = (y + usize::from(row)) * PATTERN_TABLES_WIDTH + x;
+                let colors = self.tile_row(&mut vram, table * 0x1000, tile, row, palette & 7);
+                pixels[start..start + 8].copy_from_slice(&colors);
+            }
+        }
+
+        pixels
+    }
+
+    /// Render the 64 sprites of the OAM, in 8 rows of 8 sprites, flipped and
+    /// colored like on the screen.
+    ///
+    /// The picture has a size of [`OAM_WIDTH`] x [`OAM_HEIGHT`], with a cell
+    /// of 8x16 pixels per sprite, whose lower half is left blank with 8x8
+    /// sprites. The transparent pixels show the backdrop color.
+    #[must_use]
+    pub fn render_oam(&self, mut vram: impl Vram) -> Vec<u16> {
+        let backdrop = self.color(BACKDROP);
+        let mut pixels = vec![backdrop; OAM_WIDTH * OAM_HEIGHT];
+        let height = self.sprite_height();
+
+        for (i, sprite) in self.oam.chunks_exact(4).enumerate() {
+            let (tile, attr) = (sprite[1], sprite[2]);
+            let (x, y) = ((i % 8) * 8, (i / 8) * 16);
+
+            for row in 0..height {
+                let flipped = if attr & 0x80 == 0 {
+                    row
+                } else {
+                    height - 1 - row
+                };
+                let (table, tile) = if height == 16 {
+                    (
+                        u16::from(tile & 1) << 12,
+                        (tile & 0xfe) + u8::from(flipped >= 8),
+                    )
+                } else if self.ctrl.contains(Ctrl::SPRITE_TABLE) {
+                    (0x1000, tile)
+                } else {
+                    (0x0000, tile)
+                };
+
+                let mut colors = self.tile_row(&mut vram, table, tile, flipped & 7, 4 | (attr & 3));
+                if attr & 0x40 != 0 {
+                    colors.reverse();
+                }
+
+                let start = (y + usize::from(row)) * OAM_WIDTH + x;
+                pixels[start..start + 8].copy_from_slice(&colors);
+            }
+        }
+
+        pixels
+    }
+
+    /// Return the 32 colors of the palette RAM, in the format of
+    /// [`Ppu::frame_buffer`].
+    ///
+    /// Unlike [`Ppu::palette`], the backdrop colors of the sprite palettes
+    /// are mirrored from those of the background palettes, and the greyscale
+    /// mode and the emphasis bits are applied, like on the screen.
+    #[must_use]
+    pub fn palette_snapshot(&self) -> [u16; 32] {
+        std::array::from_fn(|i| self.color(BACKDROP + u16::try_from(i).unwrap_or(0)))
+    }
+
+    /// Return the colors of a row of a tile with the given palette, which
+    /// shows the backdrop color for the transparent pixels.
+    fn tile_row(
+        &self,
+        vram: &mut impl Vram,
+        table: u16,
+        tile: u8,
+        row: u16,
+        palette: u8,
+    ) -> [u16; 8] {
+        let addr = table | (u16::from(tile) << 4) | row;
+
+        vram.tile_row(addr).map(|pixel| {
+            if pixel == 0 {
+                self.color(BACKDROP)
+            } else {
+                self.color(BACKDROP | u16::from(palette << 2 | pixel))
+            }
+        })
+    }
+}
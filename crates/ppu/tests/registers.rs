@@ -0,0 +1,251 @@
+//! The registers of the PPU, driven through its pins with nothing but an
+//! array as its VRAM: the race of `PPUSTATUS` reads with the vertical
+//! blanking flag, the copies of the scroll registers into the VRAM address,
+//! and the sprite-zero hit and sprite overflow flags.
+
+use chuck_ppu::{Access, Pins, Ppu, WIDTH};
+
+/// The vertical blanking flag of `PPUSTATUS`.
+const VBLANK: u8 = 0x80;
+
+/// The sprite-zero hit flag of `PPUSTATUS`.
+const SPRITE_ZERO: u8 = 0x40;
+
+/// The sprite overflow flag of `PPUSTATUS`.
+const OVERFLOW: u8 = 0x20;
+
+/// A PPU with a flat 16 KiB VRAM, whose first frame is over, so its
+/// registers accept writes.
+struct Harness {
+    ppu: Ppu,
+    vram: Vec<u8>,
+}
+
+impl Harness {
+    /// Create a PPU whose pattern tables hold a tile `1` with the given low
+    /// bit plane in every row, whose nametables are filled with this tile,
+    /// and whose palette holds the colors `$00`-`$1F`.
+    fn new(tile: u8) -> Self {
+        let mut vram = vec![0; 0x4000];
+        vram[0x10..0x18].fill(tile);
+        for nametable in vram[0x2000..0x3000].chunks_mut(0x400) {
+            nametable[..0x3c0].fill(1);
+        }
+
+        let mut harness = Self {
+            ppu: Ppu::new(),
+            vram,
+        };
+        while harness.ppu.frame() == 0 {
+            harness.step();
+        }
+
+        harness.ppu.write(0x2006, 0x3f);
+        harness.ppu.write(0x2006, 0x00);
+        for color in 0..0x20 {
+            harness.ppu.write(0x2007, color);
+        }
+        harness
+    }
+
+    /// Execute a dot, servicing its access of the VRAM.
+    fn step(&mut self) {
+        self.ppu.step();
+
+        let addr = usize::from(self.ppu.bus.addr);
+        match self.ppu.bus.access {
+            Access::Read => self.ppu.bus.data = self.vram[addr],
+            Access::Write => self.vram[addr] = self.ppu.bus.data,
+            Access::Idle => {}
+        }
+    }
+
+    /// Execute dots until the given dot of the given scanline is the next one.
+    fn run_to(&mut self, scanline: u16, dot: u16) {
+        while (self.ppu.scanline(), self.ppu.dot()) != (scanline, dot) {
+            self.step();
+        }
+    }
+
+    /// Execute dots until the given dot of the given scanline is the next
+    /// one, returning the address of the first nametable fetch on the way.
+    fn nametable_fetch(&mut self, scanline: u16, dot: u16) -> Option<u16> {
+        let mut fetch = None;
+        while (self.ppu.scanline(), self.ppu.dot()) != (scanline, dot) {
+            self.step();
+
+            let addr = self.ppu.bus.addr;
+            let nametable = (0x2000..0x3000).contains(&addr) && addr & 0x3ff < 0x3c0;
+            if self.ppu.bus.access == Access::Read && nametable {
+                fetch = fetch.or(Some(addr));
+            }
+        }
+        fetch
+    }
+
+    /// Write the given sprites into the OAM, hiding all the others below the
+    /// picture.
+    fn write_oam(&mut self, sprites: &[[u8; 4]]) {
+        self.ppu.write(0x2003, 0x00);
+        for i in 0..64 {
+            for byte in sprites.get(i).copied().unwrap_or([0xff; 4]) {
+                self.ppu.write(0x2004, byte);
+            }
+        }
+    }
+}
+
+#[test]
+fn status_read_race() {
+    let mut harness = Harness::new(0);
+    harness.ppu.write(0x2000, 0x80);
+
+    // Reading on the dot before the flag is set returns it clear, and
+    // suppresses both the flag and the NMI for the whole frame.
+    harness.run_to(241, 1);
+    assert_eq!(harness.ppu.read(0x2002) & VBLANK, 0);
+    harness.run_to(241, 100);
+    assert_eq!(harness.ppu.read(0x2002) & VBLANK, 0);
+    assert!(!harness.ppu.pins.contains(Pins::INT));
+
+    // Reading a dot later returns it set, clears it, and releases the NMI.
+    harness.run_to(241, 2);
+    assert!(harness.ppu.pins.contains(Pins::INT));
+    assert_eq!(harness.ppu.read(0x2002) & VBLANK, VBLANK);
+    assert_eq!(harness.ppu.read(0x2002) & VBLANK, 0);
+    assert!(!harness.ppu.pins.contains(Pins::INT));
+
+    // Without a read, the flag is cleared on the pre-render scanline.
+    harness.run_to(0, 0);
+    harness.run_to(261, 1);
+    assert_eq!(harness.ppu.peek(0x2002) & VBLANK, VBLANK);
+    harness.run_to(261, 2);
+    assert_eq!(harness.ppu.peek(0x2002) & VBLANK, 0);
+}
+
+#[test]
+fn address_toggle() {
+    let mut harness = Harness::new(0);
+
+    // Outside of rendering, the VRAM address is output on the bus.
+    harness.ppu.write(0x2006, 0x21);
+    harness.ppu.write(0x2006, 0x08);
+    harness.step();
+    assert_eq!(harness.ppu.bus.addr, 0x2108);
+
+    // Reading `PPUSTATUS` resets the write toggle between the two writes.
+    harness.ppu.write(0x2006, 0x3f);
+    harness.ppu.read(0x2002);
+    harness.ppu.write(0x2006, 0x23);
+    harness.ppu.write(0x2006, 0x45);
+    harness.step();
+    assert_eq!(harness.ppu.bus.addr, 0x2345);
+
+    // `PPUSCROLL` shares the toggle and the temporary address: its second
+    // write and the second write of `PPUADDR` complete each other.
+    harness.ppu.write(0x2005, 0x00);
+    harness.ppu.write(0x2006, 0x67);
+    harness.step();
+    assert_eq!(harness.ppu.bus.addr, 0x2367);
+
+    // `PPUDATA` accesses increment the address by 32 with `PPUCTRL` bit 2.
+    harness.ppu.write(0x2000, 0x04);
+    harness.ppu.write(0x2007, 0x00);
+    harness.step();
+    harness.step();
+    assert_eq!(harness.ppu.bus.addr, 0x2387);
+}
+
+#[test]
+fn scroll_copies() {
+    let mut harness = Harness::new(0);
+    harness.ppu.write(0x2001, 0x0a);
+
+    // The whole temporary address is copied on the pre-render scanline:
+    // nametable `$2400`, coarse X 5 and coarse Y 2.
+    harness.run_to(241, 10);
+    harness.ppu.write(0x2000, 0x01);
+    harness.ppu.write(0x2005, 5 << 3);
+    harness.ppu.write(0x2005, 2 << 3);
+    harness.run_to(261, 305);
+    assert_eq!(harness.nametable_fetch(0, 0), Some(0x2445));
+
+    // Mid-frame, only the horizontal bits are copied, at the end of the
+    // scanline: the coarse Y keeps counting the scanlines from 2, so the
+    // next scanline starts at nametable `$2000`, coarse X 9 and coarse Y 3.
+    harness.run_to(10, 100);
+    harness.ppu.write(0x2000, 0x00);
+    harness.ppu.write(0x2005, 9 << 3);
+    harness.ppu.write(0x2005, 20 << 3);
+    harness.run_to(10, 300);
+    assert_eq!(harness.nametable_fetch(11, 0), Some(0x2069));
+}
+
+#[test]
+fn fine_x_scroll() {
+    // Only the leftmost pixel of every tile is opaque.
+    let mut harness = Harness::new(0x80);
+    harness.run_to(241, 10);
+    harness.ppu.write(0x2001, 0x0a);
+    harness.ppu.write(0x2005, 0x03);
+    harness.ppu.write(0x2005, 0x00);
+    harness.run_to(20, 0);
+
+    // The fine X scroll shifts the picture by 3 pixels to the left.
+    let row = &harness.ppu.frame_buffer()[WIDTH * 10..WIDTH * 11];
+    let opaque: Vec<usize> = (0..16).filter(|&x| row[x] == 0x01).collect();
+    assert_eq!(opaque, [5, 13]);
+}
+
+#[test]
+fn sprite_zero_hit() {
+    // Every pixel of the background and of the sprites is opaque.
+    let mut harness = Harness::new(0xff);
+    harness.write_oam(&[[49, 1, 0, 100]]);
+    harness.ppu.write(0x2001, 0x1e);
+
+    // The flag is set on the first scanline of sprite zero, which starts a
+    // scanline after its Y coordinate.
+    harness.run_to(50, 100);
+    assert_eq!(harness.ppu.read(0x2002) & SPRITE_ZERO, 0);
+    harness.run_to(50, 103);
+    assert_eq!(harness.ppu.read(0x2002) & SPRITE_ZERO, SPRITE_ZERO);
+
+    // It's only cleared on the pre-render scanline.
+    harness.run_to(261, 1);
+    assert_eq!(harness.ppu.read(0x2002) & SPRITE_ZERO, SPRITE_ZERO);
+    harness.run_to(261, 2);
+    assert_eq!(harness.ppu.read(0x2002) & SPRITE_ZERO, 0);
+
+    // Transparent background pixels never hit.
+    let mut harness = Harness::new(0x00);
+    harness.vram[0x1010..0x1018].fill(0xff);
+    harness.write_oam(&[[49, 1, 0, 100]]);
+    harness.ppu.write(0x2000, 0x08);
+    harness.ppu.write(0x2001, 0x1e);
+    harness.run_to(100, 0);
+    assert_eq!(harness.ppu.read(0x2002) & SPRITE_ZERO, 0);
+}
+
+#[test]
+fn sprite_overflow() {
+    // Eight sprites on a scanline don't overflow.
+    let mut harness = Harness::new(0);
+    harness.write_oam(&[[40, 0, 0, 0]; 8]);
+    harness.ppu.write(0x2001, 0x18);
+    harness.run_to(100, 0);
+    assert_eq!(harness.ppu.read(0x2002) & OVERFLOW, 0);
+
+    // Nine do, as they're evaluated on the scanline before their first one.
+    let mut harness = Harness::new(0);
+    harness.write_oam(&[[40, 0, 0, 0]; 9]);
+    harness.ppu.write(0x2001, 0x18);
+    harness.run_to(40, 0);
+    assert_eq!(harness.ppu.read(0x2002) & OVERFLOW, 0);
+    harness.run_to(41, 0);
+    assert_eq!(harness.ppu.read(0x2002) & OVERFLOW, OVERFLOW);
+
+    // It's only cleared on the pre-render scanline.
+    harness.run_to(261, 2);
+    assert_eq!(harness.ppu.read(0x2002) & OVERFLOW, 0);
+}
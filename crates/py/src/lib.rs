@@ -0,0 +1,567 @@
+//! Python bindings for Chuck's cycle-accurate 6502 CPU core.
+//!
+//! This crate builds the `chuck_cpu` Python extension module with `pyo3`, which
+//! exposes the CPU (including its registers, pins and snapshots) and the
+//! disassembler, for driving the CPU from scripts and notebooks, as well as
+//! the environment of a whole console for agents playing a game. The module
+//! is built and installed into the active Python environment with `maturin`:
+//!
+//! ```sh
+//! cd crates/py && maturin develop --release
+//! ```
+//!
+//! The bus accesses of the CPU are serviced by a pair of Python callables, one
+//! returning the data at an address and one storing data at an address:
+//!
+//! ```python
+//! import chuck_cpu
+//!
+//! ram = bytearray(0x10000)
+//! cpu = chuck_cpu.Cpu("6502")
+//!
+//! cpu.run(7, ram.__getitem__, ram.__setitem__)
+//! print(hex(cpu.pc), chuck_cpu.disassemble(bytes(ram[:3])))
+//! ```
+//!
+//! An exception raised by a callable is raised again by the method that
+//! executed the cycle. This stops the CPU right after the failed access, except
+//! for `step_instruction`, which finishes the instruction without servicing
+//! the remaining accesses.
+//!
+//! An `Env` plays a game like the environments of reinforcement learning, see
+//! [`chuck_nes::env`]: the actions are the buttons of the controllers (as the
+//! `BUTTON_*` bits), and the observations are the picture (as RGB rows) and
+//! the values of the watched addresses, e.g. the score in the RAM:
+//!
+//! ```python
+//! import chuck_cpu
+//!
+//! env = chuck_cpu.Env(open("game.nes", "rb").read(), watched=[0x0075], frame_skip=4)
+//! pixels, ram = env.reset(seed=42)
+//!
+//! pixels, ram, frames = env.step(chuck_cpu.BUTTON_RIGHT | chuck_cpu.BUTTON_A)
+//! assert len(pixels) == chuck_cpu.WIDTH * chuck_cpu.HEIGHT * 3
+//! ```
+
+use std::num::NonZeroU32;
+
+use chuck_cpu::{disasm, Bus, Flags, Nmos6502, Pins, Registers, Ricoh2A03, Variant, Wdc65C02};
+use chuck_input::ButtonState;
+use chuck_nes::env::Observation;
+use chuck_nes::{Nes, HEIGHT, WIDTH};
+use chuck_rom::Rom;
+use chuck_video::palette::Palette;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+/// The CPU of the emulated model.
+enum Core {
+    Ricoh2A03(chuck_cpu::Cpu<Ricoh2A03>),
+    Nmos6502(chuck_cpu::Cpu<Nmos6502>),
+    Wdc65C02(chuck_cpu::Cpu<Wdc65C02>),
+}
+
+/// Evaluate an expression with the CPU of a [`Core`], whatever its model is.
+macro_rules! with_cpu {
+    ($core:expr, $cpu:ident => $body:expr) => {
+        match $core {
+            Core::Ricoh2A03($cpu) => $body,
+            Core::Nmos6502($cpu) => $body,
+            Core::Wdc65C02($cpu) => $body,
+        }
+    };
+}
+
+/// The names of the hardware models, as accepted by `Cpu(model)`.
+const MODELS: [&str; 3] = ["2a03", "6502", "65c02"];
+
+/// Return the instruction set of the model with the given name.
+fn variant(model: &str) -> PyResult<Variant> {
+    match model {
+        "2a03" | "6502" => Ok(Variant::Nmos),
+        "65c02" => Ok(Variant::Cmos),
+        _ => Err(unknown(model)),
+    }
+}
+
+/// Create an error denoting an unknown model name.
+fn unknown(model: &str) -> PyErr {
+    PyValueError::new_err(format!(
+        "unknown model {model:?}, expected one of {}",
+        MODELS.join(", ")
+    ))
+}
+
+/// The memory of the system behind a pair of Python callables.
+struct Callbacks<'a, 'py> {
+    read: &'a Bound<'py, PyAny>,
+    write: &'a Bound<'py, PyAny>,
+}
+
+impl Callbacks<'_, '_> {
+    /// Service a bus access of the CPU.
+    fn service(&self, bus: &mut Bus) -> PyResult<()> {
+        if bus.write {
+            self.write.call1((bus.addr, bus.data))?;
+        } else {
+            bus.data = self.read.call1((bus.addr,))?.extract()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A cycle-accurate 6502 CPU.
+///
+/// The model is one of "2a03" (the NES's Ricoh 2A03, the default), "6502"
+/// (the original NMOS 6502) or "65c02" (the CMOS WDC 65C02S). All registers
+/// start zeroed, and the CPU starts with the reset sequence.
+#[pyclass(module = "chuck_cpu")]
+struct Cpu {
+    core: Core,
+}
+
+#[pymethods]
+impl Cpu {
+    #[new]
+    #[pyo3(signature = (model = "2a03"))]
+    fn new(model: &str) -> PyResult<Self> {
+        let core = match model {
+            "2a03" => Core::Ricoh2A03(chuck_cpu::Cpu::new()),
+            "6502" => Core::Nmos6502(chuck_cpu::Cpu::with_model(Nmos6502)),
+            "65c02" => Core::Wdc65C02(chuck_cpu::Cpu::with_model(Wdc65C02)),
+            _ => return Err(unknown(model)),
+        };
+
+        Ok(Self { core })
+    }
+
+    /// The name of the emulated model.
+    #[getter]
+    fn model(&self) -> &'static str {
+        match self.core {
+            Core::Ricoh2A03(_) => MODELS[0],
+            Core::Nmos6502(_) => MODELS[1],
+            Core::Wdc65C02(_) => MODELS[2],
+        }
+    }
+
+    /// Execute a single cycle, servicing its bus access with `read(addr)`
+    /// or `write(addr, data)`.
+    fn step(&mut self, read: &Bound<'_, PyAny>, write: &Bound<'_, PyAny>) -> PyResult<()> {
+        let mem = Callbacks { read, write };
+
+        with_cpu!(&mut self.core, cpu => {
+            cpu.step();
+            mem.service(&mut cpu.bus)
+        })
+    }
+
+    /// Execute the given number of cycles, servicing their bus accesses with
+    /// `read(addr)` and `write(addr, data)`.
+    fn run(
+        &mut self,
+        cycles: u64,
+        read: &Bound<'_, PyAny>,
+        write: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        let mem = Callbacks { read, write };
+
+        with_cpu!(&mut self.core, cpu => {
+            for _ in 0..cycles {
+                cpu.step();
+                mem.service(&mut cpu.bus)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Execute the CPU until the opcode of the next instruction is fetched,
+    /// servicing its bus accesses with `read(addr)` and `write(addr, data)`.
+    ///
+    /// If the CPU is jammed, stopped, waiting for an interrupt or held in
+    /// reset, only a single cycle is executed. This returns the number of
+    /// cycles that were executed.
+    fn step_instruction(
+        &mut self,
+        read: &Bound<'_, PyAny>,
+        write: &Bound<'_, PyAny>,
+    ) -> PyResult<u32> {
+        let mem = Callbacks { read, write };
+
+        with_cpu!(&mut self.core, cpu => {
+            let mut result = Ok(());
+            let cycles = cpu.step_instruction(|bus| {
+                if result.is_ok() {
+                    result = mem.service(bus);
+                }
+            });
+
+            result.map(|()| cycles)
+        })
+    }
+
+    /// Start the reset sequence with the next cycle.
+    fn reset(&mut self) {
+        with_cpu!(&mut self.core, cpu => cpu.reset());
+    }
+
+    /// Save the complete state of the CPU as a binary snapshot.
+    fn save<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut state = Vec::new();
+        with_cpu!(&self.core, cpu => cpu.save(&mut state))?;
+
+        Ok(PyBytes::new(py, &state))
+    }
+
+    /// Load the complete state of the CPU from a binary snapshot, raising a
+    /// `ValueError` (and leaving the CPU untouched) if it is malformed.
+    fn load(&mut self, state: &[u8]) -> PyResult<()> {
+        with_cpu!(&mut self.core, cpu => cpu.load(&mut &state[..]))
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// The accumulator, `A`.
+    #[getter]
+    fn a(&self) -> u8 {
+        self.regs().a
+    }
+
+    #[setter]
+    fn set_a(&mut self, a: u8) {
+        self.regs_mut().a = a;
+    }
+
+    /// The first index register, `X`.
+    #[getter]
+    fn x(&self) -> u8 {
+        self.regs().x
+    }
+
+    #[setter]
+    fn set_x(&mut self, x: u8) {
+        self.regs_mut().x = x;
+    }
+
+    /// The second index register, `Y`.
+    #[getter]
+    fn y(&self) -> u8 {
+        self.regs().y
+    }
+
+    #[setter]
+    fn set_y(&mut self, y: u8) {
+        self.regs_mut().y = y;
+    }
+
+    /// The stack pointer, `S`.
+    #[getter]
+    fn sp(&self) -> u8 {
+        self.regs().sp
+    }
+
+    #[setter]
+    fn set_sp(&mut self, sp: u8) {
+        self.regs_mut().sp = sp;
+    }
+
+    /// The program counter, `PC`.
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.regs().pc
+    }
+
+    #[setter]
+    fn set_pc(&mut self, pc: u16) {
+        self.regs_mut().pc = pc;
+    }
+
+    /// The flags register, `P`.
+    #[getter]
+    fn p(&self) -> u8 {
+        self.regs().flags.bits()
+    }
+
+    #[setter]
+    fn set_p(&mut self, p: u8) {
+        self.regs_mut().flags = Flags::from_bits_retain(p);
+    }
+
+    /// The I/O control pins, a combination of the `PIN_*` constants.
+    #[getter]
+    fn pins(&self) -> u8 {
+        with_cpu!(&self.core, cpu => cpu.pins.bits())
+    }
+
+    #[setter]
+    fn set_pins(&mut self, pins: u8) {
+        with_cpu!(&mut self.core, cpu => cpu.pins = Pins::from_bits_truncate(pins));
+    }
+
+    /// The current bus access, as an `(addr, data, write)` tuple.
+    #[getter]
+    fn bus(&self) -> (u16, u8, bool) {
+        let bus = with_cpu!(&self.core, cpu => &cpu.bus);
+        (bus.addr, bus.data, bus.write)
+    }
+
+    /// The number of cycles executed since the CPU was created.
+    #[getter]
+    fn cycles(&self) -> u64 {
+        with_cpu!(&self.core, cpu => cpu.cycles)
+    }
+
+    #[setter]
+    fn set_cycles(&mut self, cycles: u64) {
+        with_cpu!(&mut self.core, cpu => cpu.cycles = cycles);
+    }
+
+    /// Whether the CPU is jammed by one of the `JAM` opcodes of the NMOS 6502.
+    #[getter]
+    fn jammed(&self) -> bool {
+        with_cpu!(&self.core, cpu => cpu.is_jammed())
+    }
+
+    fn __repr__(&self) -> String {
+        let regs = self.regs();
+
+        format!(
+            "Cpu({:?}, pc=0x{:04x}, a=0x{:02x}, x=0x{:02x}, y=0x{:02x}, sp=0x{:02x}, p=0x{:02x})",
+            self.model(),
+            regs.pc,
+            regs.a,
+            regs.x,
+            regs.y,
+            regs.sp,
+            regs.flags.bits(),
+        )
+    }
+}
+
+impl Cpu {
+    /// Return the registers of the CPU.
+    fn regs(&self) -> &Registers {
+        with_cpu!(&self.core, cpu => &cpu.regs)
+    }
+
+    /// Return the registers of the CPU, mutably.
+    fn regs_mut(&mut self) -> &mut Registers {
+        with_cpu!(&mut self.core, cpu => &mut cpu.regs)
+    }
+}
+
+/// An environment playing a game, whose episodes start from the power-up.
+///
+/// The game is the contents of an iNES or NES 2.0 file. Every step holds the
+/// buttons for `frame_skip` frames and observes the last one, whose picture
+/// is colored with the default palette.
+#[pyclass(module = "chuck_cpu", unsendable)]
+struct Env {
+    env: chuck_nes::env::Env,
+    palette: Palette,
+}
+
+#[pymethods]
+impl Env {
+    #[new]
+    #[pyo3(signature = (rom, watched = None, frame_skip = 1))]
+    fn new(rom: &[u8], watched: Option<&Bound<'_, PyAny>>, frame_skip: u32) -> PyResult<Self> {
+        let rom = Rom::parse(rom).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let nes = Nes::from_rom(&rom).map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        let mut env = chuck_nes::env::Env::new(nes);
+        if let Some(watched) = watched {
+            env.set_watched(&addresses(watched)?);
+        }
+        env.set_frames_per_step(frames_per_step(frame_skip)?);
+
+        Ok(Self {
+            env,
+            palette: Palette::default(),
+        })
+    }
+
+    /// The watched addresses of the CPU bus, whose values are observed.
+    #[getter]
+    fn watched(&self) -> Vec<u16> {
+        self.env.watched().to_vec()
+    }
+
+    #[setter]
+    fn set_watched(&mut self, watched: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.env.set_watched(&addresses(watched)?);
+        Ok(())
+    }
+
+    /// The number of frames every step holds the buttons for.
+    #[getter]
+    const fn frame_skip(&self) -> u32 {
+        self.env.frames_per_step().get()
+    }
+
+    #[setter]
+    fn set_frame_skip(&mut self, frame_skip: u32) -> PyResult<()> {
+        self.env.set_frames_per_step(frames_per_step(frame_skip)?);
+        Ok(())
+    }
+
+    /// The number of frames since the start of the episode.
+    #[getter]
+    const fn frames(&self) -> u64 {
+        self.env.frames()
+    }
+
+    /// Start a new episode and return its first observation, as a
+    /// `(pixels, ram)` tuple.
+    ///
+    /// With a seed, the episodes start from a new power-up with the contents
+    /// of the RAM generated from the seed, otherwise from the same start as
+    /// before, raising a `ValueError` if the console no longer matches it.
+    #[pyo3(signature = (seed = None))]
+    fn reset<'py>(
+        &mut self,
+        py: Python<'py>,
+        seed: Option<u64>,
+    ) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+        let observation = match seed {
+            Some(seed) => self.env.reset_with_seed(seed),
+            None => self
+                .env
+                .reset()
+                .map_err(|err| PyValueError::new_err(err.to_string()))?,
+        };
+
+        Ok(observe(py, &self.palette, observation))
+    }
+
+    /// Start the episodes from the current point of the game, e.g. after
+    /// skipping its title screen.
+    fn mark_start(&mut self) {
+        self.env.mark_start();
+    }
+
+    /// Hold the given buttons of the two controllers for a step, and return
+    /// the observation after it, as a `(pixels, ram, frames)` tuple.
+    #[pyo3(signature = (first, second = 0))]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        first: u8,
+        second: u8,
+    ) -> (Bound<'py, PyBytes>, Bound<'py, PyBytes>, u64) {
+        let buttons = [first, second].map(ButtonState::from_bits_truncate);
+        let (observation, frames) = self.env.step(buttons);
+        let (pixels, ram) = observe(py, &self.palette, observation);
+
+        (pixels, ram, frames)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Env(frames={}, frame_skip={}, watched={:?})",
+            self.env.frames(),
+            self.frame_skip(),
+            self.env.watched(),
+        )
+    }
+}
+
+/// Return the addresses of an iterable of them.
+fn addresses(watched: &Bound<'_, PyAny>) -> PyResult<Vec<u16>> {
+    watched.try_iter()?.map(|addr| addr?.extract()).collect()
+}
+
+/// Return the number of frames per step of the given frame skip.
+fn frames_per_step(frame_skip: u32) -> PyResult<NonZeroU32> {
+    NonZeroU32::new(frame_skip)
+        .ok_or_else(|| PyValueError::new_err("the frame skip must be at least 1"))
+}
+
+/// Convert an observation into the RGB rows of the picture, colored with the
+/// given palette, and the values of the watched addresses.
+fn observe<'py>(
+    py: Python<'py>,
+    palette: &Palette,
+    observation: Observation<'_>,
+) -> (Bound<'py, PyBytes>, Bound<'py, PyBytes>) {
+    let pixels: Vec<u8> = observation
+        .pixels
+        .iter()
+        .flat_map(|&pixel| {
+            let [blue, green, red, _] = palette.rgb(pixel).to_le_bytes();
+            [red, green, blue]
+        })
+        .collect();
+
+    (PyBytes::new(py, &pixels), PyBytes::new(py, observation.ram))
+}
+
+/// Disassemble the given machine code, which starts at the given address,
+/// returning a list of `(addr, bytes, text)` tuples.
+///
+/// A trailing instruction that is cut off by the end of the code is left out.
+#[pyfunction]
+#[pyo3(signature = (code, addr = 0, model = "2a03"))]
+fn disassemble<'py>(
+    py: Python<'py>,
+    code: &[u8],
+    addr: u16,
+    model: &str,
+) -> PyResult<Vec<(u16, Bound<'py, PyBytes>, String)>> {
+    let variant = variant(model)?;
+    if code.len() > 0x10000 {
+        return Err(PyValueError::new_err("code larger than the address space"));
+    }
+
+    let peek = |at: u16| {
+        let offset = usize::from(at.wrapping_sub(addr));
+        code.get(offset).copied().unwrap_or_default()
+    };
+
+    let mut lines = Vec::new();
+    let (mut pc, mut offset) = (addr, 0);
+
+    while offset < code.len() {
+        let instr = disasm::disassemble(variant, pc, peek);
+        offset += usize::from(instr.info.len);
+        pc = instr.next_addr();
+
+        if offset > code.len() {
+            break;
+        }
+
+        lines.push((
+            instr.addr,
+            PyBytes::new(py, instr.bytes()),
+            instr.to_string(),
+        ));
+    }
+
+    Ok(lines)
+}
+
+/// Python bindings for Chuck's cycle-accurate 6502 CPU core.
+#[pymodule]
+#[pyo3(name = "chuck_cpu")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Cpu>()?;
+    m.add_class::<Env>()?;
+    m.add_function(wrap_pyfunction!(disassemble, m)?)?;
+
+    m.add("PIN_SYNC", Pins::SYNC.bits())?;
+    m.add("PIN_IRQ", Pins::IRQ.bits())?;
+    m.add("PIN_NMI", Pins::NMI.bits())?;
+    m.add("PIN_RDY", Pins::RDY.bits())?;
+    m.add("PIN_RES", Pins::RES.bits())?;
+    m.add("PIN_SO", Pins::SO.bits())?;
+
+    m.add("WIDTH", WIDTH)?;
+    m.add("HEIGHT", HEIGHT)?;
+    for (name, button) in ButtonState::all().iter_names() {
+        m.add(format!("BUTTON_{name}"), button.bits())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! The errors of malformed ROM files.
+
+use std::fmt;
+
+/// A section of a ROM file following the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// The 512-byte trainer.
+    Trainer,
+    /// The PRG-ROM.
+    PrgRom,
+    /// The CHR-ROM.
+    ChrRom,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Trainer => "trainer",
+            Self::PrgRom => "PRG-ROM",
+            Self::ChrRom => "CHR-ROM",
+        })
+    }
+}
+
+/// An error encountered while parsing a ROM file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The file is shorter than the 16-byte header.
+    MissingHeader,
+    /// The file doesn't start with the `NES<EOF>` identifier.
+    InvalidMagic,
+    /// The header declares a size which is too large to be addressed.
+    InvalidSize,
+    /// The header declares no PRG-ROM, which every cartridge needs.
+    EmptyPrgRom,
+    /// The file ends within one of its sections.
+    Truncated {
+        /// The truncated section.
+        section: Section,
+        /// The size of the section declared by the header, in bytes.
+        expected: usize,
+        /// The size of the section left in the file, in bytes.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader => f.write_str("the file is too short for an iNES header"),
+            Self::InvalidMagic => f.write_str("the file is not an iNES file"),
+            Self::InvalidSize => f.write_str("the header declares an invalid ROM size"),
+            Self::EmptyPrgRom => f.write_str("the header declares no PRG-ROM"),
+            Self::Truncated {
+                section,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the {section} is truncated, expected {expected} bytes but found {actual}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,264 @@
+//! The 16-byte header of an iNES or NES 2.0 file.
+//!
+//! ```no-run
+//! Byte  iNES                         NES 2.0
+//! 0-3   "NES" $1A                    "NES" $1A
+//! 4     PRG-ROM size (16 KiB)        PRG-ROM size, LSB
+//! 5     CHR-ROM size (8 KiB)         CHR-ROM size, LSB
+//! 6     Mapper D0-D3, flags          Mapper D0-D3, flags
+//! 7     Mapper D4-D7, console type   Mapper D4-D7, console type, identifier
+//! 8     PRG-RAM size (8 KiB)         Submapper, mapper D8-D11
+//! 9     TV system                    CHR-ROM size MSB, PRG-ROM size MSB
+//! 10    (unofficial)                 PRG-NVRAM and PRG-RAM shift counts
+//! 11    -                            CHR-NVRAM and CHR-RAM shift counts
+//! 12    -                            CPU/PPU timing
+//! 13    -                            Vs. System or extended console type
+//! 14    -                            Number of miscellaneous ROMs
+//! 15    -                            Default expansion device
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/INES>
+//! - <https://www.nesdev.org/wiki/NES_2.0>
+
+use crate::Error;
+
+/// The identifier at the start of every iNES file.
+const MAGIC: [u8; 4] = *b"NES\x1a";
+
+/// The format of the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The original iNES format.
+    ///
+    /// Old headers with garbage in bytes 7-15 (e.g. `DiskDude!`) are detected,
+    /// and only the lower 4 bits of their mapper number are used.
+    INes,
+    /// The NES 2.0 format, a backwards-compatible extension of iNES.
+    Nes2,
+}
+
+/// The hardwired arrangement of the nametables, see the `CIRAM A10` pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    /// The nametables are mirrored horizontally, i.e. arranged vertically.
+    Horizontal,
+    /// The nametables are mirrored vertically, i.e. arranged horizontally.
+    Vertical,
+    /// The cartridge provides extra VRAM for four distinct nametables.
+    FourScreen,
+}
+
+/// The region (CPU/PPU timing) the ROM was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The NTSC NES and the Famicom (RP2C02).
+    Ntsc,
+    /// The PAL NES (RP2C07).
+    Pal,
+    /// All regions.
+    Multi,
+    /// The Dendy, a Famicom clone with PAL video (UA6538).
+    Dendy,
+}
+
+/// The type of the console the ROM was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Console {
+    /// The NES or the Famicom.
+    Nes,
+    /// The Nintendo Vs. System, with the PPU type and the hardware type of
+    /// byte 13 for NES 2.0.
+    VsSystem(u8),
+    /// The Nintendo PlayChoice-10.
+    PlayChoice10,
+    /// An extended console type of byte 13 for NES 2.0, e.g. a Famiclone with
+    /// decimal mode.
+    Extended(u8),
+}
+
+/// A parsed iNES or NES 2.0 header.
+///
+/// The sizes are in bytes. For iNES headers, which can't declare RAM sizes,
+/// the usual defaults are used: 8 KiB of PRG-RAM (battery-backed if the
+/// battery flag is set), and 8 KiB of CHR-RAM if there is no CHR-ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// The format of the header.
+    pub format: Format,
+    /// The mapper number, `0`-`4095` (`0`-`255` for iNES).
+    pub mapper: u16,
+    /// The submapper number, `0`-`15` (always `0` for iNES).
+    pub submapper: u8,
+    /// The hardwired nametable arrangement, which is ignored by mappers that
+    /// control the mirroring.
+    pub mirroring: Mirroring,
+    /// A flag denoting if the cartridge has battery-backed memory (or other
+    /// non-volatile memory).
+    pub battery: bool,
+    /// A flag denoting if a 512-byte trainer precedes the PRG-ROM.
+    pub trainer: bool,
+    /// The size of the PRG-ROM.
+    pub prg_rom_size: usize,
+    /// The size of the CHR-ROM, `0` if the cartridge uses CHR-RAM.
+    pub chr_rom_size: usize,
+    /// The size of the volatile PRG-RAM.
+    pub prg_ram_size: usize,
+    /// The size of the non-volatile PRG-RAM (battery-backed or EEPROM).
+    pub prg_nvram_size: usize,
+    /// The size of the volatile CHR-RAM.
+    pub chr_ram_size: usize,
+    /// The size of the non-volatile CHR-RAM.
+    pub chr_nvram_size: usize,
+    /// The region the ROM was made for.
+    pub region: Region,
+    /// The console the ROM was made for.
+    pub console: Console,
+    /// The number of miscellaneous ROMs following the CHR-ROM (NES 2.0).
+    pub misc_roms: u8,
+    /// The default expansion device, e.g. a Zapper (NES 2.0).
+    pub expansion: u8,
+}
+
+impl Header {
+    /// Parse a header from the first 16 bytes of a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are too short, don't start with the iNES
+    /// identifier, or declare a size which can't be addressed.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes: &[u8; 16] = bytes
+            .get(..16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(Error::MissingHeader)?;
+
+        if bytes[..4] != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        if bytes[7] & 0x0c == 0x08 {
+            Self::parse_nes2(bytes)
+        } else {
+            Ok(Self::parse_ines(bytes))
+        }
+    }
+
+    /// Parse an iNES header.
+    fn parse_ines(bytes: &[u8; 16]) -> Self {
+        let flags = bytes[6];
+
+        // The upper bytes of old headers contain garbage, such as the name of
+        // the tool that dumped the ROM, instead of zeroes.
+        let archaic = bytes[7] & 0x0c == 0x04 || bytes[12..].iter().any(|&byte| byte != 0);
+        let high = if archaic { 0 } else { bytes[7] };
+
+        let prg_ram_size = if archaic || bytes[8] == 0 {
+            0x2000
+        } else {
+            usize::from(bytes[8]) * 0x2000
+        };
+        let battery = flags & 0x02 != 0;
+        let chr_rom_size = usize::from(bytes[5]) * 0x2000;
+
+        Self {
+            format: Format::INes,
+            mapper: u16::from((flags >> 4) | (high & 0xf0)),
+            submapper: 0,
+            mirroring: mirroring(flags),
+            battery,
+            trainer: flags & 0x04 != 0,
+            prg_rom_size: usize::from(bytes[4]) * 0x4000,
+            chr_rom_size,
+            prg_ram_size: if battery { 0 } else { prg_ram_size },
+            prg_nvram_size: if battery { prg_ram_size } else { 0 },
+            chr_ram_size: if chr_rom_size == 0 { 0x2000 } else { 0 },
+            chr_nvram_size: 0,
+            region: if !archaic && bytes[9] & 1 != 0 {
+                Region::Pal
+            } else {
+                Region::Ntsc
+            },
+            console: match high & 3 {
+                1 => Console::VsSystem(0),
+                2 => Console::PlayChoice10,
+                _ => Console::Nes,
+            },
+            misc_roms: 0,
+            expansion: 0,
+        }
+    }
+
+    /// Parse a NES 2.0 header.
+    fn parse_nes2(bytes: &[u8; 16]) -> Result<Self, Error> {
+        let flags = bytes[6];
+
+        Ok(Self {
+            format: Format::Nes2,
+            mapper: u16::from((flags >> 4) | (bytes[7] & 0xf0)) | (u16::from(bytes[8] & 0x0f) << 8),
+            submapper: bytes[8] >> 4,
+            mirroring: mirroring(flags),
+            battery: flags & 0x02 != 0,
+            trainer: flags & 0x04 != 0,
+            prg_rom_size: rom_size(bytes[4], bytes[9] & 0x0f, 0x4000)?,
+            chr_rom_size: rom_size(bytes[5], bytes[9] >> 4, 0x2000)?,
+            prg_ram_size: ram_size(bytes[10] & 0x0f),
+            prg_nvram_size: ram_size(bytes[10] >> 4),
+            chr_ram_size: ram_size(bytes[11] & 0x0f),
+            chr_nvram_size: ram_size(bytes[11] >> 4),
+            region: match bytes[12] & 3 {
+                0 => Region::Ntsc,
+                1 => Region::Pal,
+                2 => Region::Multi,
+                _ => Region::Dendy,
+            },
+            console: match bytes[7] & 3 {
+                0 => Console::Nes,
+                1 => Console::VsSystem(bytes[13]),
+                2 => Console::PlayChoice10,
+                _ => Console::Extended(bytes[13] & 0x0f),
+            },
+            misc_roms: bytes[14] & 3,
+            expansion: bytes[15] & 0x3f,
+        })
+    }
+}
+
+/// Return the hardwired nametable arrangement of flags 6.
+const fn mirroring(flags: u8) -> Mirroring {
+    if flags & 0x08 != 0 {
+        Mirroring::FourScreen
+    } else if flags & 0x01 != 0 {
+        Mirroring::Vertical
+    } else {
+        Mirroring::Horizontal
+    }
+}
+
+/// Return the size of a ROM of a NES 2.0 header, given in units or in the
+/// exponent-multiplier notation (if the upper nibble is `$F`).
+fn rom_size(lsb: u8, msb: u8, unit: usize) -> Result<usize, Error> {
+    if msb == 0x0f {
+        // The LSB holds `EEEEEEMM`, for a size of `2^E * (MM * 2 + 1)`.
+        let exponent = u32::from(lsb >> 2);
+        let multiplier = usize::from(lsb & 3) * 2 + 1;
+
+        1usize
+            .checked_shl(exponent)
+            .filter(|size| size.leading_zeros() >= 3)
+            .map(|size| size * multiplier)
+            .ok_or(Error::InvalidSize)
+    } else {
+        Ok((usize::from(msb) << 8 | usize::from(lsb)) * unit)
+    }
+}
+
+/// Return the size of a RAM of a NES 2.0 header, given as a shift count.
+const fn ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
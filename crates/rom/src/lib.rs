@@ -0,0 +1,129 @@
+//! A parser of NES ROM files in the iNES and NES 2.0 formats.
+//!
+//! An iNES file is a 16-byte [`Header`], followed by an optional 512-byte
+//! trainer, the PRG-ROM (the program, on the CPU bus), the CHR-ROM (the
+//! graphics, on the PPU bus) and, for some formats, miscellaneous ROMs. The
+//! header describes the board of the cartridge, i.e. its mapper, its RAM and
+//! its nametable arrangement, which is everything the mapper layer of the
+//! system needs to emulate the cartridge.
+//!
+//! ```
+//! # use chuck_rom::{Format, Mirroring, Rom};
+//! let mut file = b"NES\x1a\x01\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+//! file.resize(16 + 0x4000 + 0x2000, 0);
+//!
+//! let rom = Rom::parse(&file).unwrap();
+//!
+//! assert_eq!(rom.header().format, Format::INes);
+//! assert_eq!(rom.header().mapper, 0);
+//! assert_eq!(rom.header().mirroring, Mirroring::Vertical);
+//! assert_eq!(rom.prg().len(), 0x4000);
+//! assert_eq!(rom.chr().len(), 0x2000);
+//! ```
+//!
+//! Music files in the NSF and `NSFe` formats are parsed by [`nsf`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/INES>
+//! - <https://www.nesdev.org/wiki/NES_2.0>
+
+mod error;
+mod header;
+pub mod nsf;
+
+pub use error::{Error, Section};
+pub use header::{Console, Format, Header, Mirroring, Region};
+
+/// A parsed ROM file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    /// The header.
+    header: Header,
+    /// The trainer, if any.
+    trainer: Option<Box<[u8]>>,
+    /// The PRG-ROM.
+    prg: Box<[u8]>,
+    /// The CHR-ROM.
+    chr: Box<[u8]>,
+    /// The rest of the file, e.g. the miscellaneous ROMs.
+    misc: Box<[u8]>,
+}
+
+impl Rom {
+    /// Parse a ROM file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header is malformed, declares no PRG-ROM, or
+    /// declares sections which don't fit into the rest of the file.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let header = Header::parse(bytes)?;
+        if header.prg_rom_size == 0 {
+            return Err(Error::EmptyPrgRom);
+        }
+
+        let mut rest = &bytes[16..];
+        let mut take = |section, size: usize| {
+            if rest.len() < size {
+                return Err(Error::Truncated {
+                    section,
+                    expected: size,
+                    actual: rest.len(),
+                });
+            }
+
+            let (data, tail) = rest.split_at(size);
+            rest = tail;
+            Ok(Box::<[u8]>::from(data))
+        };
+
+        let trainer = if header.trainer {
+            Some(take(Section::Trainer, 512)?)
+        } else {
+            None
+        };
+        let prg = take(Section::PrgRom, header.prg_rom_size)?;
+        let chr = take(Section::ChrRom, header.chr_rom_size)?;
+
+        Ok(Self {
+            header,
+            trainer,
+            prg,
+            chr,
+            misc: rest.into(),
+        })
+    }
+
+    /// Return the header.
+    #[must_use]
+    pub const fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Return the 512-byte trainer, which is loaded into `$7000`-`$71FF`, if
+    /// any.
+    #[must_use]
+    pub fn trainer(&self) -> Option<&[u8]> {
+        self.trainer.as_deref()
+    }
+
+    /// Return the PRG-ROM.
+    #[must_use]
+    pub fn prg(&self) -> &[u8] {
+        &self.prg
+    }
+
+    /// Return the CHR-ROM, which is empty if the cartridge uses CHR-RAM.
+    #[must_use]
+    pub fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    /// Return the data following the CHR-ROM, e.g. the miscellaneous ROMs of
+    /// NES 2.0 or the PlayChoice-10 ROMs of iNES.
+    #[must_use]
+    pub fn misc(&self) -> &[u8] {
+        &self.misc
+    }
+}
@@ -0,0 +1,394 @@
+//! A parser of NES music files in the NSF and `NSFe` formats.
+//!
+//! An NSF file is a 128-byte header followed by the program, which consists
+//! of an `INIT` routine starting a track and a `PLAY` routine called at a
+//! fixed rate (usually the frame rate) to play it. `NSFe` files hold the same
+//! information in chunks, together with the names and lengths of the tracks,
+//! which NSF2 files may append to the program as well.
+//!
+//! ```
+//! # use chuck_rom::nsf::Nsf;
+//! let mut file = b"NESM\x1a\x01\x03\x01\x00\x80\x00\x80\x03\x80".to_vec();
+//! file.resize(0x80, 0);
+//! file[0x0e..0x13].copy_from_slice(b"Title");
+//! file[0x6e..0x70].copy_from_slice(&16_639u16.to_le_bytes());
+//! file.extend([0xa9, 0x00, 0x60, 0x60]);
+//!
+//! let nsf = Nsf::parse(&file).unwrap();
+//!
+//! assert_eq!(nsf.title, "Title");
+//! assert_eq!(nsf.songs, 3);
+//! assert_eq!(nsf.start, 0);
+//! assert_eq!((nsf.load, nsf.init, nsf.play), (0x8000, 0x8000, 0x8003));
+//! assert_eq!(nsf.banks, None);
+//! assert_eq!(nsf.data.len(), 4);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/NSF>
+//! - <https://www.nesdev.org/wiki/NSFe>
+//! - <https://www.nesdev.org/wiki/NSF2>
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::Region;
+
+/// The size of the header of an NSF file.
+const HEADER_SIZE: usize = 0x80;
+
+/// The default rate of the `PLAY` routine on NTSC, in microseconds.
+const NTSC_SPEED: u16 = 16_639;
+
+/// The default rate of the `PLAY` routine on PAL, in microseconds.
+const PAL_SPEED: u16 = 19_997;
+
+/// The expansion sound chips a music file uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expansion(pub u8);
+
+impl Expansion {
+    /// The Konami VRC6.
+    pub const VRC6: Self = Self(1 << 0);
+    /// The Konami VRC7.
+    pub const VRC7: Self = Self(1 << 1);
+    /// The Famicom Disk System.
+    pub const FDS: Self = Self(1 << 2);
+    /// The Nintendo MMC5.
+    pub const MMC5: Self = Self(1 << 3);
+    /// The Namco 163.
+    pub const N163: Self = Self(1 << 4);
+    /// The Sunsoft 5B.
+    pub const S5B: Self = Self(1 << 5);
+
+    /// Check if all chips of `other` are used.
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if no expansion chips are used.
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// The information about a track of a music file, which is only known for
+/// `NSFe` and NSF2 files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Track {
+    /// The name of the track.
+    pub name: Option<String>,
+    /// The length of the track, before it fades out.
+    pub length: Option<Duration>,
+    /// The duration of the fade out after the length.
+    pub fade: Option<Duration>,
+}
+
+/// A parsed music file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nsf {
+    /// The number of tracks (songs).
+    pub songs: u8,
+    /// The track to start with, counting from 0.
+    pub start: u8,
+    /// The address the program is loaded at, within `$8000`-`$FFFF`, or
+    /// within the first bank (`$8000`-`$8FFF`) if the program is bank
+    /// switched.
+    pub load: u16,
+    /// The address of the `INIT` routine.
+    pub init: u16,
+    /// The address of the `PLAY` routine.
+    pub play: u16,
+    /// The title of the music.
+    pub title: String,
+    /// The artist.
+    pub artist: String,
+    /// The copyright holder.
+    pub copyright: String,
+    /// The person who ripped the music from the game, if known.
+    pub ripper: Option<String>,
+    /// The rates of the `PLAY` routine on NTSC, PAL and the Dendy, in
+    /// microseconds.
+    pub speeds: [u16; 3],
+    /// The initial 4 KiB banks of `$8000`-`$FFFF`, if the program is bank
+    /// switched by writes to `$5FF8`-`$5FFF`.
+    pub banks: Option<[u8; 8]>,
+    /// The region the music was made for.
+    pub region: Region,
+    /// The expansion sound chips the music uses.
+    pub expansion: Expansion,
+    /// The information about the tracks, one for every track if known.
+    pub tracks: Vec<Track>,
+    /// The order in which the tracks should be played, if given.
+    pub playlist: Option<Vec<u8>>,
+    /// The program.
+    pub data: Box<[u8]>,
+}
+
+impl Nsf {
+    /// Parse an NSF, NSF2 or `NSFe` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is truncated, has none of the formats, or
+    /// lacks the information needed to play it.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let nsf = match bytes.get(..4) {
+            Some(b"NESM") if bytes.get(4) == Some(&0x1a) => Self::parse_nsf(bytes)?,
+            Some(b"NSFE") => Self::parse_nsfe(&bytes[4..])?,
+            Some(_) => return Err(Error::InvalidMagic),
+            None => return Err(Error::Truncated),
+        };
+
+        if nsf.banks.is_none() && nsf.load < 0x8000 {
+            return Err(Error::InvalidLoadAddress(nsf.load));
+        }
+
+        Ok(nsf)
+    }
+
+    /// Parse an NSF file, including the chunks appended by NSF2.
+    fn parse_nsf(bytes: &[u8]) -> Result<Self, Error> {
+        let header = bytes.get(..HEADER_SIZE).ok_or(Error::Truncated)?;
+        let word = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+        let text = |offset: usize| text(&header[offset..offset + 32]);
+
+        let banks: [u8; 8] = header[0x70..0x78].try_into().unwrap_or_default();
+        let mut nsf = Self {
+            songs: header[0x06],
+            start: header[0x07].saturating_sub(1),
+            load: word(0x08),
+            init: word(0x0a),
+            play: word(0x0c),
+            title: text(0x0e),
+            artist: text(0x2e),
+            copyright: text(0x4e),
+            ripper: None,
+            speeds: [word(0x6e), word(0x78), word(0x78)],
+            banks: (banks != [0; 8]).then_some(banks),
+            region: region(header[0x7a]),
+            expansion: Expansion(header[0x7b]),
+            tracks: Vec::new(),
+            playlist: None,
+            data: Box::new([]),
+        };
+
+        // NSF2 files declare the length of the program if chunks of metadata
+        // follow it.
+        let length = u32::from_le_bytes([header[0x7d], header[0x7e], header[0x7f], 0]);
+        let rest = &bytes[HEADER_SIZE..];
+        match usize::try_from(length) {
+            Ok(length) if header[0x05] >= 2 && length != 0 => {
+                let data = rest.get(..length).ok_or(Error::Truncated)?;
+                nsf.data = data.into();
+                nsf.parse_chunks(&rest[length..], false)?;
+            }
+            _ => nsf.data = rest.into(),
+        }
+
+        nsf.fill_defaults();
+        Ok(nsf)
+    }
+
+    /// Parse an `NSFe` file, after its identifier.
+    fn parse_nsfe(bytes: &[u8]) -> Result<Self, Error> {
+        let mut nsf = Self {
+            songs: 0,
+            start: 0,
+            load: 0,
+            init: 0,
+            play: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ripper: None,
+            speeds: [0; 3],
+            banks: None,
+            region: Region::Ntsc,
+            expansion: Expansion::default(),
+            tracks: Vec::new(),
+            playlist: None,
+            data: Box::new([]),
+        };
+
+        nsf.parse_chunks(bytes, true)?;
+        nsf.fill_defaults();
+        Ok(nsf)
+    }
+
+    /// Parse the chunks of an `NSFe` file or of the metadata of an NSF2 file,
+    /// which only the former must start with an `INFO` chunk and contain a
+    /// `DATA` chunk.
+    fn parse_chunks(&mut self, mut bytes: &[u8], nsfe: bool) -> Result<(), Error> {
+        let (mut info, mut data) = (!nsfe, !nsfe);
+
+        loop {
+            let header = bytes.get(..8).ok_or(Error::Truncated)?;
+            let size = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let id: [u8; 4] = [header[4], header[5], header[6], header[7]];
+            let chunk = usize::try_from(size)
+                .ok()
+                .and_then(|size| bytes.get(8..8 + size))
+                .ok_or(Error::Truncated)?;
+            bytes = &bytes[8 + chunk.len()..];
+
+            match &id {
+                b"INFO" if nsfe => {
+                    self.parse_info(chunk)?;
+                    info = true;
+                }
+                _ if !info => return Err(Error::MissingChunk(*b"INFO")),
+                b"DATA" if nsfe => {
+                    self.data = chunk.into();
+                    data = true;
+                }
+                b"BANK" => {
+                    let mut banks = [0; 8];
+                    for (bank, &data) in banks.iter_mut().zip(chunk) {
+                        *bank = data;
+                    }
+                    self.banks = Some(banks);
+                }
+                b"RATE" => {
+                    for (speed, rate) in self.speeds.iter_mut().zip(chunk.chunks_exact(2)) {
+                        *speed = u16::from_le_bytes([rate[0], rate[1]]);
+                    }
+                }
+                b"auth" => {
+                    let mut strings = chunk.split(|&byte| byte == 0).map(text);
+                    for field in [&mut self.title, &mut self.artist, &mut self.copyright] {
+                        *field = strings.next().unwrap_or_default();
+                    }
+                    self.ripper = strings.next();
+                }
+                b"tlbl" => {
+                    let names = chunk.split(|&byte| byte == 0).map(text);
+                    for (track, name) in self.tracks_mut().iter_mut().zip(names) {
+                        track.name = Some(name);
+                    }
+                }
+                b"time" | b"fade" => {
+                    let times = chunk.chunks_exact(4).map(|time| {
+                        // Negative times are unknown.
+                        let time = i32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+                        u64::try_from(time).ok().map(Duration::from_millis)
+                    });
+                    for (track, time) in self.tracks_mut().iter_mut().zip(times) {
+                        if &id == b"time" {
+                            track.length = time;
+                        } else {
+                            track.fade = time;
+                        }
+                    }
+                }
+                b"plst" => self.playlist = Some(chunk.to_vec()),
+                b"NEND" => break,
+                // The chunks starting with an uppercase letter must be
+                // understood to play the music.
+                [b'A'..=b'Z', ..] => return Err(Error::UnknownChunk(id)),
+                _ => {}
+            }
+        }
+
+        if data {
+            Ok(())
+        } else {
+            Err(Error::MissingChunk(*b"DATA"))
+        }
+    }
+
+    /// Parse the `INFO` chunk of an `NSFe` file.
+    fn parse_info(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        let info = chunk.get(..8).ok_or(Error::Truncated)?;
+        let word = |offset: usize| u16::from_le_bytes([info[offset], info[offset + 1]]);
+
+        self.load = word(0);
+        self.init = word(2);
+        self.play = word(4);
+        self.region = region(info[6]);
+        self.expansion = Expansion(info[7]);
+        self.songs = chunk.get(8).copied().unwrap_or(1);
+        self.start = chunk.get(9).copied().unwrap_or(0);
+        Ok(())
+    }
+
+    /// Return the information about the tracks, for each of them.
+    fn tracks_mut(&mut self) -> &mut [Track] {
+        self.tracks
+            .resize(usize::from(self.songs), Track::default());
+        &mut self.tracks
+    }
+
+    /// Fill in the defaults of the information which is missing.
+    fn fill_defaults(&mut self) {
+        let [ntsc, pal, dendy] = &mut self.speeds;
+        for (speed, default) in [(ntsc, NTSC_SPEED), (pal, PAL_SPEED)] {
+            if *speed == 0 {
+                *speed = default;
+            }
+        }
+        if *dendy == 0 {
+            *dendy = *pal;
+        }
+
+        self.tracks_mut();
+    }
+}
+
+/// Decode the region of an NSF header or an `NSFe` `INFO` chunk.
+fn region(flags: u8) -> Region {
+    match flags & 3 {
+        0 => Region::Ntsc,
+        1 => Region::Pal,
+        _ => Region::Multi,
+    }
+}
+
+/// Decode a null-terminated string, which should be ASCII (or UTF-8 in `NSFe`
+/// files).
+fn text(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// An error encountered while parsing a music file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The file ends within its header or within a chunk.
+    Truncated,
+    /// The file doesn't start with the `NESM<EOF>` or `NSFE` identifiers.
+    InvalidMagic,
+    /// A chunk needed to play the music is missing.
+    MissingChunk([u8; 4]),
+    /// The file contains a chunk which must be understood to play the music,
+    /// but isn't known.
+    UnknownChunk([u8; 4]),
+    /// The program isn't bank switched but loaded below `$8000`.
+    InvalidLoadAddress(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => f.write_str("the file is truncated"),
+            Self::InvalidMagic => f.write_str("the file is neither an NSF nor an NSFe file"),
+            Self::MissingChunk(id) => {
+                write!(f, "the {} chunk is missing", String::from_utf8_lossy(id))
+            }
+            Self::UnknownChunk(id) => {
+                write!(f, "the {} chunk is unknown", String::from_utf8_lossy(id))
+            }
+            Self::InvalidLoadAddress(addr) => {
+                write!(f, "the program can't be loaded at ${addr:04X}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,108 @@
+//! The headless running of test ROMs declared in a manifest, see
+//! [`manifest`], shared by the `chuck-test-runner` binary and the accuracy
+//! report of the frontend.
+//!
+//! The tests run in parallel, each on a console of its own, see
+//! [`run_parallel`], and their [`Outcome`]s take the known failures of the
+//! manifest into account.
+
+pub mod manifest;
+pub mod run;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use manifest::Test;
+
+/// The outcome of a test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The test passed.
+    Passed,
+    /// The test failed, as described.
+    Failed(String),
+    /// The test failed as expected.
+    KnownFailure,
+    /// The test passed although it was expected to fail.
+    Fixed,
+}
+
+impl Outcome {
+    /// Return the outcome of a test with the given result, see [`run::run`].
+    #[must_use]
+    pub fn new(test: &Test, result: Result<(), String>) -> Self {
+        match (result, test.known_failure) {
+            (Ok(()), false) => Self::Passed,
+            (Ok(()), true) => Self::Fixed,
+            (Err(failure), false) => Self::Failed(failure),
+            (Err(_), true) => Self::KnownFailure,
+        }
+    }
+
+    /// Return the short description of the outcome, as printed for a test.
+    #[must_use]
+    pub const fn describe(&self) -> &'static str {
+        match self {
+            Self::Passed => "ok",
+            Self::Failed(_) => "FAILED",
+            Self::KnownFailure => "known failure",
+            Self::Fixed => "fixed",
+        }
+    }
+}
+
+/// Call the given function for every test, across the given number of
+/// threads, and return the results in the order of the tests.
+///
+/// The results are passed to `done` as the tests finish, with the index of
+/// their test, e.g. to print the progress.
+pub fn run_parallel<T: Send>(
+    tests: &[&Test],
+    jobs: usize,
+    run: impl Fn(&Test) -> T + Sync,
+    mut done: impl FnMut(usize, &T),
+) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<_> = tests.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(tests.len()) {
+            let sender = sender.clone();
+            let (next, run) = (&next, &run);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&test) = tests.get(index) else { break };
+                if sender.send((index, run(test))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, result) in receiver {
+            done(index, &result);
+            results[index] = Some(result);
+        }
+    });
+
+    results.into_iter().flatten().collect()
+}
+
+/// Return the path of the screenshot of a test in the given directory, named
+/// after the test with the characters which aren't safe in file names
+/// replaced.
+#[must_use]
+pub fn screenshot(dir: &Path, test: &Test) -> PathBuf {
+    let name: String = test
+        .name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+
+    dir.join(format!("{name}.png"))
+}
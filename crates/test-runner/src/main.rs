@@ -0,0 +1,138 @@
+//! A headless runner of test ROMs, which runs every ROM declared in a
+//! manifest and checks its result, see [`chuck_test_runner::manifest`].
+//!
+//! ```sh
+//! cargo run --release -p chuck-test-runner -- \
+//!     crates/test-runner/nes-test-roms.toml --roms path/to/nes-test-roms
+//! ```
+//!
+//! The tests run in parallel, and the runner fails if any test fails, except
+//! for the known failures. A known failure which passes is reported as fixed,
+//! so the manifest can be updated, and a failed hash check prints the actual
+//! hash, for new or intentionally changed pictures.
+//!
+//! With `--screenshots <DIR>`, the last picture of every failed test is
+//! saved into the directory as a PNG file named after the test, e.g. to
+//! upload it along with the results of a CI run. A failed screenshot check
+//! saves the picture of every differing screenshot as well, along with a
+//! picture of the differing pixels, see [`chuck_test_runner::manifest`].
+
+use std::error::Error;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+
+use chuck_test_runner::manifest::Manifest;
+use chuck_test_runner::{run, run_parallel, screenshot, Outcome};
+use clap::Parser;
+
+/// Run test ROMs headlessly and check their results.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The manifest declaring the tests.
+    manifest: PathBuf,
+    /// The directory the paths of the ROMs are relative to, by default the
+    /// directory of the manifest.
+    #[arg(long)]
+    roms: Option<PathBuf>,
+    /// Only run the tests whose names contain the given text.
+    #[arg(long)]
+    filter: Option<String>,
+    /// The number of tests to run in parallel, by default the number of
+    /// CPUs.
+    #[arg(long)]
+    jobs: Option<NonZeroUsize>,
+    /// The directory to save the pictures of the failed tests into.
+    #[arg(long, value_name = "DIR")]
+    screenshots: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Run the tests, returning `false` if any of them failed.
+fn run(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let manifest: Manifest = toml::from_str(&fs::read_to_string(&args.manifest)?)?;
+    let roms = args.roms.clone().unwrap_or_else(|| {
+        let dir = args.manifest.parent().unwrap_or(&args.manifest);
+        dir.to_path_buf()
+    });
+
+    let tests: Vec<_> = manifest
+        .tests
+        .iter()
+        .filter(|test| args.filter.as_ref().is_none_or(|f| test.name.contains(f)))
+        .collect();
+    let jobs = args
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    if let Some(dir) = &args.screenshots {
+        fs::create_dir_all(dir)?;
+    }
+
+    println!("running {} tests", tests.len());
+
+    let outcomes = run_parallel(
+        &tests,
+        jobs,
+        |test| {
+            let screenshot = args.screenshots.as_deref().map(|dir| screenshot(dir, test));
+            Outcome::new(test, run::run(test, &roms, screenshot.as_deref()))
+        },
+        |index, outcome| println!("test {} ... {}", tests[index].name, outcome.describe()),
+    );
+
+    // Report in the order of the manifest.
+    let outcomes: Vec<_> = tests.iter().zip(outcomes).collect();
+    let count = |pattern: fn(&Outcome) -> bool| {
+        outcomes
+            .iter()
+            .filter(|(_, outcome)| pattern(outcome))
+            .count()
+    };
+    let failed = count(|outcome| matches!(outcome, Outcome::Failed(_)));
+
+    if failed > 0 {
+        println!("\nfailures:");
+        for (test, outcome) in &outcomes {
+            if let Outcome::Failed(failure) = outcome {
+                println!("    {}: {failure}", test.name);
+            }
+        }
+    }
+
+    let fixed: Vec<_> = outcomes
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Outcome::Fixed))
+        .map(|(test, _)| test.name.as_str())
+        .collect();
+    if !fixed.is_empty() {
+        println!("\nfixed, no longer known failures:");
+        for name in &fixed {
+            println!("    {name}");
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {failed} failed; {} known failures; {} fixed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        count(|outcome| matches!(outcome, Outcome::Passed)),
+        count(|outcome| matches!(outcome, Outcome::KnownFailure)),
+        fixed.len(),
+    );
+
+    Ok(failed == 0)
+}
@@ -0,0 +1,196 @@
+//! The manifest declaring the test ROMs and their expected results, a TOML
+//! file with a `[[test]]` table for every test:
+//!
+//! ```toml
+//! # Passes once the ROM reports the status 0 at $6000.
+//! [[test]]
+//! rom = "apu_test/apu_test.nes"
+//! status = 0
+//!
+//! # Passes if the picture hashes to the given hash after 60 frames.
+//! [[test]]
+//! rom = "scanline/scanline.nes"
+//! frames = 60
+//! hash = "9f2a6c1de03b7a58"
+//!
+//! # Passes once the screen shows the given text.
+//! [[test]]
+//! name = "branch timing"
+//! rom = "branch_timing_tests/1.Branch_Basics.nes"
+//! text = "PASSED"
+//! known_failure = true
+//!
+//! # Passes if the pictures after the given frames look like the PNG files,
+//! # while the input of the movie is played, e.g. for the regression tests of
+//! # a homebrew game.
+//! [[test]]
+//! rom = "game/game.nes"
+//! movie = "game/game.fm2"
+//! screenshots = [
+//!     { frame = 60, png = "game/title.png" },
+//!     { frame = 600, png = "game/level-1.png" },
+//! ]
+//! tolerance = { channel = 8, pixels = 16 }
+//! ```
+//!
+//! The `frames` of the status and text checks are the number of frames after
+//! which the ROM is considered to hang. The screenshots are compared in the
+//! colors of the default palette, where a pixel differs if any of its color
+//! channels differs by more than the `channel` of the tolerance, and the
+//! check fails if more than its `pixels` differ (both 0 by default). The
+//! movie, which is optional, is played from a power-up like while it was
+//! recorded, see `chuck_nes::movie`. The paths of the PNG files and of the
+//! movie are relative to the directory of the ROMs, like the ROM.
+//!
+//! A known failure is a test which is expected to fail, so it doesn't fail
+//! the run, while it's reported once it passes.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// The number of frames after which a ROM with a status or text check is
+/// considered to hang, unless the test declares another number.
+const MAX_FRAMES: u32 = 3600;
+
+/// A manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The tests.
+    #[serde(default, rename = "test")]
+    pub tests: Vec<Test>,
+}
+
+/// A test of a ROM.
+#[derive(Debug)]
+pub struct Test {
+    /// The name of the test, by default the path of its ROM.
+    pub name: String,
+    /// The path of the ROM, relative to the directory of the ROMs.
+    pub rom: PathBuf,
+    /// The number of frames to run, see the [module documentation](self),
+    /// which is the frame of the last screenshot for a screenshot check.
+    pub frames: u32,
+    /// The check of the result.
+    pub check: Check,
+    /// The path of the movie whose input is played, if any.
+    pub movie: Option<PathBuf>,
+    /// The tolerance of the comparison of the screenshots.
+    pub tolerance: Tolerance,
+    /// A flag denoting if the test is expected to fail.
+    pub known_failure: bool,
+}
+
+/// The check of the result of a test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Check {
+    /// The status reported by a ROM using the protocol of blargg's test ROMs,
+    /// see [`crate::run::status`].
+    Status(u8),
+    /// The 64-bit FNV-1a hash of the picture, see [`crate::run::hash`].
+    Hash(u64),
+    /// A text shown on the screen.
+    Text(String),
+    /// The pictures after some frames.
+    Screenshots(Vec<Screenshot>),
+}
+
+/// An expected picture after a frame.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Screenshot {
+    /// The number of frames after which the picture is compared.
+    pub frame: u32,
+    /// The path of the PNG file with the expected picture, relative to the
+    /// directory of the ROMs.
+    pub png: PathBuf,
+}
+
+/// The tolerance of the comparison of screenshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tolerance {
+    /// The largest difference of a color channel of a pixel which is ignored.
+    pub channel: u8,
+    /// The largest number of differing pixels of a picture.
+    pub pixels: usize,
+}
+
+/// A test as written in the manifest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    name: Option<String>,
+    rom: PathBuf,
+    frames: Option<u32>,
+    status: Option<u8>,
+    hash: Option<String>,
+    text: Option<String>,
+    screenshots: Option<Vec<Screenshot>>,
+    movie: Option<PathBuf>,
+    tolerance: Option<Tolerance>,
+    #[serde(default)]
+    known_failure: bool,
+}
+
+impl<'de> Deserialize<'de> for Test {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let entry = Entry::deserialize(deserializer)?;
+        let check = match (entry.status, entry.hash, entry.text, entry.screenshots) {
+            (Some(status), None, None, None) => Check::Status(status),
+            (None, Some(hash), None, None) => Check::Hash(
+                u64::from_str_radix(&hash, 16)
+                    .map_err(|_| D::Error::custom(format!("invalid hash `{hash}`")))?,
+            ),
+            (None, None, Some(text), None) => Check::Text(text),
+            (None, None, None, Some(screenshots)) if !screenshots.is_empty() => {
+                Check::Screenshots(screenshots)
+            }
+            _ => {
+                return Err(D::Error::custom(
+                    "a test needs exactly one of `status`, `hash`, `text` and `screenshots`",
+                ))
+            }
+        };
+        if !matches!(check, Check::Screenshots(_))
+            && (entry.movie.is_some() || entry.tolerance.is_some())
+        {
+            return Err(D::Error::custom(
+                "only a test with `screenshots` takes a `movie` and a `tolerance`",
+            ));
+        }
+
+        let frames = match (&check, entry.frames) {
+            (Check::Screenshots(_), Some(_)) => {
+                return Err(D::Error::custom(
+                    "a test with `screenshots` runs until the last one, without `frames`",
+                ))
+            }
+            (_, Some(frames)) => frames,
+            (Check::Hash(_), None) => {
+                return Err(D::Error::custom("a test with a `hash` needs `frames`"))
+            }
+            (Check::Screenshots(screenshots), None) => screenshots
+                .iter()
+                .map(|screenshot| screenshot.frame)
+                .max()
+                .unwrap_or_default(),
+            _ => MAX_FRAMES,
+        };
+
+        Ok(Self {
+            name: entry
+                .name
+                .unwrap_or_else(|| entry.rom.display().to_string()),
+            rom: entry.rom,
+            frames,
+            check,
+            movie: entry.movie,
+            tolerance: entry.tolerance.unwrap_or_default(),
+            known_failure: entry.known_failure,
+        })
+    }
+}
@@ -0,0 +1,318 @@
+//! The headless run of a test ROM and the checks of its result.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chuck_nes::movie::Movie;
+use chuck_nes::{Nes, HEIGHT, WIDTH};
+use chuck_rom::Rom;
+use chuck_video::palette::Palette;
+use chuck_video::png;
+
+use crate::manifest::{Check, Screenshot, Test, Tolerance};
+
+/// The signature at `$6001`-`$6003` once the status at `$6000` is valid.
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+/// The status of a ROM which is still running.
+const RUNNING: u8 = 0x80;
+
+/// The status of a ROM which needs the reset button to be pressed.
+const NEEDS_RESET: u8 = 0x81;
+
+/// The number of frames until the reset button is pressed, since it must be
+/// pressed after at least 100 ms.
+const RESET_DELAY: u32 = 10;
+
+/// Run a test, returning a description of its failure.
+///
+/// If the test fails and a path for its screenshot is given, the picture of
+/// the last frame is saved there, along with the pictures of the differing
+/// screenshots of a screenshot check, named after their frames, e.g.
+/// `test-60.png` and `test-60-diff.png`.
+///
+/// # Errors
+///
+/// Returns the description of the failure if the ROM can't be read or run,
+/// or if the check fails.
+pub fn run(test: &Test, roms: &Path, screenshot: Option<&Path>) -> Result<(), String> {
+    let path = roms.join(&test.rom);
+    let bytes = fs::read(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let rom = Rom::parse(&bytes).map_err(|err| err.to_string())?;
+    let mut nes = Nes::from_rom(&rom).map_err(|err| err.to_string())?;
+
+    let result = match &test.check {
+        Check::Screenshots(screenshots) => compare(test, screenshots, roms, &mut nes, screenshot),
+        _ => check(test, &mut nes),
+    };
+    match (result, screenshot) {
+        (Err(failure), Some(path)) => match save_screenshot(&nes, path) {
+            Ok(()) => Err(failure),
+            Err(err) => Err(format!("{failure} (failed to save the screenshot: {err})")),
+        },
+        (result, _) => result,
+    }
+}
+
+/// Run the ROM of a test and check its result.
+fn check(test: &Test, nes: &mut Nes) -> Result<(), String> {
+    match &test.check {
+        &Check::Status(expected) => match status(nes, test.frames)? {
+            (status, _) if status == expected => Ok(()),
+            (status, description) => Err(format!("status {status:02x}: {}", description.trim())),
+        },
+        &Check::Hash(expected) => {
+            nes.run_to_frame(u64::from(test.frames));
+
+            match hash(nes.ppu().frame_buffer()) {
+                actual if actual == expected => Ok(()),
+                actual => Err(format!("hash {actual:016x} instead of {expected:016x}")),
+            }
+        }
+        Check::Text(expected) => {
+            for _ in 0..test.frames {
+                nes.run_frame();
+                if screen(nes).contains(expected.as_str()) {
+                    return Ok(());
+                }
+            }
+
+            let screen = screen(nes);
+            let lines: Vec<_> = screen.lines().filter(|line| !line.is_empty()).collect();
+            Err(format!("the screen shows `{}`", lines.join(" / ")))
+        }
+        Check::Screenshots(_) => unreachable!("the screenshots are compared by `compare`"),
+    }
+}
+
+/// Play the movie of a test, if any, and compare the pictures after the
+/// frames of its screenshots, in the colors of the default palette.
+///
+/// If a path for the screenshot of the test is given, the picture and the
+/// differences of every differing screenshot are saved next to it, named
+/// after the frame, e.g. `test-60.png` and `test-60-diff.png`, where the
+/// differing pixels are red on the darkened picture.
+fn compare(
+    test: &Test,
+    screenshots: &[Screenshot],
+    roms: &Path,
+    nes: &mut Nes,
+    artifacts: Option<&Path>,
+) -> Result<(), String> {
+    let movie = match &test.movie {
+        Some(path) => {
+            let path = roms.join(path);
+            let file = File::open(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+            let movie = Movie::read_fm2(&mut BufReader::new(file))
+                .map_err(|err| format!("{}: {err}", path.display()))?;
+
+            *nes.input_mut() = movie.ports();
+            nes.set_determinism(movie.determinism);
+            nes.power_cycle();
+            Some(movie)
+        }
+        None => None,
+    };
+
+    let palette = Palette::default();
+    let mut picture = vec![0; WIDTH * HEIGHT];
+    let mut failures = Vec::new();
+    for frame in 0..=test.frames {
+        for screenshot in screenshots
+            .iter()
+            .filter(|screenshot| screenshot.frame == frame)
+        {
+            palette.apply(nes.ppu().frame_buffer(), &mut picture);
+            if let Err(failure) = diff(screenshot, roms, &picture, test.tolerance, artifacts) {
+                failures.push(format!("frame {frame}: {failure}"));
+            }
+        }
+        if frame == test.frames {
+            break;
+        }
+
+        let index = usize::try_from(frame).unwrap_or(usize::MAX);
+        if let Some(movie) = movie.as_ref().filter(|movie| index < movie.inputs().len()) {
+            movie.apply(index, nes);
+        }
+        nes.run_frame();
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+/// Compare a picture with the one of a screenshot, saving the artifacts next
+/// to the given path if they differ, see [`compare`].
+fn diff(
+    screenshot: &Screenshot,
+    roms: &Path,
+    picture: &[u32],
+    tolerance: Tolerance,
+    artifacts: Option<&Path>,
+) -> Result<(), String> {
+    let path = roms.join(&screenshot.png);
+    let expected = File::open(&path)
+        .and_then(|file| png::read(&mut BufReader::new(file)))
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    if (expected.width, expected.height) != (WIDTH, HEIGHT) {
+        return Err(format!(
+            "{} is {}x{} instead of {WIDTH}x{HEIGHT} pixels",
+            screenshot.png.display(),
+            expected.width,
+            expected.height
+        ));
+    }
+
+    let differs = |actual: u32, expected: u32| {
+        let mut channels = actual.to_be_bytes().into_iter().zip(expected.to_be_bytes());
+        channels.any(|(actual, expected)| actual.abs_diff(expected) > tolerance.channel)
+    };
+    let differences: Vec<_> = picture
+        .iter()
+        .zip(&expected.pixels)
+        .map(|(&actual, &expected)| differs(actual, expected))
+        .collect();
+    let count = differences.iter().filter(|&&differs| differs).count();
+    if count <= tolerance.pixels {
+        return Ok(());
+    }
+
+    let failure = format!("{count} pixels differ from {}", screenshot.png.display());
+    let Some(path) = artifacts else {
+        return Err(failure);
+    };
+    let highlighted: Vec<_> = picture
+        .iter()
+        .zip(differences)
+        .map(|(&pixel, differs)| {
+            if differs {
+                0x00ff_0000
+            } else {
+                (pixel >> 2) & 0x003f_3f3f
+            }
+        })
+        .collect();
+    let saved = save_picture(&artifact(path, screenshot.frame, ""), picture)
+        .and_then(|()| save_picture(&artifact(path, screenshot.frame, "-diff"), &highlighted));
+    match saved {
+        Ok(()) => Err(failure),
+        Err(err) => Err(format!("{failure} (failed to save the pictures: {err})")),
+    }
+}
+
+/// Return the path of an artifact of the screenshot of the given frame, next
+/// to the screenshot of the test at the given path.
+fn artifact(path: &Path, frame: u32, suffix: &str) -> PathBuf {
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{name}-{frame}{suffix}.png"))
+}
+
+/// Save a picture as a PNG file.
+fn save_picture(path: &Path, picture: &[u32]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    png::write(&mut file, WIDTH, HEIGHT, picture)?;
+    file.flush()
+}
+
+/// Save the picture of the last frame in the colors of the default palette.
+fn save_screenshot(nes: &Nes, path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    nes.save_screenshot(&Palette::default(), &mut file)?;
+    file.flush()
+}
+
+/// Run a ROM until it reports its result by the protocol of blargg's test
+/// ROMs, returning its status and the text describing it.
+///
+/// `$6001`-`$6003` hold a signature once the result is valid, `$6000` the
+/// status (`$80` while the test is running, `$81` if the reset button should
+/// be pressed, and `0` if it passed), and `$6004` the text.
+///
+/// # Link(s)
+///
+/// - <https://github.com/christopherpow/nes-test-roms/blob/master/README.md>
+///
+/// # Errors
+///
+/// Returns a description of the failure if the ROM reports no result within
+/// the given number of frames.
+pub fn status(nes: &mut Nes, frames: u32) -> Result<(u8, String), String> {
+    let mut reset = None;
+
+    for frame in 0..frames {
+        nes.run_frame();
+
+        if reset == Some(frame) {
+            nes.reset();
+            reset = None;
+        }
+
+        let cartridge = nes.cartridge_mut();
+        let mut read = |addr| cartridge.cpu_read(addr).unwrap_or(0);
+        if [read(0x6001), read(0x6002), read(0x6003)] != SIGNATURE {
+            continue;
+        }
+
+        match read(0x6000) {
+            RUNNING => {}
+            NEEDS_RESET => {
+                reset.get_or_insert(frame + RESET_DELAY);
+            }
+            status => {
+                let text = (0x6004..0x7000)
+                    .map(&mut read)
+                    .take_while(|&byte| byte != 0)
+                    .map(char::from)
+                    .collect();
+
+                return Ok((status, text));
+            }
+        }
+    }
+
+    Err(format!("no result after {frames} frames"))
+}
+
+/// Return the text shown in the first nametable, as printed by test ROMs,
+/// with a line for every row of tiles.
+#[must_use]
+pub fn screen(nes: &Nes) -> String {
+    let mirroring = nes.cartridge().mirroring();
+    let columns = WIDTH / 8;
+    let rows = HEIGHT / 8;
+
+    let mut screen = String::new();
+    for row in 0..rows {
+        let line: String = (0..columns)
+            .map(|column| {
+                let addr = u16::try_from(0x2000 + row * columns + column).unwrap_or(0);
+                match nes.ciram()[usize::from(mirroring.ciram_addr(addr))] {
+                    byte @ 0x20..=0x7e => char::from(byte),
+                    _ => ' ',
+                }
+            })
+            .collect();
+
+        screen.push_str(line.trim_end());
+        screen.push('\n');
+    }
+
+    screen
+}
+
+/// Return the 64-bit FNV-1a hash of a picture, as given by
+/// `Ppu::frame_buffer`, which doesn't depend on the palette.
+#[must_use]
+pub fn hash(pixels: &[u16]) -> u64 {
+    pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
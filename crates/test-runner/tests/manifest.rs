@@ -0,0 +1,95 @@
+//! The parsing of the manifests, in particular of the screenshot checks with
+//! their movies and tolerances.
+
+use std::path::PathBuf;
+
+use chuck_test_runner::manifest::{Check, Manifest, Screenshot, Test, Tolerance};
+
+/// Parse a manifest with a single test.
+fn parse(test: &str) -> Result<Test, toml::de::Error> {
+    let manifest: Manifest = toml::from_str(&format!("[[test]]\n{test}"))?;
+    Ok(manifest.tests.into_iter().next().unwrap())
+}
+
+#[test]
+fn screenshots() {
+    let test = parse(
+        r#"
+        rom = "game/game.nes"
+        movie = "game/game.fm2"
+        screenshots = [
+            { frame = 600, png = "game/level-1.png" },
+            { frame = 60, png = "game/title.png" },
+        ]
+        tolerance = { channel = 8, pixels = 16 }
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        test.check,
+        Check::Screenshots(vec![
+            Screenshot {
+                frame: 600,
+                png: PathBuf::from("game/level-1.png"),
+            },
+            Screenshot {
+                frame: 60,
+                png: PathBuf::from("game/title.png"),
+            },
+        ])
+    );
+    // The test runs until the last screenshot.
+    assert_eq!(test.frames, 600);
+    assert_eq!(test.movie, Some(PathBuf::from("game/game.fm2")));
+    assert_eq!(
+        test.tolerance,
+        Tolerance {
+            channel: 8,
+            pixels: 16,
+        }
+    );
+}
+
+#[test]
+fn screenshots_defaults() {
+    let test = parse(
+        r#"
+        rom = "game.nes"
+        screenshots = [{ frame = 10, png = "game.png" }]
+        tolerance = { pixels = 4 }
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(test.movie, None);
+    assert_eq!(
+        test.tolerance,
+        Tolerance {
+            channel: 0,
+            pixels: 4,
+        }
+    );
+
+    let test = parse("rom = \"game.nes\"\nscreenshots = [{ frame = 10, png = \"game.png\" }]");
+    assert_eq!(test.unwrap().tolerance, Tolerance::default());
+}
+
+#[test]
+fn invalid_screenshots() {
+    for test in [
+        // No screenshots.
+        "rom = \"game.nes\"\nscreenshots = []",
+        // The frames are those of the screenshots.
+        "rom = \"game.nes\"\nframes = 60\nscreenshots = [{ frame = 10, png = \"game.png\" }]",
+        // Only screenshot checks take a movie or a tolerance.
+        "rom = \"game.nes\"\nstatus = 0\nmovie = \"game.fm2\"",
+        "rom = \"game.nes\"\nstatus = 0\ntolerance = { pixels = 1 }",
+        // Unknown keys.
+        "rom = \"game.nes\"\nscreenshots = [{ frame = 10, png = \"game.png\", alpha = 1 }]",
+        "rom = \"game.nes\"\nscreenshots = [{ frame = 10, png = \"game.png\" }]\n\
+         tolerance = { hue = 1 }",
+    ] {
+        assert!(parse(test).is_err(), "{test}");
+    }
+}
@@ -0,0 +1,64 @@
+//! The video output of the NES, which turns the colors of the PPU's frame
+//! buffer into RGB pictures.
+//!
+//! The PPU doesn't produce RGB colors but a composite video signal, so the
+//! colors of its frame buffer are indices into the palette of the NES's hues
+//! and brightness levels (the lower 6 bits), together with the emphasis bits
+//! of `PPUMASK` (the upper 3 bits), which only the TV turns into colors:
+//!
+//! - [`ntsc`] simulates the composite signal of an NTSC console and its
+//!   decoding by a TV, including the artifacts of the signal.
+//! - [`palette`] maps every color to an RGB color on its own, with palettes
+//!   decoded by the simulated TV or loaded from `.pal` files.
+//!
+//! The RGB pictures are stored row by row, with every pixel as `0x00RRGGBB`,
+//! and can be saved as PNG files with [`png`].
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU_palettes>
+//! - <https://www.nesdev.org/wiki/NTSC_video>
+
+pub mod ntsc;
+pub mod palette;
+pub mod png;
+
+/// The width of the PPU's picture, in pixels.
+pub const WIDTH: usize = 256;
+
+/// The height of the PPU's picture, in pixels.
+pub const HEIGHT: usize = 240;
+
+/// The conversion of the channels of colors into bytes, through a gamma curve.
+#[derive(Debug, Clone)]
+struct Quantizer {
+    /// The smallest channel values of the bytes `1`-`255`.
+    thresholds: Box<[f32; 255]>,
+}
+
+impl Quantizer {
+    /// Create a quantizer raising the channels to the power of `gamma`.
+    fn new(gamma: f32) -> Self {
+        let mut thresholds = Box::new([0.0; 255]);
+        for (byte, threshold) in (0..=254u8).zip(thresholds.iter_mut()) {
+            // The channels are rounded to the nearest byte.
+            *threshold = ((f32::from(byte) + 0.5) / 255.0).powf(gamma.recip());
+        }
+
+        Self { thresholds }
+    }
+
+    /// Convert a channel, nominally `0.0`-`1.0`, into a byte.
+    fn byte(&self, value: f32) -> u32 {
+        let byte = self
+            .thresholds
+            .partition_point(|&threshold| threshold <= value);
+        u32::try_from(byte).unwrap_or(0xff)
+    }
+
+    /// Convert the red, green and blue channels of a color into `0x00RRGGBB`.
+    fn rgb(&self, rgb: [f32; 3]) -> u32 {
+        rgb.into_iter()
+            .fold(0, |color, channel| (color << 8) | self.byte(channel))
+    }
+}
@@ -0,0 +1,341 @@
+//! A simulation of the composite video signal of an NTSC console, as decoded
+//! by a TV.
+//!
+//! The PPU generates the signal of a pixel as a square wave between two
+//! voltages, which depend on the brightness of its color, while the phase of
+//! the wave relative to the color subcarrier is its hue. A cycle of the
+//! subcarrier takes 12 samples at twice the master clock, and a pixel takes 8
+//! of them, i.e. two thirds of a cycle. The [`Filter`] generates the samples
+//! of every scanline and decodes them again like a TV: the brightness (luma)
+//! is the average of the signal over a cycle of the subcarrier, and the color
+//! (chroma) is the correlation of the signal with the subcarrier.
+//!
+//! Since a pixel is shorter than a cycle of the subcarrier, the colors of
+//! neighbouring pixels bleed into each other (artifact colors), and the
+//! chroma shows up as a pattern of dots along the edges between colors
+//! (fringing). The subcarrier is one third of a cycle further on every
+//! scanline and on every frame, which lets these artifacts crawl.
+//!
+//! ```
+//! # use chuck_video::{ntsc::{Filter, Settings, WIDTH}, HEIGHT};
+//! // A frame with a white line in the middle of a black screen.
+//! let mut pixels = vec![0x0f; chuck_video::WIDTH * HEIGHT];
+//! pixels[100 * chuck_video::WIDTH..101 * chuck_video::WIDTH].fill(0x30);
+//!
+//! let mut filter = Filter::new(Settings::default());
+//! let mut picture = vec![0; WIDTH * HEIGHT];
+//! filter.filter(&pixels, 0, &mut picture);
+//!
+//! assert_eq!(picture[0], 0x000000);
+//! assert!(picture[100 * WIDTH + WIDTH / 2] & 0xff > 0xe0);
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/NTSC_video>
+
+use std::f32::consts::PI;
+
+use crate::palette::Palette;
+use crate::Quantizer;
+
+/// The width of the filtered picture, in pixels: two for every pixel of the
+/// PPU, since the artifacts are smaller than a pixel.
+pub const WIDTH: usize = 2 * crate::WIDTH;
+
+/// The number of samples of the signal per cycle of the color subcarrier.
+const PHASES: usize = 12;
+
+/// The number of samples of the signal per pixel.
+const SAMPLES: usize = 8;
+
+/// The number of black samples before and after the pixels of a scanline,
+/// which must be a multiple of [`PHASES`].
+const PADDING: usize = PHASES;
+
+/// The voltages of the signal, relative to the sync level, of the 4
+/// brightness levels of the colors, while the wave is low.
+const LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+
+/// The voltages of the signal while the wave is high.
+const HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+
+/// The voltage of black.
+const BLACK: f32 = 0.518;
+
+/// The voltage of white.
+const WHITE: f32 = 1.962;
+
+/// The factor attenuating the signal during the phases of the emphasized
+/// colors.
+const ATTENUATION: f32 = 0.746;
+
+/// The phase of the decoded subcarrier relative to the generated one, in
+/// samples, for the hues of a TV.
+const HUE: f32 = 3.9;
+
+/// The gamma correction from the TV's gamma (2.2) to the gamma of the
+/// generated signal (2.0).
+const GAMMA: f32 = 2.2 / 2.0;
+
+/// The factors of I and Q in the red, green and blue channels of a color, in
+/// addition to Y.
+const RGB: [[f32; 2]; 3] = [
+    [0.946_882, 0.623_557],
+    [-0.274_788, -0.635_691],
+    [-1.108_545, 1.709_007],
+];
+
+/// The settings of the simulated TV.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// The shift of the hues, in degrees.
+    pub hue: f32,
+    /// The saturation of the colors, `0.0` for shades of grey and `1.0` for
+    /// the saturation of the signal.
+    pub saturation: f32,
+    /// The sharpness of the picture, from `-1.0` (blurred) through `0.0` (as
+    /// decoded) to `1.0` (sharpened).
+    pub sharpness: f32,
+    /// The strength of the chroma dots within the luma, from `0.0`, which only
+    /// keeps the artifact colors, to `1.0`, which leaves the full fringes of
+    /// a TV without a comb filter.
+    pub fringing: f32,
+    /// A flag denoting if the artifacts crawl. If not, every frame is decoded
+    /// with the same phase, which keeps the artifacts from flickering.
+    pub dot_crawl: bool,
+}
+
+impl Default for Settings {
+    /// Settings resembling an average TV.
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            sharpness: 0.0,
+            fringing: 0.25,
+            dot_crawl: true,
+        }
+    }
+}
+
+/// The composite video filter.
+///
+/// The filter has a few pre-computed tables and buffers, so it should be
+/// reused for every frame.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// The settings.
+    settings: Settings,
+    /// The normalized signal of every color, including its emphasis bits, at
+    /// every phase of the subcarrier.
+    levels: Box<[[f32; PHASES]]>,
+    /// The decoding subcarrier, as the cosine and sine at every phase, scaled
+    /// by the saturation.
+    carrier: [(f32, f32); PHASES],
+    /// The prefix sums of the signal of a scanline, and of its products with
+    /// the subcarrier.
+    sums: Vec<[f32; 3]>,
+    /// The decoded colors of a scanline, in YIQ.
+    line: Vec<[f32; 3]>,
+    /// The conversion of the decoded colors into bytes.
+    quantizer: Quantizer,
+}
+
+impl Filter {
+    /// Create a filter with the given settings.
+    #[must_use]
+    pub fn new(settings: Settings) -> Self {
+        let mut levels = vec![[0.0; PHASES]; 512].into_boxed_slice();
+        for (pixel, levels) in (0..512).zip(levels.iter_mut()) {
+            for (phase, level) in (0..PHASES).zip(levels.iter_mut()) {
+                *level = (signal(pixel, phase) - BLACK) / (WHITE - BLACK);
+            }
+        }
+
+        let mut filter = Self {
+            settings,
+            levels,
+            carrier: [(0.0, 0.0); PHASES],
+            sums: vec![[0.0; 3]; crate::WIDTH * SAMPLES + 2 * PADDING + 1],
+            line: vec![[0.0; 3]; WIDTH],
+            quantizer: Quantizer::new(GAMMA),
+        };
+
+        filter.set_settings(settings);
+        filter
+    }
+
+    /// Return the settings.
+    #[must_use]
+    pub const fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Change the settings.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+
+        let hue = HUE + settings.hue / 30.0;
+        for (phase, carrier) in (0..12u8).zip(&mut self.carrier) {
+            let angle = PI * (f32::from(phase) + hue) / 6.0;
+            *carrier = (
+                angle.cos() * settings.saturation,
+                angle.sin() * settings.saturation,
+            );
+        }
+    }
+
+    /// Return the palette of the colors decoded from areas of a single color,
+    /// i.e. without any artifacts.
+    #[must_use]
+    pub fn palette(&self) -> Palette {
+        let mut colors = Box::new([0; 512]);
+        for (levels, color) in self.levels.iter().zip(colors.iter_mut()) {
+            let mut yiq = [0.0; 3];
+            for (level, (cos, sin)) in levels.iter().zip(&self.carrier) {
+                yiq[0] += level;
+                yiq[1] += level * cos;
+                yiq[2] += level * sin;
+            }
+
+            *color = self.rgb(yiq.map(|value| value / 12.0));
+        }
+
+        Palette::new(colors)
+    }
+
+    /// Filter a frame of the PPU, storing the RGB picture of [`WIDTH`] x
+    /// [`HEIGHT`](crate::HEIGHT) pixels in `picture`.
+    ///
+    /// The `phase` is the phase of the color subcarrier at the start of the
+    /// frame, i.e. the number of PPU dots since power-up modulo 3, which makes
+    /// the artifacts crawl from frame to frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` isn't a frame buffer of the PPU, or if `picture`
+    /// doesn't have the size of the filtered picture.
+    pub fn filter(&mut self, pixels: &[u16], phase: u8, picture: &mut [u32]) {
+        assert_eq!(pixels.len(), crate::WIDTH * crate::HEIGHT);
+        assert_eq!(picture.len(), WIDTH * crate::HEIGHT);
+
+        let phase = if self.settings.dot_crawl {
+            usize::from(phase)
+        } else {
+            0
+        };
+
+        let rows = pixels.chunks_exact(crate::WIDTH);
+        for (y, (row, picture)) in rows.zip(picture.chunks_exact_mut(WIDTH)).enumerate() {
+            // Pixel `x` is drawn on dot `x + 1` of the scanline, and every dot
+            // takes 8 of the 12 phases of the subcarrier.
+            let start = (phase + y * 341 + 1) * SAMPLES % PHASES;
+            self.encode(row, start);
+            self.decode();
+            self.convert(picture);
+        }
+    }
+
+    /// Generate the signal of a scanline starting at the given phase, as the
+    /// prefix sums of the samples and of their products with the subcarrier.
+    fn encode(&mut self, row: &[u16], start: usize) {
+        // The padding is black, i.e. at level `0.0`, and since it's a
+        // multiple of the cycle, the first pixel still starts at `start`.
+        let padding = [None; PADDING];
+        let pixels = row
+            .iter()
+            .flat_map(|&pixel| [Some(&self.levels[usize::from(pixel & 0x1ff)]); SAMPLES]);
+        let samples = padding.into_iter().chain(pixels).chain(padding);
+
+        let mut sum = [0.0; 3];
+        let phases = (0..PHASES).cycle().skip(start);
+        for ((levels, phase), prefix) in samples.zip(phases).zip(&mut self.sums[1..]) {
+            let sample = levels.map_or(0.0, |levels| levels[phase]);
+            let (cos, sin) = self.carrier[phase];
+            sum[0] += sample;
+            sum[1] += sample * cos;
+            sum[2] += sample * sin;
+            *prefix = sum;
+        }
+    }
+
+    /// Decode the luma and the chroma of every pixel of the scanline.
+    fn decode(&mut self) {
+        let window = |sums: &[[f32; 3]], start: usize, end: usize| {
+            let [y0, i0, q0] = sums[start];
+            let [y1, i1, q1] = sums[end];
+            [y1 - y0, i1 - i0, q1 - q0]
+        };
+
+        let fringing = self.settings.fringing;
+        for (x, yiq) in self.line.iter_mut().enumerate() {
+            // The center of the output pixel, which covers 4 samples.
+            let center = PADDING + x * SAMPLES / 2 + SAMPLES / 4;
+
+            // Averaging over a whole cycle of the subcarrier removes the
+            // chroma from the luma, while averaging over a shorter window
+            // lets the chroma leak into the luma as fringes.
+            let [y, i, q] = window(&self.sums, center - PHASES / 2, center + PHASES / 2);
+            let [short, _, _] = window(&self.sums, center - 2, center + 2);
+            let y = y / 12.0;
+            let short = short / 4.0;
+
+            *yiq = [fringing.mul_add(short - y, y), i / 12.0, q / 12.0];
+        }
+    }
+
+    /// Convert the decoded colors of the scanline into RGB, sharpening the
+    /// luma on the way.
+    fn convert(&self, picture: &mut [u32]) {
+        let sharpness = self.settings.sharpness;
+        let luma = |x: usize| self.line[x.min(WIDTH - 1)][0];
+
+        for (x, (&[y, i, q], rgb)) in self.line.iter().zip(picture).enumerate() {
+            let blurred = (luma(x.saturating_sub(1)) + y + luma(x + 1)) / 3.0;
+            let y = sharpness.mul_add(y - blurred, y);
+
+            *rgb = self.rgb([y, i, q]);
+        }
+    }
+
+    /// Convert a color from YIQ into RGB.
+    fn rgb(&self, [y, i, q]: [f32; 3]) -> u32 {
+        self.quantizer
+            .rgb(RGB.map(|[ri, rq]| ri.mul_add(i, rq.mul_add(q, y))))
+    }
+}
+
+/// Return the voltage of the signal of a color (including its emphasis bits)
+/// at the given phase of the subcarrier.
+fn signal(pixel: usize, phase: usize) -> f32 {
+    let hue = pixel & 0x0f;
+    let emphasis = pixel >> 6;
+
+    // The colors `$xE` and `$xF` are black, at the level of `$1D`.
+    let level = if hue > 13 { 1 } else { (pixel >> 4) & 3 };
+
+    // Hue 0 is a constant high level, and hues 13 to 15 a constant low
+    // level.
+    let low = if hue == 0 { HIGH[level] } else { LOW[level] };
+    let high = if hue > 12 { LOW[level] } else { HIGH[level] };
+
+    // The wave of hue `n` is high for 6 of the 12 phases, starting at phase
+    // `12 - n`.
+    let in_phase = |hue: usize| (hue + phase) % PHASES < PHASES / 2;
+    let signal = if in_phase(hue) { high } else { low };
+
+    // The emphasis bits attenuate the signal during the phases of the hues
+    // opposite of red (hue 12), green (hue 4) and blue (hue 8), except for
+    // the blacks.
+    let attenuated = hue < 14
+        && ((emphasis & 1 != 0 && in_phase(12))
+            || (emphasis & 2 != 0 && in_phase(4))
+            || (emphasis & 4 != 0 && in_phase(8)));
+
+    if attenuated {
+        signal * ATTENUATION
+    } else {
+        signal
+    }
+}
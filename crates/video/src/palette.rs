@@ -0,0 +1,160 @@
+//! RGB palettes, which map every color of the PPU to a single RGB color.
+//!
+//! Unlike the [`ntsc`](crate::ntsc) filter, a palette ignores the artifacts
+//! of the video signal, and colors every pixel on its own. Since the colors
+//! of a TV depend on its settings, palettes are a matter of taste, so they
+//! can be loaded from the `.pal` files of other emulators and tools: 64 RGB
+//! triplets for the colors `$00`-`$3F`, optionally followed by 7 more sets of
+//! 64 colors with the emphasis bits `1`-`7`.
+//!
+//! ```
+//! # use chuck_video::palette::Palette;
+//! let palette = Palette::default();
+//! let file = palette.to_bytes();
+//! assert_eq!(Palette::parse(&file), Ok(palette));
+//!
+//! // The emphasized colors of a file with 64 colors are derived.
+//! let palette = Palette::parse(&file[..3 * 64]).unwrap();
+//! assert_ne!(palette.rgb(0x1c0), palette.rgb(0x1c));
+//! assert!(Palette::parse(&file[..100]).is_err());
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.nesdev.org/wiki/PPU_palettes>
+
+use std::fmt;
+
+use crate::ntsc::{Filter, Settings};
+
+/// The number of colors of a palette without emphasized colors.
+const COLORS: usize = 64;
+
+/// The factor dimming the channels which aren't emphasized, as 8-bit fixed
+/// point number. Emphasizing a color attenuates the video signal by about a
+/// quarter while it's in the phases of the other colors.
+const ATTENUATION: u32 = 191;
+
+/// A palette of the 512 colors of the PPU, i.e. its 64 colors with every
+/// combination of emphasis bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    /// The colors, as `0x00RRGGBB`.
+    colors: Box<[u32; 512]>,
+}
+
+impl Palette {
+    /// Create a palette from its colors as `0x00RRGGBB`.
+    pub(crate) const fn new(colors: Box<[u32; 512]>) -> Self {
+        Self { colors }
+    }
+
+    /// Parse the contents of a `.pal` file.
+    ///
+    /// If the file only has the 64 colors without emphasis, the emphasized
+    /// colors are derived by dimming every channel once for every other
+    /// emphasized channel, which approximates the attenuation of the signal,
+    /// except for the blacks `$xE` and `$xF`, which the emphasis doesn't
+    /// affect.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't consist of 64 or 512 RGB colors.
+    pub fn parse(bytes: &[u8]) -> Result<Self, InvalidPalette> {
+        if bytes.len() != 3 * COLORS && bytes.len() != 3 * 512 {
+            return Err(InvalidPalette { size: bytes.len() });
+        }
+
+        let mut colors = Box::new([0; 512]);
+        for (rgb, color) in bytes.chunks_exact(3).zip(colors.iter_mut()) {
+            *color = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+        }
+
+        if bytes.len() == 3 * COLORS {
+            let (base, emphasized) = colors.split_at_mut(COLORS);
+            for (emphasis, colors) in (1..8).zip(emphasized.chunks_exact_mut(COLORS)) {
+                for (i, (&rgb, color)) in base.iter().zip(colors).enumerate() {
+                    *color = if i & 0x0f > 13 {
+                        rgb
+                    } else {
+                        emphasize(rgb, emphasis)
+                    };
+                }
+            }
+        }
+
+        Ok(Self { colors })
+    }
+
+    /// Return the contents of a `.pal` file with all 512 colors.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.colors
+            .iter()
+            .flat_map(|color| {
+                let [_, r, g, b] = color.to_be_bytes();
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    /// Return the RGB color, as `0x00RRGGBB`, of a color of the PPU's frame
+    /// buffer.
+    #[must_use]
+    pub fn rgb(&self, pixel: u16) -> u32 {
+        self.colors[usize::from(pixel & 0x1ff)]
+    }
+
+    /// Color a frame of the PPU, storing the RGB picture in `picture`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` and `picture` have different sizes.
+    pub fn apply(&self, pixels: &[u16], picture: &mut [u32]) {
+        assert_eq!(pixels.len(), picture.len());
+
+        for (&pixel, rgb) in pixels.iter().zip(picture) {
+            *rgb = self.rgb(pixel);
+        }
+    }
+}
+
+impl Default for Palette {
+    /// Return the palette decoded by the [`ntsc`](crate::ntsc) filter with
+    /// its default settings.
+    fn default() -> Self {
+        Filter::new(Settings::default()).palette()
+    }
+}
+
+/// Dim every channel of a color once for every other channel emphasized by
+/// the given emphasis bits.
+fn emphasize(rgb: u32, emphasis: usize) -> u32 {
+    let [_, r, g, b] = rgb.to_be_bytes();
+
+    let channels = [r, g, b].into_iter().enumerate().map(|(channel, value)| {
+        let others = (emphasis & !(1 << channel)).count_ones();
+        (0..others).fold(u32::from(value), |value, _| value * ATTENUATION / 256)
+    });
+
+    channels.fold(0, |rgb, value| (rgb << 8) | value)
+}
+
+/// An error returned for a `.pal` file with neither 64 nor 512 colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPalette {
+    /// The size of the file, in bytes.
+    pub size: usize,
+}
+
+impl fmt::Display for InvalidPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a palette of {} bytes has neither 64 nor 512 colors",
+            self.size
+        )
+    }
+}
+
+impl std::error::Error for InvalidPalette {}
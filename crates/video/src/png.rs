@@ -0,0 +1,414 @@
+//! The encoding of RGB pictures as PNG files, and of sequences of pictures
+//! as animated PNG (APNG) files, e.g. for screenshots and recordings, and the
+//! decoding of PNG files, e.g. to compare screenshots.
+//!
+//! The pictures are stored as 8-bit RGB, without an alpha channel, since the
+//! pictures of the video output are opaque:
+//!
+//! ```
+//! # use std::io::Cursor;
+//! # use std::time::Duration;
+//! # use chuck_video::png::{self, Animation};
+//! let mut file = Vec::new();
+//! png::write(&mut file, 2, 1, &[0x00ff_0000, 0x0000_00ff]).unwrap();
+//! assert_eq!(file[..8], *b"\x89PNG\r\n\x1a\n");
+//!
+//! let picture = png::read(&mut file.as_slice()).unwrap();
+//! assert_eq!((picture.width, picture.height), (2, 1));
+//! assert_eq!(picture.pixels, [0x00ff_0000, 0x0000_00ff]);
+//!
+//! let delay = Duration::from_millis(20);
+//! let mut animation = Animation::new(Cursor::new(Vec::new()), 2, 1, delay).unwrap();
+//! animation.push(&[0x00ff_0000, 0x0000_00ff]).unwrap();
+//! animation.push(&[0x0000_00ff, 0x00ff_0000]).unwrap();
+//! let file = animation.finish().unwrap().into_inner();
+//!
+//! // The number of frames of the `acTL` chunk, which follows the header.
+//! assert_eq!(file[33 + 4..33 + 8], *b"acTL");
+//! assert_eq!(file[33 + 8..33 + 12], 2u32.to_be_bytes());
+//! ```
+//!
+//! # Link(s)
+//!
+//! - <https://www.w3.org/TR/png-3/>
+//! - <https://wiki.mozilla.org/APNG_Specification>
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// The signature at the start of every PNG file.
+const SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1a\n";
+
+/// The denominator of the delays of the frames of animations, so they're
+/// stored in units of 0.1 ms.
+const DELAY_DENOMINATOR: u16 = 10_000;
+
+/// Write a picture of the given size as a PNG file.
+///
+/// # Errors
+///
+/// Returns any error produced while writing the file.
+///
+/// # Panics
+///
+/// Panics if the picture doesn't have `width * height` pixels.
+pub fn write(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    picture: &[u32],
+) -> io::Result<()> {
+    writer.write_all(&SIGNATURE)?;
+    write_header(writer, width, height)?;
+    let data = compress(width, height, picture, Compression::default())?;
+    write_chunk(writer, *b"IDAT", &data)?;
+    write_chunk(writer, *b"IEND", &[])
+}
+
+/// A picture decoded from a PNG file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Picture {
+    /// The width, in pixels.
+    pub width: usize,
+    /// The height, in pixels.
+    pub height: usize,
+    /// The pixels, row by row, as `0x00RRGGBB`.
+    pub pixels: Vec<u32>,
+}
+
+/// Read a PNG file with 8-bit RGB or RGBA pixels.
+///
+/// These are the files written by [`write()`] and most screenshots of other
+/// programs. The alpha channel is ignored, and only the first frame of an
+/// animation is read.
+///
+/// # Errors
+///
+/// Returns any error produced while reading the file, or an error of kind
+/// [`io::ErrorKind::InvalidData`] if the file is malformed, or is interlaced
+/// or has another bit depth or color type.
+pub fn read(reader: &mut impl Read) -> io::Result<Picture> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut header = None;
+    let mut data = Vec::new();
+    loop {
+        let (kind, chunk) = read_chunk(reader)?;
+        match &kind {
+            b"IHDR" => header = Some(parse_header(&chunk)?),
+            b"IDAT" => data.extend(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let (width, height, channels) = header.ok_or_else(|| invalid("missing PNG header"))?;
+    let stride = width * channels;
+    let mut rows = Vec::new();
+    ZlibDecoder::new(data.as_slice())
+        .take(u64::try_from(height * (1 + stride)).unwrap_or(u64::MAX))
+        .read_to_end(&mut rows)
+        .map_err(|_| invalid("malformed PNG data"))?;
+    if rows.len() != height * (1 + stride) {
+        return Err(invalid("truncated PNG data"));
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut previous = vec![0; stride];
+    for row in rows.chunks_exact_mut(1 + stride) {
+        let (filter, row) = row.split_at_mut(1);
+        unfilter(filter[0], row, &previous, channels)?;
+        pixels.extend(
+            row.chunks_exact(channels)
+                .map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]])),
+        );
+        previous.copy_from_slice(row);
+    }
+
+    Ok(Picture {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// The writer of a sequence of pictures as an animated PNG file, which
+/// plays once.
+///
+/// The first frame is the picture shown by viewers without support for
+/// animations. The number of frames is only known once the animation is
+/// finished, so the writer must be seekable, e.g. a file.
+#[derive(Debug)]
+pub struct Animation<W> {
+    /// The file.
+    writer: W,
+    /// The width of the pictures, in pixels.
+    width: usize,
+    /// The height of the pictures, in pixels.
+    height: usize,
+    /// The delay of every frame, in units of 1 / [`DELAY_DENOMINATOR`]
+    /// seconds.
+    delay: u16,
+    /// The position of the `acTL` chunk in the file.
+    control: u64,
+    /// The number of frames written.
+    frames: u32,
+    /// The sequence number of the next `fcTL` or `fdAT` chunk.
+    sequence: u32,
+}
+
+impl<W: Write + Seek> Animation<W> {
+    /// Start an animation of pictures of the given size, each shown for the
+    /// given delay, e.g. the period of the frames of the console.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    pub fn new(mut writer: W, width: usize, height: usize, delay: Duration) -> io::Result<Self> {
+        writer.write_all(&SIGNATURE)?;
+        write_header(&mut writer, width, height)?;
+        let control = writer.stream_position()?;
+        write_chunk(&mut writer, *b"acTL", &[0; 8])?;
+
+        let units = (delay.as_micros() + 50) / 100;
+        Ok(Self {
+            writer,
+            width,
+            height,
+            delay: u16::try_from(units).unwrap_or(u16::MAX),
+            control,
+            frames: 0,
+            sequence: 0,
+        })
+    }
+
+    /// Append a frame with the given picture.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error produced while writing the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the picture doesn't have the size of the animation.
+    pub fn push(&mut self, picture: &[u32]) -> io::Result<()> {
+        // The frames are compressed quickly, since they're recorded while
+        // the game runs.
+        let data = compress(self.width, self.height, picture, Compression::fast())?;
+
+        let mut control = Vec::with_capacity(26);
+        control.extend(self.next_sequence().to_be_bytes());
+        control.extend(dimension(self.width)?.to_be_bytes());
+        control.extend(dimension(self.height)?.to_be_bytes());
+        // The offset of the frame, which covers the whole picture.
+        control.extend([0; 8]);
+        control.extend(self.delay.to_be_bytes());
+        control.extend(DELAY_DENOMINATOR.to_be_bytes());
+        // Neither disposed nor blended, since every frame replaces the last.
+        control.extend([0, 0]);
+        write_chunk(&mut self.writer, *b"fcTL", &control)?;
+
+        if self.frames == 0 {
+            write_chunk(&mut self.writer, *b"IDAT", &data)?;
+        } else {
+            let mut frame = Vec::with_capacity(4 + data.len());
+            frame.extend(self.next_sequence().to_be_bytes());
+            frame.extend(data);
+            write_chunk(&mut self.writer, *b"fdAT", &frame)?;
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Finish the animation, returning the writer of its file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of the kind [`InvalidInput`](io::ErrorKind::InvalidInput)
+    /// if no frame was pushed, since a PNG file needs a picture, and any
+    /// error produced while writing the file.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.frames == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the animation has no frames",
+            ));
+        }
+
+        write_chunk(&mut self.writer, *b"IEND", &[])?;
+        let end = self.writer.stream_position()?;
+
+        let mut control = [0; 8];
+        control[..4].copy_from_slice(&self.frames.to_be_bytes());
+        self.writer.seek(SeekFrom::Start(self.control))?;
+        write_chunk(&mut self.writer, *b"acTL", &control)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        Ok(self.writer)
+    }
+
+    /// Return the sequence number of the next chunk of the animation.
+    fn next_sequence(&mut self) -> u32 {
+        self.sequence += 1;
+        self.sequence - 1
+    }
+}
+
+/// Write the `IHDR` chunk of an 8-bit RGB picture of the given size.
+fn write_header(writer: &mut impl Write, width: usize, height: usize) -> io::Result<()> {
+    let mut header = Vec::with_capacity(13);
+    header.extend(dimension(width)?.to_be_bytes());
+    header.extend(dimension(height)?.to_be_bytes());
+    // A bit depth of 8, the color type RGB, the only compression and filter
+    // methods, and no interlacing.
+    header.extend([8, 2, 0, 0, 0]);
+    write_chunk(writer, *b"IHDR", &header)
+}
+
+/// Return the width, the height and the number of channels of a picture of
+/// the given `IHDR` chunk, if it's supported.
+fn parse_header(header: &[u8]) -> io::Result<(usize, usize, usize)> {
+    let [w0, w1, w2, w3, h0, h1, h2, h3, depth, color, 0, 0, interlace] = *header else {
+        return Err(invalid("malformed PNG header"));
+    };
+
+    let channels = match (depth, color, interlace) {
+        (8, 2, 0) => 3,
+        (8, 6, 0) => 4,
+        _ => return Err(invalid("unsupported PNG format")),
+    };
+    let size = |bytes| {
+        usize::try_from(u32::from_be_bytes(bytes))
+            .ok()
+            .filter(|&size| size > 0 && size <= 1 << 16)
+            .ok_or_else(|| invalid("unsupported PNG size"))
+    };
+
+    Ok((size([w0, w1, w2, w3])?, size([h0, h1, h2, h3])?, channels))
+}
+
+/// Undo the filter of the given type of a row of a picture with the given
+/// bytes per pixel, given the unfiltered row above it.
+fn unfilter(filter: u8, row: &mut [u8], above: &[u8], channels: usize) -> io::Result<()> {
+    for i in 0..row.len() {
+        let left = if i < channels { 0 } else { row[i - channels] };
+        let upper_left = if i < channels { 0 } else { above[i - channels] };
+        let up = above[i];
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => u8::midpoint(left, up),
+            4 => paeth(left, up, upper_left),
+            _ => return Err(invalid("invalid PNG filter")),
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+
+    Ok(())
+}
+
+/// Return the Paeth predictor of a byte, i.e. the one of the bytes to the
+/// left, above and to the upper left which is closest to `left + up -
+/// upper_left`.
+fn paeth(left: u8, up: u8, upper_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(up) - i16::from(upper_left);
+    let distance = |byte: u8| (estimate - i16::from(byte)).abs();
+
+    if distance(left) <= distance(up) && distance(left) <= distance(upper_left) {
+        left
+    } else if distance(up) <= distance(upper_left) {
+        up
+    } else {
+        upper_left
+    }
+}
+
+/// Read a chunk, returning its type and its data, whose CRC is checked.
+fn read_chunk(reader: &mut impl Read) -> io::Result<([u8; 4], Vec<u8>)> {
+    let mut head = [0; 8];
+    reader.read_exact(&mut head)?;
+    let [s0, s1, s2, s3, k0, k1, k2, k3] = head;
+    let kind = [k0, k1, k2, k3];
+
+    let size = u32::from_be_bytes([s0, s1, s2, s3]);
+    let mut data = Vec::new();
+    reader.take(u64::from(size)).read_to_end(&mut data)?;
+    let mut crc = [0; 4];
+    reader.read_exact(&mut crc)?;
+    if data.len() != usize::try_from(size).unwrap_or(usize::MAX) {
+        return Err(invalid("truncated PNG chunk"));
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&kind);
+    hasher.update(&data);
+    if hasher.finalize() != u32::from_be_bytes(crc) {
+        return Err(invalid("invalid CRC of a PNG chunk"));
+    }
+
+    Ok((kind, data))
+}
+
+/// Return an error of a malformed or unsupported PNG file.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Write a chunk of the given type.
+fn write_chunk(writer: &mut impl Write, kind: [u8; 4], data: &[u8]) -> io::Result<()> {
+    let size = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the chunk is too large"))?;
+
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(&kind);
+    crc.update(data);
+
+    writer.write_all(&size.to_be_bytes())?;
+    writer.write_all(&kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc.finalize().to_be_bytes())
+}
+
+/// Return the compressed image data of a picture, whose rows aren't
+/// filtered.
+fn compress(
+    width: usize,
+    height: usize,
+    picture: &[u32],
+    level: Compression,
+) -> io::Result<Vec<u8>> {
+    assert_eq!(picture.len(), width * height);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    let mut row = Vec::with_capacity(1 + 3 * width);
+    for pixels in picture.chunks_exact(width.max(1)) {
+        row.clear();
+        // The filter type none.
+        row.push(0);
+        for &pixel in pixels {
+            let [_, r, g, b] = pixel.to_be_bytes();
+            row.extend([r, g, b]);
+        }
+        encoder.write_all(&row)?;
+    }
+
+    encoder.finish()
+}
+
+/// Return a width or height as stored in the file.
+fn dimension(size: usize) -> io::Result<u32> {
+    u32::try_from(size)
+        .ok()
+        .filter(|&size| size > 0 && size <= 1 << 31)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid picture size"))
+}